@@ -0,0 +1,61 @@
+//! Measures the cost of lines of output passing through the client during heavy spam: decoding
+//! them from received bytes, then the copies handed from the connection to the scripts, the
+//! screen and the log. Run with `cargo bench --bench line`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use blightmud::Line;
+
+const LINES: usize = 100_000;
+
+/// The stages a line of output is handed to after being received.
+const STAGES: usize = 4;
+
+fn measure(name: &str, mut run: impl FnMut()) {
+    run();
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_secs(2) {
+        run();
+        runs += 1;
+    }
+    let per_line = start.elapsed() / (runs * LINES as u32);
+    println!("{name:<24} {per_line:>10?} per line");
+}
+
+fn received(colored: bool) -> Vec<Vec<u8>> {
+    (0..LINES)
+        .map(|i| {
+            let text = format!("A goblin hits you with its rusty dagger for {i} damage.");
+            if colored {
+                format!("\x1b[31m{text}\x1b[0m").into_bytes()
+            } else {
+                text.into_bytes()
+            }
+        })
+        .collect()
+}
+
+fn decode(input: &[Vec<u8>]) {
+    for bytes in input {
+        black_box(Line::from(black_box(bytes.as_slice())));
+    }
+}
+
+fn pipeline(input: &[Vec<u8>]) {
+    for bytes in input {
+        let line = Line::from(bytes.as_slice());
+        let stages: [Line; STAGES] = std::array::from_fn(|_| line.clone());
+        black_box(stages);
+    }
+}
+
+fn main() {
+    let plain = received(false);
+    let colored = received(true);
+    measure("decode plain", || decode(&plain));
+    measure("decode colored", || decode(&colored));
+    measure("pipeline plain", || pipeline(&plain));
+    measure("pipeline colored", || pipeline(&colored));
+}
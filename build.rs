@@ -1,5 +1,29 @@
+use std::path::PathBuf;
 use std::process::Command;
+
+/// The `fennel` feature bundles the Fennel compiler, read from `resources/lua/vendor/fennel.lua`
+/// or the file `BLIGHTMUD_FENNEL` points at.
+fn bundle_fennel() {
+    let path = match std::env::var_os("BLIGHTMUD_FENNEL") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap())
+            .join("resources/lua/vendor/fennel.lua"),
+    };
+    if !path.is_file() {
+        panic!(
+            "The fennel feature needs the Fennel compiler at {}. Download fennel-<version>.lua \
+             from https://fennel-lang.org/downloads there or set BLIGHTMUD_FENNEL to its path",
+            path.display()
+        );
+    }
+    println!("cargo:rustc-env=BLIGHTMUD_FENNEL_LUA={}", path.display());
+}
+
 fn main() {
+    if std::env::var_os("CARGO_FEATURE_FENNEL").is_some() {
+        bundle_fennel();
+    }
+
     // taken from https://stackoverflow.com/questions/43753491/include-git-commit-hash-as-string-into-rust-program
     let git_hash = if let Ok(output) = Command::new("git")
         .args(["rev-parse", "--short", "HEAD"])
@@ -0,0 +1,114 @@
+local SERVER_KEY = "__activation_server"
+
+local function lower(value)
+    if type(value) == "string" then
+        return value:lower()
+    end
+    return nil
+end
+
+local mod = {}
+local rules = {}
+local state = {
+    server = lower(store.session_read(SERVER_KEY)),
+    character = nil,
+    class = nil,
+}
+local char_registered = false
+
+local function matches(rule, value)
+    if value == nil then
+        return false
+    end
+    if type(rule) == "table" then
+        for _, r in ipairs(rule) do
+            if matches(r, value) then
+                return true
+            end
+        end
+        return false
+    end
+    return lower(rule) == value
+end
+
+local function is_active(group_rules)
+    for key, rule in pairs(group_rules) do
+        if not matches(rule, state[key]) then
+            return false
+        end
+    end
+    return true
+end
+
+function mod.refresh()
+    for group, group_rules in pairs(rules) do
+        group:set_enabled(is_active(group_rules))
+    end
+end
+
+function mod.add(group, group_rules)
+    if group_rules == nil then
+        rules[group] = nil
+        return
+    end
+    for key, _ in pairs(group_rules) do
+        if not (key == "server" or key == "character" or key == "class") then
+            error("Unknown activation rule: '" .. tostring(key) .. "'", 3)
+        end
+    end
+    if (group_rules.character or group_rules.class) and not char_registered then
+        char_registered = true
+        gmcp.on_ready(function ()
+            gmcp.register("Char")
+        end)
+    end
+    rules[group] = group_rules
+    group:set_enabled(is_active(group_rules))
+end
+
+function mod.state()
+    return {
+        server = state.server,
+        character = state.character,
+        class = state.class,
+    }
+end
+
+local function update(key, value)
+    value = lower(value)
+    if state[key] ~= value then
+        state[key] = value
+        mod.refresh()
+    end
+end
+
+local function on_char(data)
+    local ok, obj = pcall(json.decode, data)
+    if ok and type(obj) == "table" then
+        if obj.name then
+            update("character", obj.name)
+        end
+        if obj.class then
+            update("class", obj.class)
+        end
+    end
+end
+
+gmcp.receive("Char.Name", on_char)
+gmcp.receive("Char.Status", on_char)
+gmcp.receive("Char.Base", on_char)
+
+mud.on_connect(function (host)
+    store.session_write(SERVER_KEY, host)
+    update("server", host)
+end)
+
+mud.on_disconnect(function ()
+    store.session_delete(SERVER_KEY)
+    state.server = nil
+    state.character = nil
+    state.class = nil
+    mod.refresh()
+end)
+
+return mod
@@ -12,11 +12,14 @@ mod.Alias = {}
 local Alias = mod.Alias
 Alias.__index = Alias
 
-function Alias.new(re, callback)
+function Alias.new(re, callback, options)
     local ret = setmetatable({}, Alias)
 
-    ret.regex = regex.new(re)
+    ret.regex = regex.new(re, options)
     ret.callback = callback
+    ret.hits = 0
+    ret.plugin = plugin.caller()
+    ret.script = script.caller()
     ret.enabled = true
     ret.id = next_id
     next_id = next_id + 1
@@ -44,24 +47,44 @@ function Alias:is_enabled()
     return self.enabled
 end
 
+-- Packages use the source of the callback, found through debug info unless set here
+function Alias:set_source(source)
+    assert(load("return " .. source), "Alias source isn't a valid function")
+    self.source = source
+end
+
+-- Kept as an upvalue so matching doesn't look the debugger up every time
+local watch = debugger.watch
+
+-- Callbacks of plugins are timed so /plugins info can show what they cost
+local function run_callback(self, matches, line)
+    local message = "Alias callback has been running for +2 seconds. Aborting"
+    if not self.plugin then
+        watch(self.callback, message, matches, line)
+        return
+    end
+    local start = os.clock()
+    local ok, err = pcall(watch, self.callback, message, matches, line)
+    plugin.record(self.plugin, os.clock() - start)
+    if not ok then
+        error(err, 0)
+    end
+end
+
 function Alias:check_line(line)
     if not self.enabled then
         return
     end
+    if self.plugin and plugin.is_suspended(self.plugin) then
+        return
+    end
     local str = line:line()
     local matches = self.regex:match(str)
     if matches then
         line:matched(true)
-        local startTime = os.time()
-        debug.sethook(function ()
-            if os.time() > startTime + 2 then
-                debug.sethook()
-                error("Alias callback has been running for +2 seconds. Aborting", 2)
-            end
-        end, "", 500)
-    self.callback(matches, line)
-    debug.sethook()
-end
+        self.hits = self.hits + 1
+        run_callback(self, matches, line)
+    end
 end
 
 --------------------------------------------------------------------------------
@@ -75,22 +98,23 @@ mod.AliasGroup = {
 local AliasGroup = mod.AliasGroup
 AliasGroup.__index = AliasGroup
 
-function AliasGroup.new(id)
+function AliasGroup.new(id, name)
     local ret = setmetatable({}, AliasGroup)
 
     ret.id = id
+    ret.name = name
     ret.enabled = true
     ret.aliases = {}
 
     return ret
 end
 
-function AliasGroup:add(regex_or_alias, callback)
+function AliasGroup:add(regex_or_alias, callback, options)
     local alias
     if Alias.is_alias(regex_or_alias) then
         alias = regex_or_alias
     else
-        alias = Alias.new(regex_or_alias, callback)
+        alias = Alias.new(regex_or_alias, callback, options)
     end
     self.aliases[alias.id] = alias
     return alias
@@ -128,6 +152,84 @@ function AliasGroup:disable()
     self.enabled = false
 end
 
+function AliasGroup:activate_when(rules)
+    activation.add(self, rules)
+end
+
+local function callback_source(alias)
+    if alias.source then
+        return alias.source
+    elseif not alias.callback then
+        return "nil"
+    end
+    local source = script.function_source(alias.callback)
+    if not source then
+        return nil, string.format(
+            "The callback source of alias %d wasn't found, set it with alias:set_source()",
+            alias.id)
+    end
+    -- Globals are fine, locals of the script that added the alias don't come along
+    local i = 1
+    local name = debug.getupvalue(alias.callback, i)
+    while name do
+        if name ~= "_ENV" then
+            return nil, string.format(
+                "The callback of alias %d uses the local '%s', set its source with alias:set_source()",
+                alias.id, name)
+        end
+        i = i + 1
+        name = debug.getupvalue(alias.callback, i)
+    end
+    return source
+end
+
+function AliasGroup:export(path)
+    local lines = {
+        "-- Blightmud alias package, add it with alias.import() or /load",
+        string.format("local group = alias.add_group(%s)", self.name and string.format("%q", self.name) or ""),
+    }
+    local ids = {}
+    for id, _ in pairs(self.aliases) do
+        ids[#ids + 1] = id
+    end
+    table.sort(ids)
+    for _, id in ipairs(ids) do
+        local alias = self.aliases[id]
+        local source, err = callback_source(alias)
+        if not source then
+            return nil, err
+        end
+        local options = {}
+        for name, _ in pairs(alias.regex:options()) do
+            options[#options + 1] = name .. " = true"
+        end
+        table.sort(options)
+        local line
+        if #options > 0 then
+            line = string.format("group:add(%q, %s, { %s })",
+                alias.regex:regex(), source, table.concat(options, ", "))
+        else
+            line = string.format("group:add(%q, %s)", alias.regex:regex(), source)
+        end
+        if not alias.enabled then
+            line = line .. ":disable()"
+        end
+        lines[#lines + 1] = line
+    end
+    if not self.enabled then
+        lines[#lines + 1] = "group:disable()"
+    end
+    lines[#lines + 1] = "return group"
+
+    local file, err = io.open((path:gsub("^~", os.getenv("HOME") or "~")), "w")
+    if not file then
+        return nil, err
+    end
+    file:write(table.concat(lines, "\n"), "\n")
+    file:close()
+    return true
+end
+
 function AliasGroup:check_line(line)
     if not self.enabled then
         return
@@ -165,8 +267,8 @@ local function get_alias_groups()
     return user_alias_groups
 end
 
-function mod.add(regex, callback)
-    return get_alias_groups()[1]:add(regex, callback)
+function mod.add(regex, callback, options)
+    return get_alias_groups()[1]:add(regex, callback, options)
 end
 
 function mod.get(id)
@@ -179,6 +281,12 @@ end
 
 function mod.get_group(id)
     if not id then id = 1 end
+    if type(id) == "string" then
+        for _, group in pairs(get_alias_groups()) do
+            if group.name == id then return group end
+        end
+        return nil
+    end
     return get_alias_groups()[id]
 end
 
@@ -194,15 +302,36 @@ function mod.clear()
     end
 end
 
-function mod.add_group()
-    local ret = AliasGroup.new(next_group_id)
+function mod.add_group(name)
+    local ret = AliasGroup.new(next_group_id, name)
+    ret.script = script.caller()
     get_alias_groups()[next_group_id] = ret
     next_group_id = next_group_id + 1
 
     return ret
 end
 
+function mod.import(path)
+    local chunk, err = loadfile((path:gsub("^~", os.getenv("HOME") or "~")))
+    if not chunk then
+        return nil, err
+    end
+    local ok, group = pcall(chunk)
+    if not ok then
+        return nil, group
+    end
+    if getmetatable(group) ~= AliasGroup then
+        return nil, path .. " isn't an alias package"
+    end
+    return group
+end
+
 mud.add_input_listener(function(line)
+    -- Input consumed by an earlier core listener (eg. an incremental search) isn't a command,
+    -- and literal input is sent as typed
+    if line:matched() or line:literal() then
+        return line
+    end
     for _, group in pairs(system_alias_groups) do
         group:check_line(line)
     end
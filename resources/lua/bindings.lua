@@ -22,6 +22,7 @@ bind("home", "scroll_top")
 bind("end", "scroll_bottom")
 bind("pageup", "scroll_up")
 bind("pagedown", "scroll_down")
+bind("alt-p", "toggle_pause")
 
 -- ctrl + up/down
 blight.bind("\x1b[1;5a", function () search.find_up() end)
@@ -37,3 +38,9 @@ blight.bind("up", history.previous_command)
 blight.bind("down", history.next_command)
 blight.bind("ctrl-p", history.previous_command)
 blight.bind("ctrl-n", history.next_command)
+
+-- Finder
+blight.bind("ctrl-o", finder.open)
+
+-- Unread activity
+blight.bind("alt-a", activity.next_active)
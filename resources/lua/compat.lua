@@ -0,0 +1,85 @@
+-- Smooths over the differences between Lua 5.4 and LuaJIT so the bundled
+-- scripts (and user scripts) can rely on the same standard library.
+
+table.unpack = table.unpack or unpack
+table.pack = table.pack or function (...)
+    return { n = select("#", ...), ... }
+end
+
+-- LuaJIT ships the `bit` library while Lua 5.4 has bitwise operators. The
+-- operators are a syntax error in LuaJIT so they are compiled at runtime.
+if bit == nil then
+    bit = load([[
+        return {
+            band = function (a, b) return a & b end,
+            bor = function (a, b) return a | b end,
+            bxor = function (a, b) return a ~ b end,
+            bnot = function (a) return ~a end,
+            lshift = function (a, n) return a << n end,
+            rshift = function (a, n) return a >> n end,
+        }
+    ]])()
+end
+
+if utf8 == nil then
+    utf8 = {}
+    utf8.charpattern = "[\0-\x7F\xC2-\xFD][\x80-\xBF]*"
+
+    local function encode(code)
+        if code < 0x80 then
+            return string.char(code)
+        elseif code < 0x800 then
+            return string.char(0xC0 + math.floor(code / 0x40), 0x80 + code % 0x40)
+        elseif code < 0x10000 then
+            return string.char(
+                0xE0 + math.floor(code / 0x1000),
+                0x80 + math.floor(code / 0x40) % 0x40,
+                0x80 + code % 0x40
+            )
+        end
+        return string.char(
+            0xF0 + math.floor(code / 0x40000),
+            0x80 + math.floor(code / 0x1000) % 0x40,
+            0x80 + math.floor(code / 0x40) % 0x40,
+            0x80 + code % 0x40
+        )
+    end
+
+    function utf8.char(...)
+        local chars = {}
+        for i = 1, select("#", ...) do
+            chars[i] = encode(select(i, ...))
+        end
+        return table.concat(chars)
+    end
+
+    local function decode(str, i)
+        local c = str:byte(i)
+        if c < 0x80 then
+            return c, 1
+        end
+        local len = c >= 0xF0 and 4 or c >= 0xE0 and 3 or 2
+        local code = c % (2 ^ (7 - len))
+        for j = 1, len - 1 do
+            local b = str:byte(i + j)
+            if b == nil or b < 0x80 or b > 0xBF then
+                error("invalid UTF-8 code", 3)
+            end
+            code = code * 0x40 + b % 0x40
+        end
+        return code, len
+    end
+
+    function utf8.codes(str)
+        local i = 1
+        return function ()
+            if i > #str then
+                return nil
+            end
+            local pos = i
+            local code, len = decode(str, i)
+            i = i + len
+            return pos, code
+        end
+    end
+end
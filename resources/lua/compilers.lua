@@ -0,0 +1,29 @@
+-- Compilers for languages that compile to Lua. The compilers themselves aren't
+-- bundled with Blightmud, they're required from package.path (eg. installed
+-- with luarocks) the first time a script needs them.
+
+local function require_compiler(module, language)
+    local ok, compiler = pcall(require, module)
+    if not ok then
+        error(string.format(
+            "Loading %s scripts requires the '%s' module on package.path",
+            language,
+            module
+        ), 0)
+    end
+    return compiler
+end
+
+script.add_compiler("fnl", function (source, path)
+    local fennel = require_compiler("fennel", "Fennel")
+    return fennel.compileString(source, { filename = path })
+end)
+
+script.add_compiler("moon", function (source, path)
+    local moonscript = require_compiler("moonscript.base", "MoonScript")
+    local code, err = moonscript.to_lua(source)
+    if not code then
+        error(path .. ": " .. tostring(err), 0)
+    end
+    return code
+end)
@@ -0,0 +1,323 @@
+local mod = {}
+
+local breakpoints = {}
+local break_lines = {}
+local next_id = 1
+
+local stepping = nil
+local step_depth = 0
+
+-- The callback running with the debug hook, and the one paused at the debug
+-- prompt. Each is { co, message, deadline } with the pause point in info,
+-- vars and depth once paused.
+local running = nil
+local paused = nil
+
+local WATCHDOG_TIMEOUT = 2
+local own_source = debug.getinfo(1, "S").source
+local set_hook = core._debug_hook
+
+local function rebuild_index()
+    break_lines = {}
+    for _, bp in pairs(breakpoints) do
+        break_lines[bp.line] = true
+    end
+end
+
+-- Scripts loaded by Blightmud are named after their path while required
+-- modules use the regular "@path" convention.
+local function source_path(source)
+    if source:sub(1, 1) == "@" then
+        source = source:sub(2)
+    elseif source:sub(1, 1) == "=" then
+        return nil
+    end
+    return source
+end
+
+local function matches_file(source, file)
+    local path = source_path(source)
+    return path ~= nil and (path == file or path:sub(-(#file + 1)) == "/" .. file)
+end
+
+local function stack_depth()
+    local depth = 0
+    while debug.getinfo(depth + 3, "l") do
+        depth = depth + 1
+    end
+    return depth
+end
+
+local function repr(value, depth)
+    depth = depth or 0
+    if type(value) == "string" then
+        return string.format("%q", value)
+    elseif type(value) ~= "table" or depth > 1 then
+        return tostring(value)
+    end
+    local parts = {}
+    for k, v in pairs(value) do
+        if #parts >= 8 then
+            parts[#parts + 1] = "..."
+            break
+        end
+        parts[#parts + 1] = tostring(k) .. " = " .. repr(v, depth + 1)
+    end
+    return "{ " .. table.concat(parts, ", ") .. " }"
+end
+
+local function collect_locals(level)
+    local vars = {}
+    local i = 1
+    while true do
+        local name, value = debug.getlocal(level, i)
+        if not name then break end
+        if name:sub(1, 1) ~= "(" then
+            vars[#vars + 1] = { name = name, value = value }
+        end
+        i = i + 1
+    end
+    local func = debug.getinfo(level, "f").func
+    i = 1
+    while true do
+        local name, value = debug.getupvalue(func, i)
+        if not name then break end
+        vars[#vars + 1] = { name = name, value = value, upvalue = true }
+        i = i + 1
+    end
+    return vars
+end
+
+local function source_context(source, line)
+    local lines = {}
+    local path = source_path(source)
+    if not path then
+        return lines
+    end
+    local file = io.open(path:gsub("^~", os.getenv("HOME") or "~"), "r")
+    if not file then
+        return lines
+    end
+    local lnum = 0
+    for text in file:lines() do
+        lnum = lnum + 1
+        if lnum >= line - 2 and lnum <= line + 2 then
+            local marker = lnum == line and ">" or " "
+            lines[#lines + 1] = string.format("%s %4d | %s", marker, lnum, text)
+        elseif lnum > line + 2 then
+            break
+        end
+    end
+    file:close()
+    return lines
+end
+
+local function describe(info, vars)
+    local out = {
+        string.format("Paused at %s:%d", source_path(info.source) or info.short_src, info.currentline),
+    }
+    for _, l in ipairs(source_context(info.source, info.currentline)) do
+        out[#out + 1] = l
+    end
+    out[#out + 1] = "Locals:"
+    for _, var in ipairs(vars) do
+        local prefix = var.upvalue and "  ^" or "   "
+        out[#out + 1] = prefix .. var.name .. " = " .. repr(var.value)
+    end
+    out[#out + 1] = "(c)ontinue, (s)tep, (n)ext, (o)ut, (p)rint <expr>, (bt) backtrace, (a)bort"
+    return out
+end
+
+local function output(text)
+    for line in tostring(text):gmatch("[^\n]+") do
+        blight.output(line)
+    end
+end
+
+local function evaluate(expr, vars)
+    local env = setmetatable({}, { __index = _G })
+    for _, var in ipairs(vars) do
+        if not var.upvalue or rawget(env, var.name) == nil then
+            rawset(env, var.name, var.value)
+        end
+    end
+    local f, err = load("return " .. expr, "=debug", "t", env)
+    if not f then
+        return err
+    end
+    local ok, result = pcall(f)
+    return ok and repr(result) or tostring(result)
+end
+
+local function should_pause(info, line)
+    if stepping == "step" then
+        return true
+    elseif stepping == "next" then
+        return stack_depth() <= step_depth
+    elseif stepping == "out" then
+        return stack_depth() < step_depth
+    end
+    if not break_lines[line] then
+        return false
+    end
+    for _, bp in pairs(breakpoints) do
+        if bp.line == line and matches_file(info.source, bp.file) then
+            return true
+        end
+    end
+    return false
+end
+
+-- Records where the running callback pauses. Called from the hook so the
+-- paused function is at level 3 here (4 in collect_locals).
+local function capture(info)
+    running.info = info
+    running.vars = collect_locals(4)
+    running.depth = stack_depth()
+end
+
+-- Called by the hook of core._debug_hook, which yields the coroutine of the
+-- running callback when this returns true
+local function hook(line_event, line, yieldable)
+    if not running then
+        return false
+    elseif not line_event then
+        if os.time() > running.deadline then
+            error(running.message, 2)
+        end
+        return false
+    end
+    -- Coroutines started by the callback inherit the hook
+    if coroutine.running() ~= running.co or not yieldable then
+        return false
+    end
+    local info = debug.getinfo(2, "Sl")
+    if info.source == own_source or not should_pause(info, line) then
+        return false
+    end
+    capture(info)
+    return true
+end
+
+local prompt
+
+-- Runs the coroutine of a callback until it returns or pauses. A paused
+-- callback leaves Blightmud running and waits for a command at the debug
+-- prompt.
+local function run(state, ...)
+    running = state
+    state.info = nil
+    state.deadline = os.time() + WATCHDOG_TIMEOUT
+    set_hook(state.co, hook)
+    local ok, err = coroutine.resume(state.co, ...)
+    set_hook(state.co, nil)
+    running = nil
+    if not ok then
+        stepping = nil
+        error(err, 0)
+    elseif coroutine.status(state.co) == "dead" then
+        stepping = nil
+    elseif state.info then
+        paused = state
+        output(table.concat(describe(state.info, state.vars), "\n"))
+        prompt()
+    else
+        stepping = nil
+        error("attempt to yield from a callback", 0)
+    end
+end
+
+local function resume(mode)
+    local state = paused
+    paused = nil
+    stepping = mode
+    step_depth = state.depth
+    run(state)
+end
+
+local function command(input)
+    local state = paused
+    if not state then
+        return
+    end
+    local cmd, arg = input:match("^%s*(%S*)%s*(.-)%s*$")
+    if cmd == "c" or cmd == "continue" then
+        resume(nil)
+    elseif cmd == "s" or cmd == "step" then
+        resume("step")
+    elseif cmd == "n" or cmd == "next" then
+        resume("next")
+    elseif cmd == "o" or cmd == "out" then
+        resume("out")
+    elseif cmd == "a" or cmd == "abort" then
+        paused = nil
+        stepping = nil
+        output("Aborted by debugger")
+    else
+        if cmd == "p" or cmd == "print" then
+            output(arg .. " = " .. evaluate(arg, state.vars))
+        elseif cmd == "bt" or cmd == "backtrace" then
+            output(debug.traceback(state.co))
+        else
+            output("Unknown command: " .. input)
+        end
+        prompt()
+    end
+end
+
+prompt = function ()
+    ui.ask("debug>", command)
+end
+
+function mod.breakpoint(file, line)
+    local id = next_id
+    next_id = next_id + 1
+    breakpoints[id] = { file = file, line = line }
+    rebuild_index()
+    return id
+end
+mod["break"] = mod.breakpoint
+
+function mod.remove(id)
+    breakpoints[id] = nil
+    rebuild_index()
+end
+
+function mod.clear()
+    breakpoints = {}
+    rebuild_index()
+end
+
+function mod.list()
+    local list = {}
+    for id, bp in pairs(breakpoints) do
+        list[id] = { file = bp.file, line = bp.line }
+    end
+    return list
+end
+
+function mod.is_paused()
+    return paused ~= nil
+end
+
+function mod.watch(callback, message, ...)
+    if paused or not next(breakpoints) then
+        -- Only the watchdog, breakpoints are skipped while a callback is paused
+        local deadline = os.time() + WATCHDOG_TIMEOUT
+        debug.sethook(function ()
+            if os.time() > deadline then
+                debug.sethook()
+                error(message, 2)
+            end
+        end, "", 500)
+        local ok, err = pcall(callback, ...)
+        debug.sethook()
+        if not ok then
+            error(err, 0)
+        end
+        return
+    end
+    run({ co = coroutine.create(callback), message = message }, ...)
+end
+
+return mod
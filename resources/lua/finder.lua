@@ -0,0 +1,87 @@
+local mod = {}
+
+-- Only the most recent inputs are offered
+local RECENT_INPUTS = 50
+
+-- The literal start of an alias pattern, eg. "/record " for "^/record (start|stop)$"
+local function literal(pattern)
+    local text = pattern:gsub("^%^", "")
+    local stop = text:find("[%(%[%.%*%+%?%$|\\{]")
+    if stop then
+        text = text:sub(1, stop - 1)
+    end
+    return text
+end
+
+-- Runs the command if the alias pattern matches it as is, otherwise leaves it
+-- in the prompt to be completed
+local function alias_entry(kind, alias)
+    local text = literal(alias.regex:regex())
+    if text:match("^%s*$") then
+        return nil
+    end
+    return {
+        label = kind .. ": " .. text,
+        run = function ()
+            if alias.regex:test(text) then
+                mud.input(text)
+            else
+                prompt.set(text)
+            end
+        end,
+    }
+end
+
+local function add_aliases(entries, kind, groups)
+    local seen = {}
+    for _, group in pairs(groups) do
+        for _, a in pairs(group:get_aliases()) do
+            local entry = alias_entry(kind, a)
+            if entry and not seen[entry.label] then
+                seen[entry.label] = true
+                entries[#entries + 1] = entry
+            end
+        end
+    end
+end
+
+local function entries()
+    local ret = {}
+    local commands = history.commands()
+    local seen = {}
+    for i = #commands, math.max(1, #commands - RECENT_INPUTS + 1), -1 do
+        local cmd = commands[i]
+        if not seen[cmd] then
+            seen[cmd] = true
+            ret[#ret + 1] = {
+                label = "input: " .. cmd,
+                run = function () mud.input(cmd) end,
+            }
+        end
+    end
+    add_aliases(ret, "alias", alias.alias_groups)
+    add_aliases(ret, "command", alias.system_alias_groups)
+    for _, server in ipairs(servers.get_all()) do
+        local name = server.name
+        ret[#ret + 1] = {
+            label = "server: " .. name,
+            run = function () mud.input("/connect " .. name) end,
+        }
+    end
+    return ret
+end
+
+function mod.open()
+    local list = entries()
+    local labels = {}
+    for i, entry in ipairs(list) do
+        labels[i] = entry.label
+    end
+    ui.menu(labels, function (_, index)
+        if index then
+            list[index].run()
+        end
+    end, { title = "Find" })
+end
+
+return mod
@@ -4,6 +4,10 @@ local OPT = 201
 local function GMCP()
     local self = {
         receivers = {},
+        subscribers = {},
+        subscriber_counts = {},
+        next_subscriber_id = 1,
+        registered = {},
         ready_listeners = {},
         echo_gmcp = store.session_read("__echo_gmcp") == "true",
         gmcp_ready = store.session_read("__gmcp_ready") == "true",
@@ -29,6 +33,34 @@ local function GMCP()
         return values
     end
 
+    local function subneg_send(msg)
+        protocol._trace("gmcp", "out", msg)
+        core.subneg_send(OPT, string_to_bytes(msg))
+    end
+
+    -- Raises an error pointing at the caller for malformed package names
+    local function check_package(package, fn)
+        if type(package) ~= "string" or not package:match("^[%a_][%w_%-]*[%.%w_%-]*$")
+            or package:find("%.%.") or package:find("%.$") then
+            error(string.format("gmcp.%s: invalid package name '%s'", fn, tostring(package)), 3)
+        end
+    end
+
+    -- Checks if a message belongs to a package, eg. "Char.Items.List" to "Char.Items"
+    local function in_package(mod, package)
+        mod = mod:lower()
+        package = package:lower()
+        return mod == package or mod:sub(1, #package + 1) == package .. "."
+    end
+
+    local function set_supports(modules)
+        local supports = {}
+        for i, mod in ipairs(modules) do
+            supports[i] = mod .. " 1"
+        end
+        subneg_send("Core.Supports.Set " .. json.encode(supports))
+    end
+
     local _on_enable = function (proto)
         if proto == OPT then
             mud.add_tag("GMCP")
@@ -39,7 +71,8 @@ local function GMCP()
                 version=version,
                 client=program,
             }
-            core.subneg_send(201, string_to_bytes("Core.Hello " .. json.encode(hello_obj)))
+            subneg_send("Core.Hello " .. json.encode(hello_obj))
+            protocol._resync("gmcp", set_supports)
             for _,cb in ipairs(self.ready_listeners) do
                 cb()
             end
@@ -68,6 +101,7 @@ local function GMCP()
         if proto == OPT then
             local msg = _utf8_from(data)
             local mod, json_data = parse_gmcp(msg)
+            protocol._trace("gmcp", "in", msg)
             self.recv_cache[mod] = json_data
             store.session_write("__gmcp_recv_cache", json.encode(self.recv_cache))
             if self.echo_gmcp then
@@ -78,6 +112,11 @@ local function GMCP()
                     cb(json_data)
                 end
             end
+            for _,sub in pairs(self.subscribers) do
+                if in_package(mod, sub.package) then
+                    sub.cb(json_data, mod)
+                end
+            end
         end
     end
 
@@ -87,11 +126,75 @@ local function GMCP()
     end
 
     local register = function (mod)
-        core.subneg_send(OPT, string_to_bytes("Core.Supports.Add [\"" .. mod .. " 1\"]"))
+        check_package(mod, "register")
+        self.registered[mod:lower()] = true
+        protocol.remember("gmcp", mod)
+        subneg_send("Core.Supports.Add [\"" .. mod .. " 1\"]")
     end
 
     local unregister = function (mod)
-        core.subneg_send(OPT, string_to_bytes("Core.Supports.Remove [\"" .. mod .. " 1\"]"))
+        check_package(mod, "unregister")
+        self.registered[mod:lower()] = nil
+        if self.subscriber_counts[mod:lower()] == nil then
+            protocol.forget("gmcp", mod)
+            subneg_send("Core.Supports.Remove [\"" .. mod .. " 1\"]")
+        end
+    end
+
+    -- Subscriptions are counted per package regardless of case, the package is
+    -- sent to the server as the first subscriber spelled it.
+    local subscribe = function (package, callback)
+        check_package(package, "subscribe")
+        local key = package:lower()
+        local id = self.next_subscriber_id
+        self.next_subscriber_id = id + 1
+        self.subscribers[id] = { package = package, cb = callback }
+        local count = self.subscriber_counts[key]
+        if count then
+            count.count = count.count + 1
+        else
+            self.subscriber_counts[key] = { package = package, count = 1 }
+            if not self.registered[key] then
+                protocol.remember("gmcp", package)
+                if self.gmcp_ready then
+                    set_supports(protocol.remembered("gmcp"))
+                end
+            end
+        end
+        for mod, data in pairs(self.recv_cache) do
+            if in_package(mod, package) then
+                callback(data, mod)
+            end
+        end
+        return id
+    end
+
+    local unsubscribe = function (id)
+        local sub = self.subscribers[id]
+        if sub == nil then
+            return
+        end
+        self.subscribers[id] = nil
+        local key = sub.package:lower()
+        local count = self.subscriber_counts[key]
+        count.count = count.count - 1
+        if count.count == 0 then
+            self.subscriber_counts[key] = nil
+            if not self.registered[key] then
+                protocol.forget("gmcp", count.package)
+                if self.gmcp_ready then
+                    set_supports(protocol.remembered("gmcp"))
+                end
+            end
+        end
+    end
+
+    local subscriptions = function ()
+        local ret = {}
+        for _, count in pairs(self.subscriber_counts) do
+            ret[count.package] = count.count
+        end
+        return ret
     end
 
     local receive = function (mod, callback)
@@ -105,7 +208,18 @@ local function GMCP()
     end
 
     local send = function (msg)
-        core.subneg_send(OPT, string_to_bytes(msg))
+        if type(msg) ~= "string" then
+            error("gmcp.send: expected a string, got " .. type(msg), 2)
+        end
+        local mod, body = parse_gmcp(msg)
+        check_package(mod, "send")
+        if type(body) == "string" and body:match("%S") then
+            local ok, err = pcall(json.decode, body)
+            if not ok then
+                error(string.format("gmcp.send: invalid JSON for %s: %s", mod, err), 2)
+            end
+        end
+        subneg_send(msg)
     end
 
     local on_ready = function (cb)
@@ -128,6 +242,9 @@ local function GMCP()
         receive = receive,
         register = register,
         unregister = unregister,
+        subscribe = subscribe,
+        unsubscribe = unsubscribe,
+        subscriptions = subscriptions,
         echo = echo,
         _subneg_recv = _subneg_recv,
         _on_enable = _on_enable,
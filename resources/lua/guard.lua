@@ -0,0 +1,73 @@
+local mod = {}
+
+local guards = {}
+local next_id = 1
+-- Confirmed commands on their way to the server, by command
+local passes = {}
+
+function mod.add(pattern, options)
+    options = options or {}
+    local id = next_id
+    next_id = next_id + 1
+    guards[id] = {
+        id = id,
+        regex = regex.new(pattern, options),
+        message = options.message,
+    }
+    return id
+end
+
+function mod.remove(id)
+    guards[id] = nil
+end
+
+function mod.clear()
+    guards = {}
+end
+
+function mod.list()
+    local list = {}
+    for id, guard in pairs(guards) do
+        list[#list + 1] = { id = id, pattern = guard.regex:regex(), message = guard.message }
+    end
+    table.sort(list, function (a, b) return a.id < b.id end)
+    return list
+end
+
+local function matching(command)
+    local ids = {}
+    for id, _ in pairs(guards) do
+        ids[#ids + 1] = id
+    end
+    table.sort(ids)
+    for _, id in ipairs(ids) do
+        if guards[id].regex:test(command) then
+            return guards[id]
+        end
+    end
+end
+
+-- Called for every line about to be sent. Returns false to hold the line back
+-- until the user confirms it.
+function mod._check(command)
+    if passes[command] then
+        passes[command] = passes[command] > 1 and passes[command] - 1 or nil
+        return true
+    end
+    local guard = matching(command)
+    if not guard then
+        return true
+    end
+    local text = guard.message or string.format("Really send '%s'?", command)
+    ui.confirm(text, function (confirmed)
+        if confirmed then
+            passes[command] = (passes[command] or 0) + 1
+            mud.send(command)
+        else
+            blight.output(C_YELLOW .. "[guard] Not sent: " .. command .. C_RESET)
+        end
+    end)
+    return false
+end
+
+return mod
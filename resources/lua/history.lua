@@ -8,6 +8,12 @@ local command_set = {}
 local search_index = nil
 local search_commands = nil
 
+local context = "default"
+local contexts = {}
+
+local excludes = {}
+local next_exclude_id = 1
+
 if settings.get("save_history") then
     commands = json.decode(store.disk_read("__command_history") or "[]")
     for _,c in ipairs(commands) do
@@ -15,6 +21,8 @@ if settings.get("save_history") then
     end
 end
 
+contexts[context] = commands
+
 local function reset()
     index = nil
     orig_cmd = nil
@@ -112,9 +120,64 @@ function mod.next_command()
     end
 end
 
+function mod.set_context(name)
+    name = name or "default"
+    contexts[name] = contexts[name] or {}
+    context = name
+    commands = contexts[name]
+    command_set = {}
+    for _,c in ipairs(commands) do
+        command_set[c] = true
+    end
+    reset()
+end
+
+function mod.get_context()
+    return context
+end
+
+function mod.commands()
+    local ret = {}
+    for i, c in ipairs(commands) do
+        ret[i] = c
+    end
+    return ret
+end
+
+function mod.exclude(pattern)
+    if type(pattern) == "string" then
+        pattern = regex.new(pattern)
+    end
+    local id = next_exclude_id
+    next_exclude_id = next_exclude_id + 1
+    excludes[id] = pattern
+    return id
+end
+
+function mod.include(id)
+    excludes[id] = nil
+end
+
+function mod.excludes()
+    local ret = {}
+    for id, pattern in pairs(excludes) do
+        ret[id] = pattern:regex()
+    end
+    return ret
+end
+
+local function excluded(str)
+    for _, pattern in pairs(excludes) do
+        if pattern:test(str) then
+            return true
+        end
+    end
+    return false
+end
+
 local function write_to_disk()
     if settings.get("save_history") then
-        store.disk_write("__command_history", json.encode(commands))
+        store.disk_write("__command_history", json.encode(contexts["default"]))
     end
 end
 
@@ -135,9 +198,9 @@ end
 
 mud.add_input_listener(function (line)
     reset()
-    if line:source() == "user" then
+    if line:source() == "user" and not line:skip_history() then
         local str = line:line()
-        if str ~= commands[#commands] and #str > 0 then
+        if str ~= commands[#commands] and #str > 0 and not excluded(str) then
             if settings.get("smart_history") then
                 shift_commands(str)
             else
@@ -0,0 +1,147 @@
+local mod = {}
+
+-- Oldest messages are dropped once the inbox holds more than this
+local MAX_MESSAGES = 200
+
+local messages = json.decode(store.disk_read("__inbox") or "[]")
+
+local watches = {}
+local next_watch_id = 1
+
+local function unread_count()
+    local count = 0
+    for _, msg in ipairs(messages) do
+        if not msg.read then
+            count = count + 1
+        end
+    end
+    return count
+end
+
+local function update_indicator()
+    local count = unread_count()
+    if count > 0 then
+        blight.indicator("__inbox", string.format("✉ %d", count))
+        activity.set("inbox", "highlight")
+    else
+        blight.indicator("__inbox")
+        activity.clear("inbox")
+    end
+end
+
+local function write_to_disk()
+    store.disk_write("__inbox", json.encode(messages))
+end
+
+blight.on_quit(write_to_disk)
+mud.on_disconnect(write_to_disk)
+script.on_reset(write_to_disk)
+
+function mod.add(text, source)
+    table.insert(messages, {
+        text = text,
+        source = source or "script",
+        time = os.time(),
+        read = false,
+    })
+    while #messages > MAX_MESSAGES do
+        table.remove(messages, 1)
+    end
+    update_indicator()
+end
+
+function mod.list()
+    local ret = {}
+    for i, msg in ipairs(messages) do
+        ret[i] = { text = msg.text, source = msg.source, time = msg.time, read = msg.read }
+    end
+    return ret
+end
+
+function mod.unread_count()
+    return unread_count()
+end
+
+function mod.mark_read(index)
+    for i, msg in ipairs(messages) do
+        if not index or i == index then
+            msg.read = true
+        end
+    end
+    update_indicator()
+end
+
+function mod.mark_unread(index)
+    if messages[index] then
+        messages[index].read = false
+        update_indicator()
+    end
+end
+
+function mod.clear()
+    messages = {}
+    update_indicator()
+end
+
+function mod.show()
+    if #messages == 0 then
+        print("[**] Inbox is empty")
+        return
+    end
+    for i, msg in ipairs(messages) do
+        local marker = msg.read and " " or cformat("<yellow>*<reset>")
+        print(cformat("[**] %s %3d %s <cyan>%-8s<reset> %s", marker, i,
+            os.date("%H:%M", msg.time), msg.source, msg.text))
+    end
+    mod.mark_read()
+end
+
+function mod.watch(pattern)
+    if type(pattern) == "string" then
+        pattern = regex.new(pattern)
+    end
+    local id = next_watch_id
+    next_watch_id = next_watch_id + 1
+    watches[id] = pattern
+    return id
+end
+
+function mod.unwatch(id)
+    watches[id] = nil
+end
+
+function mod.watches()
+    local ret = {}
+    for id, pattern in pairs(watches) do
+        ret[id] = pattern:regex()
+    end
+    return ret
+end
+
+-- Built-in tell detection, removable with inbox.unwatch()
+mod.watch("^\\w+ (tells|whispers to|pages) you[ ,:]")
+
+mud.add_output_listener(function (line)
+    if line:prompt() then
+        return line
+    end
+    local source = line:tag("important")
+    if not source then
+        for _, pattern in pairs(watches) do
+            if pattern:test(line:line()) then
+                source = "tell"
+                -- Held back tells make the scrollback highlighted too
+                line:set_tag("important", source)
+                break
+            end
+        end
+    end
+    if source then
+        mod.add(line:line(), source)
+    end
+    return line
+end)
+
+update_indicator()
+
+return mod
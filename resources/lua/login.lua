@@ -0,0 +1,200 @@
+local CREDENTIALS_KEY = "__login_credentials"
+
+local mod = {}
+local sequences = {}
+local active = nil
+local connection = nil
+
+local function Info(msg)
+    print("[LOGIN]: " .. msg)
+end
+
+local function read_credentials()
+    return json.decode(store.disk_read(CREDENTIALS_KEY) or "{}")
+end
+
+local function as_list(value)
+    if type(value) == "table" then
+        return value
+    elseif value then
+        return { value }
+    end
+    return {}
+end
+
+local function matches_any(patterns, content)
+    for _, pattern in ipairs(patterns) do
+        if regex.new(pattern):test(content) then
+            return true
+        end
+    end
+    return false
+end
+
+local function expand(str, creds)
+    return (str:gsub("{(%w+)}", function (key)
+        return creds[key] or ""
+    end))
+end
+
+local function stop(msg)
+    if active and active.timer then
+        timer.remove(active.timer)
+    end
+    if msg then
+        Info(msg)
+    end
+    active = nil
+end
+
+local function identify(mssp_name)
+    if not connection then
+        return nil
+    end
+    local ids = {}
+    local ok, stored = pcall(servers.get_all)
+    for _, server in ipairs(ok and stored or {}) do
+        if server.host == connection.host and server.port == connection.port then
+            table.insert(ids, server.name)
+        end
+    end
+    table.sort(ids)
+    table.insert(ids, string.format("%s:%d", connection.host, connection.port))
+    if mssp_name then
+        table.insert(ids, mssp_name)
+    end
+    for _, id in ipairs(ids) do
+        if sequences[id] then
+            return id
+        end
+    end
+    return nil
+end
+
+local function send_step(step)
+    if step.send then
+        local secret = step.secret or step.send:find("{password}", 1, true) ~= nil
+        mud.send(expand(step.send, active.credentials), { gag = secret, skip_log = secret })
+    end
+end
+
+local function advance()
+    active.step = active.step + 1
+    local step = active.sequence.steps[active.step]
+    if not step then
+        if not active.sequence.success then
+            stop("Logged in as " .. (active.credentials.user or active.id))
+        end
+        return
+    end
+    if not step.expect then
+        send_step(step)
+        advance()
+    end
+end
+
+local function start(id)
+    local sequence = sequences[id]
+    local creds = read_credentials()[id] or {}
+    active = {
+        id = id,
+        sequence = sequence,
+        credentials = creds,
+        step = 0,
+        timer = nil,
+    }
+    if sequence.timeout and sequence.timeout > 0 then
+        active.timer = timer.add(sequence.timeout, 1, function ()
+            if active then
+                active.timer = nil
+                stop("Login sequence for '" .. id .. "' timed out")
+            end
+        end)
+    end
+    advance()
+end
+
+local function try_start(mssp_name)
+    if active then
+        return
+    end
+    local id = identify(mssp_name)
+    if id then
+        start(id)
+    end
+end
+
+mud.on_connect(function (host, port, reset)
+    stop()
+    connection = { host = host, port = port }
+    if not reset then
+        try_start(nil)
+    end
+end)
+
+mud.on_disconnect(function ()
+    stop()
+    connection = nil
+end)
+
+mssp.on_receive(function (values)
+    try_start(values.NAME)
+end)
+
+mud.add_output_listener(function (line)
+    if not active then
+        return line
+    end
+    local content = line:line()
+    if matches_any(as_list(active.sequence.failure), content) then
+        stop("Login sequence for '" .. active.id .. "' failed: " .. content)
+        return line
+    end
+    if active.sequence.success and matches_any(as_list(active.sequence.success), content) then
+        stop("Logged in as " .. (active.credentials.user or active.id))
+        return line
+    end
+    local step = active.sequence.steps[active.step]
+    if step and step.expect and regex.new(step.expect):test(content) then
+        send_step(step)
+        advance()
+    end
+    return line
+end)
+
+function mod.add(id, sequence)
+    assert(type(sequence) == "table" and type(sequence.steps) == "table", "A login sequence requires a 'steps' table")
+    sequences[id] = sequence
+end
+
+function mod.remove(id)
+    sequences[id] = nil
+end
+
+function mod.get(id)
+    return sequences[id]
+end
+
+function mod.set_credentials(id, user, password)
+    local creds = read_credentials()
+    creds[id] = { user = user, password = password }
+    store.disk_write(CREDENTIALS_KEY, json.encode(creds))
+end
+
+function mod.clear_credentials(id)
+    local creds = read_credentials()
+    creds[id] = nil
+    store.disk_write(CREDENTIALS_KEY, json.encode(creds))
+end
+
+function mod.is_active()
+    return active ~= nil
+end
+
+function mod.cancel()
+    if active then
+        stop("Login sequence for '" .. active.id .. "' cancelled")
+    end
+end
+
+return mod
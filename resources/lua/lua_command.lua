@@ -154,33 +154,140 @@ prettywrite = function(tbl, space, not_clever)
 end
 
 local function display(...)
-	local arg = {...}
-	arg.n = #arg
-	if arg.n > 1 then
-		for i = 1, arg.n do
-			display(arg[i])
+	for i = 1, select("#", ...) do
+		local value = select(i, ...)
+		local out = prettywrite(value, '  ') or 'nil'
+		blight.output((out:gsub("\n$", "")))
+	end
+end
+
+local function read_source(chunk, code)
+	if chunk == "repl" then
+		local lines = {}
+		for l in (code .. "\n"):gmatch("(.-)\n") do
+			lines[#lines + 1] = l
 		end
-	else
-		blight.output((prettywrite(arg[1], '  ') or 'nil') .. '\n')
+		return lines
+	end
+	local file = io.open(chunk, "r")
+	if file then
+		local lines = {}
+		for l in file:lines() do
+			lines[#lines + 1] = l
+		end
+		file:close()
+		return lines
 	end
 end
 
-alias.add("^/lua$", function()
-        blight.output("[!!] Usage: /lua <code>")
-end)
+local function show_error(err, code)
+	err = tostring(err)
+	blight.output(C_RED .. "[!!] " .. err .. C_RESET)
+	local chunk, lnum = err:match("^(.-):(%d+): ")
+	if not chunk then
+		return
+	end
+	lnum = tonumber(lnum)
+	local lines = read_source(chunk, code)
+	if not lines or not lines[lnum] then
+		return
+	end
+	for i = math.max(lnum - 2, 1), math.min(lnum + 2, #lines) do
+		local marker = i == lnum and C_RED .. ">" or " "
+		blight.output(string.format("%s %4d | %s%s", marker, i, lines[i], C_RESET))
+	end
+end
 
-alias.add("^/lua (.*)$", function(matches)
-	local f, _ = load("return "..matches[2])
+local function compile(code)
+	local f, err = load("return " .. code, "=repl")
+	if not f then
+		f, err = load(code, "=repl")
+	end
+	return f, err
+end
+
+local function evaluate(code)
+	local f, err = compile(code)
 	if not f then
-		f, _ = assert(load(matches[2]))
+		show_error(err, code)
+		return
+	end
+	local results = table.pack(pcall(f))
+	if not results[1] then
+		show_error(results[2], code)
+		return
+	end
+	if results.n > 1 then
+		_ = results[2]
+		display(table.unpack(results, 2, results.n))
+	end
+end
+
+local repl_active = false
+local repl_buffer = nil
+local suspended_groups = {}
+
+local function repl_input(code)
+	if repl_buffer then
+		code = repl_buffer .. "\n" .. code
 	end
+	local f, err = compile(code)
+	-- LuaJIT quotes the token, Lua 5.4 doesn't
+	if not f and err:match("<eof>'?$") then
+		repl_buffer = code
+		blight.output(C_BLUE .. ".." .. C_RESET)
+		return
+	end
+	repl_buffer = nil
+	evaluate(code)
+end
 
-	local r = function(...)
-		if not table.is_empty({...}) then
-			display(...)
+local repl_alias = alias.add("^([^/].*)$", function(matches)
+	repl_input(matches[2])
+end)
+repl_alias:disable()
+
+local function enter_repl()
+	repl_active = true
+	repl_buffer = nil
+	suspended_groups = {}
+	for _, group in pairs(alias.alias_groups) do
+		if group:is_enabled() then
+			group:disable()
+			suspended_groups[#suspended_groups + 1] = group
 		end
 	end
-	r(f())
+	repl_alias:enable()
+	history.set_context("lua")
+	blight.output(C_BLUE .. "[**] Entered Lua REPL. Type /repl to leave." .. C_RESET)
+end
+
+local function leave_repl()
+	repl_active = false
+	repl_buffer = nil
+	for _, group in ipairs(suspended_groups) do
+		group:enable()
+	end
+	suspended_groups = {}
+	repl_alias:disable()
+	history.set_context()
+	blight.output(C_BLUE .. "[**] Left Lua REPL." .. C_RESET)
+end
+
+alias.add("^/repl$", function()
+	if repl_active then
+		leave_repl()
+	else
+		enter_repl()
+	end
+end)
+
+alias.add("^/lua$", function()
+	blight.output("[!!] Usage: /lua <code>")
+end)
+
+alias.add("^/lua (.*)$", function(matches)
+	evaluate(matches[2])
 end)
 
 function table.is_empty(t)
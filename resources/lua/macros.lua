@@ -56,10 +56,19 @@ local function number_label (number, label)
 	return label .. color .. tostring(number) .. C_RESET
 end
 
+local function flags_label (re)
+	local flags = re:flags()
+	if flags == "" then
+		return ""
+	end
+	return cformat("<cyan>flags: %s<reset>", flags)
+end
+
 alias.add("^/aliases$", function ()
 	for id,alias in pairs(alias.get_group():get_aliases()) do
 		local enabled = state_label(alias.enabled, "enabled")
-		info(cformat("%4s : <yellow>%-20s<reset> %s", id, alias.regex:regex(), enabled))
+		local flags = flags_label(alias.regex)
+		info(cformat("%4s : <yellow>%-20s<reset> %s %s", id, alias.regex:regex(), enabled, flags))
 	end
 end)
 
@@ -70,7 +79,8 @@ alias.add("^/triggers$", function ()
 		local raw = state_label(trigger.raw, "raw")
 		local prompt = state_label(trigger.prompt, "prompt")
 		local count = number_label(trigger.count, "count: ")
-		info(cformat("%4s : <yellow>%-20s<reset> %s %s %s %s %s", id, trigger.regex:regex(), enabled, gag, raw, prompt, count))
+		local flags = flags_label(trigger.regex)
+		info(cformat("%4s : <yellow>%-20s<reset> %s %s %s %s %s %s", id, trigger.regex:regex(), enabled, gag, raw, prompt, count, flags))
 	end
 end)
 
@@ -87,6 +97,91 @@ alias.add("^/tts_keypresses (on|off)$", function (matches)
 	tts.echo_keypresses(matches[2] == "on")
 end)
 
+alias.add("^/tts_backend(?: (\\S+)(?: (.+))?)?$", function (matches)
+	local name = matches[2]
+	if not name or name == "" then
+		info(cformat("TTS backend: <yellow>%s<reset>", tts.backend()))
+		info("Available: " .. table.concat(tts.backends(), ", "))
+		return
+	end
+	local args = {}
+	for arg in (matches[3] or ""):gmatch("%S+") do
+		args[#args + 1] = arg
+	end
+	local opts = {}
+	if name == "piper" then
+		opts.model = args[1]
+	elseif name == "command" then
+		opts.program = table.remove(args, 1)
+		opts.args = args
+	end
+	local ok, err = pcall(tts.set_backend, name, opts)
+	if ok then
+		info(cformat("TTS backend set to <yellow>%s<reset>", name))
+	else
+		error(tostring(err))
+	end
+end)
+
+alias.add("^/tts rules(?: (\\S+)(?: (.+))?)?$", function (matches)
+	local cmd = matches[2]
+	if not cmd or cmd == "" then
+		local rules = tts.rules()
+		if #rules == 0 then
+			info("No TTS rules, everything is spoken")
+		end
+		for i, rule in ipairs(rules) do
+			local criteria = {}
+			for _, key in ipairs({ "kind", "direction", "tag", "source", "matched", "pattern" }) do
+				if rule[key] ~= nil then
+					criteria[#criteria + 1] = key .. "=" .. tostring(rule[key])
+				end
+			end
+			local action = rule.speak and "<green>speak<reset>" or "<red>mute<reset>"
+			info(cformat("%2d: %s (priority %d) %s", i, action, rule.priority, table.concat(criteria, " ")))
+		end
+	elseif cmd == "add" then
+		local action, rest = (matches[3] or ""):match("^(%S+)%s*(.*)$")
+		if action ~= "speak" and action ~= "mute" then
+			error("Usage: /tts rules add <speak|mute> [key=value ...] [pattern=<regex>]")
+			return
+		end
+		local rule = { speak = action == "speak" }
+		local pattern_start = rest:find("pattern=", 1, true)
+		if pattern_start then
+			rule.pattern = rest:sub(pattern_start + #"pattern=")
+			rest = rest:sub(1, pattern_start - 1)
+		end
+		for key, value in rest:gmatch("(%w+)=(%S+)") do
+			if key == "priority" then
+				rule.priority = tonumber(value)
+			elseif key == "matched" then
+				rule.matched = value == "true"
+			else
+				rule[key] = value
+			end
+		end
+		local ok, result = pcall(tts.add_rule, rule)
+		if ok then
+			info(cformat("Added TTS rule <yellow>%d<reset>", result))
+		else
+			error(tostring(result))
+		end
+	elseif cmd == "remove" then
+		local index = tonumber(matches[3])
+		if index and tts.remove_rule(index) then
+			info(cformat("Removed TTS rule <yellow>%d<reset>", index))
+		else
+			error("No such TTS rule: " .. tostring(matches[3]))
+		end
+	elseif cmd == "clear" then
+		tts.clear_rules()
+		info("Removed all TTS rules")
+	else
+		error("Usage: /tts rules [add|remove|clear]")
+	end
+end)
+
 -- Settings
 alias.add("^/settings$", function ()
     local list = settings.list()
@@ -167,7 +262,7 @@ alias.add("^/connect.*$", function (m)
         local result, server = pcall(servers.get, args[2])
         if result then
             info(cformat("Connecting to saved server: <yellow>%s<reset>", args[2]))
-            mud.connect(server.host, server.port, server.tls, server.verify_cert)
+            mud.connect(server.host, server.port, { tls = server.tls, verify = server.verify_cert })
         else
             error(server)
         end
@@ -176,13 +271,13 @@ alias.add("^/connect.*$", function (m)
     elseif #args == 4 then
         local tls = is_truth_string("tls", args[4], print_connect_usage)
         if tls ~= nil then
-            mud.connect(args[2], args[3], tls, true)
+            mud.connect(args[2], args[3], { tls = tls })
         end
     elseif #args >= 5 then
         local tls = is_truth_string("tls", args[4], print_connect_usage)
         local verify = is_truth_string("verify", args[5], print_connect_usage)
         if tls ~= nil and verify ~= nil then
-            mud.connect(args[2], args[3], tls, verify)
+            mud.connect(args[2], args[3], { tls = tls, verify = verify })
         end
     else
         print_connect_usage()
@@ -207,6 +302,119 @@ end)
 alias.add("^/stop_log$", function ()
     log.stop()
 end)
+alias.add("^/loglevel(?: (\\S+))?(?: (\\S+))?$", function (m)
+    local module, level = m[2], m[3]
+    if module == "" then
+        local levels = log.levels()
+        info(cformat("Log level: <yellow>%s<reset>", levels.default))
+        for name, module_level in pairs(levels.modules) do
+            info(cformat("  %-30s <yellow>%s<reset>", name, module_level))
+        end
+        return
+    end
+    if level == "" then
+        module, level = nil, module
+    end
+    local ok, err = pcall(log.set_level, level ~= "reset" and level or nil, module)
+    if not ok then
+        error(tostring(err))
+    elseif level == "reset" then
+        info(cformat("Log level of <yellow>%s<reset> reset", module or "blightmud"))
+    else
+        info(cformat("Log level of <yellow>%s<reset> set to <yellow>%s<reset>", module or "blightmud", level))
+    end
+end)
+alias.add("^/logs(?: (\\d+))?$", function (m)
+    local entries = log.recent(tonumber(m[2]))
+    if #entries == 0 then
+        info("The internal log is empty")
+    end
+    for _, entry in ipairs(entries) do
+        local color = (entry.level == "error" and "<red>") or (entry.level == "warn" and "<yellow>") or ""
+        info(cformat("[%s] %s%-5s<reset> %s: %s", entry.time, color, entry.level, entry.target, entry.message))
+    end
+end)
+alias.add("^/sent(?: (\\d+))?$", function (m)
+    local commands = sent.recent(tonumber(m[2]))
+    if #commands == 0 then
+        info("Nothing has been sent yet")
+    end
+    for _, command in ipairs(commands) do
+        info(cformat("%5d [%s] <yellow>%-6s<reset> %s", command.index,
+            os.date("%H:%M:%S", command.time), command.origin, command.command))
+    end
+end)
+alias.add("^/resend (\\d+)$", function (m)
+    if not sent.resend(tonumber(m[2])) then
+        error("No sent command with index " .. m[2])
+    end
+end)
+alias.add("^/guard(?: (add|remove) (.+))?$", function (m)
+    if m[2] == "add" then
+        local ok, result = pcall(guard.add, m[3])
+        if ok then
+            info(cformat("Guard %d added: <yellow>%s<reset>", result, m[3]))
+        else
+            error(tostring(result))
+        end
+    elseif m[2] == "remove" then
+        guard.remove(tonumber(m[3]))
+        info("Guard removed: " .. m[3])
+    else
+        local guards = guard.list()
+        if #guards == 0 then
+            info("No guards, add one with /guard add <regex>")
+        end
+        for _, g in ipairs(guards) do
+            info(cformat("%4d : <yellow>%s<reset>", g.id, g.pattern))
+        end
+    end
+end)
+alias.add("^/record (start|stop)(?: (\\S+))?$", function (m)
+    if m[2] == "start" then
+        log.start_recording(m[3] ~= "" and m[3] or nil)
+    else
+        log.stop_recording()
+    end
+end)
+alias.add("^/export(?: (\\S+) (.+))?$", function (m)
+    local format, range = m[2], m[3]
+    if format ~= "html" and format ~= "ansi" then
+        info("USAGE: /export <html|ansi> <count|from-to|regex>")
+        return
+    end
+    local from, to = range:match("^(%d+)-(%d+)$")
+    if from then
+        screen.export({ from = tonumber(from), to = tonumber(to) }, format)
+    elseif range:match("^%d+$") then
+        screen.export(tonumber(range), format)
+    else
+        screen.export(range, format)
+    end
+end)
+alias.add("^/scrollback(?: (\\S+) (\\S+))?$", function (m)
+    local option, value = m[2], m[3]
+    if option == "lines" or option == "memory" or option == "auto_pause" then
+        if not tonumber(value) then
+            info("USAGE: /scrollback [lines <count>|memory <mb>|spill on|off|auto_pause <lines>]")
+            return
+        end
+        screen.set_scrollback({ [option] = tonumber(value) })
+    elseif option == "spill" and (value == "on" or value == "off") then
+        screen.set_scrollback({ spill = value == "on" })
+    elseif option and option ~= "" then
+        info("USAGE: /scrollback [lines <count>|memory <mb>|spill on|off|auto_pause <lines>]")
+        return
+    end
+    local settings = screen.scrollback()
+    info(cformat("In memory: <yellow>%d<reset> lines, <yellow>%d MB<reset>", settings.lines, settings.memory))
+    info(cformat("Older lines on disk: %s", settings.spill and "<green>on<reset>" or "<red>off<reset>"))
+    if settings.auto_pause > 0 then
+        info(cformat("Output held when scrolled back more than: <yellow>%d<reset> lines", settings.auto_pause))
+    else
+        info(cformat("Output held when scrolled back: <red>off<reset>"))
+    end
+end)
 alias.add("^/load.*$", function (m)
     local args = get_args(m[1])
     if args[1] == "/load" then
@@ -219,6 +427,45 @@ alias.add("^/load.*$", function (m)
         mud.send(m[1], { gag=true, skip_log=true })
     end
 end)
+alias.add("^/scripts(?: (\\S+)(?: (.*))?)?$", function (m)
+    local action, args = m[2], get_args(m[3] or "")
+    if action == "order" and #args == 0 then
+        local order, err = script.order()
+        if err then
+            error(err)
+        end
+        if #order == 0 then
+            info("No scripts in " .. blight.config_dir())
+        end
+        for i, name in ipairs(order) do
+            local requires = script.requires(name)
+            if #requires > 0 then
+                info(string.format("%d. %s (requires %s)", i, name, table.concat(requires, ", ")))
+            else
+                info(string.format("%d. %s", i, name))
+            end
+        end
+    elseif action == "order" and args[1] == "clear" then
+        script.set_order({})
+        info("Scripts load by name")
+    elseif action == "order" then
+        script.set_order(args)
+        info("Scripts loading first: " .. table.concat(args, ", "))
+    elseif action == "requires" and #args > 0 then
+        local name = table.remove(args, 1)
+        local ok, err = pcall(script.set_requires, name, args)
+        if not ok then
+            error(tostring(err))
+        elseif #args > 0 then
+            info(string.format("%s requires %s", name, table.concat(args, ", ")))
+        else
+            info(name .. " requires no scripts")
+        end
+    else
+        info("USAGE: /scripts order [<script> ...|clear]",
+            "       /scripts requires <script> [<script> ...]")
+    end
+end)
 
 -- Server handling
 alias.add("^(?:/list_servers|/ls)$", function ()
@@ -237,6 +484,28 @@ alias.add("^(?:/list_servers|/ls)$", function ()
             verify_str = cformat("Verify:  <green>on<reset>")
         end
         info(cformat("<yellow>%-12s<reset> Host: %-25s Port: <blue>%4s<reset> %s %s", s.name, s.host, s.port, tls_str, verify_str))
+        local details = {}
+        if s.autoconnect then
+            table.insert(details, "Autoconnect")
+        end
+        if s.input_mode ~= "standard" then
+            table.insert(details, "Input: " .. s.input_mode)
+        end
+        if s.command_prefix then
+            table.insert(details, "Command prefix: " .. s.command_prefix)
+        end
+        if s.last_connected then
+            table.insert(details, "Last connected: " .. os.date("%Y-%m-%d %H:%M", s.last_connected))
+        end
+        if s.codebase then
+            table.insert(details, "Codebase: " .. s.codebase)
+        end
+        if s.players then
+            table.insert(details, "Players: " .. s.players)
+        end
+        if #details > 0 then
+            info(cformat("%-12s <bblack>%s<reset>", "", table.concat(details, ", ")))
+        end
     end
 end)
 
@@ -292,14 +561,802 @@ alias.add("^/remove_server.*$", function (m)
         info("USAGE: /remove_server <name: String>")
     end
 end)
-alias.add("^(?:/quit|/q)$", blight.quit)
-alias.add("^/help.*$", function (m)
+local function print_edit_server_usage()
+    info("USAGE: /edit_server <name: String> <field: String> <value: String>")
+    info("FIELDS: host, port, tls, verify, autoconnect, input (standard or mush), prefix")
+    info("EXAMPLE: /edit_server example port 4001")
+    info("EXAMPLE: /edit_server example tls on")
+    info("EXAMPLE: /edit_server example autoconnect on")
+    info("EXAMPLE: /edit_server example input mush")
+    info("EXAMPLE: /edit_server example prefix .")
+end
+
+alias.add("^/edit_server.*$", function (m)
     local args = get_args(m[1])
-    if #args > 1 then
+    if #args == 4 then
         local name = args[2]
-        blight.show_help(name, true)
+        local field = args[3]
+        local value = args[4]
+        local update = {}
+        if field == "host" then
+            update.host = value
+        elseif field == "port" then
+            update.port = tonumber(value)
+            if not update.port then
+                print_edit_server_usage()
+                return
+            end
+        elseif field == "tls" or field == "verify" or field == "autoconnect" then
+            local key = field == "verify" and "verify_cert" or field
+            update[key] = is_truth_string(field, value, print_edit_server_usage)
+            if update[key] == nil then
+                return
+            end
+        elseif field == "input" then
+            update.input_mode = value
+        elseif field == "prefix" then
+            update.command_prefix = value
+        else
+            print_edit_server_usage()
+            return
+        end
+        local result, err = pcall(servers.update, name, update)
+        if result then
+            info(cformat("Server updated: <yellow>%s<reset>", name))
+        else
+            error(err)
+        end
+    else
+        print_edit_server_usage()
+    end
+end)
+alias.add("^/discover_servers.*$", function (m)
+    local args = get_args(m[1])
+    if #args == 2 then
+        info("Fetching server list from: " .. args[2])
+        servers.discover(args[2])
+    else
+        info("USAGE: /discover_servers <url: String>")
+    end
+end)
+local connected_to = nil
+mud.on_connect(function (host, port)
+    connected_to = { host = host, port = port }
+end)
+mud.on_disconnect(function ()
+    connected_to = nil
+end)
+alias.add("^/server info(?: (\\S+))?$", function (m)
+    local host, port = nil, nil
+    if m[2] and m[2] ~= "" then
+        local ok, server = pcall(servers.get, m[2])
+        if not ok then
+            error(server)
+            return
+        end
+        host, port = server.host, server.port
+    elseif connected_to then
+        host, port = connected_to.host, connected_to.port
+    else
+        info("USAGE: /server info [<name>], without a name while connected")
+        return
+    end
+    local details = servers.info(host, port)
+    if not details then
+        info(string.format("Nothing recorded for %s:%d, is it a saved server?", host, port))
+        return
+    end
+    info(cformat("<yellow>%s<reset> %s:%d", details.server, host, port))
+    if details.last_connected then
+        info("Last connected: " .. os.date("%Y-%m-%d %H:%M", details.last_connected))
+    end
+    if details.summary then
+        info("MSSP: " .. details.summary)
+    end
+    if details.capabilities then
+        info(details.capabilities.summary)
+        info("Checked: " .. os.date("%Y-%m-%d %H:%M", details.capabilities.time))
+    else
+        info("Capabilities are recorded a few seconds after connecting")
+    end
+end)
+-- The server browser, a menu of the saved servers with forms for managing them
+local browse_servers
+
+local function server_label(s)
+    local details = { string.format("%s:%d", s.host, s.port) }
+    if s.tls then
+        table.insert(details, "tls")
+    end
+    if s.codebase then
+        table.insert(details, s.codebase)
+    end
+    if s.players then
+        table.insert(details, s.players .. " players")
+    end
+    if s.last_connected then
+        table.insert(details, "last " .. os.date("%Y-%m-%d %H:%M", s.last_connected))
+    end
+    return string.format("%-16s %s", s.name, table.concat(details, ", "))
+end
+
+local function update_server(name, update)
+    local ok, err = pcall(servers.update, name, update)
+    if ok then
+        info(cformat("Server updated: <yellow>%s<reset>", name))
+    else
+        error(err)
+    end
+end
+
+local function server_actions(name)
+    local ok, s = pcall(servers.get, name)
+    if not ok then
+        error(s)
+        return
+    end
+    local actions = {
+        { "Connect", function () mud.input("/connect " .. name) end },
+        { "Info", function () mud.input("/server info " .. name) end },
+        { "Edit host (" .. s.host .. ")", function ()
+            ui.ask("Host:", function (host)
+                if host ~= "" then
+                    update_server(name, { host = host })
+                end
+                server_actions(name)
+            end)
+        end },
+        { "Edit port (" .. s.port .. ")", function ()
+            ui.ask("Port:", function (port)
+                if tonumber(port) then
+                    update_server(name, { port = tonumber(port) })
+                elseif port ~= "" then
+                    error("Invalid port: " .. port)
+                end
+                server_actions(name)
+            end)
+        end },
+        { s.tls and "Disable TLS" or "Enable TLS", function ()
+            update_server(name, { tls = not s.tls })
+            server_actions(name)
+        end },
+        { s.verify_cert and "Don't verify certificate" or "Verify certificate", function ()
+            update_server(name, { verify_cert = not s.verify_cert })
+            server_actions(name)
+        end },
+        { "Remove", function ()
+            ui.confirm("Remove " .. name .. "?", function (yes)
+                if yes then
+                    local removed, err = pcall(servers.remove, name)
+                    if removed then
+                        info(cformat("Server removed: <yellow>%s<reset>", name))
+                    else
+                        error(err)
+                    end
+                end
+                browse_servers()
+            end)
+        end },
+        { "Back", function () browse_servers() end },
+    }
+    local labels = {}
+    for i, action in ipairs(actions) do
+        labels[i] = action[1]
+    end
+    ui.menu(labels, function (_, index)
+        if index then
+            actions[index][2]()
+        end
+    end, { title = server_label(s) })
+end
+
+local function add_server_form()
+    ui.ask("Name:", function (name)
+        if name == "" then
+            return browse_servers()
+        elseif name:find("%s") then
+            error("Server names can't contain spaces")
+            return browse_servers()
+        end
+        ui.ask("Host:", function (host)
+            ui.ask("Port:", function (port)
+                if not tonumber(port) then
+                    error("Invalid port: " .. port)
+                    return browse_servers()
+                end
+                ui.confirm("Use TLS?", function (tls)
+                    local ok, err = pcall(servers.add, name, host, tonumber(port), tls, tls)
+                    if ok then
+                        info(cformat("Server added: <yellow>%s<reset>", name))
+                    else
+                        error(err)
+                    end
+                    browse_servers()
+                end)
+            end)
+        end)
+    end)
+end
+
+browse_servers = function ()
+    local list = servers.get_all()
+    table.sort(list, function (a, b) return a.name < b.name end)
+    local labels = {}
+    for i, s in ipairs(list) do
+        labels[i] = server_label(s)
+    end
+    table.insert(labels, "Add server...")
+    table.insert(labels, "Discover servers...")
+    ui.menu(labels, function (_, index)
+        if not index then
+            return
+        elseif list[index] then
+            server_actions(list[index].name)
+        elseif index == #list + 1 then
+            add_server_form()
+        else
+            ui.ask("Server list url:", function (url)
+                if url ~= "" then
+                    mud.input("/discover_servers " .. url)
+                end
+            end)
+        end
+    end, { title = "Servers" })
+end
+alias.add("^/servers$", function ()
+    browse_servers()
+end)
+local function connected_server()
+    if not connected_to then
+        return nil
+    end
+    local names = {}
+    for _, server in ipairs(servers.get_all()) do
+        if server.host == connected_to.host and server.port == connected_to.port then
+            names[#names + 1] = server.name
+        end
+    end
+    table.sort(names)
+    return names[1]
+end
+local function print_palette_usage()
+    info("USAGE: /palette [<server> [<color> <color>|<color> off|clear]]")
+    info("EXAMPLE: /palette example blue bright_cyan")
+    info("EXAMPLE: /palette example 17 #5f87ff")
+    info("EXAMPLE: /palette example blue off")
+end
+alias.add("^/palette(?: (.*))?$", function (m)
+    local args = get_args(m[2] or "")
+    local server = args[1] or connected_server()
+    local ok, err = true, nil
+    if #args == 2 and args[2] == "clear" then
+        ok, err = pcall(palette.clear, server)
+    elseif #args == 3 then
+        ok, err = pcall(palette.set, server, args[2], args[3] ~= "off" and args[3] or nil)
+    elseif #args > 1 then
+        print_palette_usage()
+        return
+    end
+    if not ok then
+        error(tostring(err))
+        return
+    end
+    for _, line in ipairs(palette.preview(server)) do
+        blight.output(line)
+    end
+    if not server then
+        info("Not connected to a saved server, showing the colors as they are")
+        return
+    end
+    local remapped = {}
+    for from, to in pairs(palette.get(server)) do
+        remapped[#remapped + 1] = string.format("%s -> %s", from, to)
+    end
+    table.sort(remapped)
+    if #remapped > 0 then
+        info(cformat("Remapped for <yellow>%s<reset>: %s", server, table.concat(remapped, ", ")))
+    else
+        info(cformat("No colors remapped for <yellow>%s<reset>", server))
+    end
+end)
+alias.add("^/color_filter(?: (\\S+)(?: (\\S+))?)?$", function (m)
+    local filter, server = m[2], m[3]
+    if filter and filter ~= "" then
+        local ok, err = pcall(palette.set_filter, filter, server ~= "" and server or nil)
+        if not ok then
+            error(tostring(err))
+            info("USAGE: /color_filter [off|deuteranopia|protanopia|tritanopia|contrast|default] [<server>]")
+            return
+        end
+    end
+    info(cformat("Color filter: <yellow>%s<reset>", palette.filter()))
+    local current = connected_server()
+    if current then
+        info(cformat("Color filter for <yellow>%s<reset>: <yellow>%s<reset>", current, palette.filter(current)))
+    end
+end)
+alias.add("^/contrast(?: (\\S+)(?: (\\S+))?)?$", function (m)
+    local background, ratio = m[2], m[3]
+    if background and background ~= "" then
+        local ok, err = pcall(function ()
+            palette.set_background(background ~= "off" and background or nil)
+            if ratio ~= "" then
+                palette.min_contrast(tonumber(ratio) or 0)
+            end
+        end)
+        if not ok then
+            error(tostring(err))
+            info("USAGE: /contrast [<background>|off] [<ratio>]")
+            return
+        end
+    end
+    local current = palette.background()
+    if current then
+        info(cformat("Server colors are kept at a contrast of <yellow>%s<reset> on <yellow>%s<reset>", palette.min_contrast(), current))
+    else
+        info("Server colors aren't adjusted, no terminal background is set")
+    end
+end)
+local function print_origin_usage()
+    info(
+        "USAGE: /origin [<origin>]",
+        "USAGE: /origin <origin> marker [<text>]",
+        "USAGE: /origin <origin> color <color>|off",
+        "USAGE: /origin <origin> hide|show|reset"
+        )
+end
+
+local function print_origin_style(name, style)
+    local marker = style.marker and cformat("<yellow>%s<reset>", style.marker) or "none"
+    local hidden = style.hidden and cformat(" <red>hidden<reset>") or ""
+    info(cformat("<yellow>%-16s<reset> marker: %s color: %s%s", name, marker, style.color or "none", hidden))
+end
+
+alias.add("^/origin(?: (\\S+)(?: (\\S+)(?: (.+))?)?)?$", function (m)
+    local name, action, value = m[2], m[3], m[4]
+    if not name or name == "" then
+        local styles = origin.styles()
+        local names = {}
+        for key in pairs(styles) do
+            table.insert(names, key)
+        end
+        table.sort(names)
+        if #names == 0 then
+            info("No origin styles, all output is shown as it is")
+        end
+        for _, key in ipairs(names) do
+            print_origin_style(key, styles[key])
+        end
+        return
+    end
+    local ok, style = pcall(origin.get, name)
+    if not ok then
+        error(tostring(style))
+        return
+    end
+    if action == "marker" then
+        style.marker = value ~= "" and value or nil
+    elseif action == "color" then
+        style.color = value ~= "" and value ~= "off" and value or nil
+    elseif action == "hide" or action == "show" then
+        style.hidden = action == "hide"
+    elseif action == "reset" then
+        style = nil
+    elseif action ~= "" then
+        print_origin_usage()
+        return
+    end
+    if action ~= "" then
+        local set_ok, err = pcall(origin.set, name, style)
+        if not set_ok then
+            error(tostring(err))
+            return
+        end
+    end
+    print_origin_style(name, origin.get(name))
+end)
+alias.add("^/resolve(?: (\\S+))?$", function (m)
+    if m[2] and m[2] ~= "" then
+        dns.resolve(m[2])
     else
-        blight.show_help("help", true)
+        info("USAGE: /resolve <host>")
+    end
+end)
+alias.add("^/dns(?: (\\S+)(?: (.*))?)?$", function (m)
+    local setting, value = m[2], m[3] or ""
+    local ok, err = true, nil
+    if setting == "prefer" then
+        ok, err = pcall(dns.set, { prefer = value })
+    elseif setting == "servers" then
+        ok, err = pcall(dns.set, { servers = get_args(value) })
+    elseif setting == "timeout" and tonumber(value) then
+        ok, err = pcall(dns.set, { timeout = tonumber(value) })
+    elseif setting and setting ~= "" then
+        info("USAGE: /dns [prefer <ipv4|ipv6>|servers [<ip> ...]|timeout <seconds>]")
+        return
+    end
+    if not ok then
+        error(tostring(err))
+        return
+    end
+    local settings = dns.settings()
+    local servers = #settings.servers > 0 and table.concat(settings.servers, ", ") or "system resolver"
+    info(cformat("Prefer: <yellow>%s<reset>", settings.prefer))
+    info(cformat("Name servers: <yellow>%s<reset>", servers))
+    info(cformat("Timeout: <yellow>%ds<reset>", settings.timeout))
+end)
+alias.add("^/target(?: (.*))?$", function (m)
+    local name = m[2]
+    if name and name ~= "" then
+        if name == "clear" then
+            target.clear()
+            info("Targets cleared")
+        else
+            target.set(name)
+            info(cformat("Target: <yellow>%s<reset>", name))
+        end
+    else
+        local targets = target.list()
+        if #targets > 0 then
+            info(cformat("Targets: <yellow>%s<reset>", table.concat(targets, ", ")))
+        else
+            info("No target")
+        end
+    end
+end)
+alias.add("^/report(?: (reset))?$", function (m)
+    if m[2] == "reset" then
+        report.reset()
+        info("Session report reset")
+    else
+        report.print()
+    end
+end)
+
+alias.add("^/numpad(?: (on|off))?$", function (m)
+    if m[2] == "on" then
+        numpad.enable()
+    elseif m[2] == "off" then
+        numpad.disable()
+    else
+        numpad.toggle()
+    end
+    info("Numpad walking: " .. state_label(numpad.is_enabled(), "enabled"))
+end)
+alias.add("^/paste-as(?: (.+))?$", function (m)
+    if m[2] then
+        paste.template(m[2])
+        info("The next paste is sent as: " .. m[2])
+    elseif paste.pending() then
+        paste.template(nil)
+        info("Paste template cleared")
+    else
+        info("USAGE: /paste-as <template>, eg. /paste-as say $line")
+    end
+end)
+alias.add("^/charmode(?: (on|off))?$", function (m)
+    if m[2] == "on" then
+        mud.char_mode(true)
+    elseif m[2] == "off" then
+        mud.char_mode(false)
+    else
+        mud.char_mode(not mud.char_mode())
+    end
+end)
+alias.add("^/inbox(?: (clear))?$", function (m)
+    if m[2] == "clear" then
+        inbox.clear()
+        info("Inbox cleared")
+        return
+    end
+    inbox.show()
+end)
+alias.add("^/next-active$", function ()
+    activity.next_active()
+end)
+alias.add("^/break(?: (\\S+) (\\d+))?$", function (m)
+    if m[2] and m[2] ~= "" then
+        local id = debugger.breakpoint(m[2], tonumber(m[3]))
+        info(cformat("Breakpoint <yellow>%d<reset> set at %s:%s", id, m[2], m[3]))
+    else
+        local ids = {}
+        local breakpoints = debugger.list()
+        for id in pairs(breakpoints) do
+            ids[#ids + 1] = id
+        end
+        table.sort(ids)
+        if #ids == 0 then
+            info("No breakpoints")
+        end
+        for _, id in ipairs(ids) do
+            local bp = breakpoints[id]
+            info(cformat("<yellow>%d<reset>: %s:%d", id, bp.file, bp.line))
+        end
+    end
+end)
+alias.add("^/unbreak (\\d+|all)$", function (m)
+    if m[2] == "all" then
+        debugger.clear()
+        info("Breakpoints cleared")
+    else
+        debugger.remove(tonumber(m[2]))
+        info(cformat("Breakpoint <yellow>%s<reset> removed", m[2]))
+    end
+end)
+alias.add("^/reader(?: (\\w+) (on|off))?$", function (m)
+    if m[2] and m[2] ~= "" then
+        local ok, err = pcall(reader.set, m[2], m[3] == "on")
+        if ok then
+            info(cformat("Reader filter <yellow>%s<reset> %s", m[2], m[3]))
+        else
+            error(tostring(err))
+        end
+    else
+        local filters = reader.filters()
+        local names = {}
+        for name in pairs(filters) do
+            names[#names + 1] = name
+        end
+        table.sort(names)
+        if not settings.get("reader_mode") then
+            info("Reader mode is off, the filters apply once it's enabled with /set reader_mode on")
+        end
+        for _, name in ipairs(names) do
+            info(cformat("%-12s <yellow>%s<reset>", name, filters[name] and "on" or "off"))
+        end
+    end
+end)
+alias.add("^/soundtheme(?: (\\S+))?$", function (m)
+    local name = m[2]
+    if name and name ~= "" then
+        local ok, err = pcall(sounds.set_theme, name ~= "off" and name or nil)
+        if not ok then
+            error(tostring(err))
+        elseif name == "off" then
+            info("Sound theme disabled")
+        else
+            info(cformat("Sound theme <yellow>%s<reset> enabled", name))
+        end
+    else
+        local themes = sounds.themes()
+        if #themes == 0 then
+            info("No sound themes installed, see /help sounds")
+        end
+        local active = sounds.theme()
+        for _, theme in ipairs(themes) do
+            if theme == active then
+                info(cformat("<yellow>%s<reset> (active)", theme))
+            else
+                info(theme)
+            end
+        end
+    end
+end)
+alias.add("^/debug gmcp(?: (on|off|clear))?$", function (m)
+    local arg = m[2]
+    if arg == "on" or arg == "off" then
+        protocol.debug(arg == "on")
+        info(cformat("Protocol debugging <yellow>%s<reset>", arg))
+    elseif arg == "clear" then
+        protocol.clear_debug_log()
+        info("Protocol debug log cleared")
+    else
+        local entries = protocol.debug_log()
+        if #entries == 0 then
+            if protocol.debug() then
+                info("Protocol debug log is empty")
+            else
+                info("Protocol debugging is off, enable it with /debug gmcp on")
+            end
+        end
+        for _, entry in ipairs(entries) do
+            info(string.format("%s %s %s %s", os.date("%H:%M:%S", entry.time),
+                entry.direction == "in" and "<-" or "->", entry.kind:upper(), entry.text))
+        end
+    end
+end)
+alias.add("^/errors(?: (\\d+|clear))?$", function (m)
+    if m[2] == "clear" then
+        script.clear_errors()
+    else
+        script.show_errors(tonumber(m[2]))
+    end
+end)
+alias.add("^/health(?: (reset))?$", function (m)
+    if m[2] == "reset" then
+        metrics.reset()
+        info("Event loop metrics cleared")
+        return
+    end
+    local stats = metrics.get()
+    local function timing(t)
+        return string.format("avg %.2f ms, max %.2f ms", t.avg_ms, t.max_ms)
+    end
+    info(cformat("Events handled: <yellow>%d<reset> (%s)", stats.total.count, timing(stats.total)))
+    info(cformat("Rendering: %s", timing(stats.render)))
+    info(cformat("Queue depth: <yellow>%d<reset> now, %d at most", stats.queue_depth, stats.max_queue_depth))
+    local buckets = {}
+    for i, count in ipairs(stats.total.histogram) do
+        local label = stats.buckets[i] and ("<" .. stats.buckets[i]) or (">=" .. stats.buckets[#stats.buckets])
+        buckets[#buckets + 1] = string.format("%s ms: %d", label, count)
+    end
+    info("Latency: " .. table.concat(buckets, ", "))
+    local names = {}
+    for name in pairs(stats.events) do
+        names[#names + 1] = name
+    end
+    table.sort(names, function (a, b)
+        return stats.events[a].total_ms > stats.events[b].total_ms
+    end)
+    for i = 1, math.min(#names, 10) do
+        local t = stats.events[names[i]]
+        info(cformat("  <yellow>%-20s<reset> %8d  %s", names[i], t.count, timing(t)))
+    end
+end)
+local function format_bytes(bytes)
+    if bytes >= 1024 * 1024 then
+        return string.format("%.1f MB", bytes / (1024 * 1024))
+    elseif bytes >= 1024 then
+        return string.format("%.1f kB", bytes / 1024)
+    end
+    return string.format("%d B", bytes)
+end
+local gc_options = { mode = true, pause = true, step_multiplier = true, idle_step = true }
+local function print_memory_usage()
+    info("USAGE: /memory [collect|gc [<option> <value>]]",
+        "       options: mode <incremental|generational>, pause <percent>,",
+        "                step_multiplier <percent>, idle_step <kB>")
+end
+alias.add("^/memory(?: (\\S+)(?: (\\S+)(?: (\\S+))?)?)?$", function (m)
+    local action, option, value = m[2], m[3], m[4]
+    if action == "collect" then
+        info("Freed " .. format_bytes(memory.collect()))
+    elseif action == "gc" then
+        if option and option ~= "" then
+            if not gc_options[option] or not value or value == "" then
+                print_memory_usage()
+                return
+            end
+            local ok, err = pcall(memory.set_gc, { [option] = tonumber(value) or value })
+            if not ok then
+                error(tostring(err))
+                return
+            end
+        end
+        local gc = memory.gc()
+        info(cformat("Mode: <yellow>%s<reset>", gc.mode))
+        info(cformat("Pause: <yellow>%d%%<reset>, step multiplier: <yellow>%d%%<reset>", gc.pause, gc.step_multiplier))
+        info(cformat("Idle step: <yellow>%d kB<reset>", gc.idle_step))
+    elseif action and action ~= "" then
+        print_memory_usage()
+    else
+        local stats = memory.stats()
+        info(cformat("Lua: <yellow>%s<reset>", format_bytes(stats.lua)))
+        info(cformat("Scrollback: <yellow>%s<reset> in %d lines", format_bytes(stats.scrollback), stats.scrollback_lines))
+        if stats.scrollback_spilled > 0 then
+            info(cformat("Scrollback on disk: <yellow>%d<reset> lines", stats.scrollback_spilled))
+        end
+        info(cformat("Session store: <yellow>%s<reset>", format_bytes(stats.session_store)))
+        info(cformat("Disk store: <yellow>%s<reset>", format_bytes(stats.disk_store)))
+    end
+end)
+alias.add("^/web(?: (start|stop|bind))?(?: (\\S+))?$", function (m)
+    if m[2] == "start" then
+        local ok, url = pcall(web.start, tonumber(m[3]))
+        if ok then
+            info(cformat("Serving the session at <yellow>%s<reset>", url))
+            if web.status().exposed then
+                error("The page can be opened from other machines, anyone who sees the url can play your character")
+            end
+        else
+            error(tostring(url))
+        end
+    elseif m[2] == "stop" then
+        web.stop()
+        info("Stopped serving the session")
+    elseif m[2] == "bind" then
+        if m[3] ~= "" then
+            local ok, err = pcall(web.bind, m[3])
+            if not ok then
+                error(tostring(err))
+                return
+            end
+        end
+        info(cformat("The web frontend listens on <yellow>%s<reset>, used from the next /web start", web.bind()))
+    else
+        local status = web.status()
+        if status then
+            info(cformat("Serving the session at <yellow>%s<reset> to %d page(s)", status.url, status.clients))
+        elseif web.is_available() then
+            info("The web frontend is stopped", "USAGE: /web <start [port]|stop|bind [address]>")
+        else
+            info("Blightmud was built without the web frontend, see /help web")
+        end
+    end
+end)
+alias.add("^/snapshot(?: (save|load|delete) (\\S+))?$", function (m)
+    local action, name = m[2], m[3]
+    if action == "save" or action == "load" then
+        local ok, err = pcall(snapshot[action], name)
+        if ok then
+            info(cformat("Snapshot <yellow>%s<reset> %s", name, action == "save" and "saved" or "loaded"))
+        else
+            error(tostring(err))
+        end
+    elseif action == "delete" then
+        if snapshot.delete(name) then
+            info(cformat("Snapshot <yellow>%s<reset> deleted", name))
+        else
+            error("No snapshot named " .. name)
+        end
+    else
+        local names = snapshot.list()
+        if #names > 0 then
+            info(cformat("Snapshots: <yellow>%s<reset>", table.concat(names, ", ")))
+        else
+            info("No snapshots", "USAGE: /snapshot <save|load|delete> <name>")
+        end
+    end
+end)
+alias.add("^/backup(?: (create|restore)(?: (\\S+))?( confirm)?)?$", function (m)
+    local action, path, confirm = m[2], m[3], m[4] ~= ""
+    if action == "create" and not confirm then
+        local ok, result = pcall(backup.create, path ~= "" and path or nil)
+        if ok then
+            info(cformat("Backup written to <yellow>%s<reset>", result))
+        else
+            error(tostring(result))
+        end
+    elseif action == "restore" and path ~= "" and confirm then
+        local ok, result = pcall(backup.restore, path)
+        if ok then
+            info(cformat("Restored %d file(s), restart Blightmud to use them", result))
+        else
+            error(tostring(result))
+        end
+    elseif action == "restore" and path ~= "" then
+        local ok, result = pcall(backup.diff, path)
+        if not ok then
+            error(tostring(result))
+            return
+        end
+        local unchanged = 0
+        for _, file in ipairs(result) do
+            if file.change == "added" then
+                info(cformat("<green>added<reset>   %s", file.path))
+            elseif file.change == "changed" then
+                info(cformat("<yellow>changed<reset> %s", file.path))
+            else
+                unchanged = unchanged + 1
+            end
+        end
+        if unchanged == #result then
+            info("The files already match the backup")
+        else
+            info(cformat("%d file(s) unchanged, run <yellow>/backup restore %s confirm<reset> to restore the backup", unchanged, path))
+        end
+    else
+        info("USAGE: /backup create [<path>]", "USAGE: /backup restore <path> [confirm]")
+    end
+end)
+alias.add("^(?:/quit|/q)$", blight.quit)
+alias.add("^/help(?: search (.+)| (\\S+))?\\s*$", function (m)
+    if m[2] ~= "" then
+        help.search(m[2])
+    elseif m[3] ~= "" then
+        help.show(m[3])
+    else
+        local topics = { "help" }
+        for _, topic in ipairs(help.topics()) do
+            if topic ~= "help" then
+                topics[#topics + 1] = topic
+            end
+        end
+        ui.menu(topics, function (topic)
+            if topic then
+                help.show(topic)
+            end
+        end, { title = "Help topics" })
     end
 end)
 
@@ -307,11 +1364,26 @@ end)
 alias.add("^(?:/search|/s ).*$", function (m)
     local args = get_args(m[1])
     if #args == 1 then
-        info(
-            "USAGE: /search <regex>",
-            "USAGE: /s <regex>"
-            )
+        search.start()
     else
         search.search(args[2])
     end
 end)
+
+alias.add("^/filter(?: (.+))?$", function (m)
+    if m[2] and m[2] ~= "" then
+        search.filter(m[2])
+    elseif search.get_filter() then
+        search.clear_filter()
+    else
+        info("USAGE: /filter <regex>")
+    end
+end)
+
+alias.add("^/context$", function ()
+    if search.get_filter() then
+        search.filter_context()
+    else
+        info("No filter is active, see /help search")
+    end
+end)
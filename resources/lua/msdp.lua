@@ -100,6 +100,94 @@ local function decode(data)
     return content
 end
 
+local FRAMING_NAMES = {
+    [MSDP_VAR] = "VAR",
+    [MSDP_VAL] = "VAL",
+    [MSDP_TABLE_OPEN] = "TABLE_OPEN",
+    [MSDP_TABLE_CLOSE] = "TABLE_CLOSE",
+    [MSDP_ARRAY_OPEN] = "ARRAY_OPEN",
+    [MSDP_ARRAY_CLOSE] = "ARRAY_CLOSE",
+}
+
+-- Checks that a message of framing bytes and strings is well formed. Returns
+-- an error message if it isn't.
+local function validate(data)
+    local i = 1
+    local function fail(msg)
+        error(string.format("%s at item %d", msg, i), 0)
+    end
+    local function expect(byte)
+        if data[i] ~= byte then
+            fail("expected MSDP_" .. FRAMING_NAMES[byte])
+        end
+        i = i + 1
+    end
+    local function text()
+        local value = data[i]
+        if type(value) ~= "string" then
+            fail("expected a string")
+        elseif value:find("[\1-\6\255]") then
+            fail("string contains MSDP control characters")
+        end
+        i = i + 1
+    end
+
+    local parse_value
+    local function parse_var()
+        expect(MSDP_VAR)
+        text()
+        expect(MSDP_VAL)
+        parse_value()
+        while data[i] == MSDP_VAL do
+            i = i + 1
+            parse_value()
+        end
+    end
+    parse_value = function ()
+        if data[i] == MSDP_TABLE_OPEN then
+            i = i + 1
+            while data[i] == MSDP_VAR do
+                parse_var()
+            end
+            expect(MSDP_TABLE_CLOSE)
+        elseif data[i] == MSDP_ARRAY_OPEN then
+            i = i + 1
+            while data[i] == MSDP_VAL do
+                i = i + 1
+                parse_value()
+            end
+            expect(MSDP_ARRAY_CLOSE)
+        else
+            text()
+        end
+    end
+
+    local ok, err = pcall(function ()
+        if #data == 0 then
+            fail("empty message")
+        end
+        while i <= #data do
+            parse_var()
+        end
+    end)
+    if not ok then
+        return err
+    end
+end
+
+-- A readable form of a message for the protocol debug log
+local function describe(bytes)
+    local parts = {}
+    for _, b in ipairs(bytes) do
+        if FRAMING_NAMES[b] then
+            parts[#parts + 1] = "[" .. FRAMING_NAMES[b] .. "]"
+        else
+            parts[#parts + 1] = utf8.char(b)
+        end
+    end
+    return table.concat(parts)
+end
+
 function msdp()
     local self = {
         enabled = store.session_read("__msdp_enabled") == "true" or false,
@@ -135,7 +223,15 @@ function msdp()
     end
 
     local function msdp_send(data)
-        core.subneg_send(MSDP, assemble(data))
+        local err = validate(data)
+        if err then
+            error("msdp: malformed message, " .. err, 3)
+        end
+        local bytes = assemble(data)
+        if protocol.debug() then
+            protocol._trace("msdp", "out", describe(bytes))
+        end
+        core.subneg_send(MSDP, bytes)
     end
 
     local function store_content(content)
@@ -155,6 +251,9 @@ function msdp()
     end
 
     local set = function (var, val)
+        if type(val) == "number" then
+            val = tostring(val)
+        end
         msdp_send({ MSDP_VAR, var, MSDP_VAL, val })
     end
 
@@ -168,36 +267,40 @@ function msdp()
         end
     end
 
-    local report = function (value)
-        local payload = { MSDP_VAR, "REPORT" }
+    local function variables(value)
         if type(value) == "string" then
-            table.insert(payload, MSDP_VAL)
-            table.insert(payload, value)
-            msdp_send(payload)
+            return { value }
         elseif type(value) == "table" then
-            for _,val in ipairs(value) do
+            return value
+        end
+        return {}
+    end
+
+    local function send_report(command, vars)
+        if #vars > 0 then
+            local payload = { MSDP_VAR, command }
+            for _,val in ipairs(vars) do
                 table.insert(payload, MSDP_VAL)
                 table.insert(payload, val)
             end
             msdp_send(payload)
         end
+    end
 
+    local report = function (value)
+        local vars = variables(value)
+        send_report("REPORT", vars)
+        for _,val in ipairs(vars) do
+            protocol.remember("msdp", val)
+        end
     end
 
     local unreport = function (value)
-        local payload = { MSDP_VAR, "UNREPORT" }
-        if type(value) == "string" then
-            table.insert(payload, MSDP_VAL)
-            table.insert(payload, value)
-            msdp_send(payload)
-        elseif type(value) == "table" then
-            for _,val in ipairs(value) do
-                table.insert(payload, MSDP_VAL)
-                table.insert(payload, val)
-            end
-            msdp_send(payload)
+        local vars = variables(value)
+        send_report("UNREPORT", vars)
+        for _,val in ipairs(vars) do
+            protocol.forget("msdp", val)
         end
-
     end
 
     local list = function (list)
@@ -246,6 +349,9 @@ function msdp()
                     list
                 })
         end
+        protocol._resync("msdp", function (vars)
+            send_report("REPORT", vars)
+        end)
         for _,cb in ipairs(self.ready_listeners) do
             cb()
         end
@@ -258,6 +364,9 @@ function msdp()
     end
 
     local _subneg_recv = function (data)
+        if protocol.debug() then
+            protocol._trace("msdp", "in", describe(data))
+        end
         local recv = decode(data)
         store_content(recv)
         for var,val in pairs(recv) do
@@ -5,20 +5,23 @@ local naws_enabled = false
 -- Return table with the network byte order (e.g. big endian) encoding of the
 -- width and height given.
 local function network_dimensions(width, height)
-    bytes = {}
-    local append_byte = function(c)
-        table.insert(bytes, string.byte(c))
-    end
-    string.pack(">i2", width):gsub(".", append_byte)
-    string.pack(">i2", height):gsub(".", append_byte)
-    return bytes
+    return {
+        math.floor(width / 256) % 256, width % 256,
+        math.floor(height / 256) % 256, height % 256,
+    }
 end
 
 local function send_dimensions(width, height)
-    -- We must adjust the height to just the writable area, subtracting
-    -- the size by 2 for the input/prompt area, and by the size of the status
-    -- area.
-    height = height - 2 - blight.status_height()
+    local reported_width, reported_height = mud.reported_dimensions()
+    if reported_width then
+        -- Scripts that override the dimensions get them reported as is.
+        width, height = reported_width, reported_height
+    else
+        -- We must adjust the height to just the writable area, subtracting
+        -- the size by 2 for the input/prompt area, and by the size of the
+        -- status area.
+        height = height - 2 - blight.status_height()
+    end
     core.subneg_send(NAWS_PROTOCOL, network_dimensions(width, height))
 end
 
@@ -44,7 +47,15 @@ end)
 
 -- When dimensions change, send an updated NAWS message when enabled.
 blight.on_dimensions_change(function (width, height)
-    if naws_enabled then
+    if naws_enabled and not mud.reported_dimensions() then
         send_dimensions(width, height)
     end
 end)
+
+-- When a script overrides the reported dimensions, or removes the override,
+-- renegotiate with what's now reported.
+mud.on_reported_dimensions_change(function ()
+    if naws_enabled then
+        send_dimensions(blight.terminal_dimensions())
+    end
+end)
@@ -0,0 +1,127 @@
+local ENABLED_KEY = "__numpad_enabled"
+
+local DEFAULT_KEYS = {
+    kp8 = "n",
+    kp2 = "s",
+    kp4 = "w",
+    kp6 = "e",
+    kp7 = "nw",
+    kp9 = "ne",
+    kp1 = "sw",
+    kp3 = "se",
+    kp5 = "look",
+    ["kp-"] = "up",
+    ["kp+"] = "down",
+}
+
+local mod = {}
+local keys = {}
+local enabled = false
+local server_config = {}
+
+local function copy(tbl)
+    local ret = {}
+    for k, v in pairs(tbl) do
+        ret[k] = v
+    end
+    return ret
+end
+
+local function bind_all()
+    for key, cmd in pairs(keys) do
+        blight.bind(key, function ()
+            mud.input(cmd)
+        end)
+    end
+end
+
+local function unbind_all()
+    for key, _ in pairs(keys) do
+        blight.unbind(key)
+    end
+end
+
+local function apply(new_enabled, new_keys)
+    if enabled then
+        unbind_all()
+    end
+    enabled = new_enabled
+    keys = new_keys
+    if enabled then
+        bind_all()
+    end
+end
+
+function mod.enable()
+    store.disk_write(ENABLED_KEY, "true")
+    apply(true, keys)
+end
+
+function mod.disable()
+    store.disk_write(ENABLED_KEY, "false")
+    apply(false, keys)
+end
+
+function mod.toggle()
+    if enabled then
+        mod.disable()
+    else
+        mod.enable()
+    end
+    return enabled
+end
+
+function mod.is_enabled()
+    return enabled
+end
+
+function mod.set(key, command)
+    local new_keys = copy(keys)
+    new_keys[key] = command
+    apply(enabled, new_keys)
+end
+
+function mod.keys()
+    return copy(keys)
+end
+
+function mod.reset()
+    apply(enabled, copy(DEFAULT_KEYS))
+end
+
+function mod.server(id, config)
+    server_config[id] = config
+end
+
+mud.on_connect(function (host, port)
+    local ids = { string.format("%s:%d", host, port) }
+    local ok, stored = pcall(servers.get_all)
+    for _, server in ipairs(ok and stored or {}) do
+        if server.host == host and server.port == port then
+            table.insert(ids, 1, server.name)
+        end
+    end
+    for _, id in ipairs(ids) do
+        local config = server_config[id]
+        if config then
+            local new_keys = copy(DEFAULT_KEYS)
+            for key, cmd in pairs(config.keys or {}) do
+                new_keys[key] = cmd
+            end
+            local new_enabled = enabled
+            if config.enabled ~= nil then
+                new_enabled = config.enabled
+            end
+            apply(new_enabled, new_keys)
+            return
+        end
+    end
+end)
+
+mud.on_disconnect(function ()
+    apply(store.disk_read(ENABLED_KEY) == "true", copy(DEFAULT_KEYS))
+end)
+
+apply(store.disk_read(ENABLED_KEY) == "true", copy(DEFAULT_KEYS))
+
+return mod
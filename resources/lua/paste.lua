@@ -0,0 +1,65 @@
+local mod = {}
+local transform = nil
+local template = nil
+
+local function fill(pattern, line)
+    if pattern:find("$line", 1, true) then
+        return (pattern:gsub("%$line", function () return line end))
+    end
+    return pattern .. " " .. line
+end
+
+local function add(commands, result)
+    if type(result) == "table" then
+        for _, command in ipairs(result) do
+            commands[#commands + 1] = tostring(command)
+        end
+    elseif result ~= nil and result ~= false then
+        commands[#commands + 1] = tostring(result)
+    end
+end
+
+function mod.transform(cb)
+    transform = cb
+end
+
+function mod.template(new_template)
+    if new_template and new_template:match("^%s*$") then
+        new_template = nil
+    end
+    template = new_template
+end
+
+function mod.pending()
+    return template
+end
+
+function mod.lines(text, keep_blank)
+    local lines = {}
+    for line in (text:gsub("\r\n?", "\n") .. "\n"):gmatch("(.-)\n") do
+        if keep_blank or not line:match("^%s*$") then
+            lines[#lines + 1] = line
+        end
+    end
+    return lines
+end
+
+function mod._apply(text, keep_blank)
+    if not template and not transform then
+        return nil
+    end
+    local once = template
+    template = nil
+    local lines = mod.lines(text, keep_blank)
+    local commands = {}
+    for index, line in ipairs(lines) do
+        if once then
+            add(commands, fill(once, line))
+        else
+            add(commands, transform(line, index, #lines))
+        end
+    end
+    return commands
+end
+
+return mod
@@ -13,6 +13,87 @@ alias.add("^/plugins$", function ()
     end
 end)
 
+local function count_owned(groups, list)
+    local counts = {}
+    for _, group in pairs(groups) do
+        for _, item in pairs(group[list]) do
+            if item.plugin then
+                counts[item.plugin] = (counts[item.plugin] or 0) + 1
+            end
+        end
+    end
+    return counts
+end
+
+alias.add("^/plugins info$", function ()
+    local usage = plugin.usage()
+    local triggers = count_owned(trigger.trigger_groups, "triggers")
+    local aliases = count_owned(alias.alias_groups, "aliases")
+    local names = {}
+    for _, counts in ipairs({ usage, triggers, aliases }) do
+        for name in pairs(counts) do
+            names[name] = true
+        end
+    end
+    local sorted = {}
+    for name in pairs(names) do
+        sorted[#sorted + 1] = name
+    end
+    table.sort(sorted)
+    if #sorted == 0 then
+        print("[plugin] No plugin has registered anything")
+        return
+    end
+    for _, name in ipairs(sorted) do
+        local stats = usage[name] or { timers = 0, calls = 0, recent_ms = 0, total_ms = 0 }
+        print(string.format(
+            "[plugin]: %s%s - triggers: %d, aliases: %d, timers: %d, callbacks: %d, last minute: %.1fms, total: %.1fms",
+            name, stats.suspended and " (disabled)" or "", triggers[name] or 0, aliases[name] or 0,
+            stats.timers, stats.calls, stats.recent_ms, stats.total_ms))
+    end
+end)
+
+alias.add("^/plugins (disable|enable)(.*)$", function (m)
+    local args = get_args(m[3])
+    if #args == 0 then
+        print("USAGE: /plugins " .. m[2] .. " <plugin_name>")
+    elseif m[2] == "disable" then
+        plugin.suspend(args[1])
+        print("[plugin] Disabled: " .. args[1])
+    else
+        plugin.resume(args[1])
+        print("[plugin] Enabled: " .. args[1])
+    end
+end)
+
+alias.add("^/plugins? verbosity(.*)$", function (m)
+    local args = get_args(m[2])
+    if #args == 0 then
+        local verbosities = plugin.verbosities()
+        local names = {}
+        for name in pairs(verbosities) do
+            names[#names + 1] = name
+        end
+        table.sort(names)
+        if #names == 0 then
+            print("[plugin] All plugins show info, warnings and errors")
+        end
+        for _, name in ipairs(names) do
+            print(string.format("[plugin]: %s - %s", name, verbosities[name]))
+        end
+    elseif #args == 1 then
+        print(string.format("[plugin] %s: %s", args[1], plugin.verbosity(args[1])))
+    else
+        local ok, err = pcall(plugin.set_verbosity, args[1], args[2])
+        if ok then
+            print(string.format("[plugin] %s: %s", args[1], args[2]))
+        else
+            print("[plugin] " .. tostring(err))
+            print("USAGE: /plugins verbosity [<plugin_name> [off|error|warn|info|debug]]")
+        end
+    end
+end)
+
 alias.add("^/add_plugin.*$", function (m)
     local args = get_args(m[1])
     if #args == 1 then
@@ -24,11 +105,35 @@ alias.add("^/add_plugin.*$", function (m)
 end)
 
 
+alias.add("^/audit(.*)$", function (m)
+    local args = get_args(m[2])
+    if #args == 0 then
+        print("USAGE: /audit <plugin_name|path>")
+        return
+    end
+    local target = args[1]
+    local findings, err = plugin.audit(target)
+    if not findings then
+        print("[audit] " .. err)
+    elseif #findings == 0 then
+        print("[audit] No risky calls found in " .. target)
+    else
+        print(string.format("[audit] %d risky call(s) in %s:", #findings, target))
+        for _, f in ipairs(findings) do
+            print(string.format("[audit]   %s:%d [%s] %s %s", f.file, f.line, f.permission, f.call, f.reason))
+        end
+    end
+end)
+
 alias.add("^/enable_plugin.*$", function (m)
     local args = get_args(m[1])
     if #args == 1 then
         print("USAGE: /enable_plugin <plugin_name>")
     else
+        local findings = plugin.audit(args[2])
+        if findings and #findings > 0 then
+            print(string.format("[plugin] %s makes %d risky call(s), see /audit %s", args[2], #findings, args[2]))
+        end
         plugin.enable(args[2])
     end
 end)
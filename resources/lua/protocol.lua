@@ -0,0 +1,131 @@
+local mod = {}
+
+local STATE_KEY = "__protocol_state"
+local DEBUG_KEY = "__protocol_debug"
+-- Oldest entries are dropped once the debug log holds more than this
+local MAX_DEBUG_ENTRIES = 500
+
+-- Telnet options enabled on the current connection
+local options = {}
+-- Items restored every time a protocol is enabled, eg. { gmcp = { ["Char.Vitals"] = true } }
+local remembered = json.decode(store.session_read(STATE_KEY) or "{}")
+
+local debug_enabled = store.session_read(DEBUG_KEY) == "true"
+local debug_log = {}
+
+local resync_listeners = {}
+local next_listener_id = 1
+
+local function save()
+    store.session_write(STATE_KEY, json.encode(remembered))
+end
+
+local function sorted_keys(tbl)
+    local keys = {}
+    for key in pairs(tbl) do
+        keys[#keys + 1] = key
+    end
+    table.sort(keys)
+    return keys
+end
+
+core.on_protocol_enabled(function (proto)
+    options[proto] = true
+end)
+
+core.on_protocol_disabled(function (proto)
+    options[proto] = nil
+end)
+
+mud.on_disconnect(function ()
+    options = {}
+end)
+
+function mod.options()
+    return sorted_keys(options)
+end
+
+function mod.enabled(proto)
+    return options[proto] == true
+end
+
+function mod.remember(kind, item)
+    remembered[kind] = remembered[kind] or {}
+    if not remembered[kind][item] then
+        remembered[kind][item] = true
+        save()
+    end
+end
+
+function mod.forget(kind, item)
+    if item == nil then
+        remembered[kind] = nil
+    elseif remembered[kind] then
+        remembered[kind][item] = nil
+    end
+    save()
+end
+
+function mod.remembered(kind)
+    return sorted_keys(remembered[kind] or {})
+end
+
+function mod.on_resync(cb)
+    local id = next_listener_id
+    next_listener_id = next_listener_id + 1
+    resync_listeners[id] = cb
+    return id
+end
+
+function mod.remove_resync_listener(id)
+    resync_listeners[id] = nil
+end
+
+-- Called by the protocol modules once a protocol is enabled. Listeners may
+-- replace the items to restore or return false to skip restoring them.
+function mod._resync(kind, send)
+    local items = mod.remembered(kind)
+    for _, id in ipairs(sorted_keys(resync_listeners)) do
+        local ret = resync_listeners[id](kind, items)
+        if ret == false then
+            return
+        elseif type(ret) == "table" then
+            items = ret
+        end
+    end
+    if #items > 0 then
+        send(items)
+    end
+end
+
+function mod.debug(enabled)
+    if enabled ~= nil then
+        debug_enabled = enabled
+        store.session_write(DEBUG_KEY, tostring(enabled))
+    end
+    return debug_enabled
+end
+
+function mod.debug_log()
+    local ret = {}
+    for i, entry in ipairs(debug_log) do
+        ret[i] = { time = entry.time, kind = entry.kind, direction = entry.direction, text = entry.text }
+    end
+    return ret
+end
+
+function mod.clear_debug_log()
+    debug_log = {}
+end
+
+-- Called by the protocol modules for every message sent or received
+function mod._trace(kind, direction, text)
+    if debug_enabled then
+        table.insert(debug_log, { time = os.time(), kind = kind, direction = direction, text = text })
+        while #debug_log > MAX_DEBUG_ENTRIES do
+            table.remove(debug_log, 1)
+        end
+    end
+end
+
+return mod
@@ -0,0 +1,161 @@
+local mod = {}
+
+local TOP_COUNT = 5
+local BAR_WIDTH = 20
+
+local received = 0
+local sent = 0
+local connected_total = 0
+local connected_since = nil
+local tracked = {}
+
+if mud.is_connected() then
+    connected_since = os.time()
+end
+
+mud.on_connect(function ()
+    connected_since = os.time()
+end)
+
+mud.on_disconnect(function ()
+    if connected_since then
+        connected_total = connected_total + os.time() - connected_since
+        connected_since = nil
+    end
+end)
+
+mud.add_output_listener(function (line)
+    if not line:prompt() then
+        received = received + 1
+    end
+    return line
+end)
+
+mud.add_input_listener(function (line)
+    if not line:matched() then
+        sent = sent + 1
+    end
+    return line
+end)
+
+local function ranked(groups, field)
+    local hits = {}
+    for _, group in pairs(groups) do
+        for _, item in pairs(group[field]) do
+            if item.hits > 0 then
+                local pattern = item.regex:regex()
+                hits[pattern] = (hits[pattern] or 0) + item.hits
+            end
+        end
+    end
+    local ret = {}
+    for pattern, count in pairs(hits) do
+        ret[#ret + 1] = { pattern = pattern, hits = count }
+    end
+    table.sort(ret, function (a, b)
+        if a.hits == b.hits then
+            return a.pattern < b.pattern
+        end
+        return a.hits > b.hits
+    end)
+    return ret
+end
+
+local function reset_hits(groups, field)
+    for _, group in pairs(groups) do
+        for _, item in pairs(group[field]) do
+            item.hits = 0
+        end
+    end
+end
+
+function mod.track(name, var)
+    var = var or name
+    tracked[name] = { var = var, start = tonumber(vars.get(var)) }
+end
+
+function mod.untrack(name)
+    tracked[name] = nil
+end
+
+function mod.get()
+    local connected = connected_total
+    if connected_since then
+        connected = connected + os.time() - connected_since
+    end
+    local deltas = {}
+    for name, stat in pairs(tracked) do
+        local current = tonumber(vars.get(stat.var))
+        if not stat.start then
+            stat.start = current
+        end
+        if current then
+            deltas[name] = current - stat.start
+        end
+    end
+    return {
+        lines_received = received,
+        lines_sent = sent,
+        connected = connected,
+        triggers = ranked(trigger.trigger_groups, "triggers"),
+        aliases = ranked(alias.alias_groups, "aliases"),
+        tracked = deltas,
+    }
+end
+
+function mod.reset()
+    received = 0
+    sent = 0
+    connected_total = 0
+    if connected_since then
+        connected_since = os.time()
+    end
+    for _, stat in pairs(tracked) do
+        stat.start = tonumber(vars.get(stat.var))
+    end
+    reset_hits(trigger.trigger_groups, "triggers")
+    reset_hits(alias.alias_groups, "aliases")
+end
+
+local function duration(seconds)
+    return string.format("%dh %02dm %02ds", math.floor(seconds / 3600), math.floor(seconds % 3600 / 60), seconds % 60)
+end
+
+local function heatmap(title, entries)
+    if #entries == 0 then
+        return
+    end
+    blight.output(C_BWHITE .. title .. ":" .. C_RESET)
+    local max = entries[1].hits
+    for i = 1, math.min(TOP_COUNT, #entries) do
+        local entry = entries[i]
+        local width = math.max(1, math.floor(entry.hits / max * BAR_WIDTH + 0.5))
+        local bar = string.rep("█", width) .. string.rep(" ", BAR_WIDTH - width)
+        blight.output(string.format("  %s%s%s %6d  %s", C_RED, bar, C_RESET, entry.hits, entry.pattern))
+    end
+end
+
+function mod.print()
+    local stats = mod.get()
+    blight.output(C_BWHITE .. "Session report" .. C_RESET)
+    blight.output(string.format("  Connected:      %s", duration(stats.connected)))
+    blight.output(string.format("  Lines received: %d", stats.lines_received))
+    blight.output(string.format("  Lines sent:     %d", stats.lines_sent))
+    heatmap("Most triggered", stats.triggers)
+    heatmap("Most used aliases", stats.aliases)
+    local names = {}
+    for name, _ in pairs(stats.tracked) do
+        names[#names + 1] = name
+    end
+    if #names > 0 then
+        table.sort(names)
+        blight.output(C_BWHITE .. "Tracked:" .. C_RESET)
+        for _, name in ipairs(names) do
+            local delta = stats.tracked[name]
+            local sign = delta >= 0 and "+" or ""
+            blight.output(string.format("  %-15s %s%s", name .. ":", sign, tostring(delta)))
+        end
+    end
+end
+
+return mod
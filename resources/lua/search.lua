@@ -2,12 +2,97 @@ local mod = {}
 
 local input_pattern = regex.new("^\x1b.*?m>.*$")
 local search_pattern = nil
+-- nil, "typing" while a pattern is entered or "navigate" once it's confirmed
+local mode = nil
 
-function mod.search(str)
-    search_pattern = regex.new(str)
+function mod.search(str, options)
+    search_pattern = regex.new(str, options)
     blight.find_backward(search_pattern)
 end
 
+function mod.start()
+    search_pattern = nil
+    mode = "typing"
+    blight.output(C_YELLOW .. "[search] Type a pattern and press <enter>. An empty <enter> cancels." .. C_RESET)
+end
+
+function mod.stop()
+    if mode then
+        mode = nil
+        blight.scroll_to()
+    end
+end
+
+function mod.active()
+    return mode ~= nil
+end
+
+function mod.matches(str, callback, options)
+    blight.find_all(regex.new(str, options), callback)
+end
+
+local filter_pattern = nil
+
+function mod.filter(str, options)
+    filter_pattern = str
+    blight.filter(regex.new(str, options))
+end
+
+function mod.clear_filter()
+    filter_pattern = nil
+    blight.filter()
+end
+
+function mod.filter_context()
+    filter_pattern = nil
+    blight.filter_context()
+end
+
+function mod.get_filter()
+    return filter_pattern
+end
+
+prompt.add_prompt_listener(function(input)
+    if input == "" then
+        return
+    end
+    if mode == "typing" then
+        local ok, re = pcall(regex.new, input)
+        if ok then
+            search_pattern = re
+            blight.find_backward(re, true)
+        end
+    elseif mode == "navigate" then
+        if input == "n" then
+            mod.find_up()
+        elseif input == "N" then
+            mod.find_down()
+        else
+            mode = nil
+            return
+        end
+        prompt.set("")
+    end
+end)
+
+mud.add_input_listener(function(line)
+    if mode == "typing" then
+        line:matched(true)
+        local ok, re = pcall(regex.new, line:line())
+        if line:line() == "" or not ok then
+            mod.stop()
+        else
+            search_pattern = re
+            mode = "navigate"
+            blight.output(C_YELLOW .. "[search] Use n/N to step through matches. An empty <enter> leaves." .. C_RESET)
+        end
+    elseif mode == "navigate" and line:line() == "" then
+        line:matched(true)
+        mod.stop()
+    end
+    return line
+end)
+
 function mod.find_up()
     if search_pattern then
         blight.find_backward(search_pattern)
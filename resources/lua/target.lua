@@ -0,0 +1,129 @@
+local TARGET_VAR = "target"
+
+local mod = {}
+local targets = {}
+local death_listeners = {}
+local flee_listeners = {}
+local syncing = false
+
+local function index_of(name)
+    for i, t in ipairs(targets) do
+        if t:lower() == name:lower() then
+            return i
+        end
+    end
+    return nil
+end
+
+local function sync()
+    syncing = true
+    vars.set(TARGET_VAR, targets[1])
+    syncing = false
+end
+
+local function notify(listeners, name)
+    for _, cb in ipairs(listeners) do
+        cb(name)
+    end
+end
+
+function mod.set(name)
+    local i = index_of(name)
+    if i then
+        table.remove(targets, i)
+    end
+    table.insert(targets, 1, name)
+    sync()
+end
+
+function mod.add(name)
+    if not index_of(name) then
+        table.insert(targets, name)
+        sync()
+    end
+end
+
+function mod.remove(name)
+    local i = index_of(name)
+    if i then
+        table.remove(targets, i)
+        sync()
+        return true
+    end
+    return false
+end
+
+function mod.next()
+    if #targets > 1 then
+        table.insert(targets, table.remove(targets, 1))
+        sync()
+    end
+    return targets[1]
+end
+
+function mod.clear()
+    targets = {}
+    sync()
+end
+
+function mod.current()
+    return targets[1]
+end
+
+function mod.list()
+    return { table.unpack(targets) }
+end
+
+function mod.killed(name)
+    name = name or targets[1]
+    if name and mod.remove(name) then
+        notify(death_listeners, name)
+    end
+end
+
+function mod.fled(name)
+    name = name or targets[1]
+    if name and mod.remove(name) then
+        notify(flee_listeners, name)
+    end
+end
+
+function mod.on_death(cb)
+    table.insert(death_listeners, cb)
+end
+
+function mod.on_flee(cb)
+    table.insert(flee_listeners, cb)
+end
+
+function mod.death_pattern(re)
+    return trigger.add(re, {}, function (m)
+        mod.killed(m[2])
+    end)
+end
+
+function mod.flee_pattern(re)
+    return trigger.add(re, {}, function (m)
+        mod.fled(m[2])
+    end)
+end
+
+-- Keep the list in sync when the target variable is set elsewhere, eg. from GMCP
+vars.on_change(TARGET_VAR, function (name)
+    if syncing then
+        return
+    end
+    if name == nil or name == "" then
+        if targets[1] then
+            table.remove(targets, 1)
+        end
+    else
+        local i = index_of(tostring(name))
+        if i then
+            table.remove(targets, i)
+        end
+        table.insert(targets, 1, tostring(name))
+    end
+end)
+
+return mod
@@ -8,6 +8,17 @@ local mod = {}
 -- identify a trigger
 local next_id = 1
 
+-- A trigger whose callback sends commands more than loop_threshold times
+-- within loop_interval milliseconds is assumed to be stuck in a loop and is
+-- disabled. Matches that don't send anything aren't counted
+local loop_threshold = 50
+local loop_interval = 1000
+local clock = 0
+
+timer.on_tick(function (millis)
+    clock = millis
+end)
+
 mod.Trigger = {}
 local Trigger = mod.Trigger
 Trigger.__index = Trigger
@@ -15,12 +26,19 @@ Trigger.__index = Trigger
 function Trigger.new(re, options, callback)
     local ret = setmetatable({}, Trigger)
 
-    ret.regex = regex.new(re)
+    ret.regex = regex.new(re, options)
     ret.callback = callback
     ret.gag = options.gag or false
     ret.raw = options.raw or false
     ret.prompt = options.prompt or false
     ret.count = options.count or nil
+    ret.tag = options.tag
+    ret.important = options.important
+    ret.decoration = options.decoration or false
+    ret.sound = options.sound
+    ret.hits = 0
+    ret.plugin = plugin.caller()
+    ret.script = script.caller()
     ret.enabled = true
     if options.enabled ~= nil then
         ret.enabled = options.enabled
@@ -51,10 +69,55 @@ function Trigger:is_enabled()
     return self.enabled
 end
 
+-- Packages use the source of the callback, found through debug info unless set here
+function Trigger:set_source(source)
+    assert(load("return " .. source), "Trigger source isn't a valid function")
+    self.source = source
+end
+
+local function check_loop(self)
+    if loop_threshold <= 0 then
+        return
+    end
+    if not self.loop_start or clock - self.loop_start > loop_interval then
+        self.loop_start = clock
+        self.loop_hits = 0
+    end
+    self.loop_hits = self.loop_hits + 1
+    if self.loop_hits > loop_threshold then
+        self.enabled = false
+        self.loop_start = nil
+        error(string.format(
+            "Trigger %d sent commands %d times within %dms and was disabled to stop a trigger loop: %s",
+            self.id, loop_threshold + 1, loop_interval, self.regex:regex()), 0)
+    end
+end
+
+-- Kept as an upvalue so matching doesn't look the debugger up every time
+local watch = debugger.watch
+
+-- Callbacks of plugins are timed so /plugins info can show what they cost
+local function run_callback(self, matches, line)
+    local message = "Trigger callback has been running for +2 seconds. Aborting"
+    if not self.plugin then
+        watch(self.callback, message, matches, line)
+        return
+    end
+    local start = os.clock()
+    local ok, err = pcall(watch, self.callback, message, matches, line)
+    plugin.record(self.plugin, os.clock() - start)
+    if not ok then
+        error(err, 0)
+    end
+end
+
 function Trigger:check_line(line)
     if not self.enabled then
         return
     end
+    if self.plugin and plugin.is_suspended(self.plugin) then
+        return
+    end
     if line:prompt() ~= self.prompt then return end
     local str
     if self.raw then
@@ -69,20 +132,33 @@ function Trigger:check_line(line)
             line:gag(true)
         end
         line:matched(true)
+        self.hits = self.hits + 1
         if self.count and self.count > 0 then
             self.count = self.count - 1
         end
-
-        local startTime = os.time()
-        debug.sethook(function ()
-            if os.time() > startTime + 2 then
-                debug.sethook()
-                error("Trigger callback has been running for +2 seconds. Aborting", 2)
+        if self.tag then
+            relay.send(self.tag, line:line())
+        end
+        if self.important then
+            local source = self.important
+            if source == true then
+                source = "trigger"
             end
-        end, "", 500)
-    self.callback(matches, line)
-    debug.sethook()
-end
+            line:set_tag("important", source)
+        end
+        if self.decoration then
+            line:set_tag("decoration", "trigger")
+        end
+        if self.sound then
+            sounds.play_event(self.sound)
+        end
+
+        local sent = mud._sent_count()
+        run_callback(self, matches, line)
+        if mud._sent_count() ~= sent then
+            check_loop(self)
+        end
+    end
 end
 
 --------------------------------------------------------------------------------
@@ -96,10 +172,11 @@ mod.TriggerGroup = {
 local TriggerGroup = mod.TriggerGroup
 TriggerGroup.__index = TriggerGroup
 
-function TriggerGroup.new(id)
+function TriggerGroup.new(id, name)
     local ret = setmetatable({}, TriggerGroup)
 
     ret.id = id
+    ret.name = name
     ret.enabled = true
     ret.triggers = {}
 
@@ -149,6 +226,104 @@ function TriggerGroup:disable()
     self.enabled = false
 end
 
+function TriggerGroup:activate_when(rules)
+    activation.add(self, rules)
+end
+
+local PACKAGE_OPTIONS = { "gag", "raw", "prompt", "count", "tag", "important", "decoration", "sound" }
+
+local function callback_source(trigger)
+    if trigger.source then
+        return trigger.source
+    elseif not trigger.callback then
+        return "nil"
+    end
+    local source = script.function_source(trigger.callback)
+    if not source then
+        return nil, string.format(
+            "The callback source of trigger %d wasn't found, set it with trigger:set_source()",
+            trigger.id)
+    end
+    -- Globals are fine, locals of the script that added the trigger don't come along
+    local i = 1
+    local name = debug.getupvalue(trigger.callback, i)
+    while name do
+        if name ~= "_ENV" then
+            return nil, string.format(
+                "The callback of trigger %d uses the local '%s', set its source with trigger:set_source()",
+                trigger.id, name)
+        end
+        i = i + 1
+        name = debug.getupvalue(trigger.callback, i)
+    end
+    return source
+end
+
+-- The options of a regex, sorted so exports don't change between runs
+local function regex_options(re, options)
+    local names = {}
+    for name, _ in pairs(re:options()) do
+        names[#names + 1] = name
+    end
+    table.sort(names)
+    for _, name in ipairs(names) do
+        options[#options + 1] = name .. " = true"
+    end
+    return options
+end
+
+function TriggerGroup:export(path)
+    local lines = {
+        "-- Blightmud trigger package, add it with trigger.import() or /load",
+        string.format("local group = trigger.add_group(%s)", self.name and string.format("%q", self.name) or ""),
+    }
+    local ids = {}
+    for id, _ in pairs(self.triggers) do
+        ids[#ids + 1] = id
+    end
+    table.sort(ids)
+    for _, id in ipairs(ids) do
+        local trigger = self.triggers[id]
+        local source, err = callback_source(trigger)
+        if not source then
+            return nil, err
+        end
+        local options = {}
+        for _, key in ipairs(PACKAGE_OPTIONS) do
+            local value = trigger[key]
+            if value then
+                -- LuaJIT's %q quotes booleans and numbers
+                if type(value) == "string" then
+                    value = string.format("%q", value)
+                end
+                options[#options + 1] = string.format("%s = %s", key, tostring(value))
+            end
+        end
+        options = regex_options(trigger.regex, options)
+        if not trigger.enabled then
+            options[#options + 1] = "enabled = false"
+        end
+        local options_source = "{}"
+        if #options > 0 then
+            options_source = "{ " .. table.concat(options, ", ") .. " }"
+        end
+        lines[#lines + 1] = string.format("group:add(%q, %s, %s)",
+            trigger.regex:regex(), options_source, source)
+    end
+    if not self.enabled then
+        lines[#lines + 1] = "group:disable()"
+    end
+    lines[#lines + 1] = "return group"
+
+    local file, err = io.open((path:gsub("^~", os.getenv("HOME") or "~")), "w")
+    if not file then
+        return nil, err
+    end
+    file:write(table.concat(lines, "\n"), "\n")
+    file:close()
+    return true
+end
+
 function TriggerGroup:check_line(line)
     if not self.enabled then
         return
@@ -200,6 +375,12 @@ end
 
 function mod.get_group(id)
     if not id then id = 1 end
+    if type(id) == "string" then
+        for _, group in pairs(get_trigger_groups()) do
+            if group.name == id then return group end
+        end
+        return nil
+    end
     return get_trigger_groups()[id]
 end
 
@@ -215,14 +396,35 @@ function mod.clear()
     end
 end
 
-function mod.add_group()
-    local ret = TriggerGroup.new(next_group_id)
+function mod.loop_protection(threshold, interval)
+    loop_threshold = threshold
+    loop_interval = interval or loop_interval
+end
+
+function mod.add_group(name)
+    local ret = TriggerGroup.new(next_group_id, name)
+    ret.script = script.caller()
     get_trigger_groups()[next_group_id] = ret
     next_group_id = next_group_id + 1
 
     return ret
 end
 
+function mod.import(path)
+    local chunk, err = loadfile((path:gsub("^~", os.getenv("HOME") or "~")))
+    if not chunk then
+        return nil, err
+    end
+    local ok, group = pcall(chunk)
+    if not ok then
+        return nil, group
+    end
+    if getmetatable(group) ~= TriggerGroup then
+        return nil, path .. " isn't a trigger package"
+    end
+    return group
+end
+
 mud.add_output_listener(function(line)
     for _, group in pairs(system_trigger_groups) do
         group:check_line(line)
@@ -22,11 +22,11 @@ mod.MTTS_MSLP           =   0x400 -- Mud Server Link Protocol enabled
 
 -- Build the default MTTS value
 local mtts = 0x0
-mtts = mtts | mod.MTTS_VT100
-mtts = mtts | mod.MTTS_ANSI
-mtts = mtts | mod.MTTS_UTF8
-mtts = mtts | mod.MTTS_256_COLOR
-mtts = mtts | mod.MTTS_TRUE_COLOR
+mtts = bit.bor(mtts, mod.MTTS_VT100)
+mtts = bit.bor(mtts, mod.MTTS_ANSI)
+mtts = bit.bor(mtts, mod.MTTS_UTF8)
+mtts = bit.bor(mtts, mod.MTTS_256_COLOR)
+mtts = bit.bor(mtts, mod.MTTS_TRUE_COLOR)
 
 local NEGOTIATION_STACK = {}
 
@@ -36,9 +36,9 @@ local function init()
     index = 1
     if auto_reader_mode then
         if (tts.is_available() and tts.is_enabled()) or blight.is_reader_mode() then
-            mtts = mtts | mod.MTTS_SCREEN_READER
+            mtts = bit.bor(mtts, mod.MTTS_SCREEN_READER)
         else
-            mtts = mtts & ~mod.MTTS_SCREEN_READER
+            mtts = bit.band(mtts, bit.bnot(mod.MTTS_SCREEN_READER))
         end
     end
     NEGOTIATION_STACK = {
@@ -100,14 +100,18 @@ function mod.set_term(new_term)
 end
 
 function mod.set_mtts(new_mtts)
-    mtts = new_mtts
+    local value = tonumber(new_mtts)
+    if not value then
+        error(string.format("Invalid MTTS value: %s", tostring(new_mtts)), 2)
+    end
+    mtts = value
     Info(string.format("Set MTTS: '0x%X'", mtts))
 end
 
 function mod.add_option(mtts_opt)
     local old_mtts = mtts
-    mtts = mtts | mtts_opt
-    if mtts_opt & mod.MTTS_SCREEN_READER then
+    mtts = bit.bor(mtts, mtts_opt)
+    if bit.band(mtts_opt, mod.MTTS_SCREEN_READER) then
         auto_reader_mode = false
     end
     Info(string.format("Updated MTTS 0x%X | 0x%X = 0x%X", old_mtts, mtts_opt, mtts))
@@ -115,8 +119,8 @@ end
 
 function mod.rem_option(mtts_opt)
     local old_mtts = mtts
-    mtts = mtts & ~mtts_opt
-    if mtts_opt & mod.MTTS_SCREEN_READER then
+    mtts = bit.band(mtts, bit.bnot(mtts_opt))
+    if bit.band(mtts_opt, mod.MTTS_SCREEN_READER) then
         auto_reader_mode = false
     end
     Info(string.format("Updated MTTS 0x%X & ~0x%X = 0x%X", old_mtts, mtts_opt, mtts))
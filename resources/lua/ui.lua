@@ -0,0 +1,275 @@
+local mod = {}
+
+local FRAMES = { "⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏" }
+local BAR_WIDTH = 10
+
+local indicators = {}
+local next_id = 1
+local frame = 1
+
+local questions = {}
+local draft = nil
+local masking = false
+
+local MENU_HEIGHT = 10
+local C_REVERSE = "\x1b[7m"
+local menu = nil
+
+mod.Spinner = {}
+local Spinner = mod.Spinner
+Spinner.__index = Spinner
+
+local function remove(key)
+    if indicators[key] then
+        indicators[key] = nil
+        blight.indicator(key)
+    end
+end
+
+local function draw_spinner(spinner)
+    blight.indicator(spinner.key, FRAMES[frame] .. " " .. spinner.text)
+end
+
+function Spinner:set_text(text)
+    self.text = text
+    if indicators[self.key] then
+        draw_spinner(self)
+    end
+end
+
+function Spinner:done()
+    remove(self.key)
+end
+
+function Spinner:is_active()
+    return indicators[self.key] ~= nil
+end
+
+function mod.spinner(text, callable, ...)
+    local spinner = setmetatable({ key = "__spinner_" .. next_id, text = text }, Spinner)
+    next_id = next_id + 1
+    local task = tasks.get_current()
+    if callable then
+        task = tasks.spawn(callable, spinner, ...)
+    end
+    indicators[spinner.key] = { spinner = spinner, label = text, task = task }
+    draw_spinner(spinner)
+    return spinner
+end
+
+function mod.progress(id, pct, text)
+    local key = "__progress_" .. id
+    if pct == nil or pct >= 100 then
+        remove(key)
+        return
+    end
+    local label = text or id
+    local indicator = indicators[key]
+    if indicator then
+        indicator.label = label
+    else
+        indicators[key] = { label = label, task = tasks.get_current() }
+    end
+    pct = math.max(0, pct)
+    local filled = math.floor(pct / 100 * BAR_WIDTH + 0.5)
+    local bar = string.rep("█", filled) .. string.rep("░", BAR_WIDTH - filled)
+    blight.indicator(key, string.format("%s %s %d%%", label, bar, math.floor(pct)))
+end
+
+function mod.clear()
+    for key, _ in pairs(indicators) do
+        remove(key)
+    end
+end
+
+local function show_question()
+    local question = questions[1]
+    local masked = question ~= nil and question.masked == true
+    if masked ~= masking then
+        masking = masked
+        prompt.set_masked(masked)
+    end
+    if question then
+        prompt.set_label(C_YELLOW .. question.text .. " " .. C_RESET)
+    else
+        prompt.set_label()
+        prompt.set(draft or "")
+        draft = nil
+    end
+end
+
+local function enqueue(question)
+    questions[#questions + 1] = question
+    if #questions == 1 then
+        draft = prompt.get()
+        prompt.set("")
+        show_question()
+    end
+end
+
+function mod.ask(text, opts, callback)
+    if type(opts) == "function" then
+        callback, opts = opts, {}
+    end
+    opts = opts or {}
+    enqueue({ text = text, masked = opts.masked, callback = callback })
+end
+
+function mod.confirm(text, callback)
+    enqueue({ text = text .. " (y/n)", confirm = true, callback = callback })
+end
+
+local ANSWERS = { y = true, yes = true, n = false, no = false, [""] = false }
+
+mud.add_input_listener(function (line)
+    local question = questions[1]
+    if not question or line:source() ~= "user" then
+        return line
+    end
+    line:matched(true)
+    local answer = line:line()
+    if question.confirm then
+        answer = ANSWERS[answer:lower()]
+        if answer == nil then
+            return line
+        end
+    end
+    table.remove(questions, 1)
+    show_question()
+    question.callback(answer)
+    return line
+end)
+
+local function filter_menu()
+    menu.matches = fuzzy.filter(menu.filter, menu.items)
+    menu.selected = math.min(1, #menu.matches)
+    menu.offset = 0
+end
+
+local function draw_menu()
+    local lines = {}
+    if menu.title then
+        lines[#lines + 1] = C_BOLD .. menu.title .. C_RESET
+    end
+    lines[#lines + 1] = C_YELLOW .. "> " .. C_RESET .. menu.filter
+    if menu.selected > menu.offset + MENU_HEIGHT then
+        menu.offset = menu.selected - MENU_HEIGHT
+    elseif menu.selected > 0 and menu.selected <= menu.offset then
+        menu.offset = menu.selected - 1
+    end
+    for i = menu.offset + 1, math.min(menu.offset + MENU_HEIGHT, #menu.matches) do
+        local item = menu.items[menu.matches[i]]
+        if i == menu.selected then
+            lines[#lines + 1] = C_REVERSE .. item .. C_RESET
+        else
+            lines[#lines + 1] = item
+        end
+    end
+    if #menu.matches == 0 then
+        lines[#lines + 1] = C_BBLACK .. "No matches" .. C_RESET
+    end
+    layout.overlay(lines)
+    if tts.is_available() and menu.selected > 0 then
+        tts.speak(menu.items[menu.matches[menu.selected]], true)
+    end
+end
+
+local function close_menu()
+    local closed = menu
+    menu = nil
+    blight.remove_key_listener(closed.listener)
+    layout.overlay()
+    return closed
+end
+
+local MENU_MOVES = {
+    up = function () return -1 end,
+    down = function () return 1 end,
+    pageup = function () return -MENU_HEIGHT end,
+    pagedown = function () return MENU_HEIGHT end,
+    home = function () return -menu.selected end,
+    ["end"] = function () return #menu.matches end,
+}
+
+local function menu_key(event)
+    if event.ctrl and event.key == "c" then
+        return false
+    end
+    local move = MENU_MOVES[event.key]
+    if move and not event.ctrl and not event.alt then
+        if #menu.matches > 0 then
+            menu.selected = math.max(1, math.min(#menu.matches, menu.selected + move()))
+        end
+    elseif event.key == "enter" then
+        if menu.selected > 0 then
+            local closed = close_menu()
+            local index = closed.matches[closed.selected]
+            closed.callback(closed.items[index], index)
+        end
+        return true
+    elseif event.key == "escape" then
+        close_menu().callback(nil)
+        return true
+    elseif event.key == "backspace" then
+        menu.filter = menu.filter:gsub("[%z\1-\127\194-\244][\128-\191]*$", "")
+        filter_menu()
+    elseif not event.ctrl and not event.alt
+        and event.key:match("^[%z\1-\127\194-\244][\128-\191]*$") then
+        menu.filter = menu.filter .. event.key
+        filter_menu()
+    end
+    draw_menu()
+    return true
+end
+
+function mod.menu(items, callback, opts)
+    opts = opts or {}
+    if menu then
+        close_menu()
+    end
+    local strings = {}
+    for i, item in ipairs(items) do
+        strings[i] = tostring(item)
+    end
+    menu = {
+        items = strings,
+        callback = callback,
+        title = opts.title,
+        filter = "",
+    }
+    filter_menu()
+    menu.listener = blight.on_key(menu_key)
+    draw_menu()
+end
+
+function mod.is_menu_open()
+    return menu ~= nil
+end
+
+timer.on_tick(function ()
+    frame = frame % #FRAMES + 1
+    for key, indicator in pairs(indicators) do
+        local task = indicator.task
+        if task and task.dead then
+            remove(key)
+            if not task.success and task.error then
+                blight.output(C_RED .. indicator.label .. " failed: " .. tostring(task.error[1]) .. C_RESET)
+            end
+        elseif indicator.spinner then
+            draw_spinner(indicator.spinner)
+        end
+    end
+end)
+
+script.on_reset(function ()
+    mod.clear()
+    if menu then
+        close_menu()
+    end
+    if #questions > 0 then
+        questions = {}
+        show_question()
+    end
+end)
+
+return mod
@@ -0,0 +1,187 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::io::SaveData;
+
+/// How often a playing source checks whether speech has started or stopped.
+const DUCKING_PERIOD: Duration = Duration::from_millis(10);
+/// Pause after speech stops before the music is restored, so the volume doesn't pump between
+/// queued utterances.
+const RESTORE_DELAY: Duration = Duration::from_millis(300);
+
+/// How music is ducked while text-to-speech is speaking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuckingSettings {
+    pub enabled: bool,
+    /// The music volume while speaking, from 0.0 to 1.0.
+    pub level: f32,
+    /// Milliseconds to fade down to `level` once speech starts.
+    pub fade_out: u64,
+    /// Milliseconds to fade back to full volume once speech stops.
+    pub fade_in: u64,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: 0.3,
+            fade_out: 150,
+            fade_in: 800,
+        }
+    }
+}
+
+impl SaveData for DuckingSettings {
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("audio_ducking.ron")
+    }
+
+    fn on_load(&mut self) {
+        self.level = self.level.clamp(0.0, 1.0);
+    }
+}
+
+/// The volume of ducked sources, faded between full volume and the duck level as speech starts
+/// and stops.
+#[derive(Debug)]
+pub struct Ducker {
+    settings: DuckingSettings,
+    ducked: bool,
+    since: Instant,
+    from: f32,
+}
+
+impl Ducker {
+    pub fn new(settings: DuckingSettings) -> Self {
+        Self {
+            settings,
+            ducked: false,
+            since: Instant::now(),
+            from: 1.0,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: DuckingSettings, now: Instant) {
+        self.from = self.gain(now);
+        self.since = now;
+        self.settings = settings;
+    }
+
+    /// Starts fading down when speech starts, or back up a moment after it stops.
+    pub fn update(&mut self, speaking: bool, now: Instant) {
+        let duck = speaking && self.settings.enabled;
+        if duck != self.ducked {
+            self.from = self.gain(now);
+            self.ducked = duck;
+            self.since = if duck { now } else { now + RESTORE_DELAY };
+        }
+    }
+
+    /// The volume factor at `now`.
+    pub fn gain(&self, now: Instant) -> f32 {
+        let (target, fade) = if self.ducked {
+            (self.settings.level, self.settings.fade_out)
+        } else {
+            (1.0, self.settings.fade_in)
+        };
+        let fade = Duration::from_millis(fade);
+        let elapsed = now.saturating_duration_since(self.since);
+        if now < self.since {
+            self.from
+        } else if elapsed >= fade {
+            target
+        } else {
+            self.from + (target - self.from) * (elapsed.as_secs_f32() / fade.as_secs_f32())
+        }
+    }
+}
+
+/// Lowers the volume of `source` while `speaking` is set.
+pub fn ducked<S>(
+    source: S,
+    ducker: Arc<Mutex<Ducker>>,
+    speaking: Arc<AtomicBool>,
+) -> impl Source<Item = S::Item>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    source
+        .amplify(1.0)
+        .periodic_access(DUCKING_PERIOD, move |source| {
+            if let Ok(mut ducker) = ducker.lock() {
+                let now = Instant::now();
+                ducker.update(speaking.load(Ordering::Relaxed), now);
+                source.set_factor(ducker.gain(now));
+            }
+        })
+}
+
+#[cfg(test)]
+mod test_ducking {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_duck_and_restore() {
+        let start = Instant::now();
+        let mut ducker = Ducker::new(DuckingSettings {
+            enabled: true,
+            level: 0.2,
+            fade_out: 100,
+            fade_in: 400,
+        });
+        assert_eq!(ducker.gain(start), 1.0);
+
+        ducker.update(true, start);
+        assert_eq!(ducker.gain(start), 1.0);
+        assert!((ducker.gain(start + ms(50)) - 0.6).abs() < 0.001);
+        assert_eq!(ducker.gain(start + ms(100)), 0.2);
+
+        let stop = start + ms(1000);
+        ducker.update(false, stop);
+        assert_eq!(ducker.gain(stop + RESTORE_DELAY), 0.2);
+        assert!((ducker.gain(stop + RESTORE_DELAY + ms(200)) - 0.6).abs() < 0.001);
+        assert_eq!(ducker.gain(stop + RESTORE_DELAY + ms(400)), 1.0);
+    }
+
+    #[test]
+    fn test_speech_resuming_during_restore_delay() {
+        let start = Instant::now();
+        let mut ducker = Ducker::new(DuckingSettings::default());
+        ducker.update(true, start);
+        ducker.update(false, start + ms(1000));
+        ducker.update(true, start + ms(1100));
+        assert_eq!(ducker.gain(start + ms(1100)), 0.3);
+        assert_eq!(ducker.gain(start + ms(2000)), 0.3);
+    }
+
+    #[test]
+    fn test_disabled_ducking() {
+        let start = Instant::now();
+        let mut ducker = Ducker::new(DuckingSettings {
+            enabled: false,
+            ..DuckingSettings::default()
+        });
+        ducker.update(true, start);
+        assert_eq!(ducker.gain(start + ms(1000)), 1.0);
+    }
+}
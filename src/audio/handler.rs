@@ -9,6 +9,7 @@ pub fn handle_audio_event(event: Event, player: &mut Player) -> Result<()> {
         Event::StopMusic => player.stop_music(),
         Event::PlaySFX(path, options) => player.play_sfx(&path, options),
         Event::StopSFX => player.stop_sfx(),
+        Event::SetDucking(settings) => player.set_ducking(settings),
         _ => Err(BadEventRoutingError.into()),
     }
 }
@@ -1,3 +1,6 @@
-pub use self::{handler::handle_audio_event, player::Player, player::SourceOptions};
+pub use self::{
+    ducking::DuckingSettings, handler::handle_audio_event, player::Player, player::SourceOptions,
+};
+mod ducking;
 mod handler;
 mod player;
@@ -1,13 +1,24 @@
-use std::{fs::File, io::BufReader};
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::Instant,
+};
 
 use anyhow::Result;
 use rodio::{source::Source, Sink};
 
+use crate::io::SaveData;
+
+use super::ducking::{ducked, Ducker, DuckingSettings};
+
 pub struct Player {
     _stream: Option<rodio::OutputStream>,
     handle: Option<rodio::OutputStreamHandle>,
     music: Option<Sink>,
     sfx: Option<Sink>,
+    ducker: Arc<Mutex<Ducker>>,
+    speaking: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,7 +37,8 @@ impl Default for SourceOptions {
 }
 
 impl Player {
-    pub fn new() -> Self {
+    /// Creates a player that ducks the music while `speaking` is set.
+    pub fn new(speaking: Arc<AtomicBool>) -> Self {
         let mut music = None;
         let mut sfx = None;
         let mut stream = None;
@@ -43,6 +55,8 @@ impl Player {
             handle,
             music,
             sfx,
+            ducker: Arc::new(Mutex::new(Ducker::new(DuckingSettings::load()))),
+            speaking,
         }
     }
 
@@ -52,6 +66,8 @@ impl Player {
             handle: None,
             music: None,
             sfx: None,
+            ducker: Arc::new(Mutex::new(Ducker::new(DuckingSettings::default()))),
+            speaking: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -65,10 +81,12 @@ impl Player {
             let file = File::open(fpath)?;
             let source = rodio::Decoder::new(BufReader::new(file))?;
             let source = source.amplify(options.amplify);
+            let ducker = self.ducker.clone();
+            let speaking = self.speaking.clone();
             if options.repeat {
-                music.append(source.repeat_infinite());
+                music.append(ducked(source.repeat_infinite(), ducker, speaking));
             } else {
-                music.append(source);
+                music.append(ducked(source, ducker, speaking));
             }
             music.play();
         }
@@ -99,4 +117,12 @@ impl Player {
         self.sfx = None;
         Ok(())
     }
+
+    pub fn set_ducking(&mut self, settings: DuckingSettings) -> Result<()> {
+        settings.save();
+        if let Ok(mut ducker) = self.ducker.lock() {
+            ducker.set_settings(settings, Instant::now());
+        }
+        Ok(())
+    }
 }
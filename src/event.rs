@@ -1,18 +1,25 @@
-use crate::io::FSEvent;
-use crate::net::spawn_connect_thread;
-use crate::{audio::SourceOptions, model::Regex};
+use crate::io::{ExportFormat, ExportRange, FSEvent};
+use crate::net::{
+    spawn_connect_thread, spawn_connect_thread_with_retries, StreamDirection, STARTUP_RETRY_DELAYS,
+};
+use crate::tools::util::split_commands;
+use crate::{
+    audio::{DuckingSettings, SourceOptions},
+    model::Regex,
+};
 use crate::{
-    model::{Connection, Line, PromptMask},
-    net::{spawn_receive_thread, spawn_transmit_thread},
+    model::{ActivityBuffer, Connection, Line, PromptMask, Region, Widget},
+    net::{spawn_probe_thread, spawn_receive_thread, spawn_transmit_thread},
     session::Session,
     tts::TTSEvent,
-    ui::UserInterface,
+    ui::{PrintableCharsIterator, UserInterface},
     TelnetData,
 };
 use libmudtelnet::{bytes::Bytes, events::TelnetEvents};
 use log::debug;
 use std::sync::atomic::Ordering;
 use std::thread::JoinHandle;
+use std::time::Instant;
 use std::{
     error::Error,
     sync::mpsc::{channel, Receiver, Sender},
@@ -35,18 +42,29 @@ pub enum Event {
     ClearTags,
     ClearTimers,
     Connect(Connection),
+    /// Connects at startup, retrying while the server can't be reached.
+    ConnectWithRetries(Connection),
     Connected(u16),
     DisableProto(u8),
     Disconnect,
     DropTimedEvent(u32),
     EnableProto(u8),
+    Export(ExportRange, ExportFormat, Option<String>),
     Error(String),
+    Filter(Option<Regex>),
+    FilterContext,
+    FindAll(Regex, u32),
     FindBackward(Regex),
     FindForward(Regex),
+    Indicator(String, Option<String>),
     Info(String),
     LoadScript(String),
+    ReloadScript(String),
+    MaskInput(bool),
+    CharMode(bool),
     EvalScript(String),
     MudOutput(Line),
+    NextPage,
     Output(Line),
     PlayMusic(String, SourceOptions),
     PlaySFX(String, SourceOptions),
@@ -59,30 +77,54 @@ pub enum Event {
     QuitConfirmTimeout,
     Reconnect,
     Redraw,
+    RedrawPromptInput,
+    DrawRegions,
+    SetDucking(DuckingSettings),
+    SetRegion(Region),
+    RemoveRegion(String),
+    RegionLines(String, Vec<String>),
+    /// Brings up the unread output of a buffer, sent by `/next-active`.
+    ShowActivity(ActivityBuffer),
+    Overlay(Option<Vec<String>>),
+    SetWidget(Widget),
+    UpdateWidget(String, f64),
+    RemoveWidget(String),
     RemoveTag(String),
     RemoveTimer(u32),
     ResetScript,
     ScrollBottom,
     ScrollDown,
     ScrollLock(bool),
+    ScrollTo(usize),
     ScrollTop,
     ScrollUp,
+    ServerEcho(bool),
     ServerInput(Line),
     ServerSend(Bytes),
     SettingChanged(String, bool),
+    ReaderFiltersChanged,
+    OriginStylesChanged,
+    ScrollbackSettingsChanged,
+    ServersChanged,
     ShowHelp(String, bool),
+    RegisterHelp(String, String),
+    SearchHelp(String),
     Speak(String, bool),
     SpeakStop,
     StartLogging(String, bool),
     StatusAreaHeight(u16),
     StatusLine(usize, String),
     StopLogging,
+    StartRecording(Option<String>, Vec<Regex>),
+    StopRecording,
     StopMusic,
     StopSFX,
+    StreamFilter(StreamDirection, bool),
     TTSEnabled(bool),
     TTSEvent(TTSEvent),
     TimedEvent(u32),
     TimerTick(u128),
+    TogglePause,
     SetPromptInput(String),
     SetPromptCursorPos(usize),
     SetPromptMask(PromptMask),
@@ -91,8 +133,42 @@ pub enum Event {
     UserInputCursor(usize),
     FSEvent(FSEvent),
     FSMonitor(String),
+    /// Files changed in the directories watched for the `live_reload` setting.
+    ScriptsChanged(FSEvent),
     LuaError(String),
+    LuaWarning(String),
+    ShowErrors(Option<usize>),
+    ClearErrors,
+    TimestampFormat(String),
+}
+
+impl Event {
+    /// The name of the event's variant, eg. "MudOutput", without formatting its content.
+    pub fn name(&self) -> String {
+        /// Keeps the leading identifier written to it and aborts formatting after that.
+        struct NameWriter(String);
+
+        impl std::fmt::Write for NameWriter {
+            fn write_str(&mut self, s: &str) -> std::fmt::Result {
+                match s.find(|c: char| !c.is_alphanumeric() && c != '_') {
+                    Some(end) => {
+                        self.0.push_str(&s[..end]);
+                        Err(std::fmt::Error)
+                    }
+                    None => {
+                        self.0.push_str(s);
+                        Ok(())
+                    }
+                }
+            }
+        }
+
+        let mut writer = NameWriter(String::new());
+        let _ = std::fmt::write(&mut writer, format_args!("{self:?}"));
+        writer.0
+    }
 }
+
 use anyhow::Result as AResult;
 type Result = AResult<()>;
 
@@ -136,7 +212,18 @@ impl EventHandler {
         transmit_writer: &mut Option<Sender<TelnetData>>,
     ) -> Result {
         match event {
-            Event::ServerSend(data) => {
+            Event::ServerSend(mut data) => {
+                if self
+                    .session
+                    .stream_filters
+                    .is_active(StreamDirection::Outbound)
+                {
+                    if let Ok(script) = self.session.lua_script.lock() {
+                        data = Bytes::from(
+                            script.filter_stream(StreamDirection::Outbound, data.to_vec()),
+                        );
+                    }
+                }
                 debug!("Sending: {:?}", data);
                 if let Some(transmit_writer) = &transmit_writer {
                     transmit_writer.send(Some(data))?;
@@ -149,17 +236,50 @@ impl EventHandler {
                 if let Ok(script) = self.session.lua_script.lock() {
                     let mut output_buffer = self.session.output_buffer.lock().unwrap();
                     output_buffer.input_sent();
-                    script.on_mud_input(&mut line);
-                    if self.session.echo_input.load(Ordering::Relaxed) {
-                        screen.print_send(&line);
+                    let user = line.flags.source.as_deref() == Some("user");
+                    let masked = user && self.session.input_masked();
+                    if masked {
+                        line.flags.gag = true;
+                        line.flags.skip_log = true;
+                        line.flags.skip_history = true;
+                    } else if user {
+                        self.session.input_rules.lock().unwrap().prepare(&mut line);
                     }
-                    if let Ok(mut logger) = self.session.logger.lock() {
-                        logger.log_line("> ", &line)?;
-                    }
-                    if !line.flags.matched {
-                        if let Ok(mut parser) = self.session.telnet_parser.lock() {
-                            if let TelnetEvents::DataSend(buffer) = parser.send_text(line.line()) {
-                                self.session.main_writer.send(Event::ServerSend(buffer))?;
+                    let stacking = self.session.command_stacking.load(Ordering::Relaxed);
+                    let lines = match script.command_separator(stacking) {
+                        Some(separator) if user && !masked && !line.flags.literal => {
+                            split_commands(line.line(), &separator)
+                                .into_iter()
+                                .map(|cmd| {
+                                    let mut cmd_line = Line::from(cmd);
+                                    cmd_line.flags = line.flags.clone();
+                                    cmd_line
+                                })
+                                .collect()
+                        }
+                        _ => vec![line],
+                    };
+                    for mut line in lines {
+                        script.on_mud_input(&mut line);
+                        if self.session.echo_input.load(Ordering::Relaxed) {
+                            screen.print_send(&line);
+                            if let Some(content) = line.print_line() {
+                                self.record(&format!("\x1b[93m> {content}\x1b[39m"))?;
+                            }
+                        }
+                        if let Ok(mut logger) = self.session.logger.lock() {
+                            logger.log_line("> ", &line)?;
+                        }
+                        if !line.flags.matched && script.check_guard(&line) {
+                            if let Ok(mut parser) = self.session.telnet_parser.lock() {
+                                if let TelnetEvents::DataSend(buffer) =
+                                    parser.send_text(line.line())
+                                {
+                                    if self.session.connected() {
+                                        script.record_sent(&line);
+                                    }
+                                    self.session.main_writer.send(Event::ServerSend(buffer))?;
+                                }
                             }
                         }
                     }
@@ -174,11 +294,24 @@ impl EventHandler {
                 spawn_connect_thread(self.session.clone(), connection);
                 Ok(())
             }
+            Event::ConnectWithRetries(connection) => {
+                self.session.disconnect();
+                spawn_connect_thread_with_retries(
+                    self.session.clone(),
+                    connection,
+                    &STARTUP_RETRY_DELAYS,
+                );
+                Ok(())
+            }
             Event::Connected(id) => {
                 let (writer, reader): (Sender<TelnetData>, Receiver<TelnetData>) = channel();
+                self.session.capabilities.reset();
+                self.session.refresh_server_settings();
                 spawn_receive_thread(self.session.clone());
+                spawn_probe_thread(self.session.clone(), id);
                 spawn_transmit_thread(self.session.clone(), reader);
                 transmit_writer.replace(writer);
+                self.session.watchdog.start(Instant::now());
                 let host = self.session.host();
                 let port = self.session.port();
                 debug!("Connected to {}:{}", host, port);
@@ -192,6 +325,7 @@ impl EventHandler {
                 Ok(())
             }
             Event::Disconnect => {
+                self.session.watchdog.stop();
                 if self.session.connected() {
                     self.session.disconnect();
                     screen.print_info(&format!(
@@ -209,6 +343,10 @@ impl EventHandler {
                         });
                     }
                     transmit_writer.take();
+                    if self.session.server_echo.load(Ordering::Relaxed) {
+                        self.session.main_writer.send(Event::ServerEcho(false))?;
+                    }
+                    self.session.char_mode.store(false, Ordering::Relaxed);
                     screen.set_host("", 0)?;
                     screen.clear_tags()?;
                     screen.print_prompt(&Line::from(""));
@@ -247,11 +385,33 @@ impl EventHandler {
         Ok(())
     }
 
+    fn record(&self, line: &str) -> Result {
+        if let Ok(mut recorder) = self.session.recorder.lock() {
+            recorder.record(line)?;
+        }
+        Ok(())
+    }
+
+    /// Prints a line of output and adds it to any ongoing recording.
+    fn print_output(&self, screen: &mut Box<dyn UserInterface>, line: &Line) -> Result {
+        screen.print_output(line);
+        if let Some(content) = line.print_line() {
+            self.record(content)?;
+        }
+        Ok(())
+    }
+
     fn handle_logging(&self, event: Event) -> Result {
         match event {
             Event::MudOutput(line) | Event::Output(line) => self.log_line("", &line),
-            Event::Error(line) => self.log_str("[!!] ", &line),
-            Event::Info(line) => self.log_str("[**] ", &line),
+            Event::Error(line) => {
+                self.record(&format!("\x1b[31m[!!] {line}\x1b[39m"))?;
+                self.log_str("[!!] ", &line)
+            }
+            Event::Info(line) => {
+                self.record(&format!("[**] {line}"))?;
+                self.log_str("[**] ", &line)
+            }
             Event::Prompt(prompt) => {
                 self.log_line("", &prompt)?;
                 Ok(())
@@ -260,6 +420,46 @@ impl EventHandler {
         }
     }
 
+    /// Prints the prompt input between the prompt label and any dynamic prompt
+    /// segments registered from Lua.
+    fn print_prompt_input(&self, screen: &mut Box<dyn UserInterface>, input: &str, pos: usize) {
+        let masked;
+        let input = if self.session.input_masked() {
+            masked = "*".repeat(input.printable_chars().count());
+            masked.as_str()
+        } else {
+            input
+        };
+        let (label, segments) = if let Ok(script) = self.session.lua_script.lock() {
+            (script.prompt_label(), script.prompt_segments(input))
+        } else {
+            (String::new(), String::new())
+        };
+        if label.is_empty() && segments.is_empty() {
+            screen.print_prompt_input(input, pos);
+        } else {
+            let offset = label.as_str().printable_chars().count();
+            screen.print_prompt_input(
+                &format!("{label}\x1b[0m{input}\x1b[0m{segments}"),
+                pos + offset,
+            );
+        }
+    }
+
+    fn char_mode_changed(&self, screen: &mut Box<dyn UserInterface>, was_active: bool) -> Result {
+        let active = self.session.char_mode_active();
+        if active != was_active {
+            if active {
+                screen.add_tag("CHAR")?;
+                screen.print_info("Character mode enabled, keys are sent as they're typed");
+            } else {
+                screen.remove_tag("CHAR")?;
+                screen.print_info("Character mode disabled");
+            }
+        }
+        Ok(())
+    }
+
     pub fn handle_output_events(
         &self,
         event: Event,
@@ -270,25 +470,26 @@ impl EventHandler {
             Event::MudOutput(mut line) => {
                 if let Ok(script) = self.session.lua_script.lock() {
                     script.on_mud_output(&mut line);
-                    screen.print_output(&line);
-                    script.get_output_lines().iter().for_each(|l| {
-                        screen.print_output(l);
-                    });
+                    self.print_output(screen, &line)?;
+                    for l in script.get_output_lines() {
+                        self.print_output(screen, &l)?;
+                    }
                 }
                 Ok(())
             }
-            Event::Output(line) => {
-                screen.print_output(&line);
-                Ok(())
-            }
+            Event::Output(line) => self.print_output(screen, &line),
             Event::Prompt(mut prompt) => {
                 if let Ok(script) = self.session.lua_script.lock() {
                     script.on_mud_output(&mut prompt);
-                    script.get_output_lines().iter().for_each(|l| {
-                        screen.print_output(l);
-                    });
+                    for l in script.get_output_lines() {
+                        self.print_output(screen, &l)?;
+                    }
                 }
+                screen.acknowledge_sent()?;
                 screen.print_prompt(&prompt);
+                if let Some(content) = prompt.print_line().filter(|content| !content.is_empty()) {
+                    self.record(content)?;
+                }
                 Ok(())
             }
             Event::SetPromptMask(mask) => {
@@ -296,9 +497,10 @@ impl EventHandler {
                     let mut lua_ctx = self.session.lua_script.lock().unwrap();
                     let updated_mask_table = command_buffer.set_mask(mask);
                     lua_ctx.set_prompt_mask_content(updated_mask_table);
+                    drop(lua_ctx);
                     let mut prompt_input = self.session.prompt_input.lock().unwrap();
                     *prompt_input = command_buffer.get_masked_buffer();
-                    screen.print_prompt_input(&prompt_input, command_buffer.get_pos());
+                    self.print_prompt_input(screen, &prompt_input, command_buffer.get_pos());
                 }
                 Ok(())
             }
@@ -310,7 +512,7 @@ impl EventHandler {
                     }
                     let mut prompt_input = self.session.prompt_input.lock().unwrap();
                     *prompt_input = command_buffer.get_masked_buffer();
-                    screen.print_prompt_input(&prompt_input, command_buffer.get_pos());
+                    self.print_prompt_input(screen, &prompt_input, command_buffer.get_pos());
                 }
                 Ok(())
             }
@@ -320,12 +522,43 @@ impl EventHandler {
                 }
                 let mut prompt_input = self.session.prompt_input.lock().unwrap();
                 *prompt_input = input_buffer;
-                screen.print_prompt_input(&prompt_input, pos);
+                self.print_prompt_input(screen, &prompt_input, pos);
                 Ok(())
             }
             Event::UserInputCursor(pos) => {
                 let prompt_input = self.session.prompt_input.lock().unwrap();
-                screen.print_prompt_input(&prompt_input, pos);
+                self.print_prompt_input(screen, &prompt_input, pos);
+                Ok(())
+            }
+            Event::MaskInput(masked) => {
+                self.session.masked_input.store(masked, Ordering::Relaxed);
+                let pos = self.session.command_buffer.lock().unwrap().get_pos();
+                let prompt_input = self.session.prompt_input.lock().unwrap();
+                self.print_prompt_input(screen, &prompt_input, pos);
+                Ok(())
+            }
+            Event::CharMode(enabled) => {
+                let was_active = self.session.char_mode_active();
+                self.session.char_mode.store(enabled, Ordering::Relaxed);
+                self.char_mode_changed(screen, was_active)
+            }
+            Event::ServerEcho(echo) => {
+                self.session.server_echo.store(echo, Ordering::Relaxed);
+                if let Ok(mut script) = self.session.lua_script.lock() {
+                    script.on_echo_change(!echo);
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_output(l);
+                    });
+                }
+                let pos = self.session.command_buffer.lock().unwrap().get_pos();
+                let prompt_input = self.session.prompt_input.lock().unwrap();
+                self.print_prompt_input(screen, &prompt_input, pos);
+                Ok(())
+            }
+            Event::RedrawPromptInput => {
+                let pos = self.session.command_buffer.lock().unwrap().get_pos();
+                let prompt_input = self.session.prompt_input.lock().unwrap();
+                self.print_prompt_input(screen, &prompt_input, pos);
                 Ok(())
             }
             Event::Error(msg) => {
@@ -372,6 +605,26 @@ impl EventHandler {
                 screen.reset_scroll()?;
                 Ok(())
             }
+            Event::TogglePause => {
+                screen.toggle_pause()?;
+                Ok(())
+            }
+            Event::NextPage => {
+                screen.next_page()?;
+                Ok(())
+            }
+            Event::ScrollTo(row) => {
+                screen.scroll_to(row)?;
+                Ok(())
+            }
+            Event::Filter(pattern) => {
+                screen.set_filter(pattern)?;
+                Ok(())
+            }
+            Event::FilterContext => {
+                screen.filter_context()?;
+                Ok(())
+            }
             Event::FindForward(pattern) => {
                 screen.find_down(&pattern)?;
                 Ok(())
@@ -411,6 +664,13 @@ mod event_test {
 
     use super::*;
 
+    #[test]
+    fn test_event_name() {
+        assert_eq!(Event::Redraw.name(), "Redraw");
+        assert_eq!(Event::MudOutput(Line::from("hello")).name(), "MudOutput");
+        assert_eq!(Event::ShowErrors(Some(1)).name(), "ShowErrors");
+    }
+
     fn build_session() -> (Session, Receiver<Event>, Receiver<TimerEvent>) {
         let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
         let (timer_writer, timer_reader): (Sender<TimerEvent>, Receiver<TimerEvent>) = channel();
@@ -455,6 +715,7 @@ mod event_test {
         screen.expect_scroll_top().times(1).returning(|| Ok(()));
         screen.expect_scroll_down().times(1).returning(|| Ok(()));
         screen.expect_reset_scroll().times(1).returning(|| Ok(()));
+        screen.expect_toggle_pause().times(1).returning(|| Ok(()));
         screen
             .expect_scroll_lock()
             .times(1)
@@ -479,6 +740,9 @@ mod event_test {
         assert!(handler
             .handle_scroll_events(Event::ScrollBottom, &mut screen)
             .is_ok());
+        assert!(handler
+            .handle_scroll_events(Event::TogglePause, &mut screen)
+            .is_ok());
         assert!(handler
             .handle_scroll_events(Event::ScrollLock(true), &mut screen)
             .is_ok());
@@ -528,6 +792,10 @@ mod event_test {
             .times(2)
             .return_const(());
         screen.expect_print_prompt().times(1).return_const(());
+        screen
+            .expect_acknowledge_sent()
+            .times(1)
+            .returning(|| Ok(()));
         screen.expect_print_prompt_input().times(1).return_const(());
         screen.expect_print_error().times(1).return_const(());
         screen.expect_print_info().times(1).return_const(());
@@ -611,4 +879,117 @@ mod event_test {
         session.echo_input.store(false, Ordering::Relaxed);
         send_event();
     }
+
+    #[test]
+    fn test_masked_input() {
+        let (mut session, _reader, _) = build_session();
+
+        let mut logger = MockLogWriter::new();
+        logger
+            .expect_log_line()
+            .withf(|_, line: &Line| line.flags.skip_log && line.flags.skip_history)
+            .times(2)
+            .returning(|_, _| Ok(()));
+        session.logger = Arc::new(Mutex::new(logger));
+
+        let mut screen = MockUserInterface::new();
+        screen
+            .expect_print_send()
+            .withf(|line: &Line| line.line() == "pass;word" && line.flags.gag)
+            .times(2)
+            .return_const(());
+
+        let mut handler = EventHandler::from(&session);
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        let mut input_line = Line::from("pass;word");
+        input_line.flags.source = Some("user".to_string());
+
+        session.echo_input.store(true, Ordering::Relaxed);
+        session.command_stacking.store(true, Ordering::Relaxed);
+        session.masked_input.store(true, Ordering::Relaxed);
+        assert!(handler
+            .handle_server_events(
+                Event::ServerInput(input_line.clone()),
+                &mut screen,
+                &mut None
+            )
+            .is_ok());
+        session.masked_input.store(false, Ordering::Relaxed);
+        session.server_echo.store(true, Ordering::Relaxed);
+        assert!(handler
+            .handle_server_events(Event::ServerInput(input_line), &mut screen, &mut None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_char_mode() {
+        let (session, _reader, _) = build_session();
+        let handler = EventHandler::from(&session);
+
+        let mut screen = MockUserInterface::new();
+        screen
+            .expect_add_tag()
+            .with(eq("CHAR"))
+            .times(1)
+            .returning(|_| Ok(()));
+        screen
+            .expect_remove_tag()
+            .with(eq("CHAR"))
+            .times(1)
+            .returning(|_| Ok(()));
+        screen.expect_print_info().times(2).return_const(());
+        screen.expect_print_prompt_input().return_const(());
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+
+        let mut handle = |event: Event| {
+            assert!(handler.handle_output_events(event, &mut screen).is_ok());
+            session.char_mode_active()
+        };
+        assert!(!handle(Event::ServerEcho(true)));
+        assert!(handle(Event::CharMode(true)));
+        assert!(handle(Event::ServerEcho(false)));
+        assert!(!handle(Event::CharMode(false)));
+    }
+
+    #[test]
+    fn test_command_stacking() {
+        let (mut session, _reader, _) = build_session();
+
+        let mut logger = MockLogWriter::new();
+        logger.expect_log_line().times(4).returning(|_, _| Ok(()));
+        session.logger = Arc::new(Mutex::new(logger));
+
+        let mut screen = MockUserInterface::new();
+        for cmd in ["n", "e;w", "look"] {
+            screen
+                .expect_print_send()
+                .with(eq(Line::from(cmd)))
+                .times(1)
+                .return_const(());
+        }
+        screen
+            .expect_print_send()
+            .with(eq(Line::from("n;e\\;w;look")))
+            .times(1)
+            .return_const(());
+
+        let mut handler = EventHandler::from(&session);
+        let mut screen: Box<dyn UserInterface> = Box::new(screen);
+        let mut input_line = Line::from("n;e\\;w;look");
+        input_line.flags.source = Some("user".to_string());
+
+        session.echo_input.store(true, Ordering::Relaxed);
+        session.command_stacking.store(true, Ordering::Relaxed);
+        assert!(handler
+            .handle_server_events(
+                Event::ServerInput(input_line.clone()),
+                &mut screen,
+                &mut None
+            )
+            .is_ok());
+        session.command_stacking.store(false, Ordering::Relaxed);
+        assert!(handler
+            .handle_server_events(Event::ServerInput(input_line), &mut screen, &mut None)
+            .is_ok());
+    }
 }
@@ -0,0 +1,374 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::crypt;
+use crate::tools::util::expand_tilde;
+
+/// Backups start with this once they're decompressed.
+const MAGIC: &[u8] = b"BLIGHTMUD-BACKUP-1\n";
+
+/// Left out of backups as they grow large and aren't needed to move an install.
+const SKIPPED_DIRS: [&str; 4] = [
+    "data/logs",
+    "data/recordings",
+    "data/exports",
+    "data/backups",
+];
+
+/// The disk store, and the key in it holding the passwords saved by `login.set_credentials`.
+const STORE_PATH: &str = "data/store/data.ron";
+const CREDENTIALS_KEY: &str = "__login_credentials";
+
+/// The directories that are backed up, by the name their files are stored under.
+fn roots() -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("config", crate::CONFIG_DIR.clone()),
+        ("data", crate::DATA_DIR.clone()),
+    ]
+}
+
+/// What restoring a backup does to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupChange {
+    Added,
+    Changed,
+    Unchanged,
+}
+
+impl BackupChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Changed => "changed",
+            Self::Unchanged => "unchanged",
+        }
+    }
+}
+
+struct FileChange {
+    path: PathBuf,
+    data: Vec<u8>,
+    change: BackupChange,
+}
+
+/// The files of a backup, by their path below `config/` or `data/`.
+#[derive(Debug, Default, PartialEq)]
+pub struct BackupFiles {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl BackupFiles {
+    /// Reads the config and data directories. Login credentials are left out unless the store is
+    /// encrypted.
+    pub fn collect() -> Result<Self> {
+        Self::collect_from(&roots())
+    }
+
+    fn collect_from(roots: &[(&str, PathBuf)]) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for (name, dir) in roots {
+            if dir.is_dir() {
+                collect_dir(dir, name, &mut files)?;
+            }
+        }
+        if let Some(store) = files.get_mut(STORE_PATH) {
+            *store = without_credentials(store)?;
+        }
+        Ok(Self { files })
+    }
+
+    /// Writes the backup to `path`, or to a file named after the current time when `path` is a
+    /// directory or not given, and returns the path of the file.
+    pub fn write(&self, path: Option<&str>) -> Result<PathBuf> {
+        let path = match path {
+            Some(path) => PathBuf::from(expand_tilde(path).as_ref()),
+            None => {
+                let dir = crate::DATA_DIR.join("backups");
+                fs::create_dir_all(&dir)?;
+                dir
+            }
+        };
+        let path = if path.is_dir() {
+            path.join(
+                Local::now()
+                    .format("blightmud-%Y%m%d-%H%M%S.backup")
+                    .to_string(),
+            )
+        } else {
+            path
+        };
+        let file = fs::File::create(&path).with_context(|| format!("Unable to write {path:?}"))?;
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(MAGIC)?;
+        for (name, data) in &self.files {
+            for field in [name.as_bytes(), data] {
+                encoder.write_all(&(field.len() as u32).to_be_bytes())?;
+                encoder.write_all(field)?;
+            }
+        }
+        encoder.finish()?;
+        Ok(path)
+    }
+
+    pub fn read(path: &str) -> Result<Self> {
+        let path = expand_tilde(path);
+        let file =
+            fs::File::open(path.as_ref()).with_context(|| format!("Unable to read {path}"))?;
+        let mut data = vec![];
+        GzDecoder::new(file)
+            .read_to_end(&mut data)
+            .with_context(|| format!("{path} isn't a Blightmud backup"))?;
+        Self::decode(&data).with_context(|| format!("Unable to read {path}"))
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let Some(mut records) = data.strip_prefix(MAGIC) else {
+            bail!("Not a Blightmud backup");
+        };
+        let mut files = BTreeMap::new();
+        while !records.is_empty() {
+            let name = String::from_utf8(take_field(&mut records)?.to_vec())?;
+            if !is_valid_path(&name) {
+                bail!("Invalid path in the backup: {name}");
+            }
+            files.insert(name, take_field(&mut records)?.to_vec());
+        }
+        Ok(Self { files })
+    }
+
+    /// Compares the backup with the files on disk without changing anything.
+    pub fn diff(&self) -> Result<Vec<(String, BackupChange)>> {
+        Ok(self
+            .files
+            .keys()
+            .cloned()
+            .zip(self.changes(&roots())?)
+            .map(|(name, file)| (name, file.change))
+            .collect())
+    }
+
+    /// Writes the added and changed files, returning how many were written.
+    pub fn restore(&self) -> Result<usize> {
+        self.restore_to(&roots())
+    }
+
+    fn restore_to(&self, roots: &[(&str, PathBuf)]) -> Result<usize> {
+        let mut count = 0;
+        for file in self.changes(roots)? {
+            if file.change != BackupChange::Unchanged {
+                if let Some(parent) = file.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&file.path, file.data)
+                    .with_context(|| format!("Unable to write {:?}", file.path))?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The changes to the files of the backup, in the same order.
+    fn changes(&self, roots: &[(&str, PathBuf)]) -> Result<Vec<FileChange>> {
+        self.files
+            .iter()
+            .map(|(name, data)| {
+                let Some(path) = local_path(roots, name) else {
+                    bail!("Invalid path in the backup: {name}");
+                };
+                let data = if name == STORE_PATH {
+                    with_local_credentials(data.clone(), &path)?
+                } else {
+                    data.clone()
+                };
+                let change = match fs::read(&path) {
+                    Ok(local) if local == data => BackupChange::Unchanged,
+                    Ok(_) => BackupChange::Changed,
+                    Err(_) => BackupChange::Added,
+                };
+                Ok(FileChange { path, data, change })
+            })
+            .collect()
+    }
+}
+
+fn collect_dir(dir: &Path, prefix: &str, files: &mut BTreeMap<String, Vec<u8>>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{prefix}/{}", entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() && !SKIPPED_DIRS.contains(&name.as_str()) {
+            collect_dir(&path, &name, files)?;
+        } else if path.is_file() {
+            files.insert(name, fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+fn take_field<'a>(records: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if records.len() < 4 {
+        bail!("The backup is truncated");
+    }
+    let (len, rest) = records.split_at(4);
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if rest.len() < len {
+        bail!("The backup is truncated");
+    }
+    let (field, rest) = rest.split_at(len);
+    *records = rest;
+    Ok(field)
+}
+
+/// Backups only restore files below the config and data directories.
+fn is_valid_path(name: &str) -> bool {
+    let Some((root, rest)) = name.split_once('/') else {
+        return false;
+    };
+    matches!(root, "config" | "data")
+        && rest
+            .split('/')
+            .all(|part| !matches!(part, "" | "." | "..") && !part.contains('\\'))
+}
+
+fn local_path(roots: &[(&str, PathBuf)], name: &str) -> Option<PathBuf> {
+    let (root, rest) = name.split_once('/')?;
+    let (_, dir) = roots.iter().find(|(name, _)| *name == root)?;
+    Some(dir.join(rest))
+}
+
+fn without_credentials(store: &[u8]) -> Result<Vec<u8>> {
+    if crypt::is_encrypted(store) {
+        return Ok(store.to_vec());
+    }
+    let mut values: BTreeMap<String, String> = ron::de::from_bytes(store)?;
+    values.remove(CREDENTIALS_KEY);
+    Ok(ron::ser::to_string(&values)?.into_bytes())
+}
+
+/// Keeps the local login credentials when restoring a store that was backed up without them.
+fn with_local_credentials(store: Vec<u8>, local: &Path) -> Result<Vec<u8>> {
+    if crypt::is_encrypted(&store) {
+        return Ok(store);
+    }
+    let local: HashMap<String, String> = fs::read(local)
+        .map_err(anyhow::Error::from)
+        .and_then(crypt::decrypt)
+        .and_then(|local| Ok(ron::de::from_bytes(&local)?))
+        .unwrap_or_default();
+    let Some(credentials) = local.get(CREDENTIALS_KEY) else {
+        return Ok(store);
+    };
+    let mut values: BTreeMap<String, String> = ron::de::from_bytes(&store)?;
+    if values.contains_key(CREDENTIALS_KEY) {
+        return Ok(store);
+    }
+    values.insert(CREDENTIALS_KEY.to_string(), credentials.clone());
+    Ok(ron::ser::to_string(&values)?.into_bytes())
+}
+
+#[cfg(test)]
+mod test_backup {
+    use super::*;
+
+    fn store(values: &[(&str, &str)]) -> Vec<u8> {
+        let values: HashMap<&str, &str> = values.iter().copied().collect();
+        ron::ser::to_string(&values).unwrap().into_bytes()
+    }
+
+    fn read_store(path: &Path) -> HashMap<String, String> {
+        ron::de::from_bytes(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let root = std::env::temp_dir().join(format!("blightmud_backup_{}", std::process::id()));
+        let roots = vec![("config", root.join("config")), ("data", root.join("data"))];
+        for dir in ["config/scripts", "data/store", "data/logs/host"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("config/scripts/main.lua"), "print()").unwrap();
+        fs::write(root.join("config/settings.ron"), "()").unwrap();
+        fs::write(root.join("data/logs/host/log.log"), "log").unwrap();
+        fs::write(
+            root.join("data/store/data.ron"),
+            store(&[("hp", "10"), (CREDENTIALS_KEY, "secret")]),
+        )
+        .unwrap();
+
+        let backup = BackupFiles::collect_from(&roots).unwrap();
+        assert_eq!(
+            backup.files.keys().collect::<Vec<_>>(),
+            vec![
+                "config/scripts/main.lua",
+                "config/settings.ron",
+                "data/store/data.ron"
+            ]
+        );
+        assert_eq!(
+            ron::de::from_bytes::<HashMap<String, String>>(&backup.files[STORE_PATH]).unwrap(),
+            HashMap::from([("hp".to_string(), "10".to_string())])
+        );
+
+        let path = backup
+            .write(Some(root.to_str().unwrap()))
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(BackupFiles::read(&path).unwrap(), backup);
+
+        fs::write(root.join("config/scripts/main.lua"), "changed").unwrap();
+        fs::remove_file(root.join("config/settings.ron")).unwrap();
+        fs::write(
+            root.join("data/store/data.ron"),
+            store(&[(CREDENTIALS_KEY, "new")]),
+        )
+        .unwrap();
+        assert_eq!(backup.restore_to(&roots).unwrap(), 3);
+        assert_eq!(
+            fs::read_to_string(root.join("config/scripts/main.lua")).unwrap(),
+            "print()"
+        );
+        assert!(root.join("config/settings.ron").exists());
+        let restored = read_store(&root.join("data/store/data.ron"));
+        assert_eq!(restored["hp"], "10");
+        assert_eq!(restored[CREDENTIALS_KEY], "new");
+        let changes = backup.changes(&roots).unwrap();
+        assert!(changes
+            .iter()
+            .all(|file| file.change == BackupChange::Unchanged));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_backups() {
+        assert!(BackupFiles::decode(b"not a backup").is_err());
+        assert!(BackupFiles::decode(&[MAGIC, &[0, 0, 0, 9], b"config"].concat()).is_err());
+        let record = |name: &str| {
+            [
+                MAGIC,
+                &(name.len() as u32).to_be_bytes(),
+                name.as_bytes(),
+                &[0, 0, 0, 0],
+            ]
+            .concat()
+        };
+        assert!(BackupFiles::decode(&record("config/main.lua")).is_ok());
+        for name in [
+            "config",
+            "data/../../etc",
+            "/etc/passwd",
+            "cache/a",
+            "data//a",
+        ] {
+            assert!(BackupFiles::decode(&record(name)).is_err(), "{name}");
+        }
+    }
+}
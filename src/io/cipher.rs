@@ -0,0 +1,96 @@
+use std::num::NonZeroU32;
+
+use anyhow::{anyhow, bail, Result};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+
+/// ChaCha20-Poly1305 with a key derived from a passphrase with PBKDF2-HMAC-SHA256.
+pub struct Cipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl Cipher {
+    pub fn new(passphrase: &str, salt: &[u8], iterations: u32) -> Result<Self> {
+        let iterations =
+            NonZeroU32::new(iterations).ok_or_else(|| anyhow!("Invalid iteration count"))?;
+        let mut key = [0; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(|_| anyhow!("Invalid key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn random_bytes(len: usize) -> Result<Vec<u8>> {
+        let mut bytes = vec![0; len];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| anyhow!("Failed to generate random bytes"))?;
+        Ok(bytes)
+    }
+
+    /// Returns a random nonce followed by the encrypted data and its tag.
+    pub fn seal(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("Failed to generate a nonce"))?;
+        let mut sealed = data.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt"))?;
+        let mut out = nonce.to_vec();
+        out.append(&mut sealed);
+        Ok(out)
+    }
+
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            bail!("Encrypted data is truncated");
+        }
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+        let mut sealed = sealed.to_vec();
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .map_err(|_| anyhow!("Wrong passphrase or corrupt data"))?;
+        Ok(plain.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod test_cipher {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let salt = Cipher::random_bytes(16).unwrap();
+        let cipher = Cipher::new("hunter2", &salt, 10).unwrap();
+        let sealed = cipher.seal(b"secret").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..NONCE_LEN + 6], b"secret");
+        assert_ne!(sealed, cipher.seal(b"secret").unwrap());
+        assert_eq!(cipher.open(&sealed).unwrap(), b"secret");
+
+        let wrong = Cipher::new("hunter3", &salt, 10).unwrap();
+        assert!(wrong.open(&sealed).is_err());
+        assert!(cipher.open(&sealed[..NONCE_LEN]).is_err());
+        assert!(Cipher::new("hunter2", &salt, 0).is_err());
+    }
+}
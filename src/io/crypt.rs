@@ -0,0 +1,236 @@
+use std::{fs::File, io::Write, path::PathBuf};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "encryption")]
+use super::cipher::Cipher;
+use super::SaveData;
+
+/// Encrypted files start with this, so files written before encryption was turned on can still
+/// be read.
+const MAGIC: &[u8] = b"BLIGHTMUD-ENCRYPTED-1\n";
+
+/// Encrypted with the key to tell a wrong passphrase apart from corrupt files.
+#[cfg(feature = "encryption")]
+const CHECK: &[u8] = b"blightmud";
+
+#[cfg(feature = "encryption")]
+const SALT_LEN: usize = 16;
+
+#[cfg(feature = "encryption")]
+const ITERATIONS: u32 = 600_000;
+
+#[cfg(feature = "encryption")]
+static CIPHER: std::sync::OnceLock<Cipher> = std::sync::OnceLock::new();
+
+/// How the key is derived from the passphrase. The file exists while encryption is on.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    salt: Vec<u8>,
+    iterations: u32,
+    check: Vec<u8>,
+}
+
+impl SaveData for EncryptionConfig {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("encryption.ron")
+    }
+}
+
+/// Checks if the contents of a file, or a log, are encrypted.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Checks if the store, saved servers and logs are encrypted.
+pub fn is_enabled() -> bool {
+    EncryptionConfig::path().is_ok_and(|path| path.exists())
+}
+
+#[cfg(feature = "encryption")]
+pub fn is_unlocked() -> bool {
+    CIPHER.get().is_some()
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn is_unlocked() -> bool {
+    false
+}
+
+/// Derives the key from the passphrase, failing if it's the wrong one.
+#[cfg(feature = "encryption")]
+pub fn unlock(passphrase: &str) -> Result<()> {
+    let config = EncryptionConfig::try_load()?;
+    let cipher = Cipher::new(passphrase, &config.salt, config.iterations)?;
+    if cipher.open(&config.check).ok().as_deref() != Some(CHECK) {
+        bail!("Wrong passphrase");
+    }
+    if CIPHER.set(cipher).is_err() {
+        bail!("Encryption is already unlocked");
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn unlock(_passphrase: &str) -> Result<()> {
+    bail!("Blightmud was built without encryption, see `/help encryption`")
+}
+
+/// Turns encryption on with a new passphrase. Files are encrypted as they're saved.
+#[cfg(feature = "encryption")]
+pub fn enable(passphrase: &str) -> Result<()> {
+    if is_enabled() {
+        bail!("Encryption is already on");
+    }
+    let salt = Cipher::random_bytes(SALT_LEN)?;
+    let cipher = Cipher::new(passphrase, &salt, ITERATIONS)?;
+    let config = EncryptionConfig {
+        salt,
+        iterations: ITERATIONS,
+        check: cipher.seal(CHECK)?,
+    };
+    if CIPHER.set(cipher).is_err() {
+        bail!("Encryption is already unlocked");
+    }
+    config.save();
+    if !is_enabled() {
+        bail!("Failed to save {:?}", EncryptionConfig::relative_path());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn enable(_passphrase: &str) -> Result<()> {
+    bail!("Blightmud was built without encryption, see `/help encryption`")
+}
+
+/// Turns encryption off, files are written in plain text from then on. Needs to be unlocked
+/// first so the encrypted files can still be read.
+pub fn disable() -> Result<()> {
+    if !is_unlocked() {
+        bail!("Encryption isn't unlocked");
+    }
+    std::fs::remove_file(EncryptionConfig::path()?)?;
+    Ok(())
+}
+
+/// Encrypts the contents of a file when encryption is on. Refuses to write anything while it's
+/// locked so encrypted files aren't overwritten.
+#[cfg(feature = "encryption")]
+pub fn encrypt(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_enabled() {
+        return Ok(data);
+    }
+    let Some(cipher) = CIPHER.get() else {
+        bail!("Encryption is locked, restart Blightmud to enter the passphrase")
+    };
+    Ok([MAGIC, &cipher.seal(&data)?].concat())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(data: Vec<u8>) -> Result<Vec<u8>> {
+    if is_enabled() {
+        bail!("The files are encrypted but Blightmud was built without encryption")
+    }
+    Ok(data)
+}
+
+/// Decrypts the contents of a file, plain files are returned as they are.
+#[cfg(feature = "encryption")]
+pub fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
+    let Some(sealed) = data.strip_prefix(MAGIC) else {
+        return Ok(data);
+    };
+    let Some(cipher) = CIPHER.get() else {
+        bail!("The file is encrypted, restart Blightmud to enter the passphrase")
+    };
+    cipher.open(sealed)
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
+    if data.starts_with(MAGIC) {
+        bail!("The file is encrypted but Blightmud was built without encryption")
+    }
+    Ok(data)
+}
+
+/// Writes every chunk as a length prefixed record so logs are encrypted as they're written.
+#[cfg(feature = "encryption")]
+struct RecordWriter {
+    file: File,
+    cipher: &'static Cipher,
+}
+
+#[cfg(feature = "encryption")]
+impl Write for RecordWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let sealed = self.cipher.seal(buf).map_err(std::io::Error::other)?;
+        self.file.write_all(&(sealed.len() as u32).to_be_bytes())?;
+        self.file.write_all(&sealed)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Wraps a new log file so it's encrypted when encryption is on.
+#[cfg(feature = "encryption")]
+pub fn log_writer(mut file: File) -> Result<Box<dyn Write + Send>> {
+    if !is_enabled() {
+        return Ok(Box::new(file));
+    }
+    let Some(cipher) = CIPHER.get() else {
+        bail!("Encryption is locked, restart Blightmud to enter the passphrase")
+    };
+    file.write_all(MAGIC)?;
+    Ok(Box::new(RecordWriter { file, cipher }))
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn log_writer(file: File) -> Result<Box<dyn Write + Send>> {
+    if is_enabled() {
+        bail!("The files are encrypted but Blightmud was built without encryption")
+    }
+    Ok(Box::new(file))
+}
+
+/// Decrypts a log written by [log_writer], plain logs are returned as they are.
+pub fn decrypt_log(data: Vec<u8>) -> Result<Vec<u8>> {
+    let Some(mut records) = data.strip_prefix(MAGIC) else {
+        return Ok(data);
+    };
+    let mut out = vec![];
+    while !records.is_empty() {
+        if records.len() < 4 {
+            bail!("The log is truncated");
+        }
+        let (len, rest) = records.split_at(4);
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if rest.len() < len {
+            bail!("The log is truncated");
+        }
+        let (sealed, rest) = rest.split_at(len);
+        out.append(&mut decrypt([MAGIC, sealed].concat())?);
+        records = rest;
+    }
+    Ok(out)
+}
+
+#[cfg(all(test, not(feature = "encryption")))]
+mod test_crypt {
+    use super::*;
+
+    #[test]
+    fn test_without_encryption() {
+        assert!(!is_unlocked());
+        assert!(unlock("hunter2").is_err());
+        assert_eq!(encrypt(b"plain".to_vec()).unwrap(), b"plain");
+        assert_eq!(decrypt(b"plain".to_vec()).unwrap(), b"plain");
+        assert!(decrypt([MAGIC, b"sealed"].concat()).is_err());
+        assert_eq!(decrypt_log(b"log".to_vec()).unwrap(), b"log");
+    }
+}
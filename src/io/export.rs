@@ -0,0 +1,327 @@
+use anyhow::Result;
+use chrono::Local;
+use std::{fmt::Write as _, path::PathBuf, sync::Arc};
+
+use crate::model::Regex;
+
+const ANSI_COLORS: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// The part of the scrollback to export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportRange {
+    /// The last n lines.
+    Last(usize),
+    /// The lines from and to the given line numbers, counting from 1.
+    Lines(usize, usize),
+    /// The lines matching a pattern, ignoring colors.
+    Matching(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Ansi,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "html" => Some(Self::Html),
+            "ansi" => Some(Self::Ansi),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Ansi => "ans",
+        }
+    }
+}
+
+impl ExportRange {
+    pub fn select<'a>(&self, lines: &'a [Arc<str>]) -> Vec<&'a str> {
+        match self {
+            Self::Last(count) => lines[lines.len().saturating_sub(*count)..]
+                .iter()
+                .map(|line| &**line)
+                .collect(),
+            Self::Lines(from, to) => {
+                let from = from.saturating_sub(1).min(lines.len());
+                let to = (*to).clamp(from, lines.len());
+                lines[from..to].iter().map(|line| &**line).collect()
+            }
+            Self::Matching(pattern) => lines
+                .iter()
+                .filter(|line| {
+                    let clean = strip_ansi_escapes::strip_str(&***line);
+                    pattern.is_match(&clean)
+                })
+                .map(|line| &**line)
+                .collect(),
+        }
+    }
+}
+
+fn get_and_ensure_export_dir() -> PathBuf {
+    let path = crate::DATA_DIR.clone().join("exports");
+    std::fs::create_dir_all(&path).ok();
+    path
+}
+
+/// Writes the lines to `<name>.<ext>` in the exports directory, named after the current time if
+/// no name is given, and returns the path of the file.
+pub fn export(lines: &[&str], format: ExportFormat, name: Option<&str>) -> Result<PathBuf> {
+    let name = name.map_or_else(
+        || Local::now().format("%Y%m%d.%H:%M:%S").to_string(),
+        String::from,
+    );
+    let path = get_and_ensure_export_dir().join(format!("{name}.{}", format.extension()));
+    let content = match format {
+        ExportFormat::Html => render_html(lines),
+        ExportFormat::Ansi => {
+            let mut content = lines.join("\x1b[0m\n");
+            content.push_str("\x1b[0m\n");
+            content
+        }
+    };
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+#[derive(Default, Clone, PartialEq)]
+struct Style {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(fg) = &self.fg {
+            write!(css, "color:{fg};").ok();
+        }
+        if let Some(bg) = &self.bg {
+            write!(css, "background-color:{bg};").ok();
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split(';')
+            .map(|code| code.parse::<u16>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some(ANSI_COLORS[(code - 30) as usize].to_string()),
+                90..=97 => self.fg = Some(ANSI_COLORS[(code - 82) as usize].to_string()),
+                40..=47 => self.bg = Some(ANSI_COLORS[(code - 40) as usize].to_string()),
+                100..=107 => self.bg = Some(ANSI_COLORS[(code - 92) as usize].to_string()),
+                38 => self.fg = extended_color(&mut codes),
+                48 => self.bg = extended_color(&mut codes),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads a 256 color (`5;n`) or true color (`2;r;g;b`) parameter.
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<String> {
+    match codes.next()? {
+        5 => {
+            let index = codes.next()? as usize;
+            Some(match index {
+                0..=15 => ANSI_COLORS[index].to_string(),
+                16..=231 => {
+                    let level = |n: usize| if n == 0 { 0 } else { 55 + n * 40 };
+                    let index = index - 16;
+                    format!(
+                        "#{:02x}{:02x}{:02x}",
+                        level(index / 36),
+                        level(index / 6 % 6),
+                        level(index % 6)
+                    )
+                }
+                _ => {
+                    let gray = 8 + (index.min(255) - 232) * 10;
+                    format!("#{gray:02x}{gray:02x}{gray:02x}")
+                }
+            })
+        }
+        2 => {
+            let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+            Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                r.min(255),
+                g.min(255),
+                b.min(255)
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn escape_html(c: char, html: &mut String) {
+    match c {
+        '&' => html.push_str("&amp;"),
+        '<' => html.push_str("&lt;"),
+        '>' => html.push_str("&gt;"),
+        '"' => html.push_str("&quot;"),
+        c => html.push(c),
+    }
+}
+
+/// Closes the current span, if any, and opens one for `style` unless it's the default style.
+fn open_span(style: &Style, html: &mut String, open: &mut bool) {
+    if *open {
+        html.push_str("</span>");
+        *open = false;
+    }
+    let css = style.css();
+    if !css.is_empty() {
+        write!(html, "<span style=\"{css}\">").ok();
+        *open = true;
+    }
+}
+
+/// Renders a line with ANSI colors as HTML spans. Escape sequences other than colors are
+/// dropped.
+fn render_html_line(line: &str, style: &mut Style, html: &mut String) {
+    let mut open = false;
+    open_span(style, html, &mut open);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            escape_html(c, html);
+            continue;
+        }
+        if chars.peek() != Some(&'[') {
+            chars.next();
+            continue;
+        }
+        chars.next();
+        let mut params = String::new();
+        let mut command = None;
+        for c in chars.by_ref() {
+            if c.is_ascii_digit() || c == ';' {
+                params.push(c);
+            } else {
+                command = Some(c);
+                break;
+            }
+        }
+        if command == Some('m') {
+            let previous = style.clone();
+            style.apply(&params);
+            if *style != previous {
+                open_span(style, html, &mut open);
+            }
+        }
+    }
+    if open {
+        html.push_str("</span>");
+    }
+}
+
+/// Renders a single line with ANSI colors as HTML, eg. for the web frontend.
+#[cfg(feature = "web")]
+pub fn ansi_to_html(line: &str) -> String {
+    let mut html = String::new();
+    render_html_line(line, &mut Style::default(), &mut html);
+    html
+}
+
+fn render_html(lines: &[&str]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Blightmud export</title>\n</head>\n\
+         <body style=\"background-color:#000000;color:#e5e5e5;\">\n<pre style=\"font-family:monospace;\">\n",
+    );
+    let mut style = Style::default();
+    for line in lines {
+        render_html_line(line, &mut style, &mut html);
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod export_tests {
+
+    use super::*;
+
+    fn lines() -> Vec<Arc<str>> {
+        vec![
+            Arc::from("\x1b[31mBob\x1b[0m tells you 'hi'"),
+            Arc::from("You are hungry."),
+            Arc::from("\x1b[1;38;5;196mAlice\x1b[22m <waves>"),
+        ]
+    }
+
+    #[test]
+    fn test_select() {
+        let lines = lines();
+        assert_eq!(ExportRange::Last(1).select(&lines), vec![&*lines[2]]);
+        assert_eq!(ExportRange::Last(10).select(&lines).len(), 3);
+        assert_eq!(
+            ExportRange::Lines(2, 3).select(&lines),
+            vec![&*lines[1], &*lines[2]]
+        );
+        assert!(ExportRange::Lines(5, 10).select(&lines).is_empty());
+        let pattern = Regex::new("^Bob tells", None).unwrap();
+        assert_eq!(
+            ExportRange::Matching(pattern).select(&lines),
+            vec![&*lines[0]]
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        let lines = lines();
+        let lines: Vec<&str> = lines.iter().map(|line| &**line).collect();
+        let html = render_html(&lines);
+        assert!(html.contains(
+            "<span style=\"color:#cd0000;\">Bob</span> tells you 'hi'\nYou are hungry.\n"
+        ));
+        assert!(html.contains(
+            "<span style=\"color:#ff0000;font-weight:bold;\">Alice</span>\
+             <span style=\"color:#ff0000;\"> &lt;waves&gt;</span>\n"
+        ));
+    }
+
+    #[test]
+    fn test_export() {
+        let path = export(&["\x1b[32mgreen"], ExportFormat::Ansi, Some("test_export")).unwrap();
+        assert_eq!(path.extension().unwrap(), "ans");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\x1b[32mgreen\x1b[0m\n"
+        );
+        std::fs::remove_file(path).ok();
+    }
+}
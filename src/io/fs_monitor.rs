@@ -19,10 +19,17 @@ pub struct FSMonitor {
 
 impl FSMonitor {
     pub fn new(main_writer: Sender<Event>) -> Result<Self> {
-        let watcher = new_debouncer(Duration::from_secs(5), move |res: DebounceEventResult| {
-            main_writer
-                .send(Event::FSEvent(FSEvent::from(res)))
-                .unwrap();
+        Self::with_debounce(main_writer, Duration::from_secs(5), Event::FSEvent)
+    }
+
+    /// A monitor sending its changes as `event` once nothing has changed for `timeout`.
+    pub fn with_debounce(
+        main_writer: Sender<Event>,
+        timeout: Duration,
+        event: fn(FSEvent) -> Event,
+    ) -> Result<Self> {
+        let watcher = new_debouncer(timeout, move |res: DebounceEventResult| {
+            main_writer.send(event(FSEvent::from(res))).unwrap();
         })
         .unwrap();
 
@@ -34,6 +41,10 @@ impl FSMonitor {
             .watcher()
             .watch(p, notify::RecursiveMode::Recursive)
     }
+
+    pub fn unwatch(&mut self, p: &Path) -> notify::Result<()> {
+        self.watcher.watcher().unwatch(p)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -0,0 +1,260 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Number of recent entries kept for viewing in the client.
+const RECENT_ENTRIES: usize = 500;
+/// Module paths may be given without the crate, eg. "net" for "blightmud::net".
+const CRATE_PREFIX: &str = "blightmud::";
+
+lazy_static! {
+    static ref LOGGER: InternalLogger = InternalLogger::default();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {:5} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+/// Blightmud's own diagnostic log, as opposed to the session logs. Entries are written to the log
+/// file and kept in memory so they can be read without leaving the client. The level can be set
+/// for each module while running.
+struct InternalLogger {
+    state: Mutex<LoggerState>,
+}
+
+impl Default for InternalLogger {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(LoggerState {
+                default: LevelFilter::Info,
+                modules: BTreeMap::new(),
+                file: None,
+                recent: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+struct LoggerState {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+    file: Option<File>,
+    recent: VecDeque<LogEntry>,
+}
+
+impl LoggerState {
+    /// The level of the most specific module matching `target`.
+    fn level(&self, target: &str) -> LevelFilter {
+        let target = module_path(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module.as_str()
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level any module logs at.
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .values()
+            .copied()
+            .fold(self.default, |max, level| max.max(level))
+    }
+}
+
+fn module_path(module: &str) -> &str {
+    module.strip_prefix(CRATE_PREFIX).unwrap_or(module)
+}
+
+impl InternalLogger {
+    fn set_level(&self, module: Option<&str>, level: Option<LevelFilter>) -> LevelFilter {
+        let mut state = self.state.lock().unwrap();
+        match (module, level) {
+            (None, Some(level)) => state.default = level,
+            (None, None) => state.default = LevelFilter::Info,
+            (Some(module), Some(level)) => {
+                state.modules.insert(module_path(module).to_string(), level);
+            }
+            (Some(module), None) => {
+                state.modules.remove(module_path(module));
+            }
+        }
+        state.max_level()
+    }
+
+    fn levels(&self) -> (LevelFilter, Vec<(String, LevelFilter)>) {
+        let state = self.state.lock().unwrap();
+        (
+            state.default,
+            state
+                .modules
+                .iter()
+                .map(|(module, level)| (module.clone(), *level))
+                .collect(),
+        )
+    }
+
+    fn recent(&self, count: usize) -> Vec<LogEntry> {
+        let state = self.state.lock().unwrap();
+        let skip = state.recent.len().saturating_sub(count);
+        state.recent.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Log for InternalLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.state
+            .lock()
+            .is_ok_and(|state| metadata.level() <= state.level(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if record.level() > state.level(record.target()) {
+            return;
+        }
+        let entry = LogEntry {
+            time: Local::now().format("%H:%M:%S%.3f").to_string(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        if let Some(file) = &mut state.file {
+            let _ = writeln!(file, "{entry}");
+        }
+        if state.recent.len() >= RECENT_ENTRIES {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(entry);
+    }
+
+    fn flush(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(file) = &mut state.file {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Starts logging to `path` at `level`.
+pub fn start(path: &Path, level: LevelFilter) -> io::Result<()> {
+    let file = File::create(path)?;
+    {
+        let mut state = LOGGER.state.lock().unwrap();
+        state.file = Some(file);
+        state.default = level;
+    }
+    // The logger is only set once per process, later calls just point it at the new file.
+    let _ = log::set_logger(&*LOGGER);
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Sets the level of a module, or the default level if `module` is `None`. A `level` of `None`
+/// resets the module to the default level, or the default level to info.
+pub fn set_level(module: Option<&str>, level: Option<LevelFilter>) {
+    log::set_max_level(LOGGER.set_level(module, level));
+}
+
+/// The default level and the levels set for modules.
+pub fn levels() -> (LevelFilter, Vec<(String, LevelFilter)>) {
+    LOGGER.levels()
+}
+
+/// The last `count` entries, oldest first.
+pub fn recent(count: usize) -> Vec<LogEntry> {
+    LOGGER.recent(count)
+}
+
+#[cfg(test)]
+mod test_internal_log {
+    use super::*;
+
+    fn log(logger: &InternalLogger, target: &str, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .target(target)
+                .level(level)
+                .args(format_args!("{message}"))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn test_module_levels() {
+        let logger = InternalLogger::default();
+        assert_eq!(
+            logger.set_level(Some("net"), Some(LevelFilter::Trace)),
+            LevelFilter::Trace
+        );
+        logger.set_level(Some("blightmud::net::tls"), Some(LevelFilter::Warn));
+
+        log(
+            &logger,
+            "blightmud::net::mud_connection",
+            Level::Debug,
+            "connecting",
+        );
+        log(&logger, "blightmud::net::tls", Level::Info, "handshake");
+        log(&logger, "blightmud::network", Level::Debug, "other module");
+        log(&logger, "blightmud::lua", Level::Info, "loaded");
+        log(&logger, "rustls", Level::Debug, "dependency");
+
+        let messages: Vec<String> = logger.recent(10).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["connecting", "loaded"]);
+
+        assert_eq!(logger.set_level(Some("net"), None), LevelFilter::Info);
+        assert_eq!(
+            logger.set_level(None, Some(LevelFilter::Error)),
+            LevelFilter::Warn
+        );
+        let (default, modules) = logger.levels();
+        assert_eq!(default, LevelFilter::Error);
+        assert_eq!(modules, vec![("net::tls".to_string(), LevelFilter::Warn)]);
+    }
+
+    #[test]
+    fn test_recent_entries() {
+        let logger = InternalLogger::default();
+        for i in 0..RECENT_ENTRIES + 5 {
+            log(&logger, "blightmud", Level::Info, &i.to_string());
+        }
+        let recent = logger.recent(2);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[1].message, (RECENT_ENTRIES + 4).to_string());
+        assert_eq!(logger.recent(usize::MAX).len(), RECENT_ENTRIES);
+        assert!(recent[0]
+            .to_string()
+            .ends_with(&format!("INFO  blightmud: {}", RECENT_ENTRIES + 3)));
+    }
+}
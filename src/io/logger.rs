@@ -1,14 +1,15 @@
 use anyhow::Result;
 use chrono::{self, Local};
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::collections::VecDeque;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
 use strip_ansi_escapes::Writer as StripWriter;
 
 #[cfg(test)]
 use mockall::automock;
 
+use super::crypt;
 use crate::model::Line;
 
 #[cfg_attr(test, automock)]
@@ -19,15 +20,148 @@ pub trait LogWriter {
 
     fn log_line(&mut self, prefix: &str, line: &Line) -> Result<()>;
 
+    /// Prefixes logged lines with the time they were received, or stops doing so on `None`.
+    fn set_timestamp_format(&mut self, format: Option<String>);
+
+    /// Skips blocks of lines the server resends after reconnecting when enabled.
+    fn set_dedupe(&mut self, enabled: bool);
+
     fn stop_logging(&mut self) -> Result<()>;
 
     #[cfg(test)]
     fn is_logging(&self) -> bool;
 }
 
+/// How many logged lines are remembered to recognize replayed blocks.
+const DEDUPE_HISTORY: usize = 500;
+/// How many lines after logging starts may begin a replayed block.
+const DEDUPE_WINDOW: usize = 100;
+/// The fewest lines matching the history that are taken for a replay rather than chance.
+const MIN_REPLAYED_LINES: usize = 3;
+
+/// Finds blocks of lines, after logging starts again, that repeat lines logged before.
+#[derive(Default)]
+struct Dedupe {
+    /// Hashes of the last logged lines from the server, kept across reconnects.
+    history: VecDeque<u64>,
+    /// How many more lines may begin a replayed block.
+    window: usize,
+    /// Lines held back while they match the history.
+    pending: Vec<(u64, Arc<str>)>,
+    /// The positions in the history the last pending line matches.
+    matches: Vec<usize>,
+}
+
+impl Dedupe {
+    fn hash(content: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn restart(&mut self) {
+        self.window = if self.history.is_empty() {
+            0
+        } else {
+            DEDUPE_WINDOW
+        };
+    }
+
+    fn remember(&mut self, hash: u64) {
+        if self.history.len() == DEDUPE_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(hash);
+    }
+
+    /// Returns the lines to log, holding back lines that may be part of a replayed block.
+    fn filter(&mut self, hash: u64, line: Arc<str>) -> Vec<Arc<str>> {
+        if !self.pending.is_empty() {
+            let matches: Vec<usize> = self
+                .matches
+                .iter()
+                .map(|i| i + 1)
+                .filter(|i| self.history.get(*i) == Some(&hash))
+                .collect();
+            if !matches.is_empty() {
+                self.pending.push((hash, line));
+                self.matches = matches;
+                // A replay that reached the last logged line can't go on.
+                if self.pending.len() >= MIN_REPLAYED_LINES
+                    && self.matches.contains(&(self.history.len() - 1))
+                {
+                    self.pending.clear();
+                    self.window = 0;
+                }
+                return vec![];
+            }
+        }
+        let mut lines = self.resolve();
+        if self.window > 0 {
+            self.window -= 1;
+            self.matches = (0..self.history.len())
+                .filter(|i| self.history[*i] == hash)
+                .collect();
+            if !self.matches.is_empty() {
+                self.pending.push((hash, line));
+                return lines;
+            }
+        }
+        self.remember(hash);
+        lines.push(line);
+        lines
+    }
+
+    /// Drops the held back lines if there are enough of them to be a replay, otherwise returns
+    /// them to be logged.
+    fn resolve(&mut self) -> Vec<Arc<str>> {
+        let pending = std::mem::take(&mut self.pending);
+        if pending.len() >= MIN_REPLAYED_LINES {
+            self.window = 0;
+            return vec![];
+        }
+        pending
+            .into_iter()
+            .map(|(hash, line)| {
+                self.remember(hash);
+                line
+            })
+            .collect()
+    }
+}
+
 #[derive(Default)]
 pub struct Logger {
-    file: Option<BufWriter<StripWriter<File>>>,
+    file: Option<BufWriter<StripWriter<Box<dyn Write + Send>>>>,
+    timestamp_format: Option<String>,
+    dedupe: Option<Dedupe>,
+}
+
+impl Logger {
+    fn write_str(&mut self, line: &str) -> Result<()> {
+        if let Some(mut writer) = self.file.take() {
+            writer.write_all(line.as_bytes())?;
+            if !line.ends_with('\n') {
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            self.file = Some(writer);
+        }
+        Ok(())
+    }
+
+    /// Logs the lines held back by deduping, unless they turned out to be a replay.
+    fn resolve_pending(&mut self) -> Result<()> {
+        let lines = self
+            .dedupe
+            .as_mut()
+            .map(Dedupe::resolve)
+            .unwrap_or_default();
+        for line in lines {
+            self.write_str(&line)?;
+        }
+        Ok(())
+    }
 }
 
 fn get_and_ensure_log_dir(host: &str) -> std::path::PathBuf {
@@ -41,33 +175,68 @@ impl LogWriter for Logger {
         if self.file.is_none() {
             let path = get_and_ensure_log_dir(host);
 
-            let logfile = path.join(format!("{}.log", Local::now().format("%Y%m%d.%H:%M:%S")));
-            self.file = Some(BufWriter::new(StripWriter::new(File::create(logfile)?)));
+            let extension = if crypt::is_enabled() {
+                "log.enc"
+            } else {
+                "log"
+            };
+            let logfile = path.join(format!(
+                "{}.{extension}",
+                Local::now().format("%Y%m%d.%H:%M:%S")
+            ));
+            let writer = crypt::log_writer(std::fs::File::create(logfile)?)?;
+            self.file = Some(BufWriter::new(StripWriter::new(writer)));
+            if let Some(dedupe) = &mut self.dedupe {
+                dedupe.restart();
+            }
         }
         Ok(())
     }
 
     fn log_str(&mut self, line: &str) -> Result<()> {
-        if let Some(mut writer) = self.file.take() {
-            writer.write_all(line.as_bytes())?;
-            if !line.ends_with('\n') {
-                writer.write_all(b"\n")?;
+        self.resolve_pending()?;
+        self.write_str(line)
+    }
+
+    fn log_line(&mut self, prefix: &str, line: &Line) -> Result<()> {
+        let Some(content) = line.log_line() else {
+            return Ok(());
+        };
+        // Held back by deduping, shared with the line unless it's prefixed
+        let text: Arc<str> = match &self.timestamp_format {
+            Some(format) => {
+                let timestamp = line.format_timestamp(format);
+                Arc::from(format!("{timestamp} {prefix}{content}"))
             }
-            writer.flush()?;
-            self.file = Some(writer);
+            None if prefix.is_empty() => line.shared_clean_line(),
+            None => Arc::from(format!("{prefix}{content}")),
+        };
+        match &mut self.dedupe {
+            Some(dedupe) if prefix.is_empty() && self.file.is_some() => {
+                for line in dedupe.filter(Dedupe::hash(content), text) {
+                    self.write_str(&line)?;
+                }
+                Ok(())
+            }
+            _ => self.log_str(&text),
         }
-        Ok(())
     }
 
-    fn log_line(&mut self, prefix: &str, line: &Line) -> Result<()> {
-        if let Some(line) = line.log_line() {
-            self.log_str(&format!("{}{}", prefix, &line))
-        } else {
-            Ok(())
+    fn set_timestamp_format(&mut self, format: Option<String>) {
+        self.timestamp_format = format;
+    }
+
+    fn set_dedupe(&mut self, enabled: bool) {
+        if !enabled {
+            self.resolve_pending().ok();
+            self.dedupe = None;
+        } else if self.dedupe.is_none() {
+            self.dedupe = Some(Dedupe::default());
         }
     }
 
     fn stop_logging(&mut self) -> Result<()> {
+        self.resolve_pending()?;
         if let Some(mut writer) = self.file.take() {
             writer.flush()?;
         }
@@ -94,4 +263,76 @@ mod logger_tests {
         logger.stop_logging().unwrap();
         assert!(!logger.is_logging());
     }
+
+    fn filter_all(dedupe: &mut Dedupe, lines: &[&str]) -> Vec<String> {
+        let mut logged = vec![];
+        for line in lines {
+            logged.extend(dedupe.filter(Dedupe::hash(line), Arc::from(*line)));
+        }
+        logged.extend(dedupe.resolve());
+        logged.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let mut dedupe = Dedupe::default();
+        let session = ["Welcome!", "A room", "Exits: n", "> ", "You say hi", "> "];
+        assert_eq!(filter_all(&mut dedupe, &session), session);
+
+        // The last screen is replayed after a banner and followed by new output.
+        dedupe.restart();
+        assert_eq!(
+            filter_all(
+                &mut dedupe,
+                &[
+                    "Welcome back!",
+                    "Exits: n",
+                    "> ",
+                    "You say hi",
+                    "> ",
+                    "Bob arrives"
+                ]
+            ),
+            vec!["Welcome back!", "Bob arrives"]
+        );
+
+        // Short runs matching earlier lines are kept.
+        dedupe.restart();
+        assert_eq!(
+            filter_all(&mut dedupe, &["A room", "Exits: n", "Bob leaves"]),
+            vec!["A room", "Exits: n", "Bob leaves"]
+        );
+
+        // Only the first lines after logging starts again are checked.
+        for i in 0..DEDUPE_WINDOW {
+            dedupe.filter(Dedupe::hash(&i.to_string()), Arc::from(i.to_string()));
+        }
+        assert_eq!(
+            filter_all(&mut dedupe, &["A room", "Exits: n", "> "]),
+            vec!["A room", "Exits: n", "> "]
+        );
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let dir = get_and_ensure_log_dir("timestamps");
+        std::fs::remove_dir_all(&dir).ok();
+        let line = Line::from("You are hungry.");
+        let mut logger = Logger::default();
+        logger.start_logging("timestamps").unwrap();
+        logger.set_timestamp_format(Some("[%Y]".to_string()));
+        logger.log_line("", &line).unwrap();
+        logger.set_timestamp_format(None);
+        logger.log_line("", &line).unwrap();
+        logger.stop_logging().unwrap();
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(file.path()).unwrap(),
+            format!(
+                "{} You are hungry.\nYou are hungry.\n",
+                line.format_timestamp("[%Y]")
+            )
+        );
+    }
 }
@@ -1,11 +1,23 @@
+mod backup;
+#[cfg(feature = "encryption")]
+mod cipher;
+pub mod crypt;
 mod exec;
+mod export;
 mod fs_monitor;
+pub mod internal_log;
 pub mod logger;
+mod recorder;
 mod save;
 
+pub use backup::BackupFiles;
 pub use exec::exec;
+#[cfg(feature = "web")]
+pub use export::ansi_to_html;
+pub use export::{export, ExportFormat, ExportRange};
 pub use fs_monitor::{FSEvent, FSMonitor};
 pub use logger::{LogWriter, Logger};
+pub use recorder::Recorder;
 pub use save::SaveData;
 
 #[cfg(test)]
@@ -0,0 +1,130 @@
+use anyhow::{bail, Result};
+use chrono::Local;
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::Instant,
+};
+
+use crate::model::Regex;
+use crate::tools::util::is_valid_file_name;
+
+const SCRUBBED: &str = "****";
+
+/// Records the session as an asciicast v2 file that can be played back with asciinema.
+#[derive(Default)]
+pub struct Recorder {
+    file: Option<BufWriter<File>>,
+    path: Option<PathBuf>,
+    start: Option<Instant>,
+    scrub: Vec<Regex>,
+}
+
+fn get_and_ensure_recording_dir() -> PathBuf {
+    let path = crate::DATA_DIR.clone().join("recordings");
+    std::fs::create_dir_all(&path).ok();
+    path
+}
+
+impl Recorder {
+    /// Starts recording to `<name>.cast` in the recordings directory, named after the current
+    /// time if no name is given. Text matching any of the `scrub` patterns is left out of the
+    /// recording.
+    pub fn start(
+        &mut self,
+        name: Option<&str>,
+        dimensions: (u16, u16),
+        scrub: Vec<Regex>,
+    ) -> Result<PathBuf> {
+        if let Some(name) = name.filter(|name| !is_valid_file_name(name)) {
+            bail!("Invalid recording name: '{name}'");
+        }
+        self.stop()?;
+        let now = Local::now();
+        let name = name.map_or_else(|| now.format("%Y%m%d.%H:%M:%S").to_string(), String::from);
+        let path = get_and_ensure_recording_dir().join(format!("{name}.cast"));
+        let mut file = BufWriter::new(File::create(&path)?);
+        let header = json!({
+            "version": 2,
+            "width": dimensions.0,
+            "height": dimensions.1,
+            "timestamp": now.timestamp(),
+            "env": { "TERM": std::env::var("TERM").unwrap_or_default() },
+        });
+        writeln!(file, "{header}")?;
+        self.file = Some(file);
+        self.path = Some(path.clone());
+        self.start = Some(Instant::now());
+        self.scrub = scrub;
+        Ok(path)
+    }
+
+    /// Records a line of rendered output.
+    pub fn record(&mut self, line: &str) -> Result<()> {
+        if let (Some(file), Some(start)) = (&mut self.file, self.start) {
+            let mut line = line.to_string();
+            for re in &self.scrub {
+                line = re.replace_all(&line, SCRUBBED).to_string();
+            }
+            let time = start.elapsed().as_secs_f64();
+            writeln!(file, "{}", json!([time, "o", format!("{line}\r\n")]))?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Stops recording, returning the path of the finished recording.
+    pub fn stop(&mut self) -> Result<Option<PathBuf>> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        self.start = None;
+        self.scrub.clear();
+        Ok(self.path.take())
+    }
+
+    #[cfg(test)]
+    pub fn is_recording(&self) -> bool {
+        self.file.is_some()
+    }
+}
+
+#[cfg(test)]
+mod recorder_tests {
+
+    use super::*;
+
+    #[test]
+    fn test_recording() {
+        let mut recorder = Recorder::default();
+        assert!(!recorder.is_recording());
+        assert!(recorder.start(Some("../escape"), (80, 24), vec![]).is_err());
+        assert!(!recorder.is_recording());
+        let scrub = vec![Regex::new("hunter\\d", None).unwrap()];
+        let path = recorder
+            .start(Some("test_recording"), (80, 24), scrub)
+            .unwrap();
+        assert!(recorder.is_recording());
+        recorder.record("\x1b[31mYou are hungry.\x1b[0m").unwrap();
+        recorder.record("> my password is hunter2").unwrap();
+        assert_eq!(recorder.stop().unwrap(), Some(path.clone()));
+        assert!(!recorder.is_recording());
+        recorder.record("Not recorded").unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "\x1b[31mYou are hungry.\x1b[0m\r\n");
+        assert_eq!(lines[2][2], "> my password is ****\r\n");
+        std::fs::remove_file(path).ok();
+    }
+}
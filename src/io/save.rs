@@ -1,3 +1,4 @@
+use super::crypt;
 use crate::DATA_DIR;
 
 use anyhow::Result;
@@ -14,6 +15,11 @@ pub trait SaveData: DeserializeOwned + Serialize + Default {
         false
     }
 
+    /// Encrypted when encryption is on, see [crypt].
+    fn is_secret() -> bool {
+        false
+    }
+
     fn on_load(&mut self) {}
 
     fn path() -> Result<PathBuf> {
@@ -29,8 +35,8 @@ pub trait SaveData: DeserializeOwned + Serialize + Default {
     fn try_load() -> Result<Self> {
         let path = Self::path()?;
         if path.exists() {
-            let file = fs::File::open(&path)?;
-            let mut obj: Self = ron::de::from_reader(&file)?;
+            let data = crypt::decrypt(fs::read(&path)?)?;
+            let mut obj: Self = ron::de::from_bytes(&data)?;
             obj.on_load();
             Ok(obj)
         } else {
@@ -38,6 +44,14 @@ pub trait SaveData: DeserializeOwned + Serialize + Default {
         }
     }
 
+    /// Loads and saves the file again, eg. to encrypt it after encryption was turned on.
+    fn resave() -> Result<()> {
+        if Self::path()?.exists() {
+            Self::try_load()?.save();
+        }
+        Ok(())
+    }
+
     fn load() -> Self {
         Self::try_load()
             .map_err(|err| error!("Load data error: {}", err))
@@ -51,6 +65,11 @@ pub trait SaveData: DeserializeOwned + Serialize + Default {
             } else {
                 ron::ser::to_string(&self)?
             };
+            let contents = if Self::is_secret() {
+                crypt::encrypt(contents.into_bytes())?
+            } else {
+                contents.into_bytes()
+            };
             fs::write(Self::path()?, contents)?;
             Ok(())
         };
@@ -4,13 +4,25 @@ use lazy_static::lazy_static;
 use libmudtelnet::bytes::Bytes;
 use libmudtelnet::events::TelnetEvents;
 use log::{error, info};
+/// Lines of output, exposed for the benchmarks in `benches/`.
+#[doc(hidden)]
+pub use model::Line;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Instant;
 use std::{env, fs, thread, time};
 pub use tools::register_panic_hook;
 use ui::HelpHandler;
 
+#[cfg(all(feature = "lua54", feature = "luajit"))]
+compile_error!(
+    "The lua54 and luajit features can't be enabled together, build LuaJIT with `--no-default-features --features luajit`"
+);
+#[cfg(not(any(feature = "lua54", feature = "luajit")))]
+compile_error!("Either the lua54 or the luajit feature has to be enabled");
+
 mod audio;
 mod event;
 mod io;
@@ -24,17 +36,29 @@ mod tts;
 mod ui;
 
 use crate::event::{spawn_quit_confirm_timeout_thread, Event, QuitMethod};
-use crate::io::{FSMonitor, SaveData};
-use crate::model::{Servers, ECHO_INPUT, HIDE_TOPBAR, READER_MODE, SCROLL_SPLIT};
+use crate::io::{crypt, FSEvent, FSMonitor, SaveData};
+use crate::model::{
+    autoconnect_servers, ColorFilters, ErrorLog, OriginStyles, ReaderFilters, ScriptError,
+    ScriptOrder, ScrollbackSettings, Servers, Widgets, COLLAPSE_REPEATS, COMMAND_STACKING,
+    DEFAULT_TIMESTAMP_FORMAT, ECHO_INPUT, HIDE_TOPBAR, OUTPUT_SEPARATOR, PAGING, PENDING_ECHO,
+    PROMPT_SPACING, READER_MODE, SCROLL_SPLIT, TIMESTAMPS, TUI_RENDERER,
+};
 use crate::session::{Session, SessionBuilder};
 use crate::timer::{spawn_timer_thread, TimerEvent};
-use crate::tools::patch::migrate_v2_settings_and_servers;
+use crate::tools::patch::{migrate_dirs, migrate_v2_settings_and_servers};
 use crate::tools::util::expand_tilde;
 use crate::ui::{spawn_input_thread, UiWrapper, UserInterface};
 use event::EventHandler;
 use getopts::Matches;
-use model::{Connection, Settings, CONFIRM_QUIT, LOGGING_ENABLED, SAVE_HISTORY};
-use net::check_latest_version;
+use model::{
+    Connection, Settings, CONFIRM_QUIT, CONNECTION_WATCHDOG, INPUT_FIFO, LIVE_RELOAD,
+    LOGGING_ENABLED, LOG_DEDUPE, SAVE_HISTORY, STALL_RECONNECT,
+};
+use net::{
+    accepted_charset_is_utf8, check_latest_version, control_request, ControlSocket, InputFifo,
+    Watchdog, WatchdogStatus,
+};
+use serde_json::json;
 
 pub const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), env!("GIT_DESCRIBE"));
 pub const PROJECT_NAME: &str = env!("CARGO_PKG_NAME");
@@ -45,6 +69,20 @@ const XDG_DATA_DIR: &str = "~/.local/share/blightmud";
 #[cfg(all(not(test), not(debug_assertions)))]
 const XDG_CONFIG_DIR: &str = "~/.config/blightmud";
 
+#[cfg(all(not(test), not(debug_assertions)))]
+const XDG_CACHE_DIR: &str = "~/.cache/blightmud";
+
+/// The blightmud directory in the XDG base directory given by `var`, eg. `$XDG_CONFIG_HOME`,
+/// or `default` if it isn't set.
+#[cfg(all(not(test), not(debug_assertions)))]
+fn xdg_dir(var: &str, default: &str) -> PathBuf {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(PROJECT_NAME))
+        .unwrap_or_else(|| PathBuf::from(expand_tilde(default).as_ref()))
+}
+
 type TelnetData = Option<Bytes>;
 
 lazy_static! {
@@ -54,7 +92,7 @@ lazy_static! {
             let data_dir = if cfg!(target_os = "macos") && MACOS_DEPRECATED_DIR.exists() {
                 MACOS_DEPRECATED_DIR.to_path_buf()
             } else {
-                PathBuf::from(expand_tilde(XDG_DATA_DIR).as_ref())
+                xdg_dir("XDG_DATA_HOME", XDG_DATA_DIR)
             };
 
             let _ = std::fs::create_dir_all(&data_dir);
@@ -75,7 +113,7 @@ lazy_static! {
             let config_dir = if cfg!(target_os = "macos") && MACOS_DEPRECATED_DIR.exists() {
                 MACOS_DEPRECATED_DIR.to_path_buf()
             } else {
-                PathBuf::from(expand_tilde(XDG_CONFIG_DIR).as_ref())
+                xdg_dir("XDG_CONFIG_HOME", XDG_CONFIG_DIR)
             };
 
             let _ = std::fs::create_dir_all(&config_dir);
@@ -90,10 +128,49 @@ lazy_static! {
         #[cfg(all(not(test), debug_assertions))]
         PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".run/config")
     };
+    /// Holds what can be downloaded again, eg. plugins, and is left out of backups.
+    pub static ref CACHE_DIR: PathBuf = {
+        #[cfg(all(not(test), not(debug_assertions)))]
+        {
+            let cache_dir = xdg_dir("XDG_CACHE_HOME", XDG_CACHE_DIR);
+            let _ = std::fs::create_dir_all(&cache_dir);
+            cache_dir
+        }
+
+        #[cfg(test)]
+        {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".run/test/cache")
+        }
+
+        #[cfg(all(not(test), debug_assertions))]
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".run/cache")
+    };
     pub static ref MACOS_DEPRECATED_DIR: PathBuf =
         PathBuf::from(expand_tilde("~/Library/Application Support/blightmud").as_ref());
 }
 
+/// Directories that have moved since earlier releases, as `(from, to)`: the default XDG
+/// directories when `$XDG_CONFIG_HOME` or `$XDG_DATA_HOME` points elsewhere, and plugins which
+/// used to be kept with the data.
+fn moved_dirs() -> Vec<(PathBuf, PathBuf)> {
+    let plugins = (DATA_DIR.join("plugins"), CACHE_DIR.join("plugins"));
+    #[cfg(all(not(test), not(debug_assertions)))]
+    if !MACOS_DEPRECATED_DIR.exists() {
+        return vec![
+            (
+                PathBuf::from(expand_tilde(XDG_CONFIG_DIR).as_ref()),
+                CONFIG_DIR.clone(),
+            ),
+            (
+                PathBuf::from(expand_tilde(XDG_DATA_DIR).as_ref()),
+                DATA_DIR.clone(),
+            ),
+            plugins,
+        ];
+    }
+    vec![plugins]
+}
+
 fn register_terminal_resize_listener(session: Session) -> thread::JoinHandle<()> {
     let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGWINCH]).unwrap();
     let main_thread_writer = session.main_writer;
@@ -121,7 +198,7 @@ fn start_logging(log_level: log::LevelFilter) -> std::io::Result<()> {
 
     let logfile = logpath.join("log.txt");
 
-    simple_logging::log_to_file(logfile.to_str().unwrap(), log_level)?;
+    io::internal_log::start(&logfile, log_level)?;
 
     Ok(())
 }
@@ -140,29 +217,211 @@ pub struct RuntimeConfig {
     pub eval: Option<String>,
     pub integration_test: bool,
     pub no_update_check: bool,
+    /// Run after `script`, `eval`, `connect` and `world`, in order.
+    pub actions: Vec<StartupAction>,
+}
+
+/// Something to do once Blightmud has started, given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupAction {
+    /// Evaluates Lua code.
+    Eval(String),
+    /// Runs a line as if it was typed, eg. a macro like `/start_log`.
+    Command(String),
+    /// Connects to `HOST:PORT[:tls]`.
+    Connect(String),
+    /// Connects to a saved server.
+    World(String),
+}
+
+impl StartupAction {
+    fn connects(&self) -> bool {
+        matches!(self, Self::Connect(_) | Self::World(_))
+    }
+}
+
+impl RuntimeConfig {
+    /// Checks the servers to connect to, returning an error for the first invalid one.
+    pub fn validate(&self) -> Result<()> {
+        let actions = self.actions.iter().filter_map(|action| match action {
+            StartupAction::Connect(server) => Some(server),
+            _ => None,
+        });
+        match self
+            .connect
+            .iter()
+            .chain(actions)
+            .find(|server| parse_connect(server, self.tls, true).is_none())
+        {
+            Some(server) => bail!("Invalid server '{server}', expected HOST:PORT[:tls]"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a `HOST:PORT[:tls]` server. TLS is also used if `tls` is set, eg. by `--tls`.
+fn parse_connect(server: &str, tls: bool, verify_cert: bool) -> Option<Connection> {
+    let mut parts = server.split(':');
+    let host = parts.next().filter(|host| !host.is_empty())?;
+    let port = parts.next()?.parse().ok()?;
+    let tls = match parts.next() {
+        None => tls,
+        Some("tls") => true,
+        Some(_) => return None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Connection::new(host, port, tls, verify_cert))
 }
 
 impl From<Matches> for RuntimeConfig {
     fn from(matches: Matches) -> Self {
-        let world = matches.opt_get::<String>("world").ok().unwrap();
-        let connect = matches.opt_get::<String>("connect").ok().unwrap();
+        let mut actions = vec![];
+        for (name, action) in [
+            ("eval", StartupAction::Eval as fn(String) -> StartupAction),
+            ("command", StartupAction::Command),
+            ("connect", StartupAction::Connect),
+            ("world", StartupAction::World),
+        ] {
+            for (pos, value) in matches.opt_strs_pos(name) {
+                actions.push((pos, action(value)));
+            }
+        }
+        actions.sort_by_key(|(pos, _)| *pos);
         Self {
             reader_mode: matches.opt_present("reader-mode"),
             headless_mode: false,
             verbose: matches.opt_present("verbose"),
-            world,
+            world: None,
             use_tts: matches.opt_defined("tts") && matches.opt_present("tts"),
             tls: matches.opt_present("tls"),
             no_verify: matches.opt_present("no-verify"),
-            connect,
+            connect: None,
             script: None,
             eval: None,
             integration_test: false,
             no_update_check: matches.opt_present("no-update-check"),
+            actions: actions.into_iter().map(|(_, action)| action).collect(),
         }
     }
 }
 
+/// Writes the Lua API definitions for editor tooling (EmmyLua/LuaLS) to `blightmud.lua` in the
+/// provided directory and returns the path of the written file.
+pub fn dump_api(dir: &str) -> Result<PathBuf> {
+    let dir = PathBuf::from(expand_tilde(dir).as_ref());
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("blightmud.lua");
+    fs::write(&path, lua::api_stubs())?;
+    Ok(path)
+}
+
+/// Subcommands that control a running Blightmud through its control socket.
+pub const CONTROL_COMMANDS: [&str; 4] = ["send", "output", "status", "triggers"];
+
+/// Runs a control subcommand, eg. `blightmud send look`, and returns what to print.
+pub fn run_control_command(command: &str, args: &[String]) -> Result<String> {
+    let text = args.join(" ");
+    let result = match command {
+        "send" => control_request("send", json!({ "input": text }))?,
+        "output" => control_request("output", json!({ "text": text }))?,
+        "status" | "triggers" => control_request(command, json!({}))?,
+        _ => bail!("Unknown command: {command}"),
+    };
+    Ok(match result {
+        serde_json::Value::Bool(_) => String::new(),
+        result => serde_json::to_string_pretty(&result)?,
+    })
+}
+
+fn read_passphrase(prompt: &str) -> Result<String> {
+    use std::io::Write;
+    use termion::input::TermRead;
+
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{prompt}")?;
+    stdout.flush()?;
+    let passphrase = std::io::stdin().read_passwd(&mut stdout)?;
+    writeln!(stdout)?;
+    passphrase.ok_or_else(|| anyhow::anyhow!("No passphrase entered"))
+}
+
+/// Asks for the passphrase if the store, saved servers and logs are encrypted.
+pub fn unlock_encryption() -> Result<()> {
+    if !crypt::is_enabled() || crypt::is_unlocked() {
+        return Ok(());
+    }
+    let mut attempts = 1;
+    loop {
+        match crypt::unlock(&read_passphrase("Passphrase: ")?) {
+            Err(err) if attempts < 3 && err.to_string() == "Wrong passphrase" => {
+                eprintln!("{err}");
+                attempts += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Turns encryption on or off, asking for the passphrase, and rewrites the encrypted files.
+pub fn set_encryption(enable: bool) -> Result<String> {
+    if enable {
+        let passphrase = read_passphrase("New passphrase: ")?;
+        if passphrase.is_empty() {
+            bail!("The passphrase can't be empty");
+        }
+        if read_passphrase("Repeat the passphrase: ")? != passphrase {
+            bail!("The passphrases don't match");
+        }
+        crypt::enable(&passphrase)?;
+    } else {
+        if !crypt::is_enabled() {
+            bail!("Encryption is already off");
+        }
+        unlock_encryption()?;
+        crypt::disable()?;
+    }
+    Servers::resave()?;
+    lua::resave_disk_storage()?;
+    Ok(if enable {
+        "Encryption is on, logs written from now on are encrypted".to_string()
+    } else {
+        format!("Encryption is off, decrypted {} logs", decrypt_logs()?)
+    })
+}
+
+/// Decrypts the encrypted session logs, as they can't be read once encryption is off.
+fn decrypt_logs() -> Result<usize> {
+    let mut count = 0;
+    let Ok(hosts) = fs::read_dir(DATA_DIR.join("logs")) else {
+        return Ok(0);
+    };
+    for host in hosts.flatten().filter(|host| host.path().is_dir()) {
+        for log in fs::read_dir(host.path())?.flatten() {
+            let path = log.path();
+            if path.extension().is_some_and(|ext| ext == "enc") {
+                fs::write(
+                    path.with_extension(""),
+                    crypt::decrypt_log(fs::read(&path)?)?,
+                )?;
+                fs::remove_file(&path)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Reads a session log, decrypting it if it's encrypted.
+pub fn read_log(path: &str) -> Result<Vec<u8>> {
+    let data = fs::read(expand_tilde(path).as_ref())?;
+    if crypt::is_enabled() {
+        unlock_encryption()?;
+    }
+    crypt::decrypt_log(data)
+}
+
 pub fn start(rt: RuntimeConfig) -> Result<()> {
     let log_level = if rt.verbose {
         log::LevelFilter::Debug
@@ -175,6 +434,7 @@ pub fn start(rt: RuntimeConfig) -> Result<()> {
     }
 
     info!("Starting application");
+    ui::remove_stale_spill_files();
 
     let (main_writer, main_thread_read): (Sender<Event>, Receiver<Event>) = channel();
     let timer_writer = spawn_timer_thread(main_writer.clone());
@@ -196,6 +456,7 @@ pub fn start(rt: RuntimeConfig) -> Result<()> {
         .headless(rt.headless_mode)
         .save_history(settings.get(SAVE_HISTORY).unwrap())
         .echo_input(settings.get(ECHO_INPUT).unwrap())
+        .command_stacking(settings.get(COMMAND_STACKING).unwrap())
         .build();
 
     if let Err(error) = run(main_thread_read, session, rt) {
@@ -207,41 +468,349 @@ pub fn start(rt: RuntimeConfig) -> Result<()> {
     }
 }
 
-fn handle_config(main_writer: &Sender<Event>, rt: &RuntimeConfig) {
+fn handle_config(main_writer: &Sender<Event>, rt: &RuntimeConfig) -> VecDeque<StartupAction> {
     if let Some(path) = &rt.script {
         main_writer.send(Event::LoadScript(path.clone())).ok();
     }
     if let Some(script) = &rt.eval {
         main_writer.send(Event::EvalScript(script.clone())).ok();
     }
-    if let Some(connect) = &rt.connect {
-        let split: Vec<&str> = connect.split(':').collect();
-        let host = split[0];
-        let port: u16 = split[1].parse().unwrap();
-        let tls = rt.tls;
-        let no_verify = rt.no_verify;
-        main_writer
-            .send(Event::Connect(Connection::new(host, port, tls, !no_verify)))
-            .unwrap();
-    } else if let Some(world) = &rt.world {
-        let servers = Servers::try_load().expect("Error loading servers.ron");
-        if let Some(world) = servers.get(world) {
-            main_writer.send(Event::Connect(world.clone())).unwrap();
+    let connect = match (&rt.connect, &rt.world) {
+        (Some(server), _) => Some(StartupAction::Connect(server.clone())),
+        (None, Some(world)) => Some(StartupAction::World(world.clone())),
+        (None, None) => None,
+    };
+    let mut actions: VecDeque<StartupAction> =
+        connect.into_iter().chain(rt.actions.clone()).collect();
+    if !actions.iter().any(StartupAction::connects) && !rt.integration_test {
+        if let Some(world) = autoconnect_world(main_writer) {
+            actions.push_front(StartupAction::World(world));
         }
-    } else {
+    }
+    if !actions.iter().any(StartupAction::connects) {
         main_writer
             .send(Event::ShowHelp("welcome".to_string(), false))
             .unwrap();
     }
+    run_startup_actions(main_writer, &mut actions, rt);
+    actions
+}
+
+/// Picks the saved server flagged to connect at startup. Only one connection is supported at a
+/// time so the first by name is used and any others are reported.
+fn autoconnect_world(main_writer: &Sender<Event>) -> Option<String> {
+    let servers = Servers::try_load().ok()?;
+    let mut names = autoconnect_servers(&servers).into_iter();
+    let world = names.next()?;
+    let skipped: Vec<String> = names.collect();
+    if !skipped.is_empty() {
+        main_writer
+            .send(Event::Info(format!(
+                "Autoconnecting to {world}, skipping: {}",
+                skipped.join(", ")
+            )))
+            .unwrap();
+    }
+    Some(world)
+}
+
+/// Runs the startup actions up to and including the next connect. The rest are run once that
+/// connection is up, so eg. a `--command` after `--world` is sent to the world.
+fn run_startup_actions(
+    main_writer: &Sender<Event>,
+    actions: &mut VecDeque<StartupAction>,
+    rt: &RuntimeConfig,
+) {
+    while let Some(action) = actions.pop_front() {
+        let connects = action.connects();
+        let event = startup_event(action, rt);
+        let failed = matches!(event, Event::Error(_));
+        main_writer.send(event).unwrap();
+        if connects && !failed {
+            break;
+        }
+    }
+}
+
+fn startup_event(action: StartupAction, rt: &RuntimeConfig) -> Event {
+    match action {
+        StartupAction::Eval(script) => Event::EvalScript(script),
+        StartupAction::Command(command) => {
+            let mut line = model::Line::from(command);
+            line.flags.source = Some("user".to_string());
+            Event::ServerInput(line)
+        }
+        StartupAction::Connect(server) => match parse_connect(&server, rt.tls, !rt.no_verify) {
+            Some(connection) => Event::ConnectWithRetries(connection),
+            None => Event::Error(format!("Invalid server: {server}")),
+        },
+        StartupAction::World(world) => match Servers::try_load()
+            .ok()
+            .and_then(|servers| servers.get(&world).cloned())
+        {
+            Some(connection) => Event::ConnectWithRetries(connection),
+            None => Event::Error(format!("Unknown world: {world}")),
+        },
+    }
+}
+
+fn draw_regions(session: &Session, screen: &mut Box<dyn UserInterface>) -> Result<()> {
+    if let Ok(script) = session.lua_script.lock() {
+        for (name, lines) in script.draw_regions(screen.width()) {
+            screen.set_region_lines(&name, lines)?;
+        }
+        script.get_output_lines().iter().for_each(|l| {
+            screen.print_output(l);
+        });
+    }
+    Ok(())
+}
+
+fn draw_widgets(
+    widgets: &Widgets,
+    regions: &[String],
+    screen: &mut Box<dyn UserInterface>,
+) -> Result<()> {
+    let now = time::Instant::now();
+    for region in regions {
+        screen.set_region_lines(region, widgets.lines(region, screen.width(), now))?;
+    }
+    Ok(())
+}
+
+fn print_script_error(error: &ScriptError, screen: &mut Box<dyn UserInterface>) {
+    if error.count > 1 {
+        screen.print_error(&format!(
+            "[Lua] Script error ({}x): {} (see /errors)",
+            error.count, error.message
+        ));
+    } else {
+        match &error.source {
+            Some(source) => screen.print_error(&format!("[Lua] Script error in {source}:")),
+            None => screen.print_error("[Lua] Script error:"),
+        }
+        screen.print_error(&format!("\t{}", error.message));
+        for line in &error.trace {
+            screen.print_error(&format!("\t{line}"));
+        }
+    }
+}
+
+/// Shows timestamps on screen and in logs in `format` if the `timestamps` setting is enabled.
+fn apply_timestamps(
+    session: &Session,
+    screen: &mut Box<dyn UserInterface>,
+    format: &str,
+) -> Result<()> {
+    let format = Settings::load()
+        .get(TIMESTAMPS)?
+        .then(|| format.to_string());
+    if let Ok(mut logger) = session.logger.lock() {
+        logger.set_timestamp_format(format.clone());
+    }
+    screen.set_timestamp_format(format);
+    Ok(())
+}
+
+/// Skips blocks the server resends after reconnecting in logs if the `log_dedupe` setting is
+/// enabled.
+fn apply_log_dedupe(session: &Session) -> Result<()> {
+    let enabled = Settings::load().get(LOG_DEDUPE)?;
+    if let Ok(mut logger) = session.logger.lock() {
+        logger.set_dedupe(enabled);
+    }
+    Ok(())
+}
+
+/// Watches for the server to stop responding if the `connection_watchdog` setting is enabled.
+fn apply_connection_watchdog(session: &Session) -> Result<()> {
+    session
+        .watchdog
+        .set_enabled(Settings::load().get(CONNECTION_WATCHDOG)?);
+    Ok(())
+}
+
+/// How long a changed script has to stay unchanged before `live_reload` reloads it.
+const LIVE_RELOAD_DEBOUNCE: time::Duration = time::Duration::from_millis(500);
+
+/// Watches the config directory for changed scripts while `live_reload` is on.
+fn apply_live_reload(monitor: &mut FSMonitor, screen: &mut Box<dyn UserInterface>) -> Result<()> {
+    if Settings::load().get(LIVE_RELOAD)? {
+        if let Err(err) = monitor.watch(CONFIG_DIR.as_path()) {
+            screen.print_error(&format!("Failed to watch scripts for live_reload: {err}"));
+        }
+    } else {
+        monitor.unwatch(CONFIG_DIR.as_path()).ok();
+    }
+    Ok(())
+}
+
+/// Opens or closes the input pipe to follow the `input_fifo` setting.
+fn apply_input_fifo(fifo: &mut Option<InputFifo>, session: &Session) -> Result<()> {
+    if Settings::load().get(INPUT_FIFO)? {
+        if fifo.is_none() {
+            *fifo = InputFifo::start(session.main_writer.clone());
+        }
+    } else {
+        fifo.take();
+    }
+    Ok(())
+}
+
+/// Reloads a script with [lua::LuaScript::reload_script] and shows how it went.
+fn reload_script(path: &str, session: &Session, screen: &mut Box<dyn UserInterface>) {
+    let mut lua = session.lua_script.lock().unwrap();
+    if let Err(err) = lua.reload_script(path) {
+        screen.print_error(&format!("Failed to reload file: {err}"));
+    } else {
+        screen.print_info(&format!("Reloaded script: {path}"));
+        lua.get_output_lines().iter().for_each(|l| {
+            screen.print_output(l);
+        });
+    }
+}
+
+/// Acts on what the connection watchdog found, see `/help settings`.
+fn handle_watchdog(
+    status: WatchdogStatus,
+    session: &Session,
+    screen: &mut Box<dyn UserInterface>,
+) -> Result<()> {
+    match status {
+        WatchdogStatus::Probe => {
+            session
+                .main_writer
+                .send(Event::ServerSend(Bytes::from_static(&Watchdog::PROBE)))?;
+        }
+        WatchdogStatus::Stalled(seconds) => {
+            screen.add_tag("STALLED")?;
+            screen.print_error(&format!(
+                "The server hasn't responded for {seconds} seconds"
+            ));
+            if let Ok(mut script) = session.lua_script.lock() {
+                script.on_stall(seconds);
+                script.get_output_lines().iter().for_each(|l| {
+                    screen.print_output(l);
+                });
+            }
+            if Settings::load().get(STALL_RECONNECT)? {
+                session.main_writer.send(Event::Disconnect)?;
+                session.main_writer.send(Event::Reconnect)?;
+            }
+        }
+        WatchdogStatus::Recovered => {
+            screen.remove_tag("STALLED")?;
+            screen.print_info("The server is responding again");
+        }
+    }
+    Ok(())
+}
+
+/// Applies the settings controlling how output lines are laid out in the main view.
+fn apply_output_settings(screen: &mut Box<dyn UserInterface>) -> Result<()> {
+    let settings = Settings::load();
+    screen.set_line_spacing(
+        settings.get(PROMPT_SPACING)?,
+        settings.get(OUTPUT_SEPARATOR)?,
+    );
+    screen.set_collapse_repeats(settings.get(COLLAPSE_REPEATS)?);
+    screen.set_pending_echo(settings.get(PENDING_ECHO)?);
+    screen.set_paging(settings.get(PAGING)?);
+    Ok(())
+}
+
+/// Transforms output colors with the color filter of the saved server connected to, or the
+/// general one.
+fn apply_color_filter(session: &Session, screen: &mut Box<dyn UserInterface>) {
+    let filter = ColorFilters::load().get(session.server_name().as_deref());
+    screen.set_color_filter(filter);
+}
+
+/// Marks or hides output by where it comes from.
+fn apply_origin_styles(screen: &mut Box<dyn UserInterface>) {
+    screen.set_origin_styles(OriginStyles::load());
+}
+
+fn apply_scrollback_settings(screen: &mut Box<dyn UserInterface>) {
+    screen.set_scrollback_settings(&ScrollbackSettings::load());
+}
+
+/// Filters output for screen readers if the `reader_mode` setting is enabled.
+fn apply_reader_filters(screen: &mut Box<dyn UserInterface>) -> Result<()> {
+    let filters = Settings::load().get(READER_MODE)?.then(ReaderFilters::load);
+    screen.set_reader_filters(filters);
+    Ok(())
+}
+
+fn show_errors(error_log: &ErrorLog, index: Option<usize>, screen: &mut Box<dyn UserInterface>) {
+    if let Some(index) = index {
+        match error_log.get(index.saturating_sub(1)) {
+            Some(error) => {
+                let mut error = error.clone();
+                error.count = 1;
+                print_script_error(&error, screen);
+            }
+            None => screen.print_error(&format!("No script error with index {index}")),
+        }
+    } else if error_log.is_empty() {
+        screen.print_info("No script errors");
+    } else {
+        screen.print_info("Script errors:");
+        for (i, error) in error_log.iter().enumerate() {
+            let source = error
+                .source
+                .as_ref()
+                .map(|source| format!(" [{source}]"))
+                .unwrap_or_default();
+            let kind = if error.warning { "warning: " } else { "" };
+            screen.print_info(&format!(
+                "{:>3}: ({}x) {}{}{}",
+                i + 1,
+                error.count,
+                kind,
+                error.message,
+                source
+            ));
+        }
+    }
+}
+
+/// The paths of the scripts in the config dir, in the order they're loaded.
+fn config_scripts(main_writer: &Sender<Event>, rt: &RuntimeConfig) -> Vec<String> {
+    if rt.integration_test {
+        return vec![];
+    }
+    let scripts = ScriptOrder::scripts_in(CONFIG_DIR.as_path());
+    ScriptOrder::load()
+        .resolve(&scripts)
+        .unwrap_or_else(|err| {
+            main_writer
+                .send(Event::Error(format!("Script order: {err}")))
+                .unwrap();
+            scripts
+        })
+        .into_iter()
+        .map(|script| CONFIG_DIR.join(script).to_str().unwrap().to_string())
+        .collect()
+}
+
+/// Takes the next event off the main thread's channel. Events already waiting are moved to `queue`
+/// so its length tells how far behind the main thread is.
+fn next_event(reader: &Receiver<Event>, queue: &mut VecDeque<Event>) -> Option<Event> {
+    if queue.is_empty() {
+        queue.push_back(reader.recv().ok()?);
+    }
+    queue.extend(reader.try_iter());
+    queue.pop_front()
 }
 
 fn run(main_thread_read: Receiver<Event>, mut session: Session, rt: RuntimeConfig) -> Result<()> {
     let mut transmit_writer: Option<Sender<TelnetData>> = None;
-    let help_handler = HelpHandler::new(session.main_writer.clone());
+    let mut help_handler = HelpHandler::new(session.main_writer.clone());
     let mut event_handler = EventHandler::from(&session);
 
     let mut player = if !rt.integration_test {
-        Player::new()
+        Player::new(session.tts_ctrl.lock().unwrap().speaking())
     } else {
         Player::disabled()
     };
@@ -253,37 +822,40 @@ fn run(main_thread_read: Receiver<Event>, mut session: Session, rt: RuntimeConfi
     };
 
     let mut fs_monitor = FSMonitor::new(session.main_writer.clone())?;
+    let mut reload_monitor = FSMonitor::with_debounce(
+        session.main_writer.clone(),
+        LIVE_RELOAD_DEBOUNCE,
+        Event::ScriptsChanged,
+    )?;
+    let mut widgets = Widgets::default();
+    let mut error_log = ErrorLog::default();
+    let mut timestamp_format = DEFAULT_TIMESTAMP_FORMAT.to_string();
 
     screen.setup()?;
+    apply_timestamps(&session, &mut screen, &timestamp_format)?;
+    apply_log_dedupe(&session)?;
+    apply_connection_watchdog(&session)?;
+    apply_live_reload(&mut reload_monitor, &mut screen)?;
+    apply_output_settings(&mut screen)?;
+    apply_reader_filters(&mut screen)?;
+    apply_color_filter(&session, &mut screen);
+    apply_origin_styles(&mut screen);
+    apply_scrollback_settings(&mut screen);
 
     let _ = spawn_input_thread(session.clone());
     let _ = register_terminal_resize_listener(session.clone());
-
-    let lua_scripts = if !rt.integration_test {
-        fs::read_dir(CONFIG_DIR.as_path())?
-            .filter_map(|entry| match entry {
-                Ok(file) => {
-                    if let Ok(filename) = file.file_name().into_string() {
-                        if filename.ends_with(".lua") {
-                            Some(file.path())
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            })
-            .collect::<Vec<PathBuf>>()
+    let _control_socket = if !rt.integration_test {
+        ControlSocket::start(session.clone())
     } else {
-        vec![]
+        None
     };
+    let mut input_fifo = None;
+    if !rt.integration_test {
+        apply_input_fifo(&mut input_fifo, &session)?;
+    }
 
-    for script in lua_scripts {
-        session
-            .main_writer
-            .send(Event::LoadScript(script.to_str().unwrap().to_string()))?;
+    for script in config_scripts(&session.main_writer, &rt) {
+        session.main_writer.send(Event::LoadScript(script))?;
     }
 
     if !rt.no_update_check {
@@ -294,6 +866,7 @@ fn run(main_thread_read: Receiver<Event>, mut session: Session, rt: RuntimeConfi
 
     if cfg!(not(debug_assertions)) {
         migrate_v2_settings_and_servers(session.main_writer.clone());
+        migrate_dirs(&moved_dirs(), session.main_writer.clone());
     }
 
     #[cfg(all(not(debug_assertions), target_os = "macos"))]
@@ -312,11 +885,20 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
         }
     }
 
-    handle_config(&session.main_writer, &rt);
+    let mut startup_actions = handle_config(&session.main_writer, &rt);
 
     let mut quit_pending = false;
     let mut quit_error: Option<String> = None;
-    while let Ok(event) = main_thread_read.recv() {
+    let mut queue = VecDeque::new();
+    // Whether nothing but timer ticks were handled since the last tick.
+    let mut idle = true;
+    while let Some(event) = next_event(&main_thread_read, &mut queue) {
+        session.metrics.record_queue_depth(queue.len());
+        let name = event.name();
+        let started = Instant::now();
+        if !matches!(event, Event::TimerTick(_)) {
+            idle = false;
+        }
         match event {
             Event::SetPromptInput(line) => {
                 if let Ok(mut buffer) = session.command_buffer.lock() {
@@ -346,14 +928,20 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             Event::ServerSend(_)
             | Event::ServerInput(_)
             | Event::Connect(_)
+            | Event::ConnectWithRetries(_)
             | Event::Connected(_)
             | Event::Reconnect
             | Event::Disconnect => {
+                let connected = matches!(event, Event::Connected(_));
                 event_handler.handle_server_events(
                     event.clone(),
                     &mut screen,
                     &mut transmit_writer,
                 )?;
+                if connected {
+                    apply_color_filter(&session, &mut screen);
+                    run_startup_actions(&session.main_writer, &mut startup_actions, &rt);
+                }
             }
             Event::MudOutput(_)
             | Event::Output(_)
@@ -365,12 +953,20 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             | Event::ClearTags
             | Event::UserInputBuffer(_, _)
             | Event::UserInputCursor(_)
+            | Event::RedrawPromptInput
+            | Event::MaskInput(_)
+            | Event::ServerEcho(_)
+            | Event::CharMode(_)
             | Event::SetPromptMask(_)
             | Event::ClearPromptMask => {
                 //tts_ctrl.handle_events(event.clone());
                 event_handler.handle_output_events(event, &mut screen)?;
             }
-            Event::PlayMusic(_, _) | Event::StopMusic | Event::PlaySFX(_, _) | Event::StopSFX => {
+            Event::PlayMusic(_, _)
+            | Event::StopMusic
+            | Event::PlaySFX(_, _)
+            | Event::StopSFX
+            | Event::SetDucking(_) => {
                 if let Err(err) = audio::handle_audio_event(event, &mut player) {
                     screen.print_error(&err.to_string())
                 }
@@ -385,18 +981,42 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             Event::SpeakStop => session.tts_ctrl.lock().unwrap().flush(),
             Event::TTSEvent(event) => session.tts_ctrl.lock().unwrap().handle(event),
             Event::SettingChanged(name, value) => match name.as_str() {
-                READER_MODE => {
+                READER_MODE | TUI_RENDERER => {
+                    let reader_mode = Settings::try_load()?.get(READER_MODE)?;
                     if let Ok(mut lua) = session.lua_script.lock() {
-                        lua.set_reader_mode(value);
+                        lua.set_reader_mode(reader_mode);
                     }
-                    screen = Box::new(UiWrapper::new_from(screen, &session, value)?);
+                    screen = Box::new(UiWrapper::new_from(screen, &session, reader_mode)?);
+                    apply_timestamps(&session, &mut screen, &timestamp_format)?;
+                    apply_output_settings(&mut screen)?;
+                    apply_reader_filters(&mut screen)?;
+                    apply_color_filter(&session, &mut screen);
+                }
+                TIMESTAMPS => apply_timestamps(&session, &mut screen, &timestamp_format)?,
+                LOG_DEDUPE => apply_log_dedupe(&session)?,
+                CONNECTION_WATCHDOG => apply_connection_watchdog(&session)?,
+                LIVE_RELOAD => apply_live_reload(&mut reload_monitor, &mut screen)?,
+                INPUT_FIFO if !rt.integration_test => apply_input_fifo(&mut input_fifo, &session)?,
+                PROMPT_SPACING | OUTPUT_SEPARATOR | COLLAPSE_REPEATS | PENDING_ECHO | PAGING => {
+                    apply_output_settings(&mut screen)?
                 }
                 HIDE_TOPBAR | SCROLL_SPLIT => {
                     screen.setup()?;
                 }
                 ECHO_INPUT => session.echo_input.store(value, Ordering::Relaxed),
+                COMMAND_STACKING => session.command_stacking.store(value, Ordering::Relaxed),
                 _ => {}
             },
+            Event::ReaderFiltersChanged => apply_reader_filters(&mut screen)?,
+            Event::OriginStylesChanged => apply_origin_styles(&mut screen),
+            Event::ScrollbackSettingsChanged => apply_scrollback_settings(&mut screen),
+            Event::ServersChanged => {
+                session.refresh_server_settings();
+                apply_color_filter(&session, &mut screen);
+            }
+            Event::StreamFilter(direction, active) => {
+                session.stream_filters.set_active(direction, active)
+            }
             Event::StartLogging(world, force) => {
                 if Settings::load().get(LOGGING_ENABLED)? || force {
                     session.start_logging(&world)
@@ -405,6 +1025,12 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             Event::StopLogging => {
                 session.stop_logging();
             }
+            Event::StartRecording(name, scrub) => {
+                session.start_recording(name, scrub);
+            }
+            Event::StopRecording => {
+                session.stop_recording();
+            }
             Event::EnableProto(proto) => {
                 if let Ok(mut parser) = session.telnet_parser.lock() {
                     parser.options.support(proto);
@@ -453,6 +1079,12 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 }
             }
             Event::ProtoSubnegSend(proto, data) => {
+                if let Some(utf8) = accepted_charset_is_utf8(proto, &data) {
+                    if let Ok(mut output_buffer) = session.output_buffer.lock() {
+                        output_buffer.set_utf8(utf8);
+                    }
+                    session.capabilities.utf8(utf8);
+                }
                 if let Ok(mut parser) = session.telnet_parser.lock() {
                     if let Some(TelnetEvents::DataSend(data)) = parser.subnegotiation(proto, data) {
                         session.main_writer.send(Event::ServerSend(data)).unwrap();
@@ -464,12 +1096,38 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             | Event::ScrollDown
             | Event::ScrollTop
             | Event::ScrollBottom
+            | Event::TogglePause
+            | Event::NextPage
+            | Event::ScrollTo(_)
+            | Event::Filter(_)
+            | Event::FilterContext
             | Event::FindForward(_)
             | Event::FindBackward(_) => {
                 event_handler.handle_scroll_events(event, &mut screen)?;
             }
             Event::StatusAreaHeight(height) => screen.set_status_area_height(height)?,
             Event::StatusLine(index, info) => screen.set_status_line(index, info)?,
+            Event::Indicator(id, text) => screen.set_indicator(id, text)?,
+            Event::SetRegion(region) => screen.set_region(region)?,
+            Event::RemoveRegion(name) => screen.remove_region(&name)?,
+            Event::RegionLines(name, lines) => screen.set_region_lines(&name, lines)?,
+            Event::ShowActivity(buffer) => screen.show_activity(&buffer)?,
+            Event::Overlay(lines) => screen.set_overlay(lines)?,
+            Event::DrawRegions => draw_regions(&session, &mut screen)?,
+            Event::SetWidget(widget) => {
+                let regions = widgets.set(widget, time::Instant::now());
+                draw_widgets(&widgets, &regions, &mut screen)?;
+            }
+            Event::UpdateWidget(id, value) => {
+                if let Some(region) = widgets.update(&id, value, time::Instant::now()) {
+                    draw_widgets(&widgets, &[region], &mut screen)?;
+                }
+            }
+            Event::RemoveWidget(id) => {
+                if let Some(region) = widgets.remove(&id) {
+                    draw_widgets(&widgets, &[region], &mut screen)?;
+                }
+            }
             Event::LoadScript(path) => {
                 info!("Loading script: {}", path);
                 let mut lua = session.lua_script.lock().unwrap();
@@ -482,6 +1140,18 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                     });
                 }
             }
+            Event::ReloadScript(path) => reload_script(&path, &session, &mut screen),
+            Event::ScriptsChanged(FSEvent::Update(paths)) => {
+                if Settings::load().get(LIVE_RELOAD)? {
+                    let changed = session.lua_script.lock().unwrap().changed_scripts(&paths);
+                    for path in changed {
+                        reload_script(&path, &session, &mut screen);
+                    }
+                }
+            }
+            Event::ScriptsChanged(FSEvent::Error(err, _)) => {
+                error!("Watching scripts for live_reload failed: {err}");
+            }
             Event::EvalScript(script) => {
                 let mut lua = session.lua_script.lock().unwrap();
                 if let Err(err) = lua.eval(&script) {
@@ -494,6 +1164,14 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 }
             }
             Event::LuaError(error) => {
+                let entry = error_log.push(&error);
+                print_script_error(entry, &mut screen);
+                if let Ok(script) = session.lua_script.lock() {
+                    script.on_error(entry);
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_output(l);
+                    });
+                }
                 if rt.integration_test {
                     session
                         .main_writer
@@ -501,6 +1179,21 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                         .unwrap();
                 }
             }
+            Event::LuaWarning(warning) => {
+                let entry = error_log.push_warning(&warning);
+                if entry.count == 1 {
+                    screen.print_error(&format!("[Lua] Warning: {}", entry.message));
+                }
+            }
+            Event::ShowErrors(index) => show_errors(&error_log, index, &mut screen),
+            Event::TimestampFormat(format) => {
+                timestamp_format = format;
+                apply_timestamps(&session, &mut screen, &timestamp_format)?;
+            }
+            Event::ClearErrors => {
+                error_log.clear();
+                screen.print_info("Script errors cleared");
+            }
             Event::ResetScript => {
                 info!("Clearing scripts");
                 if let Ok(mut script) = session.lua_script.lock() {
@@ -509,7 +1202,16 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                         screen.print_output(l);
                     });
                     screen.print_info("Clearing scripts...");
-                    script.reset((screen.width(), screen.height()))?;
+                    session.stream_filters.clear();
+                    let host = session.host();
+                    let connection = session
+                        .connected()
+                        .then_some((host.as_str(), session.port()));
+                    let scripts = config_scripts(&session.main_writer, &rt);
+                    script.reset((screen.width(), screen.height()), &scripts, connection)?;
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_output(l);
+                    });
                     screen.print_info("Done");
                 }
                 session.timer_writer.send(TimerEvent::Clear(true))?;
@@ -517,6 +1219,8 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
             Event::ShowHelp(hfile, lock) => {
                 help_handler.show_help(&hfile, lock)?;
             }
+            Event::RegisterHelp(topic, content) => help_handler.register(topic, content),
+            Event::SearchHelp(pattern) => help_handler.show_search(&pattern)?,
             Event::AddTimedEvent(duration, count, id, core) => {
                 session
                     .timer_writer
@@ -530,12 +1234,48 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                     });
                 }
             }
+            Event::Export(range, format, name) => {
+                let lines = range.select(screen.scrollback());
+                match io::export(&lines, format, name.as_deref()) {
+                    Ok(path) => screen.print_info(&format!(
+                        "Exported {} lines to: {}",
+                        lines.len(),
+                        path.display()
+                    )),
+                    Err(err) => screen.print_error(&format!("Failed to export: {err}")),
+                }
+            }
+            Event::FindAll(pattern, id) => {
+                let matches = screen.find_all(&pattern);
+                if let Ok(mut script) = session.lua_script.lock() {
+                    script.on_find_all(id, matches);
+                    script.get_output_lines().iter().for_each(|l| {
+                        screen.print_output(l);
+                    });
+                }
+            }
             Event::TimerTick(millis) => {
+                if !widgets.is_empty() {
+                    let regions = widgets.tick(time::Instant::now());
+                    draw_widgets(&widgets, &regions, &mut screen)?;
+                }
                 if let Ok(mut script) = session.lua_script.lock() {
                     script.tick(millis);
                     script.get_output_lines().iter().for_each(|l| {
                         screen.print_output(l);
                     });
+                    if idle {
+                        script.gc_idle_step();
+                    }
+                }
+                idle = true;
+                session.metrics.record_scrollback(
+                    screen.scrollback().len(),
+                    screen.scrollback_bytes(),
+                    screen.scrollback_spilled(),
+                );
+                if let Some(status) = session.watchdog.check(Instant::now()) {
+                    handle_watchdog(status, &session, &mut screen)?;
                 }
             }
             Event::DropTimedEvent(id) => {
@@ -569,8 +1309,9 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 if let Ok(mut script) = session.lua_script.lock() {
                     script.set_dimensions((screen.width(), screen.height()));
                 }
-                let prompt_input = session.prompt_input.lock().unwrap();
-                screen.print_prompt_input(&prompt_input, prompt_input.len());
+                draw_regions(&session, &mut screen)?;
+                draw_widgets(&widgets, &widgets.regions(), &mut screen)?;
+                event_handler.handle_output_events(Event::RedrawPromptInput, &mut screen)?;
             }
             Event::Quit(method) => {
                 if Settings::load().get(CONFIRM_QUIT)?
@@ -596,7 +1337,10 @@ For more info: https://github.com/LiquidityC/Blightmud/issues/173"#;
                 quit_pending = false;
             }
         };
+        session.metrics.record_event(&name, started.elapsed());
+        let started = Instant::now();
         screen.flush();
+        session.metrics.record_render(started.elapsed());
     }
     if let Ok(lua) = session.lua_script.lock() {
         lua.on_quit();
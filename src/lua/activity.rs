@@ -0,0 +1,180 @@
+use mlua::{AnyUserData, Function, Lua, Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::event::Event;
+use crate::model::{self, ActivityBuffer, ActivityLevel, ACTIVITY_INDICATOR};
+
+/// The buffers with unread output, for scripts to read, flag and jump to.
+pub struct Activity {
+    activity: model::Activity,
+}
+
+impl Activity {
+    pub const LUA_GLOBAL_NAME: &'static str = "activity";
+
+    pub fn new(activity: model::Activity) -> Self {
+        Self { activity }
+    }
+}
+
+pub const API: ApiModule = lua_api!("activity", "Buffers with unread output", [
+    "list() -> { buffer: string, level: \"output\"|\"highlight\" }[]" => "Returns the buffers with unread output, longest waiting first",
+    "set(buffer: string, level?: \"output\"|\"highlight\")" => "Marks a buffer as having unread output",
+    "clear(buffer: string)" => "Marks a buffer as read",
+    "next_active()" => "Brings up the buffer with the most important unread output, like /next-active",
+]);
+
+fn activity(ctx: &Lua) -> mlua::Result<model::Activity> {
+    let activity: AnyUserData = ctx.globals().get(Activity::LUA_GLOBAL_NAME)?;
+    let activity = activity.borrow::<Activity>()?;
+    Ok(activity.activity.clone())
+}
+
+/// Shows the changed activity in the status area.
+fn draw_indicator(ctx: &Lua, activity: &model::Activity) -> mlua::Result<()> {
+    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+    backend
+        .writer
+        .send(Event::Indicator(
+            ACTIVITY_INDICATOR.to_string(),
+            activity.indicator(),
+        ))
+        .unwrap();
+    Ok(())
+}
+
+impl UserData for Activity {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("list", |ctx, ()| {
+            let list = ctx.create_table()?;
+            for (buffer, level) in activity(ctx)?.list() {
+                let entry = ctx.create_table()?;
+                entry.set("buffer", buffer.to_string())?;
+                entry.set("level", level.as_str())?;
+                list.push(entry)?;
+            }
+            Ok(list)
+        });
+        methods.add_function("set", |ctx, (buffer, level): (String, Option<String>)| {
+            let level = match level {
+                Some(level) => ActivityLevel::parse(&level).ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!("Unknown activity level: {level}"))
+                })?,
+                None => ActivityLevel::Output,
+            };
+            let activity = activity(ctx)?;
+            if activity.record(ActivityBuffer::from(buffer.as_str()), level) {
+                draw_indicator(ctx, &activity)?;
+            }
+            Ok(())
+        });
+        methods.add_function("clear", |ctx, buffer: String| {
+            let activity = activity(ctx)?;
+            if activity.clear(&ActivityBuffer::from(buffer.as_str())) {
+                draw_indicator(ctx, &activity)?;
+            }
+            Ok(())
+        });
+        methods.add_function("next_active", |ctx, ()| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            match activity(ctx)?.next() {
+                // The inbox lives in lua, it's marked read once shown
+                Some(ActivityBuffer::Inbox) => {
+                    let inbox: Table = ctx.globals().get("inbox")?;
+                    inbox.get::<_, Function>("show")?.call::<_, ()>(())?;
+                }
+                Some(buffer) => backend.writer.send(Event::ShowActivity(buffer)).unwrap(),
+                None => backend
+                    .writer
+                    .send(Event::Info("No unread activity".to_string()))
+                    .unwrap(),
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_activity {
+    use std::sync::mpsc::{channel, Receiver};
+
+    use mlua::Lua;
+
+    use super::*;
+
+    fn lua() -> (Lua, model::Activity, Receiver<Event>) {
+        let lua = Lua::new();
+        let (writer, reader) = channel();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        let activity = model::Activity::default();
+        lua.globals()
+            .set(Activity::LUA_GLOBAL_NAME, Activity::new(activity.clone()))
+            .unwrap();
+        (lua, activity, reader)
+    }
+
+    #[test]
+    fn test_set_and_clear() {
+        let (lua, activity, reader) = lua();
+        lua.load(r#"activity.set("hud") activity.set("scrollback", "highlight")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            activity.level(&ActivityBuffer::Region("hud".to_string())),
+            Some(ActivityLevel::Output)
+        );
+        let (buffer, level): (String, String) = lua
+            .load("local list = activity.list() return list[2].buffer, list[2].level")
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (buffer.as_str(), level.as_str()),
+            ("scrollback", "highlight")
+        );
+        assert_eq!(
+            reader.try_iter().last(),
+            Some(Event::Indicator(
+                ACTIVITY_INDICATOR.to_string(),
+                Some("act: hud, scrollback!".to_string())
+            ))
+        );
+
+        lua.load(r#"activity.clear("hud") activity.clear("scrollback")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(activity.next(), None);
+        assert_eq!(
+            reader.try_iter().last(),
+            Some(Event::Indicator(ACTIVITY_INDICATOR.to_string(), None))
+        );
+        assert!(lua.load(r#"activity.set("hud", "loud")"#).exec().is_err());
+    }
+
+    #[test]
+    fn test_next_active() {
+        let (lua, activity, reader) = lua();
+        lua.load("activity.next_active()").exec().unwrap();
+        assert_eq!(
+            reader.try_recv(),
+            Ok(Event::Info("No unread activity".to_string()))
+        );
+
+        activity.record(ActivityBuffer::Scrollback, ActivityLevel::Output);
+        activity.record(ActivityBuffer::Inbox, ActivityLevel::Highlight);
+        lua.load(
+            r#"
+            inbox = { show = function () activity.clear("inbox") end }
+            activity.next_active()
+            activity.next_active()
+            "#,
+        )
+        .exec()
+        .unwrap();
+        assert_eq!(activity.level(&ActivityBuffer::Inbox), None);
+        assert_eq!(
+            reader.try_iter().last(),
+            Some(Event::ShowActivity(ActivityBuffer::Scrollback))
+        );
+    }
+}
@@ -0,0 +1,621 @@
+use std::fmt::Write;
+
+/// A function exposed to Lua along with its signature, written in a LuaLS inspired notation:
+/// `name(param: type, optional?: type, ...: type) -> type, name: type`. Methods on classes are
+/// prefixed with `:`.
+pub struct ApiFunction {
+    pub signature: &'static str,
+    pub doc: &'static str,
+}
+
+/// The functions exposed by a global Lua module or by a class of objects handed to scripts.
+pub struct ApiModule {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub class: bool,
+    pub functions: &'static [ApiFunction],
+}
+
+/// Reserved words that can't be used in `function module.name()` declarations.
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Declares the Lua API of a module, eg.
+///
+/// ```ignore
+/// pub const API: ApiModule = lua_api!("mud", "Interaction with the MUD", [
+///     "send(msg: string, options?: table)" => "Sends a command to the MUD",
+/// ]);
+/// ```
+macro_rules! lua_api {
+    (class $name:literal, $doc:literal, [$($signature:literal => $fdoc:literal),* $(,)?]) => {
+        lua_api!(@module $name, $doc, true, [$($signature => $fdoc),*])
+    };
+    ($name:literal, $doc:literal, [$($signature:literal => $fdoc:literal),* $(,)?]) => {
+        lua_api!(@module $name, $doc, false, [$($signature => $fdoc),*])
+    };
+    (@module $name:literal, $doc:literal, $class:literal, [$($signature:literal => $fdoc:literal),*]) => {
+        $crate::lua::api::ApiModule {
+            name: $name,
+            doc: $doc,
+            class: $class,
+            functions: &[$($crate::lua::api::ApiFunction {
+                signature: $signature,
+                doc: $fdoc,
+            }),*],
+        }
+    };
+}
+
+const GLOBAL_FUNCTIONS: ApiModule = lua_api!("", "", [
+    "cformat(msg: string, ...: any) -> string" => "Formats a string, replacing color tags such as `<red>` or `<red:black>` with their escape sequences",
+    "bytes_to_string(bytes: integer[]) -> string" => "Converts a list of bytes to a string",
+]);
+
+const JSON: ApiModule = lua_api!("json", "JSON encoding and decoding", [
+    "encode(value: any) -> string" => "Encodes a Lua value as JSON",
+    "decode(str: string) -> any" => "Decodes a JSON string",
+]);
+
+const DEBUGGER: ApiModule = lua_api!("debugger", "Breakpoints and stepping in trigger and alias callbacks", [
+    "breakpoint(file: string, line: integer) -> integer" => "Adds a breakpoint and returns its id",
+    "break(file: string, line: integer) -> integer" => "Alias of `debugger.breakpoint`",
+    "remove(id: integer)" => "Removes a breakpoint",
+    "clear()" => "Removes all breakpoints",
+    "list() -> table<integer, { file: string, line: integer }>" => "Returns the breakpoints by id",
+    "is_paused() -> boolean" => "Checks if a callback is paused at the debug prompt",
+    "watch(callback: function, message: string, ...: any)" => "Runs a callback with breakpoints and the watchdog enabled",
+]);
+
+const TRIGGER: ApiModule = lua_api!("trigger", "Triggers matching lines from the MUD", [
+    "add(regex: string, options: { gag?: boolean, raw?: boolean, prompt?: boolean, count?: integer, enabled?: boolean, important?: boolean|string, fancy?: boolean, icase?: boolean, multiline?: boolean, dotall?: boolean }, callback: fun(matches: string[], line: Line)) -> trigger.Trigger" => "Adds a trigger to the default group",
+    "get(id: integer) -> trigger.Trigger?" => "Returns a trigger from the default group",
+    "get_group(id?: integer|string) -> trigger.TriggerGroup?" => "Returns a trigger group by id or name, the default group if no id is provided",
+    "remove(id: integer)" => "Removes a trigger from the default group",
+    "clear()" => "Removes all triggers in the default group",
+    "add_group(name?: string) -> trigger.TriggerGroup" => "Creates a new trigger group",
+    "loop_protection(threshold: integer, interval?: integer)" => "Sets how often a trigger may send commands before it's disabled as a loop",
+    "import(path: string) -> trigger.TriggerGroup?, error: string?" => "Adds the trigger group in a package written by TriggerGroup:export()",
+]);
+
+const TRIGGER_CLASS: ApiModule = lua_api!(class "trigger.Trigger", "A trigger", [
+    "new(regex: string, options: table, callback: fun(matches: string[], line: Line)) -> trigger.Trigger" => "Creates a trigger that isn't added to any group",
+    "is_trigger(obj: any) -> boolean" => "Checks if an object is a trigger",
+    ":enable()" => "Enables the trigger",
+    ":disable()" => "Disables the trigger",
+    ":set_enabled(flag: boolean)" => "Enables or disables the trigger",
+    ":is_enabled() -> boolean" => "Checks if the trigger is enabled",
+    ":set_source(source: string)" => "Sets the callback source written to packages",
+    ":check_line(line: Line)" => "Matches the trigger against a line",
+]);
+
+const TRIGGER_GROUP: ApiModule = lua_api!(class "trigger.TriggerGroup", "A group of triggers", [
+    "new(id: integer, name?: string) -> trigger.TriggerGroup" => "Creates a trigger group",
+    ":add(regex_or_trigger: string|trigger.Trigger, options?: table, callback?: fun(matches: string[], line: Line)) -> trigger.Trigger" => "Adds a trigger to the group",
+    ":get(id: integer) -> trigger.Trigger?" => "Returns a trigger in the group",
+    ":get_triggers() -> table<integer, trigger.Trigger>" => "Returns the triggers in the group",
+    ":remove(id: integer)" => "Removes a trigger from the group",
+    ":clear()" => "Removes all triggers from the group",
+    ":is_enabled() -> boolean" => "Checks if the group is enabled",
+    ":set_enabled(flag: boolean)" => "Enables or disables the group",
+    ":enable()" => "Enables the group",
+    ":disable()" => "Disables the group",
+    ":activate_when(rules?: { server?: string|string[], character?: string|string[], class?: string|string[] })" => "Enables the group only while the connection matches the rules",
+    ":export(path: string) -> boolean?, error: string?" => "Writes the group and its callbacks to a package file",
+    ":check_line(line: Line)" => "Matches the triggers in the group against a line",
+]);
+
+const ALIAS: ApiModule = lua_api!("alias", "Aliases matching user input", [
+    "add(regex: string, callback: fun(matches: string[], line: Line), options?: { icase?: boolean, multiline?: boolean, dotall?: boolean }) -> alias.Alias" => "Adds an alias to the default group",
+    "get(id: integer) -> alias.Alias?" => "Returns an alias from the default group",
+    "get_group(id?: integer|string) -> alias.AliasGroup?" => "Returns an alias group by id or name, the default group if no id is provided",
+    "remove(id: integer)" => "Removes an alias from the default group",
+    "clear()" => "Removes all aliases in the default group",
+    "add_group(name?: string) -> alias.AliasGroup" => "Creates a new alias group",
+    "import(path: string) -> alias.AliasGroup?, error: string?" => "Adds the alias group in a package written by AliasGroup:export()",
+]);
+
+const ALIAS_CLASS: ApiModule = lua_api!(class "alias.Alias", "An alias", [
+    "new(regex: string, callback: fun(matches: string[], line: Line), options?: table) -> alias.Alias" => "Creates an alias that isn't added to any group",
+    "is_alias(obj: any) -> boolean" => "Checks if an object is an alias",
+    ":enable()" => "Enables the alias",
+    ":disable()" => "Disables the alias",
+    ":set_enabled(flag: boolean)" => "Enables or disables the alias",
+    ":is_enabled() -> boolean" => "Checks if the alias is enabled",
+    ":set_source(source: string)" => "Sets the callback source written to packages",
+    ":check_line(line: Line)" => "Matches the alias against a line",
+]);
+
+const ALIAS_GROUP: ApiModule = lua_api!(class "alias.AliasGroup", "A group of aliases", [
+    "new(id: integer, name?: string) -> alias.AliasGroup" => "Creates an alias group",
+    ":add(regex_or_alias: string|alias.Alias, callback?: fun(matches: string[], line: Line), options?: table) -> alias.Alias" => "Adds an alias to the group",
+    ":get(id: integer) -> alias.Alias?" => "Returns an alias in the group",
+    ":get_aliases() -> table<integer, alias.Alias>" => "Returns the aliases in the group",
+    ":remove(id: integer)" => "Removes an alias from the group",
+    ":clear()" => "Removes all aliases from the group",
+    ":is_enabled() -> boolean" => "Checks if the group is enabled",
+    ":set_enabled(flag: boolean)" => "Enables or disables the group",
+    ":enable()" => "Enables the group",
+    ":disable()" => "Disables the group",
+    ":activate_when(rules?: { server?: string|string[], character?: string|string[], class?: string|string[] })" => "Enables the group only while the connection matches the rules",
+    ":export(path: string) -> boolean?, error: string?" => "Writes the group and its callbacks to a package file",
+    ":check_line(line: Line)" => "Matches the aliases in the group against a line",
+]);
+
+const SEARCH: ApiModule = lua_api!("search", "Searching the output buffer", [
+    "search(str: string, options?: table)" => "Searches the output for a pattern",
+    "start()" => "Enters incremental search mode",
+    "stop()" => "Leaves incremental search mode",
+    "active() -> boolean" => "Returns true while in incremental search mode",
+    "matches(str: string, callback: fun(lines: integer[]), options?: table)" => "Looks up the lines matching a pattern",
+    "filter(str: string, options?: table)" => "Shows only output matching a pattern",
+    "clear_filter()" => "Shows all output again",
+    "filter_context()" => "Shows all output again, scrolled to the top line of the filtered view",
+    "get_filter() -> string?" => "Returns the active filter pattern",
+    "find_up()" => "Scrolls to the previous match",
+    "find_down()" => "Scrolls to the next match",
+    "find_last_input()" => "Scrolls to the previous line of input",
+    "find_next_input()" => "Scrolls to the next line of input",
+]);
+
+const HISTORY: ApiModule = lua_api!("history", "The command history", [
+    "previous_command()" => "Puts the previous command in the prompt",
+    "next_command()" => "Puts the next command in the prompt",
+    "set_context(name?: string)" => "Switches to a separate history, the default history if no name is provided",
+    "get_context() -> string?" => "Returns the name of the current history context",
+    "commands() -> string[]" => "Returns the commands in the current history context, oldest first",
+    "exclude(pattern: string|Regex) -> integer" => "Keeps commands matching the pattern out of the history",
+    "include(id: integer)" => "Removes an exclusion pattern",
+    "excludes() -> table<integer, string>" => "Returns the exclusion patterns",
+]);
+
+const INBOX: ApiModule = lua_api!("inbox", "Tells and important lines", [
+    "add(text: string, source?: string)" => "Adds an unread message to the inbox",
+    "list() -> { text: string, source: string, time: integer, read: boolean }[]" => "Returns the messages in the inbox, oldest first",
+    "unread_count() -> integer" => "Returns the number of unread messages",
+    "mark_read(index?: integer)" => "Marks a message as read, or all messages if no index is provided",
+    "mark_unread(index: integer)" => "Marks a message as unread",
+    "clear()" => "Removes all messages",
+    "show()" => "Prints the messages and marks them as read, like /inbox",
+    "watch(pattern: string|Regex) -> integer" => "Collects lines matching the pattern",
+    "unwatch(id: integer)" => "Removes a watched pattern",
+    "watches() -> table<integer, string>" => "Returns the watched patterns",
+]);
+
+const FINDER: ApiModule = lua_api!("finder", "Finding and running commands", [
+    "open()" => "Opens the finder",
+]);
+
+const PASTE: ApiModule = lua_api!("paste", "Sending pasted text as commands", [
+    "transform(callback?: fun(line: string, index: integer, count: integer): string|string[]|nil)" => "Sends each pasted line as what the callback returns, or removes the callback",
+    "template(template?: string)" => "Sends the next paste with each line filled into the template, or clears it",
+    "pending() -> string?" => "Returns the template waiting for the next paste",
+    "lines(text: string) -> string[]" => "Splits pasted text into its non-empty lines",
+]);
+
+const GUARD: ApiModule = lua_api!("guard", "Confirming commands before they're sent", [
+    "add(pattern: string, options?: { message?: string, icase?: boolean }) -> integer" => "Asks for confirmation before sending commands matching a pattern",
+    "remove(id: integer)" => "Removes a guard",
+    "clear()" => "Removes all guards",
+    "list() -> { id: integer, pattern: string, message?: string }[]" => "Returns the guards",
+]);
+
+const PROTOCOL: ApiModule = lua_api!("protocol", "Protocol state kept across reconnects", [
+    "options() -> integer[]" => "Returns the telnet options enabled on the current connection",
+    "enabled(proto: integer) -> boolean" => "Checks if a telnet option is enabled",
+    "remember(kind: string, item: string)" => "Restores an item every time a protocol is enabled",
+    "forget(kind: string, item?: string)" => "Stops restoring an item, or all items of a kind",
+    "remembered(kind: string) -> string[]" => "Returns the items restored for a protocol",
+    "on_resync(callback: fun(kind: string, items: string[]): string[]|boolean|nil) -> integer" => "Adds a callback run before a protocol's state is restored",
+    "remove_resync_listener(id: integer)" => "Removes a resync callback",
+    "debug(enabled?: boolean) -> boolean" => "Toggles mirroring GMCP and MSDP traffic into the debug log",
+    "debug_log() -> table[]" => "Returns the debug log",
+    "clear_debug_log()" => "Empties the debug log",
+]);
+
+const GMCP: ApiModule = lua_api!("gmcp", "The GMCP protocol", [
+    "echo(enabled: boolean)" => "Prints incoming GMCP messages",
+    "register(module: string)" => "Registers a GMCP module with the server",
+    "unregister(module: string)" => "Unregisters a GMCP module",
+    "receive(module: string, callback: fun(data: string))" => "Adds a listener for a GMCP module",
+    "subscribe(package: string, callback: fun(data: string, module: string)) -> integer" => "Subscribes to a GMCP package and adds a listener for its messages",
+    "unsubscribe(id: integer)" => "Removes a subscription",
+    "subscriptions() -> table<string, integer>" => "Returns the number of subscriptions per package",
+    "send(msg: string)" => "Validates and sends a GMCP message",
+    "on_ready(callback: function)" => "Adds a callback for when GMCP has been negotiated",
+]);
+
+const MSDP: ApiModule = lua_api!("msdp", "The MSDP protocol", [
+    "get(var: string) -> any" => "Returns the value of an MSDP variable",
+    "set(var: string, val: any)" => "Sets an MSDP variable",
+    "register(var: string, callback: fun(value: any))" => "Adds a listener for an MSDP variable",
+    "report(var: string)" => "Asks the server to report changes to a variable",
+    "unreport(var: string)" => "Stops reporting of a variable",
+    "list(list: string)" => "Requests a list from the server",
+    "send(var: string)" => "Requests the value of a variable",
+    "on_ready(callback: function)" => "Adds a callback for when MSDP has been negotiated",
+]);
+
+const TASKS: ApiModule = lua_api!("tasks", "Cooperative tasks", [
+    "spawn(callable: function, ...: any) -> tasks.Task" => "Creates and starts a task",
+    "spawn_later(time: number, callable: function, ...: any) -> tasks.Task" => "Creates a task and starts it after a delay",
+    "sleep(time: number)" => "Suspends the current task",
+    "idle()" => "Suspends the current task until the next tick",
+    "yield() -> any[]" => "Relinquishes control, returning the values sent to the task",
+    "get_current() -> tasks.Task?" => "Returns the running task",
+    "get_tasks() -> tasks.Task[]" => "Returns all tasks",
+    "is_task(obj: any) -> boolean" => "Checks if an object is a task",
+]);
+
+const TASK: ApiModule = lua_api!(class "tasks.Task", "A task", [
+    "new(callable: function, ...: any) -> tasks.Task" => "Creates a task",
+    "spawn(callable: function, ...: any) -> tasks.Task" => "Creates and starts a task",
+    "spawn_later(time: number, callable: function, ...: any) -> tasks.Task" => "Creates a task and starts it after a delay",
+    "get_current() -> tasks.Task?" => "Returns the running task",
+    ":start()" => "Starts the task",
+    ":startLater(time: number)" => "Starts the task after a delay",
+    ":kill()" => "Stops the task",
+    ":send(value: any)" => "Sends a value to the task",
+    ":sleep(time: number)" => "Suspends the task",
+    ":idle()" => "Suspends the task until the next tick",
+]);
+
+const TTYPE: ApiModule = lua_api!("ttype", "The TTYPE/MTTS protocol", [
+    "set_term(new_term: string)" => "Sets the reported terminal type",
+    "set_mtts(new_mtts: integer)" => "Sets the reported MTTS value",
+    "add_option(mtts_opt: integer)" => "Adds an MTTS flag",
+    "rem_option(mtts_opt: integer)" => "Removes an MTTS flag",
+]);
+
+const MSSP: ApiModule = lua_api!("mssp", "The MSSP protocol", [
+    "get() -> table<string, string|string[]>" => "Returns the received MSSP values",
+    "print()" => "Prints the received MSSP values",
+    "on_receive(callback: fun(values: table<string, string|string[]>))" => "Adds a callback for when MSSP values are received",
+]);
+
+const LOGIN: ApiModule = lua_api!("login", "Automatic login sequences", [
+    "add(id: string, sequence: table)" => "Adds a login sequence",
+    "remove(id: string)" => "Removes a login sequence",
+    "get(id: string) -> table?" => "Returns a login sequence",
+    "set_credentials(id: string, user: string, password: string)" => "Stores the credentials for a login sequence",
+    "clear_credentials(id: string)" => "Removes the stored credentials",
+    "is_active() -> boolean" => "Checks if a login sequence is running",
+    "cancel()" => "Stops the running login sequence",
+]);
+
+const TARGET: ApiModule = lua_api!("target", "Combat targets", [
+    "set(name: string)" => "Sets the current target",
+    "add(name: string)" => "Adds a target to the queue",
+    "remove(name: string) -> boolean" => "Removes a target from the queue",
+    "next() -> string?" => "Moves on to the next target",
+    "clear()" => "Clears all targets",
+    "current() -> string?" => "Returns the current target",
+    "list() -> string[]" => "Returns the queued targets",
+    "killed(name?: string)" => "Marks a target as killed",
+    "fled(name?: string)" => "Marks a target as fled",
+    "on_death(callback: fun(name: string))" => "Adds a callback for when a target is killed",
+    "on_flee(callback: fun(name: string))" => "Adds a callback for when a target flees",
+    "death_pattern(re: string) -> integer" => "Adds a trigger marking the current target as killed",
+    "flee_pattern(re: string) -> integer" => "Adds a trigger marking the current target as fled",
+]);
+
+const NUMPAD: ApiModule = lua_api!("numpad", "Numpad walking", [
+    "enable()" => "Enables numpad walking",
+    "disable()" => "Disables numpad walking",
+    "toggle() -> boolean" => "Toggles numpad walking",
+    "is_enabled() -> boolean" => "Checks if numpad walking is enabled",
+    "set(key: string, command: string)" => "Binds a numpad key to a command",
+    "keys() -> table<string, string>" => "Returns the numpad bindings",
+    "reset()" => "Restores the default bindings",
+    "server(id: string, config: table)" => "Sets the numpad config for a server",
+]);
+
+const REPORT: ApiModule = lua_api!("report", "Session statistics", [
+    "get() -> table" => "Returns the session statistics",
+    "print()" => "Prints the session report",
+    "reset()" => "Resets the session statistics",
+    "track(name: string, var?: string)" => "Reports the change of a numeric session variable",
+    "untrack(name: string)" => "Stops reporting a tracked variable",
+]);
+
+const UI: ApiModule = lua_api!("ui", "Progress indicators, questions and menus", [
+    "spinner(text: string, callable?: fun(spinner: ui.Spinner, ...: any), ...: any) -> ui.Spinner" => "Shows a spinner in the status area",
+    "progress(id: string, pct?: number, text?: string)" => "Shows a progress bar in the status area, or removes it",
+    "clear()" => "Removes all indicators",
+    "ask(text: string, options?: { masked?: boolean }, callback: fun(answer: string))" => "Asks a question on the input line",
+    "confirm(text: string, callback: fun(confirmed: boolean))" => "Asks a yes or no question on the input line",
+    "menu(items: string[], callback: fun(item: string?, index: integer?), options?: { title?: string })" => "Shows a selection list over the output",
+    "is_menu_open() -> boolean" => "Checks if a menu is shown",
+]);
+
+const UI_SPINNER: ApiModule = lua_api!(class "ui.Spinner", "A spinner", [
+    ":set_text(text: string)" => "Changes the text of the spinner",
+    ":done()" => "Removes the spinner",
+    ":is_active() -> boolean" => "Checks if the spinner is still shown",
+]);
+
+const ACTIVATION: ApiModule = lua_api!("activation", "Group activation rules", [
+    "add(group: trigger.TriggerGroup|alias.AliasGroup, rules?: table)" => "Sets the activation rules of a group",
+    "refresh()" => "Enables or disables the groups with rules",
+    "state() -> { server?: string, character?: string, class?: string }" => "Returns the connection state rules are matched against",
+]);
+
+/// Every module and class exposed to Lua.
+pub fn modules() -> Vec<&'static ApiModule> {
+    vec![
+        &GLOBAL_FUNCTIONS,
+        &super::blight::API,
+        &super::core::API,
+        &super::tts::API,
+        &super::regex::API,
+        &super::mud::API,
+        &super::fs::API,
+        &super::log::API,
+        &super::timer::API,
+        &super::script::API,
+        &super::settings::API,
+        &super::store::API,
+        &super::store::NAMESPACE_API,
+        &super::plugin::API,
+        &super::audio::API,
+        &super::sounds::API,
+        &super::metrics::API,
+        &super::memory::API,
+        &super::sent::API,
+        &super::web::API,
+        &super::activity::API,
+        &super::socket::API,
+        &super::dns::API,
+        &super::servers::API,
+        &super::palette::API,
+        &super::prompt::API,
+        &super::prompt_mask::API,
+        &super::layout::API,
+        &super::vars::API,
+        &super::snapshot::API,
+        &super::backup::API,
+        &super::relay::API,
+        &super::widget::API,
+        &super::fmt::API,
+        &super::screen::API,
+        &super::fuzzy::API,
+        &super::help::API,
+        &super::reader::API,
+        &super::origin::API,
+        &JSON,
+        &DEBUGGER,
+        &TRIGGER,
+        &ALIAS,
+        &SEARCH,
+        &HISTORY,
+        &INBOX,
+        &FINDER,
+        &PASTE,
+        &GUARD,
+        &PROTOCOL,
+        &GMCP,
+        &MSDP,
+        &TASKS,
+        &TTYPE,
+        &MSSP,
+        &LOGIN,
+        &TARGET,
+        &NUMPAD,
+        &REPORT,
+        &ACTIVATION,
+        &UI,
+        &super::line::API,
+        &super::regex::REGEX_API,
+        &super::exec_response::API,
+        &super::fs_event::API,
+        &super::socket::SOCKET_API,
+        &TRIGGER_CLASS,
+        &TRIGGER_GROUP,
+        &ALIAS_CLASS,
+        &ALIAS_GROUP,
+        &TASK,
+        &UI_SPINNER,
+        #[cfg(feature = "spellcheck")]
+        &super::spellcheck::API,
+    ]
+}
+
+/// Generates EmmyLua/LuaLS definitions for the entire Lua API.
+pub fn stubs() -> String {
+    let mut out = format!(
+        "---@meta\n-- Lua API definitions for {} {}, generated by `blightmud --dump-api`.\n\n",
+        crate::PROJECT_NAME,
+        crate::VERSION
+    );
+    for module in modules() {
+        write_module(&mut out, module);
+    }
+    out
+}
+
+fn write_module(out: &mut String, module: &ApiModule) {
+    if !module.name.is_empty() {
+        write_doc(out, module.doc);
+        if !module.class {
+            // Keeps the module apart from classes with the same name, eg. `regex` and `Regex`.
+            writeln!(out, "---@class {}lib", module.name).unwrap();
+            writeln!(out, "{} = {{}}", module.name).unwrap();
+        } else if module.name.contains('.') {
+            writeln!(out, "---@class {}", module.name).unwrap();
+            writeln!(out, "{} = {{}}", module.name).unwrap();
+        } else {
+            writeln!(out, "---@class {}", module.name).unwrap();
+            writeln!(out, "local {} = {{}}", module.name).unwrap();
+        }
+        out.push('\n');
+    }
+
+    for function in module.functions {
+        let signature = Signature::parse(function.signature);
+        write_doc(out, function.doc);
+        for (name, ty) in &signature.params {
+            writeln!(out, "---@param {name} {ty}").unwrap();
+        }
+        for (name, ty) in &signature.returns {
+            match name {
+                Some(name) => writeln!(out, "---@return {ty} {name}").unwrap(),
+                None => writeln!(out, "---@return {ty}").unwrap(),
+            }
+        }
+        let params = signature
+            .params
+            .iter()
+            .map(|(name, _)| name.trim_end_matches('?'))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let name = signature.name;
+        match (module.name, signature.method) {
+            (table, false) if KEYWORDS.contains(&name) => {
+                writeln!(out, "{table}[\"{name}\"] = function({params}) end\n").unwrap()
+            }
+            ("", _) => writeln!(out, "function {name}({params}) end\n").unwrap(),
+            (table, true) => writeln!(out, "function {table}:{name}({params}) end\n").unwrap(),
+            (table, false) => writeln!(out, "function {table}.{name}({params}) end\n").unwrap(),
+        }
+    }
+}
+
+fn write_doc(out: &mut String, doc: &str) {
+    for line in doc.lines() {
+        writeln!(out, "---{line}").unwrap();
+    }
+}
+
+impl ApiFunction {
+    #[cfg(test)]
+    pub fn name(&self) -> &'static str {
+        Signature::parse(self.signature).name
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Signature<'a> {
+    name: &'a str,
+    method: bool,
+    params: Vec<(&'a str, &'a str)>,
+    returns: Vec<(Option<&'a str>, &'a str)>,
+}
+
+impl<'a> Signature<'a> {
+    fn parse(signature: &'a str) -> Self {
+        let (method, signature) = match signature.strip_prefix(':') {
+            Some(signature) => (true, signature),
+            None => (false, signature),
+        };
+        let (name, rest) = signature.split_once('(').unwrap_or((signature, ")"));
+        let close = closing_paren(rest);
+        let params = split_top_level(&rest[..close])
+            .into_iter()
+            .map(|param| match param.split_once(':') {
+                Some((name, ty)) => (name.trim(), ty.trim()),
+                None => (param, "any"),
+            })
+            .collect();
+        let returns = match rest[close + 1..].trim().strip_prefix("->") {
+            Some(returns) => split_top_level(returns)
+                .into_iter()
+                .map(|ret| match ret.split_once(": ") {
+                    Some((name, ty)) if !name.contains(['(', '{', '<']) => (Some(name), ty),
+                    _ => (None, ret),
+                })
+                .collect(),
+            None => vec![],
+        };
+        Self {
+            name: name.trim(),
+            method,
+            params,
+            returns,
+        }
+    }
+}
+
+/// Returns the index of the parenthesis closing the parameter list.
+fn closing_paren(s: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '{' | '<' | '[' => depth += 1,
+            ')' if depth == 0 => return i,
+            ')' | '}' | '>' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+/// Splits on commas that aren't nested inside a type, eg. `fun(a: string, b: string)`.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '{' | '<' | '[' => depth += 1,
+            ')' | '}' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod test_api {
+    use super::*;
+
+    #[test]
+    fn test_parse_signature() {
+        assert_eq!(
+            Signature::parse("add(regex: string, options?: table, callback: fun(m: string[], line: Line)) -> trigger.Trigger"),
+            Signature {
+                name: "add",
+                method: false,
+                params: vec![
+                    ("regex", "string"),
+                    ("options?", "table"),
+                    ("callback", "fun(m: string[], line: Line)")
+                ],
+                returns: vec![(None, "trigger.Trigger")],
+            }
+        );
+        assert_eq!(
+            Signature::parse(":terminal_dimensions() -> width: integer, table<string, integer>"),
+            Signature {
+                name: "terminal_dimensions",
+                method: true,
+                params: vec![],
+                returns: vec![(Some("width"), "integer"), (None, "table<string, integer>")],
+            }
+        );
+        assert_eq!(
+            Signature::parse("output(...: string)").params,
+            vec![("...", "string")]
+        );
+    }
+
+    #[test]
+    fn test_stubs() {
+        let stubs = stubs();
+        assert!(stubs.starts_with("---@meta\n"));
+        assert!(stubs.contains(
+            "---Sends a command to the MUD\n---@param msg string\n---@param options? { gag?: boolean, skip_log?: boolean, history?: boolean }\nfunction mud.send(msg, options) end\n"
+        ));
+        assert!(stubs.contains("---@class Line\nlocal Line = {}\n"));
+        assert!(stubs.contains("---@return boolean\nfunction Line:gag(flag) end\n"));
+        assert!(stubs.contains("---@class trigger.Trigger\ntrigger.Trigger = {}\n"));
+        assert!(stubs.contains("function cformat(msg, ...) end\n"));
+        assert!(stubs.contains("debugger[\"break\"] = function(file, line) end\n"));
+    }
+}
@@ -0,0 +1,96 @@
+use mlua::{Lua, Result as LuaResult, Table};
+
+use super::constants::API_VERSIONS;
+
+/// The current version of the Lua API.
+pub const API_VERSION: u32 = 2;
+/// The version assumed for scripts that haven't declared one.
+pub const LEGACY_API_VERSION: u32 = 1;
+
+/// Returns the chunk name of the function at the given stack level. Level 1 is the Lua function
+/// calling into Rust.
+fn chunk_at(lua: &Lua, level: usize) -> Option<String> {
+    lua.inspect_stack(level)
+        .and_then(|debug| debug.source().source.map(String::from))
+}
+
+/// Returns the `file:line` of the function at the given stack level.
+pub fn call_site(lua: &Lua, level: usize) -> Option<String> {
+    lua.inspect_stack(level).and_then(|debug| {
+        let source = debug.source().source?.to_string();
+        let source = source.strip_prefix('@').unwrap_or(&source).to_string();
+        Some(format!("{source}:{}", debug.curr_line()))
+    })
+}
+
+/// Prefixes a deprecation warning with the call site at the given stack level.
+pub fn deprecation(lua: &Lua, level: usize, msg: &str) -> String {
+    match call_site(lua, level) {
+        Some(site) => format!("{site}: {msg}"),
+        None => msg.to_string(),
+    }
+}
+
+/// Declares the API version for the script at the given stack level.
+pub fn declare(lua: &Lua, level: usize, version: u32) -> LuaResult<()> {
+    if !(LEGACY_API_VERSION..=API_VERSION).contains(&version) {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Unsupported API version {version}, supported versions are {LEGACY_API_VERSION} to {API_VERSION}"
+        )));
+    }
+    if let Some(chunk) = chunk_at(lua, level) {
+        let versions: Table = lua.named_registry_value(API_VERSIONS)?;
+        versions.set(chunk, version)?;
+    }
+    Ok(())
+}
+
+/// Returns the declared API version for the script at the given stack level.
+pub fn version_at(lua: &Lua, level: usize) -> LuaResult<u32> {
+    let chunk = match chunk_at(lua, level) {
+        Some(chunk) => chunk,
+        None => return Ok(LEGACY_API_VERSION),
+    };
+    let versions: Table = lua.named_registry_value(API_VERSIONS)?;
+    let declared = versions
+        .pairs::<String, u32>()
+        .collect::<LuaResult<Vec<(String, u32)>>>()?;
+    Ok(resolve(&declared, &chunk).unwrap_or(LEGACY_API_VERSION))
+}
+
+/// A declaration covers the declaring file as well as every file in and below its directory so
+/// a plugin only has to declare its version in `main.lua`. The closest declaration wins.
+fn resolve(declared: &[(String, u32)], chunk: &str) -> Option<u32> {
+    declared
+        .iter()
+        .filter_map(|(path, version)| {
+            if path == chunk {
+                return Some((usize::MAX, *version));
+            }
+            let (dir, _) = path.strip_prefix('@')?.rsplit_once('/')?;
+            let rest = chunk.strip_prefix('@')?.strip_prefix(dir)?;
+            rest.starts_with('/').then_some((dir.len(), *version))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, version)| version)
+}
+
+#[cfg(test)]
+mod test_api_version {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let declared = vec![
+            ("@/plugins/foo/main.lua".to_string(), 2),
+            ("@/plugins/foo/legacy/main.lua".to_string(), 1),
+            ("=repl".to_string(), 2),
+        ];
+        assert_eq!(resolve(&declared, "@/plugins/foo/main.lua"), Some(2));
+        assert_eq!(resolve(&declared, "@/plugins/foo/lib/util.lua"), Some(2));
+        assert_eq!(resolve(&declared, "@/plugins/foo/legacy/old.lua"), Some(1));
+        assert_eq!(resolve(&declared, "@/plugins/foobar/main.lua"), None);
+        assert_eq!(resolve(&declared, "=repl"), Some(2));
+        assert_eq!(resolve(&declared, "@/scripts/main.lua"), None);
+    }
+}
@@ -1,8 +1,12 @@
 use mlua::{Table, UserData, UserDataMethods};
 
-use crate::{audio::SourceOptions, event::Event};
+use crate::{
+    audio::{DuckingSettings, SourceOptions},
+    event::Event,
+    io::SaveData,
+};
 
-use super::{backend::Backend, constants::BACKEND};
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
 
 fn parse_audio_options(opts: &Option<Table>) -> SourceOptions {
     let mut options = SourceOptions::default();
@@ -13,8 +17,35 @@ fn parse_audio_options(opts: &Option<Table>) -> SourceOptions {
     options
 }
 
+fn parse_ducking_options(opts: &Table) -> mlua::Result<DuckingSettings> {
+    let mut settings = DuckingSettings::load();
+    settings.enabled = opts
+        .get::<_, Option<bool>>("enabled")?
+        .unwrap_or(settings.enabled);
+    settings.level = opts
+        .get::<_, Option<f32>>("level")?
+        .unwrap_or(settings.level)
+        .clamp(0.0, 1.0);
+    settings.fade_out = opts
+        .get::<_, Option<u64>>("fade_out")?
+        .unwrap_or(settings.fade_out);
+    settings.fade_in = opts
+        .get::<_, Option<u64>>("fade_in")?
+        .unwrap_or(settings.fade_in);
+    Ok(settings)
+}
+
 pub struct Audio {}
 
+pub const API: ApiModule = lua_api!("audio", "Music and sound effects", [
+    "play_music(path: string, options?: { loop?: boolean, amplify?: number })" => "Plays music",
+    "stop_music()" => "Stops the music",
+    "play_sfx(path: string, options?: { amplify?: number })" => "Plays a sound effect",
+    "stop_sfx()" => "Stops sound effects",
+    "ducking() -> { enabled: boolean, level: number, fade_out: integer, fade_in: integer }" => "Returns how music is ducked while text-to-speech speaks",
+    "set_ducking(options: { enabled?: boolean, level?: number, fade_out?: integer, fade_in?: integer })" => "Changes how music is ducked while text-to-speech speaks",
+]);
+
 impl UserData for Audio {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function(
@@ -45,6 +76,21 @@ impl UserData for Audio {
             backend.writer.send(Event::StopSFX).unwrap();
             Ok(())
         });
+        methods.add_function("ducking", |ctx, ()| {
+            let settings = DuckingSettings::load();
+            let table = ctx.create_table()?;
+            table.set("enabled", settings.enabled)?;
+            table.set("level", settings.level)?;
+            table.set("fade_out", settings.fade_out)?;
+            table.set("fade_in", settings.fade_in)?;
+            Ok(table)
+        });
+        methods.add_function("set_ducking", |ctx, opts: Table| {
+            let settings = parse_ducking_options(&opts)?;
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::SetDucking(settings)).unwrap();
+            Ok(())
+        });
     }
 }
 
@@ -123,6 +169,29 @@ mod test_player {
         );
     }
 
+    #[test]
+    fn test_set_ducking() {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals().set("audio", Audio {}).unwrap();
+        lua.load(r#"audio.set_ducking({ level = 1.5, fade_in = 2000 })"#)
+            .exec()
+            .unwrap();
+        match reader.recv() {
+            Ok(Event::SetDucking(settings)) => {
+                assert_eq!(settings.level, 1.0);
+                assert_eq!(settings.fade_in, 2000);
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+        assert!(lua
+            .load(r#"audio.set_ducking({ level = "loud" })"#)
+            .exec()
+            .is_err());
+    }
+
     #[test]
     fn test_stop_sfx() {
         assert_event(r#"audio.stop_sfx()"#, Event::StopSFX);
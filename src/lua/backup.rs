@@ -0,0 +1,51 @@
+use mlua::{Lua, Result as LuaResult, Table, UserData, UserDataMethods};
+
+use super::api::ApiModule;
+use crate::io::BackupFiles;
+
+/// Backups of the config and data directories.
+pub struct Backup {}
+
+pub const API: ApiModule = lua_api!("backup", "Backing up and restoring scripts, settings and data", [
+    "create(path?: string) -> string" => "Writes a backup and returns the path of the file",
+    "diff(path: string) -> { path: string, change: \"added\"|\"changed\"|\"unchanged\" }[]" => "Lists what restoring a backup would change, without changing anything",
+    "restore(path: string) -> integer" => "Restores the added and changed files of a backup and returns how many were written",
+]);
+
+fn create(_: &Lua, path: Option<String>) -> LuaResult<String> {
+    BackupFiles::collect()
+        .and_then(|backup| backup.write(path.as_deref()))
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(mlua::Error::external)
+}
+
+fn diff(lua: &Lua, path: String) -> LuaResult<Table<'_>> {
+    let changes = BackupFiles::read(&path)
+        .and_then(|backup| backup.diff())
+        .map_err(mlua::Error::external)?;
+    lua.create_sequence_from(
+        changes
+            .into_iter()
+            .map(|(path, change)| {
+                let file = lua.create_table()?;
+                file.set("path", path)?;
+                file.set("change", change.as_str())?;
+                Ok(file)
+            })
+            .collect::<LuaResult<Vec<Table>>>()?,
+    )
+}
+
+fn restore(_: &Lua, path: String) -> LuaResult<usize> {
+    BackupFiles::read(&path)
+        .and_then(|backup| backup.restore())
+        .map_err(mlua::Error::external)
+}
+
+impl UserData for Backup {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("create", create);
+        methods.add_function("diff", diff);
+        methods.add_function("restore", restore);
+    }
+}
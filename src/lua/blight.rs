@@ -1,17 +1,29 @@
-use super::{constants::*, regex::Regex, ui_event::UiEvent};
+use super::api_version::{self, API_VERSION};
+use super::plugin::{PluginVerbosity, Verbosity};
+use super::{api::ApiModule, constants::*, regex::Regex, runtime, script, ui_event::UiEvent};
 use crate::event::{Event, QuitMethod};
-use crate::{model::Line, PROJECT_NAME, VERSION};
+use crate::io::SaveData;
+use crate::model::{
+    is_valid_timestamp_format, Color, Line, Origin, DEFAULT_TIMESTAMP_FORMAT, THROTTLE_TAG,
+};
+use crate::{PROJECT_NAME, VERSION};
 use log::debug;
 use mlua::{
     AnyUserData, FromLua, Function, Result as LuaResult, Table, UserData, UserDataMethods, Variadic,
 };
+use std::collections::HashMap;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, FromLua)]
 pub struct Blight {
     main_writer: Sender<Event>,
     output_lines: Vec<Line>,
+    /// When the output printed with `blight.output_throttled` for a key started replacing
+    /// itself.
+    throttled: HashMap<String, Instant>,
     ui_events: Vec<UiEvent>,
+    next_key_listener_id: u32,
     pub screen_dimensions: (u16, u16),
     pub core_mode: bool,
     pub reader_mode: bool,
@@ -23,7 +35,9 @@ impl Blight {
         Self {
             main_writer: writer,
             output_lines: vec![],
+            throttled: HashMap::new(),
             ui_events: vec![],
+            next_key_listener_id: 1,
             screen_dimensions: (0, 0),
             core_mode: false,
             reader_mode: false,
@@ -35,12 +49,53 @@ impl Blight {
         self.core_mode = mode;
     }
 
+    /// Queues output that replaces the output queued for the same key within `window`, rather
+    /// than being added after it.
+    fn output_throttled(&mut self, key: String, mut line: Line, window: Duration) {
+        let now = Instant::now();
+        line.set_tag(THROTTLE_TAG, &key);
+        match self.throttled.get(&key) {
+            Some(since) if now.duration_since(*since) < window => line.flags.replace_last = true,
+            _ => {
+                self.throttled.insert(key, now);
+            }
+        }
+        self.output_lines.push(line);
+    }
+
     pub fn get_output_lines(&mut self) -> Vec<Line> {
         let return_lines = self.output_lines.clone();
         self.output_lines.clear();
         return_lines
     }
 
+    /// Queues output of a plugin at a level, prefixed with the plugin's name, unless the
+    /// verbosity set for the plugin hides it.
+    fn output_for(&mut self, plugin: &str, level: Verbosity, text: &str) {
+        let verbosity = PluginVerbosity::load()
+            .get(plugin)
+            .copied()
+            .unwrap_or_default();
+        if !verbosity.shows(level) {
+            return;
+        }
+        let prefix = match level {
+            Verbosity::Error => Some(Color::Indexed(1)),
+            Verbosity::Warn => Some(Color::Indexed(3)),
+            Verbosity::Debug => Some(Color::Indexed(8)),
+            _ => None,
+        }
+        .map_or_else(
+            || format!("[{plugin}]"),
+            |color| format!("{}[{plugin}]\x1b[0m", color.escape(false)),
+        );
+        let mut line = Line::from(format!("{prefix} {text}"));
+        line.flags.origin = Origin::Plugin(plugin.to_string());
+        line.set_tag("plugin", plugin);
+        line.set_tag("level", &level.to_string());
+        self.output_lines.push(line);
+    }
+
     pub fn get_ui_events(&mut self) -> Vec<UiEvent> {
         let events = self.ui_events.clone();
         self.ui_events.clear();
@@ -48,14 +103,82 @@ impl Blight {
     }
 }
 
+pub const API: ApiModule = lua_api!("blight", "Core Blightmud functionality", [
+    "output(...: string)" => "Prints to the output window",
+    "terminal_dimensions() -> width: integer, height: integer" => "Returns the dimensions of the terminal",
+    "bind(cmd: string, callback: function)" => "Binds a key combination to a callback",
+    "unbind(cmd: string)" => "Removes a key binding",
+    "ui(cmd: string)" => "Runs a UI command, eg. `scroll_up` or `step_word_left`",
+    "debug(...: string)" => "Writes to the debug log",
+    "is_core_mode() -> boolean" => "Checks if the calling script is part of Blightmud",
+    "is_reader_mode() -> boolean" => "Checks if screen reader mode is enabled",
+    "status_height(height?: integer) -> integer" => "Sets and returns the height of the status area",
+    "status_line(index: integer, line: string)" => "Sets a line in the status area",
+    "indicator(id: string, text?: string)" => "Shows a progress indicator in the status area, or removes it",
+    "timestamp_format(format?: string) -> string" => "Sets and returns the strftime format of line timestamps",
+    "version() -> name: string, version: string" => "Returns the Blightmud name and version",
+    "lua_version() -> version: string, jit: string?" => "Returns the Lua version and, when running on LuaJIT, the LuaJIT version",
+    "api_version(version?: integer) -> declared: integer, latest: integer" => "Declares the API version the calling script is written against",
+    "caller_api_version(level?: integer) -> integer" => "Returns the API version declared by the script at the given stack level",
+    "deprecated(msg: string, level?: integer)" => "Reports a deprecation warning along with the call site",
+    "config_dir() -> string" => "Returns the Blightmud config directory",
+    "data_dir() -> string" => "Returns the Blightmud data directory",
+    "cache_dir() -> string" => "Returns the Blightmud cache directory",
+    "on_quit(callback: function)" => "Adds a callback for when Blightmud quits",
+    "on_complete(callback: fun(input: string): string[]?)" => "Adds a tab completion provider",
+    "on_dimensions_change(callback: fun(width: integer, height: integer))" => "Adds a callback for when the terminal is resized",
+    "on_key(callback: fun(event: { key: string, ctrl: boolean, alt: boolean, shift: boolean }): boolean?) -> integer" => "Adds a callback for key presses, run before key bindings. Returning true consumes the key",
+    "remove_key_listener(id: integer)" => "Removes a key press callback",
+    "quit()" => "Quits Blightmud",
+    "show_help(subject: string, lock_scroll: boolean)" => "Shows a help file",
+    "find_backward(re: Regex, from_bottom?: boolean)" => "Scrolls to the previous line matching a regex",
+    "find_forward(re: Regex)" => "Scrolls to the next line matching a regex",
+    "find_all(re: Regex, callback: fun(lines: integer[]))" => "Looks up the scrollback lines matching a regex",
+    "scroll_to(line?: integer)" => "Scrolls the scrollback to a line, or back to the bottom",
+    "filter(re?: Regex)" => "Shows only output matching a regex, or all output again",
+    "filter_context()" => "Leaves the filtered output at the context of the top line in view",
+    "output_throttled(key: string, text: string, ms: integer)" => "Prints output that replaces the output printed for the same key within an interval",
+    "output_for(plugin: string, level: string, ...: string)" => "Prints output of a plugin at a level, prefixed with its name, unless the plugin's verbosity hides it",
+]);
+
 impl UserData for Blight {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("output", |ctx, strings: Variadic<String>| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
             let mut this = this_aux.borrow_mut::<Blight>()?;
-            this.output_lines.push(Line::from(strings.join(" ")));
+            let mut line = Line::from(strings.join(" "));
+            line.flags.origin = script::origin(ctx);
+            this.output_lines.push(line);
             Ok(())
         });
+        methods.add_function(
+            "output_for",
+            |ctx, (plugin, level, strings): (String, String, Variadic<String>)| {
+                let level = match level.parse::<Verbosity>() {
+                    Ok(Verbosity::Off) | Err(_) => {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "Invalid output level: {level}, use error, warn, info or debug"
+                        )))
+                    }
+                    Ok(level) => level,
+                };
+                let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                let mut this = this_aux.borrow_mut::<Blight>()?;
+                this.output_for(&plugin, level, &strings.join(" "));
+                Ok(())
+            },
+        );
+        methods.add_function(
+            "output_throttled",
+            |ctx, (key, text, ms): (String, String, u64)| {
+                let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                let mut this = this_aux.borrow_mut::<Blight>()?;
+                let mut line = Line::from(text);
+                line.flags.origin = script::origin(ctx);
+                this.output_throttled(key, line, Duration::from_millis(ms));
+                Ok(())
+            },
+        );
         methods.add_function("terminal_dimensions", |ctx, _: ()| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
             let this = this_aux.borrow::<Blight>()?;
@@ -128,15 +251,68 @@ impl UserData for Blight {
                 .unwrap();
             Ok(())
         });
+        methods.add_function("indicator", |ctx, (id, text): (String, Option<String>)| {
+            let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            this.main_writer.send(Event::Indicator(id, text)).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "timestamp_format",
+            |ctx, format: Option<String>| -> LuaResult<String> {
+                if let Some(format) = format {
+                    if !is_valid_timestamp_format(&format) {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "Invalid timestamp format: '{format}'"
+                        )));
+                    }
+                    let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                    let this = this_aux.borrow::<Blight>()?;
+                    this.main_writer
+                        .send(Event::TimestampFormat(format.clone()))
+                        .unwrap();
+                    ctx.set_named_registry_value(TIMESTAMP_FORMAT, format)?;
+                }
+                Ok(ctx
+                    .named_registry_value::<Option<String>>(TIMESTAMP_FORMAT)?
+                    .unwrap_or_else(|| DEFAULT_TIMESTAMP_FORMAT.to_string()))
+            },
+        );
         methods.add_function("version", |_, _: ()| -> LuaResult<(&str, &str)> {
             Ok((PROJECT_NAME, VERSION))
         });
+        methods.add_function("lua_version", |ctx, ()| runtime::lua_version(ctx));
+        methods.add_function(
+            "api_version",
+            |ctx, version: Option<u32>| -> LuaResult<(u32, u32)> {
+                if let Some(version) = version {
+                    api_version::declare(ctx, 1, version)?;
+                }
+                Ok((api_version::version_at(ctx, 1)?, API_VERSION))
+            },
+        );
+        methods.add_function("caller_api_version", |ctx, level: Option<usize>| {
+            api_version::version_at(ctx, level.unwrap_or(2))
+        });
+        methods.add_function(
+            "deprecated",
+            |ctx, (msg, level): (String, Option<usize>)| {
+                let warning = api_version::deprecation(ctx, level.unwrap_or(2), &msg);
+                let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer.send(Event::LuaWarning(warning)).unwrap();
+                Ok(())
+            },
+        );
         methods.add_function("config_dir", |_, ()| -> mlua::Result<String> {
             Ok(crate::CONFIG_DIR.to_string_lossy().to_string())
         });
         methods.add_function("data_dir", |_, ()| -> mlua::Result<String> {
             Ok(crate::DATA_DIR.to_string_lossy().to_string())
         });
+        methods.add_function("cache_dir", |_, ()| -> mlua::Result<String> {
+            Ok(crate::CACHE_DIR.to_string_lossy().to_string())
+        });
         methods.add_function("on_quit", |ctx, func: Function| -> mlua::Result<()> {
             let table: Table = ctx.named_registry_value(BLIGHT_ON_QUIT_LISTENER_TABLE)?;
             table.set(table.raw_len() + 1, func)?;
@@ -159,6 +335,19 @@ impl UserData for Blight {
                 Ok(())
             },
         );
+        methods.add_function("on_key", |ctx, func: Function| -> mlua::Result<u32> {
+            let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+            let mut this = this_aux.borrow_mut::<Blight>()?;
+            let id = this.next_key_listener_id;
+            this.next_key_listener_id += 1;
+            let table: Table = ctx.named_registry_value(BLIGHT_ON_KEY_LISTENER_TABLE)?;
+            table.set(id, func)?;
+            Ok(id)
+        });
+        methods.add_function("remove_key_listener", |ctx, id: u32| -> mlua::Result<()> {
+            let table: Table = ctx.named_registry_value(BLIGHT_ON_KEY_LISTENER_TABLE)?;
+            table.set(id, mlua::Nil)
+        });
         methods.add_function("quit", |ctx, ()| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
             let this = this_aux.borrow::<Blight>()?;
@@ -175,18 +364,62 @@ impl UserData for Blight {
                 .unwrap();
             Ok(())
         });
-        methods.add_function("find_backward", |ctx, re: Regex| {
+        methods.add_function(
+            "find_backward",
+            |ctx, (re, from_bottom): (Regex, Option<bool>)| {
+                let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                let re = re.default_engine()?;
+                if from_bottom.unwrap_or_default() {
+                    this.main_writer.send(Event::ScrollBottom).unwrap();
+                }
+                this.main_writer.send(Event::FindBackward(re)).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("find_forward", |ctx, re: Regex| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
             let this = this_aux.borrow::<Blight>()?;
             this.main_writer
-                .send(Event::FindBackward(re.regex))
+                .send(Event::FindForward(re.default_engine()?))
                 .unwrap();
             Ok(())
         });
-        methods.add_function("find_forward", |ctx, re: Regex| {
+        methods.add_function(
+            "find_all",
+            |ctx, (re, callback): (Regex, Function)| -> LuaResult<()> {
+                let re = re.default_engine()?;
+                let cb_table: Table = ctx.named_registry_value(SEARCH_CALLBACK_TABLE)?;
+                let id: u32 = ctx.named_registry_value(SEARCH_NEXT_ID)?;
+                cb_table.raw_set(id, callback)?;
+                ctx.set_named_registry_value(SEARCH_NEXT_ID, id + 1)?;
+                let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+                let this = this_aux.borrow::<Blight>()?;
+                this.main_writer.send(Event::FindAll(re, id)).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("scroll_to", |ctx, line: Option<usize>| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
             let this = this_aux.borrow::<Blight>()?;
-            this.main_writer.send(Event::FindForward(re.regex)).unwrap();
+            let event = match line {
+                Some(line) => Event::ScrollTo(line.saturating_sub(1)),
+                None => Event::ScrollBottom,
+            };
+            this.main_writer.send(event).unwrap();
+            Ok(())
+        });
+        methods.add_function("filter", |ctx, re: Option<Regex>| {
+            let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            let re = re.map(Regex::default_engine).transpose()?;
+            this.main_writer.send(Event::Filter(re)).unwrap();
+            Ok(())
+        });
+        methods.add_function("filter_context", |ctx, ()| {
+            let this_aux = ctx.globals().get::<_, AnyUserData>("blight")?;
+            let this = this_aux.borrow::<Blight>()?;
+            this.main_writer.send(Event::FilterContext).unwrap();
             Ok(())
         });
     }
@@ -200,11 +433,13 @@ mod test_blight {
 
     use crate::event::{Event, QuitMethod};
     use crate::lua::UiEvent;
+    use crate::model::{Line, Origin, THROTTLE_TAG};
 
     use super::Blight;
     use crate::lua::constants::{
         BLIGHT_ON_DIMENSIONS_CHANGE_LISTENER_TABLE, BLIGHT_ON_QUIT_LISTENER_TABLE,
-        COMMAND_BINDING_TABLE, COMPLETION_CALLBACK_TABLE, STATUS_AREA_HEIGHT,
+        COMMAND_BINDING_TABLE, COMPLETION_CALLBACK_TABLE, SEARCH_CALLBACK_TABLE, SEARCH_NEXT_ID,
+        STATUS_AREA_HEIGHT,
     };
     use crate::{PROJECT_NAME, VERSION};
 
@@ -228,6 +463,9 @@ mod test_blight {
             .unwrap();
         lua.set_named_registry_value(STATUS_AREA_HEIGHT, 1u16)
             .unwrap();
+        lua.set_named_registry_value(SEARCH_CALLBACK_TABLE, lua.create_table().unwrap())
+            .unwrap();
+        lua.set_named_registry_value(SEARCH_NEXT_ID, 1).unwrap();
         (lua, reader)
     }
 
@@ -251,6 +489,16 @@ mod test_blight {
             .ends_with(".run/test/data"));
     }
 
+    #[test]
+    fn test_cache_dir() {
+        let (lua, _reader) = get_lua_state();
+        assert!(lua
+            .load("return blight.cache_dir()")
+            .call::<_, String>(())
+            .unwrap()
+            .ends_with(".run/test/cache"));
+    }
+
     #[test]
     fn test_version() {
         let (lua, _reader) = get_lua_state();
@@ -308,6 +556,91 @@ mod test_blight {
         assert_eq!(it.next().unwrap(), &crate::model::Line::from("on_quit"));
     }
 
+    #[test]
+    fn output_for() {
+        let (lua, _reader) = get_lua_state();
+        lua.load(
+            r#"
+        blight.output_for("origin_test", "info", "Room", "saved")
+        blight.output_for("origin_test", "debug", "Path cache hit")
+        blight.output_for("origin_test", "error", "No map")
+        "#,
+        )
+        .exec()
+        .unwrap();
+        let blight_aux = lua.globals().get::<_, AnyUserData>("blight").unwrap();
+        let lines = blight_aux
+            .borrow_mut::<Blight>()
+            .unwrap()
+            .get_output_lines();
+        assert_eq!(
+            lines,
+            vec![
+                Line::from("[origin_test] Room saved"),
+                Line::from("\x1b[31m[origin_test]\x1b[0m No map"),
+            ]
+        );
+        assert_eq!(lines[1].tag("level"), Some("error"));
+        assert_eq!(
+            lines[1].flags.origin,
+            Origin::Plugin("origin_test".to_string())
+        );
+        assert!(lua
+            .load(r#"blight.output_for("origin_test", "off", "hi")"#)
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn output_throttled() {
+        let (lua, _reader) = get_lua_state();
+        let output = || -> Vec<crate::model::Line> {
+            let blight_aux = lua.globals().get::<_, AnyUserData>("blight").unwrap();
+            let mut blight = blight_aux.borrow_mut::<Blight>().unwrap();
+            blight.get_output_lines()
+        };
+        lua.load(
+            r#"
+        blight.output_throttled("hp", "HP: 100", 50)
+        blight.output_throttled("hp", "HP: 90", 50)
+        blight.output_throttled("sp", "SP: 20", 50)
+        blight.output_throttled("hp", "HP: 80", 50)
+        "#,
+        )
+        .exec()
+        .unwrap();
+        let lines = output();
+        let printed = |lines: &[crate::model::Line]| -> Vec<(String, String, bool)> {
+            lines
+                .iter()
+                .map(|line| {
+                    (
+                        line.line().to_string(),
+                        line.tag(THROTTLE_TAG).unwrap_or_default().to_string(),
+                        line.flags.replace_last,
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(
+            printed(&lines),
+            vec![
+                ("HP: 100".to_string(), "hp".to_string(), false),
+                ("HP: 90".to_string(), "hp".to_string(), true),
+                ("SP: 20".to_string(), "sp".to_string(), false),
+                ("HP: 80".to_string(), "hp".to_string(), true),
+            ]
+        );
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        lua.load(r#"blight.output_throttled("hp", "HP: 70", 50)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            printed(&output()),
+            vec![("HP: 70".to_string(), "hp".to_string(), false)]
+        );
+    }
+
     #[test]
     fn quit() {
         let (lua, reader) = get_lua_state();
@@ -326,7 +659,53 @@ mod test_blight {
         lua.load(r#"blight.find_backward(regex.new("test"))"#)
             .exec()
             .unwrap();
-        assert_eq!(reader.recv(), Ok(Event::FindBackward(re)));
+        assert_eq!(reader.recv(), Ok(Event::FindBackward(re.clone())));
+        lua.load(r#"blight.find_backward(regex.new("test"), true)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::ScrollBottom));
+        assert_eq!(reader.recv(), Ok(Event::FindBackward(re.clone())));
+        lua.load(r#"blight.find_all(regex.new("test"), function () end)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::FindAll(re, 1)));
+        let table: mlua::Table = lua.named_registry_value(SEARCH_CALLBACK_TABLE).unwrap();
+        assert_eq!(table.raw_len(), 1);
+        lua.load("blight.scroll_to(12)").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::ScrollTo(11)));
+        lua.load("blight.scroll_to()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::ScrollBottom));
+    }
+
+    #[test]
+    fn filter() {
+        let (lua, reader) = get_lua_state();
+        let re = crate::model::Regex::new("tells you", None).unwrap();
+        lua.load(r#"blight.filter(regex.new("tells you"))"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Filter(Some(re))));
+        lua.load("blight.filter_context()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::FilterContext));
+        lua.load("blight.filter()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Filter(None)));
+    }
+
+    #[test]
+    fn indicator() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"blight.indicator("dl", "Downloading")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Indicator(
+                "dl".to_string(),
+                Some("Downloading".to_string())
+            ))
+        );
+        lua.load(r#"blight.indicator("dl")"#).exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Indicator("dl".to_string(), None)));
     }
 
     #[test]
@@ -430,4 +809,26 @@ mod test_blight {
             .unwrap();
         assert_eq!(height, 1);
     }
+
+    #[test]
+    fn test_timestamp_format() {
+        let (lua, reader) = get_lua_state();
+        assert_eq!(
+            lua.load("return blight.timestamp_format()")
+                .call::<_, String>(())
+                .unwrap(),
+            "%H:%M:%S"
+        );
+        assert_eq!(
+            lua.load("return blight.timestamp_format('%H:%M')")
+                .call::<_, String>(())
+                .unwrap(),
+            "%H:%M"
+        );
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::TimestampFormat("%H:%M".to_string())
+        );
+        assert!(lua.load("blight.timestamp_format('%Q')").exec().is_err());
+    }
 }
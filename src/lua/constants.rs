@@ -1,5 +1,8 @@
+use std::ffi::CStr;
+
 pub const ON_CONNECTION_CALLBACK_TABLE: &str = "__connection_callback_table";
 pub const ON_DISCONNECT_CALLBACK_TABLE: &str = "__disconnect_callback_table";
+pub const ON_STALL_CALLBACK_TABLE: &str = "__stall_callback_table";
 pub const IS_CONNECTED: &str = "__is_connected_bool";
 pub const TIMED_CALLBACK_TABLE: &str = "__timed_callback_table";
 pub const TIMED_CALLBACK_TABLE_CORE: &str = "__timed_callback_table_core";
@@ -11,18 +14,46 @@ pub const MUD_OUTPUT_LISTENER_TABLE: &str = "__output_listeners";
 pub const MUD_INPUT_LISTENER_TABLE: &str = "__input_listeners";
 pub const BLIGHT_ON_QUIT_LISTENER_TABLE: &str = "__on_quit_listeners";
 pub const BLIGHT_ON_DIMENSIONS_CHANGE_LISTENER_TABLE: &str = "__on_dimensions_change_listeners";
+pub const BLIGHT_ON_KEY_LISTENER_TABLE: &str = "__on_key_listeners";
 pub const BACKEND: &str = "__blight_backend_wrapper";
 pub const CONNECTION_ID: &str = "__blight_connection_id";
 pub const COMPLETION_CALLBACK_TABLE: &str = "__completion_callback_table";
 pub const PROMPT_CONTENT: &str = "__prompt_content";
+pub const PROMPT_LABEL: &str = "__prompt_label";
 pub const PROMPT_CURSOR_INDEX: &str = "__prompt_cursor_index";
 pub const PROMPT_MASK_CONTENT: &str = "__prompt_mask_content";
+pub const PROMPT_MASK_SEGMENTS: &str = "__prompt_mask_segments";
 pub const PROMPT_INPUT_LISTENER_TABLE: &str = "__prompt_listeners";
 pub const FS_LISTENERS: &str = "__fs_listeners";
 pub const SCRIPT_RESET_LISTENERS: &str = "__script_reset_listeners";
+pub const SCRIPT_ERROR_LISTENERS: &str = "__script_error_listeners";
+pub const SCRIPT_COMPILERS: &str = "__script_compilers";
+pub const API_VERSIONS: &str = "__api_versions";
+pub const PLUGIN_USAGE: &str = "__plugin_usage";
+pub const LOADED_SCRIPTS: &str = "__loaded_scripts";
+pub const TIMER_SCRIPTS: &str = "__timer_scripts";
 pub const STATUS_AREA_HEIGHT: &str = "__status_area_height";
+pub const LAYOUT_REGIONS: &str = "__layout_regions";
+pub const SESSION_VARS: &str = "__session_vars";
+pub const SESSION_VAR_LISTENERS: &str = "__session_var_listeners";
+pub const COMMAND_SEPARATOR: &str = "__command_separator";
+pub const COMMAND_STACKING: &str = "__command_stacking";
+pub const CHAR_MODE: &str = "__char_mode";
+pub const REPORTED_DIMENSIONS: &str = "__reported_dimensions";
+pub const REPORTED_DIMENSIONS_LISTENER_TABLE: &str = "__reported_dimensions_listeners";
+pub const HELP_TOPICS: &str = "__help_topics";
+pub const HANDLING_INPUT: &str = "__handling_input";
+pub const SENT_COUNT: &str = "__sent_count";
 
 // Core tables
 pub const PROTO_ENABLED_LISTENERS_TABLE: &str = "__protocol_enabled_listeners";
 pub const PROTO_DISABLED_LISTENERS_TABLE: &str = "__protocol_disabled_listeners";
+pub const ECHO_CHANGE_LISTENERS_TABLE: &str = "__echo_change_listeners";
 pub const PROTO_SUBNEG_LISTENERS_TABLE: &str = "__protocol_subneg_listeners";
+pub const STREAM_FILTER_INBOUND_TABLE: &str = "__stream_filter_inbound";
+pub const STREAM_FILTER_OUTBOUND_TABLE: &str = "__stream_filter_outbound";
+pub const DEBUG_HOOK: &CStr = c"__debug_hook";
+pub const TIMESTAMP_FORMAT: &str = "__timestamp_format";
+pub const SEARCH_CALLBACK_TABLE: &str = "__search_callback_table";
+pub const SEARCH_NEXT_ID: &str = "__search_next_id";
+pub const GC_IDLE_STEP: &str = "__gc_idle_step";
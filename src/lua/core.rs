@@ -1,14 +1,17 @@
-use std::sync::mpsc::Sender;
+use std::{ffi::c_int, sync::mpsc::Sender};
 
 use libmudtelnet::bytes::Bytes;
 use log::debug;
-use mlua::{AnyUserData, Table, UserData, UserDataMethods};
+use mlua::{ffi, AnyUserData, Table, UserData, UserDataFields, UserDataMethods};
 
-use crate::{event::Event, io::exec};
+use crate::{event::Event, io::exec, net::StreamDirection};
 
 use super::{
+    api::ApiModule,
     constants::{
-        PROTO_DISABLED_LISTENERS_TABLE, PROTO_ENABLED_LISTENERS_TABLE, PROTO_SUBNEG_LISTENERS_TABLE,
+        DEBUG_HOOK, ECHO_CHANGE_LISTENERS_TABLE, PROTO_DISABLED_LISTENERS_TABLE,
+        PROTO_ENABLED_LISTENERS_TABLE, PROTO_SUBNEG_LISTENERS_TABLE, STREAM_FILTER_INBOUND_TABLE,
+        STREAM_FILTER_OUTBOUND_TABLE,
     },
     exec_response::ExecResponse,
 };
@@ -33,7 +36,86 @@ impl Core {
     }
 }
 
+pub const API: ApiModule = lua_api!("core", "Low level telnet and system access", [
+    "enable_protocol(proto: integer)" => "Enables a telnet protocol",
+    "disable_protocol(proto: integer)" => "Disables a telnet protocol",
+    "on_protocol_enabled(callback: fun(proto: integer))" => "Adds a callback for when a protocol is enabled",
+    "on_protocol_disabled(callback: fun(proto: integer))" => "Adds a callback for when a protocol is disabled",
+    "on_echo_change(callback: fun(local_echo: boolean))" => "Adds a callback for when the server turns local echo on or off",
+    "subneg_recv(callback: fun(proto: integer, data: integer[]))" => "Adds a callback for subnegotiation data",
+    "subneg_send(proto: integer, data: integer[])" => "Sends subnegotiation data",
+    "add_stream_filter(direction: \"inbound\"|\"outbound\", callback: fun(data: string): string?) -> integer" => "Adds a filter for the raw data sent to or received from the server",
+    "remove_stream_filter(id: integer)" => "Removes a stream filter",
+    "exec(cmd: string) -> ExecResponse" => "Runs a shell command",
+    "time() -> integer" => "Returns the time in milliseconds since the epoch",
+]);
+
+#[cfg(feature = "luajit")]
+extern "C-unwind" {
+    // Exported by the LuaJIT fork mlua builds, but not declared for Lua 5.1
+    fn lua_isyieldable(state: *mut ffi::lua_State) -> c_int;
+}
+
+#[cfg(not(feature = "luajit"))]
+use ffi::lua_isyieldable;
+
+/// Instructions between the count events the debugger uses to abort runaway callbacks.
+const DEBUG_HOOK_COUNT: c_int = 500;
+
+/// The line and count hook of the debugger. Calls the function registered with
+/// `core._debug_hook` with whether it's a line event, the current line and whether the coroutine
+/// can yield here, and yields the coroutine if that returns true. The debugger pauses callbacks that way rather than
+/// blocking until the user resumes them.
+unsafe extern "C-unwind" fn debug_hook(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let line_event = (*ar).event == ffi::LUA_HOOKLINE;
+    // Not inside calls into C like string.gsub, and not in the hook function called below
+    let yieldable = line_event && lua_isyieldable(state) != 0;
+    ffi::lua_getinfo(state, c"l".as_ptr(), ar);
+    if ffi::lua_getfield(state, ffi::LUA_REGISTRYINDEX, DEBUG_HOOK.as_ptr()) != ffi::LUA_TFUNCTION {
+        ffi::lua_pop(state, 1);
+        return;
+    }
+    ffi::lua_pushboolean(state, line_event as c_int);
+    ffi::lua_pushinteger(state, (*ar).currentline as ffi::lua_Integer);
+    ffi::lua_pushboolean(state, yieldable as c_int);
+    ffi::lua_call(state, 3, 1);
+    let pause = ffi::lua_toboolean(state, -1) != 0;
+    ffi::lua_pop(state, 1);
+    if pause && yieldable {
+        ffi::lua_yield(state, 0);
+    }
+}
+
+/// `core._debug_hook(co, hook)` sets [debug_hook] on a coroutine, calling `hook`, or removes it
+/// when `hook` is nil.
+unsafe extern "C-unwind" fn set_debug_hook(state: *mut ffi::lua_State) -> c_int {
+    let thread = ffi::lua_tothread(state, 1);
+    if thread.is_null() {
+        return 0;
+    }
+    if ffi::lua_type(state, 2) == ffi::LUA_TFUNCTION {
+        ffi::lua_pushvalue(state, 2);
+        ffi::lua_setfield(state, ffi::LUA_REGISTRYINDEX, DEBUG_HOOK.as_ptr());
+        ffi::lua_sethook(
+            thread,
+            Some(debug_hook),
+            ffi::LUA_MASKLINE | ffi::LUA_MASKCOUNT,
+            DEBUG_HOOK_COUNT,
+        );
+    } else {
+        ffi::lua_sethook(thread, None, 0, 0);
+    }
+    0
+}
+
 impl UserData for Core {
+    fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+        // A Lua hook can't yield, so the debugger's hook is written against the C API
+        fields.add_field_function_get("_debug_hook", |ctx, _| unsafe {
+            ctx.create_c_function(set_debug_hook)
+        });
+    }
+
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("enable_protocol", |ctx, proto: u8| {
             let this_aux = ctx.globals().get::<_, AnyUserData>("core")?;
@@ -63,6 +145,14 @@ impl UserData for Core {
             ctx.set_named_registry_value(PROTO_DISABLED_LISTENERS_TABLE, table)?;
             Ok(())
         });
+        methods.add_function_mut("on_echo_change", |ctx, cb: mlua::Function| {
+            let table: Table = ctx.named_registry_value(ECHO_CHANGE_LISTENERS_TABLE)?;
+            let this_aux = ctx.globals().get::<_, AnyUserData>("core")?;
+            let mut this = this_aux.borrow_mut::<Core>()?;
+            table.set(this.next_index(), cb)?;
+            ctx.set_named_registry_value(ECHO_CHANGE_LISTENERS_TABLE, table)?;
+            Ok(())
+        });
         methods.add_function_mut("subneg_recv", |ctx, cb: mlua::Function| {
             let table: Table = ctx.named_registry_value(PROTO_SUBNEG_LISTENERS_TABLE)?;
             let this_aux = ctx.globals().get::<_, AnyUserData>("core")?;
@@ -85,6 +175,45 @@ impl UserData for Core {
                 .unwrap();
             Ok(())
         });
+        methods.add_function_mut(
+            "add_stream_filter",
+            |ctx, (direction, cb): (String, mlua::Function)| {
+                let direction = direction
+                    .parse::<StreamDirection>()
+                    .map_err(|err| mlua::Error::RuntimeError(err.to_string()))?;
+                let table_name = match direction {
+                    StreamDirection::Inbound => STREAM_FILTER_INBOUND_TABLE,
+                    StreamDirection::Outbound => STREAM_FILTER_OUTBOUND_TABLE,
+                };
+                let table: Table = ctx.named_registry_value(table_name)?;
+                let this_aux = ctx.globals().get::<_, AnyUserData>("core")?;
+                let mut this = this_aux.borrow_mut::<Core>()?;
+                let id = this.next_index();
+                table.set(id, cb)?;
+                this.main_writer
+                    .send(Event::StreamFilter(direction, true))
+                    .unwrap();
+                Ok(id)
+            },
+        );
+        methods.add_function("remove_stream_filter", |ctx, id: u32| {
+            let this_aux = ctx.globals().get::<_, AnyUserData>("core")?;
+            let this = this_aux.borrow::<Core>()?;
+            for (direction, table_name) in [
+                (StreamDirection::Inbound, STREAM_FILTER_INBOUND_TABLE),
+                (StreamDirection::Outbound, STREAM_FILTER_OUTBOUND_TABLE),
+            ] {
+                let table: Table = ctx.named_registry_value(table_name)?;
+                if table.contains_key(id)? {
+                    table.set(id, mlua::Nil)?;
+                    let active = table.pairs::<mlua::Value, mlua::Value>().next().is_some();
+                    this.main_writer
+                        .send(Event::StreamFilter(direction, active))
+                        .unwrap();
+                }
+            }
+            Ok(())
+        });
         methods.add_function(
             "exec",
             |_, cmd: String| -> Result<ExecResponse, mlua::Error> {
@@ -0,0 +1,96 @@
+use std::{net::IpAddr, sync::mpsc::Sender, thread};
+
+use mlua::{Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::{
+    event::Event,
+    io::SaveData,
+    net::{resolve, DnsSettings},
+};
+
+pub struct Dns;
+
+impl Dns {
+    pub const LUA_GLOBAL_NAME: &'static str = "dns";
+}
+
+pub const API: ApiModule = lua_api!("dns", "How host names are resolved when connecting", [
+    "settings() -> { prefer: string, servers: string[], timeout: integer }" => "Returns the DNS settings",
+    "set(options: { prefer?: string, servers?: string[], timeout?: integer })" => "Changes and saves the DNS settings",
+    "resolve(host: string)" => "Looks up a host in the background and prints its addresses in the order a connection tries them",
+]);
+
+fn parse_dns_options(opts: &Table) -> mlua::Result<DnsSettings> {
+    let mut settings = DnsSettings::load();
+    if let Some(prefer) = opts.get::<_, Option<String>>("prefer")? {
+        settings.prefer = prefer
+            .parse()
+            .map_err(|err: anyhow::Error| mlua::Error::RuntimeError(err.to_string()))?;
+    }
+    if let Some(servers) = opts.get::<_, Option<Vec<String>>>("servers")? {
+        settings.servers = servers
+            .iter()
+            .map(|server| {
+                server.parse::<IpAddr>().map_err(|_| {
+                    mlua::Error::RuntimeError(format!("Invalid name server address: {server}"))
+                })
+            })
+            .collect::<mlua::Result<_>>()?;
+    }
+    if let Some(timeout) = opts.get::<_, Option<u64>>("timeout")? {
+        settings.timeout = timeout.max(1);
+    }
+    Ok(settings)
+}
+
+/// Prints what `host` resolves to once the lookup is done.
+fn spawn_resolve_report(writer: Sender<Event>, host: String, settings: DnsSettings) {
+    thread::Builder::new()
+        .name("dns-report-thread".to_string())
+        .spawn(move || match resolve(&host, 0, &settings) {
+            Ok(resolution) => {
+                writer
+                    .send(Event::Info(format!("{host} ({}):", resolution.source)))
+                    .unwrap();
+                for (i, addr) in resolution.addresses.iter().enumerate() {
+                    let family = if addr.is_ipv6() { "IPv6" } else { "IPv4" };
+                    let first = if i == 0 { ", tried first" } else { "" };
+                    writer
+                        .send(Event::Info(format!("    {} ({family}{first})", addr.ip())))
+                        .unwrap();
+                }
+            }
+            Err(err) => writer.send(Event::Error(err.to_string())).unwrap(),
+        })
+        .unwrap();
+}
+
+impl UserData for Dns {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("settings", |ctx, ()| {
+            let settings = DnsSettings::load();
+            let table = ctx.create_table()?;
+            table.set("prefer", settings.prefer.to_string())?;
+            table.set(
+                "servers",
+                settings
+                    .servers
+                    .iter()
+                    .map(|server| server.to_string())
+                    .collect::<Vec<_>>(),
+            )?;
+            table.set("timeout", settings.timeout)?;
+            Ok(table)
+        });
+        methods.add_function("set", |_, opts: Table| {
+            parse_dns_options(&opts)?.save();
+            Ok(())
+        });
+        methods.add_function("resolve", |ctx, host: String| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            spawn_resolve_report(backend.writer, host, DnsSettings::load());
+            Ok(())
+        });
+    }
+}
@@ -1,3 +1,4 @@
+use super::api::ApiModule;
 use std::process::Output;
 
 use mlua::{UserData, UserDataMethods};
@@ -8,6 +9,12 @@ pub struct ExecResponse {
     stderr: String,
 }
 
+pub const API: ApiModule = lua_api!(class "ExecResponse", "The result of `core.exec`", [
+    ":code() -> integer?" => "Returns the exit code",
+    ":stdout() -> string" => "Returns the output",
+    ":stderr() -> string" => "Returns the error output",
+]);
+
 impl UserData for ExecResponse {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method(
@@ -0,0 +1,445 @@
+use mlua::{AnyUserData, Lua, Result as LuaResult, Table, UserData, UserDataMethods, Value};
+
+use super::{api::ApiModule, blight::Blight};
+use crate::ui::PrintableCharsIterator;
+
+const DEFAULT_WIDTH: usize = 80;
+const RESET: &str = "\x1b[0m";
+const ELLIPSIS: char = '…';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl Align {
+    fn parse(align: Option<String>) -> LuaResult<Self> {
+        match align.as_deref() {
+            None | Some("left") => Ok(Self::Left),
+            Some("right") => Ok(Self::Right),
+            Some("center") => Ok(Self::Center),
+            Some(other) => Err(mlua::Error::RuntimeError(format!(
+                "Invalid alignment: '{other}'"
+            ))),
+        }
+    }
+}
+
+/// The number of columns `s` takes on screen, ignoring ANSI escape sequences.
+fn width(s: &str) -> usize {
+    s.printable_chars().count()
+}
+
+/// Cuts `s` down to `max` columns, ending it with an ellipsis. Colors are reset before the
+/// ellipsis so a cut off escape sequence doesn't bleed into what follows.
+fn truncate(s: &str, max: usize) -> String {
+    if width(s) <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let end = s
+        .printable_char_indices()
+        .nth(max - 1)
+        .map_or(s.len(), |(offset, _)| offset);
+    let mut ret = s[..end].to_string();
+    if ret.contains('\x1b') {
+        ret.push_str(RESET);
+    }
+    ret.push(ELLIPSIS);
+    ret
+}
+
+/// Fits `s` to exactly `size` columns.
+fn pad(s: &str, size: usize, align: Align) -> String {
+    let s = truncate(s, size);
+    let fill = size - width(&s);
+    let (left, right) = match align {
+        Align::Left => (0, fill),
+        Align::Right => (fill, 0),
+        Align::Center => (fill / 2, fill - fill / 2),
+    };
+    format!("{}{s}{}", " ".repeat(left), " ".repeat(right))
+}
+
+struct TableOptions {
+    headers: Option<Vec<String>>,
+    widths: Option<Vec<Option<usize>>>,
+    align: Vec<Align>,
+    border: bool,
+    max_width: usize,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            headers: None,
+            widths: None,
+            align: vec![],
+            border: true,
+            max_width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+/// Shrinks the widest columns until the table fits in `max_width`.
+fn fit_widths(widths: &mut [usize], overhead: usize, max_width: usize) {
+    while widths.iter().sum::<usize>() + overhead > max_width {
+        match widths.iter_mut().max() {
+            Some(widest) if *widest > 1 => *widest -= 1,
+            _ => break,
+        }
+    }
+}
+
+fn border_line(widths: &[usize], left: &str, mid: &str, right: &str) -> String {
+    let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+    format!("{left}{}{right}", segments.join(mid))
+}
+
+fn render_table(rows: &[Vec<String>], opts: &TableOptions) -> Vec<String> {
+    let count = rows
+        .iter()
+        .chain(opts.headers.iter())
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0);
+    if count == 0 {
+        return vec![];
+    }
+
+    let mut widths: Vec<usize> = (0..count)
+        .map(|col| {
+            let fixed = opts
+                .widths
+                .as_ref()
+                .and_then(|widths| widths.get(col).copied().flatten());
+            fixed.unwrap_or_else(|| {
+                rows.iter()
+                    .chain(opts.headers.iter())
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+        })
+        .collect();
+    let overhead = if opts.border {
+        3 * count + 1
+    } else {
+        2 * (count - 1)
+    };
+    fit_widths(&mut widths, overhead, opts.max_width);
+
+    let render_row = |row: &Vec<String>| -> String {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(col, &w)| {
+                let cell = row.get(col).map_or("", String::as_str);
+                let align = opts.align.get(col).copied().unwrap_or(Align::Left);
+                pad(cell, w, align)
+            })
+            .collect();
+        if opts.border {
+            format!("│ {} │", cells.join(" │ "))
+        } else {
+            cells.join("  ").trim_end().to_string()
+        }
+    };
+
+    let mut lines = vec![];
+    if opts.border {
+        lines.push(border_line(&widths, "┌", "┬", "┐"));
+    }
+    if let Some(headers) = &opts.headers {
+        lines.push(render_row(headers));
+        if opts.border {
+            lines.push(border_line(&widths, "├", "┼", "┤"));
+        } else {
+            let segments: Vec<String> = widths.iter().map(|&w| "─".repeat(w)).collect();
+            lines.push(segments.join("  "));
+        }
+    }
+    lines.extend(rows.iter().map(render_row));
+    if opts.border {
+        lines.push(border_line(&widths, "└", "┴", "┘"));
+    }
+    lines
+}
+
+fn render_box(
+    text: &[String],
+    title: Option<&str>,
+    box_width: Option<usize>,
+    max_width: usize,
+) -> Vec<String> {
+    let content = text
+        .iter()
+        .map(|line| width(line))
+        .chain(title.map(|title| width(title) + 1))
+        .max()
+        .unwrap_or(0);
+    let inner = box_width
+        .unwrap_or(content)
+        .min(max_width.saturating_sub(4));
+
+    let top = match title {
+        Some(title) => {
+            let title = truncate(title, inner.saturating_sub(1));
+            let fill = inner.saturating_sub(width(&title) + 1);
+            format!("┌─ {title} {}┐", "─".repeat(fill))
+        }
+        None => format!("┌{}┐", "─".repeat(inner + 2)),
+    };
+    let mut lines = vec![top];
+    lines.extend(
+        text.iter()
+            .map(|line| format!("│ {} │", pad(line, inner, Align::Left))),
+    );
+    lines.push(format!("└{}┘", "─".repeat(inner + 2)));
+    lines
+}
+
+/// Lays `items` out in columns filled top to bottom, like `ls` does.
+fn render_columns(items: &[String], max_width: usize, gap: usize) -> Vec<String> {
+    if items.is_empty() {
+        return vec![];
+    }
+    let col_width = items
+        .iter()
+        .map(|item| width(item))
+        .max()
+        .unwrap_or(0)
+        .min(max_width);
+    let cols = ((max_width + gap) / (col_width + gap)).max(1);
+    let rows = items.len().div_ceil(cols);
+    (0..rows)
+        .map(|row| {
+            let cells: Vec<String> = items
+                .iter()
+                .skip(row)
+                .step_by(rows)
+                .map(|item| pad(item, col_width, Align::Left))
+                .collect();
+            cells.join(&" ".repeat(gap)).trim_end().to_string()
+        })
+        .collect()
+}
+
+fn cell_string(value: Value) -> LuaResult<String> {
+    match value {
+        Value::Nil => Ok(String::new()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) => Ok(s.to_str()?.to_string()),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "Unable to format a {} value",
+            other.type_name()
+        ))),
+    }
+}
+
+/// Reads a list of cells, keeping the positions of `nil` cells in rows like `{ "a", nil, "c" }`.
+fn cells(row: Table) -> LuaResult<Vec<String>> {
+    let len = row
+        .clone()
+        .pairs::<Value, Value>()
+        .filter_map(|pair| match pair {
+            Ok((Value::Integer(i), _)) if i > 0 => Some(i as usize),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    (1..=len).map(|i| cell_string(row.raw_get(i)?)).collect()
+}
+
+fn lines(text: Value) -> LuaResult<Vec<String>> {
+    match text {
+        Value::Table(table) => cells(table),
+        other => Ok(cell_string(other)?.lines().map(String::from).collect()),
+    }
+}
+
+/// The width of the output area, falling back to 80 columns before the screen is known.
+fn screen_width(lua: &Lua) -> LuaResult<usize> {
+    let blight: AnyUserData = lua.globals().get("blight")?;
+    let width = blight.borrow::<Blight>()?.screen_dimensions.0 as usize;
+    Ok(if width == 0 { DEFAULT_WIDTH } else { width })
+}
+
+fn table_options(lua: &Lua, opts: Option<Table>) -> LuaResult<TableOptions> {
+    let mut options = TableOptions {
+        max_width: screen_width(lua)?,
+        ..Default::default()
+    };
+    if let Some(opts) = opts {
+        if let Some(headers) = opts.get::<_, Option<Table>>("headers")? {
+            options.headers = Some(cells(headers)?);
+        }
+        options.widths = match opts.get::<_, Value>("widths")? {
+            Value::Nil => None,
+            Value::String(s) if s.to_str()? == "auto" => None,
+            Value::Table(widths) => Some(
+                widths
+                    .sequence_values::<Option<usize>>()
+                    .collect::<LuaResult<_>>()?,
+            ),
+            _ => {
+                return Err(mlua::Error::RuntimeError(
+                    "'widths' must be \"auto\" or a list of numbers".to_string(),
+                ))
+            }
+        };
+        options.align = match opts.get::<_, Value>("align")? {
+            Value::Nil => vec![],
+            Value::Table(align) => align
+                .sequence_values::<Option<String>>()
+                .map(|align| Align::parse(align?))
+                .collect::<LuaResult<_>>()?,
+            other => vec![Align::parse(Some(cell_string(other)?))?],
+        };
+        options.border = opts.get::<_, Option<bool>>("border")?.unwrap_or(true);
+        if let Some(max_width) = opts.get::<_, Option<usize>>("max_width")? {
+            options.max_width = max_width;
+        }
+    }
+    Ok(options)
+}
+
+pub struct Fmt {}
+
+pub const API: ApiModule = lua_api!("fmt", "ANSI aware tables, boxes and columns", [
+    "width(text: string) -> number" => "Returns the number of columns a string takes on screen",
+    "pad(text: string, width: number, align?: string) -> string" => "Pads or truncates a string to a width",
+    "truncate(text: string, width: number) -> string" => "Truncates a string to a width with an ellipsis",
+    "table(rows: table[], options?: table) -> string[]" => "Renders rows as an aligned table",
+    "box(text: string|string[], options?: table) -> string[]" => "Renders text inside a box",
+    "columns(items: string[], options?: table) -> string[]" => "Lays items out in columns",
+]);
+
+impl UserData for Fmt {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("width", |_, text: String| Ok(width(&text)));
+        methods.add_function(
+            "pad",
+            |_, (text, size, align): (String, usize, Option<String>)| {
+                Ok(pad(&text, size, Align::parse(align)?))
+            },
+        );
+        methods.add_function("truncate", |_, (text, size): (String, usize)| {
+            Ok(truncate(&text, size))
+        });
+        methods.add_function("table", |lua, (rows, opts): (Table, Option<Table>)| {
+            let rows = rows
+                .sequence_values::<Table>()
+                .map(|row| cells(row?))
+                .collect::<LuaResult<Vec<_>>>()?;
+            Ok(render_table(&rows, &table_options(lua, opts)?))
+        });
+        methods.add_function("box", |lua, (text, opts): (Value, Option<Table>)| {
+            let (title, box_width) = match &opts {
+                Some(opts) => (
+                    opts.get::<_, Option<String>>("title")?,
+                    opts.get::<_, Option<usize>>("width")?,
+                ),
+                None => (None, None),
+            };
+            Ok(render_box(
+                &lines(text)?,
+                title.as_deref(),
+                box_width,
+                screen_width(lua)?,
+            ))
+        });
+        methods.add_function("columns", |lua, (items, opts): (Table, Option<Table>)| {
+            let mut max_width = screen_width(lua)?;
+            let mut gap = 2;
+            if let Some(opts) = opts {
+                max_width = opts.get::<_, Option<usize>>("width")?.unwrap_or(max_width);
+                gap = opts.get::<_, Option<usize>>("gap")?.unwrap_or(gap);
+            }
+            Ok(render_columns(&cells(items)?, max_width, gap))
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_fmt {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_width_and_pad() {
+        let red = "\x1b[31mred\x1b[0m";
+        assert_eq!(width(red), 3);
+        assert_eq!(pad(red, 5, Align::Right), format!("  {red}"));
+        assert_eq!(pad("ab", 5, Align::Center), " ab  ");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+        assert_eq!(truncate("\x1b[31mabcdef", 3), "\x1b[31mab\x1b[0m…");
+        assert_eq!(truncate("abc", 3), "abc");
+    }
+
+    #[test]
+    fn test_table() {
+        let rows = vec![strings(&["Bob", "12"]), strings(&["Alice", "7"])];
+        let opts = TableOptions {
+            headers: Some(strings(&["Name", "Lvl"])),
+            align: vec![Align::Left, Align::Right],
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&rows, &opts),
+            vec![
+                "┌───────┬─────┐",
+                "│ Name  │ Lvl │",
+                "├───────┼─────┤",
+                "│ Bob   │  12 │",
+                "│ Alice │   7 │",
+                "└───────┴─────┘",
+            ]
+        );
+
+        let opts = TableOptions {
+            headers: Some(strings(&["Name", "Lvl"])),
+            border: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&rows, &opts),
+            vec!["Name   Lvl", "─────  ───", "Bob    12", "Alice  7"]
+        );
+    }
+
+    #[test]
+    fn test_table_fits_width() {
+        let rows = vec![strings(&["a", "a very long description"])];
+        let opts = TableOptions {
+            border: false,
+            max_width: 12,
+            ..Default::default()
+        };
+        assert_eq!(render_table(&rows, &opts), vec!["a  a very l…"]);
+    }
+
+    #[test]
+    fn test_box() {
+        assert_eq!(
+            render_box(&strings(&["hello"]), Some("Hi"), None, 80),
+            vec!["┌─ Hi ──┐", "│ hello │", "└───────┘"]
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let items = strings(&["a", "b", "c", "d", "e"]);
+        assert_eq!(render_columns(&items, 7, 2), vec!["a  c  e", "b  d"]);
+    }
+}
@@ -3,12 +3,17 @@ use mlua::{Function, Table, UserData};
 use crate::event::Event;
 
 use super::{
+    api::ApiModule,
     backend::Backend,
     constants::{BACKEND, FS_LISTENERS},
 };
 
 pub struct Fs {}
 
+pub const API: ApiModule = lua_api!("fs", "File system access", [
+    "monitor(path: string, callback: fun(event: FsEvent))" => "Watches a path for changes",
+]);
+
 impl UserData for Fs {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("monitor", |ctx, (path, cb): (String, Function)| {
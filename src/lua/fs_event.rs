@@ -1,3 +1,4 @@
+use super::api::ApiModule;
 use mlua::{UserData, UserDataFields};
 
 use crate::io::FSEvent as mFsEvent;
@@ -30,6 +31,8 @@ impl From<mFsEvent> for FSEvent {
     }
 }
 
+pub const API: ApiModule = lua_api!(class "FsEvent", "A file system change", []);
+
 impl UserData for FSEvent {
     fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("paths", |_, this| Ok(this.paths.clone()));
@@ -0,0 +1,51 @@
+use mlua::{UserData, UserDataMethods};
+
+use super::api::ApiModule;
+use crate::tools::fuzzy;
+
+pub struct Fuzzy {}
+
+pub const API: ApiModule = lua_api!("fuzzy", "Fuzzy matching", [
+    "score(pattern: string, text: string) -> integer?" => "Scores how well a text matches a pattern, nil if it doesn't match",
+    "filter(pattern: string, items: string[]) -> integer[]" => "Returns the indexes of the matching items, best match first",
+]);
+
+impl UserData for Fuzzy {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("score", |_, (pattern, text): (String, String)| {
+            Ok(fuzzy::score(&pattern, &text))
+        });
+        methods.add_function(
+            "filter",
+            |_, (pattern, items): (String, Vec<String>)| -> mlua::Result<Vec<usize>> {
+                Ok(fuzzy::filter(&pattern, &items)
+                    .into_iter()
+                    .map(|i| i + 1)
+                    .collect())
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_fuzzy {
+    use mlua::Lua;
+
+    use super::Fuzzy;
+
+    #[test]
+    fn test_filter() {
+        let lua = Lua::new();
+        lua.globals().set("fuzzy", Fuzzy {}).unwrap();
+        let indexes: Vec<usize> = lua
+            .load(r#"return fuzzy.filter("cf", { "flee", "cast fireball", "scarf" })"#)
+            .call(())
+            .unwrap();
+        assert_eq!(indexes, vec![2, 3]);
+        let score: Option<i64> = lua
+            .load(r#"return fuzzy.score("xyz", "flee")"#)
+            .call(())
+            .unwrap();
+        assert_eq!(score, None);
+    }
+}
@@ -0,0 +1,118 @@
+use mlua::{Result as LuaResult, Table, UserData, UserDataMethods};
+
+use super::{
+    api::ApiModule,
+    backend::Backend,
+    constants::{BACKEND, HELP_TOPICS},
+};
+use crate::event::Event;
+use crate::ui::HelpHandler;
+
+pub struct Help {}
+
+pub const API: ApiModule = lua_api!("help", "Help topics", [
+    "register(topic: string, markdown: string)" => "Adds a help topic",
+    "topics() -> string[]" => "Returns the names of all help topics",
+    "show(topic: string)" => "Shows a help topic",
+    "search(text: string)" => "Lists the help topics mentioning a text",
+]);
+
+impl UserData for Help {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function(
+            "register",
+            |ctx, (topic, markdown): (String, String)| -> LuaResult<()> {
+                if HelpHandler::builtin_topics().contains(&topic.as_str()) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Can't replace the built in help topic '{topic}'"
+                    )));
+                }
+                let topics: Table = ctx.named_registry_value(HELP_TOPICS)?;
+                topics.set(topic.as_str(), true)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::RegisterHelp(topic, markdown))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("topics", |ctx, ()| -> LuaResult<Vec<String>> {
+            let registered: Table = ctx.named_registry_value(HELP_TOPICS)?;
+            let mut topics: Vec<String> = HelpHandler::builtin_topics()
+                .into_iter()
+                .map(String::from)
+                .chain(
+                    registered
+                        .pairs::<String, bool>()
+                        .filter_map(Result::ok)
+                        .map(|(topic, _)| topic),
+                )
+                .collect();
+            topics.sort();
+            Ok(topics)
+        });
+        methods.add_function("show", |ctx, topic: String| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::ShowHelp(topic, true)).unwrap();
+            Ok(())
+        });
+        methods.add_function("search", |ctx, text: String| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::SearchHelp(text)).unwrap();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_help_topics {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use mlua::Lua;
+
+    use super::Help;
+    use crate::event::Event;
+    use crate::lua::backend::Backend;
+    use crate::lua::constants::{BACKEND, HELP_TOPICS};
+
+    fn get_lua_state() -> (Lua, Receiver<Event>) {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.set_named_registry_value(HELP_TOPICS, lua.create_table().unwrap())
+            .unwrap();
+        lua.globals().set("help", Help {}).unwrap();
+        (lua, reader)
+    }
+
+    #[test]
+    fn test_register() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r##"help.register("mapper", "# Mapper")"##)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::RegisterHelp(
+                "mapper".to_string(),
+                "# Mapper".to_string()
+            ))
+        );
+        let topics: Vec<String> = lua.load("return help.topics()").call(()).unwrap();
+        assert!(topics.contains(&"mapper".to_string()));
+        assert!(topics.contains(&"trigger".to_string()));
+        assert!(lua.load(r#"help.register("trigger", "")"#).exec().is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"help.search("scrollback")"#).exec().unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SearchHelp("scrollback".to_string()))
+        );
+    }
+}
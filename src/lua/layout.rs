@@ -0,0 +1,181 @@
+use mlua::{Function, Result as LuaResult, Table, UserData, UserDataMethods};
+
+use super::{
+    api::ApiModule,
+    backend::Backend,
+    constants::{BACKEND, LAYOUT_REGIONS},
+};
+use crate::event::Event;
+use crate::model::{Region, RegionPosition};
+
+pub struct Layout {}
+
+pub const API: ApiModule = lua_api!("layout", "Screen regions", [
+    "region(name: string, options?: table)" => "Adds or updates a region",
+    "remove(name: string)" => "Removes a region",
+    "print(name: string, lines: string[])" => "Sets the content of a region",
+    "redraw()" => "Redraws the screen",
+    "regions() -> string[]" => "Returns the region names",
+    "overlay(lines?: string[])" => "Shows lines in a box over the output, or removes the box",
+]);
+
+impl UserData for Layout {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "region",
+            |ctx, (name, opts): (String, Option<Table>)| -> LuaResult<()> {
+                let (height, position, draw) = if let Some(opts) = opts {
+                    (
+                        opts.get::<_, Option<u16>>("height")?.unwrap_or(1),
+                        opts.get::<_, Option<String>>("position")?
+                            .unwrap_or_else(|| "bottom".to_string()),
+                        opts.get::<_, Option<Function>>("draw")?,
+                    )
+                } else {
+                    (1, "bottom".to_string(), None)
+                };
+                let regions: Table = ctx.named_registry_value(LAYOUT_REGIONS)?;
+                if let Some(draw) = draw {
+                    regions.set(name.as_str(), draw)?;
+                } else {
+                    regions.set(name.as_str(), true)?;
+                }
+                let region = Region::new(&name, RegionPosition::from(position.as_str()), height);
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend.writer.send(Event::SetRegion(region)).unwrap();
+                backend.writer.send(Event::DrawRegions).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("remove", |ctx, name: String| -> LuaResult<()> {
+            let regions: Table = ctx.named_registry_value(LAYOUT_REGIONS)?;
+            regions.set(name.as_str(), mlua::Nil)?;
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::RemoveRegion(name)).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "print",
+            |ctx, (name, lines): (String, Vec<String>)| -> LuaResult<()> {
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::RegionLines(name, lines))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("redraw", |ctx, ()| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::DrawRegions).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "overlay",
+            |ctx, lines: Option<Vec<String>>| -> LuaResult<()> {
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend.writer.send(Event::Overlay(lines)).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("regions", |ctx, ()| -> LuaResult<Vec<String>> {
+            let regions: Table = ctx.named_registry_value(LAYOUT_REGIONS)?;
+            let mut names = regions
+                .pairs::<String, mlua::Value>()
+                .filter_map(Result::ok)
+                .map(|(name, _)| name)
+                .collect::<Vec<String>>();
+            names.sort();
+            Ok(names)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_layout {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use mlua::Lua;
+
+    use super::Layout;
+    use crate::event::Event;
+    use crate::lua::backend::Backend;
+    use crate::lua::constants::{BACKEND, LAYOUT_REGIONS};
+    use crate::model::{Region, RegionPosition};
+
+    fn get_lua_state() -> (Lua, Receiver<Event>) {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.set_named_registry_value(LAYOUT_REGIONS, lua.create_table().unwrap())
+            .unwrap();
+        lua.globals().set("layout", Layout {}).unwrap();
+        (lua, reader)
+    }
+
+    #[test]
+    fn test_region() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"layout.region("hud", { height = 2, position = "top" })"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetRegion(Region::new("hud", RegionPosition::Top, 2)))
+        );
+        assert_eq!(reader.recv(), Ok(Event::DrawRegions));
+
+        lua.load(r#"layout.region("bar")"#).exec().unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::SetRegion(Region::new(
+                "bar",
+                RegionPosition::Bottom,
+                1
+            )))
+        );
+        assert_eq!(reader.recv(), Ok(Event::DrawRegions));
+        let names: Vec<String> = lua.load("return layout.regions()").call(()).unwrap();
+        assert_eq!(names, vec!["bar", "hud"]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"layout.remove("hud")"#).exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::RemoveRegion("hud".to_string())));
+    }
+
+    #[test]
+    fn test_print() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"layout.print("hud", { "line 1", "line 2" })"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::RegionLines(
+                "hud".to_string(),
+                vec!["line 1".to_string(), "line 2".to_string()]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"layout.overlay({ "attack", "flee" })"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Overlay(Some(vec![
+                "attack".to_string(),
+                "flee".to_string()
+            ])))
+        );
+        lua.load("layout.overlay()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Overlay(None)));
+    }
+}
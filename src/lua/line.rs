@@ -1,3 +1,4 @@
+use super::api::ApiModule;
 use mlua::{FromLua, UserData, UserDataMethods};
 
 use crate::model::Line as mLine;
@@ -17,6 +18,27 @@ impl From<mLine> for Line {
     }
 }
 
+pub const API: ApiModule = lua_api!(class "Line", "A line of output or input", [
+    ":line() -> string" => "Returns the line without color codes",
+    ":raw() -> string" => "Returns the line with color codes",
+    ":gag(flag?: boolean) -> boolean" => "Sets and returns whether the line is hidden",
+    ":tts_gag(flag?: boolean) -> boolean" => "Sets and returns whether the line is skipped by text-to-speech",
+    ":tts_interrupt(flag?: boolean) -> boolean" => "Sets and returns whether the line interrupts text-to-speech",
+    ":skip_log(flag?: boolean) -> boolean" => "Sets and returns whether the line is left out of the log",
+    ":skip_history(flag?: boolean) -> boolean" => "Sets and returns whether the line is left out of the command history",
+    ":matched(flag?: boolean) -> boolean" => "Sets and returns whether the line has been matched",
+    ":prompt() -> boolean" => "Checks if the line is a prompt",
+    ":replace(line: string)" => "Replaces the content of the line",
+    ":source() -> string?" => "Returns the source of the line",
+    ":literal() -> boolean" => "Checks if the line is sent as typed, without aliases",
+    ":origin() -> string" => "Returns where a line of output comes from, server, client, script or plugin:<name>",
+    ":set_tag(key: string, value?: string)" => "Sets a tag",
+    ":tag(key: string) -> string?" => "Returns a tag",
+    ":tags() -> table<string, string>" => "Returns all tags",
+    ":replacement() -> string?" => "Returns the replacement of the line",
+    ":timestamp() -> integer" => "Returns when the line was received, in seconds since the epoch",
+]);
+
 impl UserData for Line {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method("line", |_, this, _: ()| -> mlua::Result<String> {
@@ -58,6 +80,15 @@ impl UserData for Line {
                 Ok(this.inner.flags.skip_log)
             },
         );
+        methods.add_method_mut(
+            "skip_history",
+            |_, this, val: Option<bool>| -> mlua::Result<bool> {
+                if let Some(val) = val {
+                    this.inner.flags.skip_history = val;
+                }
+                Ok(this.inner.flags.skip_history)
+            },
+        );
         methods.add_method("prompt", |_, this, _: ()| -> mlua::Result<bool> {
             Ok(this.inner.flags.prompt)
         });
@@ -75,6 +106,33 @@ impl UserData for Line {
             Ok(())
         });
         methods.add_method("source", |_, this, ()| Ok(this.inner.flags.source.clone()));
+        methods.add_method("literal", |_, this, ()| Ok(this.inner.flags.literal));
+        methods.add_method("origin", |_, this, ()| {
+            Ok(this.inner.flags.origin.to_string())
+        });
+        methods.add_method("timestamp", |_, this, ()| {
+            Ok(this.inner.timestamp().timestamp())
+        });
+        methods.add_method_mut(
+            "set_tag",
+            |_, this, (key, value): (String, Option<String>)| -> mlua::Result<()> {
+                if let Some(value) = value {
+                    this.inner.set_tag(&key, &value);
+                } else {
+                    this.inner.remove_tag(&key);
+                }
+                Ok(())
+            },
+        );
+        methods.add_method(
+            "tag",
+            |_, this, key: String| -> mlua::Result<Option<String>> {
+                Ok(this.inner.tag(&key).map(|val| val.to_string()))
+            },
+        );
+        methods.add_method("tags", |ctx, this, ()| -> mlua::Result<mlua::Table> {
+            ctx.create_table_from(this.inner.flags.tags.clone())
+        });
         methods.add_method(
             "replacement",
             |_, this, _: ()| -> mlua::Result<Option<String>> { Ok(this.replacement.clone()) },
@@ -153,6 +211,19 @@ mod test_lua_line {
         assert!(line.inner.flags.skip_log);
     }
 
+    #[test]
+    fn test_skip_history() {
+        test_lua!("test_line" => test_line());
+
+        assert_lua_bool!("test_line:skip_history()", false);
+        let line: Line = global!("test_line");
+        assert!(!line.inner.flags.skip_history);
+
+        assert_lua_bool!("test_line:skip_history(true)", true);
+        let line: Line = global!("test_line");
+        assert!(line.inner.flags.skip_history);
+    }
+
     #[test]
     fn test_matched() {
         test_lua!("test_line" => test_line());
@@ -172,6 +243,36 @@ mod test_lua_line {
         assert_lua_bool!("test_line:prompt()", false);
     }
 
+    #[test]
+    fn test_tags() {
+        test_lua!("test_line" => test_line());
+        assert_lua!(Option<String>, "test_line:tag(\"channel\")", None);
+        run_lua!("test_line:set_tag(\"channel\", \"gossip\")");
+        assert_lua!(
+            Option<String>,
+            "test_line:tag(\"channel\")",
+            Some("gossip".to_string())
+        );
+        assert_lua_string!("test_line:tags().channel", "gossip");
+        let line: Line = global!("test_line");
+        assert_eq!(line.inner.tag("channel"), Some("gossip"));
+
+        run_lua!("test_line:set_tag(\"channel\", nil)");
+        let line: Line = global!("test_line");
+        assert_eq!(line.inner.tag("channel"), None);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        test_lua!("test_line" => test_line());
+        let line: Line = global!("test_line");
+        assert_lua!(
+            i64,
+            "test_line:timestamp()",
+            line.inner.timestamp().timestamp()
+        );
+    }
+
     #[test]
     fn test_replace() {
         test_lua!("test_line" => test_line());
@@ -1,7 +1,7 @@
-use mlua::{UserData, UserDataMethods};
+use mlua::{FromLua, Table, UserData, UserDataMethods, Value};
 
-use super::{backend::Backend, constants::BACKEND};
-use crate::event::Event;
+use super::{api::ApiModule, backend::Backend, constants::BACKEND, regex::Regex};
+use crate::{event::Event, io::internal_log, model::Regex as MRegex};
 
 pub struct Log {}
 
@@ -11,6 +11,37 @@ impl Log {
     }
 }
 
+pub const API: ApiModule = lua_api!("log", "Logging of the session", [
+    "start(name: string)" => "Starts logging to a file",
+    "stop()" => "Stops logging",
+    "start_recording(name?: string, options?: { scrub?: (string|Regex)[] })" => "Starts recording the session to an asciicast file",
+    "stop_recording()" => "Stops recording",
+    "set_level(level?: string, module?: string)" => "Sets the level of Blightmud's internal log, for a module or the default",
+    "levels() -> { default: string, modules: table<string, string> }" => "Returns the levels of the internal log",
+    "recent(count?: integer) -> { time: string, level: string, target: string, message: string }[]" => "Returns the most recent entries of the internal log",
+]);
+
+/// Number of entries returned by `log.recent()` without a count.
+const DEFAULT_RECENT: usize = 50;
+
+fn scrub_patterns(ctx: &mlua::Lua, options: Option<Table>) -> mlua::Result<Vec<MRegex>> {
+    let scrub: Option<Table> = match options {
+        Some(options) => options.get("scrub")?,
+        None => None,
+    };
+    let Some(scrub) = scrub else {
+        return Ok(vec![]);
+    };
+    scrub
+        .sequence_values::<Value>()
+        .map(|value| match value? {
+            Value::String(pattern) => MRegex::new(pattern.to_str()?, None)
+                .map_err(|err| mlua::Error::RuntimeError(err.to_string())),
+            value => Ok(Regex::from_lua(value, ctx)?.default_engine()?),
+        })
+        .collect()
+}
+
 impl UserData for Log {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("start", |ctx, name: String| {
@@ -26,6 +57,62 @@ impl UserData for Log {
             backend.writer.send(Event::StopLogging).unwrap();
             Ok(())
         });
+        methods.add_function(
+            "start_recording",
+            |ctx, (name, options): (Option<String>, Option<Table>)| {
+                let scrub = scrub_patterns(ctx, options)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::StartRecording(name, scrub))
+                    .unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("stop_recording", |ctx, _: ()| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::StopRecording).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "set_level",
+            |_, (level, module): (Option<String>, Option<String>)| {
+                let level = level
+                    .map(|level| level.parse::<log::LevelFilter>())
+                    .transpose()
+                    .map_err(|_| {
+                        mlua::Error::RuntimeError(
+                            "Invalid log level, expected one of: off, error, warn, info, debug, trace"
+                                .to_string(),
+                        )
+                    })?;
+                internal_log::set_level(module.as_deref(), level);
+                Ok(())
+            },
+        );
+        methods.add_function("levels", |ctx, ()| {
+            let (default, modules) = internal_log::levels();
+            let result = ctx.create_table()?;
+            result.set("default", default.to_string().to_lowercase())?;
+            let module_levels = ctx.create_table()?;
+            for (module, level) in modules {
+                module_levels.set(module, level.to_string().to_lowercase())?;
+            }
+            result.set("modules", module_levels)?;
+            Ok(result)
+        });
+        methods.add_function("recent", |ctx, count: Option<usize>| {
+            let entries = ctx.create_table()?;
+            for entry in internal_log::recent(count.unwrap_or(DEFAULT_RECENT)) {
+                let table = ctx.create_table()?;
+                table.set("time", entry.time)?;
+                table.set("level", entry.level.to_string().to_lowercase())?;
+                table.set("target", entry.target)?;
+                table.set("message", entry.message)?;
+                entries.push(table)?;
+            }
+            Ok(entries)
+        });
     }
 }
 
@@ -38,6 +125,7 @@ mod test_log {
     use crate::{
         event::Event,
         lua::{backend::Backend, constants::BACKEND},
+        model::Regex as MRegex,
     };
 
     use super::Log;
@@ -62,8 +150,47 @@ mod test_log {
         );
     }
 
+    #[test]
+    fn test_set_level() {
+        let lua = Lua::new();
+        lua.globals().set("log", Log::new()).unwrap();
+        assert!(lua.load(r#"log.set_level("loud", "net")"#).exec().is_err());
+        lua.load(r#"log.set_level("trace", "test_set_level")"#)
+            .exec()
+            .unwrap();
+        let level: String = lua
+            .load("return log.levels().modules.test_set_level")
+            .eval()
+            .unwrap();
+        assert_eq!(level, "trace");
+        lua.load(r#"log.set_level(nil, "test_set_level")"#)
+            .exec()
+            .unwrap();
+        assert!(lua
+            .load("return log.levels().modules.test_set_level == nil")
+            .eval::<bool>()
+            .unwrap());
+    }
+
     #[test]
     fn test_stop() {
         assert_event("log.stop()", Event::StopLogging);
     }
+
+    #[test]
+    fn test_start_recording() {
+        assert_event("log.start_recording()", Event::StartRecording(None, vec![]));
+        assert_event(
+            "log.start_recording(\"fight\", { scrub = { \"^secret\" } })",
+            Event::StartRecording(
+                Some("fight".to_string()),
+                vec![MRegex::new("^secret", None).unwrap()],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_stop_recording() {
+        assert_event("log.stop_recording()", Event::StopRecording);
+    }
 }
@@ -1,25 +1,60 @@
 use super::fs_event::FSEvent;
 use super::{
-    audio::Audio, backend::Backend, blight::*, line::Line as LuaLine, plugin, script::Script,
-    socket::SocketLib, tts::Tts,
+    audio::Audio,
+    backend::Backend,
+    blight::*,
+    line::Line as LuaLine,
+    plugin,
+    script::{self, Script},
+    socket::SocketLib,
+    tts::Tts,
 };
 use super::{constants::*, core::Core, ui_event::UiEvent};
 use super::{
     log::Log, mud::Mud, regex::RegexLib, settings::Settings, store::Store, timer::Timer, util::*,
 };
+use crate::io::SaveData;
+use crate::lua::activity::Activity;
+use crate::lua::backup::Backup;
+use crate::lua::dns::Dns;
+use crate::lua::fmt::Fmt;
 use crate::lua::fs::Fs;
+use crate::lua::fuzzy::Fuzzy;
+use crate::lua::help::Help;
+use crate::lua::layout::Layout;
+use crate::lua::memory::{self, Memory};
+use crate::lua::metrics::Metrics;
+use crate::lua::origin::OriginStyler;
+use crate::lua::palette::Palette;
 use crate::lua::prompt::Prompt;
 use crate::lua::prompt_mask::PromptMask;
+use crate::lua::reader::Reader;
+use crate::lua::relay::Relay;
+use crate::lua::screen::Screen;
+use crate::lua::sent::Sent;
+use crate::lua::snapshot::Snapshots;
+use crate::lua::sounds::Sounds;
 #[cfg(feature = "spellcheck")]
 use crate::lua::spellcheck::{self, Spellchecker};
-use crate::model::Completions;
+use crate::lua::vars::Vars;
+use crate::lua::web::Web;
+use crate::lua::widget::Widget;
+use crate::model::{Completions, EventMetrics, GcSettings, SentLog};
+use crate::net::{StreamDirection, WebMirror};
 use crate::tools::util::expand_tilde;
 use crate::{event::Event, lua::servers::Servers, model, model::Line};
 use anyhow::Result;
+use lazy_static::lazy_static;
 use log::{debug, info};
-use mlua::{AnyUserData, FromLua, Lua, Result as LuaResult, Value};
+use mlua::{
+    AnyUserData, AnyUserDataExt, ChunkMode, FromLua, Lua, Result as LuaResult, TableExt, Value,
+};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
 use std::{fs::File, sync::mpsc::Sender};
 
 pub struct LuaScriptBuilder {
@@ -27,6 +62,10 @@ pub struct LuaScriptBuilder {
     dimensions: (u16, u16),
     reader_mode: bool,
     tts_enabled: bool,
+    metrics: EventMetrics,
+    sent_log: SentLog,
+    web: WebMirror,
+    activity: model::Activity,
 }
 
 impl LuaScriptBuilder {
@@ -36,6 +75,10 @@ impl LuaScriptBuilder {
             dimensions: (0, 0),
             reader_mode: false,
             tts_enabled: false,
+            metrics: EventMetrics::default(),
+            sent_log: SentLog::default(),
+            web: WebMirror::default(),
+            activity: model::Activity::default(),
         }
     }
 
@@ -54,24 +97,102 @@ impl LuaScriptBuilder {
         self
     }
 
+    pub fn metrics(mut self, metrics: EventMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn web(mut self, web: WebMirror) -> Self {
+        self.web = web;
+        self
+    }
+
+    pub fn activity(mut self, activity: model::Activity) -> Self {
+        self.activity = activity;
+        self
+    }
+
     pub fn build(self) -> LuaScript {
         let main_writer = self.writer.clone();
         let reader_mode = self.reader_mode;
         let tts_enabled = self.tts_enabled;
+        let metrics = self.metrics.clone();
+        let sent_log = self.sent_log.clone();
+        let web = self.web.clone();
+        let activity = self.activity.clone();
         LuaScript {
             state: create_default_lua_state(self, None),
             writer: main_writer,
             tts_enabled,
             reader_mode,
+            metrics,
+            sent_log,
+            web,
+            activity,
         }
     }
 }
 
+/// A trigger as listed to external tools, see [LuaScript::triggers].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TriggerInfo {
+    pub id: u32,
+    pub group: Option<String>,
+    pub regex: String,
+    /// The regex options as inline flags, eg. `im`.
+    pub flags: String,
+    pub enabled: bool,
+    pub gag: bool,
+    pub raw: bool,
+    pub prompt: bool,
+    pub count: Option<u32>,
+}
+
 pub struct LuaScript {
     state: Lua,
     writer: Sender<Event>,
     tts_enabled: bool,
     reader_mode: bool,
+    metrics: EventMetrics,
+    sent_log: SentLog,
+    web: WebMirror,
+    activity: model::Activity,
+}
+
+lazy_static! {
+    /// Bytecode of the bundled lua resources, compiled by the first state that loads them and
+    /// shared with every later state so `script.reset()` doesn't parse them again.
+    static ref COMPILED_RESOURCES: Mutex<HashMap<&'static str, Vec<u8>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Loads a bundled lua resource as a function, from its compiled bytecode if it has been loaded
+/// before.
+fn load_resource<'lua>(
+    state: &'lua Lua,
+    name: &'static str,
+    source: &'static str,
+) -> LuaResult<mlua::Function<'lua>> {
+    let bytecode = COMPILED_RESOURCES.lock().unwrap().get(name).cloned();
+    if let Some(bytecode) = bytecode {
+        return state
+            .load(bytecode)
+            .set_name(name)
+            .set_mode(ChunkMode::Binary)
+            .into_function();
+    }
+    let function = state.load(source).set_name(name).into_function()?;
+    COMPILED_RESOURCES
+        .lock()
+        .unwrap()
+        .insert(name, function.dump(false));
+    Ok(function)
+}
+
+/// Logs how long a stage of creating a lua state took, seen with `--verbose`.
+fn log_stage(stage: &str, start: &mut Instant) {
+    debug!("Lua state: {} took {:?}", stage, start.elapsed());
+    *start = Instant::now();
 }
 
 /// load the provided filenames in the lua resource directory as named chunks that get called,
@@ -82,10 +203,12 @@ macro_rules! lua_global_resources {
         $(
             let name = Path::new($path).file_name().unwrap().to_string_lossy();
             let name = name.strip_suffix(".lua");
-            let value = $state
-                .load(include_str!(concat!("../../resources/lua/", $path)))
-                .set_name(concat!("../../resources/lua/", $path))
-                .call::<_, mlua::Value>(())?;
+            let value = load_resource(
+                &$state,
+                concat!("../../resources/lua/", $path),
+                include_str!(concat!("../../resources/lua/", $path)),
+            )?
+            .call::<_, mlua::Value>(())?;
             $globals.set(name, value)?;
         )+
     }};
@@ -95,9 +218,12 @@ macro_rules! lua_global_resources {
 macro_rules! lua_resources {
     ($state: ident, $($path: expr),+ $(,)?) => {{
         $(
-            $state.load(include_str!(concat!("../../resources/lua/", $path)))
-                .set_name($path)
-                .exec()?;
+            load_resource(
+                &$state,
+                $path,
+                include_str!(concat!("../../resources/lua/", $path)),
+            )?
+            .call::<_, ()>(())?;
         )+
     }};
 }
@@ -116,6 +242,8 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
 
     blight.screen_dimensions = builder.dimensions;
     blight.core_mode(true);
+    let created = Instant::now();
+    let mut start = created;
     let result: LuaResult<()> = (|| {
         let globals = state.globals();
 
@@ -123,6 +251,7 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
         state.set_named_registry_value(MUD_OUTPUT_LISTENER_TABLE, state.create_table()?)?;
         state.set_named_registry_value(MUD_INPUT_LISTENER_TABLE, state.create_table()?)?;
         state.set_named_registry_value(BLIGHT_ON_QUIT_LISTENER_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(BLIGHT_ON_KEY_LISTENER_TABLE, state.create_table()?)?;
         state.set_named_registry_value(
             BLIGHT_ON_DIMENSIONS_CHANGE_LISTENER_TABLE,
             state.create_table()?,
@@ -130,21 +259,43 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
         state.set_named_registry_value(TIMED_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(TIMED_CALLBACK_TABLE_CORE, state.create_table()?)?;
         state.set_named_registry_value(TIMED_NEXT_ID, 1)?;
+        state.set_named_registry_value(SEARCH_CALLBACK_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(SEARCH_NEXT_ID, 1)?;
         state.set_named_registry_value(TIMER_TICK_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(TIMER_TICK_CALLBACK_TABLE_CORE, state.create_table()?)?;
         state.set_named_registry_value(COMMAND_BINDING_TABLE, state.create_table()?)?;
         state.set_named_registry_value(PROTO_ENABLED_LISTENERS_TABLE, state.create_table()?)?;
         state.set_named_registry_value(PROTO_DISABLED_LISTENERS_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(ECHO_CHANGE_LISTENERS_TABLE, state.create_table()?)?;
         state.set_named_registry_value(PROTO_SUBNEG_LISTENERS_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(STREAM_FILTER_INBOUND_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(STREAM_FILTER_OUTBOUND_TABLE, state.create_table()?)?;
         state.set_named_registry_value(ON_CONNECTION_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(ON_DISCONNECT_CALLBACK_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(ON_STALL_CALLBACK_TABLE, state.create_table()?)?;
+        state
+            .set_named_registry_value(REPORTED_DIMENSIONS_LISTENER_TABLE, state.create_table()?)?;
         state.set_named_registry_value(COMPLETION_CALLBACK_TABLE, state.create_table()?)?;
         state.set_named_registry_value(FS_LISTENERS, state.create_table()?)?;
         state.set_named_registry_value(SCRIPT_RESET_LISTENERS, state.create_table()?)?;
+        state.set_named_registry_value(SCRIPT_ERROR_LISTENERS, state.create_table()?)?;
+        state.set_named_registry_value(SCRIPT_COMPILERS, state.create_table()?)?;
+        state.set_named_registry_value(API_VERSIONS, state.create_table()?)?;
+        state.set_named_registry_value(PLUGIN_USAGE, plugin::PluginUsage::default())?;
+        state.set_named_registry_value(LOADED_SCRIPTS, state.create_table()?)?;
+        state.set_named_registry_value(TIMER_SCRIPTS, state.create_table()?)?;
         state.set_named_registry_value(PROMPT_CONTENT, String::new())?;
         state.set_named_registry_value(PROMPT_CURSOR_INDEX, 0)?;
         state.set_named_registry_value(PROMPT_INPUT_LISTENER_TABLE, state.create_table()?)?;
+        state.set_named_registry_value(PROMPT_MASK_SEGMENTS, state.create_table()?)?;
         state.set_named_registry_value(STATUS_AREA_HEIGHT, 1)?;
+        state.set_named_registry_value(LAYOUT_REGIONS, state.create_table()?)?;
+        state.set_named_registry_value(SESSION_VARS, state.create_table()?)?;
+        state.set_named_registry_value(COMMAND_SEPARATOR, ";")?;
+        state.set_named_registry_value(SESSION_VAR_LISTENERS, state.create_table()?)?;
+        state.set_named_registry_value(HELP_TOPICS, state.create_table()?)?;
+        memory::apply_gc_settings(&state, &GcSettings::load())?;
+        log_stage("registry", &mut start);
 
         globals.set("blight", blight)?;
         globals.set("core", Core::new(writer.clone()))?;
@@ -159,27 +310,93 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
         globals.set(Store::LUA_GLOBAL_NAME, store)?;
         globals.set("plugin", plugin::Handler::new())?;
         globals.set("audio", Audio {})?;
+        globals.set(Sounds::LUA_GLOBAL_NAME, Sounds::load())?;
+        globals.set(
+            Metrics::LUA_GLOBAL_NAME,
+            Metrics::new(builder.metrics.clone()),
+        )?;
+        globals.set(Memory::LUA_GLOBAL_NAME, Memory)?;
+        globals.set(Sent::LUA_GLOBAL_NAME, Sent::new(builder.sent_log.clone()))?;
+        globals.set(Web::LUA_GLOBAL_NAME, Web::new(builder.web.clone()))?;
+        globals.set(
+            Activity::LUA_GLOBAL_NAME,
+            Activity::new(builder.activity.clone()),
+        )?;
         globals.set("socket", SocketLib {})?;
+        globals.set(Dns::LUA_GLOBAL_NAME, Dns)?;
         globals.set("servers", Servers {})?;
+        globals.set(Palette::LUA_GLOBAL_NAME, Palette)?;
         globals.set("prompt", Prompt {})?;
         globals.set("prompt_mask", PromptMask {})?;
+        globals.set("layout", Layout {})?;
+        globals.set("vars", Vars {})?;
+        globals.set("snapshot", Snapshots {})?;
+        globals.set("backup", Backup {})?;
+        globals.set(Relay::LUA_GLOBAL_NAME, Relay::default())?;
+        globals.set("widget", Widget {})?;
+        globals.set("fmt", Fmt {})?;
+        globals.set("screen", Screen {})?;
+        globals.set("fuzzy", Fuzzy {})?;
+        globals.set("help", Help {})?;
+        globals.set("reader", Reader {})?;
+        globals.set(OriginStyler::LUA_GLOBAL_NAME, OriginStyler)?;
         #[cfg(feature = "spellcheck")]
         globals.set(spellcheck::LUA_GLOBAL_NAME, Spellchecker::new())?;
 
+        log_stage("modules", &mut start);
+
+        lua_resources!(state, "compat.lua");
+
+        let package: mlua::Table = globals.get("package")?;
+        // LuaJIT follows Lua 5.1 and calls the searchers loaders
+        let searchers: mlua::Table = match package.get("searchers")? {
+            mlua::Value::Table(searchers) => searchers,
+            _ => package.get("loaders")?,
+        };
+        searchers.raw_insert(2, state.create_function(script::search_compiled_module)?)?;
+        #[cfg(feature = "fennel")]
+        {
+            let preload: mlua::Table = package.get("preload")?;
+            preload.set(
+                "fennel",
+                state.create_function(|ctx, ()| {
+                    load_resource(
+                        ctx,
+                        "fennel.lua",
+                        include_str!(env!("BLIGHTMUD_FENNEL_LUA")),
+                    )?
+                    .call::<_, mlua::Value>(())
+                })?,
+            )?;
+        }
+
         lua_global_resources!(
             state,
             globals,
             "json.lua",
+            "debugger.lua",
             "trigger.lua",
-            "alias.lua",
             "search.lua",
+            "ui.lua",
+            "alias.lua",
             "history.lua",
+            "inbox.lua",
+            "finder.lua",
+            "paste.lua",
+            "protocol.lua",
             "gmcp.lua",
             "msdp.lua",
+            "activation.lua",
             "tasks.lua",
             "ttype.lua",
-            "mssp.lua"
+            "mssp.lua",
+            "login.lua",
+            "target.lua",
+            "report.lua",
+            "numpad.lua",
+            "guard.lua",
         );
+        log_stage("core scripts", &mut start);
 
         lua_resources!(
             state,
@@ -191,7 +408,9 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
             "plugins.lua",
             "telnet_charset.lua",
             "naws.lua",
+            "compilers.lua",
         );
+        log_stage("macros and plugins", &mut start);
 
         {
             let blight_aud: AnyUserData = globals.get("blight")?;
@@ -200,9 +419,11 @@ fn create_default_lua_state(builder: LuaScriptBuilder, store: Option<Store>) ->
         }
 
         lua_resources!(state, "../../resources/lua/on_state_created.lua");
+        log_stage("on_state_created", &mut start);
 
         Ok(())
     })();
+    debug!("Lua state created in {:?}", created.elapsed());
 
     if let Err(err) = result {
         output_stack_trace(&writer, &err.to_string());
@@ -223,15 +444,47 @@ impl LuaScript {
         });
     }
 
-    pub fn reset(&mut self, dimensions: (u16, u16)) -> Result<()> {
+    /// Replaces the lua state with a fresh one and loads `scripts` into it. The prompt content
+    /// carries over, and if `connection` is the live connection the `on_connect` callbacks of the
+    /// new state are called with `reset` set once the scripts are loaded, so they pick up the
+    /// session where it is.
+    pub fn reset(
+        &mut self,
+        dimensions: (u16, u16),
+        scripts: &[String],
+        connection: Option<(&str, u16)>,
+    ) -> Result<()> {
         let store = self.state.globals().get(Store::LUA_GLOBAL_NAME)?;
+        let prompt: String = self.state.named_registry_value(PROMPT_CONTENT)?;
+        let cursor: usize = self.state.named_registry_value(PROMPT_CURSOR_INDEX)?;
+        let connection_id: Option<u16> = self.state.named_registry_value(CONNECTION_ID)?;
         let builder = LuaScriptBuilder {
             writer: self.writer.clone(),
             dimensions,
             tts_enabled: self.tts_enabled,
             reader_mode: self.reader_mode,
+            metrics: self.metrics.clone(),
+            sent_log: self.sent_log.clone(),
+            web: self.web.clone(),
+            activity: self.activity.clone(),
         };
         self.state = create_default_lua_state(builder, store);
+        self.set_prompt_content(prompt, cursor);
+        let connection_id = connection_id.unwrap_or_default();
+        if connection.is_some() {
+            self.state.set_named_registry_value(IS_CONNECTED, true)?;
+            self.state
+                .set_named_registry_value(CONNECTION_ID, connection_id)?;
+        }
+        for path in scripts {
+            if let Err(err) = self.load_script(path) {
+                self.writer
+                    .send(Event::Error(format!("Failed to load file: {err}")))?;
+            }
+        }
+        if let Some((host, port)) = connection {
+            self.run_on_connect(host, port, connection_id, true);
+        }
         Ok(())
     }
 
@@ -299,6 +552,31 @@ impl LuaScript {
         });
     }
 
+    /// Evaluates the dynamic prompt segments and returns the combined output
+    /// to be rendered after the prompt input.
+    pub fn prompt_label(&self) -> String {
+        self.state
+            .named_registry_value::<Option<String>>(PROMPT_LABEL)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    pub fn prompt_segments(&self, content: &str) -> String {
+        self.exec_lua(&mut || -> LuaResult<String> {
+            let table: mlua::Table = self.state.named_registry_value(PROMPT_MASK_SEGMENTS)?;
+            let mut segments = vec![];
+            for pair in table.pairs::<String, mlua::Function>() {
+                let (_, cb) = pair?;
+                if let Some(segment) = cb.call::<_, Option<String>>(content)? {
+                    segments.push(segment);
+                }
+            }
+            Ok(segments.concat())
+        })
+        .unwrap_or_default()
+    }
+
     pub fn on_mud_output(&self, line: &mut Line) {
         if !line.flags.bypass_script {
             let mut lline = LuaLine::from(line.clone());
@@ -318,8 +596,29 @@ impl LuaScript {
         }
     }
 
+    /// Checks a line about to be sent against the guards, which hold it back until the user
+    /// confirms it. Lines kept out of the log, like passwords, aren't checked.
+    pub fn check_guard(&self, line: &Line) -> bool {
+        if line.flags.skip_log {
+            return true;
+        }
+        self.exec_lua(&mut || -> LuaResult<bool> {
+            let module: mlua::Table = self.state.globals().get("guard")?;
+            module.call_function("_check", line.line())
+        })
+        .unwrap_or(true)
+    }
+
+    /// Adds a line that went to the server to the record scripts and `/sent` read.
+    pub fn record_sent(&self, line: &Line) {
+        self.sent_log.record(line);
+    }
+
     pub fn on_mud_input(&self, line: &mut Line) {
         if !line.flags.bypass_script {
+            self.state
+                .set_named_registry_value(HANDLING_INPUT, true)
+                .ok();
             let mut lline = LuaLine::from(line.clone());
             let res = self.exec_lua(&mut || -> LuaResult<()> {
                 let table: mlua::Table =
@@ -334,12 +633,31 @@ impl LuaScript {
                 }
                 Ok(())
             });
+            self.state
+                .set_named_registry_value(HANDLING_INPUT, false)
+                .ok();
             if res.is_none() {
                 line.flags.matched = true;
             }
         }
     }
 
+    /// Notifies script error listeners. Errors raised by the listeners themselves are only logged
+    /// to avoid reporting loops.
+    pub fn on_error(&self, error: &model::ScriptError) {
+        let result = (|| -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(SCRIPT_ERROR_LISTENERS)?;
+            for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                let (_, cb) = pair?;
+                cb.call::<_, ()>((error.message.clone(), error.source.clone(), error.count))?;
+            }
+            Ok(())
+        })();
+        if let Err(err) = result {
+            debug!("Script error listener failed: {err}");
+        }
+    }
+
     pub fn on_quit(&self) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let table: mlua::Table = self
@@ -363,7 +681,7 @@ impl LuaScript {
                     let table: mlua::Table =
                         self.state.named_registry_value(TIMED_CALLBACK_TABLE)?;
                     match table.get(id)? {
-                        mlua::Value::Function(func) => func.call::<_, ()>(()),
+                        mlua::Value::Function(func) => self.call_plugin_timer(id, func, ()),
                         _ => Ok(()),
                     }
                 }
@@ -371,6 +689,39 @@ impl LuaScript {
         });
     }
 
+    /// Calls a timer or tick callback unless its plugin is suspended, adding the time it took to
+    /// the plugin's usage.
+    fn call_plugin_timer<'lua>(
+        &'lua self,
+        id: u32,
+        func: mlua::Function<'lua>,
+        args: impl mlua::IntoLuaMulti<'lua>,
+    ) -> LuaResult<()> {
+        match plugin::usage::timer_owner(&self.state, id) {
+            Some((_, true)) => Ok(()),
+            Some((name, false)) => {
+                let start = Instant::now();
+                let result = func.call::<_, ()>(args);
+                plugin::usage::record(&self.state, &name, start.elapsed());
+                result
+            }
+            None => func.call::<_, ()>(args),
+        }
+    }
+
+    /// Runs the callback registered by `blight.find_all` with the matching line numbers.
+    pub fn on_find_all(&mut self, id: u32, matches: Vec<usize>) {
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(SEARCH_CALLBACK_TABLE)?;
+            if let mlua::Value::Function(func) = table.get(id)? {
+                table.set(id, mlua::Value::Nil)?;
+                let lines: Vec<usize> = matches.iter().map(|index| index + 1).collect();
+                func.call::<_, ()>(lines)?;
+            }
+            Ok(())
+        });
+    }
+
     pub fn tick(&mut self, millis: u128) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let core_tick_table: mlua::Table = self
@@ -378,23 +729,39 @@ impl LuaScript {
                 .named_registry_value(TIMER_TICK_CALLBACK_TABLE_CORE)?;
             let tick_table: mlua::Table =
                 self.state.named_registry_value(TIMER_TICK_CALLBACK_TABLE)?;
-            let pairs = core_tick_table
+            for pair in core_tick_table
                 .pairs::<mlua::Integer, mlua::Function>()
-                .chain(tick_table.pairs::<mlua::Integer, mlua::Function>());
-            for pair in pairs.flatten() {
+                .flatten()
+            {
                 pair.1.call::<_, ()>(millis)?;
             }
+            for (id, func) in tick_table
+                .pairs::<mlua::Integer, mlua::Function>()
+                .flatten()
+            {
+                self.call_plugin_timer(id as u32, func, millis)?;
+            }
 
             Ok(())
         });
     }
 
+    /// Lets the garbage collector catch up while nothing else is going on.
+    pub fn gc_idle_step(&mut self) {
+        self.exec_lua(&mut || -> LuaResult<()> { memory::idle_step(&self.state) });
+    }
+
     pub fn remove_timed_function(&mut self, id: u32) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let core_table: mlua::Table =
                 self.state.named_registry_value(TIMED_CALLBACK_TABLE_CORE)?;
             let table: mlua::Table = self.state.named_registry_value(TIMED_CALLBACK_TABLE)?;
             core_table.set(id, mlua::Nil)?;
+            let owners: mlua::Table = self.state.named_registry_value(TIMER_SCRIPTS)?;
+            owners.set(id, mlua::Nil)?;
+            if let Some(usage) = plugin::usage::usage(&self.state) {
+                usage.borrow_mut::<plugin::PluginUsage>()?.remove_timer(id);
+            }
             table.set(id, mlua::Nil)
         });
     }
@@ -410,13 +777,109 @@ impl LuaScript {
             let package: mlua::Table = self.state.globals().get("package")?;
             let ppath = package.get::<&str, String>("path")?;
             package.set("path", format!("{dir}/?.lua;{ppath}"))?;
-            let result = self.state.load(&content).set_name(path).exec();
+            let loaded: mlua::Table = self.state.named_registry_value(LOADED_SCRIPTS)?;
+            loaded.set(script::canonical_path(path), true)?;
+            let result = script::compile(&self.state, path, content.clone())
+                .and_then(|source| self.state.load(&source).set_name(format!("@{path}")).exec());
             package.set("path", ppath)?;
             result
         });
         Ok(())
     }
 
+    /// Removes the triggers, aliases, trigger and alias groups and timers the script at `path`
+    /// added and loads it again. Anything else it registered, eg. listeners, stays.
+    pub fn reload_script(&mut self, path: &str) -> Result<()> {
+        if !Path::new(expand_tilde(path).as_ref()).is_file() {
+            anyhow::bail!("No such script: {path}");
+        }
+        let script = script::canonical_path(path);
+        self.exec_lua(&mut || -> LuaResult<()> {
+            self.remove_owned(&script, "trigger", "trigger_groups", "triggers")?;
+            self.remove_owned(&script, "alias", "alias_groups", "aliases")?;
+            self.remove_owned_timers(&script)
+        });
+        self.load_script(path)
+    }
+
+    /// Returns the loaded scripts among `paths` that `live_reload` should reload.
+    pub fn changed_scripts(&self, paths: &[std::path::PathBuf]) -> Vec<String> {
+        let exclusions = model::ReloadExclusions::load();
+        let mut changed = vec![];
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let loaded: mlua::Table = self.state.named_registry_value(LOADED_SCRIPTS)?;
+            for path in paths {
+                let path = script::canonical_path(&path.to_string_lossy());
+                if loaded.contains_key(path.as_str())?
+                    && !exclusions.excludes(&path)
+                    && !changed.contains(&path)
+                {
+                    changed.push(path);
+                }
+            }
+            Ok(())
+        });
+        changed
+    }
+
+    /// Removes the groups of a trigger or alias module added by `script`, and its entries in the
+    /// remaining groups.
+    fn remove_owned(&self, script: &str, module: &str, groups: &str, items: &str) -> LuaResult<()> {
+        let module: mlua::Table = self.state.globals().get(module)?;
+        let groups: mlua::Table = module.get(groups)?;
+        let owned_by = |table: &mlua::Table| -> LuaResult<bool> {
+            Ok(table.get::<_, Option<String>>("script")?.as_deref() == Some(script))
+        };
+        let mut owned_groups = vec![];
+        for pair in groups.clone().pairs::<mlua::Integer, mlua::Table>() {
+            let (id, group) = pair?;
+            if owned_by(&group)? {
+                owned_groups.push(id);
+                continue;
+            }
+            let entries: mlua::Table = group.get(items)?;
+            let mut owned = vec![];
+            for pair in entries.clone().pairs::<mlua::Integer, mlua::Table>() {
+                let (id, entry) = pair?;
+                if owned_by(&entry)? {
+                    owned.push(id);
+                }
+            }
+            for id in owned {
+                entries.set(id, Value::Nil)?;
+            }
+        }
+        for id in owned_groups {
+            groups.set(id, Value::Nil)?;
+        }
+        Ok(())
+    }
+
+    fn remove_owned_timers(&self, script: &str) -> LuaResult<()> {
+        let owners: mlua::Table = self.state.named_registry_value(TIMER_SCRIPTS)?;
+        let mut owned = vec![];
+        for pair in owners.clone().pairs::<u32, String>() {
+            let (id, owner) = pair?;
+            if owner == script {
+                owned.push(id);
+            }
+        }
+        let timers: mlua::Table = self.state.named_registry_value(TIMED_CALLBACK_TABLE)?;
+        let ticks: mlua::Table = self.state.named_registry_value(TIMER_TICK_CALLBACK_TABLE)?;
+        for id in owned {
+            owners.set(id, Value::Nil)?;
+            ticks.set(id, Value::Nil)?;
+            if timers.contains_key(id)? {
+                timers.set(id, Value::Nil)?;
+                self.writer.send(Event::RemoveTimer(id)).unwrap();
+            }
+            if let Some(usage) = plugin::usage::usage(&self.state) {
+                usage.borrow_mut::<plugin::PluginUsage>()?.remove_timer(id);
+            }
+        }
+        Ok(())
+    }
+
     pub fn eval(&mut self, script: &str) -> Result<()> {
         self.exec_lua(&mut || -> LuaResult<()> {
             self.state.load(script).exec()?;
@@ -426,6 +889,10 @@ impl LuaScript {
     }
 
     pub fn on_connect(&mut self, host: &str, port: u16, id: u16) {
+        self.run_on_connect(host, port, id, false);
+    }
+
+    fn run_on_connect(&mut self, host: &str, port: u16, id: u16, reset: bool) {
         self.exec_lua(&mut || -> LuaResult<()> {
             self.state.set_named_registry_value(IS_CONNECTED, true)?;
             self.state.set_named_registry_value(CONNECTION_ID, id)?;
@@ -434,7 +901,7 @@ impl LuaScript {
                 .named_registry_value(ON_CONNECTION_CALLBACK_TABLE)?;
             for pair in table.pairs::<mlua::Value, mlua::Function>() {
                 let (_, cb) = pair.unwrap();
-                cb.call::<_, ()>((host, port))?;
+                cb.call::<_, ()>((host, port, reset))?;
             }
             Ok(())
         });
@@ -443,6 +910,9 @@ impl LuaScript {
     pub fn on_disconnect(&mut self) {
         self.exec_lua(&mut || -> LuaResult<()> {
             self.state.set_named_registry_value(IS_CONNECTED, false)?;
+            self.state
+                .set_named_registry_value(COMMAND_STACKING, mlua::Nil)?;
+            self.state.set_named_registry_value(CHAR_MODE, false)?;
             let table: mlua::Table = self
                 .state
                 .named_registry_value(ON_DISCONNECT_CALLBACK_TABLE)?;
@@ -454,6 +924,17 @@ impl LuaScript {
         });
     }
 
+    pub fn on_stall(&mut self, seconds: u64) {
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(ON_STALL_CALLBACK_TABLE)?;
+            for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                let (_, cb) = pair.unwrap();
+                cb.call::<_, ()>(seconds)?;
+            }
+            Ok(())
+        });
+    }
+
     pub fn set_dimensions(&mut self, dim: (u16, u16)) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let blight_aud: AnyUserData = self.state.globals().get("blight")?;
@@ -472,6 +953,66 @@ impl LuaScript {
         });
     }
 
+    /// Returns the command separator if command stacking is enabled, either by script for the
+    /// current connection or by the `command_stacking` setting.
+    pub fn command_separator(&self, enabled: bool) -> Option<String> {
+        let stacking: Option<bool> = self.state.named_registry_value(COMMAND_STACKING).ok()?;
+        if stacking.unwrap_or(enabled) {
+            self.state.named_registry_value(COMMAND_SEPARATOR).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Calls the draw functions of all script declared layout regions.
+    pub fn draw_regions(&self, width: u16) -> Vec<(String, Vec<String>)> {
+        self.exec_lua(&mut || -> LuaResult<Vec<(String, Vec<String>)>> {
+            let table: mlua::Table = self.state.named_registry_value(LAYOUT_REGIONS)?;
+            let mut regions = vec![];
+            for pair in table.pairs::<String, mlua::Value>() {
+                let (name, draw) = pair?;
+                if let mlua::Value::Function(draw) = draw {
+                    let lines = draw.call::<_, Option<Vec<String>>>(width)?;
+                    regions.push((name, lines.unwrap_or_default()));
+                }
+            }
+            Ok(regions)
+        })
+        .unwrap_or_default()
+    }
+
+    /// Lists the triggers of every user trigger group.
+    pub fn triggers(&self) -> Vec<TriggerInfo> {
+        self.exec_lua(&mut || -> LuaResult<Vec<TriggerInfo>> {
+            let module: mlua::Table = self.state.globals().get("trigger")?;
+            let groups: mlua::Table = module.get("trigger_groups")?;
+            let mut triggers = vec![];
+            for group in groups.sequence_values::<mlua::Table>() {
+                let group = group?;
+                let name: Option<String> = group.get("name")?;
+                let entries: mlua::Table = group.call_method("get_triggers", ())?;
+                for pair in entries.pairs::<u32, mlua::Table>() {
+                    let (id, trigger) = pair?;
+                    let regex: AnyUserData = trigger.get("regex")?;
+                    triggers.push(TriggerInfo {
+                        id,
+                        group: name.clone(),
+                        regex: regex.call_method("regex", ())?,
+                        flags: regex.call_method("flags", ())?,
+                        enabled: trigger.get("enabled")?,
+                        gag: trigger.get("gag")?,
+                        raw: trigger.get("raw")?,
+                        prompt: trigger.get("prompt")?,
+                        count: trigger.get("count")?,
+                    });
+                }
+            }
+            triggers.sort_by_key(|trigger| trigger.id);
+            Ok(triggers)
+        })
+        .unwrap_or_default()
+    }
+
     pub fn set_reader_mode(&mut self, reader_mode: bool) {
         self.reader_mode = reader_mode;
         self.exec_lua(&mut || -> LuaResult<()> {
@@ -507,6 +1048,19 @@ impl LuaScript {
         });
     }
 
+    pub fn on_echo_change(&mut self, local_echo: bool) {
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self
+                .state
+                .named_registry_value(ECHO_CHANGE_LISTENERS_TABLE)?;
+            for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                let (_, cb) = pair?;
+                cb.call::<_, ()>(local_echo)?;
+            }
+            Ok(())
+        });
+    }
+
     pub fn proto_enabled(&mut self, proto: u8) {
         self.exec_lua(&mut || -> LuaResult<()> {
             let table: mlua::Table = self
@@ -533,6 +1087,31 @@ impl LuaScript {
         });
     }
 
+    /// Runs the data through all filters registered for the given direction, in the order they
+    /// were added. A filter returning `nil` leaves the data untouched.
+    pub fn filter_stream(&self, direction: StreamDirection, bytes: Vec<u8>) -> Vec<u8> {
+        let table_name = match direction {
+            StreamDirection::Inbound => STREAM_FILTER_INBOUND_TABLE,
+            StreamDirection::Outbound => STREAM_FILTER_OUTBOUND_TABLE,
+        };
+        let mut data = bytes;
+        self.exec_lua(&mut || -> LuaResult<()> {
+            let table: mlua::Table = self.state.named_registry_value(table_name)?;
+            let mut filters = table
+                .pairs::<u32, mlua::Function>()
+                .collect::<LuaResult<Vec<_>>>()?;
+            filters.sort_by_key(|(id, _)| *id);
+            for (_, cb) in filters {
+                let input = self.state.create_string(&data)?;
+                if let Some(output) = cb.call::<_, Option<mlua::String>>(input)? {
+                    data = output.as_bytes().to_vec();
+                }
+            }
+            Ok(())
+        });
+        data
+    }
+
     pub fn tab_complete(&mut self, input: &str) -> Completions {
         self.exec_lua(&mut || -> LuaResult<Completions> {
             let mut completions = Completions::default();
@@ -561,6 +1140,39 @@ impl LuaScript {
         .unwrap_or_default()
     }
 
+    /// Runs the `blight.on_key` callbacks for a key press, returning true if one of them
+    /// consumed the key.
+    pub fn on_key(&mut self, key: &str, ctrl: bool, alt: bool, shift: bool) -> bool {
+        self.exec_lua(&mut || -> LuaResult<bool> {
+            let table: mlua::Table = self
+                .state
+                .named_registry_value(BLIGHT_ON_KEY_LISTENER_TABLE)?;
+            let event = self.state.create_table()?;
+            event.set("key", key)?;
+            event.set("ctrl", ctrl)?;
+            event.set("alt", alt)?;
+            event.set("shift", shift)?;
+            for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                let (_, cb) = pair?;
+                if cb.call::<_, Option<bool>>(event.clone())? == Some(true) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })
+        .unwrap_or(false)
+    }
+
+    /// Returns the commands a bracketed paste is sent as, or `None` when no paste template or
+    /// transform is set and the text should be typed into the prompt.
+    pub fn on_paste(&mut self, text: &str, keep_blank: bool) -> Option<Vec<String>> {
+        self.exec_lua(&mut || -> LuaResult<Option<Vec<String>>> {
+            let module: mlua::Table = self.state.globals().get("paste")?;
+            module.call_function("_apply", (text, keep_blank))
+        })
+        .flatten()
+    }
+
     pub fn check_bindings(&mut self, cmd: &str) -> bool {
         let mut response = false;
         self.exec_lua(&mut || -> LuaResult<()> {
@@ -593,18 +1205,21 @@ impl LuaScript {
 
 #[cfg(test)]
 mod lua_script_tests {
+    use super::script;
     use super::LuaScript;
     use super::LuaScriptBuilder;
     use super::CONNECTION_ID;
     use crate::event::QuitMethod;
     use crate::lua::constants::TIMED_CALLBACK_TABLE;
+    use crate::model;
     use crate::model::Completions;
     use crate::model::{Connection, PromptMask, Regex};
+    use crate::net::StreamDirection;
     use crate::{event::Event, lua::regex::Regex as LReg, model::Line, PROJECT_NAME, VERSION};
     use libmudtelnet::bytes::Bytes;
     use mlua::Table;
     use std::{
-        collections::BTreeMap,
+        collections::{BTreeMap, BTreeSet},
         sync::mpsc::{channel, Receiver, Sender},
     };
 
@@ -761,6 +1376,37 @@ mod lua_script_tests {
 
         assert!(check_alias_match(&lua, Line::from("test")));
         assert!(!check_alias_match(&lua, Line::from(" test")));
+        assert!(!check_alias_match(&lua, Line::from("TEST")));
+
+        lua.state
+            .load(r#"alias.add("^north$", function () end, { icase = true })"#)
+            .exec()
+            .unwrap();
+        assert!(check_alias_match(&lua, Line::from("NORTH")));
+    }
+
+    #[test]
+    fn test_alias_sends() {
+        let (lua, reader) = get_lua();
+        lua.state
+            .load(r#"alias.add("^ko$", function () mud.send("kill orc") end)"#)
+            .exec()
+            .unwrap();
+        while reader.try_recv().is_ok() {}
+
+        assert!(check_alias_match(&lua, Line::from("ko")));
+        match reader.try_recv() {
+            Ok(Event::ServerInput(line)) => {
+                assert_eq!(line.line(), "kill orc");
+                assert!(line.flags.from_alias);
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        lua.state.load(r#"mud.send("look")"#).exec().unwrap();
+        match reader.try_recv() {
+            Ok(Event::ServerInput(line)) => assert!(!line.flags.from_alias),
+            other => panic!("Unexpected event: {other:?}"),
+        }
     }
 
     #[test]
@@ -779,6 +1425,38 @@ mod lua_script_tests {
         assert!(!check_alias_match(&lua, Line::from("test")));
     }
 
+    #[test]
+    fn test_compiled_resources() {
+        let (mut lua, _reader) = get_lua();
+        lua.reset((80, 80), &[], None).unwrap();
+        assert!(super::COMPILED_RESOURCES
+            .lock()
+            .unwrap()
+            .contains_key("../../resources/lua/finder.lua"));
+        assert!(lua
+            .state
+            .load("return type(debugger.breakpoint) == 'function'")
+            .eval::<bool>()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_globals_metatable() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+            setmetatable(_G, { __index = function (_, key) error("undefined global " .. key) end })
+            hits = 0
+            trigger.add("^hit$", {}, function () hits = hits + 1 end)
+            "#,
+            )
+            .exec()
+            .unwrap();
+        lua.on_mud_output(&mut Line::from("hit"));
+        assert_eq!(lua.state.globals().get::<_, i64>("hits").unwrap(), 1);
+    }
+
     #[test]
     fn test_dimensions() {
         let mut lua = get_lua().0;
@@ -842,6 +1520,67 @@ mod lua_script_tests {
         );
     }
 
+    #[test]
+    fn test_reported_dimensions() {
+        let (mut lua, reader) = get_lua();
+        let naws_sent = || -> Vec<Bytes> {
+            reader
+                .try_iter()
+                .filter_map(|event| match event {
+                    Event::ProtoSubnegSend(31, data) => Some(data),
+                    _ => None,
+                })
+                .collect()
+        };
+        let status_height: u16 = lua
+            .state
+            .load("return blight.status_height()")
+            .call(())
+            .unwrap();
+        let height = 78 - status_height as u8;
+
+        lua.proto_enabled(31);
+        assert_eq!(
+            naws_sent(),
+            vec![Bytes::copy_from_slice(&[0, 80, 0, height])]
+        );
+
+        lua.state
+            .load("mud.set_reported_dimensions(100, 30)")
+            .exec()
+            .unwrap();
+        assert_eq!(naws_sent(), vec![Bytes::copy_from_slice(&[0, 100, 0, 30])]);
+        let dim: (u16, u16) = lua
+            .state
+            .load("return mud.reported_dimensions()")
+            .call(())
+            .unwrap();
+        assert_eq!(dim, (100, 30));
+
+        lua.set_dimensions((120, 40));
+        assert!(naws_sent().is_empty());
+
+        lua.state
+            .load("mud.set_reported_dimensions()")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            naws_sent(),
+            vec![Bytes::copy_from_slice(&[
+                0,
+                120,
+                0,
+                38 - status_height as u8
+            ])]
+        );
+        let dim: (Option<u16>, Option<u16>) = lua
+            .state
+            .load("return mud.reported_dimensions()")
+            .call(())
+            .unwrap();
+        assert_eq!(dim, (None, None));
+    }
+
     #[test]
     fn test_version() {
         let lua = get_lua().0;
@@ -893,6 +1632,11 @@ mod lua_script_tests {
         assert_event("script.reset()", Event::ResetScript);
     }
 
+    #[test]
+    fn test_masked_prompt() {
+        assert_event("prompt.set_masked(true)", Event::MaskInput(true));
+    }
+
     #[test]
     fn test_sending() {
         assert_events(
@@ -1004,30 +1748,1095 @@ mod lua_script_tests {
     }
 
     #[test]
-    fn test_on_connect_test() {
-        let lua_code = r#"
-        mud.on_connect(function (host, port)
-            blight.output(host .. ":" .. port .. "-1")
-        end)
-        mud.on_connect(function (host, port)
-            blight.output(host .. ":" .. port .. "-2")
-        end)
-        mud.on_connect(function (host, port)
-            blight.output(host .. ":" .. port .. "-3")
-        end)
-        "#;
-
-        let (mut lua, _reader) = get_lua();
-        lua.state.load(lua_code).exec().unwrap();
-
-        lua.on_connect("test", 21, 12);
+    fn test_login_sequence() {
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        login.set_credentials("login.test:4000", "bob", "secret")
+        login.add("login.test:4000", {
+            steps = {
+                { expect = "^Name:", send = "{user}" },
+                { expect = "^Password:", send = "{password}" },
+            },
+            failure = "^Wrong password",
+        })
+        "#,
+            )
+            .exec()
+            .unwrap();
+        while reader.try_recv().is_ok() {}
+
+        lua.on_connect("login.test", 4000, 1);
+        lua.on_mud_output(&mut Line::from("Name:"));
+        let mut expected = Line::from("bob");
+        expected.flags.bypass_script = true;
+        expected.flags.source = Some("script".to_string());
+        assert_eq!(reader.try_recv(), Ok(Event::ServerInput(expected)));
+
+        lua.on_mud_output(&mut Line::from("Password:"));
+        let mut expected = Line::from("secret");
+        expected.flags.bypass_script = true;
+        expected.flags.source = Some("script".to_string());
+        expected.flags.gag = true;
+        expected.flags.skip_log = true;
+        assert_eq!(reader.try_recv(), Ok(Event::ServerInput(expected)));
         assert_eq!(
             lua.get_output_lines(),
-            [
-                Line::from("test:21-1"),
-                Line::from("test:21-2"),
-                Line::from("test:21-3"),
-            ]
+            [Line::from("[LOGIN]: Logged in as bob")]
+        );
+
+        lua.on_connect("login.test", 4000, 2);
+        lua.on_mud_output(&mut Line::from("Wrong password"));
+        assert_eq!(
+            lua.get_output_lines(),
+            [Line::from(
+                "[LOGIN]: Login sequence for 'login.test:4000' failed: Wrong password"
+            )]
+        );
+        lua.on_mud_output(&mut Line::from("Password:"));
+        assert!(reader.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_target() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        events = {}
+        target.on_death(function (name) table.insert(events, "died:" .. name) end)
+        target.on_flee(function (name) table.insert(events, "fled:" .. name) end)
+        target.death_pattern("^(\\w+) is DEAD!$")
+        target.set("rat")
+        target.add("orc")
+        target.add("goblin")
+        "#,
+            )
+            .exec()
+            .unwrap();
+        let current =
+            || -> Option<String> { lua.state.load("return target.current()").call(()).unwrap() };
+        assert_eq!(current(), Some("rat".to_string()));
+        assert_eq!(
+            lua.state
+                .load("return vars.get('target')")
+                .call::<_, String>(())
+                .unwrap(),
+            "rat"
+        );
+
+        lua.on_mud_output(&mut Line::from("rat is DEAD!"));
+        assert_eq!(current(), Some("orc".to_string()));
+        lua.state.load("target.fled()").exec().unwrap();
+        assert_eq!(current(), Some("goblin".to_string()));
+        lua.state
+            .load("vars.set('target', 'dragon')")
+            .exec()
+            .unwrap();
+        assert_eq!(current(), Some("dragon".to_string()));
+        let list: Vec<String> = lua.state.load("return target.list()").call(()).unwrap();
+        assert_eq!(list, vec!["dragon", "goblin"]);
+        let events: Vec<String> = lua.state.globals().get("events").unwrap();
+        assert_eq!(events, vec!["died:rat", "fled:orc"]);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        group = trigger.add_group()
+        group:disable()
+        vars.set("mode", "pk")
+        vars.set("wimpy", 30)
+        store.session_write("weapon", "sword")
+        snapshot.save("test_snapshot")
+        group:enable()
+        vars.set("mode", "craft")
+        store.session_write("weapon", "hammer")
+        snapshot.load("test_snapshot")
+        "#,
+            )
+            .exec()
+            .unwrap();
+        let state: (bool, String, i64, String) = lua
+            .state
+            .load(
+                r#"return group:is_enabled(), vars.get("mode"), vars.get("wimpy"),
+                store.session_read("weapon")"#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(state, (false, "pk".to_string(), 30, "sword".to_string()));
+        let names: Vec<String> = lua.state.load("return snapshot.list()").eval().unwrap();
+        assert!(names.contains(&"test_snapshot".to_string()));
+        assert!(lua
+            .state
+            .load("return snapshot.delete('test_snapshot')")
+            .eval::<bool>()
+            .unwrap());
+        assert!(lua
+            .state
+            .load("snapshot.load('test_snapshot')")
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_ui_indicators() {
+        let (mut lua, reader) = get_lua();
+        let spinner_key = "__spinner_1".to_string();
+        lua.state
+            .load(r#"spinner = ui.spinner("Downloading")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Indicator(
+                spinner_key.clone(),
+                Some("⠋ Downloading".to_string())
+            ))
+        );
+        lua.tick(100);
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Indicator(
+                spinner_key.clone(),
+                Some("⠙ Downloading".to_string())
+            ))
+        );
+        lua.state.load("spinner:done()").exec().unwrap();
+        assert_eq!(reader.recv(), Ok(Event::Indicator(spinner_key, None)));
+
+        lua.state
+            .load(r#"ui.progress("map", 50, "Importing map")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Indicator(
+                "__progress_map".to_string(),
+                Some("Importing map █████░░░░░ 50%".to_string())
+            ))
+        );
+        lua.state.load(r#"ui.progress("map", 100)"#).exec().unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Indicator("__progress_map".to_string(), None))
+        );
+
+        lua.state
+            .load(
+                r#"ui.spinner("Installing", function () tasks.yield() error("no network", 0) end)"#,
+            )
+            .exec()
+            .unwrap();
+        for millis in [200, 300, 400] {
+            lua.tick(millis);
+        }
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::Indicator("__spinner_2".to_string(), None)));
+        assert!(lua
+            .get_output_lines()
+            .iter()
+            .any(|line| line.line().contains("Installing failed: no network")));
+    }
+
+    #[test]
+    fn test_guard() {
+        let (lua, reader) = get_lua();
+        let answer = |answer: &str| {
+            let mut line = Line::from(answer);
+            line.flags.source = Some("user".to_string());
+            lua.on_mud_input(&mut line);
+        };
+        lua.state
+            .load(r#"guard.add("^give .* all$", { icase = true })"#)
+            .exec()
+            .unwrap();
+        while reader.try_recv().is_ok() {}
+
+        assert!(lua.check_guard(&Line::from("look")));
+        let command = Line::from("GIVE sword ALL");
+        assert!(!lua.check_guard(&command));
+        assert_eq!(
+            lua.prompt_label(),
+            "\x1b[33mReally send 'GIVE sword ALL'? (y/n) \x1b[0m"
+        );
+        answer("y");
+        let sent = reader.try_iter().find_map(|event| match event {
+            Event::ServerInput(line) => Some(line),
+            _ => None,
+        });
+        assert_eq!(
+            sent.map(|line| line.line().to_string()).as_deref(),
+            Some("GIVE sword ALL")
+        );
+        assert!(lua.check_guard(&command));
+        assert!(!lua.check_guard(&command));
+
+        answer("n");
+        assert!(!reader
+            .try_iter()
+            .any(|event| matches!(event, Event::ServerInput(_))));
+        let mut password = Line::from("give all");
+        password.flags.skip_log = true;
+        assert!(lua.check_guard(&password));
+    }
+
+    #[test]
+    fn test_ui_questions() {
+        let (lua, reader) = get_lua();
+        let input = |line: &str| {
+            let mut line = Line::from(line);
+            line.flags.source = Some("user".to_string());
+            lua.on_mud_input(&mut line);
+            line
+        };
+        lua.state
+            .load(
+                r#"
+        prompt.set("kill orc")
+        ui.confirm("Send password?", function (ok) confirmed = ok end)
+        ui.ask("Password:", { masked = true }, function (answer) password = answer end)
+        "#,
+            )
+            .exec()
+            .unwrap();
+        assert_eq!(lua.prompt_label(), "\x1b[33mSend password? (y/n) \x1b[0m");
+
+        assert!(input("maybe").flags.matched);
+        assert_eq!(
+            lua.state
+                .globals()
+                .get::<_, Option<bool>>("confirmed")
+                .unwrap(),
+            None
+        );
+        assert!(input("Y").flags.matched);
+        assert_eq!(
+            lua.state
+                .globals()
+                .get::<_, Option<bool>>("confirmed")
+                .unwrap(),
+            Some(true)
+        );
+        assert_eq!(lua.prompt_label(), "\x1b[33mPassword: \x1b[0m");
+        assert!(reader
+            .try_iter()
+            .any(|event| event == Event::MaskInput(true)));
+
+        assert!(input("secret").flags.matched);
+        assert_eq!(
+            lua.state.globals().get::<_, String>("password").unwrap(),
+            "secret"
+        );
+        assert_eq!(lua.prompt_label(), "");
+        let prompt: String = lua.state.load("return prompt.get()").eval().unwrap();
+        assert_eq!(prompt, "kill orc");
+        assert!(!input("look").flags.matched);
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::MaskInput(false)));
+        assert!(events.contains(&Event::RedrawPromptInput));
+    }
+
+    #[test]
+    fn test_report() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        trigger.add("^A kobold", {}, function () end)
+        trigger.add("^You hit", {}, function () end)
+        alias.add("^k$", function () end)
+        vars.set("gold", 10)
+        report.track("gold")
+        "#,
+            )
+            .exec()
+            .unwrap();
+        for line in ["A kobold hits you", "You hit a kobold", "A kobold flees"] {
+            lua.on_mud_output(&mut Line::from(line));
+        }
+        lua.on_mud_input(&mut Line::from("k"));
+        lua.on_mud_input(&mut Line::from("look"));
+        lua.state.load(r#"vars.set("gold", 25)"#).exec().unwrap();
+
+        let stats: (i64, i64, String, i64, String, i64) = lua
+            .state
+            .load(
+                r#"
+        local stats = report.get()
+        return stats.lines_received, stats.lines_sent, stats.triggers[1].pattern,
+            stats.triggers[1].hits, stats.aliases[1].pattern, stats.tracked.gold
+        "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(
+            stats,
+            (3, 1, "^A kobold".to_string(), 2, "^k$".to_string(), 15)
+        );
+
+        lua.state
+            .load("report.print() report.reset()")
+            .exec()
+            .unwrap();
+        let received: i64 = lua
+            .state
+            .load("return report.get().lines_received")
+            .eval()
+            .unwrap();
+        assert_eq!(received, 0);
+    }
+
+    #[test]
+    fn test_trigger_loop_protection() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        hits = 0
+        loop = trigger.add("^You are hungry", {}, function ()
+            hits = hits + 1
+            mud.send("eat bread")
+        end)
+        quiet = trigger.add("^You are hungry", {}, function () end)
+        trigger.loop_protection(3, 500)
+        "#,
+            )
+            .exec()
+            .unwrap();
+        for _ in 0..5 {
+            lua.on_mud_output(&mut Line::from("You are hungry"));
+        }
+        let state: (i64, bool) = lua
+            .state
+            .load("return hits, loop:is_enabled()")
+            .eval()
+            .unwrap();
+        assert_eq!(state, (4, false));
+        assert!(lua
+            .state
+            .load("return quiet:is_enabled()")
+            .eval::<bool>()
+            .unwrap());
+
+        lua.state.load("loop:enable()").exec().unwrap();
+        for i in 0..5 {
+            lua.tick(i * 200);
+            lua.on_mud_output(&mut Line::from("You are hungry"));
+        }
+        let state: (i64, bool) = lua
+            .state
+            .load("return hits, loop:is_enabled()")
+            .eval()
+            .unwrap();
+        assert_eq!(state, (9, true));
+    }
+
+    #[test]
+    fn test_paste() {
+        let (mut lua, _reader) = get_lua();
+        assert_eq!(lua.on_paste("look\n", false), None);
+
+        lua.state
+            .load(r#"paste.template("say $line!")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            lua.on_paste("hi\r\n\nthere", false),
+            Some(vec!["say hi!".to_string(), "say there!".to_string()])
+        );
+        lua.state.load(r#"paste.template("$line")"#).exec().unwrap();
+        assert_eq!(
+            lua.on_paste("hi\n\nthere", true),
+            Some(["hi", "", "there"].map(String::from).to_vec())
+        );
+        assert_eq!(lua.on_paste("look", false), None);
+
+        lua.state
+            .load(
+                r#"
+        paste.transform(function (line, index, count)
+            if index == 1 then
+                return { "mail bob", line }
+            elseif index == count then
+                return { line, "." }
+            end
+            return line
+        end)
+        "#,
+            )
+            .exec()
+            .unwrap();
+        assert_eq!(
+            lua.on_paste("a\nb\nc", false),
+            Some(["mail bob", "a", "b", "c", "."].map(String::from).to_vec())
+        );
+        lua.state.load("paste.transform()").exec().unwrap();
+        assert_eq!(lua.on_paste("look", false), None);
+    }
+
+    #[test]
+    fn test_suspended_plugin() {
+        let (mut lua, _reader) = get_lua();
+        let path = crate::CACHE_DIR.join("plugins/walker/main.lua");
+        lua.state
+            .load(
+                r#"
+        hits = 0
+        ticks = 0
+        trigger.add("^You are hungry", {}, function () hits = hits + 1 end)
+        timer.on_tick(function () ticks = ticks + 1 end)
+        "#,
+            )
+            .set_name(format!("@{}", path.to_string_lossy()))
+            .exec()
+            .unwrap();
+        lua.on_mud_output(&mut Line::from("You are hungry"));
+        lua.tick(100);
+        lua.state.load("plugin.suspend('walker')").exec().unwrap();
+        lua.on_mud_output(&mut Line::from("You are hungry"));
+        lua.tick(200);
+
+        let state: (i64, i64, u64, usize) = lua
+            .state
+            .load(
+                "local usage = plugin.usage().walker return hits, ticks, usage.calls, usage.timers",
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(state, (1, 1, 2, 1));
+    }
+
+    #[test]
+    fn test_group_activation() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        local group = trigger.add_group("necromancer")
+        group:activate_when({ server = "achaea.com", class = "Necromancer" })
+        alias.add_group("achaea"):activate_when({ server = { "achaea.com", "lusternia.com" } })
+        "#,
+            )
+            .exec()
+            .unwrap();
+        let enabled = |lua: &LuaScript| -> (bool, bool) {
+            lua.state
+                .load(
+                    r#"return trigger.get_group("necromancer"):is_enabled(),
+                    alias.get_group("achaea"):is_enabled()"#,
+                )
+                .eval()
+                .unwrap()
+        };
+        assert_eq!(enabled(&lua), (false, false));
+        lua.on_connect("Achaea.com", 23, 1);
+        assert_eq!(enabled(&lua), (false, true));
+        lua.proto_subneg(201, br#"Char.Status {"name":"Bob","class":"Necromancer"}"#);
+        assert_eq!(enabled(&lua), (true, true));
+        lua.on_disconnect();
+        assert_eq!(enabled(&lua), (false, false));
+        assert!(lua
+            .state
+            .load(r#"trigger.add_group():activate_when({ race = "elf" })"#)
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_numpad() {
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        numpad.enable()
+        numpad.set("kp5", "scan")
+        numpad.server("numpad.test:23", { keys = { kp8 = "north" } })
+        "#,
+            )
+            .exec()
+            .unwrap();
+        while reader.try_recv().is_ok() {}
+
+        let mut expected = Line::from("scan");
+        expected.flags.source = Some("script".to_string());
+        assert!(lua.check_bindings("kp5"));
+        assert_eq!(reader.try_recv(), Ok(Event::ServerInput(expected)));
+
+        lua.on_connect("numpad.test", 23, 1);
+        let mut expected = Line::from("north");
+        expected.flags.source = Some("script".to_string());
+        assert!(lua.check_bindings("kp8"));
+        assert_eq!(reader.try_recv(), Ok(Event::ServerInput(expected)));
+
+        lua.state.load("numpad.disable()").exec().unwrap();
+        assert!(!lua.check_bindings("kp8"));
+    }
+
+    #[test]
+    fn test_lua_repl() {
+        let (lua, _reader) = get_lua();
+        let input = |line: &str| {
+            let mut line = Line::from(line);
+            line.flags.source = Some("user".to_string());
+            lua.on_mud_input(&mut line);
+            line
+        };
+        lua.state
+            .load(r#"alias.add("^look$", function () aliased = true end)"#)
+            .exec()
+            .unwrap();
+
+        assert!(input("/repl").flags.matched);
+        assert!(input("look").flags.matched);
+        assert!(input("function f()").flags.matched);
+        input("return { a = 1 } end");
+        input("f()");
+        lua.get_output_lines();
+        input("_.a + 1");
+        let output = lua.get_output_lines();
+        assert_eq!(output.last().unwrap().line(), "2");
+        input("error('boom')");
+        let output = lua.get_output_lines();
+        assert!(output[0].line().contains("boom"));
+        // LuaJIT has no position for an error raised through a tail call
+        #[cfg(feature = "lua54")]
+        {
+            assert!(output[0].line().contains("repl:1: boom"));
+            assert!(output[1].line().contains("error('boom')"));
+        }
+
+        let (aliased, context): (Option<bool>, String) = lua
+            .state
+            .load("return aliased, history.get_context()")
+            .call(())
+            .unwrap();
+        assert_eq!(aliased, None);
+        assert_eq!(context, "lua");
+
+        input("/repl");
+        assert!(input("look").flags.matched);
+        let (aliased, context): (Option<bool>, String) = lua
+            .state
+            .load("return aliased, history.get_context()")
+            .call(())
+            .unwrap();
+        assert_eq!(aliased, Some(true));
+        assert_eq!(context, "default");
+    }
+
+    #[test]
+    fn test_history_excludes() {
+        let (lua, _reader) = get_lua();
+        let input = |line: &str, skip_history: bool| {
+            let mut line = Line::from(line);
+            line.flags.source = Some("user".to_string());
+            line.flags.skip_history = skip_history;
+            lua.on_mud_input(&mut line);
+        };
+        let previous = || -> String {
+            lua.state
+                .load("history.previous_command() return prompt.get()")
+                .call(())
+                .unwrap()
+        };
+        lua.state
+            .load(r#"id = history.exclude("^kill ")"#)
+            .exec()
+            .unwrap();
+
+        input("look", false);
+        input("kill orc", false);
+        input("secret", true);
+        assert_eq!(previous(), "look");
+
+        lua.state.load("history.include(id)").exec().unwrap();
+        input("kill orc", false);
+        assert_eq!(previous(), "kill orc");
+    }
+
+    #[test]
+    fn test_on_key() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                keys = {}
+                id = blight.on_key(function (event)
+                    table.insert(keys, event.key)
+                    return event.ctrl and event.key == "p"
+                end)
+                bound = false
+                blight.bind("ctrl-p", function () bound = true end)
+                "#,
+            )
+            .exec()
+            .unwrap();
+
+        assert!(!lua.on_key("a", false, false, false));
+        assert!(lua.on_key("p", true, false, false));
+        let keys: Vec<String> = lua.state.load("return keys").call(()).unwrap();
+        assert_eq!(keys, vec!["a", "p"]);
+
+        lua.state
+            .load("blight.remove_key_listener(id)")
+            .exec()
+            .unwrap();
+        assert!(!lua.on_key("p", true, false, false));
+        assert!(lua.check_bindings("ctrl-p"));
+    }
+
+    #[test]
+    fn test_ui_menu() {
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                picked = false
+                ui.menu({ "attack", "flee", "cast fireball" }, function (item, index)
+                    picked = { item, index }
+                end, { title = "Combat" })
+                "#,
+            )
+            .exec()
+            .unwrap();
+        let overlay = || {
+            reader
+                .try_iter()
+                .filter_map(|event| match event {
+                    Event::Overlay(lines) => Some(lines),
+                    _ => None,
+                })
+                .last()
+                .unwrap()
+        };
+        assert_eq!(
+            overlay(),
+            Some(vec![
+                "\x1b[1mCombat\x1b[0m".to_string(),
+                "\x1b[33m> \x1b[0m".to_string(),
+                "\x1b[7mattack\x1b[0m".to_string(),
+                "flee".to_string(),
+                "cast fireball".to_string(),
+            ])
+        );
+
+        assert!(lua.on_key("c", false, false, false));
+        assert!(lua.on_key("f", false, false, false));
+        let lines = overlay().unwrap();
+        assert_eq!(lines[1], "\x1b[33m> \x1b[0mcf");
+        assert_eq!(lines[2..], ["\x1b[7mcast fireball\x1b[0m".to_string()]);
+
+        assert!(lua.on_key("backspace", false, false, false));
+        assert!(lua.on_key("down", false, false, false));
+        assert!(!lua.on_key("c", true, false, false));
+        assert!(lua.on_key("enter", false, false, false));
+        assert_eq!(overlay(), None);
+        let (item, index): (String, u32) = lua
+            .state
+            .load("return picked[1], picked[2]")
+            .call(())
+            .unwrap();
+        assert_eq!((item.as_str(), index), ("attack", 1));
+        assert!(!lua.on_key("a", false, false, false));
+    }
+
+    #[test]
+    fn test_finder() {
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                alias.add("^kill (\\w+)$", function () end)
+                alias.add("^recall$", function () end)
+                finder.open()
+                "#,
+            )
+            .exec()
+            .unwrap();
+        for c in "recal".chars() {
+            lua.on_key(&c.to_string(), false, false, false);
+        }
+        lua.on_key("enter", false, false, false);
+        let input = reader.try_iter().find_map(|event| match event {
+            Event::ServerInput(line) => Some(line.line().to_string()),
+            _ => None,
+        });
+        assert_eq!(input, Some("recall".to_string()));
+
+        lua.state.load("finder.open()").exec().unwrap();
+        for c in "kill".chars() {
+            lua.on_key(&c.to_string(), false, false, false);
+        }
+        lua.on_key("enter", false, false, false);
+        let content: String = lua.state.load("return prompt.get()").call(()).unwrap();
+        assert_eq!(content, "kill ");
+    }
+
+    #[test]
+    fn test_server_browser() {
+        let (mut lua, reader) = get_lua();
+        let mut line = Line::from("/servers");
+        line.flags.source = Some("user".to_string());
+        lua.on_mud_input(&mut line);
+        assert!(line.flags.matched);
+        let lines = reader
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Overlay(lines) => lines,
+                _ => None,
+            })
+            .last()
+            .unwrap();
+        assert_eq!(lines[0], "\x1b[1mServers\x1b[0m");
+        assert!(lines.iter().any(|line| line.contains("Add server...")));
+        assert_eq!(lines.last().unwrap(), "Discover servers...");
+        assert!(lua.on_key("escape", false, false, false));
+    }
+
+    #[test]
+    fn test_inbox() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                inbox.clear()
+                trigger.add("^\\[Clan\\]", { important = "clan" }, function () end)
+                "#,
+            )
+            .exec()
+            .unwrap();
+
+        test_trigger("Bob tells you, 'hi'", &lua);
+        test_trigger("[Clan] Alice: raid at 8", &lua);
+        test_trigger("You are hungry.", &lua);
+        let unread = || -> i64 {
+            lua.state
+                .load("return inbox.unread_count()")
+                .call(())
+                .unwrap()
+        };
+        assert_eq!(unread(), 2);
+        let (text, source): (String, String) = lua
+            .state
+            .load("local msg = inbox.list()[2] return msg.text, msg.source")
+            .call(())
+            .unwrap();
+        assert_eq!(text, "[Clan] Alice: raid at 8");
+        assert_eq!(source, "clan");
+        let inbox_level = || -> Option<String> {
+            lua.state
+                .load(
+                    r#"
+                    for _, act in ipairs(activity.list()) do
+                        if act.buffer == "inbox" then return act.level end
+                    end
+                    "#,
+                )
+                .call(())
+                .unwrap()
+        };
+        assert_eq!(inbox_level(), Some("highlight".to_string()));
+
+        lua.state.load("inbox.mark_read(1)").exec().unwrap();
+        assert_eq!(unread(), 1);
+        lua.state.load("inbox.mark_read()").exec().unwrap();
+        assert_eq!(unread(), 0);
+        assert_eq!(inbox_level(), None);
+        lua.state.load("inbox.clear()").exec().unwrap();
+    }
+
+    #[test]
+    fn test_debugger_breakpoint() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"trigger.add("^hit$", {}, function ()
+    local a = 1
+    a = a + 1
+    result = a
+end)"#,
+            )
+            .set_name("scripts/dbg_test.lua")
+            .exec()
+            .unwrap();
+        lua.state
+            .load(r#"debugger["break"]("dbg_test.lua", 3)"#)
+            .exec()
+            .unwrap();
+        let input = |lua: &mut LuaScript, command: &str| -> String {
+            let mut line = Line::from(command);
+            line.flags.source = Some("user".to_string());
+            lua.on_mud_input(&mut line);
+            assert!(line.flags.matched);
+            lua.get_output_lines()
+                .iter()
+                .map(|line| line.line().to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+
+        // The callback is paused without holding up the line
+        lua.get_output_lines();
+        assert!(test_trigger("hit", &lua));
+        let result: Option<i32> = lua.state.globals().get("result").unwrap();
+        assert_eq!(result, None);
+        let written = lua
+            .get_output_lines()
+            .iter()
+            .map(|line| line.line().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert!(written.contains("Paused at scripts/dbg_test.lua:3"));
+        assert!(written.contains("   a = 1"));
+
+        assert!(input(&mut lua, "p a * 10").contains("a * 10 = 10"));
+        let written = input(&mut lua, "step");
+        assert!(written.contains("Paused at scripts/dbg_test.lua:4"));
+        assert!(written.contains("   a = 2"));
+        input(&mut lua, "continue");
+        let result: i32 = lua.state.globals().get("result").unwrap();
+        assert_eq!(result, 2);
+
+        // Once resumed, input goes to the mud again
+        let mut line = Line::from("look");
+        line.flags.source = Some("user".to_string());
+        lua.on_mud_input(&mut line);
+        assert!(!line.flags.matched);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let (lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+script.on_error(function (msg, source, count) last_error = { msg, source, count } end)
+script.report_error("broken")"#,
+            )
+            .set_name("@/data/plugins/foo/main.lua")
+            .exec()
+            .unwrap();
+        let error = "/data/plugins/foo/main.lua:3: broken";
+        assert_eq!(reader.recv(), Ok(Event::LuaError(error.to_string())));
+
+        let mut log = model::ErrorLog::default();
+        lua.on_error(log.push(error));
+        let (msg, source, count): (String, String, u32) = lua
+            .state
+            .load("return table.unpack(last_error)")
+            .call(())
+            .unwrap();
+        assert_eq!(msg, error);
+        assert_eq!(source, "plugin foo (main.lua:3)");
+        assert_eq!(count, 1);
+
+        lua.state
+            .load("script.on_error(function () error('loop') end)")
+            .exec()
+            .unwrap();
+        lua.on_error(log.push(error));
+        assert!(reader.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_output_origin() {
+        let (lua, _reader) = get_lua();
+        let origin = |chunk: &str| {
+            lua.state
+                .load("blight.output('hello')")
+                .set_name(chunk)
+                .exec()
+                .unwrap();
+            lua.get_output_lines()[0].flags.origin.clone()
+        };
+        let plugin = crate::CACHE_DIR.join("plugins/foo/main.lua");
+        assert_eq!(
+            origin(&format!("@{}", plugin.display())),
+            model::Origin::Plugin("foo".to_string())
+        );
+        assert_eq!(
+            origin("@/home/user/scripts/main.lua"),
+            model::Origin::Script
+        );
+        assert_eq!(origin("=repl"), model::Origin::Script);
+        assert_eq!(origin("macros.lua"), model::Origin::Client);
+    }
+
+    #[test]
+    fn test_api_version() {
+        let (lua, reader) = get_lua();
+        let run = |code: &str, chunk: &str| lua.state.load(code).set_name(chunk).exec();
+        let version = |chunk: &str| -> (u32, u32) {
+            lua.state
+                .load("local major, minor = blight.api_version() return major, minor")
+                .set_name(chunk)
+                .call(())
+                .unwrap()
+        };
+
+        assert_eq!(version("@/scripts/new/lib.lua"), (1, 2));
+        run("blight.api_version(2)", "@/scripts/new/main.lua").unwrap();
+        assert_eq!(version("@/scripts/new/lib.lua"), (2, 2));
+        assert_eq!(version("@/scripts/old.lua"), (1, 2));
+        assert!(run("blight.api_version(3)", "@/scripts/other.lua").is_err());
+
+        assert!(run("mud.connect('mud.org', 23, true)", "@/scripts/new/lib.lua").is_err());
+        run(
+            "mud.connect('mud.org', 23, true, false)",
+            "@/scripts/old.lua",
+        )
+        .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::LuaWarning(
+                "/scripts/old.lua:1: mud.connect(host, port, tls, verify) is deprecated, pass { tls = ..., verify = ... } instead".to_string()
+            ))
+        );
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::Connect(Connection::new("mud.org", 23, true, false)))
+        );
+        run(
+            "mud.connect('mud.org', 23, { tls = true })",
+            "@/scripts/new/lib.lua",
+        )
+        .unwrap();
+        run("mud.connect('mud.org', 23)", "@/scripts/old.lua").unwrap();
+        assert!(reader
+            .try_iter()
+            .all(|event| matches!(event, Event::Connect(_))));
+
+        run(
+            "ttype.set_mtts(137) ttype.set_mtts('137')",
+            "@/scripts/new/lib.lua",
+        )
+        .unwrap();
+        assert!(run("ttype.set_mtts('MTTS 137')", "@/scripts/new/lib.lua").is_err());
+    }
+
+    #[test]
+    fn test_command_separator() {
+        let (mut lua, _reader) = get_lua();
+        assert_eq!(lua.command_separator(false), None);
+        assert_eq!(lua.command_separator(true), Some(";".to_string()));
+        lua.state
+            .load("mud.command_separator('&') mud.command_stacking(true)")
+            .exec()
+            .unwrap();
+        assert_eq!(lua.command_separator(false), Some("&".to_string()));
+        lua.state
+            .load("mud.command_stacking(false)")
+            .exec()
+            .unwrap();
+        assert_eq!(lua.command_separator(true), None);
+        lua.on_disconnect();
+        assert_eq!(lua.command_separator(true), Some("&".to_string()));
+    }
+
+    #[test]
+    fn test_triggers() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+                trigger.add("^You are hungry$", { gag = true, icase = true }, function () end)
+                local group = trigger.add_group("combat")
+                group:add("^(\\w+) attacks you", { count = 2 }, function () end):disable()
+                "#,
+            )
+            .exec()
+            .unwrap();
+        let triggers = lua.triggers();
+        assert_eq!(triggers.len(), 2);
+        assert_eq!(triggers[0].regex, "^You are hungry$");
+        assert_eq!(triggers[0].flags, "i");
+        assert!(triggers[0].gag && triggers[0].enabled);
+        assert_eq!(triggers[0].group, None);
+        assert_eq!(triggers[1].group.as_deref(), Some("combat"));
+        assert_eq!(triggers[1].count, Some(2));
+        assert_eq!(triggers[1].flags, "");
+        assert!(!triggers[1].enabled);
+        assert!(test_trigger("YOU ARE HUNGRY", &lua));
+    }
+
+    #[test]
+    fn test_stream_filters() {
+        let (lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+            first = core.add_stream_filter("out", function (data) return data:upper() end)
+            core.add_stream_filter("out", function (data) return data .. "!" end)
+            core.add_stream_filter("in", function (data) return nil end)
+            "#,
+            )
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::StreamFilter(StreamDirection::Outbound, true))
+        );
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::StreamFilter(StreamDirection::Outbound, true))
+        );
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::StreamFilter(StreamDirection::Inbound, true))
+        );
+        assert_eq!(
+            lua.filter_stream(StreamDirection::Outbound, b"look".to_vec()),
+            b"LOOK!"
+        );
+        assert_eq!(
+            lua.filter_stream(StreamDirection::Inbound, vec![0xff, 0x00, 0xfa]),
+            vec![0xff, 0x00, 0xfa]
+        );
+        lua.state
+            .load("core.remove_stream_filter(first)")
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::StreamFilter(StreamDirection::Outbound, true))
+        );
+        assert_eq!(
+            lua.filter_stream(StreamDirection::Outbound, b"look".to_vec()),
+            b"look!"
+        );
+    }
+
+    #[test]
+    fn test_on_connect_test() {
+        let lua_code = r#"
+        mud.on_connect(function (host, port)
+            blight.output(host .. ":" .. port .. "-1")
+        end)
+        mud.on_connect(function (host, port)
+            blight.output(host .. ":" .. port .. "-2")
+        end)
+        mud.on_connect(function (host, port)
+            blight.output(host .. ":" .. port .. "-3")
+        end)
+        "#;
+
+        let (mut lua, _reader) = get_lua();
+        lua.state.load(lua_code).exec().unwrap();
+
+        lua.on_connect("test", 21, 12);
+        assert_eq!(
+            lua.get_output_lines(),
+            [
+                Line::from("test:21-1"),
+                Line::from("test:21-2"),
+                Line::from("test:21-3"),
+            ]
         );
         assert_eq!(
             lua.state
@@ -1035,7 +2844,7 @@ mod lua_script_tests {
                 .unwrap(),
             12
         );
-        lua.reset((100, 100)).unwrap();
+        lua.reset((100, 100), &[], None).unwrap();
         lua.state.load(lua_code).exec().unwrap();
         lua.on_connect("server", 1000, 13);
         assert_eq!(
@@ -1054,6 +2863,82 @@ mod lua_script_tests {
         );
     }
 
+    #[test]
+    fn test_reset_keeps_session() {
+        let dir = std::env::temp_dir().join(format!("blight_reset_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.lua");
+        std::fs::write(
+            &path,
+            r#"
+            connected_on_load = mud.is_connected()
+            mud.on_connect(function (host, port, reset)
+                reconnected = string.format("%s:%d %s", host, port, tostring(reset))
+            end)
+            "#,
+        )
+        .unwrap();
+
+        let (mut lua, _reader) = get_lua();
+        lua.on_connect("mud.com", 4000, 7);
+        lua.set_prompt_content("say hello".to_string(), 3);
+        let scripts = [path.to_str().unwrap().to_string()];
+        lua.reset((100, 100), &scripts, Some(("mud.com", 4000)))
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(lua
+            .state
+            .load("return mud.is_connected()")
+            .eval::<bool>()
+            .unwrap());
+        assert_eq!(
+            lua.state
+                .load("return connected_on_load, reconnected")
+                .eval::<(bool, String)>()
+                .unwrap(),
+            (true, "mud.com:4000 true".to_string())
+        );
+        assert_eq!(
+            lua.state
+                .named_registry_value::<u16>(CONNECTION_ID)
+                .unwrap(),
+            7
+        );
+        assert_eq!(
+            lua.state
+                .load("return prompt.get()")
+                .eval::<String>()
+                .unwrap(),
+            "say hello"
+        );
+        assert_eq!(
+            lua.state
+                .load("return prompt.get_cursor_pos()")
+                .eval::<usize>()
+                .unwrap(),
+            4
+        );
+
+        lua.reset((100, 100), &[], None).unwrap();
+        assert!(!lua
+            .state
+            .load("return mud.is_connected()")
+            .eval::<bool>()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_on_stall() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(r#"mud.on_stall(function (seconds) blight.output("stalled " .. seconds) end)"#)
+            .exec()
+            .unwrap();
+
+        lua.on_stall(75);
+        assert_eq!(lua.get_output_lines(), [Line::from("stalled 75")]);
+    }
+
     #[test]
     fn test_on_disconnect_test() {
         let lua_code = r#"
@@ -1080,7 +2965,7 @@ mod lua_script_tests {
                 Line::from("disconnected3"),
             ]
         );
-        lua.reset((100, 100)).unwrap();
+        lua.reset((100, 100), &[], None).unwrap();
         lua.state.load(lua_code).exec().unwrap();
         lua.on_disconnect();
         assert_eq!(
@@ -1227,6 +3112,13 @@ mod lua_script_tests {
             reader.recv().unwrap(),
             Event::ShowHelp("test1".to_string(), true)
         );
+        lua.on_mud_input(&mut Line::from("/help search some text"));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::SearchHelp("some text".to_string())
+        );
+        lua.on_mud_input(&mut Line::from("/help"));
+        assert!(matches!(reader.recv().unwrap(), Event::Overlay(Some(_))));
     }
 
     #[test]
@@ -1346,6 +3238,19 @@ mod lua_script_tests {
         assert_eq!(lua.state.globals().get::<_, u32>("subneg").unwrap(), 201);
     }
 
+    #[test]
+    fn confirm_echo_change() {
+        let (mut lua, _reader) = get_lua();
+        lua.state
+            .load(r#"core.on_echo_change(function (echo) local_echo = echo end)"#)
+            .exec()
+            .unwrap();
+        lua.on_echo_change(false);
+        assert!(!lua.state.globals().get::<_, bool>("local_echo").unwrap());
+        lua.on_echo_change(true);
+        assert!(lua.state.globals().get::<_, bool>("local_echo").unwrap());
+    }
+
     #[test]
     fn confirm_proto_subneg() {
         let (mut lua, _reader) = get_lua();
@@ -1362,6 +3267,245 @@ mod lua_script_tests {
         assert_eq!(lua.state.globals().get::<_, u32>("subneg").unwrap(), 201);
     }
 
+    #[test]
+    fn test_protocol_resync() {
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        gmcp.register("Room.Info")
+        gmcp.register("Char.Vitals")
+        gmcp.register("Comm.Channel")
+        gmcp.unregister("Comm.Channel")
+        msdp.report({ "HEALTH", "MANA" })
+        "#,
+            )
+            .exec()
+            .unwrap();
+        reader.try_iter().for_each(drop);
+
+        let subnegs = |reader: &Receiver<Event>| -> Vec<(u8, String)> {
+            reader
+                .try_iter()
+                .filter_map(|event| match event {
+                    Event::ProtoSubnegSend(proto, data) => {
+                        Some((proto, String::from_utf8_lossy(&data).to_string()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        lua.on_connect("mud.com", 4000, 1);
+        lua.proto_enabled(201);
+        lua.proto_enabled(69);
+        let sent = subnegs(&reader);
+        assert!(sent[0].1.starts_with("Core.Hello "));
+        assert_eq!(
+            sent[1],
+            (
+                201,
+                r#"Core.Supports.Set ["Char.Vitals 1","Room.Info 1"]"#.to_string()
+            )
+        );
+        assert_eq!(
+            sent.last().unwrap(),
+            &(69, "\x01REPORT\x02HEALTH\x02MANA".to_string())
+        );
+        let options: Vec<u8> = lua.state.load("return protocol.options()").eval().unwrap();
+        assert_eq!(options, vec![69, 201]);
+
+        lua.on_disconnect();
+        let options: Vec<u8> = lua.state.load("return protocol.options()").eval().unwrap();
+        assert!(options.is_empty());
+        lua.state
+            .load(r#"protocol.on_resync(function (kind) return kind ~= "gmcp" end)"#)
+            .exec()
+            .unwrap();
+        lua.on_connect("mud.com", 4000, 2);
+        lua.proto_enabled(201);
+        let sent = subnegs(&reader);
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].1.starts_with("Core.Hello "));
+    }
+
+    #[test]
+    fn test_gmcp_subscribe() {
+        let (mut lua, reader) = get_lua();
+        lua.on_connect("mud.com", 4000, 1);
+        lua.proto_enabled(201);
+        reader.try_iter().for_each(drop);
+        lua.state
+            .load(
+                r#"
+        items = {}
+        first = gmcp.subscribe("Char.Items", function (_, mod) table.insert(items, mod) end)
+        second = gmcp.subscribe("char.items", function () end)
+        third = gmcp.subscribe("Char.Items", function () end)
+        gmcp.subscribe("Room", function () end)
+        "#,
+            )
+            .exec()
+            .unwrap();
+        let supports_sets = |reader: &Receiver<Event>| -> Vec<String> {
+            reader
+                .try_iter()
+                .filter_map(|event| match event {
+                    Event::ProtoSubnegSend(201, data) => {
+                        Some(String::from_utf8_lossy(&data).to_string())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(
+            supports_sets(&reader),
+            vec![
+                r#"Core.Supports.Set ["Char.Items 1"]"#,
+                r#"Core.Supports.Set ["Char.Items 1","Room 1"]"#,
+            ]
+        );
+
+        lua.proto_subneg(201, b"Char.Items.List {}");
+        lua.proto_subneg(201, b"Char.ItemsX {}");
+        lua.proto_subneg(201, b"Room.Info {}");
+        let items: Vec<String> = lua.state.globals().get("items").unwrap();
+        assert_eq!(items, vec!["Char.Items.List"]);
+
+        let count = |lua: &LuaScript| -> Option<u32> {
+            lua.state
+                .load(r#"return gmcp.subscriptions()["Char.Items"]"#)
+                .eval()
+                .unwrap()
+        };
+        assert_eq!(count(&lua), Some(3));
+        lua.state
+            .load("gmcp.unsubscribe(first) gmcp.unsubscribe(second)")
+            .exec()
+            .unwrap();
+        assert!(supports_sets(&reader).is_empty());
+        assert_eq!(count(&lua), Some(1));
+        lua.state.load("gmcp.unsubscribe(third)").exec().unwrap();
+        assert_eq!(
+            supports_sets(&reader),
+            vec![r#"Core.Supports.Set ["Room 1"]"#]
+        );
+        assert_eq!(count(&lua), None);
+    }
+
+    #[test]
+    fn test_protocol_validation() {
+        let (mut lua, reader) = get_lua();
+        lua.on_connect("mud.com", 4000, 1);
+        let err = |lua: &LuaScript, code: &str| -> String {
+            lua.state.load(code).exec().unwrap_err().to_string()
+        };
+        assert!(err(&lua, r#"gmcp.send("Char.Health {hp: 1}")"#)
+            .contains("gmcp.send: invalid JSON for Char.Health"));
+        assert!(err(&lua, r#"gmcp.send("Char..Health {}")"#)
+            .contains("gmcp.send: invalid package name 'Char..Health'"));
+        assert!(
+            err(&lua, r#"gmcp.subscribe("Char Items", function () end)"#)
+                .contains("invalid package name")
+        );
+        assert!(err(&lua, r#"msdp.set("ROOM", "a\1b")"#)
+            .contains("string contains MSDP control characters"));
+        assert!(err(&lua, r#"msdp.report({ "HEALTH", {} })"#).contains("expected a string"));
+        assert!(reader
+            .try_iter()
+            .all(|event| !matches!(event, Event::ProtoSubnegSend(_, _))));
+
+        lua.state
+            .load(
+                r#"
+        gmcp.send("Char.Health { \"hp\": 1 }")
+        gmcp.send("Core.Ping")
+        msdp.set("ROOM", 5)
+        "#,
+            )
+            .exec()
+            .unwrap();
+        let sent: Vec<(u8, Vec<u8>)> = reader
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::ProtoSubnegSend(proto, data) => Some((proto, data.to_vec())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2], (69, b"\x01ROOM\x025".to_vec()));
+    }
+
+    #[test]
+    fn test_protocol_debug_log() {
+        let (mut lua, _reader) = get_lua();
+        lua.on_connect("mud.com", 4000, 1);
+        lua.proto_subneg(201, b"Room.Info {}");
+        lua.state
+            .load(r#"protocol.debug(true) msdp.send("HEALTH")"#)
+            .exec()
+            .unwrap();
+        lua.proto_subneg(201, b"Char.Vitals {}");
+        lua.proto_subneg(69, b"\x01HEALTH\x02100");
+        let log: Vec<(String, String, String)> = lua
+            .state
+            .load(
+                r#"
+        local ret = {}
+        for _, entry in ipairs(protocol.debug_log()) do
+            table.insert(ret, { entry.kind, entry.direction, entry.text })
+        end
+        return ret
+        "#,
+            )
+            .eval::<Vec<Table>>()
+            .unwrap()
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.get(1).unwrap(),
+                    entry.get(2).unwrap(),
+                    entry.get(3).unwrap(),
+                )
+            })
+            .collect();
+        let entry = |kind: &str, dir: &str, text: &str| {
+            (kind.to_string(), dir.to_string(), text.to_string())
+        };
+        assert_eq!(
+            log,
+            vec![
+                entry("msdp", "out", "[VAR]SEND[VAL]HEALTH"),
+                entry("gmcp", "in", "Char.Vitals {}"),
+                entry("msdp", "in", "[VAR]HEALTH[VAL]100"),
+            ]
+        );
+        lua.state
+            .load("protocol.clear_debug_log() protocol.debug(false)")
+            .exec()
+            .unwrap();
+        lua.proto_subneg(201, b"Char.Vitals {}");
+        let len: usize = lua
+            .state
+            .load("return #protocol.debug_log()")
+            .eval()
+            .unwrap();
+        assert_eq!(len, 0);
+    }
+
+    #[test]
+    fn test_mssp_receive() {
+        let (mut lua, _reader) = get_lua();
+        lua.proto_subneg(70, b"\x01NAME\x02Blight\x01PORT\x024000\x024001");
+        let (name, ports): (String, Vec<String>) = lua
+            .state
+            .load("return mssp.get().NAME, mssp.get().PORT")
+            .eval()
+            .unwrap();
+        assert_eq!(name, "Blight");
+        assert_eq!(ports, vec!["4000", "4001"]);
+    }
+
     #[test]
     fn confirm_completion() {
         let (mut lua, _reader) = get_lua();
@@ -1445,6 +3589,42 @@ mod lua_script_tests {
         assert_eq!(lua.state.globals().get::<_, String>("buf").unwrap(), "test");
     }
 
+    #[test]
+    fn test_incremental_search() {
+        let (lua, reader) = get_lua();
+        let re = crate::model::Regex::new("kob", None).unwrap();
+        lua.on_mud_input(&mut Line::from("/search"));
+        lua.on_prompt_update("kob");
+        assert_eq!(reader.recv().unwrap(), Event::ScrollBottom);
+        assert_eq!(reader.recv().unwrap(), Event::FindBackward(re.clone()));
+
+        let mut line = Line::from("kob");
+        lua.on_mud_input(&mut line);
+        assert!(line.flags.matched);
+        lua.on_prompt_update("n");
+        assert_eq!(reader.recv().unwrap(), Event::FindBackward(re.clone()));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::SetPromptInput("".to_string())
+        );
+        lua.on_prompt_update("N");
+        assert_eq!(reader.recv().unwrap(), Event::FindForward(re));
+        assert_eq!(
+            reader.recv().unwrap(),
+            Event::SetPromptInput("".to_string())
+        );
+
+        let mut line = Line::from("");
+        lua.on_mud_input(&mut line);
+        assert!(line.flags.matched);
+        assert_eq!(reader.recv().unwrap(), Event::ScrollBottom);
+        assert!(!lua
+            .state
+            .load("return search.active()")
+            .eval::<bool>()
+            .unwrap());
+    }
+
     #[test]
     fn set_prompt_mask_content() {
         let (mut lua, _reader) = get_lua();
@@ -1461,6 +3641,35 @@ mod lua_script_tests {
         assert_eq!(result.get::<i32, String>(21).unwrap(), "bye");
     }
 
+    #[test]
+    fn draw_regions() {
+        let (lua, _reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+        layout.region("hud", { height = 1, draw = function (width) return { "w" .. width } end })
+        layout.region("plain", { height = 1 })
+        "#,
+            )
+            .exec()
+            .unwrap();
+        assert_eq!(
+            lua.draw_regions(80),
+            vec![("hud".to_string(), vec!["w80".to_string()])]
+        );
+    }
+
+    #[test]
+    fn prompt_segments() {
+        let (lua, _reader) = get_lua();
+        assert_eq!(lua.prompt_segments("input"), "");
+        lua.state
+            .load(r#"prompt_mask.add_segment("len", function (data) return " [" .. #data .. "]" end)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(lua.prompt_segments("input"), " [5]");
+    }
+
     #[test]
     fn test_gmcp_utf8() {
         let (lua, _reader) = get_lua();
@@ -1500,4 +3709,231 @@ mod lua_script_tests {
         let recv_data: String = lua.state.globals().get("recv_data").unwrap();
         assert_eq!(recv_data.trim_start(), gmcp_payload);
     }
+
+    #[test]
+    fn test_script_compilers() {
+        // Only the extension of the path templates is swapped, not the `.lua` in directory names
+        let dir = std::env::temp_dir().join(format!("blight_compilers_{}.lua", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.say"), "say (require('greeting'))").unwrap();
+        std::fs::write(dir.join("greeting.say"), "return 'hello'").unwrap();
+
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(
+                r#"
+            script.add_compiler(".say", function (source, path)
+                return (source:gsub("^say (.*)$", "blight.output(%1)"))
+            end)
+            "#,
+            )
+            .exec()
+            .unwrap();
+        lua.load_script(dir.join("main.say").to_str().unwrap())
+            .unwrap();
+        assert_eq!(lua.get_output_lines(), vec![Line::from("hello")]);
+
+        lua.load_script(dir.join("missing.fnl").to_str().unwrap())
+            .unwrap_err();
+        #[cfg(not(feature = "fennel"))]
+        {
+            std::fs::write(dir.join("main.fnl"), "(print :hi)").unwrap();
+            lua.load_script(dir.join("main.fnl").to_str().unwrap())
+                .unwrap();
+            assert!(matches!(
+                reader.recv(),
+                Ok(Event::LuaError(err)) if err.contains("requires the 'fennel' module")
+            ));
+        }
+        #[cfg(feature = "fennel")]
+        {
+            std::fs::write(dir.join("main.fnl"), r#"(blight.output "hi")"#).unwrap();
+            lua.load_script(dir.join("main.fnl").to_str().unwrap())
+                .unwrap();
+            assert_eq!(lua.get_output_lines(), vec![Line::from("hi")]);
+            assert!(reader.try_recv().is_err());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_group_packages() {
+        let dir = std::env::temp_dir().join(format!("blight_packages_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reflexes.lua");
+        std::fs::write(
+            &path,
+            r#"
+            local group = trigger.add_group("reflexes")
+            group:add("^You are hungry$", { gag = true, icase = true }, function () eaten = true end)
+            group:add("^(\\w+) attacks you$", { count = 2 },
+                function (m)
+                    attacker = m[2]
+                end)
+            local count = 0
+            alias.add_group("counter"):add("^hi$", function () count = count + 1 end)
+            alias.add_group("farewell"):add("^bye$", function () said = "bye" end):disable()
+            "#,
+        )
+        .unwrap();
+
+        let (mut lua, _reader) = get_lua();
+        lua.load_script(path.to_str().unwrap()).unwrap();
+        lua.state
+            .globals()
+            .set("dir", dir.to_str().unwrap())
+            .unwrap();
+        lua.state
+            .load(
+                r#"
+            local reflexes = trigger.get_group("reflexes")
+            assert(reflexes:export(dir .. "/triggers.pkg"))
+            reflexes:clear()
+            imported = assert(trigger.import(dir .. "/triggers.pkg"))
+
+            local counter = alias.get_group("counter")
+            local ok, err = counter:export(dir .. "/counter.pkg")
+            assert(not ok and err:find("'count'"), err)
+            for _, a in pairs(counter:get_aliases()) do
+                a:set_source('function () said = "hi" end')
+            end
+            assert(counter:export(dir .. "/counter.pkg"))
+
+            assert(alias.get_group("farewell"):export(dir .. "/farewell.pkg"))
+            farewell = assert(alias.import(dir .. "/farewell.pkg"))
+            ok, err = trigger.import(dir .. "/farewell.pkg")
+            assert(not ok and err:find("isn't a trigger package"), err)
+            "#,
+            )
+            .exec()
+            .unwrap();
+
+        assert!(test_trigger("YOU ARE HUNGRY", &lua));
+        assert!(test_trigger("Bob attacks you", &lua));
+        let globals = lua.state.globals();
+        assert!(globals.get::<_, bool>("eaten").unwrap());
+        assert_eq!(globals.get::<_, String>("attacker").unwrap(), "Bob");
+        let imported: Table = globals.get("imported").unwrap();
+        assert_eq!(imported.get::<_, String>("name").unwrap(), "reflexes");
+
+        let counter = std::fs::read_to_string(dir.join("counter.pkg")).unwrap();
+        assert!(counter.contains(r#"group:add("^hi$", function () said = "hi" end)"#));
+        let farewell = std::fs::read_to_string(dir.join("farewell.pkg")).unwrap();
+        assert!(farewell.contains(r#"function () said = "bye" end):disable()"#));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_script() {
+        let dir = std::env::temp_dir().join(format!("blight_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.lua");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(
+            &path,
+            r#"
+            trigger.add("^old$", {}, function () end)
+            trigger.add_group("combat"):add("^fight$", {}, function () end)
+            timer.add(60, 0, function () end)
+            "#,
+        )
+        .unwrap();
+
+        let (mut lua, reader) = get_lua();
+        lua.state
+            .load(r#"trigger.add("^mine$", {}, function () end)"#)
+            .exec()
+            .unwrap();
+        lua.load_script(path_str).unwrap();
+        assert!(test_trigger("old", &lua));
+        assert!(test_trigger("fight", &lua));
+        while reader.try_recv().is_ok() {}
+
+        std::fs::write(&path, r#"trigger.add("^new$", {}, function () end)"#).unwrap();
+        assert_eq!(
+            lua.changed_scripts(&[path.clone(), dir.join("other.lua"), path.clone()]),
+            vec![script::canonical_path(path_str)]
+        );
+        lua.reload_script(path_str).unwrap();
+        assert!(!test_trigger("old", &lua));
+        assert!(!test_trigger("fight", &lua));
+        assert!(test_trigger("new", &lua));
+        assert!(test_trigger("mine", &lua));
+        assert!(matches!(reader.try_recv(), Ok(Event::RemoveTimer(_))));
+        let groups: usize = lua
+            .state
+            .load("local n = 0 for _ in pairs(trigger.trigger_groups) do n = n + 1 end return n")
+            .eval()
+            .unwrap();
+        assert_eq!(groups, 1);
+
+        assert!(lua
+            .reload_script(dir.join("missing.lua").to_str().unwrap())
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn lua_functions(lua: &LuaScript, value: mlua::Value) -> BTreeSet<String> {
+        // Userdata methods end up in the metatable's __index, or in its upvalues when mlua
+        // generates an __index function.
+        let tables: Vec<Table> = lua
+            .state
+            .load(
+                r#"
+            local value = ...
+            if type(value) == "table" then
+                return { value }
+            end
+            local index = debug.getmetatable(value).__index
+            if type(index) == "table" then
+                return { index }
+            end
+            local tables = {}
+            for i = 1, math.huge do
+                local name, upvalue = debug.getupvalue(index, i)
+                if name == nil then
+                    return tables
+                elseif type(upvalue) == "table" and (name == "methods" or name == "__index") then
+                    tables[#tables + 1] = upvalue
+                end
+            end
+            "#,
+            )
+            .call(value)
+            .unwrap();
+        tables
+            .iter()
+            .flat_map(|table| table.clone().pairs::<String, mlua::Value>())
+            .filter_map(|pair| pair.ok())
+            .filter(|(name, value)| {
+                matches!(value, mlua::Value::Function(_)) && !name.starts_with('_')
+            })
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn test_api_definitions_are_complete() {
+        let (lua, _reader) = get_lua();
+        let globals = lua.state.globals();
+        for module in crate::lua::api::modules().iter().filter(|m| !m.class) {
+            let documented: BTreeSet<String> = module
+                .functions
+                .iter()
+                .map(|f| f.name().to_string())
+                .collect();
+            let registered = if module.name.is_empty() {
+                documented
+                    .iter()
+                    .filter(|name| {
+                        matches!(globals.get(name.as_str()), Ok(mlua::Value::Function(_)))
+                    })
+                    .cloned()
+                    .collect()
+            } else {
+                lua_functions(&lua, globals.get(module.name).unwrap())
+            };
+            assert_eq!(documented, registered, "module '{}'", module.name);
+        }
+    }
 }
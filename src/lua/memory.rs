@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use mlua::{AnyUserData, Lua, Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, constants::GC_IDLE_STEP, metrics::event_metrics, store::Store};
+use crate::{
+    io::SaveData,
+    model::{GcMode, GcSettings},
+};
+
+/// Memory use of the client and tuning of the Lua garbage collector.
+pub struct Memory;
+
+impl Memory {
+    pub const LUA_GLOBAL_NAME: &'static str = "memory";
+}
+
+pub const API: ApiModule = lua_api!("memory", "Memory use and garbage collector tuning", [
+    "stats() -> { lua: integer, scrollback: integer, scrollback_lines: integer, scrollback_spilled: integer, session_store: integer, disk_store: integer }" => "Returns the bytes used by the Lua state, the scrollback and the stores",
+    "gc() -> { mode: string, pause: integer, step_multiplier: integer, idle_step: integer }" => "Returns the garbage collector settings",
+    "set_gc(options: { mode?: string, pause?: integer, step_multiplier?: integer, idle_step?: integer })" => "Changes, applies and saves the garbage collector settings",
+    "collect() -> integer" => "Runs a full garbage collection and returns the bytes it freed",
+]);
+
+/// Sets up the garbage collector of a Lua state with the settings.
+pub fn apply_gc_settings(lua: &Lua, settings: &GcSettings) -> mlua::Result<()> {
+    match settings.mode {
+        GcMode::Incremental => {
+            lua.gc_inc(settings.pause as i32, settings.step_multiplier as i32, 0);
+        }
+        #[cfg(feature = "lua54")]
+        GcMode::Generational => {
+            lua.gc_gen(0, 0);
+        }
+        // LuaJIT only has the incremental collector
+        #[cfg(not(feature = "lua54"))]
+        GcMode::Generational => {
+            lua.gc_inc(settings.pause as i32, settings.step_multiplier as i32, 0);
+        }
+    }
+    lua.set_named_registry_value(GC_IDLE_STEP, settings.idle_step())
+}
+
+/// Does a step of garbage collection if the settings ask for one on idle ticks.
+pub fn idle_step(lua: &Lua) -> mlua::Result<()> {
+    if let Some(kbytes) = lua.named_registry_value::<Option<i32>>(GC_IDLE_STEP)? {
+        lua.gc_step_kbytes(kbytes)?;
+    }
+    Ok(())
+}
+
+fn stored_bytes(values: &HashMap<String, String>) -> usize {
+    values
+        .iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum()
+}
+
+fn parse_gc_options(opts: &Table) -> mlua::Result<GcSettings> {
+    let mut settings = GcSettings::load();
+    if let Some(mode) = opts.get::<_, Option<String>>("mode")? {
+        settings.mode = mode.parse().map_err(mlua::Error::RuntimeError)?;
+    }
+    if let Some(pause) = opts.get::<_, Option<u32>>("pause")? {
+        settings.pause = pause.max(100);
+    }
+    if let Some(step_multiplier) = opts.get::<_, Option<u32>>("step_multiplier")? {
+        settings.step_multiplier = step_multiplier.max(100);
+    }
+    if let Some(idle_step) = opts.get::<_, Option<u32>>("idle_step")? {
+        settings.idle_step = idle_step;
+    }
+    Ok(settings)
+}
+
+impl UserData for Memory {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("stats", |ctx, ()| {
+            let snapshot = event_metrics(ctx)?.snapshot();
+            let store: AnyUserData = ctx.globals().get(Store::LUA_GLOBAL_NAME)?;
+            let session_store = stored_bytes(&store.borrow::<Store>()?.memory_storage);
+            let table = ctx.create_table()?;
+            table.set("lua", ctx.used_memory())?;
+            table.set("scrollback", snapshot.scrollback_bytes)?;
+            table.set("scrollback_lines", snapshot.scrollback_lines)?;
+            table.set("scrollback_spilled", snapshot.scrollback_spilled)?;
+            table.set("session_store", session_store)?;
+            table.set(
+                "disk_store",
+                stored_bytes(&HashMap::<String, String>::load()),
+            )?;
+            Ok(table)
+        });
+        methods.add_function("gc", |ctx, ()| {
+            let settings = GcSettings::load();
+            let table = ctx.create_table()?;
+            table.set("mode", settings.mode.to_string())?;
+            table.set("pause", settings.pause)?;
+            table.set("step_multiplier", settings.step_multiplier)?;
+            table.set("idle_step", settings.idle_step)?;
+            Ok(table)
+        });
+        methods.add_function("set_gc", |ctx, opts: Table| {
+            let settings = parse_gc_options(&opts)?;
+            apply_gc_settings(ctx, &settings)?;
+            settings.save();
+            Ok(())
+        });
+        methods.add_function("collect", |ctx, ()| {
+            let before = ctx.used_memory();
+            ctx.gc_collect()?;
+            Ok(before.saturating_sub(ctx.used_memory()))
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_memory {
+    use mlua::Lua;
+
+    use super::*;
+    use crate::lua::metrics::Metrics;
+    use crate::model::EventMetrics;
+
+    #[test]
+    fn test_idle_step() {
+        let lua = Lua::new();
+        let settings = GcSettings {
+            idle_step: 8,
+            ..Default::default()
+        };
+        apply_gc_settings(&lua, &settings).unwrap();
+        assert_eq!(
+            lua.named_registry_value::<Option<i32>>(GC_IDLE_STEP)
+                .unwrap(),
+            Some(8)
+        );
+        idle_step(&lua).unwrap();
+        apply_gc_settings(
+            &lua,
+            &GcSettings {
+                mode: GcMode::Generational,
+                ..settings
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            lua.named_registry_value::<Option<i32>>(GC_IDLE_STEP)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let lua = Lua::new();
+        let metrics = EventMetrics::default();
+        metrics.record_scrollback(1, 5, 0);
+        let mut store = Store::new();
+        store
+            .memory_storage
+            .insert("hp".to_string(), "100".to_string());
+        lua.globals()
+            .set(Metrics::LUA_GLOBAL_NAME, Metrics::new(metrics))
+            .unwrap();
+        lua.globals().set(Store::LUA_GLOBAL_NAME, store).unwrap();
+        lua.globals().set(Memory::LUA_GLOBAL_NAME, Memory).unwrap();
+        let (lua_bytes, scrollback, lines, session_store): (usize, usize, usize, usize) = lua
+            .load(
+                r#"
+                local stats = memory.stats()
+                return stats.lua, stats.scrollback, stats.scrollback_lines, stats.session_store
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert!(lua_bytes > 0);
+        assert_eq!((scrollback, lines, session_store), (5, 1, 5));
+    }
+}
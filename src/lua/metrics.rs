@@ -0,0 +1,102 @@
+use mlua::{AnyUserData, Lua, Table, UserData, UserDataMethods};
+
+use super::api::ApiModule;
+use crate::model::{EventMetrics, Timings, LATENCY_BUCKETS};
+
+/// Read access to the metrics of the main event loop.
+pub struct Metrics {
+    metrics: EventMetrics,
+}
+
+impl Metrics {
+    pub const LUA_GLOBAL_NAME: &'static str = "metrics";
+
+    pub fn new(metrics: EventMetrics) -> Self {
+        Self { metrics }
+    }
+}
+
+pub const API: ApiModule = lua_api!("metrics", "Event loop metrics for diagnosing stutters", [
+    "get() -> { events: table<string, table>, total: table, render: table, queue_depth: integer, max_queue_depth: integer, buckets: integer[] }" => "Returns the counters and latencies of the event loop",
+    "reset()" => "Clears the collected metrics",
+]);
+
+pub fn event_metrics(ctx: &Lua) -> mlua::Result<EventMetrics> {
+    let metrics: AnyUserData = ctx.globals().get(Metrics::LUA_GLOBAL_NAME)?;
+    let metrics = metrics.borrow::<Metrics>()?;
+    Ok(metrics.metrics.clone())
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn timings_table<'lua>(ctx: &'lua Lua, timings: &Timings) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("count", timings.count)?;
+    table.set("total_ms", millis(timings.total))?;
+    table.set("avg_ms", millis(timings.average()))?;
+    table.set("max_ms", millis(timings.max))?;
+    table.set("histogram", timings.histogram.to_vec())?;
+    Ok(table)
+}
+
+impl UserData for Metrics {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("get", |ctx, ()| {
+            let snapshot = event_metrics(ctx)?.snapshot();
+            let events = ctx.create_table()?;
+            for (name, timings) in &snapshot.events {
+                events.set(name.as_str(), timings_table(ctx, timings)?)?;
+            }
+            let table = ctx.create_table()?;
+            table.set("events", events)?;
+            table.set("total", timings_table(ctx, &snapshot.total())?)?;
+            table.set("render", timings_table(ctx, &snapshot.render)?)?;
+            table.set("queue_depth", snapshot.queue_depth)?;
+            table.set("max_queue_depth", snapshot.max_queue_depth)?;
+            table.set("buckets", LATENCY_BUCKETS.to_vec())?;
+            Ok(table)
+        });
+        methods.add_function("reset", |ctx, ()| {
+            event_metrics(ctx)?.reset();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_metrics {
+    use std::time::Duration;
+
+    use mlua::Lua;
+
+    use super::*;
+
+    #[test]
+    fn test_get_and_reset() {
+        let lua = Lua::new();
+        let metrics = EventMetrics::default();
+        metrics.record_event("MudOutput", Duration::from_millis(2));
+        metrics.record_event("MudOutput", Duration::from_millis(30));
+        metrics.record_queue_depth(4);
+        lua.globals()
+            .set(Metrics::LUA_GLOBAL_NAME, Metrics::new(metrics.clone()))
+            .unwrap();
+
+        let (count, max, slow, depth): (u64, f64, u64, usize) = lua
+            .load(
+                r#"
+                local m = metrics.get()
+                local output = m.events.MudOutput
+                return output.count, output.max_ms, output.histogram[4], m.max_queue_depth
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!((count, max, slow, depth), (2, 30.0, 1, 4));
+
+        lua.load("metrics.reset()").exec().unwrap();
+        assert_eq!(metrics.snapshot().events.len(), 0);
+    }
+}
@@ -1,29 +1,52 @@
+pub use self::api::stubs as api_stubs;
 pub use self::lua_script::{LuaScript, LuaScriptBuilder};
+pub use self::store::resave_disk_storage;
 pub use self::ui_event::UiEvent;
 
 #[cfg(test)]
 #[macro_use]
 mod test_help;
+#[macro_use]
+mod api;
+mod activity;
+mod api_version;
 mod audio;
 mod backend;
+mod backup;
 mod blight;
 mod constants;
 mod core;
+mod dns;
 mod exec_response;
+mod fmt;
 mod fs;
 mod fs_event;
+mod fuzzy;
+mod help;
+mod layout;
 mod line;
 mod log;
 mod lua_script;
+mod memory;
+mod metrics;
 mod mud;
+mod origin;
+mod palette;
 mod plugin;
 mod prompt;
 mod prompt_mask;
+mod reader;
 mod regex;
+mod relay;
+mod runtime;
+mod screen;
 mod script;
+mod sent;
 mod servers;
 mod settings;
+mod snapshot;
 mod socket;
+mod sounds;
 #[cfg(feature = "spellcheck")]
 mod spellcheck;
 mod store;
@@ -31,3 +54,6 @@ mod timer;
 mod tts;
 mod ui_event;
 pub mod util;
+mod vars;
+mod web;
+mod widget;
@@ -1,4 +1,4 @@
-use libmudtelnet::bytes::Bytes;
+use libmudtelnet::{bytes::Bytes, Parser};
 use mlua::{Function, Table, UserData, UserDataMethods};
 
 use crate::{
@@ -7,13 +7,33 @@ use crate::{
 };
 
 use super::{
+    api::ApiModule,
+    api_version,
     backend::Backend,
     constants::{
-        BACKEND, IS_CONNECTED, MUD_INPUT_LISTENER_TABLE, MUD_OUTPUT_LISTENER_TABLE,
-        ON_CONNECTION_CALLBACK_TABLE, ON_DISCONNECT_CALLBACK_TABLE,
+        BACKEND, CHAR_MODE, COMMAND_SEPARATOR, COMMAND_STACKING, HANDLING_INPUT, IS_CONNECTED,
+        MUD_INPUT_LISTENER_TABLE, MUD_OUTPUT_LISTENER_TABLE, ON_CONNECTION_CALLBACK_TABLE,
+        ON_DISCONNECT_CALLBACK_TABLE, ON_STALL_CALLBACK_TABLE, REPORTED_DIMENSIONS,
+        REPORTED_DIMENSIONS_LISTENER_TABLE, SENT_COUNT,
     },
+    script,
+    vars::expand_vars,
 };
 
+/// Counts the commands sent by scripts, so triggers can tell whether their callback sent any.
+fn count_sent(ctx: &mlua::Lua) -> mlua::Result<()> {
+    let count = ctx.named_registry_value::<Option<i64>>(SENT_COUNT)?;
+    ctx.set_named_registry_value(SENT_COUNT, count.unwrap_or_default() + 1)
+}
+
+/// Lines sent while input is handled come from an alias, see [crate::model::SentOrigin].
+fn from_alias(ctx: &mlua::Lua) -> bool {
+    ctx.named_registry_value::<Option<bool>>(HANDLING_INPUT)
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
+
 pub struct Mud {}
 
 impl Mud {
@@ -22,6 +42,32 @@ impl Mud {
     }
 }
 
+pub const API: ApiModule = lua_api!("mud", "Interaction with the MUD", [
+    "add_output_listener(callback: fun(line: Line))" => "Adds a listener for lines from the MUD",
+    "add_input_listener(callback: fun(line: Line))" => "Adds a listener for user input",
+    "output(msg: string)" => "Sends a line through the triggers as if it came from the MUD",
+    "connect(host: string, port: integer, options?: { tls?: boolean, verify?: boolean })" => "Connects to a MUD",
+    "disconnect()" => "Disconnects from the MUD",
+    "reconnect()" => "Reconnects to the last MUD",
+    "send(msg: string, options?: { gag?: boolean, skip_log?: boolean, history?: boolean })" => "Sends a command to the MUD",
+    "send_bytes(bytes: integer[])" => "Sends raw bytes to the MUD",
+    "send_no_newline(text: string)" => "Sends text to the MUD without a line ending",
+    "input(line: string)" => "Runs a line as if it was typed by the user",
+    "command_separator(separator?: string) -> string" => "Sets and returns the command stacking separator",
+    "command_stacking(enabled?: boolean)" => "Overrides command stacking for the current connection",
+    "char_mode(enabled?: boolean) -> boolean" => "Sets and returns whether keys are sent to the MUD as they're typed",
+    "on_connect(callback: fun(host: string, port: integer, reset: boolean))" => "Adds a callback for when a connection is made",
+    "on_disconnect(callback: function)" => "Adds a callback for when the connection is closed",
+    "on_stall(callback: fun(seconds: integer))" => "Adds a callback for when the server stops responding",
+    "is_connected() -> boolean" => "Checks if there is a connection",
+    "add_tag(tag: string)" => "Adds a tag to the current connection",
+    "remove_tag(tag: string)" => "Removes a tag from the current connection",
+    "clear_tags()" => "Removes all tags from the current connection",
+    "set_reported_dimensions(width?: integer, height?: integer)" => "Overrides the window size reported to the MUD, the terminal size if omitted",
+    "reported_dimensions() -> width: integer?, height: integer?" => "Returns the overridden window size reported to the MUD",
+    "on_reported_dimensions_change(callback: fun(width?: integer, height?: integer))" => "Adds a callback for when the reported window size is overridden",
+]);
+
 impl UserData for Mud {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function(
@@ -42,25 +88,48 @@ impl UserData for Mud {
         );
         methods.add_function("output", |ctx, msg: String| {
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
-            backend
-                .writer
-                .send(Event::MudOutput(Line::from(msg)))
-                .unwrap();
+            let mut line = Line::from(msg);
+            line.flags.origin = script::origin(ctx);
+            backend.writer.send(Event::MudOutput(line)).unwrap();
             Ok(())
         });
         methods.add_function(
             "connect",
-            |ctx, (host, port, tls, verify): (String, u16, bool, Option<bool>)| {
+            |ctx, (host, port, options, verify): (String, u16, mlua::Value, Option<bool>)| {
                 let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                let (tls, verify) = match options {
+                    mlua::Value::Table(options) => (
+                        options.get::<_, Option<bool>>("tls")?.unwrap_or(false),
+                        options.get::<_, Option<bool>>("verify")?,
+                    ),
+                    mlua::Value::Nil if verify.is_none() => (false, None),
+                    // API version 1 took tls and verify as separate arguments
+                    options if api_version::version_at(ctx, 1)? < 2 => {
+                        backend
+                            .writer
+                            .send(Event::LuaWarning(api_version::deprecation(
+                                ctx,
+                                1,
+                                "mud.connect(host, port, tls, verify) is deprecated, pass { tls = ..., verify = ... } instead",
+                            )))
+                            .unwrap();
+                        (options.as_boolean().unwrap_or(false), verify)
+                    }
+                    _ => {
+                        return Err(mlua::Error::RuntimeError(
+                            "mud.connect expects an options table, eg. { tls = true }".to_string(),
+                        ))
+                    }
+                };
                 let verify_cert = if tls { verify.unwrap_or(true) } else { false };
                 backend
                     .writer
-                    .send(Event::Connect(Connection {
-                        host,
+                    .send(Event::Connect(Connection::new(
+                        &host,
                         port,
                         tls,
                         verify_cert,
-                    }))
+                    )))
                     .unwrap();
                 Ok(())
             },
@@ -78,20 +147,38 @@ impl UserData for Mud {
         methods.add_function(
             "send",
             |ctx, (msg, options): (String, Option<mlua::Table>)| {
-                let mut line = Line::from(msg);
+                let from_alias = from_alias(ctx);
+                let expand = match &options {
+                    Some(table) => table.get::<_, Option<bool>>("expand")?,
+                    None => None,
+                }
+                .unwrap_or(from_alias);
+                let mut line = if expand {
+                    Line::from(expand_vars(ctx, &msg)?)
+                } else {
+                    Line::from(msg)
+                };
                 line.flags.bypass_script = true;
                 line.flags.source = Some("script".to_string());
+                line.flags.from_alias = from_alias;
 
                 if let Some(table) = options {
                     line.flags.gag = table.get("gag")?;
                     line.flags.skip_log = table.get("skip_log")?;
+                    line.flags.skip_history =
+                        table.get::<_, Option<bool>>("history")? == Some(false);
                 }
 
+                count_sent(ctx)?;
                 let backend: Backend = ctx.named_registry_value(BACKEND)?;
                 backend.writer.send(Event::ServerInput(line)).unwrap();
                 Ok(())
             },
         );
+        methods.add_function("_sent_count", |ctx, ()| {
+            ctx.named_registry_value::<Option<i64>>(SENT_COUNT)
+                .map(Option::unwrap_or_default)
+        });
         methods.add_function("send_bytes", |ctx, bytes: Vec<u8>| {
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend
@@ -100,13 +187,50 @@ impl UserData for Mud {
                 .unwrap();
             Ok(())
         });
+        methods.add_function("send_no_newline", |ctx, text: mlua::String| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend
+                .writer
+                .send(Event::ServerSend(Parser::escape_iac(
+                    text.as_bytes().to_vec(),
+                )))
+                .unwrap();
+            Ok(())
+        });
         methods.add_function("input", |ctx, line: String| {
             let mut line = Line::from(line);
             line.flags.source = Some("script".to_string());
+            line.flags.from_alias = from_alias(ctx);
+            count_sent(ctx)?;
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend.writer.send(Event::ServerInput(line)).unwrap();
             Ok(())
         });
+        methods.add_function(
+            "command_separator",
+            |ctx, separator: Option<String>| -> mlua::Result<String> {
+                if let Some(separator) = separator {
+                    ctx.set_named_registry_value(COMMAND_SEPARATOR, separator)?;
+                }
+                ctx.named_registry_value(COMMAND_SEPARATOR)
+            },
+        );
+        methods.add_function("command_stacking", |ctx, enabled: Option<bool>| {
+            ctx.set_named_registry_value(COMMAND_STACKING, enabled)
+        });
+        methods.add_function(
+            "char_mode",
+            |ctx, enabled: Option<bool>| -> mlua::Result<bool> {
+                if let Some(enabled) = enabled {
+                    ctx.set_named_registry_value(CHAR_MODE, enabled)?;
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend.writer.send(Event::CharMode(enabled)).unwrap();
+                }
+                Ok(ctx
+                    .named_registry_value::<Option<bool>>(CHAR_MODE)?
+                    .unwrap_or_default())
+            },
+        );
         methods.add_function("on_connect", |ctx, callback: mlua::Function| {
             let table: mlua::Table = ctx.named_registry_value(ON_CONNECTION_CALLBACK_TABLE)?;
             table.raw_set(table.raw_len() + 1, callback)?;
@@ -117,6 +241,41 @@ impl UserData for Mud {
             table.set(table.raw_len() + 1, callback)?;
             Ok(())
         });
+        methods.add_function("on_stall", |ctx, callback: mlua::Function| {
+            let table: mlua::Table = ctx.named_registry_value(ON_STALL_CALLBACK_TABLE)?;
+            table.raw_set(table.raw_len() + 1, callback)?;
+            Ok(())
+        });
+        methods.add_function(
+            "set_reported_dimensions",
+            |ctx, (width, height): (Option<u16>, Option<u16>)| {
+                let (width, height) = width.zip(height).unzip();
+                ctx.set_named_registry_value(
+                    REPORTED_DIMENSIONS,
+                    width.zip(height).map(|(width, height)| vec![width, height]),
+                )?;
+                let table: mlua::Table =
+                    ctx.named_registry_value(REPORTED_DIMENSIONS_LISTENER_TABLE)?;
+                for pair in table.pairs::<mlua::Value, mlua::Function>() {
+                    let (_, cb) = pair?;
+                    cb.call::<_, ()>((width, height))?;
+                }
+                Ok(())
+            },
+        );
+        methods.add_function("reported_dimensions", |ctx, ()| {
+            let dimensions: Option<Vec<u16>> = ctx.named_registry_value(REPORTED_DIMENSIONS)?;
+            Ok(dimensions.map_or((None, None), |dim| (Some(dim[0]), Some(dim[1]))))
+        });
+        methods.add_function(
+            "on_reported_dimensions_change",
+            |ctx, callback: mlua::Function| {
+                let table: mlua::Table =
+                    ctx.named_registry_value(REPORTED_DIMENSIONS_LISTENER_TABLE)?;
+                table.raw_set(table.raw_len() + 1, callback)?;
+                Ok(())
+            },
+        );
         methods.add_function("is_connected", |ctx, ()| {
             let value: bool = ctx.named_registry_value(IS_CONNECTED)?;
             Ok(value)
@@ -150,6 +309,7 @@ mod test_mud {
         event::Event,
         lua::constants::MUD_INPUT_LISTENER_TABLE,
         lua::constants::MUD_OUTPUT_LISTENER_TABLE,
+        lua::constants::{COMMAND_SEPARATOR, COMMAND_STACKING, HANDLING_INPUT, SESSION_VARS},
         lua::{backend::Backend, constants::BACKEND},
         model::Connection,
         model::Line,
@@ -201,51 +361,65 @@ mod test_mud {
     fn test_connect() {
         assert_event(
             "mud.connect(\"hostname\", 99)",
-            Event::Connect(Connection {
-                host: "hostname".to_string(),
-                port: 99,
-                tls: false,
-                verify_cert: false,
-            }),
+            Event::Connect(Connection::new("hostname", 99, false, false)),
         );
         assert_event(
-            "mud.connect(\"hostname\", 99, false)",
-            Event::Connect(Connection {
-                host: "hostname".to_string(),
-                port: 99,
-                tls: false,
-                verify_cert: false,
-            }),
+            "mud.connect(\"hostname\", 99, {})",
+            Event::Connect(Connection::new("hostname", 99, false, false)),
         );
         assert_event(
-            "mud.connect(\"hostname\", 99, true)",
-            Event::Connect(Connection {
-                host: "hostname".to_string(),
-                port: 99,
-                tls: true,
-                verify_cert: true,
-            }),
+            "mud.connect(\"hostname\", 99, { tls = true })",
+            Event::Connect(Connection::new("hostname", 99, true, true)),
         );
         assert_event(
-            "mud.connect(\"hostname\", 99, true, true)",
-            Event::Connect(Connection {
-                host: "hostname".to_string(),
-                port: 99,
-                tls: true,
-                verify_cert: true,
-            }),
+            "mud.connect(\"hostname\", 99, { tls = true, verify = false })",
+            Event::Connect(Connection::new("hostname", 99, true, false)),
         );
         assert_event(
-            "mud.connect(\"hostname\", 99, true, false)",
-            Event::Connect(Connection {
-                host: "hostname".to_string(),
-                port: 99,
-                tls: true,
-                verify_cert: false,
-            }),
+            "mud.connect(\"hostname\", 99, { verify = true })",
+            Event::Connect(Connection::new("hostname", 99, false, false)),
         );
     }
 
+    #[test]
+    fn test_command_stacking() {
+        let lua = Lua::new();
+        lua.set_named_registry_value(COMMAND_SEPARATOR, ";")
+            .unwrap();
+        lua.globals().set("mud", Mud::new()).unwrap();
+        let separator: String = lua
+            .load("mud.command_stacking(false) return mud.command_separator('||')")
+            .call(())
+            .unwrap();
+        assert_eq!(separator, "||");
+        assert_eq!(
+            lua.named_registry_value::<Option<bool>>(COMMAND_STACKING)
+                .unwrap(),
+            Some(false)
+        );
+        lua.load("mud.command_stacking(nil)").exec().unwrap();
+        assert_eq!(
+            lua.named_registry_value::<Option<bool>>(COMMAND_STACKING)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_char_mode() {
+        assert_event("mud.char_mode(true)", Event::CharMode(true));
+
+        let (writer, _reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals().set("mud", Mud::new()).unwrap();
+        let enabled: bool = lua.load("return mud.char_mode()").call(()).unwrap();
+        assert!(!enabled);
+        let enabled: bool = lua.load("return mud.char_mode(true)").call(()).unwrap();
+        assert!(enabled);
+    }
+
     #[test]
     fn test_default_disconnect() {
         assert_event("mud.disconnect()", Event::Disconnect);
@@ -263,6 +437,40 @@ mod test_mud {
         assert_eq!(reader.recv().unwrap(), Event::Disconnect);
     }
 
+    #[test]
+    fn test_send_expand() {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        let vars = lua.create_table().unwrap();
+        vars.set("target", "orc").unwrap();
+        lua.set_named_registry_value(SESSION_VARS, vars).unwrap();
+        lua.globals().set("mud", Mud::new()).unwrap();
+
+        let sent = |code: &str| {
+            lua.load(code).exec().unwrap();
+            reader.recv().unwrap()
+        };
+        assert_eq!(
+            sent("mud.send('kill $target')"),
+            Event::ServerInput(Line::from("kill $target"))
+        );
+        assert_eq!(
+            sent("mud.send('kill $target', { expand = true })"),
+            Event::ServerInput(Line::from("kill orc"))
+        );
+        lua.set_named_registry_value(HANDLING_INPUT, true).unwrap();
+        assert_eq!(
+            sent("mud.send('kill $target')"),
+            Event::ServerInput(Line::from("kill orc"))
+        );
+        assert_eq!(
+            sent("mud.send('kill $target', { expand = false })"),
+            Event::ServerInput(Line::from("kill $target"))
+        );
+    }
+
     #[test]
     fn test_send_bytes() {
         assert_event(
@@ -271,6 +479,14 @@ mod test_mud {
         );
     }
 
+    #[test]
+    fn test_send_no_newline() {
+        assert_event(
+            "mud.send_no_newline('y\\xff')",
+            Event::ServerSend(Bytes::copy_from_slice(&[b'y', 0xff, 0xff])),
+        );
+    }
+
     #[test]
     fn test_mud_output_command() {
         let lua_code = r#"
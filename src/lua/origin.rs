@@ -0,0 +1,70 @@
+use mlua::{Lua, Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::{
+    event::Event,
+    io::SaveData,
+    model::{Origin, OriginStyle, OriginStyles},
+};
+
+/// Styles of output by where it comes from.
+pub struct OriginStyler;
+
+impl OriginStyler {
+    pub const LUA_GLOBAL_NAME: &'static str = "origin";
+}
+
+pub const API: ApiModule = lua_api!("origin", "Marking and hiding output by where it comes from", [
+    "styles() -> table<string, table>" => "Returns the styles of all origins that have one",
+    "get(origin: string) -> { marker?: string, color?: string, hidden: boolean }" => "Returns the style of an origin",
+    "set(origin: string, style?: { marker?: string, color?: string, hidden?: boolean })" => "Sets the style of an origin, or resets it without one",
+]);
+
+fn parse_origin(origin: &str) -> mlua::Result<Origin> {
+    origin.parse().map_err(mlua::Error::RuntimeError)
+}
+
+fn style_table<'lua>(ctx: &'lua Lua, style: &OriginStyle) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("marker", style.marker.clone())?;
+    table.set("color", style.color.map(|color| color.to_string()))?;
+    table.set("hidden", style.hidden)?;
+    Ok(table)
+}
+
+impl UserData for OriginStyler {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("styles", |ctx, ()| {
+            let table = ctx.create_table()?;
+            for (origin, style) in OriginStyles::load().styles() {
+                table.set(origin.as_str(), style_table(ctx, style)?)?;
+            }
+            Ok(table)
+        });
+        methods.add_function("get", |ctx, origin: String| {
+            let origin = parse_origin(&origin)?;
+            let styles = OriginStyles::load();
+            style_table(ctx, styles.get(&origin).unwrap_or(&OriginStyle::default()))
+        });
+        methods.add_function("set", |ctx, (origin, style): (String, Option<Table>)| {
+            let origin = parse_origin(&origin)?;
+            let style = match style {
+                Some(table) => OriginStyle {
+                    marker: table.get("marker")?,
+                    color: table
+                        .get::<_, Option<String>>("color")?
+                        .map(|color| color.parse().map_err(mlua::Error::RuntimeError))
+                        .transpose()?,
+                    hidden: table.get::<_, Option<bool>>("hidden")?.unwrap_or_default(),
+                },
+                None => OriginStyle::default(),
+            };
+            let mut styles = OriginStyles::load();
+            styles.set(&origin, style);
+            styles.save();
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::OriginStylesChanged).unwrap();
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,163 @@
+use mlua::{Lua, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::{
+    event::Event,
+    io::SaveData,
+    model::{contrast, Color, ColorFilter, ColorFilters, MinContrast, Palettes, Servers},
+};
+
+/// Per server color remapping and color filters.
+pub struct Palette;
+
+impl Palette {
+    pub const LUA_GLOBAL_NAME: &'static str = "palette";
+}
+
+pub const API: ApiModule = lua_api!("palette", "Remapping and filtering the colors of output", [
+    "get(server: string) -> table<string, string>" => "Returns the remapped colors of a saved server",
+    "set(server: string, from: string, to?: string)" => "Remaps a color of a saved server, or stops remapping it without `to`",
+    "clear(server: string)" => "Stops remapping the colors of a saved server",
+    "preview(server?: string) -> string[]" => "Returns a grid of the 256 colors as they're shown for a saved server",
+    "filter(server?: string) -> string" => "Returns the color filter used for a saved server, or in general",
+    "set_filter(filter: string, server?: string)" => "Sets the color filter used in general or for a saved server, \"default\" drops the one of a server",
+    "background() -> string?" => "Returns the background color of the terminal, if set",
+    "set_background(color?: string)" => "Sets the background color of the terminal that server colors are kept readable on, or stops adjusting them without one",
+    "min_contrast(ratio?: number) -> number" => "Sets and returns the contrast ratio server colors are kept at, from 1 to 21",
+    "contrast(color: string, background?: string) -> number" => "Returns the contrast ratio of a color on a background, the terminal background by default",
+    "readable(color: string, background?: string) -> string" => "Returns the color adjusted to the minimum contrast on a background, the terminal background by default",
+]);
+
+fn parse_color(color: &str) -> mlua::Result<Color> {
+    color.parse().map_err(mlua::Error::RuntimeError)
+}
+
+/// The given background, or the one set for the terminal.
+fn background(background: Option<String>) -> mlua::Result<Color> {
+    match background {
+        Some(background) => parse_color(&background),
+        None => ColorFilters::load().background.ok_or_else(|| {
+            mlua::Error::RuntimeError("No terminal background color is set".to_string())
+        }),
+    }
+}
+
+fn check_server(server: &str) -> mlua::Result<()> {
+    if Servers::load().contains_key(server) {
+        Ok(())
+    } else {
+        Err(mlua::Error::RuntimeError(format!(
+            "Saved server does not exist: {server}"
+        )))
+    }
+}
+
+/// Saves the palettes or color filters and applies them.
+fn save(ctx: &Lua, data: impl SaveData) -> mlua::Result<()> {
+    data.save();
+    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+    backend.writer.send(Event::ServersChanged).unwrap();
+    Ok(())
+}
+
+impl UserData for Palette {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("get", |ctx, server: String| {
+            let table = ctx.create_table()?;
+            if let Some(palette) = Palettes::load().get(&server) {
+                for (from, to) in palette.colors() {
+                    table.set(from.to_string(), to.to_string())?;
+                }
+            }
+            Ok(table)
+        });
+        methods.add_function(
+            "set",
+            |ctx, (server, from, to): (String, String, Option<String>)| {
+                check_server(&server)?;
+                let from = parse_color(&from)?;
+                let to = to.as_deref().map(parse_color).transpose()?;
+                let mut palettes = Palettes::load();
+                let palette = palettes.entry(server.clone()).or_default();
+                palette.set(from, to);
+                if palette.is_empty() {
+                    palettes.remove(&server);
+                }
+                save(ctx, palettes)
+            },
+        );
+        methods.add_function("clear", |ctx, server: String| {
+            let mut palettes = Palettes::load();
+            if palettes.remove(&server).is_some() {
+                save(ctx, palettes)?;
+            }
+            Ok(())
+        });
+        methods.add_function("preview", |_, server: Option<String>| {
+            let palette = server
+                .and_then(|server| Palettes::load().remove(&server))
+                .unwrap_or_default();
+            Ok(palette.preview())
+        });
+        methods.add_function("filter", |_, server: Option<String>| {
+            Ok(ColorFilters::load().get(server.as_deref()).to_string())
+        });
+        methods.add_function(
+            "set_filter",
+            |ctx, (filter, server): (String, Option<String>)| {
+                let mut filters = ColorFilters::load();
+                match server {
+                    Some(server) if filter == "default" => {
+                        filters.servers.remove(&server);
+                    }
+                    Some(server) => {
+                        check_server(&server)?;
+                        let filter = filter.parse().map_err(mlua::Error::RuntimeError)?;
+                        filters.servers.insert(server, filter);
+                    }
+                    None => {
+                        filters.filter = filter
+                            .parse::<ColorFilter>()
+                            .map_err(mlua::Error::RuntimeError)?;
+                    }
+                }
+                save(ctx, filters)
+            },
+        );
+        methods.add_function("background", |_, ()| {
+            Ok(ColorFilters::load()
+                .background
+                .map(|color| color.to_string()))
+        });
+        methods.add_function("set_background", |ctx, color: Option<String>| {
+            let mut filters = ColorFilters::load();
+            filters.background = color.as_deref().map(parse_color).transpose()?;
+            save(ctx, filters)
+        });
+        methods.add_function("min_contrast", |ctx, ratio: Option<f32>| {
+            let mut filters = ColorFilters::load();
+            let Some(ratio) = ratio else {
+                return Ok(filters.min_contrast);
+            };
+            if !(1.0..=21.0).contains(&ratio) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "Invalid contrast ratio: {ratio}, use 1 to 21"
+                )));
+            }
+            filters.min_contrast = ratio;
+            save(ctx, filters)?;
+            Ok(ratio)
+        });
+        methods.add_function("contrast", |_, (color, on): (String, Option<String>)| {
+            Ok(contrast(parse_color(&color)?, background(on)?))
+        });
+        methods.add_function("readable", |_, (color, on): (String, Option<String>)| {
+            let color = parse_color(&color)?;
+            let min_contrast = MinContrast {
+                background: background(on)?,
+                ratio: ColorFilters::load().min_contrast,
+            };
+            Ok(min_contrast.readable(color).unwrap_or(color).to_string())
+        });
+    }
+}
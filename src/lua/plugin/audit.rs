@@ -0,0 +1,217 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::functions::{get_plugin_dir, get_plugins};
+use crate::tools::util::expand_tilde;
+
+/// What a risky call needs to be allowed to do. Findings are grouped by these so they can be
+/// matched against the grants of a permission system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    Exec,
+    FileSystem,
+    Network,
+    Eval,
+}
+
+impl Permission {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Exec => "exec",
+            Self::FileSystem => "filesystem",
+            Self::Network => "network",
+            Self::Eval => "eval",
+        }
+    }
+}
+
+/// A risky call found in a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// The file, relative to the audited plugin or directory.
+    pub file: String,
+    pub line: usize,
+    pub permission: Permission,
+    pub call: String,
+    pub reason: &'static str,
+}
+
+struct Rule {
+    pattern: Regex,
+    permission: Permission,
+    reason: &'static str,
+}
+
+impl Rule {
+    /// Matches calls of `call`, but not methods or fields that happen to share its name.
+    fn new(call: &str, permission: Permission, reason: &'static str) -> Self {
+        Self {
+            pattern: Regex::new(&format!(r"(?:^|[^.:\w])(?P<call>{call})\s*\(")).unwrap(),
+            permission,
+            reason,
+        }
+    }
+}
+
+lazy_static! {
+    static ref RULES: Vec<Rule> = vec![
+        Rule::new(r"os\.execute", Permission::Exec, "runs a shell command"),
+        Rule::new(r"io\.popen", Permission::Exec, "runs a shell command"),
+        Rule::new(r"core\.exec", Permission::Exec, "runs a shell command"),
+        Rule::new(
+            r"io\.open",
+            Permission::FileSystem,
+            "opens a file outside the plugin directory"
+        ),
+        Rule::new(
+            r"os\.(remove|rename)",
+            Permission::FileSystem,
+            "deletes or moves files"
+        ),
+        Rule::new(
+            r"socket\.connect",
+            Permission::Network,
+            "opens a network connection"
+        ),
+        Rule::new(
+            r"loadstring|load|dofile",
+            Permission::Eval,
+            "runs code that isn't part of the script"
+        ),
+    ];
+}
+
+/// Reason given to code loaded at runtime by a script that also talks to the network.
+const DOWNLOADED_CODE: &str = "runs code that may have been downloaded";
+
+/// Returns the risky calls in a script's source. Only whole line comments are skipped, this is a
+/// heuristic to warn about a script rather than proof of what it does.
+pub fn audit_source(file: &str, source: &str) -> Vec<Finding> {
+    let mut findings = vec![];
+    for (index, line) in source.lines().enumerate() {
+        if line.trim_start().starts_with("--") {
+            continue;
+        }
+        for rule in RULES.iter() {
+            let Some(found) = rule.pattern.captures(line) else {
+                continue;
+            };
+            // Files built from the plugin's own directory are what plugins are meant to use
+            if rule.permission == Permission::FileSystem && line.contains("plugin.dir(") {
+                continue;
+            }
+            findings.push(Finding {
+                file: file.to_string(),
+                line: index + 1,
+                permission: rule.permission,
+                call: found["call"].to_string(),
+                reason: rule.reason,
+            });
+        }
+    }
+    if findings.iter().any(|f| f.permission == Permission::Network) {
+        for finding in findings.iter_mut() {
+            if finding.permission == Permission::Eval {
+                finding.reason = DOWNLOADED_CODE;
+            }
+        }
+    }
+    findings
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if entry.file_name() != ".git" {
+                    collect_scripts(&path, scripts);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "lua") {
+                scripts.push(path);
+            }
+        }
+    }
+}
+
+/// Audits an installed plugin by name, or else a script or directory of scripts by path.
+pub fn audit(target: &str) -> Result<Vec<Finding>> {
+    let root = if get_plugins().iter().any(|name| name == target) && !target.contains("..") {
+        get_plugin_dir().join(target)
+    } else {
+        PathBuf::from(expand_tilde(target).as_ref())
+    };
+    let mut scripts = vec![];
+    if root.is_dir() {
+        collect_scripts(&root, &mut scripts);
+    } else if root.is_file() {
+        scripts.push(root.clone());
+    } else {
+        bail!("No plugin or script named '{}'", target);
+    }
+    scripts.sort();
+
+    let mut findings = vec![];
+    for script in scripts {
+        let source = fs::read_to_string(&script)?;
+        let file = script
+            .strip_prefix(&root)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .unwrap_or(&script)
+            .to_string_lossy()
+            .to_string();
+        findings.extend(audit_source(&file, &source));
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod test_audit {
+    use super::*;
+
+    #[test]
+    fn test_audit_source() {
+        let source = r#"
+-- os.execute("ignored in comments")
+local f = io.open(plugin.dir("walker") .. "/data.txt")
+local g = io.open("/etc/passwd")
+os.execute ("rm -rf ~")
+"#;
+        let findings = audit_source("main.lua", source);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].line, 4);
+        assert_eq!(findings[0].permission, Permission::FileSystem);
+        assert_eq!(findings[0].call, "io.open");
+        assert_eq!(findings[1].line, 5);
+        assert_eq!(findings[1].permission, Permission::Exec);
+        assert_eq!(findings[1].call, "os.execute");
+    }
+
+    #[test]
+    fn test_downloaded_code() {
+        let offline = audit_source("a.lua", "local f = loadstring(text)");
+        assert_eq!(offline[0].reason, "runs code that isn't part of the script");
+
+        let online = audit_source(
+            "b.lua",
+            "local s = socket.connect(host, 80)\nlocal f = load(s:read())",
+        );
+        assert_eq!(online.len(), 2);
+        assert_eq!(online[1].permission, Permission::Eval);
+        assert_eq!(online[1].reason, DOWNLOADED_CODE);
+
+        assert!(audit_source("c.lua", "plugin.load(\"walker\")").is_empty());
+    }
+
+    #[test]
+    fn test_missing_target() {
+        assert!(audit("/no/such/plugin/anywhere").is_err());
+    }
+}
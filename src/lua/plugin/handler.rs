@@ -1,13 +1,18 @@
+use std::time::{Duration, Instant};
+
+use crate::lua::api::ApiModule;
 use mlua::{UserData, UserDataMethods};
 
 use crate::io::SaveData;
 use crate::lua::{backend::Backend, constants::BACKEND};
 
 use super::{
+    audit::audit,
     functions::{
         add_plugin, get_plugin_dir, get_plugins, load_plugin, remove_plugin, update_plugin,
     },
-    settings::AutoLoadPlugins,
+    settings::{AutoLoadPlugins, PluginVerbosity, Verbosity},
+    usage::{self, PluginUsage},
 };
 
 pub struct Handler {}
@@ -18,6 +23,37 @@ impl Handler {
     }
 }
 
+pub const API: ApiModule = lua_api!("plugin", "Plugin management", [
+    "add(url: string, with_submodules: boolean)" => "Installs a plugin from a git repository",
+    "load(name: string) -> success: boolean, error: string" => "Loads a plugin",
+    "remove(name: string)" => "Removes a plugin",
+    "get_all() -> string[]" => "Returns the installed plugins",
+    "update(name: string)" => "Updates a plugin",
+    "enable(name: string)" => "Loads a plugin on startup",
+    "disable(name: string)" => "Doesn't load a plugin on startup",
+    "enabled() -> string[]" => "Returns the plugins loaded on startup",
+    "dir(name?: string) -> string" => "Returns the directory of a plugin, or of all plugins",
+    "caller() -> string?" => "Returns the plugin the running script belongs to",
+    "suspend(name: string)" => "Stops a loaded plugin's triggers, aliases and timers from running",
+    "resume(name: string)" => "Lets a suspended plugin's triggers, aliases and timers run again",
+    "is_suspended(name: string) -> boolean" => "Returns whether a plugin is suspended",
+    "record(name: string, seconds: number)" => "Adds the time a callback of a plugin took",
+    "audit(target: string) -> { file: string, line: integer, permission: string, call: string, reason: string }[]?, error: string?" => "Scans a plugin or script for risky calls",
+    "usage() -> table<string, { suspended: boolean, calls: integer, total_ms: number, recent_ms: number, timers: integer }>" => "Returns the callback time and timers of the plugins",
+    "verbosity(name: string) -> string" => "Returns how much output of a plugin `blight.output_for` shows",
+    "set_verbosity(name: string, verbosity: string)" => "Sets how much output of a plugin is shown, off, error, warn, info or debug",
+    "verbosities() -> table<string, string>" => "Returns the plugins with a verbosity other than info",
+]);
+
+fn set_suspended(ctx: &mlua::Lua, name: &str, suspended: bool) -> mlua::Result<()> {
+    if let Some(usage) = usage::usage(ctx) {
+        usage
+            .borrow_mut::<PluginUsage>()?
+            .set_suspended(name, suspended);
+    }
+    Ok(())
+}
+
 impl UserData for Handler {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function("add", |ctx, (url, with_submodules): (String, bool)| {
@@ -81,6 +117,86 @@ impl UserData for Handler {
                 Ok(get_plugin_dir().to_string_lossy().to_string())
             }
         });
+        methods.add_function("caller", |ctx, ()| Ok(usage::caller(ctx)));
+        methods.add_function("suspend", |ctx, name: String| {
+            set_suspended(ctx, &name, true)
+        });
+        methods.add_function("resume", |ctx, name: String| {
+            set_suspended(ctx, &name, false)
+        });
+        methods.add_function("is_suspended", |ctx, name: String| {
+            Ok(match usage::usage(ctx) {
+                Some(usage) => usage.borrow::<PluginUsage>()?.is_suspended(&name),
+                None => false,
+            })
+        });
+        methods.add_function("record", |ctx, (name, seconds): (String, f64)| {
+            usage::record(
+                ctx,
+                &name,
+                Duration::try_from_secs_f64(seconds).unwrap_or_default(),
+            );
+            Ok(())
+        });
+        methods.add_function("audit", |ctx, target: String| {
+            let findings = match audit(&target) {
+                Ok(findings) => findings,
+                Err(err) => return Ok((None, Some(err.to_string()))),
+            };
+            let table = ctx.create_table()?;
+            for finding in findings {
+                let entry = ctx.create_table()?;
+                entry.set("file", finding.file)?;
+                entry.set("line", finding.line)?;
+                entry.set("permission", finding.permission.name())?;
+                entry.set("call", finding.call)?;
+                entry.set("reason", finding.reason)?;
+                table.set(table.raw_len() + 1, entry)?;
+            }
+            Ok((Some(table), None))
+        });
+        methods.add_function("usage", |ctx, ()| {
+            let table = ctx.create_table()?;
+            if let Some(usage) = usage::usage(ctx) {
+                let usage = usage.borrow::<PluginUsage>()?;
+                let now = Instant::now();
+                for (name, plugin) in usage.plugins() {
+                    let entry = ctx.create_table()?;
+                    entry.set("suspended", plugin.suspended)?;
+                    entry.set("calls", plugin.calls)?;
+                    entry.set("total_ms", plugin.total.as_secs_f64() * 1000.0)?;
+                    entry.set("recent_ms", plugin.recent(now).as_secs_f64() * 1000.0)?;
+                    entry.set("timers", usage.timers(name))?;
+                    table.set(name.as_str(), entry)?;
+                }
+            }
+            Ok(table)
+        });
+        methods.add_function("verbosity", |_, name: String| {
+            let verbosity = PluginVerbosity::load()
+                .get(&name)
+                .copied()
+                .unwrap_or_default();
+            Ok(verbosity.to_string())
+        });
+        methods.add_function("set_verbosity", |_, (name, verbosity): (String, String)| {
+            let verbosity: Verbosity = verbosity.parse().map_err(mlua::Error::RuntimeError)?;
+            let mut verbosities = PluginVerbosity::load();
+            if verbosity == Verbosity::default() {
+                verbosities.remove(&name);
+            } else {
+                verbosities.insert(name, verbosity);
+            }
+            verbosities.save();
+            Ok(())
+        });
+        methods.add_function("verbosities", |ctx, ()| {
+            let table = ctx.create_table()?;
+            for (name, verbosity) in PluginVerbosity::load() {
+                table.set(name, verbosity.to_string())?;
+            }
+            Ok(table)
+        });
     }
 }
 
@@ -88,12 +204,15 @@ impl UserData for Handler {
 mod test_plugin {
     use mlua::Lua;
 
-    use super::Handler;
+    use super::{get_plugin_dir, Handler, PluginUsage};
+    use crate::lua::constants::PLUGIN_USAGE;
 
     fn get_lua_state() -> Lua {
         let plugin = Handler::new();
         let lua = Lua::new();
         lua.globals().set("plugin", plugin).unwrap();
+        lua.set_named_registry_value(PLUGIN_USAGE, PluginUsage::default())
+            .unwrap();
         lua
     }
 
@@ -104,7 +223,7 @@ mod test_plugin {
             .load("return plugin.dir()")
             .call::<_, String>(())
             .unwrap()
-            .ends_with(".run/test/data/plugins"));
+            .ends_with(".run/test/cache/plugins"));
     }
 
     #[test]
@@ -114,6 +233,46 @@ mod test_plugin {
             .load("return plugin.dir(\"awesome\")")
             .call::<_, String>(())
             .unwrap()
-            .ends_with(".run/test/data/plugins/awesome"));
+            .ends_with(".run/test/cache/plugins/awesome"));
+    }
+
+    #[test]
+    fn test_caller() {
+        let lua = get_lua_state();
+        assert_eq!(
+            lua.load("return plugin.caller()")
+                .call::<_, Option<String>>(())
+                .unwrap(),
+            None
+        );
+        let path = get_plugin_dir().join("walker").join("lib").join("util.lua");
+        let name: Option<String> = lua
+            .load("return (function () local name = plugin.caller() return name end)()")
+            .set_name(format!("@{}", path.to_string_lossy()))
+            .call(())
+            .unwrap();
+        assert_eq!(name, Some("walker".to_string()));
+    }
+
+    #[test]
+    fn test_suspend_and_usage() {
+        let lua = get_lua_state();
+        let (suspended, calls, total, still_suspended): (bool, u64, f64, bool) = lua
+            .load(
+                r#"
+                plugin.suspend("walker")
+                plugin.record("walker", 0.002)
+                plugin.record("walker", 0.003)
+                local usage = plugin.usage().walker
+                plugin.resume("walker")
+                return usage.suspended, usage.calls, usage.total_ms, plugin.is_suspended("walker")
+                "#,
+            )
+            .call(())
+            .unwrap();
+        assert!(suspended);
+        assert_eq!(calls, 2);
+        assert!((total - 5.0).abs() < 0.001);
+        assert!(!still_suspended);
     }
 }
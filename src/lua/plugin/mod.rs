@@ -1,5 +1,9 @@
-pub use handler::Handler;
+pub use handler::{Handler, API};
+pub use settings::{PluginVerbosity, Verbosity};
+pub use usage::PluginUsage;
 
+mod audit;
 mod functions;
 mod handler;
 mod settings;
+pub mod usage;
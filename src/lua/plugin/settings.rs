@@ -1,4 +1,10 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::io::SaveData;
 
@@ -13,3 +19,83 @@ impl SaveData for AutoLoadPlugins {
         true
     }
 }
+
+/// The level of output a plugin prints with `blight.output_for`, and how much of it is shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Verbosity {
+    /// Shows nothing, a verbosity but not a level to print at.
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl Verbosity {
+    /// Checks if output at `level` is shown with this verbosity.
+    pub fn shows(&self, level: Verbosity) -> bool {
+        level != Self::Off && level <= *self
+    }
+}
+
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            _ => Err(format!(
+                "Invalid verbosity: {s}, use off, error, warn, info or debug"
+            )),
+        }
+    }
+}
+
+/// Verbosity by plugin name, plugins that aren't in it show info and more important output.
+pub type PluginVerbosity = HashMap<String, Verbosity>;
+
+impl SaveData for PluginVerbosity {
+    fn relative_path() -> std::path::PathBuf {
+        crate::DATA_DIR.join("plugin_verbosity.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_plugin_settings {
+    use super::Verbosity;
+
+    #[test]
+    fn test_verbosity() {
+        assert!(Verbosity::Info.shows(Verbosity::Warn));
+        assert!(Verbosity::Info.shows(Verbosity::Info));
+        assert!(!Verbosity::Info.shows(Verbosity::Debug));
+        assert!(!Verbosity::Warn.shows(Verbosity::Info));
+        assert!(!Verbosity::Off.shows(Verbosity::Error));
+        assert!(!Verbosity::Debug.shows(Verbosity::Off));
+        assert_eq!("warn".parse(), Ok(Verbosity::Warn));
+        assert_eq!(Verbosity::Debug.to_string(), "debug");
+        assert!("verbose".parse::<Verbosity>().is_err());
+    }
+}
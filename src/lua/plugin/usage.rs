@@ -0,0 +1,205 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use mlua::{AnyUserData, Lua, UserData};
+
+use super::functions::get_plugin_dir;
+use crate::lua::constants::PLUGIN_USAGE;
+
+/// Callback time older than this no longer counts as recent.
+pub const RECENT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Callback time of a single plugin and whether it's suspended.
+#[derive(Debug, Default, Clone)]
+pub struct Usage {
+    pub suspended: bool,
+    pub calls: u64,
+    pub total: Duration,
+    samples: VecDeque<(Instant, Duration)>,
+}
+
+impl Usage {
+    fn record(&mut self, now: Instant, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        self.samples.push_back((now, elapsed));
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.samples.front() {
+            if now.duration_since(*at) <= RECENT_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Time spent in callbacks within the last [RECENT_WINDOW].
+    pub fn recent(&self, now: Instant) -> Duration {
+        self.samples
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= RECENT_WINDOW)
+            .map(|(_, elapsed)| *elapsed)
+            .sum()
+    }
+}
+
+/// What the loaded plugins cost: their callback time and the timers they own. Triggers and
+/// aliases carry their owning plugin themselves.
+#[derive(Debug, Default)]
+pub struct PluginUsage {
+    plugins: BTreeMap<String, Usage>,
+    timers: HashMap<u32, String>,
+}
+
+impl UserData for PluginUsage {}
+
+impl PluginUsage {
+    pub fn record(&mut self, name: &str, elapsed: Duration) {
+        self.entry(name).record(Instant::now(), elapsed);
+    }
+
+    pub fn set_suspended(&mut self, name: &str, suspended: bool) {
+        self.entry(name).suspended = suspended;
+    }
+
+    pub fn is_suspended(&self, name: &str) -> bool {
+        self.plugins.get(name).is_some_and(|usage| usage.suspended)
+    }
+
+    pub fn add_timer(&mut self, id: u32, name: String) {
+        self.entry(&name);
+        self.timers.insert(id, name);
+    }
+
+    pub fn remove_timer(&mut self, id: u32) {
+        self.timers.remove(&id);
+    }
+
+    pub fn clear_timers(&mut self) {
+        self.timers.clear();
+    }
+
+    pub fn timer_owner(&self, id: u32) -> Option<&str> {
+        self.timers.get(&id).map(String::as_str)
+    }
+
+    /// Number of timers and tick callbacks owned by the plugin.
+    pub fn timers(&self, name: &str) -> usize {
+        self.timers.values().filter(|owner| *owner == name).count()
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = (&String, &Usage)> {
+        self.plugins.iter()
+    }
+
+    fn entry(&mut self, name: &str) -> &mut Usage {
+        if !self.plugins.contains_key(name) {
+            self.plugins.insert(name.to_string(), Usage::default());
+        }
+        self.plugins.get_mut(name).unwrap()
+    }
+}
+
+/// Returns the usage of the state, if it tracks any.
+pub fn usage(lua: &Lua) -> Option<AnyUserData<'_>> {
+    lua.named_registry_value(PLUGIN_USAGE).ok()
+}
+
+/// Returns the plugin owning the closest script on the stack that lives in the plugin
+/// directory, ie. the plugin registering whatever is being registered.
+pub fn caller(lua: &Lua) -> Option<String> {
+    let mut level = 1;
+    while let Some(debug) = lua.inspect_stack(level) {
+        if let Some(name) = debug.source().source.as_deref().and_then(plugin_of) {
+            return Some(name);
+        }
+        level += 1;
+    }
+    None
+}
+
+/// Returns the plugin a script belongs to, if it lives in the plugin directory.
+pub fn plugin_of(source: &str) -> Option<String> {
+    let dir = get_plugin_dir().to_string_lossy().to_string();
+    let path = source.strip_prefix('@').unwrap_or(source);
+    path.strip_prefix(dir.as_str())
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|rest| rest.split('/').next())
+        .map(str::to_string)
+}
+
+/// Records the calling plugin, if any, as the owner of a timer.
+pub fn add_timer(lua: &Lua, id: u32) {
+    if let (Some(usage), Some(name)) = (usage(lua), caller(lua)) {
+        if let Ok(mut usage) = usage.borrow_mut::<PluginUsage>() {
+            usage.add_timer(id, name);
+        }
+    }
+}
+
+/// Returns the plugin owning a timer and whether it's suspended.
+pub fn timer_owner(lua: &Lua, id: u32) -> Option<(String, bool)> {
+    let usage = usage(lua)?;
+    let usage = usage.borrow::<PluginUsage>().ok()?;
+    let name = usage.timer_owner(id)?;
+    Some((name.to_string(), usage.is_suspended(name)))
+}
+
+/// Adds the time a callback of the plugin took.
+pub fn record(lua: &Lua, name: &str, elapsed: Duration) {
+    if let Some(usage) = usage(lua) {
+        if let Ok(mut usage) = usage.borrow_mut::<PluginUsage>() {
+            usage.record(name, elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_usage {
+    use super::*;
+
+    #[test]
+    fn test_recent_window() {
+        let start = Instant::now();
+        let mut usage = Usage::default();
+        usage.record(start, Duration::from_millis(5));
+        usage.record(start + Duration::from_secs(30), Duration::from_millis(7));
+        assert_eq!(
+            usage.recent(start + Duration::from_secs(40)),
+            Duration::from_millis(12)
+        );
+        assert_eq!(
+            usage.recent(start + Duration::from_secs(70)),
+            Duration::from_millis(7)
+        );
+        usage.record(start + Duration::from_secs(100), Duration::from_millis(1));
+        assert_eq!(usage.samples.len(), 1);
+        assert_eq!(usage.calls, 3);
+        assert_eq!(usage.total, Duration::from_millis(13));
+    }
+
+    #[test]
+    fn test_timers_and_suspension() {
+        let mut usage = PluginUsage::default();
+        usage.add_timer(1, "walker".to_string());
+        usage.add_timer(2, "walker".to_string());
+        usage.add_timer(3, "mapper".to_string());
+        assert_eq!(usage.timers("walker"), 2);
+        usage.remove_timer(2);
+        assert_eq!(usage.timers("walker"), 1);
+        assert_eq!(usage.timer_owner(3), Some("mapper"));
+
+        assert!(!usage.is_suspended("walker"));
+        usage.set_suspended("walker", true);
+        assert!(usage.is_suspended("walker"));
+        assert!(!usage.is_suspended("unknown"));
+
+        usage.clear_timers();
+        assert_eq!(usage.timer_owner(3), None);
+        assert_eq!(usage.plugins().count(), 2);
+    }
+}
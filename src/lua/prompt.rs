@@ -3,13 +3,26 @@ use mlua::{Function, Table, UserData};
 use crate::event::Event;
 
 use super::{
+    api::ApiModule,
     backend::Backend,
-    constants::{BACKEND, PROMPT_CONTENT, PROMPT_CURSOR_INDEX, PROMPT_INPUT_LISTENER_TABLE},
+    constants::{
+        BACKEND, PROMPT_CONTENT, PROMPT_CURSOR_INDEX, PROMPT_INPUT_LISTENER_TABLE, PROMPT_LABEL,
+    },
 };
 
 #[derive(Debug, Clone)]
 pub struct Prompt {}
 
+pub const API: ApiModule = lua_api!("prompt", "The input prompt", [
+    "set(line: string)" => "Sets the prompt content",
+    "get() -> string" => "Returns the prompt content",
+    "get_cursor_pos() -> integer" => "Returns the cursor position",
+    "set_cursor_pos(pos: integer)" => "Sets the cursor position",
+    "add_prompt_listener(callback: fun(input: string))" => "Adds a listener for prompt changes",
+    "set_label(label?: string)" => "Shows a label before the prompt content, or removes it",
+    "set_masked(masked: boolean)" => "Shows typed input as asterisks and keeps it out of the echo, logs and history",
+]);
+
 impl UserData for Prompt {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("set", |ctx, line: String| {
@@ -38,6 +51,17 @@ impl UserData for Prompt {
             backend.writer.send(Event::SetPromptCursorPos(pos)).unwrap();
             Ok(())
         });
+        methods.add_function("set_label", |ctx, label: Option<String>| {
+            ctx.set_named_registry_value(PROMPT_LABEL, label)?;
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::RedrawPromptInput).unwrap();
+            Ok(())
+        });
+        methods.add_function("set_masked", |ctx, masked: bool| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::MaskInput(masked)).unwrap();
+            Ok(())
+        });
         methods.add_function(
             "add_prompt_listener",
             |ctx, func: Function| -> mlua::Result<()> {
@@ -1,16 +1,35 @@
-use mlua::{Result as LuaResult, String as LuaString, Table, UserData};
+use mlua::{Function, Result as LuaResult, String as LuaString, Table, UserData};
 use std::ops::Not;
 
 use super::{
+    api::ApiModule,
     backend::Backend,
-    constants::{BACKEND, PROMPT_CONTENT, PROMPT_MASK_CONTENT},
+    constants::{BACKEND, PROMPT_CONTENT, PROMPT_MASK_CONTENT, PROMPT_MASK_SEGMENTS},
 };
 use crate::event::Event;
 use crate::model;
 
+fn valid_mask(prompt_data: &str, prompt_mask: &model::PromptMask) -> bool {
+    prompt_mask
+        .keys()
+        .map(|offset| *offset as usize)
+        .any(|offset| offset > prompt_data.len() + 1 || !prompt_data.is_char_boundary(offset))
+        .not()
+}
+
 #[derive(Debug, Clone)]
 pub struct PromptMask {}
 
+pub const API: ApiModule = lua_api!("prompt_mask", "Masks decorating the input prompt", [
+    "set(data: string, mask: table<integer, string>) -> boolean" => "Sets a mask for the prompt content",
+    "highlight(data: string, start: integer, stop: integer, style: table) -> boolean" => "Highlights part of the prompt content",
+    "add_segment(id: string, callback: fun(data: string): table?)" => "Adds a mask segment provider",
+    "remove_segment(id: string)" => "Removes a mask segment provider",
+    "refresh()" => "Reapplies the mask segments",
+    "clear()" => "Removes the mask",
+    "get() -> table<integer, string>" => "Returns the mask",
+]);
+
 impl UserData for PromptMask {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function(
@@ -22,13 +41,7 @@ impl UserData for PromptMask {
                     return Ok(false);
                 }
                 let prompt_mask = model::PromptMask::from(mask);
-                let valid = prompt_mask
-                    .iter()
-                    .map(|(offset, _)| *offset as usize)
-                    .any(|offset| {
-                        offset > prompt_data.len() + 1 || !prompt_data.is_char_boundary(offset)
-                    })
-                    .not();
+                let valid = valid_mask(&prompt_data, &prompt_mask);
                 if valid {
                     ctx.named_registry_value::<Backend>(BACKEND)?
                         .writer
@@ -38,6 +51,47 @@ impl UserData for PromptMask {
                 Ok(valid)
             },
         );
+        methods.add_function(
+            "highlight",
+            |ctx, (data, start, stop, style): (LuaString, i32, i32, Table)| -> LuaResult<bool> {
+                let prompt_data: String = ctx.named_registry_value(PROMPT_CONTENT)?;
+                if prompt_data != data.to_str()? || start < 1 || stop < start {
+                    return Ok(false);
+                }
+                let style = model::MaskStyle::from(style);
+                let prompt_mask = model::PromptMask::highlight(start - 1, stop - 1, &style);
+                let valid = valid_mask(&prompt_data, &prompt_mask);
+                if valid {
+                    ctx.named_registry_value::<Backend>(BACKEND)?
+                        .writer
+                        .send(Event::SetPromptMask(prompt_mask))
+                        .unwrap();
+                }
+                Ok(valid)
+            },
+        );
+        methods.add_function(
+            "add_segment",
+            |ctx, (id, func): (String, Function)| -> LuaResult<()> {
+                let table: Table = ctx.named_registry_value(PROMPT_MASK_SEGMENTS)?;
+                table.set(id, func)?;
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend.writer.send(Event::RedrawPromptInput).unwrap();
+                Ok(())
+            },
+        );
+        methods.add_function("remove_segment", |ctx, id: String| -> LuaResult<()> {
+            let table: Table = ctx.named_registry_value(PROMPT_MASK_SEGMENTS)?;
+            table.set(id, mlua::Nil)?;
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::RedrawPromptInput).unwrap();
+            Ok(())
+        });
+        methods.add_function("refresh", |ctx, ()| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::RedrawPromptInput).unwrap();
+            Ok(())
+        });
         methods.add_function("clear", |ctx, ()| -> LuaResult<()> {
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend.writer.send(Event::ClearPromptMask).unwrap();
@@ -53,7 +107,9 @@ impl UserData for PromptMask {
 mod test_prompt_mask {
     use crate::event::Event;
     use crate::lua::backend::Backend;
-    use crate::lua::constants::{BACKEND, PROMPT_CONTENT, PROMPT_MASK_CONTENT};
+    use crate::lua::constants::{
+        BACKEND, PROMPT_CONTENT, PROMPT_MASK_CONTENT, PROMPT_MASK_SEGMENTS,
+    };
     use crate::lua::prompt_mask::PromptMask;
     use crate::model;
     use mlua::{Lua, Table};
@@ -69,6 +125,8 @@ mod test_prompt_mask {
         lua.set_named_registry_value(PROMPT_CONTENT, prompt_content)
             .unwrap();
         lua.set_named_registry_value(BACKEND, backend).unwrap();
+        lua.set_named_registry_value(PROMPT_MASK_SEGMENTS, lua.create_table().unwrap())
+            .unwrap();
         (lua, reader)
     }
 
@@ -145,6 +203,45 @@ mod test_prompt_mask {
         assert!(reader.try_recv().is_err());
     }
 
+    #[test]
+    fn test_highlight() {
+        let prompt_state = "kill orc";
+        let (lua, reader) = get_lua_state(prompt_state);
+        let expected_mask = model::PromptMask::from(BTreeMap::from([
+            (5, "\x1b[1;31m".to_string()),
+            (8, "\x1b[0m".to_string()),
+        ]));
+        lua.load(r#"ok = prompt_mask.highlight("kill orc", 6, 8, { fg = "red", bold = true })"#)
+            .exec()
+            .unwrap();
+        assert!(lua.globals().get::<_, bool>("ok").unwrap());
+        assert_eq!(reader.recv(), Ok(Event::SetPromptMask(expected_mask)));
+
+        lua.load(r#"ok = prompt_mask.highlight("kill orc", 6, 20, { fg = "red" })"#)
+            .exec()
+            .unwrap();
+        assert!(!lua.globals().get::<_, bool>("ok").unwrap());
+        assert!(reader.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_segments() {
+        let (lua, reader) = get_lua_state("");
+        lua.load(r#"prompt_mask.add_segment("hp", function () return "100" end)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::RedrawPromptInput));
+        let table: Table = lua.named_registry_value(PROMPT_MASK_SEGMENTS).unwrap();
+        assert!(table.get::<_, mlua::Function>("hp").is_ok());
+
+        lua.load(r#"prompt_mask.remove_segment("hp")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(reader.recv(), Ok(Event::RedrawPromptInput));
+        let table: Table = lua.named_registry_value(PROMPT_MASK_SEGMENTS).unwrap();
+        assert!(table.get::<_, mlua::Function>("hp").is_err());
+    }
+
     #[test]
     fn test_clear_mask() {
         let (lua, reader) = get_lua_state("");
@@ -0,0 +1,58 @@
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::{event::Event, io::SaveData, model};
+use mlua::{Error, Result, Table, UserData, UserDataMethods};
+
+pub struct Reader {}
+
+pub const API: ApiModule = lua_api!("reader", "Reader mode output filters", [
+    "filters() -> table<string, boolean>" => "Returns all reader filters",
+    "get(filter: string) -> boolean" => "Checks if a reader filter is enabled",
+    "set(filter: string, enabled: boolean)" => "Enables or disables a reader filter",
+]);
+
+impl UserData for Reader {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("filters", |ctx, ()| -> Result<Table<'lua>> {
+            let filters = model::ReaderFilters::try_load().map_err(Error::external)?;
+            let result = ctx.create_table()?;
+            model::READER_FILTERS.iter().try_for_each(|key| {
+                result.set(key.to_string(), filters.get(key).map_err(Error::external)?)
+            })?;
+            Ok(result)
+        });
+        methods.add_function("get", |_, key: String| -> Result<bool> {
+            let filters = model::ReaderFilters::try_load().map_err(Error::external)?;
+            filters.get(&key).map_err(Error::external)
+        });
+        methods.add_function("set", |ctx, (key, val): (String, bool)| {
+            let mut filters = model::ReaderFilters::try_load().map_err(Error::external)?;
+            filters.set(&key, val).map_err(Error::external)?;
+            filters.save();
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend
+                .writer
+                .send(Event::ReaderFiltersChanged)
+                .map_err(Error::external)?;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_reader {
+    use super::Reader;
+    use mlua::Lua;
+
+    #[test]
+    fn test_reader_filters() {
+        let lua = Lua::new();
+        lua.globals().set("reader", Reader {}).unwrap();
+        let filters: mlua::Table = lua.load("return reader.filters()").call(()).unwrap();
+        assert!(matches!(
+            filters.raw_get(crate::model::PROGRESS_FILTER).unwrap(),
+            mlua::Value::Boolean(_),
+        ));
+        assert!(lua.load(r#"return reader.get("colors")"#).exec().is_err());
+        assert!(lua.load(r#"reader.set("colors", true)"#).exec().is_err());
+    }
+}
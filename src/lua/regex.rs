@@ -1,93 +1,205 @@
+use super::api::ApiModule;
 use crate::model::{Regex as Re, RegexOptions};
+use fancy_regex::{Captures as FancyCaptures, Regex as FancyRe};
 use mlua::{FromLua, Table, UserData, UserDataMethods};
 use std::fmt::{Display, Formatter};
 
+/// Reads the first of `names` that is set, so the short names can be used too.
+fn option(opts: &Table, names: &[&str]) -> bool {
+    names
+        .iter()
+        .find_map(|name| opts.get::<_, Option<bool>>(*name).ok().flatten())
+        .unwrap_or(false)
+}
+
 fn parse_regex_options(opts: &Option<Table>) -> RegexOptions {
     let mut options = RegexOptions::default();
     if let Some(opts) = &opts {
-        options.case_insensitive = opts
-            .get("case_insensitive")
-            .unwrap_or(options.case_insensitive);
-        options.multi_line = opts.get("multi_line").unwrap_or(options.multi_line);
-        options.dot_matches_new_line = opts
-            .get("dot_matches_new_line")
-            .unwrap_or(options.dot_matches_new_line);
-        options.swap_greed = opts.get("swap_greed").unwrap_or(options.swap_greed);
-        options.ignore_whitespace = opts
-            .get("ignore_whitespace")
-            .unwrap_or(options.ignore_whitespace);
+        options.case_insensitive = option(opts, &["case_insensitive", "icase"]);
+        options.multi_line = option(opts, &["multi_line", "multiline"]);
+        options.dot_matches_new_line = option(opts, &["dot_matches_new_line", "dotall"]);
+        options.swap_greed = option(opts, &["swap_greed"]);
+        options.ignore_whitespace = option(opts, &["ignore_whitespace"]);
     }
     options
 }
 
+/// Builds a regex for the backtracking engine, with the options passed as inline flags since its
+/// builder doesn't apply them to the parts of a pattern it matches itself.
+fn build_fancy(pattern: &str, options: &RegexOptions) -> mlua::Result<FancyRe> {
+    let flags = options.flags();
+    let pattern = if flags.is_empty() {
+        pattern.to_string()
+    } else {
+        format!("(?{flags}){pattern}")
+    };
+    FancyRe::new(&pattern).map_err(|err| mlua::Error::RuntimeError(err.to_string()))
+}
+
+fn build(pattern: &str, options: RegexOptions, fancy: bool) -> mlua::Result<Regex> {
+    if fancy {
+        return build_fancy(pattern, &options).map(|re| Regex {
+            engine: Engine::Fancy(re, options),
+        });
+    }
+    match Re::new(pattern, Some(options.clone())) {
+        Ok(re) => Ok(Regex {
+            engine: Engine::Default(re),
+        }),
+        // Point out the option when the pattern is fine for the backtracking engine
+        Err(err) if build_fancy(pattern, &options).is_ok() => Err(mlua::Error::RuntimeError(
+            format!("{err}\nLookaround and backreferences need the `fancy = true` option"),
+        )),
+        Err(err) => Err(mlua::Error::RuntimeError(err.to_string())),
+    }
+}
+
+fn runtime_error(err: fancy_regex::Error) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("Fancy regex failed to match: {err}"))
+}
+
+fn fancy_captures(captures: FancyCaptures) -> Vec<String> {
+    captures
+        .iter()
+        .map(|c| match c {
+            Some(m) => m.as_str().to_string(),
+            None => String::new(),
+        })
+        .collect()
+}
+
 pub struct RegexLib;
 
+pub const API: ApiModule = lua_api!("regex", "Regular expressions", [
+    "new(pattern: string, options?: { case_insensitive?: boolean, multi_line?: boolean, dot_matches_new_line?: boolean, swap_greed?: boolean, ignore_whitespace?: boolean, fancy?: boolean, icase?: boolean, multiline?: boolean, dotall?: boolean }) -> Regex" => "Compiles a regular expression",
+]);
+
 impl UserData for RegexLib {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function(
             "new",
             |_, (pattern, opts): (String, Option<Table>)| -> mlua::Result<Regex> {
                 let options = parse_regex_options(&opts);
-                match Re::new(&pattern, Some(options)) {
-                    Ok(re) => Ok(Regex { regex: re }),
-                    Err(msg) => Err(mlua::Error::RuntimeError(msg.to_string())),
-                }
+                let fancy = opts
+                    .as_ref()
+                    .and_then(|opts| opts.get::<_, Option<bool>>("fancy").ok().flatten())
+                    .unwrap_or(false);
+                build(&pattern, options, fancy)
             },
         );
     }
 }
 
+/// The default engine matches in linear time. The fancy engine adds lookaround and
+/// backreferences by backtracking, but only for the parts of a pattern that use them.
+#[derive(Clone)]
+pub enum Engine {
+    Default(Re),
+    Fancy(FancyRe, RegexOptions),
+}
+
 #[derive(Clone, FromLua)]
 pub struct Regex {
-    pub regex: Re,
+    pub engine: Engine,
+}
+
+impl Regex {
+    /// Returns the regex for functions the fancy engine isn't supported by.
+    pub fn default_engine(self) -> mlua::Result<Re> {
+        match self.engine {
+            Engine::Default(re) => Ok(re),
+            Engine::Fancy(..) => Err(mlua::Error::RuntimeError(format!(
+                "'{self}' is a fancy regex, which can only be used by triggers and Regex methods. Create it without `fancy = true`"
+            ))),
+        }
+    }
+
+    pub fn options(&self) -> &RegexOptions {
+        match &self.engine {
+            Engine::Default(re) => re.options(),
+            Engine::Fancy(_, options) => options,
+        }
+    }
 }
 
 impl Display for Regex {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.regex.fmt(f)
+        match &self.engine {
+            Engine::Default(re) => re.fmt(f),
+            // Without the `(?flags)` prefix the options were turned into
+            Engine::Fancy(re, options) => match options.flags().len() {
+                0 => re.as_str().fmt(f),
+                len => re.as_str()[len + 3..].fmt(f),
+            },
+        }
     }
 }
 
+pub const REGEX_API: ApiModule = lua_api!(class "Regex", "A compiled regular expression", [
+    ":test(src: string) -> boolean" => "Checks if the regex matches",
+    ":match(src: string) -> string[]?" => "Returns the captures of the first match",
+    ":match_all(src: string) -> string[][]?" => "Returns the captures of all matches",
+    ":replace(src: string, replace: string, count?: integer) -> string" => "Replaces matches",
+    ":regex() -> string" => "Returns the pattern",
+    ":is_fancy() -> boolean" => "Checks if the regex uses the fancy engine",
+    ":flags() -> string" => "Returns the options as inline flags, eg. `im`",
+    ":options() -> table" => "Returns the options the regex was created with",
+]);
+
 impl UserData for Regex {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_method(
             "test",
             |_, this, src: String| -> mlua::Result<mlua::Value> {
-                Ok(mlua::Value::Boolean(this.regex.is_match(&src)))
+                let matched = match &this.engine {
+                    Engine::Default(re) => re.is_match(&src),
+                    Engine::Fancy(re, _) => re.is_match(&src).map_err(runtime_error)?,
+                };
+                Ok(mlua::Value::Boolean(matched))
             },
         );
         methods.add_method(
             "match",
             |_, this, src: String| -> mlua::Result<Option<Vec<String>>> {
-                let re = &this.regex;
-                let matches = re.captures(&src).map(|captures| {
-                    captures
-                        .iter()
-                        .map(|c| match c {
-                            Some(m) => m.as_str().to_string(),
-                            None => String::new(),
-                        })
-                        .collect()
-                });
-                Ok(matches)
-            },
-        );
-        methods.add_method(
-            "match_all",
-            |_, this, src: String| -> mlua::Result<Option<Vec<Vec<String>>>> {
-                let re = &this.regex;
-                let matches = re
-                    .captures_iter(&src)
-                    .map(|captures| {
+                let matches = match &this.engine {
+                    Engine::Default(re) => re.captures(&src).map(|captures| {
                         captures
                             .iter()
                             .map(|c| match c {
                                 Some(m) => m.as_str().to_string(),
                                 None => String::new(),
                             })
-                            .collect::<Vec<String>>()
-                    })
-                    .collect::<Vec<Vec<String>>>();
+                            .collect()
+                    }),
+                    Engine::Fancy(re, _) => re
+                        .captures(&src)
+                        .map_err(runtime_error)?
+                        .map(fancy_captures),
+                };
+                Ok(matches)
+            },
+        );
+        methods.add_method(
+            "match_all",
+            |_, this, src: String| -> mlua::Result<Option<Vec<Vec<String>>>> {
+                let matches = match &this.engine {
+                    Engine::Default(re) => re
+                        .captures_iter(&src)
+                        .map(|captures| {
+                            captures
+                                .iter()
+                                .map(|c| match c {
+                                    Some(m) => m.as_str().to_string(),
+                                    None => String::new(),
+                                })
+                                .collect::<Vec<String>>()
+                        })
+                        .collect::<Vec<Vec<String>>>(),
+                    Engine::Fancy(re, _) => re
+                        .captures_iter(&src)
+                        .map(|captures| captures.map(fancy_captures).map_err(runtime_error))
+                        .collect::<mlua::Result<Vec<Vec<String>>>>()?,
+                };
 
                 if !matches.is_empty() {
                     Ok(Some(matches))
@@ -102,12 +214,40 @@ impl UserData for Regex {
              this,
              (src, replace, count): (String, String, Option<usize>)|
              -> mlua::Result<String> {
-                let re = &this.regex;
                 let limit = count.unwrap_or(0);
-                Ok(re.replacen::<&str>(&src, limit, &replace).to_mut().clone())
+                match &this.engine {
+                    Engine::Default(re) => {
+                        Ok(re.replacen::<&str>(&src, limit, &replace).to_mut().clone())
+                    }
+                    Engine::Fancy(re, _) => Ok(re
+                        .try_replacen(&src, limit, replace.as_str())
+                        .map_err(runtime_error)?
+                        .into_owned()),
+                }
             },
         );
         methods.add_method("regex", |_, this, ()| Ok(this.to_string()));
+        methods.add_method("is_fancy", |_, this, ()| {
+            Ok(matches!(this.engine, Engine::Fancy(..)))
+        });
+        methods.add_method("flags", |_, this, ()| Ok(this.options().flags()));
+        methods.add_method("options", |lua, this, ()| {
+            let options = this.options();
+            let table = lua.create_table()?;
+            for (name, set) in [
+                ("case_insensitive", options.case_insensitive),
+                ("multi_line", options.multi_line),
+                ("dot_matches_new_line", options.dot_matches_new_line),
+                ("swap_greed", options.swap_greed),
+                ("ignore_whitespace", options.ignore_whitespace),
+                ("fancy", matches!(this.engine, Engine::Fancy(..))),
+            ] {
+                if set {
+                    table.set(name, true)?;
+                }
+            }
+            Ok(table)
+        });
     }
 }
 
@@ -381,4 +521,83 @@ mod test_regexp {
             None
         );
     }
+
+    #[test]
+    fn test_short_options() {
+        let state = get_lua();
+        assert_eq!(
+            state
+                .load(
+                    r#"
+            local re = regex.new("^start.*END$", { icase = true, multiline = true, dotall = true })
+            local options = re:options()
+            return re:test("x\nstart\nend"), re:flags(), options.case_insensitive, options.swap_greed
+            "#,
+                )
+                .call::<_, (bool, String, bool, Option<bool>)>(())
+                .unwrap(),
+            (true, "ims".to_string(), true, None)
+        );
+        assert_eq!(
+            state
+                .load(
+                    r#"
+            local re = regex.new("^(?!bob)\\w+$", { fancy = true, icase = true })
+            return re:regex(), re:flags(), re:options().fancy
+            "#,
+                )
+                .call::<_, (String, String, bool)>(())
+                .unwrap(),
+            ("^(?!bob)\\w+$".to_string(), "i".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_fancy() {
+        let state = get_lua();
+        let err = state
+            .load(r#"regex.new("^(?!Bob )(\\w+) tells you")"#)
+            .exec()
+            .unwrap_err();
+        assert!(err.to_string().contains("`fancy = true`"));
+        assert!(!state
+            .load(r#"regex.new("^(\\w+")"#)
+            .exec()
+            .unwrap_err()
+            .to_string()
+            .contains("fancy"));
+
+        assert_eq!(
+            state
+                .load(
+                    r#"
+            local re = regex.new("^(?!bob )(\\w+) tells you", { fancy = true, case_insensitive = true })
+            return re:is_fancy(), re:test("Bob tells you"), re:match("Jim tells you")[2]
+            "#,
+                )
+                .call::<_, (bool, bool, String)>(())
+                .unwrap(),
+            (true, false, "Jim".to_string())
+        );
+        assert_eq!(
+            state
+                .load(
+                    r#"
+            local re = regex.new("\\b(\\w+) \\1\\b", { fancy = true })
+            return re:replace("the the cat sat sat", "$1")
+            "#,
+                )
+                .call::<_, String>(())
+                .unwrap(),
+            "the cat sat"
+        );
+        assert_eq!(
+            state
+                .load(r#"return regex.new("(a)(?=b)", { fancy = true }):match_all("ab ac ab")"#)
+                .call::<_, Option<Vec<Vec<String>>>>(())
+                .unwrap()
+                .map(|matches| matches.len()),
+            Some(2)
+        );
+    }
 }
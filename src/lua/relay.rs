@@ -0,0 +1,143 @@
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::net::{RelayWorker, WebhookFormat};
+use mlua::{AnyUserData, Lua, Result as LuaResult, UserData, UserDataMethods};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Webhooks lines are forwarded to, by trigger tag, and the running listener if any. The worker
+/// posting messages is started when first needed, and it and the listener are stopped when the
+/// script state is dropped.
+#[derive(Default)]
+pub struct Relay {
+    webhooks: HashMap<String, (String, WebhookFormat)>,
+    listener: Option<Arc<AtomicBool>>,
+    worker: Option<RelayWorker>,
+}
+
+impl Relay {
+    pub const LUA_GLOBAL_NAME: &'static str = "relay";
+
+    fn worker(&mut self, lua: &Lua) -> LuaResult<&RelayWorker> {
+        if self.worker.is_none() {
+            let backend: Backend = lua.named_registry_value(BACKEND)?;
+            self.worker = Some(RelayWorker::new(backend.writer));
+        }
+        Ok(self.worker.as_ref().unwrap())
+    }
+
+    fn stop(&mut self) {
+        if let Some(running) = self.listener.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn with_relay<R>(lua: &Lua, f: impl FnOnce(&mut Relay) -> R) -> LuaResult<R> {
+    let relay: AnyUserData = lua.globals().get(Relay::LUA_GLOBAL_NAME)?;
+    let mut relay = relay.borrow_mut::<Relay>()?;
+    Ok(f(&mut relay))
+}
+
+pub const API: ApiModule = lua_api!("relay", "Forwarding of tagged lines to chat webhooks", [
+    "add_webhook(tag: string, url: string, format?: string)" => "Forwards lines matched by triggers with `tag` to a webhook",
+    "remove_webhook(tag: string)" => "Stops forwarding lines with `tag`",
+    "send(tag: string, text: string) -> boolean" => "Forwards text to the webhook for `tag`",
+    "listen(url: string, interval?: number)" => "Prints messages polled from an endpoint",
+    "stop_listening()" => "Stops polling the listen endpoint",
+]);
+
+impl UserData for Relay {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "add_webhook",
+            |ctx, (tag, url, format): (String, String, Option<String>)| -> LuaResult<()> {
+                let format = match format {
+                    Some(name) => WebhookFormat::parse(&name).ok_or_else(|| {
+                        mlua::Error::RuntimeError(format!("Unknown webhook format: '{name}'"))
+                    })?,
+                    None => WebhookFormat::Discord,
+                };
+                with_relay(ctx, |relay| relay.webhooks.insert(tag, (url, format)))?;
+                Ok(())
+            },
+        );
+        methods.add_function("remove_webhook", |ctx, tag: String| -> LuaResult<()> {
+            with_relay(ctx, |relay| relay.webhooks.remove(&tag))?;
+            Ok(())
+        });
+        methods.add_function(
+            "send",
+            |ctx, (tag, text): (String, String)| -> LuaResult<bool> {
+                with_relay(ctx, |relay| match relay.webhooks.get(&tag).cloned() {
+                    Some((url, format)) => Ok(relay.worker(ctx)?.send(url, format, text)),
+                    None => Ok(false),
+                })?
+            },
+        );
+        methods.add_function(
+            "listen",
+            |ctx, (url, interval): (String, Option<f64>)| -> LuaResult<()> {
+                let interval = Duration::try_from_secs_f64(interval.unwrap_or(10.0))
+                    .ok()
+                    .filter(|interval| *interval >= Duration::from_secs(1))
+                    .ok_or_else(|| {
+                        mlua::Error::RuntimeError(
+                            "The listen interval must be at least 1 second".to_string(),
+                        )
+                    })?;
+                with_relay(ctx, |relay| {
+                    relay.stop();
+                    relay.listener = Some(relay.worker(ctx)?.listen(url, interval));
+                    Ok(())
+                })?
+            },
+        );
+        methods.add_function("stop_listening", |ctx, ()| with_relay(ctx, Relay::stop));
+    }
+}
+
+#[cfg(test)]
+mod test_relay {
+    use super::Relay;
+    use mlua::Lua;
+
+    fn get_lua() -> Lua {
+        let lua = Lua::new();
+        lua.globals()
+            .set(Relay::LUA_GLOBAL_NAME, Relay::default())
+            .unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_webhooks() {
+        let lua = get_lua();
+        lua.load(r#"relay.add_webhook("guild", "https://example.com/hook", "slack")"#)
+            .exec()
+            .unwrap();
+        assert!(lua
+            .load(r#"relay.add_webhook("guild", "https://example.com/hook", "irc")"#)
+            .exec()
+            .is_err());
+        assert!(!lua
+            .load(r#"relay.remove_webhook("guild"); return relay.send("guild", "hi")"#)
+            .eval::<bool>()
+            .unwrap());
+        for interval in ["0.1", "0/0", "math.huge", "-1"] {
+            assert!(lua
+                .load(&format!(
+                    r#"relay.listen("https://example.com", {interval})"#
+                ))
+                .exec()
+                .is_err());
+        }
+    }
+}
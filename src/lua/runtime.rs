@@ -0,0 +1,70 @@
+use mlua::{Lua, Result as LuaResult, Value};
+
+/// Returns the version of the Lua runtime, eg. `Lua 5.4`, along with the LuaJIT version when
+/// running on LuaJIT.
+pub fn lua_version(lua: &Lua) -> LuaResult<(String, Option<String>)> {
+    let globals = lua.globals();
+    let version = globals.get("_VERSION")?;
+    let jit = match globals.get("jit")? {
+        Value::Table(jit) => jit.get("version")?,
+        _ => None,
+    };
+    Ok((version, jit))
+}
+
+#[cfg(test)]
+mod test_runtime {
+    use super::*;
+
+    const COMPAT: &str = include_str!("../../resources/lua/compat.lua");
+
+    #[test]
+    fn test_lua_version() {
+        let lua = Lua::new();
+        #[cfg(feature = "lua54")]
+        assert_eq!(lua_version(&lua).unwrap(), ("Lua 5.4".to_string(), None));
+        #[cfg(feature = "luajit")]
+        {
+            let (version, jit) = lua_version(&lua).unwrap();
+            assert_eq!(version, "Lua 5.1");
+            assert!(jit.is_some_and(|jit| jit.starts_with("LuaJIT")));
+        }
+    }
+
+    #[test]
+    fn test_compat_polyfills() {
+        let lua = Lua::new();
+        // Strip the state down to what LuaJIT provides before loading the polyfills.
+        lua.load(
+            "unpack = table.unpack or unpack; table.unpack = nil; table.pack = nil; utf8 = nil",
+        )
+        .exec()
+        .unwrap();
+        lua.load(COMPAT).exec().unwrap();
+        let (a, b): (i64, i64) = lua.load("return table.unpack({ 1, 2 })").eval().unwrap();
+        assert_eq!((a, b), (1, 2));
+        let n: i64 = lua.load("return table.pack(1, nil, 3).n").eval().unwrap();
+        assert_eq!(n, 3);
+        let s: String = lua
+            .load("return utf8.char(72, 228, 8364, 128075)")
+            .eval()
+            .unwrap();
+        assert_eq!(s, "Hä€👋");
+        let codes: Vec<i64> = lua
+            .load(
+                r#"
+            local codes = {}
+            for _, c in utf8.codes("Hä€👋") do codes[#codes + 1] = c end
+            return codes
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(codes, vec![72, 228, 8364, 128075]);
+        let bits: (i64, i64, i64) = lua
+            .load("return bit.bor(1, 4), bit.band(7, bit.bnot(2)), bit.lshift(1, 4)")
+            .eval()
+            .unwrap();
+        assert_eq!(bits, (5, 5, 16));
+    }
+}
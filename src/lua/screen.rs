@@ -0,0 +1,168 @@
+use mlua::{FromLua, Result as LuaResult, Table, UserData, UserDataMethods, Value};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND, regex::Regex};
+use crate::event::Event;
+use crate::io::{ExportFormat, ExportRange, SaveData};
+use crate::model::{Regex as MRegex, ScrollbackSettings, MIN_SCROLLBACK_LINES};
+
+pub struct Screen {}
+
+pub const API: ApiModule = lua_api!("screen", "Exporting the scrollback and limiting its memory use", [
+    "export(range: integer|{ from: integer, to: integer }|string|Regex, format?: \"html\"|\"ansi\", name?: string)" => "Exports part of the scrollback to a file",
+    "scrollback() -> { lines: integer, memory: integer, spill: boolean, auto_pause: integer }" => "Returns how much scrollback is kept in memory, whether older lines are kept on disk and how far back scrolling holds output",
+    "set_scrollback(options: { lines?: integer, memory?: integer, spill?: boolean, auto_pause?: integer })" => "Changes, applies and saves the scrollback limits",
+]);
+
+fn parse_range(ctx: &mlua::Lua, range: Value) -> LuaResult<ExportRange> {
+    match range {
+        Value::Integer(count) => Ok(ExportRange::Last(count.max(0) as usize)),
+        Value::Table(range) => Ok(ExportRange::Lines(range.get("from")?, range.get("to")?)),
+        Value::String(pattern) => MRegex::new(pattern.to_str()?, None)
+            .map(ExportRange::Matching)
+            .map_err(|err| mlua::Error::RuntimeError(err.to_string())),
+        value => Ok(ExportRange::Matching(
+            Regex::from_lua(value, ctx)?.default_engine()?,
+        )),
+    }
+}
+
+fn parse_scrollback_options(opts: &Table) -> LuaResult<ScrollbackSettings> {
+    let mut settings = ScrollbackSettings::load();
+    if let Some(lines) = opts.get::<_, Option<usize>>("lines")? {
+        settings.lines = lines.max(MIN_SCROLLBACK_LINES);
+    }
+    if let Some(memory) = opts.get::<_, Option<usize>>("memory")? {
+        settings.memory = memory.max(1);
+    }
+    if let Some(spill) = opts.get::<_, Option<bool>>("spill")? {
+        settings.spill = spill;
+    }
+    if let Some(auto_pause) = opts.get::<_, Option<usize>>("auto_pause")? {
+        settings.auto_pause = auto_pause;
+    }
+    Ok(settings)
+}
+
+impl UserData for Screen {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("scrollback", |ctx, ()| {
+            let settings = ScrollbackSettings::load();
+            let table = ctx.create_table()?;
+            table.set("lines", settings.lines)?;
+            table.set("memory", settings.memory)?;
+            table.set("spill", settings.spill)?;
+            table.set("auto_pause", settings.auto_pause)?;
+            Ok(table)
+        });
+        methods.add_function("set_scrollback", |ctx, opts: Table| {
+            parse_scrollback_options(&opts)?.save();
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend
+                .writer
+                .send(Event::ScrollbackSettingsChanged)
+                .unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "export",
+            |ctx, (range, format, name): (Value, Option<String>, Option<String>)| {
+                let range = parse_range(ctx, range)?;
+                let format = match format {
+                    Some(format) => ExportFormat::parse(&format).ok_or_else(|| {
+                        mlua::Error::RuntimeError(format!("Unknown export format: {format}"))
+                    })?,
+                    None => ExportFormat::Html,
+                };
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::Export(range, format, name))
+                    .unwrap();
+                Ok(())
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_screen {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use mlua::{Lua, Table};
+
+    use super::{parse_scrollback_options, Screen};
+    use crate::{
+        event::Event,
+        io::{ExportFormat, ExportRange},
+        lua::{backend::Backend, constants::BACKEND, regex::RegexLib},
+        model::Regex,
+    };
+
+    fn assert_event(lua_code: &str, event: Event) {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals().set("screen", Screen {}).unwrap();
+        lua.globals().set("regex", RegexLib {}).unwrap();
+        lua.load(lua_code).exec().unwrap();
+
+        assert_eq!(reader.recv(), Ok(event));
+    }
+
+    #[test]
+    fn test_export() {
+        assert_event(
+            "screen.export(100)",
+            Event::Export(ExportRange::Last(100), ExportFormat::Html, None),
+        );
+        assert_event(
+            "screen.export({ from = 10, to = 20 }, 'ansi', 'fight')",
+            Event::Export(
+                ExportRange::Lines(10, 20),
+                ExportFormat::Ansi,
+                Some("fight".to_string()),
+            ),
+        );
+        let pattern = Regex::new("tells you", None).unwrap();
+        assert_event(
+            "screen.export('tells you')",
+            Event::Export(
+                ExportRange::Matching(pattern.clone()),
+                ExportFormat::Html,
+                None,
+            ),
+        );
+        assert_event(
+            "screen.export(regex.new('tells you'))",
+            Event::Export(ExportRange::Matching(pattern), ExportFormat::Html, None),
+        );
+    }
+
+    #[test]
+    fn test_set_scrollback() {
+        assert_event(
+            "screen.set_scrollback({ lines = 10, spill = false })",
+            Event::ScrollbackSettingsChanged,
+        );
+    }
+
+    #[test]
+    fn test_scrollback_options() {
+        let lua = Lua::new();
+        let opts: Table = lua
+            .load("return { memory = 0, auto_pause = 0 }")
+            .eval()
+            .unwrap();
+        let settings = parse_scrollback_options(&opts).unwrap();
+        assert_eq!(settings.memory, 1);
+        assert_eq!(settings.auto_pause, 0);
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        let lua = Lua::new();
+        lua.globals().set("screen", Screen {}).unwrap();
+        assert!(lua.load("screen.export(10, 'pdf')").exec().is_err());
+    }
+}
@@ -1,15 +1,168 @@
-use mlua::{UserData, UserDataMethods};
+use std::path::Path;
 
-use crate::event::Event;
+use mlua::{Function, Lua, Result as LuaResult, Table, UserData, UserDataMethods, Value};
+
+use crate::{
+    event::Event,
+    io::SaveData,
+    model::{Origin, ReloadExclusions, ScriptOrder},
+    tools::util::expand_tilde,
+};
 
 use super::{
+    api::ApiModule,
+    api_version,
     backend::Backend,
-    constants::{BACKEND, SCRIPT_RESET_LISTENERS},
+    constants::{BACKEND, SCRIPT_COMPILERS, SCRIPT_ERROR_LISTENERS, SCRIPT_RESET_LISTENERS},
+    plugin,
 };
 
+/// Compiles a script written in a language that compiles to Lua, eg. Fennel. Scripts without a
+/// registered compiler for their file extension are returned as is.
+pub fn compile(lua: &Lua, path: &str, source: String) -> LuaResult<String> {
+    let compilers: Table = lua.named_registry_value(SCRIPT_COMPILERS)?;
+    let compiler = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => compilers.get::<_, Option<Function>>(ext)?,
+        None => None,
+    };
+    match compiler {
+        Some(compiler) => compiler.call((source, path)),
+        None => Ok(source),
+    }
+}
+
+/// Returns the path a script is known by, with `~` expanded and links resolved so it can be
+/// compared with the paths of file system events.
+pub fn canonical_path(path: &str) -> String {
+    let path = expand_tilde(path);
+    std::fs::canonicalize(path.as_ref())
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Returns the path of the closest script on the stack that was loaded from a file, ie. the
+/// script registering whatever is being registered.
+pub fn caller(lua: &Lua) -> Option<String> {
+    let mut level = 1;
+    while let Some(debug) = lua.inspect_stack(level) {
+        if let Some(source) = debug.source().source {
+            if let Some(path) = source.strip_prefix('@') {
+                return Some(canonical_path(path));
+            }
+        }
+        level += 1;
+    }
+    None
+}
+
+/// Returns where output printed by the running code comes from, ie. the closest code on the
+/// stack that isn't bundled with the client. Code run from the command line counts as a script.
+pub fn origin(lua: &Lua) -> Origin {
+    let mut level = 1;
+    while let Some(debug) = lua.inspect_stack(level) {
+        level += 1;
+        let Some(source) = debug.source().source else {
+            continue;
+        };
+        if source.starts_with('@') {
+            return plugin::usage::plugin_of(&source).map_or(Origin::Script, Origin::Plugin);
+        }
+        // Bundled resources are named after their file, without the `@` of script files
+        if source != "=[C]" && !source.ends_with(".lua") {
+            return Origin::Script;
+        }
+    }
+    Origin::Client
+}
+
+/// Returns the source code of a function defined in a script file or a chunk of code, so it can
+/// be written to another file. Functions sharing their first or last line with other code are cut
+/// at their `function` keyword and last `end`, and `None` is returned when that isn't valid code.
+pub fn function_source(lua: &Lua, function: &Function) -> Option<String> {
+    let info = function.info();
+    let first = info.line_defined.filter(|line| *line > 0)?;
+    let last = info.last_line_defined?;
+    let source = info.source?;
+    let text = match source.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path).ok()?,
+        None if source.starts_with('=') => return None,
+        None => source,
+    };
+    let code = text
+        .lines()
+        .skip(first - 1)
+        .take(last + 1 - first)
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let start = code.lines().next()?.rfind("function")?;
+    let end = code.rfind("end")? + "end".len();
+    let code = code.get(start..end)?.to_string();
+    lua.load(format!("return {code}"))
+        .into_function()
+        .ok()
+        .map(|_| code)
+}
+
+/// A `package.searchers` entry that lets `require` find modules written in a compiled language
+/// by looking for the compiler's file extension along `package.path`, in place of the `.lua`
+/// ending of each template.
+pub fn search_compiled_module<'lua>(
+    lua: &'lua Lua,
+    name: String,
+) -> LuaResult<(Value<'lua>, Option<String>)> {
+    let compilers: Table = lua.named_registry_value(SCRIPT_COMPILERS)?;
+    let package: Table = lua.globals().get("package")?;
+    let path: String = package.get("path")?;
+    let searchpath: Function = package.get("searchpath")?;
+    let mut errors = String::new();
+    for pair in compilers.pairs::<String, Function>() {
+        let (ext, compiler) = pair?;
+        let ext_path = path
+            .split(';')
+            .filter_map(|template| template.strip_suffix(".lua"))
+            .map(|template| format!("{template}.{ext}"))
+            .collect::<Vec<String>>()
+            .join(";");
+        let (file, error): (Option<String>, Option<String>) =
+            searchpath.call((name.as_str(), ext_path))?;
+        if let Some(file) = file {
+            let source = std::fs::read_to_string(&file).map_err(mlua::Error::external)?;
+            let source: String = compiler.call((source, file.as_str()))?;
+            let chunk = lua
+                .load(&source)
+                .set_name(format!("@{file}"))
+                .into_function()?;
+            return Ok((Value::Function(chunk), Some(file)));
+        }
+        errors.push_str(&error.unwrap_or_default());
+    }
+    Ok((Value::String(lua.create_string(&errors)?), None))
+}
+
 #[derive(Clone)]
 pub struct Script {}
 
+pub const API: ApiModule = lua_api!("script", "Loading and resetting scripts", [
+    "load(path: string)" => "Loads a script",
+    "reset()" => "Resets the script engine",
+    "on_reset(callback: function)" => "Adds a callback for when the script engine is reset",
+    "on_error(callback: fun(msg: string, source: string?, count: integer))" => "Adds a callback for script errors",
+    "report_error(msg: string)" => "Adds an error to the error buffer",
+    "show_errors(index?: integer)" => "Prints the collected script errors",
+    "clear_errors()" => "Empties the error buffer",
+    "add_compiler(extension: string, compile: fun(source: string, path: string): string)" => "Registers a compiler for scripts with the given file extension",
+    "reload(path: string)" => "Removes the triggers, aliases and timers a script added and loads it again",
+    "caller() -> string?" => "Returns the path of the script file that is running",
+    "function_source(f: function) -> string?" => "Returns the source code of a function defined in a script",
+    "exclude_from_reload(pattern: string)" => "Stops live_reload from reloading scripts with the pattern in their path",
+    "include_in_reload(pattern: string)" => "Removes a live_reload exclusion",
+    "reload_exclusions() -> string[]" => "Returns the live_reload exclusions",
+    "order() -> string[], string?" => "Returns the order the scripts in the config directory load in on start",
+    "set_order(scripts: string[])" => "Sets the scripts in the config directory that load first on start",
+    "requires(script: string) -> string[]" => "Returns the scripts a script in the config directory loads after",
+    "set_requires(script: string, requires: string[])" => "Sets the scripts a script in the config directory loads after",
+]);
+
 impl UserData for Script {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function("load", |ctx, path: String| {
@@ -17,6 +170,63 @@ impl UserData for Script {
             backend.writer.send(Event::LoadScript(path)).unwrap();
             Ok(())
         });
+        methods.add_function("reload", |ctx, path: String| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::ReloadScript(path)).unwrap();
+            Ok(())
+        });
+        methods.add_function("caller", |ctx, ()| Ok(caller(ctx)));
+        methods.add_function("function_source", |ctx, f: Function| {
+            Ok(function_source(ctx, &f))
+        });
+        methods.add_function("exclude_from_reload", |_, pattern: String| {
+            let mut exclusions = ReloadExclusions::load();
+            exclusions.add(&pattern);
+            exclusions.save();
+            Ok(())
+        });
+        methods.add_function("include_in_reload", |_, pattern: String| {
+            let mut exclusions = ReloadExclusions::load();
+            exclusions.remove(&pattern);
+            exclusions.save();
+            Ok(())
+        });
+        methods.add_function("reload_exclusions", |_, ()| {
+            Ok(ReloadExclusions::load()
+                .patterns()
+                .cloned()
+                .collect::<Vec<String>>())
+        });
+        methods.add_function("order", |_, ()| {
+            let scripts = ScriptOrder::scripts_in(crate::CONFIG_DIR.as_path());
+            match ScriptOrder::load().resolve(&scripts) {
+                Ok(order) => Ok((order, None)),
+                Err(err) => Ok((scripts, Some(err))),
+            }
+        });
+        methods.add_function("set_order", |_, scripts: Vec<String>| {
+            let mut order = ScriptOrder::load();
+            order.set_order(scripts);
+            order.save();
+            Ok(())
+        });
+        methods.add_function("requires", |_, script: String| {
+            Ok(ScriptOrder::load()
+                .requires(&script)
+                .cloned()
+                .collect::<Vec<String>>())
+        });
+        methods.add_function(
+            "set_requires",
+            |_, (script, requires): (String, Vec<String>)| {
+                let mut order = ScriptOrder::load();
+                order
+                    .set_requires(&script, requires)
+                    .map_err(mlua::Error::RuntimeError)?;
+                order.save();
+                Ok(())
+            },
+        );
         methods.add_function("reset", |ctx, ()| {
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             backend.writer.send(Event::ResetScript).unwrap();
@@ -26,6 +236,37 @@ impl UserData for Script {
             let listeners: mlua::Table = ctx.named_registry_value(SCRIPT_RESET_LISTENERS)?;
             listeners.set(listeners.raw_len() + 1, cb)?;
             Ok(())
-        })
+        });
+        methods.add_function("on_error", |ctx, cb: mlua::Function| {
+            let listeners: mlua::Table = ctx.named_registry_value(SCRIPT_ERROR_LISTENERS)?;
+            listeners.set(listeners.raw_len() + 1, cb)?;
+            Ok(())
+        });
+        methods.add_function("report_error", |ctx, msg: String| {
+            let error = match api_version::call_site(ctx, 1) {
+                Some(location) => format!("{location}: {msg}"),
+                None => msg,
+            };
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::LuaError(error)).unwrap();
+            Ok(())
+        });
+        methods.add_function("show_errors", |ctx, index: Option<usize>| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::ShowErrors(index)).unwrap();
+            Ok(())
+        });
+        methods.add_function("clear_errors", |ctx, ()| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::ClearErrors).unwrap();
+            Ok(())
+        });
+        methods.add_function(
+            "add_compiler",
+            |ctx, (extension, compiler): (String, Function)| {
+                let compilers: Table = ctx.named_registry_value(SCRIPT_COMPILERS)?;
+                compilers.set(extension.trim_start_matches('.'), compiler)
+            },
+        );
     }
 }
@@ -0,0 +1,128 @@
+use mlua::{AnyUserData, Lua, Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::{
+    event::Event,
+    model::{Line, SentCommand, SentLog},
+};
+
+/// Number of commands returned by `sent.recent()` without a count.
+const DEFAULT_RECENT: usize = 20;
+
+/// Read access to the commands sent to the server.
+pub struct Sent {
+    log: SentLog,
+}
+
+impl Sent {
+    pub const LUA_GLOBAL_NAME: &'static str = "sent";
+
+    pub fn new(log: SentLog) -> Self {
+        Self { log }
+    }
+}
+
+pub const API: ApiModule = lua_api!("sent", "The commands sent to the MUD", [
+    "recent(count?: integer) -> { index: integer, time: integer, command: string, origin: string }[]" => "Returns the most recently sent commands, oldest first",
+    "get(index: integer) -> { index: integer, time: integer, command: string, origin: string }?" => "Returns a sent command by its index",
+    "resend(index: integer) -> boolean" => "Sends a command again, without running aliases",
+    "clear()" => "Forgets the sent commands",
+]);
+
+fn sent_log(ctx: &Lua) -> mlua::Result<SentLog> {
+    let sent: AnyUserData = ctx.globals().get(Sent::LUA_GLOBAL_NAME)?;
+    let sent = sent.borrow::<Sent>()?;
+    Ok(sent.log.clone())
+}
+
+fn command_table<'lua>(ctx: &'lua Lua, command: &SentCommand) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("index", command.index)?;
+    table.set("time", command.time.timestamp())?;
+    table.set("command", command.command.as_str())?;
+    table.set("origin", command.origin.name())?;
+    Ok(table)
+}
+
+impl UserData for Sent {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("recent", |ctx, count: Option<usize>| {
+            let commands = ctx.create_table()?;
+            for command in sent_log(ctx)?.recent(count.unwrap_or(DEFAULT_RECENT)) {
+                commands.push(command_table(ctx, &command)?)?;
+            }
+            Ok(commands)
+        });
+        methods.add_function("get", |ctx, index: u64| {
+            sent_log(ctx)?
+                .get(index)
+                .map(|command| command_table(ctx, &command))
+                .transpose()
+        });
+        methods.add_function("resend", |ctx, index: u64| {
+            let Some(command) = sent_log(ctx)?.get(index) else {
+                return Ok(false);
+            };
+            let mut line = Line::from(command.command);
+            line.flags.bypass_script = true;
+            line.flags.source = Some("script".to_string());
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::ServerInput(line)).unwrap();
+            Ok(true)
+        });
+        methods.add_function("clear", |ctx, ()| {
+            sent_log(ctx)?.clear();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_sent {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    #[test]
+    fn test_recent_and_resend() {
+        let lua = Lua::new();
+        let (writer, reader) = channel();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        let log = SentLog::default();
+        lua.globals()
+            .set(Sent::LUA_GLOBAL_NAME, Sent::new(log.clone()))
+            .unwrap();
+        let mut typed = Line::from("look");
+        typed.flags.source = Some("user".to_string());
+        log.record(&typed);
+        log.record(&Line::from("kill orc"));
+
+        let (count, command, origin): (usize, String, String) = lua
+            .load(
+                r#"
+                local recent = sent.recent()
+                return #recent, recent[2].command, sent.get(1).origin
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (count, command.as_str(), origin.as_str()),
+            (2, "kill orc", "typed")
+        );
+
+        assert!(lua.load("return sent.resend(2)").eval::<bool>().unwrap());
+        match reader.try_recv() {
+            Ok(Event::ServerInput(line)) => {
+                assert_eq!(line.line(), "kill orc");
+                assert!(line.flags.bypass_script);
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        assert!(!lua.load("return sent.resend(3)").eval::<bool>().unwrap());
+
+        lua.load("sent.clear()").exec().unwrap();
+        assert!(log.recent(10).is_empty());
+    }
+}
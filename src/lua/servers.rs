@@ -1,6 +1,12 @@
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::event::Event;
 use crate::io::SaveData;
-use crate::model::{Connection, Servers as MServers};
-use mlua::{IntoLua, UserData, UserDataMethods};
+use crate::model::{
+    find_servers, Capabilities, ColorFilters, Connection, Palettes, ServerInfo, ServerInfos,
+    Servers as MServers,
+};
+use crate::net::{discover_servers, parse_mssp, MsspValues};
+use mlua::{IntoLua, Lua, Table, UserData, UserDataMethods};
 
 #[cfg(test)]
 use mockall::automock;
@@ -8,6 +14,18 @@ use mockall::automock;
 pub struct Server {
     name: String,
     connection: Connection,
+    info: ServerInfo,
+}
+
+impl Server {
+    fn new(name: String, connection: Connection, infos: &ServerInfos) -> Self {
+        let info = infos.get(&name).cloned().unwrap_or_default();
+        Self {
+            name,
+            connection,
+            info,
+        }
+    }
 }
 
 impl UserData for Server {
@@ -21,6 +39,27 @@ impl UserData for Server {
                     "port" => Ok(this.connection.port.into_lua(ctx)?),
                     "tls" => Ok(this.connection.tls.into_lua(ctx)?),
                     "verify_cert" => Ok(this.connection.verify_cert.into_lua(ctx)?),
+                    "autoconnect" => Ok(this.connection.autoconnect.into_lua(ctx)?),
+                    "input_mode" => Ok(this.connection.input_mode.to_string().into_lua(ctx)?),
+                    "command_prefix" => Ok(this
+                        .connection
+                        .command_prefix
+                        .map(String::from)
+                        .into_lua(ctx)?),
+                    "last_connected" => Ok(this.info.last_connected.into_lua(ctx)?),
+                    "players" => Ok(this.info.mssp.get("PLAYERS").cloned().into_lua(ctx)?),
+                    "codebase" => Ok(this.info.mssp.get("CODEBASE").cloned().into_lua(ctx)?),
+                    "uptime" => Ok(this.info.uptime().into_lua(ctx)?),
+                    "mssp" => Ok(ctx
+                        .create_table_from(this.info.mssp.clone())?
+                        .into_lua(ctx)?),
+                    "capabilities" => Ok(this
+                        .info
+                        .capabilities
+                        .as_ref()
+                        .map(|capabilities| capabilities_table(ctx, capabilities))
+                        .transpose()?
+                        .into_lua(ctx)?),
                     _ => Err(mlua::Error::external(format!("Invalid index: {key}"))),
                 }
             },
@@ -28,6 +67,65 @@ impl UserData for Server {
     }
 }
 
+/// Converts MSSP values to a table, variables with several values become lists.
+fn mssp_table<'lua>(ctx: &'lua Lua, values: &MsspValues) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    for (key, vals) in values {
+        if let [val] = vals.as_slice() {
+            table.set(key.as_str(), val.as_str())?;
+        } else {
+            table.set(key.as_str(), vals.clone())?;
+        }
+    }
+    Ok(table)
+}
+
+/// Parses a command prefix, a single character other than a space or an empty string to use `/`.
+fn command_prefix(prefix: &str) -> mlua::Result<Option<char>> {
+    let mut chars = prefix.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) | (Some('/'), None) => Ok(None),
+        (Some(c), None) if !c.is_whitespace() => Ok(Some(c)),
+        _ => Err(mlua::Error::external(format!(
+            "Invalid command prefix: '{prefix}', use a single character"
+        ))),
+    }
+}
+
+fn capabilities_table<'lua>(
+    ctx: &'lua Lua,
+    capabilities: &Capabilities,
+) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    table.set(
+        "protocols",
+        capabilities.protocols.iter().cloned().collect::<Vec<_>>(),
+    )?;
+    table.set("utf8", capabilities.utf8)?;
+    table.set("tls", capabilities.tls)?;
+    table.set("prompt", capabilities.prompt.clone())?;
+    table.set("time", capabilities.time)?;
+    table.set("summary", capabilities.summary())?;
+    Ok(table)
+}
+
+fn info_table<'lua>(ctx: &'lua Lua, name: &str, info: &ServerInfo) -> mlua::Result<Table<'lua>> {
+    let now = chrono::Local::now().timestamp();
+    let table = ctx.create_table()?;
+    table.set("server", name)?;
+    table.set("name", info.mssp_name())?;
+    table.set("players", info.players())?;
+    table.set("uptime", info.uptime())?;
+    table.set("codebase", info.codebase())?;
+    table.set("summary", info.summary(now))?;
+    table.set("last_connected", info.last_connected)?;
+    table.set("mssp", ctx.create_table_from(info.mssp.clone())?)?;
+    if let Some(capabilities) = &info.capabilities {
+        table.set("capabilities", capabilities_table(ctx, capabilities)?)?;
+    }
+    Ok(table)
+}
+
 pub struct Servers {}
 
 struct ServerLoader {}
@@ -45,6 +143,16 @@ impl ServerLoader {
     }
 }
 
+pub const API: ApiModule = lua_api!("servers", "Saved servers", [
+    "add(name: string, host: string, port: integer, tls: boolean, verify?: boolean)" => "Saves a server",
+    "remove(name: string)" => "Removes a server",
+    "get(name: string) -> table" => "Returns a server",
+    "get_all() -> table[]" => "Returns all servers",
+    "update(name: string, options: table)" => "Updates a server",
+    "info(host: string, port?: integer) -> table?" => "Returns the MSSP details and capabilities of a saved server",
+    "discover(url: string)" => "Lists the servers from a MUD list endpoint that aren't saved yet",
+]);
+
 impl UserData for Servers {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function(
@@ -60,12 +168,7 @@ impl UserData for Servers {
                         "Saved server already exists for {name}"
                     )))
                 } else {
-                    let connection = Connection {
-                        host,
-                        port,
-                        tls,
-                        verify_cert: verify.unwrap_or(false),
-                    };
+                    let connection = Connection::new(&host, port, tls, verify.unwrap_or(false));
                     servers.insert(name, connection);
                     servers.save();
                     Ok(())
@@ -76,6 +179,18 @@ impl UserData for Servers {
             let mut servers = ServerLoader::get()?;
             if servers.remove(&name).is_some() {
                 servers.save();
+                let mut infos = ServerInfos::load();
+                if infos.remove(&name).is_some() {
+                    infos.save();
+                }
+                let mut palettes = Palettes::load();
+                if palettes.remove(&name).is_some() {
+                    palettes.save();
+                }
+                let mut filters = ColorFilters::load();
+                if filters.servers.remove(&name).is_some() {
+                    filters.save();
+                }
                 Ok(())
             } else {
                 Err(mlua::Error::external(format!(
@@ -87,10 +202,8 @@ impl UserData for Servers {
             let servers = ServerLoader::get()?;
             if servers.contains_key(&name) {
                 if let Some(connection) = servers.get(&name) {
-                    Ok(Server {
-                        name,
-                        connection: connection.clone(),
-                    })
+                    let connection = connection.clone();
+                    Ok(Server::new(name, connection, &ServerInfos::load()))
                 } else {
                     Err(mlua::Error::external(format!(
                         "Failed to read server: {name}"
@@ -104,13 +217,111 @@ impl UserData for Servers {
         });
         methods.add_function("get_all", |_, ()| -> mlua::Result<Vec<Server>> {
             let servers = ServerLoader::get()?;
+            let infos = ServerInfos::load();
             Ok(servers
                 .iter()
-                .map(|(name, conn)| Server {
-                    name: name.to_string(),
-                    connection: conn.clone(),
-                })
+                .map(|(name, conn)| Server::new(name.to_string(), conn.clone(), &infos))
                 .collect())
         });
+        methods.add_function(
+            "update",
+            |ctx, (name, opts): (String, Table)| -> mlua::Result<()> {
+                let mut servers = ServerLoader::get()?;
+                if let Some(connection) = servers.get_mut(&name) {
+                    if let Some(host) = opts.get::<_, Option<String>>("host")? {
+                        connection.host = host;
+                    }
+                    if let Some(port) = opts.get::<_, Option<u16>>("port")? {
+                        connection.port = port;
+                    }
+                    if let Some(tls) = opts.get::<_, Option<bool>>("tls")? {
+                        connection.tls = tls;
+                    }
+                    if let Some(verify) = opts.get::<_, Option<bool>>("verify_cert")? {
+                        connection.verify_cert = verify;
+                    }
+                    if let Some(autoconnect) = opts.get::<_, Option<bool>>("autoconnect")? {
+                        connection.autoconnect = autoconnect;
+                    }
+                    if let Some(mode) = opts.get::<_, Option<String>>("input_mode")? {
+                        connection.input_mode = mode.parse().map_err(mlua::Error::external)?;
+                    }
+                    if let Some(prefix) = opts.get::<_, Option<String>>("command_prefix")? {
+                        connection.command_prefix = command_prefix(&prefix)?;
+                    }
+                    servers.save();
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend.writer.send(Event::ServersChanged).unwrap();
+                    Ok(())
+                } else {
+                    Err(mlua::Error::external(format!(
+                        "Saved server does not exist: {name}"
+                    )))
+                }
+            },
+        );
+        methods.add_function(
+            "_record_connect",
+            |_, (host, port): (String, u16)| -> mlua::Result<()> {
+                let names = find_servers(&ServerLoader::get()?, &host, port);
+                if !names.is_empty() {
+                    let now = chrono::Local::now().timestamp();
+                    let mut infos = ServerInfos::load();
+                    for name in names {
+                        infos.entry(name).or_default().last_connected = Some(now);
+                    }
+                    infos.save();
+                }
+                Ok(())
+            },
+        );
+        methods.add_function(
+            "_record_mssp",
+            |ctx, (host, port, data): (String, u16, Vec<u8>)| {
+                let values = parse_mssp(&data);
+                let info = ServerInfo {
+                    mssp: values
+                        .iter()
+                        .map(|(key, vals)| (key.clone(), vals.join(", ")))
+                        .collect(),
+                    ..Default::default()
+                };
+                let names = find_servers(&ServerLoader::get()?, &host, port);
+                if !names.is_empty() {
+                    let mut infos = ServerInfos::load();
+                    for name in names {
+                        infos.entry(name).or_default().mssp = info.mssp.clone();
+                    }
+                    infos.save();
+                }
+                let summary = info.summary(chrono::Local::now().timestamp());
+                Ok((mssp_table(ctx, &values)?, summary))
+            },
+        );
+        methods.add_function(
+            "info",
+            |ctx, (host, port): (String, Option<u16>)| -> mlua::Result<Option<Table>> {
+                let servers = ServerLoader::get()?;
+                let mut names: Vec<&String> = servers
+                    .iter()
+                    .filter(|(_, conn)| {
+                        conn.host == host && port.is_none_or(|port| conn.port == port)
+                    })
+                    .map(|(name, _)| name)
+                    .collect();
+                names.sort();
+                let infos = ServerInfos::load();
+                names
+                    .into_iter()
+                    .find_map(|name| infos.get(name).map(|info| (name, info)))
+                    .map(|(name, info)| info_table(ctx, name, info))
+                    .transpose()
+            },
+        );
+        methods.add_function("discover", |ctx, url: String| -> mlua::Result<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            discover_servers(backend.writer, url, ServerLoader::get()?);
+            Ok(())
+        });
     }
 }
@@ -1,4 +1,4 @@
-use super::{backend::Backend, constants::BACKEND};
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
 use crate::{event::Event, io::SaveData, model};
 use mlua::{Error, Result, Table, UserData, UserDataMethods};
 
@@ -12,6 +12,12 @@ impl Settings {
     }
 }
 
+pub const API: ApiModule = lua_api!("settings", "Blightmud settings", [
+    "list() -> table<string, boolean>" => "Returns all settings",
+    "get(key: string) -> boolean" => "Returns a setting",
+    "set(key: string, value: boolean)" => "Changes a setting",
+]);
+
 impl UserData for Settings {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("list", |ctx, _: ()| -> Result<Table<'lua>> {
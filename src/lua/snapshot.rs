@@ -0,0 +1,200 @@
+use super::api::ApiModule;
+use super::store::Store;
+use super::vars::set_var;
+use crate::lua::constants::SESSION_VARS;
+use crate::tools::util::is_valid_file_name;
+use anyhow::Context;
+use mlua::{AnyUserData, Lua, Result as LuaResult, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+/// The session variable values that can be written to a snapshot.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum VarValue {
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl VarValue {
+    fn from_lua(value: Value) -> Option<Self> {
+        match value {
+            Value::Boolean(b) => Some(Self::Bool(b)),
+            Value::Integer(i) => Some(Self::Integer(i)),
+            Value::Number(n) => Some(Self::Number(n)),
+            Value::String(s) => Some(Self::String(s.to_str().ok()?.to_string())),
+            _ => None,
+        }
+    }
+
+    fn into_lua(self, lua: &Lua) -> LuaResult<Value<'_>> {
+        Ok(match self {
+            Self::Bool(b) => Value::Boolean(b),
+            Self::Integer(i) => Value::Integer(i),
+            Self::Number(n) => Value::Number(n),
+            Self::String(s) => Value::String(lua.create_string(&s)?),
+        })
+    }
+}
+
+/// The session state captured by `/snapshot save`.
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    store: HashMap<String, String>,
+    trigger_groups: BTreeMap<i64, bool>,
+    alias_groups: BTreeMap<i64, bool>,
+    vars: BTreeMap<String, VarValue>,
+}
+
+fn snapshot_dir() -> PathBuf {
+    crate::DATA_DIR.join("snapshots")
+}
+
+fn snapshot_path(name: &str) -> LuaResult<PathBuf> {
+    if !is_valid_file_name(name) {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Invalid snapshot name: '{name}'"
+        )));
+    }
+    Ok(snapshot_dir().join(format!("{name}.ron")))
+}
+
+/// Returns the user groups of the `trigger` or `alias` module.
+fn user_groups<'lua>(lua: &'lua Lua, module: &str, field: &str) -> LuaResult<Table<'lua>> {
+    lua.globals().get::<_, Table>(module)?.get(field)
+}
+
+fn group_states(groups: Table) -> LuaResult<BTreeMap<i64, bool>> {
+    groups
+        .pairs::<i64, Table>()
+        .map(|pair| {
+            let (id, group) = pair?;
+            Ok((id, group.get("enabled")?))
+        })
+        .collect()
+}
+
+fn restore_group_states(groups: Table, states: BTreeMap<i64, bool>) -> LuaResult<()> {
+    for (id, enabled) in states {
+        if let Some(group) = groups.get::<_, Option<Table>>(id)? {
+            group.set("enabled", enabled)?;
+        }
+    }
+    Ok(())
+}
+
+fn save(lua: &Lua, name: String) -> LuaResult<()> {
+    let path = snapshot_path(&name)?;
+    let store: AnyUserData = lua.globals().get(Store::LUA_GLOBAL_NAME)?;
+    let vars: Table = lua.named_registry_value(SESSION_VARS)?;
+    let snapshot = Snapshot {
+        store: store.borrow::<Store>()?.memory_storage.clone(),
+        trigger_groups: group_states(user_groups(lua, "trigger", "trigger_groups")?)?,
+        alias_groups: group_states(user_groups(lua, "alias", "alias_groups")?)?,
+        vars: vars
+            .pairs::<String, Value>()
+            .filter_map(|pair| {
+                let (name, value) = pair.ok()?;
+                Some((name, VarValue::from_lua(value)?))
+            })
+            .collect(),
+    };
+    let write = || -> anyhow::Result<()> {
+        fs::create_dir_all(snapshot_dir())?;
+        let contents = ron::ser::to_string_pretty(&snapshot, Default::default())?;
+        fs::write(&path, contents).with_context(|| format!("Unable to write {path:?}"))?;
+        Ok(())
+    };
+    write().map_err(mlua::Error::external)
+}
+
+fn load(lua: &Lua, name: String) -> LuaResult<()> {
+    let path = snapshot_path(&name)?;
+    if !path.exists() {
+        return Err(mlua::Error::RuntimeError(format!(
+            "No snapshot named '{name}'"
+        )));
+    }
+    let snapshot: Snapshot = fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(ron::from_str(&contents)?))
+        .map_err(mlua::Error::external)?;
+
+    let store: AnyUserData = lua.globals().get(Store::LUA_GLOBAL_NAME)?;
+    store.borrow_mut::<Store>()?.restore_session(snapshot.store);
+    restore_group_states(
+        user_groups(lua, "trigger", "trigger_groups")?,
+        snapshot.trigger_groups,
+    )?;
+    restore_group_states(
+        user_groups(lua, "alias", "alias_groups")?,
+        snapshot.alias_groups,
+    )?;
+    for (name, value) in snapshot.vars {
+        set_var(lua, &name, value.into_lua(lua)?)?;
+    }
+    Ok(())
+}
+
+fn list(_: &Lua, (): ()) -> LuaResult<Vec<String>> {
+    let mut names: Vec<String> = match fs::read_dir(snapshot_dir()) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    Ok(names)
+}
+
+fn delete(_: &Lua, name: String) -> LuaResult<bool> {
+    let path = snapshot_path(&name)?;
+    Ok(fs::remove_file(path).is_ok())
+}
+
+pub struct Snapshots {}
+
+pub const API: ApiModule = lua_api!("snapshot", "Named snapshots of the session state", [
+    "save(name: string)" => "Saves the session store, trigger and alias group states and variables",
+    "load(name: string)" => "Restores a saved snapshot",
+    "list() -> string[]" => "Returns the names of the saved snapshots",
+    "delete(name: string) -> boolean" => "Removes a saved snapshot",
+]);
+
+impl UserData for Snapshots {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("save", save);
+        methods.add_function("load", load);
+        methods.add_function("list", list);
+        methods.add_function("delete", delete);
+    }
+}
+
+#[cfg(test)]
+mod test_snapshot {
+    use super::{snapshot_path, VarValue};
+    use mlua::{Lua, Value};
+
+    #[test]
+    fn test_snapshot_names() {
+        assert!(snapshot_path("pk-setup_2").is_ok());
+        assert!(snapshot_path("").is_err());
+        assert!(snapshot_path("../config").is_err());
+        assert!(snapshot_path("a b").is_err());
+    }
+
+    #[test]
+    fn test_var_values() {
+        let lua = Lua::new();
+        let value = VarValue::from_lua(Value::Integer(5)).unwrap();
+        assert_eq!(value, VarValue::Integer(5));
+        assert_eq!(value.into_lua(&lua).unwrap(), Value::Integer(5));
+        assert!(VarValue::from_lua(Value::Table(lua.create_table().unwrap())).is_none());
+    }
+}
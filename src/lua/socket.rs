@@ -1,3 +1,4 @@
+use super::api::ApiModule;
 use std::{
     io::Write,
     net::{Shutdown, TcpStream},
@@ -13,6 +14,10 @@ use crate::{
 
 pub struct SocketLib;
 
+pub const API: ApiModule = lua_api!("socket", "TCP sockets", [
+    "connect(host: string, port: integer) -> Socket?" => "Opens a connection",
+]);
+
 impl UserData for SocketLib {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_function(
@@ -37,6 +42,11 @@ pub struct Socket {
     connection: TcpStream,
 }
 
+pub const SOCKET_API: ApiModule = lua_api!(class "Socket", "A TCP connection", [
+    ":send(data: string)" => "Sends data",
+    ":close()" => "Closes the connection",
+]);
+
 impl UserData for Socket {
     fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
         methods.add_method_mut("send", |_, this, data: String| {
@@ -0,0 +1,184 @@
+use log::error;
+use mlua::{AnyUserData, Error, Table, UserData, UserDataMethods};
+
+use crate::{
+    audio::SourceOptions,
+    event::Event,
+    io::SaveData,
+    model::{SoundTheme, SoundThemeConfig},
+};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+
+/// Event sounds played from the active sound theme.
+pub struct Sounds {
+    theme: Option<SoundTheme>,
+}
+
+impl Sounds {
+    pub const LUA_GLOBAL_NAME: &'static str = "sounds";
+
+    /// Loads the theme selected with `/soundtheme`, if any.
+    pub fn load() -> Self {
+        let theme = SoundThemeConfig::load()
+            .theme
+            .and_then(|name| match SoundTheme::load(&name) {
+                Ok(theme) => Some(theme),
+                Err(err) => {
+                    error!("Failed to load sound theme: {}", err);
+                    None
+                }
+            });
+        Self { theme }
+    }
+}
+
+pub const API: ApiModule = lua_api!("sounds", "Sound themes for game events", [
+    "play_event(event: string, options?: { amplify?: number }) -> boolean" => "Plays the active theme's sound for an event",
+    "set_theme(name?: string)" => "Switches to another sound theme, or turns themes off",
+    "theme() -> string?" => "Returns the name of the active sound theme",
+    "themes() -> string[]" => "Lists the installed sound themes",
+    "events() -> string[]" => "Lists the events the active theme has sounds for",
+    "reload()" => "Reads the active theme from disk again",
+]);
+
+fn with_sounds<R>(ctx: &mlua::Lua, f: impl FnOnce(&mut Sounds) -> R) -> mlua::Result<R> {
+    let sounds: AnyUserData = ctx.globals().get(Sounds::LUA_GLOBAL_NAME)?;
+    let mut sounds = sounds.borrow_mut::<Sounds>()?;
+    Ok(f(&mut sounds))
+}
+
+impl UserData for Sounds {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function(
+            "play_event",
+            |ctx, (event, opts): (String, Option<Table>)| {
+                let Some((path, amplify)) = with_sounds(ctx, |sounds| {
+                    sounds.theme.as_ref().and_then(|theme| theme.sound(&event))
+                })?
+                else {
+                    return Ok(false);
+                };
+                let mut options = SourceOptions {
+                    amplify,
+                    ..Default::default()
+                };
+                if let Some(opts) = opts {
+                    options.amplify *= opts.get::<_, Option<f32>>("amplify")?.unwrap_or(1.0);
+                }
+                let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                backend
+                    .writer
+                    .send(Event::PlaySFX(path.to_string_lossy().to_string(), options))
+                    .map_err(Error::external)?;
+                Ok(true)
+            },
+        );
+        methods.add_function("set_theme", |ctx, name: Option<String>| {
+            let theme = name
+                .as_deref()
+                .map(SoundTheme::load)
+                .transpose()
+                .map_err(Error::external)?;
+            with_sounds(ctx, |sounds| sounds.theme = theme)?;
+            SoundThemeConfig { theme: name }.save();
+            Ok(())
+        });
+        methods.add_function("theme", |ctx, ()| {
+            with_sounds(ctx, |sounds| {
+                sounds.theme.as_ref().map(|theme| theme.name.clone())
+            })
+        });
+        methods.add_function("themes", |_, ()| Ok(SoundTheme::available()));
+        methods.add_function("events", |ctx, ()| {
+            with_sounds(ctx, |sounds| {
+                sounds
+                    .theme
+                    .as_ref()
+                    .map(|theme| theme.events())
+                    .unwrap_or_default()
+            })
+        });
+        methods.add_function("reload", |ctx, ()| {
+            let name = with_sounds(ctx, |sounds| {
+                sounds.theme.as_ref().map(|theme| theme.name.clone())
+            })?;
+            if let Some(name) = name {
+                let theme = SoundTheme::load(&name).map_err(Error::external)?;
+                with_sounds(ctx, |sounds| sounds.theme = Some(theme))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_sounds {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use mlua::Lua;
+
+    use super::*;
+
+    #[test]
+    fn test_play_event() {
+        let dir = std::env::temp_dir().join(format!("blightmud_sounds_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("theme.ron"),
+            r#"(sounds: { "tell_received": (file: "tell.wav", amplify: 0.5) })"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("tell.wav"), "").unwrap();
+        let theme = SoundTheme::load_from("beeps", &dir).unwrap();
+
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals()
+            .set(Sounds::LUA_GLOBAL_NAME, Sounds { theme: None })
+            .unwrap();
+        assert!(!lua
+            .load(r#"return sounds.play_event("tell_received")"#)
+            .call::<_, bool>(())
+            .unwrap());
+        assert_eq!(
+            lua.load("return sounds.theme()")
+                .call::<_, Option<String>>(())
+                .unwrap(),
+            None
+        );
+
+        lua.globals()
+            .set(Sounds::LUA_GLOBAL_NAME, Sounds { theme: Some(theme) })
+            .unwrap();
+        assert!(lua
+            .load(r#"return sounds.play_event("tell_received", { amplify = 2.0 })"#)
+            .call::<_, bool>(())
+            .unwrap());
+        assert_eq!(
+            reader.try_recv(),
+            Ok(Event::PlaySFX(
+                dir.join("tell.wav").to_string_lossy().to_string(),
+                SourceOptions {
+                    repeat: false,
+                    amplify: 1.0,
+                }
+            ))
+        );
+        assert!(!lua
+            .load(r#"return sounds.play_event("death")"#)
+            .call::<_, bool>(())
+            .unwrap());
+        assert!(reader.try_recv().is_err());
+        assert_eq!(
+            lua.load("return sounds.events()")
+                .call::<_, Vec<String>>(())
+                .unwrap(),
+            vec!["tell_received"]
+        );
+        assert!(lua.load(r#"sounds.set_theme("../x")"#).exec().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use super::api::ApiModule;
 use anyhow::{anyhow, Result};
 use hunspell_rs::{CheckResult, Hunspell};
 use mlua::prelude::LuaError;
@@ -41,6 +42,12 @@ impl Spellchecker {
     }
 }
 
+pub const API: ApiModule = lua_api!("spellcheck", "Spell checking", [
+    "init(aff_path: string, dict_path: string)" => "Loads a dictionary",
+    "check(word: string) -> boolean" => "Checks the spelling of a word",
+    "suggest(word: string) -> string[]" => "Returns suggestions for a word",
+]);
+
 impl UserData for Spellchecker {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function(
@@ -1,17 +1,45 @@
+use super::api::ApiModule;
 use crate::io::SaveData;
 use log::debug;
-use mlua::{AnyUserData, FromLua, Result, UserData, UserDataMethods};
+use mlua::{AnyUserData, FromLua, Lua, Result, Table, UserData, UserDataMethods};
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf};
 
 impl SaveData for HashMap<String, String> {
     fn relative_path() -> PathBuf {
         PathBuf::from("store/data.ron")
     }
+
+    fn is_secret() -> bool {
+        true
+    }
+}
+
+/// Expiry times, in milliseconds since the epoch, of keys in the disk storage.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct DiskExpiry(HashMap<String, i64>);
+
+impl SaveData for DiskExpiry {
+    fn relative_path() -> PathBuf {
+        PathBuf::from("store/expiry.ron")
+    }
+
+    fn is_secret() -> bool {
+        true
+    }
+}
+
+/// Writes the disk storage again, eg. to encrypt it after encryption was turned on.
+pub fn resave_disk_storage() -> anyhow::Result<()> {
+    HashMap::<String, String>::resave()?;
+    DiskExpiry::resave()
 }
 
 #[derive(Clone, FromLua)]
 pub struct Store {
     pub memory_storage: HashMap<String, String>,
+    memory_expiry: HashMap<String, i64>,
 }
 
 impl Store {
@@ -20,42 +48,287 @@ impl Store {
     pub fn new() -> Self {
         Self {
             memory_storage: HashMap::new(),
+            memory_expiry: HashMap::new(),
+        }
+    }
+
+    /// Writes values to the session storage without an expiry.
+    pub fn restore_session(&mut self, values: HashMap<String, String>) {
+        for (key, value) in values {
+            self.memory_expiry.remove(&key);
+            self.memory_storage.insert(key, value);
         }
     }
 }
 
-impl UserData for Store {
-    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_function("session_write", |ctx, (key, val): (String, String)| {
-            let store_aud: AnyUserData = ctx.globals().get(Store::LUA_GLOBAL_NAME)?;
+#[derive(Clone, Copy)]
+enum Storage {
+    Session,
+    Disk,
+}
+
+/// Keys in a namespace are stored as `namespace/key` so the default namespace sees everything.
+fn scoped_key(namespace: &str, key: &str) -> String {
+    if namespace.is_empty() {
+        key.to_string()
+    } else {
+        format!("{namespace}/{key}")
+    }
+}
+
+fn unscoped_key<'a>(namespace: &str, key: &'a str) -> Option<&'a str> {
+    if namespace.is_empty() {
+        Some(key)
+    } else {
+        key.strip_prefix(namespace)?.strip_prefix('/')
+    }
+}
+
+/// Removes expired keys, returning true if anything was removed.
+fn purge(values: &mut HashMap<String, String>, expiry: &mut HashMap<String, i64>) -> bool {
+    let now = chrono::Utc::now().timestamp_millis();
+    let expired: Vec<String> = expiry
+        .iter()
+        .filter(|(_, time)| **time <= now)
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in &expired {
+        values.remove(key);
+        expiry.remove(key);
+    }
+    !expired.is_empty()
+}
+
+/// Runs `f` on the values and expiry times of a storage. Disk storage is written back when
+/// `modify` is set or when expired keys were removed.
+fn with_storage<R>(
+    lua: &Lua,
+    storage: Storage,
+    modify: bool,
+    f: impl FnOnce(&mut HashMap<String, String>, &mut HashMap<String, i64>) -> Result<R>,
+) -> Result<R> {
+    match storage {
+        Storage::Session => {
+            let store_aud: AnyUserData = lua.globals().get(Store::LUA_GLOBAL_NAME)?;
             let mut store = store_aud.borrow_mut::<Store>()?;
-            store.memory_storage.insert(key, val);
-            Ok(())
-        });
-        methods.add_function(
-            "session_read",
-            |ctx, key: String| -> Result<Option<String>> {
-                let store_aud: AnyUserData = ctx.globals().get(Store::LUA_GLOBAL_NAME)?;
-                let store = store_aud.borrow::<Store>()?;
-                match store.memory_storage.get(key.as_str()) {
-                    Some(val) => Ok(Some(val.to_string())),
-                    _ => Ok(None),
+            let store = &mut *store;
+            purge(&mut store.memory_storage, &mut store.memory_expiry);
+            f(&mut store.memory_storage, &mut store.memory_expiry)
+        }
+        Storage::Disk => {
+            let mut values: HashMap<String, String> = HashMap::load();
+            let mut expiry = DiskExpiry::load();
+            let purged = purge(&mut values, &mut expiry.0);
+            let result = f(&mut values, &mut expiry.0)?;
+            if modify || purged {
+                values.save();
+                expiry.save();
+            }
+            Ok(result)
+        }
+    }
+}
+
+/// The time, in milliseconds since the epoch, at which a value written now for `ttl` seconds
+/// expires.
+fn expiry_time(ttl: f64) -> Result<i64> {
+    let invalid = || mlua::Error::RuntimeError(format!("Invalid ttl: {ttl}"));
+    if !ttl.is_finite() {
+        return Err(invalid());
+    }
+    chrono::Utc::now()
+        .timestamp_millis()
+        .checked_add((ttl * 1000.0) as i64)
+        .ok_or_else(invalid)
+}
+
+fn write(
+    lua: &Lua,
+    storage: Storage,
+    namespace: &str,
+    (key, value, ttl): (String, String, Option<f64>),
+) -> Result<()> {
+    let key = scoped_key(namespace, &key);
+    debug!("Writing to store: {} -> {}", key, value);
+    let time = ttl.map(expiry_time).transpose()?;
+    with_storage(lua, storage, true, |values, expiry| {
+        match time {
+            Some(time) => expiry.insert(key.clone(), time),
+            None => expiry.remove(&key),
+        };
+        values.insert(key, value);
+        Ok(())
+    })
+}
+
+fn read(lua: &Lua, storage: Storage, namespace: &str, key: String) -> Result<Option<String>> {
+    let key = scoped_key(namespace, &key);
+    let value = with_storage(lua, storage, false, |values, _| {
+        Ok(values.get(&key).cloned())
+    })?;
+    debug!("Reading from store: {} -> {:?}", key, value);
+    Ok(value)
+}
+
+fn delete(lua: &Lua, storage: Storage, namespace: &str, key: String) -> Result<()> {
+    let key = scoped_key(namespace, &key);
+    with_storage(lua, storage, true, |values, expiry| {
+        values.remove(&key);
+        expiry.remove(&key);
+        Ok(())
+    })
+}
+
+fn increment(
+    lua: &Lua,
+    storage: Storage,
+    namespace: &str,
+    (key, amount): (String, Option<i64>),
+) -> Result<i64> {
+    let key = scoped_key(namespace, &key);
+    with_storage(lua, storage, true, |values, _| {
+        let current = match values.get(&key) {
+            Some(value) => value.parse::<i64>().map_err(|_| {
+                mlua::Error::RuntimeError(format!("Stored value of '{key}' isn't an integer"))
+            })?,
+            None => 0,
+        };
+        let value = current
+            .checked_add(amount.unwrap_or(1))
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("Counter '{key}' would overflow")))?;
+        values.insert(key, value.to_string());
+        Ok(value)
+    })
+}
+
+fn keys(lua: &Lua, storage: Storage, namespace: &str, _: ()) -> Result<Vec<String>> {
+    with_storage(lua, storage, false, |values, _| {
+        let mut keys: Vec<String> = values
+            .keys()
+            .filter_map(|key| unscoped_key(namespace, key))
+            .map(String::from)
+            .collect();
+        keys.sort();
+        Ok(keys)
+    })
+}
+
+fn export<'lua>(lua: &'lua Lua, namespace: &str, _: ()) -> Result<Table<'lua>> {
+    let data = lua.create_table()?;
+    for (name, storage) in [("session", Storage::Session), ("disk", Storage::Disk)] {
+        let entries = with_storage(lua, storage, false, |values, _| {
+            Ok(values
+                .iter()
+                .filter_map(|(key, value)| Some((unscoped_key(namespace, key)?, value)))
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect::<HashMap<String, String>>())
+        })?;
+        data.set(name, entries)?;
+    }
+    Ok(data)
+}
+
+fn import(lua: &Lua, namespace: &str, data: Table) -> Result<()> {
+    for (name, storage) in [("session", Storage::Session), ("disk", Storage::Disk)] {
+        if let Some(entries) = data.get::<_, Option<HashMap<String, String>>>(name)? {
+            with_storage(lua, storage, true, |values, expiry| {
+                for (key, value) in entries {
+                    let key = scoped_key(namespace, &key);
+                    expiry.remove(&key);
+                    values.insert(key, value);
                 }
-            },
-        );
-        methods.add_function("disk_write", |_ctx, (key, val): (String, String)| {
-            debug!("Writing to disk: {} -> {}", key, val);
-            let mut persistent_data = HashMap::load();
-            persistent_data.insert(key, val);
-            persistent_data.save();
-            Ok(())
-        });
-        methods.add_function("disk_read", |_ctx, key: String| -> Result<Option<String>> {
-            let persistent_data: HashMap<String, String> = HashMap::load();
-            let val = persistent_data.get(key.as_str()).map(|val| val.to_string());
-            debug!("Reading from disk: {} -> {:?}", key, val);
-            Ok(val)
-        });
+                Ok(())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Every store function along with the storage it operates on, shared by the default namespace
+/// and the tables returned from `store.namespace()`.
+macro_rules! store_functions {
+    ($bind:ident) => {
+        $bind!("session_write", Storage::Session => write);
+        $bind!("session_read", Storage::Session => read);
+        $bind!("session_delete", Storage::Session => delete);
+        $bind!("session_incr", Storage::Session => increment);
+        $bind!("session_keys", Storage::Session => keys);
+        $bind!("disk_write", Storage::Disk => write);
+        $bind!("disk_read", Storage::Disk => read);
+        $bind!("disk_delete", Storage::Disk => delete);
+        $bind!("disk_incr", Storage::Disk => increment);
+        $bind!("disk_keys", Storage::Disk => keys);
+        $bind!("export", export);
+        $bind!("import", import);
+    };
+}
+
+fn namespace(lua: &Lua, name: String) -> Result<Table<'_>> {
+    if name.is_empty() {
+        return Err(mlua::Error::RuntimeError(
+            "Store namespaces can't be empty".to_string(),
+        ));
+    }
+    let table = lua.create_table()?;
+    macro_rules! bind {
+        ($name:literal, $storage:expr => $f:ident) => {
+            bind!($name, |lua, ns, args| $f(lua, $storage, ns, args))
+        };
+        ($name:literal, $f:expr) => {{
+            let name = name.clone();
+            table.set(
+                $name,
+                lua.create_function(move |lua, args| $f(lua, name.as_str(), args))?,
+            )?;
+        }};
+    }
+    store_functions!(bind);
+    Ok(table)
+}
+
+pub const API: ApiModule = lua_api!("store", "Storage of session and persistent data", [
+    "session_write(key: string, value: string, ttl?: number)" => "Stores a value until Blightmud quits, or for `ttl` seconds",
+    "session_read(key: string) -> string?" => "Reads a session value",
+    "session_delete(key: string)" => "Removes a session value",
+    "session_incr(key: string, amount?: integer) -> integer" => "Atomically increments a session counter",
+    "session_keys() -> string[]" => "Returns the session keys",
+    "disk_write(key: string, value: string, ttl?: number)" => "Stores a value on disk, optionally for `ttl` seconds",
+    "disk_read(key: string) -> string?" => "Reads a value stored on disk",
+    "disk_delete(key: string)" => "Removes a value stored on disk",
+    "disk_incr(key: string, amount?: integer) -> integer" => "Atomically increments a counter stored on disk",
+    "disk_keys() -> string[]" => "Returns the keys stored on disk",
+    "export() -> { session: table<string, string>, disk: table<string, string> }" => "Returns all stored values",
+    "import(data: { session?: table<string, string>, disk?: table<string, string> })" => "Stores values returned from `export`",
+    "namespace(name: string) -> StoreNamespace" => "Returns the store functions operating on keys in a namespace",
+]);
+
+pub const NAMESPACE_API: ApiModule = lua_api!(class "StoreNamespace", "Store functions operating on keys in a namespace", [
+    "session_write(key: string, value: string, ttl?: number)" => "Stores a value until Blightmud quits, or for `ttl` seconds",
+    "session_read(key: string) -> string?" => "Reads a session value",
+    "session_delete(key: string)" => "Removes a session value",
+    "session_incr(key: string, amount?: integer) -> integer" => "Atomically increments a session counter",
+    "session_keys() -> string[]" => "Returns the session keys",
+    "disk_write(key: string, value: string, ttl?: number)" => "Stores a value on disk, optionally for `ttl` seconds",
+    "disk_read(key: string) -> string?" => "Reads a value stored on disk",
+    "disk_delete(key: string)" => "Removes a value stored on disk",
+    "disk_incr(key: string, amount?: integer) -> integer" => "Atomically increments a counter stored on disk",
+    "disk_keys() -> string[]" => "Returns the keys stored on disk",
+    "export() -> { session: table<string, string>, disk: table<string, string> }" => "Returns all values in the namespace",
+    "import(data: { session?: table<string, string>, disk?: table<string, string> })" => "Stores values returned from `export`",
+]);
+
+impl UserData for Store {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        macro_rules! bind {
+            ($name:literal, $storage:expr => $f:ident) => {
+                bind!($name, |lua, ns, args| $f(lua, $storage, ns, args))
+            };
+            ($name:literal, $f:expr) => {
+                methods.add_function($name, |lua, args| $f(lua, "", args))
+            };
+        }
+        store_functions!(bind);
+        methods.add_function("namespace", namespace);
     }
 }
 
@@ -64,11 +337,17 @@ mod test_store {
     use super::Store;
     use mlua::Lua;
 
+    fn get_lua() -> Lua {
+        let lua = Lua::new();
+        lua.globals()
+            .set(Store::LUA_GLOBAL_NAME, Store::new())
+            .unwrap();
+        lua
+    }
+
     #[test]
     fn test_memory_storage() {
-        let lua = Lua::new();
-        let store = Store::new();
-        lua.globals().set(Store::LUA_GLOBAL_NAME, store).unwrap();
+        let lua = get_lua();
 
         lua.load("store.session_write(\"abc\",\"def\")")
             .exec()
@@ -79,4 +358,103 @@ mod test_store {
             .unwrap();
         assert_eq!("def", value);
     }
+
+    #[test]
+    fn test_namespaces() {
+        let lua = get_lua();
+        lua.load(
+            r#"
+            local ns = store.namespace("plugin")
+            ns.session_write("key", "namespaced")
+            store.session_write("key", "global")
+            "#,
+        )
+        .exec()
+        .unwrap();
+        let values: (String, String, Vec<String>, Vec<String>) = lua
+            .load(
+                r#"
+            local ns = store.namespace("plugin")
+            return ns.session_read("key"), store.session_read("plugin/key"),
+                ns.session_keys(), store.session_keys()
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(
+            values,
+            (
+                "namespaced".to_string(),
+                "namespaced".to_string(),
+                vec!["key".to_string()],
+                vec!["key".to_string(), "plugin/key".to_string()],
+            )
+        );
+        assert!(lua.load("store.namespace('')").exec().is_err());
+    }
+
+    #[test]
+    fn test_ttl_and_delete() {
+        let lua = get_lua();
+        let values: (Option<String>, Option<String>, Option<String>) = lua
+            .load(
+                r#"
+            store.session_write("expired", "value", -1)
+            store.session_write("fresh", "value", 60)
+            store.session_write("deleted", "value")
+            store.session_delete("deleted")
+            return store.session_read("expired"), store.session_read("fresh"),
+                store.session_read("deleted")
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(values, (None, Some("value".to_string()), None));
+    }
+
+    #[test]
+    fn test_increment() {
+        let lua = get_lua();
+        let values: (i64, i64, i64) = lua
+            .load(
+                r#"
+            local ns = store.namespace("counter")
+            return ns.session_incr("kills"), ns.session_incr("kills", 5),
+                ns.session_incr("kills", -2)
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(values, (1, 6, 4));
+        assert!(lua
+            .load("store.session_write('text', 'abc'); store.session_incr('text')")
+            .exec()
+            .is_err());
+        assert!(lua
+            .load("store.session_incr('kills', 2^62); store.session_incr('kills', 2^62)")
+            .exec()
+            .is_err());
+        assert!(lua
+            .load("store.session_write('key', 'value', math.huge)")
+            .exec()
+            .is_err());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let lua = get_lua();
+        let value: String = lua
+            .load(
+                r#"
+            local source = store.namespace("source")
+            source.session_write("a", "1")
+            local data = source.export()
+            store.namespace("copy").import({ session = data.session })
+            return store.session_read("copy/a")
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(value, "1");
+    }
 }
@@ -1,12 +1,15 @@
 use mlua::{Lua, UserData, UserDataMethods};
 
 use super::{
+    api::ApiModule,
     backend::Backend,
     blight::Blight,
     constants::{
-        BACKEND, TIMED_CALLBACK_TABLE, TIMED_CALLBACK_TABLE_CORE, TIMED_NEXT_ID,
+        BACKEND, TIMED_CALLBACK_TABLE, TIMED_CALLBACK_TABLE_CORE, TIMED_NEXT_ID, TIMER_SCRIPTS,
         TIMER_TICK_CALLBACK_TABLE, TIMER_TICK_CALLBACK_TABLE_CORE,
     },
+    plugin::usage::{self, PluginUsage},
+    script,
 };
 use crate::event::Event;
 use chrono::Duration;
@@ -26,6 +29,17 @@ fn is_core_mode(lua: &Lua) -> Result<bool, mlua::Error> {
     Ok(blight.core_mode)
 }
 
+/// Records the calling script and plugin, if any, as the owner of a timer.
+fn add_owner(lua: &Lua, id: u32) -> Result<(), mlua::Error> {
+    usage::add_timer(lua, id);
+    if let Some(path) = script::caller(lua) {
+        if let Ok(owners) = lua.named_registry_value::<mlua::Table>(TIMER_SCRIPTS) {
+            owners.set(id, path)?;
+        }
+    }
+    Ok(())
+}
+
 fn user_mode_only(lua: &Lua) -> Result<(), mlua::Error> {
     if is_core_mode(lua)? {
         let boxed_error =
@@ -35,6 +49,14 @@ fn user_mode_only(lua: &Lua) -> Result<(), mlua::Error> {
     Ok(())
 }
 
+pub const API: ApiModule = lua_api!("timer", "Timed callbacks", [
+    "add(duration: number, count: integer, callback: function) -> integer" => "Adds a timer, a count of 0 repeats forever",
+    "get_ids() -> integer[]" => "Returns the ids of all timers",
+    "clear()" => "Removes all timers",
+    "remove(id: integer)" => "Removes a timer",
+    "on_tick(callback: fun(millis: integer))" => "Adds a callback running on every tick",
+]);
+
 impl UserData for Timer {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function(
@@ -53,6 +75,7 @@ impl UserData for Timer {
                 let lua_id: mlua::Integer = lua.named_registry_value(TIMED_NEXT_ID)?;
                 let id = lua_id as u32;
                 cb_table.raw_set(id, callback)?;
+                add_owner(lua, id)?;
                 backend
                     .writer
                     .send(Event::AddTimedEvent(duration, count, id, core_mode))
@@ -74,6 +97,10 @@ impl UserData for Timer {
             user_mode_only(ctx)?;
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             ctx.set_named_registry_value(TIMED_CALLBACK_TABLE, ctx.create_table()?)?;
+            ctx.set_named_registry_value(TIMER_SCRIPTS, ctx.create_table()?)?;
+            if let Some(usage) = usage::usage(ctx) {
+                usage.borrow_mut::<PluginUsage>()?.clear_timers();
+            }
             backend.writer.send(Event::ClearTimers).unwrap();
             Ok(())
         });
@@ -82,6 +109,12 @@ impl UserData for Timer {
             let backend: Backend = ctx.named_registry_value(BACKEND)?;
             let timer_table: mlua::Table = ctx.named_registry_value(TIMED_CALLBACK_TABLE)?;
             timer_table.raw_set(timer_idx, mlua::Nil)?;
+            if let Ok(owners) = ctx.named_registry_value::<mlua::Table>(TIMER_SCRIPTS) {
+                owners.raw_set(timer_idx, mlua::Nil)?;
+            }
+            if let Some(usage) = usage::usage(ctx) {
+                usage.borrow_mut::<PluginUsage>()?.remove_timer(timer_idx);
+            }
             backend.writer.send(Event::RemoveTimer(timer_idx)).unwrap();
             Ok(())
         });
@@ -94,6 +127,7 @@ impl UserData for Timer {
             };
             let lua_id: mlua::Integer = ctx.named_registry_value(TIMED_NEXT_ID)?;
             tick_table.raw_set(lua_id, func)?;
+            add_owner(ctx, lua_id as u32)?;
             ctx.set_named_registry_value(TIMED_NEXT_ID, lua_id + 1)?;
             Ok(())
         });
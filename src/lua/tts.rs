@@ -1,8 +1,12 @@
-use mlua::{AnyUserData, MetaMethod, UserData, UserDataMethods};
+use mlua::{AnyUserData, MetaMethod, Table, UserData, UserDataMethods};
 
-use crate::{event::Event, tts::TTSEvent};
+use crate::{
+    event::Event,
+    io::SaveData,
+    tts::{SpeechRule, TTSBackend, TTSEvent, TTSRules, TTSSettings, TTSSubstitutions},
+};
 
-use super::{backend::Backend, constants::BACKEND};
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
 
 pub struct Tts {
     pub enabled: bool,
@@ -14,6 +18,120 @@ impl Tts {
     }
 }
 
+fn parse_rule(rule: &Table) -> mlua::Result<SpeechRule> {
+    let opt = |key: &str| -> mlua::Result<Option<String>> { rule.get(key) };
+    Ok(SpeechRule {
+        speak: rule.get::<_, Option<bool>>("speak")?.unwrap_or(true),
+        priority: rule.get::<_, Option<i32>>("priority")?.unwrap_or_default(),
+        tag: opt("tag")?,
+        pattern: opt("pattern")?,
+        matched: rule.get("matched")?,
+        kind: opt("kind")?
+            .map(|kind| kind.parse())
+            .transpose()
+            .map_err(mlua::Error::external)?,
+        direction: opt("direction")?
+            .map(|direction| direction.parse())
+            .transpose()
+            .map_err(mlua::Error::external)?,
+        source: opt("source")?,
+    })
+}
+
+fn rule_table<'lua>(ctx: &'lua mlua::Lua, rule: SpeechRule) -> mlua::Result<Table<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("speak", rule.speak)?;
+    table.set("priority", rule.priority)?;
+    table.set("tag", rule.tag)?;
+    table.set("pattern", rule.pattern)?;
+    table.set("matched", rule.matched)?;
+    table.set("kind", rule.kind.map(|kind| kind.to_string()))?;
+    table.set(
+        "direction",
+        rule.direction.map(|direction| direction.to_string()),
+    )?;
+    table.set("source", rule.source)?;
+    Ok(table)
+}
+
+fn reload_rules(ctx: &mlua::Lua, rules: TTSRules) -> mlua::Result<()> {
+    rules.save();
+    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+    backend
+        .writer
+        .send(Event::TTSEvent(TTSEvent::ReloadRules))
+        .unwrap();
+    Ok(())
+}
+
+fn parse_backend(name: &str, opts: Option<Table>) -> mlua::Result<TTSBackend> {
+    let opt = |key: &str| -> mlua::Result<Option<String>> {
+        opts.as_ref()
+            .map_or(Ok(None), |opts| opts.get::<_, Option<String>>(key))
+    };
+    let required = |key: &str| -> mlua::Result<String> {
+        opt(key)?.ok_or_else(|| {
+            mlua::Error::RuntimeError(format!("The {name} backend requires the '{key}' option"))
+        })
+    };
+    match name {
+        "builtin" => Ok(TTSBackend::Builtin),
+        "speech-dispatcher" => Ok(TTSBackend::SpeechDispatcher),
+        "piper" => Ok(TTSBackend::Piper {
+            model: required("model")?,
+        }),
+        "espeak-ng" => Ok(TTSBackend::EspeakNg),
+        "command" => {
+            let program = required("program")?;
+            let args = opts
+                .as_ref()
+                .map_or(Ok(None), |opts| opts.get::<_, Option<Vec<String>>>("args"))?
+                .unwrap_or_default();
+            Ok(TTSBackend::Command { program, args })
+        }
+        _ => Err(mlua::Error::RuntimeError(format!(
+            "Unknown TTS backend: {name}, expected one of: {}",
+            TTSBackend::NAMES.join(", ")
+        ))),
+    }
+}
+
+#[cfg(feature = "tts")]
+pub const API: ApiModule = lua_api!("tts", "Text-to-speech", [
+    "is_available() -> boolean" => "Checks if Blightmud was built with text-to-speech",
+    "speak(msg: string, interrupt?: boolean)" => "Speaks a message",
+    "speak_direct(msg: string)" => "Speaks a message, interrupting the current speech",
+    "stop()" => "Stops speaking",
+    "enable(enabled: boolean)" => "Enables or disables text-to-speech",
+    "is_enabled() -> boolean" => "Checks if text-to-speech is enabled",
+    "set_rate(rate: number)" => "Sets the speech rate",
+    "change_rate(change: number)" => "Changes the speech rate",
+    "echo_keypresses(enabled: boolean)" => "Speaks keys as they are typed",
+    "step_back(step: integer)" => "Moves the reading position back",
+    "step_forward(step: integer)" => "Moves the reading position forward",
+    "scan_back(step: integer)" => "Moves back to the previous line of output",
+    "scan_forward(step: integer)" => "Moves forward to the next line of output",
+    "scan_input_back()" => "Moves back to the previous line of input",
+    "scan_input_forward()" => "Moves forward to the next line of input",
+    "step_begin()" => "Moves to the first line",
+    "step_end()" => "Moves to the last line",
+    "set_backend(name: string, options?: { model?: string, program?: string, args?: string[] })" => "Switches the speech engine",
+    "backend() -> string" => "Returns the name of the speech engine",
+    "backends() -> string[]" => "Lists the available speech engines",
+    "add_substitution(pattern: string, spoken: string)" => "Speaks text matching a regex as something else",
+    "remove_substitution(pattern: string) -> boolean" => "Removes a substitution",
+    "substitutions() -> { pattern: string, spoken: string }[]" => "Lists the substitutions applied before speaking",
+    "add_rule(rule: { speak?: boolean, priority?: integer, tag?: string, pattern?: string, matched?: boolean, kind?: string, direction?: string, source?: string }) -> integer" => "Adds a rule deciding what's spoken",
+    "remove_rule(index: integer) -> boolean" => "Removes a speech rule",
+    "rules() -> table[]" => "Lists the speech rules",
+    "clear_rules()" => "Removes all speech rules",
+]);
+
+#[cfg(not(feature = "tts"))]
+pub const API: ApiModule = lua_api!("tts", "Text-to-speech", [
+    "is_available() -> boolean" => "Checks if Blightmud was built with text-to-speech",
+]);
+
 impl UserData for Tts {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("is_available", |_, _: ()| Ok(cfg!(feature = "tts")));
@@ -134,6 +252,90 @@ impl UserData for Tts {
                 backend.writer.send(Event::TTSEvent(TTSEvent::End)).unwrap();
                 Ok(())
             });
+            methods.add_function(
+                "set_backend",
+                |ctx, (name, opts): (String, Option<Table>)| {
+                    let tts_backend = parse_backend(&name, opts)?;
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend
+                        .writer
+                        .send(Event::TTSEvent(TTSEvent::SetBackend(tts_backend)))
+                        .unwrap();
+                    Ok(())
+                },
+            );
+            methods.add_function("backend", |_, ()| Ok(TTSSettings::load().backend.name()));
+            methods.add_function("backends", |_, ()| Ok(TTSBackend::NAMES.to_vec()));
+            methods.add_function(
+                "add_substitution",
+                |ctx, (pattern, spoken): (String, String)| {
+                    let mut substitutions =
+                        TTSSubstitutions::try_load().map_err(mlua::Error::external)?;
+                    substitutions
+                        .add(&pattern, &spoken)
+                        .map_err(mlua::Error::external)?;
+                    substitutions.save();
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend
+                        .writer
+                        .send(Event::TTSEvent(TTSEvent::ReloadSubstitutions))
+                        .unwrap();
+                    Ok(())
+                },
+            );
+            methods.add_function("remove_substitution", |ctx, pattern: String| {
+                let mut substitutions =
+                    TTSSubstitutions::try_load().map_err(mlua::Error::external)?;
+                let removed = substitutions.remove(&pattern);
+                if removed {
+                    substitutions.save();
+                    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+                    backend
+                        .writer
+                        .send(Event::TTSEvent(TTSEvent::ReloadSubstitutions))
+                        .unwrap();
+                }
+                Ok(removed)
+            });
+            methods.add_function("add_rule", |ctx, rule: Table| {
+                let mut rules = TTSRules::try_load().map_err(mlua::Error::external)?;
+                rules
+                    .add(parse_rule(&rule)?)
+                    .map_err(mlua::Error::external)?;
+                let index = rules.rules.len();
+                reload_rules(ctx, rules)?;
+                Ok(index)
+            });
+            methods.add_function("remove_rule", |ctx, index: usize| {
+                let mut rules = TTSRules::try_load().map_err(mlua::Error::external)?;
+                if index == 0 || rules.remove(index - 1).is_none() {
+                    return Ok(false);
+                }
+                reload_rules(ctx, rules)?;
+                Ok(true)
+            });
+            methods.add_function("rules", |ctx, ()| {
+                let rules = TTSRules::try_load().map_err(mlua::Error::external)?;
+                let result = ctx.create_table()?;
+                for rule in rules.rules {
+                    result.push(rule_table(ctx, rule)?)?;
+                }
+                Ok(result)
+            });
+            methods.add_function("clear_rules", |ctx, ()| {
+                reload_rules(ctx, TTSRules::default())
+            });
+            methods.add_function("substitutions", |ctx, ()| {
+                let substitutions = TTSSubstitutions::try_load().map_err(mlua::Error::external)?;
+                let result = ctx.create_table()?;
+                for sub in substitutions.substitutions {
+                    let entry = ctx.create_table()?;
+                    entry.set("pattern", sub.pattern)?;
+                    entry.set("spoken", sub.spoken)?;
+                    result.push(entry)?;
+                }
+                Ok(result)
+            });
         } else {
             methods.add_meta_function(MetaMethod::Index, |ctx, _: ()| {
                 let func: mlua::Function = ctx.load("function () end").eval()?;
@@ -160,3 +362,60 @@ impl UserData for Tts {
         }
     }
 }
+
+#[cfg(test)]
+mod test_tts {
+    use mlua::Lua;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_backend() {
+        let lua = Lua::new();
+        assert_eq!(
+            parse_backend("espeak-ng", None).unwrap(),
+            TTSBackend::EspeakNg
+        );
+        let opts: Table = lua
+            .load(r#"return { program = "say", args = { "-v", "Alex" } }"#)
+            .eval()
+            .unwrap();
+        assert_eq!(
+            parse_backend("command", Some(opts)).unwrap(),
+            TTSBackend::Command {
+                program: "say".to_string(),
+                args: vec!["-v".to_string(), "Alex".to_string()],
+            }
+        );
+        assert_eq!(
+            parse_backend("piper", None).unwrap_err().to_string(),
+            "runtime error: The piper backend requires the 'model' option"
+        );
+        assert!(parse_backend("festival", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let lua = Lua::new();
+        let rule: Table = lua
+            .load(r#"return { speak = false, priority = 5, kind = "prompt", direction = "received" }"#)
+            .eval()
+            .unwrap();
+        let rule = parse_rule(&rule).unwrap();
+        assert!(!rule.speak);
+        assert_eq!(rule.priority, 5);
+        assert_eq!(rule.kind, Some(crate::tts::SpeechKind::Prompt));
+        assert_eq!(
+            rule.direction.map(|d| d.to_string()).as_deref(),
+            Some("received")
+        );
+        assert_eq!(rule.tag, None);
+        let table = rule_table(&lua, rule.clone()).unwrap();
+        assert_eq!(parse_rule(&table).unwrap(), rule);
+
+        let rule: Table = lua.load(r#"return { kind = "window" }"#).eval().unwrap();
+        assert!(parse_rule(&rule).is_err());
+        let rule: Table = lua.load("return {}").eval().unwrap();
+        assert!(parse_rule(&rule).unwrap().speak);
+    }
+}
@@ -16,6 +16,7 @@ pub enum UiEvent {
     ScrollDown,
     ScrollTop,
     ScrollBottom,
+    TogglePause,
     Complete,
     Unknown(String),
 }
@@ -39,6 +40,7 @@ impl From<&str> for UiEvent {
             "scroll_down" => UiEvent::ScrollDown,
             "scroll_top" => UiEvent::ScrollTop,
             "scroll_bottom" => UiEvent::ScrollBottom,
+            "toggle_pause" => UiEvent::TogglePause,
             "complete" => UiEvent::Complete,
             _ => UiEvent::Unknown(s.to_string()),
         }
@@ -1,14 +1,7 @@
 use crate::event::Event;
 use std::sync::mpsc::Sender;
 
+/// Reports a script error. The main loop records it in the error log and prints it.
 pub fn output_stack_trace(writer: &Sender<Event>, error: &str) {
-    writer
-        .send(Event::Error("[Lua] Script error:".to_string()))
-        .unwrap();
-    for line in error.split('\n') {
-        writer
-            .send(Event::Error(format!("\t{line}").to_string()))
-            .unwrap();
-    }
     writer.send(Event::LuaError(error.to_string())).ok();
 }
@@ -0,0 +1,367 @@
+use lazy_static::lazy_static;
+use mlua::{Function, Lua, Result as LuaResult, Table, UserData, UserDataMethods, Value};
+use regex::Regex;
+
+use super::api::ApiModule;
+use super::constants::{SESSION_VARS, SESSION_VAR_LISTENERS};
+use crate::model::{evaluate, format_value};
+
+lazy_static! {
+    /// `@{expression}` or its `@@{` escape, `$name` and `%t`, matched together so substituted
+    /// values are never expanded again.
+    static ref REFERENCE_PATTERN: Regex =
+        Regex::new(r"@(@?)\{([^}]*)\}|\$([A-Za-z_][A-Za-z0-9_]*)|%t").unwrap();
+}
+
+/// Stores a session variable and notifies `on_change` listeners if the value changed.
+pub fn set_var<'lua>(ctx: &'lua Lua, name: &str, value: Value<'lua>) -> LuaResult<()> {
+    let vars: Table = ctx.named_registry_value(SESSION_VARS)?;
+    let old: Value = vars.get(name)?;
+    if old == value {
+        return Ok(());
+    }
+    vars.set(name, value.clone())?;
+    let listeners: Table = ctx.named_registry_value(SESSION_VAR_LISTENERS)?;
+    if let Some(callbacks) = listeners.get::<_, Option<Table>>(name)? {
+        for cb in callbacks.sequence_values::<Function>() {
+            cb?.call::<_, ()>((value.clone(), old.clone(), name))?;
+        }
+    }
+    Ok(())
+}
+
+fn json_to_lua<'lua>(ctx: &'lua Lua, value: &serde_json::Value) -> LuaResult<Value<'lua>> {
+    Ok(match value {
+        serde_json::Value::Bool(b) => Value::Boolean(*b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::String(ctx.create_string(s)?),
+        _ => Value::Nil,
+    })
+}
+
+fn numeric_var(vars: &Table, name: &str) -> Option<f64> {
+    match vars.get::<_, Value>(name).ok()? {
+        Value::Integer(i) => Some(i as f64),
+        Value::Number(n) => Some(n),
+        Value::String(s) => s.to_str().ok()?.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Converts a variable to the text it expands to, if it can be expanded.
+fn var_text(value: Value) -> LuaResult<Option<String>> {
+    Ok(match value {
+        Value::String(s) => Some(s.to_str()?.to_string()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+/// Replaces `@{expression}` with its evaluated result, `$name` with the value of the named
+/// session variable and `%t` with the current target, in a single pass. `@@{` is left as a
+/// literal `@{`. References to variables that aren't set are left untouched.
+pub fn expand_vars(ctx: &Lua, content: &str) -> LuaResult<String> {
+    let vars = match ctx.named_registry_value::<Option<Table>>(SESSION_VARS)? {
+        Some(vars) => vars,
+        None => return Ok(content.to_string()),
+    };
+    let mut result = String::with_capacity(content.len());
+    let mut last = 0;
+    for captures in REFERENCE_PATTERN.captures_iter(content) {
+        let whole = captures.get(0).unwrap();
+        let replacement = if let Some(expr) = captures.get(2) {
+            if captures[1].is_empty() {
+                let value = evaluate(expr.as_str(), &|name| numeric_var(&vars, name))
+                    .map_err(mlua::Error::external)?;
+                format_value(value)
+            } else {
+                whole.as_str()[1..].to_string()
+            }
+        } else {
+            let name = captures.get(3).map_or("target", |name| name.as_str());
+            match var_text(vars.get(name)?)? {
+                Some(text) => text,
+                None => continue,
+            }
+        };
+        result.push_str(&content[last..whole.start()]);
+        result.push_str(&replacement);
+        last = whole.end();
+    }
+    result.push_str(&content[last..]);
+    Ok(result)
+}
+
+pub struct Vars {}
+
+pub const API: ApiModule = lua_api!("vars", "Session variables", [
+    "set(name: string, value: any)" => "Sets a variable",
+    "get(name: string) -> any" => "Returns a variable",
+    "all() -> table<string, any>" => "Returns all variables",
+    "clear()" => "Removes all variables",
+    "on_change(name: string, callback: fun(value: any, old: any, name: string))" => "Adds a listener for changes to a variable",
+    "expand(str: string) -> string" => "Expands variable references in a string",
+    "map_gmcp(name: string, module: string, path: string)" => "Keeps a variable in sync with a GMCP value",
+    "map_msdp(name: string, variable: string)" => "Keeps a variable in sync with an MSDP variable",
+]);
+
+impl UserData for Vars {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("set", |ctx, (name, value): (String, Value)| {
+            set_var(ctx, &name, value)
+        });
+        methods.add_function("get", |ctx, name: String| -> LuaResult<Value> {
+            let vars: Table = ctx.named_registry_value(SESSION_VARS)?;
+            vars.get(name)
+        });
+        methods.add_function("all", |ctx, ()| -> LuaResult<Table> {
+            let vars: Table = ctx.named_registry_value(SESSION_VARS)?;
+            let copy = ctx.create_table()?;
+            for pair in vars.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                copy.set(key, value)?;
+            }
+            Ok(copy)
+        });
+        methods.add_function("clear", |ctx, ()| -> LuaResult<()> {
+            let vars: Table = ctx.named_registry_value(SESSION_VARS)?;
+            let names = vars
+                .pairs::<String, Value>()
+                .filter_map(Result::ok)
+                .map(|(name, _)| name)
+                .collect::<Vec<String>>();
+            for name in names {
+                set_var(ctx, &name, Value::Nil)?;
+            }
+            Ok(())
+        });
+        methods.add_function(
+            "on_change",
+            |ctx, (name, cb): (String, Function)| -> LuaResult<()> {
+                let listeners: Table = ctx.named_registry_value(SESSION_VAR_LISTENERS)?;
+                let callbacks = match listeners.get::<_, Option<Table>>(name.as_str())? {
+                    Some(callbacks) => callbacks,
+                    None => {
+                        let callbacks = ctx.create_table()?;
+                        listeners.set(name.as_str(), callbacks.clone())?;
+                        callbacks
+                    }
+                };
+                callbacks.push(cb)?;
+                Ok(())
+            },
+        );
+        methods.add_function("expand", |ctx, content: String| expand_vars(ctx, &content));
+        methods.add_function(
+            "map_gmcp",
+            |ctx, (name, module, path): (String, String, String)| -> LuaResult<()> {
+                let gmcp: Table = ctx.globals().get("gmcp")?;
+                let receive: Function = gmcp.get("receive")?;
+                let cb = ctx.create_function(move |ctx, data: String| -> LuaResult<()> {
+                    let json: serde_json::Value =
+                        serde_json::from_str(&data).map_err(mlua::Error::external)?;
+                    let value = path
+                        .split('.')
+                        .filter(|key| !key.is_empty())
+                        .try_fold(&json, |value, key| value.get(key));
+                    if let Some(value) = value {
+                        set_var(ctx, &name, json_to_lua(ctx, value)?)?;
+                    }
+                    Ok(())
+                })?;
+                receive.call::<_, ()>((module, cb))
+            },
+        );
+        methods.add_function(
+            "map_msdp",
+            |ctx, (name, variable): (String, String)| -> LuaResult<()> {
+                let msdp: Table = ctx.globals().get("msdp")?;
+                let register: Function = msdp.get("register")?;
+                let cb =
+                    ctx.create_function(move |ctx, value: Value| set_var(ctx, &name, value))?;
+                register.call::<_, ()>((variable, cb))
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_vars {
+    use mlua::Lua;
+
+    use super::{expand_vars, Vars};
+    use crate::lua::constants::{SESSION_VARS, SESSION_VAR_LISTENERS};
+
+    fn get_lua_state() -> Lua {
+        let lua = Lua::new();
+        lua.set_named_registry_value(SESSION_VARS, lua.create_table().unwrap())
+            .unwrap();
+        lua.set_named_registry_value(SESSION_VAR_LISTENERS, lua.create_table().unwrap())
+            .unwrap();
+        lua.globals().set("vars", Vars {}).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_set_get() {
+        let lua = get_lua_state();
+        lua.load(r#"vars.set("hp", 100) vars.set("target", "orc")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            lua.load(r#"return vars.get("hp")"#)
+                .call::<_, i64>(())
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            lua.load(r#"return vars.get("target")"#)
+                .call::<_, String>(())
+                .unwrap(),
+            "orc"
+        );
+        assert_eq!(
+            lua.load(r#"local n = 0 for _ in pairs(vars.all()) do n = n + 1 end return n"#)
+                .call::<_, i64>(())
+                .unwrap(),
+            2
+        );
+        lua.load("vars.clear()").exec().unwrap();
+        assert!(lua
+            .load(r#"return vars.get("hp") == nil"#)
+            .call::<_, bool>(())
+            .unwrap());
+    }
+
+    #[test]
+    fn test_on_change() {
+        let lua = get_lua_state();
+        lua.load(
+            r#"
+        changes = {}
+        vars.on_change("hp", function (new, old, name)
+            table.insert(changes, string.format("%s:%s->%s", name, tostring(old), tostring(new)))
+        end)
+        vars.set("hp", 10)
+        vars.set("hp", 10)
+        vars.set("hp", 5)
+        vars.set("sp", 5)
+        "#,
+        )
+        .exec()
+        .unwrap();
+        let changes: Vec<String> = lua.globals().get("changes").unwrap();
+        assert_eq!(changes, vec!["hp:nil->10", "hp:10->5"]);
+    }
+
+    #[test]
+    fn test_expand() {
+        let lua = get_lua_state();
+        lua.load(r#"vars.set("target", "orc") vars.set("gold", 12)"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            expand_vars(&lua, "kill $target with $weapon for $gold$").unwrap(),
+            "kill orc with $weapon for 12$"
+        );
+        assert_eq!(
+            expand_vars(&Lua::new(), "kill $target").unwrap(),
+            "kill $target"
+        );
+    }
+
+    #[test]
+    fn test_expand_expressions() {
+        let lua = get_lua_state();
+        lua.load(r#"vars.set("gold", 25) vars.set("count", "4")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            expand_vars(&lua, "put @{gold/2} coins in bag").unwrap(),
+            "put 12.5 coins in bag"
+        );
+        assert_eq!(
+            expand_vars(&lua, "get @{count * (gold - 5)} $gold").unwrap(),
+            "get 80 25"
+        );
+        assert!(expand_vars(&lua, "get @{mana / 2}").is_err());
+        assert_eq!(
+            expand_vars(&lua, "say @@{gold/2} is @{gold/5}").unwrap(),
+            "say @{gold/2} is 5"
+        );
+    }
+
+    #[test]
+    fn test_expand_target() {
+        let lua = get_lua_state();
+        assert_eq!(expand_vars(&lua, "kill %t").unwrap(), "kill %t");
+        lua.load(r#"vars.set("target", "rat")"#).exec().unwrap();
+        assert_eq!(expand_vars(&lua, "kill %t").unwrap(), "kill rat");
+        lua.load(r#"vars.set("msg", "%t is @{1/0} $msg")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            expand_vars(&lua, "say $msg to %t").unwrap(),
+            "say %t is @{1/0} $msg to rat"
+        );
+    }
+
+    #[test]
+    fn test_map_gmcp() {
+        let lua = get_lua_state();
+        lua.load(
+            r#"
+        local receivers = {}
+        gmcp = {
+            receive = function (module, cb) receivers[module] = cb end,
+        }
+        vars.map_gmcp("hp", "Char.Vitals", "hp")
+        vars.map_gmcp("room", "Room.Info", "area.name")
+        receivers["Char.Vitals"]('{"hp": 42, "mp": 10}')
+        receivers["Room.Info"]('{"area": {"name": "Town"}}')
+        "#,
+        )
+        .exec()
+        .unwrap();
+        assert_eq!(
+            lua.load(r#"return vars.get("hp")"#)
+                .call::<_, i64>(())
+                .unwrap(),
+            42
+        );
+        assert_eq!(
+            lua.load(r#"return vars.get("room")"#)
+                .call::<_, String>(())
+                .unwrap(),
+            "Town"
+        );
+    }
+
+    #[test]
+    fn test_map_msdp() {
+        let lua = get_lua_state();
+        lua.load(
+            r#"
+        local listeners = {}
+        msdp = {
+            register = function (var, cb) listeners[var] = cb end,
+        }
+        vars.map_msdp("hp", "HEALTH")
+        listeners["HEALTH"]("55")
+        "#,
+        )
+        .exec()
+        .unwrap();
+        assert_eq!(
+            lua.load(r#"return vars.get("hp")"#)
+                .call::<_, String>(())
+                .unwrap(),
+            "55"
+        );
+    }
+}
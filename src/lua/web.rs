@@ -0,0 +1,146 @@
+use std::net::IpAddr;
+
+use mlua::{AnyUserData, Lua, UserData, UserDataMethods, Value};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::io::SaveData;
+use crate::model::WebSettings;
+use crate::net::{WebMirror, WebStatus};
+
+/// Control of the web frontend mirroring the session.
+pub struct Web {
+    web: WebMirror,
+}
+
+impl Web {
+    pub const LUA_GLOBAL_NAME: &'static str = "web";
+
+    pub fn new(web: WebMirror) -> Self {
+        Self { web }
+    }
+}
+
+pub const API: ApiModule = lua_api!("web", "Serving the session as a web page", [
+    "is_available() -> boolean" => "Checks if Blightmud was built with the web frontend",
+    "start(port?: integer) -> string" => "Starts serving the session and returns the url of the page",
+    "stop()" => "Stops serving the session and disconnects open pages",
+    "status() -> { bind: string, port: integer, url: string, clients: integer, exposed: boolean }?" => "Returns the running server, or nil",
+    "bind(address?: string) -> string" => "Sets and returns the address the server listens on, 127.0.0.1 by default",
+]);
+
+fn web_mirror(ctx: &Lua) -> mlua::Result<WebMirror> {
+    let web: AnyUserData = ctx.globals().get(Web::LUA_GLOBAL_NAME)?;
+    let web = web.borrow::<Web>()?;
+    Ok(web.web.clone())
+}
+
+fn status_table<'lua>(ctx: &'lua Lua, status: &WebStatus) -> mlua::Result<Value<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("bind", status.bind.to_string())?;
+    table.set("port", status.port)?;
+    table.set("url", status.url())?;
+    table.set("clients", status.clients)?;
+    table.set("exposed", status.is_exposed())?;
+    Ok(Value::Table(table))
+}
+
+impl UserData for Web {
+    fn add_methods<'lua, T: UserDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("is_available", |_, ()| Ok(cfg!(feature = "web")));
+        methods.add_function("start", |ctx, port: Option<u16>| {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            let bind = WebSettings::load().bind;
+            let status = web_mirror(ctx)?
+                .start(bind, port.unwrap_or_default(), backend.writer)
+                .map_err(mlua::Error::RuntimeError)?;
+            Ok(status.url())
+        });
+        methods.add_function("stop", |ctx, ()| {
+            web_mirror(ctx)?.stop();
+            Ok(())
+        });
+        methods.add_function("status", |ctx, ()| match web_mirror(ctx)?.status() {
+            Some(status) => status_table(ctx, &status),
+            None => Ok(Value::Nil),
+        });
+        methods.add_function("bind", |_, address: Option<String>| {
+            let mut settings = WebSettings::load();
+            if let Some(address) = address {
+                settings.bind = address.parse::<IpAddr>().map_err(|_| {
+                    mlua::Error::RuntimeError(format!("Invalid bind address: {address}"))
+                })?;
+                settings.save();
+            }
+            Ok(settings.bind.to_string())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_web {
+    use std::sync::mpsc::channel;
+
+    use mlua::Lua;
+
+    use super::*;
+
+    fn lua() -> Lua {
+        let lua = Lua::new();
+        let (writer, _) = channel();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals()
+            .set(Web::LUA_GLOBAL_NAME, Web::new(WebMirror::default()))
+            .unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_start_and_stop() {
+        let lua = lua();
+        let available: bool = lua.load("return web.is_available()").eval().unwrap();
+        let started = lua.load("return web.start()").eval::<String>();
+        if available {
+            let url = started.unwrap();
+            let (port, clients): (u16, usize) = lua
+                .load("local s = web.status() return s.port, s.clients")
+                .eval()
+                .unwrap();
+            assert!(url.starts_with(&format!("http://127.0.0.1:{port}/?token=")));
+            assert_eq!(clients, 0);
+        } else {
+            assert!(started.is_err());
+        }
+        let stopped: bool = lua
+            .load("web.stop() return web.status() == nil")
+            .eval()
+            .unwrap();
+        assert!(stopped);
+
+        assert!(lua.load("web.bind('localhost')").exec().is_err());
+        let bind: String = lua.load("return web.bind('::1')").eval().unwrap();
+        assert_eq!(bind, "::1");
+        let bind: String = lua.load("return web.bind('127.0.0.1')").eval().unwrap();
+        assert_eq!(bind, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_url() {
+        let status = |bind: &str| WebStatus {
+            bind: bind.parse().unwrap(),
+            port: 8080,
+            token: "abc".to_string(),
+            clients: 0,
+        };
+        assert_eq!(
+            status("127.0.0.1").url(),
+            "http://127.0.0.1:8080/?token=abc"
+        );
+        assert_eq!(status("0.0.0.0").url(), "http://127.0.0.1:8080/?token=abc");
+        assert_eq!(status("::").url(), "http://127.0.0.1:8080/?token=abc");
+        assert_eq!(status("::1").url(), "http://[::1]:8080/?token=abc");
+        assert!(!status("127.0.0.1").is_exposed());
+        assert!(status("0.0.0.0").is_exposed());
+        assert!(status("192.168.1.2").is_exposed());
+    }
+}
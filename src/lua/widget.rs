@@ -0,0 +1,141 @@
+use mlua::{Result as LuaResult, Table, UserData, UserDataMethods};
+
+use super::{api::ApiModule, backend::Backend, constants::BACKEND};
+use crate::event::Event;
+use crate::model::{MaskStyle, Widget as MWidget, WidgetKind};
+
+pub const DEFAULT_REGION: &str = "widgets";
+
+fn create_widget(kind: WidgetKind, id: &str, opts: Option<Table>) -> LuaResult<MWidget> {
+    let mut widget = MWidget::new(id, DEFAULT_REGION, kind);
+    if let Some(opts) = opts {
+        if let Some(region) = opts.get::<_, Option<String>>("region")? {
+            widget.region = region;
+        }
+        widget.label = opts.get::<_, Option<String>>("label")?.unwrap_or_default();
+        widget.max = opts.get::<_, Option<f64>>("max")?.unwrap_or(1.0);
+        widget.value = opts.get::<_, Option<f64>>("value")?.unwrap_or(match kind {
+            WidgetKind::Countdown => widget.max,
+            _ => 0.0,
+        });
+        widget.expire = opts.get::<_, Option<f64>>("expire")?;
+        widget.color = opts.get::<_, Option<String>>("color")?.map(|color| {
+            MaskStyle {
+                fg: Some(color),
+                ..Default::default()
+            }
+            .to_ansi()
+        });
+    } else if kind == WidgetKind::Countdown {
+        widget.value = widget.max;
+    }
+    Ok(widget)
+}
+
+fn send_widget(
+    ctx: &mlua::Lua,
+    kind: WidgetKind,
+    id: String,
+    opts: Option<Table>,
+) -> LuaResult<()> {
+    let widget = create_widget(kind, &id, opts)?;
+    let backend: Backend = ctx.named_registry_value(BACKEND)?;
+    backend.writer.send(Event::SetWidget(widget)).unwrap();
+    Ok(())
+}
+
+pub struct Widget {}
+
+pub const API: ApiModule = lua_api!("widget", "Status area widgets", [
+    "bar(id: string, options?: table)" => "Adds or updates a bar",
+    "gauge(id: string, options?: table)" => "Adds or updates a gauge",
+    "countdown(id: string, options?: table)" => "Adds or updates a countdown",
+    "set(id: string, value: number)" => "Sets the value of a widget",
+    "remove(id: string)" => "Removes a widget",
+]);
+
+impl UserData for Widget {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("bar", |ctx, (id, opts): (String, Option<Table>)| {
+            send_widget(ctx, WidgetKind::Bar, id, opts)
+        });
+        methods.add_function("gauge", |ctx, (id, opts): (String, Option<Table>)| {
+            send_widget(ctx, WidgetKind::Gauge, id, opts)
+        });
+        methods.add_function("countdown", |ctx, (id, opts): (String, Option<Table>)| {
+            send_widget(ctx, WidgetKind::Countdown, id, opts)
+        });
+        methods.add_function("set", |ctx, (id, value): (String, f64)| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::UpdateWidget(id, value)).unwrap();
+            Ok(())
+        });
+        methods.add_function("remove", |ctx, id: String| -> LuaResult<()> {
+            let backend: Backend = ctx.named_registry_value(BACKEND)?;
+            backend.writer.send(Event::RemoveWidget(id)).unwrap();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test_widget {
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    use mlua::Lua;
+
+    use super::Widget;
+    use crate::event::Event;
+    use crate::lua::backend::Backend;
+    use crate::lua::constants::BACKEND;
+    use crate::model::{Widget as MWidget, WidgetKind};
+
+    fn get_lua_state() -> (Lua, Receiver<Event>) {
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+        let lua = Lua::new();
+        lua.set_named_registry_value(BACKEND, Backend::new(writer))
+            .unwrap();
+        lua.globals().set("widget", Widget {}).unwrap();
+        (lua, reader)
+    }
+
+    #[test]
+    fn test_bar() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"widget.bar("balance", { region = "hud", label = "Bal", max = 4.0, value = 1, expire = 5 })"#)
+            .exec()
+            .unwrap();
+        let mut expected = MWidget::new("balance", "hud", WidgetKind::Bar);
+        expected.label = "Bal".to_string();
+        expected.max = 4.0;
+        expected.value = 1.0;
+        expected.expire = Some(5.0);
+        assert_eq!(reader.recv(), Ok(Event::SetWidget(expected)));
+    }
+
+    #[test]
+    fn test_countdown() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"widget.countdown("eq", { max = 3, color = "red" })"#)
+            .exec()
+            .unwrap();
+        let mut expected = MWidget::new("eq", "widgets", WidgetKind::Countdown);
+        expected.max = 3.0;
+        expected.value = 3.0;
+        expected.color = Some("\x1b[31m".to_string());
+        assert_eq!(reader.recv(), Ok(Event::SetWidget(expected)));
+    }
+
+    #[test]
+    fn test_set_remove() {
+        let (lua, reader) = get_lua_state();
+        lua.load(r#"widget.set("eq", 2.5) widget.remove("eq")"#)
+            .exec()
+            .unwrap();
+        assert_eq!(
+            reader.recv(),
+            Ok(Event::UpdateWidget("eq".to_string(), 2.5))
+        );
+        assert_eq!(reader.recv(), Ok(Event::RemoveWidget("eq".to_string())));
+    }
+}
@@ -1,10 +1,13 @@
 use std::env;
 
-use blightmud::{register_panic_hook, RuntimeConfig, PROJECT_NAME, VERSION};
+use blightmud::{register_panic_hook, RuntimeConfig, CONTROL_COMMANDS, PROJECT_NAME, VERSION};
 use getopts::Options;
 
 fn print_help(program: &str, opts: Options) {
-    let brief = format!("USAGE: {program} [options]\n\n{PROJECT_NAME} {VERSION}");
+    let brief = format!(
+        "USAGE: {program} [options]\n       {program} <{}> [args]\n\n{PROJECT_NAME} {VERSION}",
+        CONTROL_COMMANDS.join("|")
+    );
     print!("{}", opts.usage(&brief));
 }
 
@@ -23,7 +26,7 @@ fn print_version() {
 
 fn setup_options() -> Options {
     let mut opts = Options::new();
-    opts.optopt("c", "connect", "Connect to server", "HOST:PORT");
+    opts.optopt("c", "connect", "Connect to server", "HOST:PORT[:tls]");
     opts.optflag(
         "t",
         "tls",
@@ -42,6 +45,13 @@ fn setup_options() -> Options {
         );
     }
     opts.optopt("w", "world", "Connect to a predefined world", "WORLD");
+    opts.optmulti("e", "eval", "Evaluate Lua code after starting", "LUA");
+    opts.optmulti(
+        "",
+        "command",
+        "Run a command after starting, as if it was typed",
+        "COMMAND",
+    );
     opts.optflag("h", "help", "Print help menu");
     opts.optflag("v", "version", "Print version information");
     opts.optflag("V", "verbose", "Enable verbose logging");
@@ -51,6 +61,26 @@ fn setup_options() -> Options {
         "no-update-check",
         "Skip checking for new Blightmud versions at startup",
     );
+    opts.optopt(
+        "",
+        "dump-api",
+        "Write Lua API definitions for editor autocompletion to DIR and exit",
+        "DIR",
+    );
+    if cfg!(feature = "encryption") {
+        opts.optopt(
+            "",
+            "encryption",
+            "Turn encryption of the store, saved servers and logs on or off",
+            "on|off",
+        );
+        opts.optopt(
+            "",
+            "read-log",
+            "Print a session log, decrypting it if it's encrypted",
+            "FILE",
+        );
+    }
     //opts.optflag("H", "headless-mode", "Runs Blightmud without a TUI");
 
     opts
@@ -74,15 +104,72 @@ fn main() {
     } else if matches.opt_present("v") {
         print_version();
         return;
+    } else if let Some(command) = matches
+        .free
+        .first()
+        .filter(|command| CONTROL_COMMANDS.contains(&command.as_str()))
+    {
+        match blightmud::run_control_command(command, &matches.free[1..]) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{output}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    } else if let Some(dir) = matches.opt_str("dump-api") {
+        match blightmud::dump_api(&dir) {
+            Ok(path) => println!("Wrote Lua API definitions to {}", path.display()),
+            Err(err) => eprintln!("Failed to write Lua API definitions: {err}"),
+        }
+        return;
+    } else if matches.opt_defined("encryption") && matches.opt_present("encryption") {
+        let result = match matches.opt_str("encryption").as_deref() {
+            Some("on") => blightmud::set_encryption(true),
+            Some("off") => blightmud::set_encryption(false),
+            _ => {
+                eprintln!("Expected --encryption on or --encryption off\n");
+                print_help(program, opts);
+                return;
+            }
+        };
+        match result {
+            Ok(message) => println!("{message}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    } else if let Some(path) = matches
+        .opt_defined("read-log")
+        .then(|| matches.opt_str("read-log"))
+        .flatten()
+    {
+        match blightmud::read_log(&path) {
+            Ok(log) => {
+                std::io::Write::write_all(&mut std::io::stdout(), &log).ok();
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
     }
 
     let rt = RuntimeConfig::from(matches);
 
-    if let Some(connect) = &rt.connect {
-        if !connect.contains(':') {
-            print_help(program, opts);
-            return;
-        }
+    if let Err(err) = rt.validate() {
+        eprintln!("{err}\n");
+        print_help(program, opts);
+        return;
+    }
+
+    if let Err(err) = blightmud::unlock_encryption() {
+        eprintln!("{err}");
+        std::process::exit(1);
     }
 
     register_panic_hook(rt.headless_mode);
@@ -94,7 +181,48 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use blightmud::RuntimeConfig;
+    use blightmud::{RuntimeConfig, StartupAction};
+
+    fn parse(args: &[&str]) -> RuntimeConfig {
+        let args: Vec<String> = args.iter().map(|s| String::from(*s)).collect();
+        let matches = match setup_options().parse(&args) {
+            Ok(m) => m,
+            Err(f) => panic!("{}", f.to_string()),
+        };
+        RuntimeConfig::from(matches)
+    }
+
+    #[test]
+    fn test_startup_actions() {
+        let rt = parse(&[
+            "-e",
+            "script.load('x.lua')",
+            "--connect",
+            "mud.org:4000:tls",
+            "--command",
+            "/start_log session",
+            "--eval",
+            "print('connected')",
+        ]);
+        assert_eq!(
+            rt.actions,
+            vec![
+                StartupAction::Eval("script.load('x.lua')".to_string()),
+                StartupAction::Connect("mud.org:4000:tls".to_string()),
+                StartupAction::Command("/start_log session".to_string()),
+                StartupAction::Eval("print('connected')".to_string()),
+            ]
+        );
+        assert!(rt.validate().is_ok());
+
+        for server in ["mud.org", "mud.org:port", "mud.org:4000:ssl", ":4000"] {
+            assert!(parse(&["--connect", server]).validate().is_err());
+        }
+        assert_eq!(
+            parse(&["--world", "Avalon"]).actions,
+            vec![StartupAction::World("Avalon".to_string())]
+        );
+    }
 
     #[test]
     fn test_config_parse() {
@@ -116,6 +244,9 @@ mod tests {
         let rt = RuntimeConfig::from(matches);
         assert!(rt.verbose);
         assert!(rt.no_update_check);
-        assert_eq!(rt.connect, Some("localhost:8080".to_string()));
+        assert_eq!(
+            rt.actions,
+            vec![StartupAction::Connect("localhost:8080".to_string())]
+        );
     }
 }
@@ -0,0 +1,196 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// The status area indicator listing the buffers with unread activity.
+pub const ACTIVITY_INDICATOR: &str = "__activity";
+
+/// How much a buffer wants attention, the lowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActivityLevel {
+    /// Anything new arrived.
+    Output,
+    /// Something matched, eg. a tell or a line of an `important` trigger.
+    Highlight,
+}
+
+impl ActivityLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level {
+            "output" => Some(Self::Output),
+            "highlight" => Some(Self::Highlight),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Output => "output",
+            Self::Highlight => "highlight",
+        }
+    }
+}
+
+/// The places output ends up in that can have unread lines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ActivityBuffer {
+    Inbox,
+    /// Output held off the screen, while paused or scrolled far back.
+    Scrollback,
+    /// A layout region, by name, that got new content while it wasn't on the screen.
+    Region(String),
+}
+
+impl From<&str> for ActivityBuffer {
+    fn from(name: &str) -> Self {
+        match name {
+            "inbox" => Self::Inbox,
+            "scrollback" => Self::Scrollback,
+            region => Self::Region(region.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ActivityBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inbox => write!(f, "inbox"),
+            Self::Scrollback => write!(f, "scrollback"),
+            Self::Region(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The buffers with unread activity in the order they got it, shared between the screen noticing
+/// unread output and the scripts reading and jumping to it.
+#[derive(Debug, Default, Clone)]
+pub struct Activity(Arc<Mutex<Vec<(ActivityBuffer, ActivityLevel)>>>);
+
+impl Activity {
+    /// Records activity in a buffer, which keeps the highest level it got until it's read.
+    /// Returns whether that changed anything.
+    pub fn record(&self, buffer: ActivityBuffer, level: ActivityLevel) -> bool {
+        let mut buffers = self.0.lock().unwrap();
+        match buffers.iter_mut().find(|(b, _)| *b == buffer) {
+            Some((_, current)) if *current >= level => false,
+            Some((_, current)) => {
+                *current = level;
+                true
+            }
+            None => {
+                buffers.push((buffer, level));
+                true
+            }
+        }
+    }
+
+    /// Marks a buffer as read, returning whether it had any activity.
+    pub fn clear(&self, buffer: &ActivityBuffer) -> bool {
+        let mut buffers = self.0.lock().unwrap();
+        let len = buffers.len();
+        buffers.retain(|(b, _)| b != buffer);
+        len != buffers.len()
+    }
+
+    #[cfg(test)]
+    pub fn level(&self, buffer: &ActivityBuffer) -> Option<ActivityLevel> {
+        let buffers = self.0.lock().unwrap();
+        buffers.iter().find(|(b, _)| b == buffer).map(|(_, l)| *l)
+    }
+
+    pub fn list(&self) -> Vec<(ActivityBuffer, ActivityLevel)> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The buffer to go to next: the one with the highest level, of those the one waiting the
+    /// longest.
+    pub fn next(&self) -> Option<ActivityBuffer> {
+        let buffers = self.0.lock().unwrap();
+        let level = buffers.iter().map(|(_, l)| *l).max()?;
+        buffers
+            .iter()
+            .find(|(_, l)| *l == level)
+            .map(|(b, _)| b.clone())
+    }
+
+    /// The status area text, eg. `act: inbox!, hud`, with highlighted buffers marked by a `!`.
+    pub fn indicator(&self) -> Option<String> {
+        let buffers = self.0.lock().unwrap();
+        if buffers.is_empty() {
+            return None;
+        }
+        let names = buffers
+            .iter()
+            .map(|(buffer, level)| match level {
+                ActivityLevel::Output => buffer.to_string(),
+                ActivityLevel::Highlight => format!("{buffer}!"),
+            })
+            .collect::<Vec<String>>();
+        Some(format!("act: {}", names.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod test_activity {
+    use super::*;
+
+    #[test]
+    fn test_record_and_clear() {
+        let activity = Activity::default();
+        assert!(activity.record(ActivityBuffer::Scrollback, ActivityLevel::Output));
+        assert!(!activity.record(ActivityBuffer::Scrollback, ActivityLevel::Output));
+        assert!(activity.record(ActivityBuffer::Scrollback, ActivityLevel::Highlight));
+        // The level isn't lowered until the buffer is read
+        assert!(!activity.record(ActivityBuffer::Scrollback, ActivityLevel::Output));
+        assert_eq!(
+            activity.level(&ActivityBuffer::Scrollback),
+            Some(ActivityLevel::Highlight)
+        );
+        assert!(activity.clear(&ActivityBuffer::Scrollback));
+        assert!(!activity.clear(&ActivityBuffer::Scrollback));
+        assert_eq!(activity.level(&ActivityBuffer::Scrollback), None);
+    }
+
+    #[test]
+    fn test_next() {
+        let activity = Activity::default();
+        assert_eq!(activity.next(), None);
+        activity.record("hud".into(), ActivityLevel::Output);
+        activity.record(ActivityBuffer::Scrollback, ActivityLevel::Output);
+        assert_eq!(activity.next(), Some(ActivityBuffer::Region("hud".into())));
+        activity.record(ActivityBuffer::Scrollback, ActivityLevel::Highlight);
+        activity.record(ActivityBuffer::Inbox, ActivityLevel::Highlight);
+        assert_eq!(activity.next(), Some(ActivityBuffer::Scrollback));
+        activity.clear(&ActivityBuffer::Scrollback);
+        assert_eq!(activity.next(), Some(ActivityBuffer::Inbox));
+    }
+
+    #[test]
+    fn test_indicator() {
+        let activity = Activity::default();
+        assert_eq!(activity.indicator(), None);
+        activity.record(ActivityBuffer::Inbox, ActivityLevel::Highlight);
+        activity.record("hud".into(), ActivityLevel::Output);
+        assert_eq!(activity.indicator(), Some("act: inbox!, hud".to_string()));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(ActivityBuffer::from("inbox"), ActivityBuffer::Inbox);
+        assert_eq!(
+            ActivityBuffer::from("scrollback"),
+            ActivityBuffer::Scrollback
+        );
+        assert_eq!(
+            ActivityBuffer::from("map"),
+            ActivityBuffer::Region("map".to_string())
+        );
+        assert_eq!(ActivityBuffer::from("map").to_string(), "map");
+        assert_eq!(
+            ActivityLevel::parse("highlight"),
+            Some(ActivityLevel::Highlight)
+        );
+        assert_eq!(ActivityLevel::parse("loud"), None);
+    }
+}
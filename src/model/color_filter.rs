@@ -0,0 +1,391 @@
+use std::{cell::Cell, collections::BTreeMap, fmt, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{palette::map_colors, Color};
+use crate::io::SaveData;
+
+/// How much lighter than this foreground colors are made by the contrast filter, and how much
+/// darker than this background colors, as relative luminance.
+const MIN_FOREGROUND_LUMINANCE: f32 = 0.45;
+const MAX_BACKGROUND_LUMINANCE: f32 = 0.15;
+
+/// The contrast ratio server colors are kept at by default when a background is set.
+pub const DEFAULT_MIN_CONTRAST: f32 = 3.0;
+
+/// Colors as seen with a color vision deficiency, by linear RGB matrix (Machado et al. 2009).
+const PROTANOPIA: [[f32; 3]; 3] = [
+    [0.152_286, 1.052_583, -0.204_868],
+    [0.114_503, 0.786_281, 0.099_216],
+    [-0.003_882, -0.048_116, 1.051_998],
+];
+const DEUTERANOPIA: [[f32; 3]; 3] = [
+    [0.367_322, 0.860_646, -0.227_968],
+    [0.280_085, 0.672_501, 0.047_413],
+    [-0.011_820, 0.042_940, 0.968_881],
+];
+const TRITANOPIA: [[f32; 3]; 3] = [
+    [1.255_528, -0.076_749, -0.178_779],
+    [-0.078_411, 0.930_809, 0.147_602],
+    [0.004_733, 0.691_367, 0.303_900],
+];
+
+/// A transform of the output colors that keeps color coded text distinguishable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ColorFilter {
+    #[default]
+    Off,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    /// Lightens dark text and darkens light backgrounds.
+    Contrast,
+}
+
+impl fmt::Display for ColorFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Off => "off",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Protanopia => "protanopia",
+            Self::Tritanopia => "tritanopia",
+            Self::Contrast => "contrast",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ColorFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "deuteranopia" => Ok(Self::Deuteranopia),
+            "protanopia" => Ok(Self::Protanopia),
+            "tritanopia" => Ok(Self::Tritanopia),
+            "contrast" => Ok(Self::Contrast),
+            _ => Err(format!(
+                "Invalid color filter: {s}, use off, deuteranopia, protanopia, tritanopia or contrast"
+            )),
+        }
+    }
+}
+
+fn to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(channel: f32) -> u8 {
+    let c = channel.clamp(0.0, 1.0);
+    let c = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+fn linear(color: Color) -> [f32; 3] {
+    let (r, g, b) = color.rgb();
+    [to_linear(r), to_linear(g), to_linear(b)]
+}
+
+fn from_linear_rgb(rgb: [f32; 3]) -> Color {
+    Color::Rgb(
+        from_linear(rgb[0]),
+        from_linear(rgb[1]),
+        from_linear(rgb[2]),
+    )
+}
+
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The WCAG contrast ratio of two relative luminances, from 1 to 21.
+fn luminance_contrast(a: f32, b: f32) -> f32 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// The WCAG contrast ratio of two colors, from 1 for the same color to 21 for black and white.
+pub fn contrast(a: Color, b: Color) -> f32 {
+    luminance_contrast(luminance(linear(a)), luminance(linear(b)))
+}
+
+/// Moves the difference a color vision deficiency hides into the channels that are still seen,
+/// the daltonize method.
+fn daltonize(rgb: [f32; 3], deficiency: &[[f32; 3]; 3]) -> [f32; 3] {
+    let seen: Vec<f32> = deficiency
+        .iter()
+        .map(|row| row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2])
+        .collect();
+    let error = [rgb[0] - seen[0], rgb[1] - seen[1], rgb[2] - seen[2]];
+    [
+        rgb[0],
+        rgb[1] + 0.7 * error[0] + error[1],
+        rgb[2] + 0.7 * error[0] + error[2],
+    ]
+}
+
+/// Mixes a color with white or black until it has the given luminance.
+fn mix_to_luminance(rgb: [f32; 3], target: f32, towards: f32) -> [f32; 3] {
+    let current = luminance(rgb);
+    if (towards - current).abs() < f32::EPSILON {
+        return rgb;
+    }
+    let amount = (target - current) / (towards - current);
+    rgb.map(|c| c + (towards - c) * amount)
+}
+
+impl ColorFilter {
+    /// The color to show instead of `color`, none if the filter leaves it as it is.
+    pub fn transform(&self, color: Color, background: bool) -> Option<Color> {
+        let rgb = linear(color);
+        let filtered = match self {
+            Self::Off => return None,
+            Self::Deuteranopia => daltonize(rgb, &DEUTERANOPIA),
+            Self::Protanopia => daltonize(rgb, &PROTANOPIA),
+            Self::Tritanopia => daltonize(rgb, &TRITANOPIA),
+            Self::Contrast if background && luminance(rgb) > MAX_BACKGROUND_LUMINANCE => {
+                mix_to_luminance(rgb, MAX_BACKGROUND_LUMINANCE, 0.0)
+            }
+            Self::Contrast if !background && luminance(rgb) < MIN_FOREGROUND_LUMINANCE => {
+                mix_to_luminance(rgb, MIN_FOREGROUND_LUMINANCE, 1.0)
+            }
+            Self::Contrast => return None,
+        };
+        let filtered = from_linear_rgb(filtered);
+        (filtered.rgb() != color.rgb()).then_some(filtered)
+    }
+
+    /// Rewrites the colors set by the SGR sequences in `line`.
+    pub fn apply(&self, line: &str) -> String {
+        if *self == Self::Off {
+            return line.to_string();
+        }
+        map_colors(line, |color, background| self.transform(color, background))
+    }
+}
+
+/// Keeps the colors servers send readable on the terminal background, like dark gray on black.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinContrast {
+    pub background: Color,
+    /// The contrast ratio foreground colors are brought up to.
+    pub ratio: f32,
+}
+
+impl MinContrast {
+    /// The color to show instead of a foreground color, none if it's readable as it is. Colors
+    /// are lightened on dark backgrounds and darkened on light ones.
+    pub fn readable(&self, color: Color) -> Option<Color> {
+        let background = luminance(linear(self.background));
+        let rgb = linear(color);
+        if luminance_contrast(luminance(rgb), background) >= self.ratio {
+            return None;
+        }
+        let adjusted = if luminance_contrast(1.0, background) >= luminance_contrast(0.0, background)
+        {
+            let target = (self.ratio * (background + 0.05) - 0.05).min(1.0);
+            mix_to_luminance(rgb, target, 1.0)
+        } else {
+            let target = ((background + 0.05) / self.ratio - 0.05).max(0.0);
+            mix_to_luminance(rgb, target, 0.0)
+        };
+        let adjusted = from_linear_rgb(adjusted);
+        (adjusted.rgb() != color.rgb()).then_some(adjusted)
+    }
+
+    /// Rewrites the foreground colors set by the SGR sequences in `line`. Lines that set a
+    /// background color of their own are left as they are.
+    pub fn apply(&self, line: &str) -> String {
+        let sets_background = Cell::new(false);
+        map_colors(line, |_, background| {
+            if background {
+                sets_background.set(true);
+            }
+            None
+        });
+        if sets_background.get() {
+            return line.to_string();
+        }
+        map_colors(line, |color, _| self.readable(color))
+    }
+}
+
+fn default_min_contrast() -> f32 {
+    DEFAULT_MIN_CONTRAST
+}
+
+/// The color filter used for output, and the ones used for some saved servers instead.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ColorFilters {
+    #[serde(default)]
+    pub filter: ColorFilter,
+    /// Filters by saved server name.
+    #[serde(default)]
+    pub servers: BTreeMap<String, ColorFilter>,
+    /// The background color of the terminal, server colors aren't adjusted without one.
+    #[serde(default)]
+    pub background: Option<Color>,
+    #[serde(default = "default_min_contrast")]
+    pub min_contrast: f32,
+}
+
+impl Default for ColorFilters {
+    fn default() -> Self {
+        Self {
+            filter: ColorFilter::default(),
+            servers: BTreeMap::new(),
+            background: None,
+            min_contrast: DEFAULT_MIN_CONTRAST,
+        }
+    }
+}
+
+impl ColorFilters {
+    /// The minimum contrast server colors are kept at, if a background is set.
+    pub fn min_contrast(&self) -> Option<MinContrast> {
+        self.background.map(|background| MinContrast {
+            background,
+            ratio: self.min_contrast,
+        })
+    }
+
+    /// The filter for a saved server, or for output in general without one.
+    pub fn get(&self, server: Option<&str>) -> ColorFilter {
+        server
+            .and_then(|server| self.servers.get(server))
+            .copied()
+            .unwrap_or(self.filter)
+    }
+}
+
+impl SaveData for ColorFilters {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("color_filters.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_color_filter {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!("tritanopia".parse(), Ok(ColorFilter::Tritanopia));
+        assert_eq!(ColorFilter::Contrast.to_string(), "contrast");
+        assert!("mono".parse::<ColorFilter>().is_err());
+    }
+
+    #[test]
+    fn test_daltonize() {
+        let filter = ColorFilter::Deuteranopia;
+        // Red and green are pulled apart, grays are left alone
+        let Some(Color::Rgb(_, _, red_blue)) = filter.transform(Color::Indexed(9), false) else {
+            panic!("Red wasn't changed");
+        };
+        let Some(Color::Rgb(_, _, green_blue)) = filter.transform(Color::Indexed(10), false) else {
+            panic!("Green wasn't changed");
+        };
+        assert!(red_blue.abs_diff(green_blue) > 100);
+        assert_eq!(filter.transform(Color::Indexed(8), false), None);
+        assert_eq!(filter.transform(Color::Indexed(244), true), None);
+        assert_eq!(ColorFilter::Off.transform(Color::Indexed(9), false), None);
+    }
+
+    #[test]
+    fn test_contrast() {
+        let filter = ColorFilter::Contrast;
+        let Some(Color::Rgb(r, g, b)) = filter.transform(Color::Indexed(4), false) else {
+            panic!("Dark blue text wasn't lightened");
+        };
+        assert!(b > 238 && r > 0 && g > 0);
+        assert_eq!(filter.transform(Color::Indexed(15), false), None);
+        assert_eq!(filter.transform(Color::Indexed(0), true), None);
+        assert!(filter.transform(Color::Indexed(7), true).is_some());
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(ColorFilter::Off.apply("\x1b[34mblue"), "\x1b[34mblue");
+        assert_eq!(
+            ColorFilter::Contrast.apply("\x1b[1;97;44mwhite on blue\x1b[0m"),
+            "\x1b[1;97;44mwhite on blue\x1b[0m"
+        );
+        assert!(ColorFilter::Contrast
+            .apply("\x1b[34mblue")
+            .starts_with("\x1b[38;2;"));
+    }
+
+    #[test]
+    fn test_server_filters() {
+        let mut filters = ColorFilters {
+            filter: ColorFilter::Protanopia,
+            ..Default::default()
+        };
+        filters.servers.insert("mush".to_string(), ColorFilter::Off);
+        assert_eq!(filters.get(Some("mush")), ColorFilter::Off);
+        assert_eq!(filters.get(Some("other")), ColorFilter::Protanopia);
+        assert_eq!(filters.get(None), ColorFilter::Protanopia);
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        assert!((contrast(Color::Indexed(0), Color::Indexed(15)) - 21.0).abs() < 0.01);
+        assert!((contrast(Color::Indexed(4), Color::Indexed(4)) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_min_contrast() {
+        let dark = MinContrast {
+            background: Color::Indexed(0),
+            ratio: DEFAULT_MIN_CONTRAST,
+        };
+        let gray = dark.readable(Color::Indexed(236)).unwrap();
+        assert!(contrast(gray, Color::Indexed(0)) >= 2.9);
+        assert!(matches!(gray, Color::Rgb(r, g, b) if r == g && g == b));
+        assert_eq!(dark.readable(Color::Indexed(15)), None);
+
+        let light = MinContrast {
+            background: Color::Indexed(15),
+            ratio: 4.5,
+        };
+        let yellow = light.readable(Color::Indexed(11)).unwrap();
+        assert!(contrast(yellow, Color::Indexed(15)) >= 4.4);
+        assert_eq!(light.readable(Color::Indexed(0)), None);
+    }
+
+    #[test]
+    fn test_min_contrast_apply() {
+        let min_contrast = MinContrast {
+            background: Color::Indexed(0),
+            ratio: DEFAULT_MIN_CONTRAST,
+        };
+        assert!(min_contrast
+            .apply("\x1b[38;5;236mdark\x1b[0m")
+            .starts_with("\x1b[38;2;"));
+        assert_eq!(
+            min_contrast.apply("\x1b[38;5;236;47mown background"),
+            "\x1b[38;5;236;47mown background"
+        );
+        assert_eq!(min_contrast.apply("plain"), "plain");
+    }
+
+    #[test]
+    fn test_min_contrast_setting() {
+        let filters: ColorFilters = ron::from_str("(filter: Off)").unwrap();
+        assert_eq!(filters.min_contrast, DEFAULT_MIN_CONTRAST);
+        assert_eq!(filters.min_contrast(), None);
+    }
+}
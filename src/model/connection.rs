@@ -1,8 +1,9 @@
+use super::InputMode;
 use crate::io::SaveData;
 
 use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
 use std::path::PathBuf;
 
@@ -14,6 +15,14 @@ pub struct Connection {
     pub tls: bool,
     #[serde(default)]
     pub verify_cert: bool,
+    /// Connect to the server when Blightmud starts.
+    #[serde(default)]
+    pub autoconnect: bool,
+    #[serde(default)]
+    pub input_mode: InputMode,
+    /// Replaces `/` as the prefix of client commands typed while connected to the server.
+    #[serde(default)]
+    pub command_prefix: Option<char>,
 }
 
 impl Connection {
@@ -23,6 +32,9 @@ impl Connection {
             port,
             tls,
             verify_cert,
+            autoconnect: false,
+            input_mode: InputMode::default(),
+            command_prefix: None,
         }
     }
 }
@@ -47,6 +59,172 @@ impl SaveData for Servers {
     fn is_pretty() -> bool {
         true
     }
+
+    fn is_secret() -> bool {
+        true
+    }
+}
+
+/// Metadata collected about a saved server while connecting to it.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct ServerInfo {
+    #[serde(default)]
+    pub last_connected: Option<i64>,
+    #[serde(default)]
+    pub mssp: BTreeMap<String, String>,
+    /// What the server negotiated right after the last connection.
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+}
+
+/// What a server negotiated shortly after connecting, to configure scripts by and to include
+/// in bug reports.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct Capabilities {
+    /// Names of the telnet options the server enabled, eg. "GMCP".
+    #[serde(default)]
+    pub protocols: BTreeSet<String>,
+    #[serde(default)]
+    pub utf8: bool,
+    #[serde(default)]
+    pub tls: bool,
+    /// How prompts are terminated, "GA" or "EOR", none if they aren't.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// The unix timestamp of when the capabilities were collected.
+    #[serde(default)]
+    pub time: i64,
+}
+
+impl Capabilities {
+    /// The name of a telnet option, or its number if it has none here.
+    pub fn option_name(option: u8) -> String {
+        match option {
+            0 => "BINARY",
+            3 => "SGA",
+            24 => "TTYPE",
+            25 => "EOR",
+            31 => "NAWS",
+            42 => "CHARSET",
+            46 => "START-TLS",
+            69 => "MSDP",
+            70 => "MSSP",
+            86 => "MCCP2",
+            91 => "MXP",
+            201 => "GMCP",
+            _ => return format!("option {option}"),
+        }
+        .to_string()
+    }
+
+    /// A one line description, eg. "Server supports: GMCP, MCCP2, MSSP, UTF-8; prompt style: GA".
+    pub fn summary(&self) -> String {
+        let mut supports: Vec<&str> = self.protocols.iter().map(|name| name.as_str()).collect();
+        if self.utf8 {
+            supports.push("UTF-8");
+        }
+        if self.tls {
+            supports.push("TLS");
+        }
+        let supports = if supports.is_empty() {
+            "nothing".to_string()
+        } else {
+            supports.join(", ")
+        };
+        let prompt = self.prompt.as_deref().unwrap_or("unterminated");
+        format!("Server supports: {supports}; prompt style: {prompt}")
+    }
+}
+
+pub type ServerInfos = HashMap<String, ServerInfo>;
+
+impl ServerInfo {
+    fn mssp_value(&self, key: &str) -> Option<&str> {
+        self.mssp
+            .get(key)
+            .map(|val| val.trim())
+            .filter(|val| !val.is_empty())
+    }
+
+    /// The name the server reports over MSSP.
+    pub fn mssp_name(&self) -> Option<&str> {
+        self.mssp_value("NAME")
+    }
+
+    pub fn players(&self) -> Option<u32> {
+        self.mssp_value("PLAYERS")?.parse().ok()
+    }
+
+    /// The unix timestamp of when the server was started.
+    pub fn uptime(&self) -> Option<i64> {
+        self.mssp_value("UPTIME")?.parse().ok()
+    }
+
+    pub fn codebase(&self) -> Option<&str> {
+        self.mssp_value("CODEBASE")
+    }
+
+    /// A one line description of the server built from its MSSP values, eg.
+    /// "Blight (Diku): 12 players, up 3d 4h". Returns `None` if no MSSP values are known.
+    pub fn summary(&self, now: i64) -> Option<String> {
+        if self.mssp.is_empty() {
+            return None;
+        }
+        let mut summary = self.mssp_name().unwrap_or("Unknown server").to_string();
+        if let Some(codebase) = self.codebase() {
+            summary.push_str(&format!(" ({codebase})"));
+        }
+        let mut details = vec![];
+        if let Some(players) = self.players() {
+            let plural = if players == 1 { "" } else { "s" };
+            details.push(format!("{players} player{plural}"));
+        }
+        if let Some(started) = self.uptime().filter(|started| *started <= now) {
+            let minutes = (now - started) / 60;
+            let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+            details.push(if days > 0 {
+                format!("up {days}d {hours}h")
+            } else {
+                format!("up {hours}h {minutes}m")
+            });
+        }
+        if !details.is_empty() {
+            summary.push_str(&format!(": {}", details.join(", ")));
+        }
+        Some(summary)
+    }
+}
+
+impl SaveData for ServerInfos {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("server_info.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+/// Returns the names of the saved servers to connect to at startup, sorted by name.
+pub fn autoconnect_servers(servers: &Servers) -> Vec<String> {
+    let mut names: Vec<String> = servers
+        .iter()
+        .filter(|(_, conn)| conn.autoconnect)
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Returns the names of all saved servers pointing at the given host and port.
+pub fn find_servers(servers: &Servers, host: &str, port: u16) -> Vec<String> {
+    let mut names: Vec<String> = servers
+        .iter()
+        .filter(|(_, conn)| conn.host == host && conn.port == port)
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    names
 }
 
 #[cfg(test)]
@@ -67,4 +245,88 @@ mod test_connection {
             "Host: host.com, Port: 4000 TLS: false Verify: false".to_string()
         );
     }
+
+    #[test]
+    fn test_find_servers() {
+        let mut servers = Servers::new();
+        servers.insert(
+            "b".to_string(),
+            Connection::new("mud.com", 4000, false, false),
+        );
+        servers.insert(
+            "a".to_string(),
+            Connection::new("mud.com", 4000, true, true),
+        );
+        servers.insert(
+            "c".to_string(),
+            Connection::new("mud.com", 5000, false, false),
+        );
+        assert_eq!(find_servers(&servers, "mud.com", 4000), vec!["a", "b"]);
+        assert_eq!(find_servers(&servers, "mud.com", 5000), vec!["c"]);
+        assert!(find_servers(&servers, "other.com", 4000).is_empty());
+    }
+
+    #[test]
+    fn test_autoconnect_servers() {
+        let mut servers = Servers::new();
+        assert!(autoconnect_servers(&servers).is_empty());
+        for name in ["c", "a", "b"] {
+            let mut conn = Connection::new("mud.com", 4000, false, false);
+            conn.autoconnect = name != "b";
+            servers.insert(name.to_string(), conn);
+        }
+        assert_eq!(autoconnect_servers(&servers), vec!["a", "c"]);
+
+        let conn: Connection = ron::from_str(r#"(host: "mud.com", port: 4000)"#).unwrap();
+        assert!(!conn.autoconnect);
+    }
+
+    #[test]
+    fn test_capabilities_summary() {
+        let mut capabilities = Capabilities::default();
+        assert_eq!(
+            capabilities.summary(),
+            "Server supports: nothing; prompt style: unterminated"
+        );
+        for option in [201, 70, 86] {
+            capabilities
+                .protocols
+                .insert(Capabilities::option_name(option));
+        }
+        capabilities.utf8 = true;
+        capabilities.prompt = Some("GA".to_string());
+        assert_eq!(
+            capabilities.summary(),
+            "Server supports: GMCP, MCCP2, MSSP, UTF-8; prompt style: GA"
+        );
+        assert_eq!(Capabilities::option_name(200), "option 200");
+
+        let info: ServerInfo = ron::from_str("(last_connected: Some(1))").unwrap();
+        assert_eq!(info.capabilities, None);
+    }
+
+    #[test]
+    fn test_server_info_summary() {
+        let mut info = ServerInfo::default();
+        assert_eq!(info.summary(1000), None);
+        info.mssp.insert("NAME".to_string(), "Blight".to_string());
+        assert_eq!(info.summary(1000), Some("Blight".to_string()));
+        info.mssp.insert("CODEBASE".to_string(), "Diku".to_string());
+        info.mssp.insert("PLAYERS".to_string(), "12".to_string());
+        info.mssp.insert("UPTIME".to_string(), "1000".to_string());
+        assert_eq!(info.players(), Some(12));
+        assert_eq!(info.uptime(), Some(1000));
+        let now = 1000 + 3 * 86400 + 4 * 3600 + 120;
+        assert_eq!(
+            info.summary(now),
+            Some("Blight (Diku): 12 players, up 3d 4h".to_string())
+        );
+        assert_eq!(
+            info.summary(1000 + 3720),
+            Some("Blight (Diku): 12 players, up 1h 2m".to_string())
+        );
+        info.mssp.insert("PLAYERS".to_string(), "many".to_string());
+        info.mssp.remove("NAME");
+        assert_eq!(info.summary(0), Some("Unknown server (Diku)".to_string()));
+    }
 }
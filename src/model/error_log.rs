@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+const MAX_ERRORS: usize = 100;
+
+/// Prefix of the chunk names used for the scripts bundled with Blightmud.
+const INTERNAL_CHUNK: &str = "../../resources/lua/";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub message: String,
+    pub trace: Vec<String>,
+    pub source: Option<String>,
+    pub count: u32,
+    pub warning: bool,
+}
+
+/// Collects script errors so they can be reviewed after they have scrolled by. Identical errors
+/// are only stored once along with the number of times they occurred.
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    errors: VecDeque<ScriptError>,
+}
+
+impl ErrorLog {
+    /// Records an error and returns the stored entry.
+    pub fn push(&mut self, error: &str) -> &ScriptError {
+        self.record(error, false)
+    }
+
+    /// Records a warning, eg. use of a deprecated function, and returns the stored entry.
+    pub fn push_warning(&mut self, warning: &str) -> &ScriptError {
+        self.record(warning, true)
+    }
+
+    fn record(&mut self, error: &str, warning: bool) -> &ScriptError {
+        let mut lines = error.lines();
+        let message = lines.next().unwrap_or_default().to_string();
+        if let Some(index) = self
+            .errors
+            .iter()
+            .position(|e| e.message == message && e.warning == warning)
+        {
+            let mut entry = self.errors.remove(index).unwrap();
+            entry.count += 1;
+            self.errors.push_back(entry);
+        } else {
+            if self.errors.len() >= MAX_ERRORS {
+                self.errors.pop_front();
+            }
+            self.errors.push_back(ScriptError {
+                source: error_source(error),
+                trace: lines.map(String::from).collect(),
+                message,
+                count: 1,
+                warning,
+            });
+        }
+        self.errors.back().unwrap()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ScriptError> {
+        self.errors.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScriptError> {
+        self.errors.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.errors.clear();
+    }
+}
+
+/// Finds the first script location in an error message and its stack trace that isn't part of
+/// Blightmud itself, eg. `plugin foo (main.lua:12)` or `/home/user/script.lua:3`.
+pub fn error_source(error: &str) -> Option<String> {
+    error
+        .lines()
+        .filter_map(location)
+        .find(|(path, _)| !path.starts_with(INTERNAL_CHUNK) && *path != "[C]")
+        .map(|(path, line)| match path.split_once("/plugins/") {
+            Some((_, plugin_path)) => match plugin_path.split_once('/') {
+                Some((plugin, file)) => format!("plugin {plugin} ({file}:{line})"),
+                None => format!("{path}:{line}"),
+            },
+            None => format!("{path}:{line}"),
+        })
+}
+
+fn location(line: &str) -> Option<(&str, &str)> {
+    let line = ["runtime error: ", "syntax error: "]
+        .iter()
+        .fold(line.trim_start(), |line, prefix| {
+            line.strip_prefix(prefix).unwrap_or(line)
+        });
+    let (path, rest) = if let Some(rest) = line.strip_prefix("[string \"") {
+        let (path, rest) = rest.split_once("\"]:")?;
+        (path, rest)
+    } else {
+        line.split_once(':')?
+    };
+    let (line_nr, _) = rest.split_once(':')?;
+    if !line_nr.is_empty() && line_nr.chars().all(|c| c.is_ascii_digit()) {
+        Some((path, line_nr))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_error_log {
+    use super::*;
+
+    const ERROR: &str = "runtime error: ../../resources/lua/trigger.lua:80: bad argument
+stack traceback:
+\t[C]: in ?
+\t../../resources/lua/trigger.lua:80: in function <../../resources/lua/trigger.lua:76>
+\t/home/user/.config/blightmud/script.lua:12: in function 'cb'";
+
+    #[test]
+    fn test_error_source() {
+        assert_eq!(
+            error_source(ERROR),
+            Some("/home/user/.config/blightmud/script.lua:12".to_string())
+        );
+        assert_eq!(
+            error_source("runtime error: /home/user/script.lua:4: oops"),
+            Some("/home/user/script.lua:4".to_string())
+        );
+        assert_eq!(
+            error_source("[string \"/data/plugins/foo/main.lua\"]:3: oops"),
+            Some("plugin foo (main.lua:3)".to_string())
+        );
+        assert_eq!(
+            error_source("stack traceback:\n\t[C]: in function 'error'\n\t/data/plugins/bar/lib/util.lua:7: in main chunk"),
+            Some("plugin bar (lib/util.lua:7)".to_string())
+        );
+        assert_eq!(
+            error_source("../../resources/lua/alias.lua:3: failure"),
+            None
+        );
+        assert_eq!(error_source("Just a message"), None);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut log = ErrorLog::default();
+        log.push(ERROR);
+        log.push("other error");
+        let entry = log.push(ERROR);
+        assert_eq!(entry.count, 2);
+        assert_eq!(entry.trace.len(), 4);
+        assert_eq!(log.iter().count(), 2);
+        assert_eq!(log.get(0).unwrap().message, "other error");
+        let entry = log.push_warning("other error");
+        assert!(entry.warning);
+        assert_eq!(entry.count, 1);
+        log.clear();
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut log = ErrorLog::default();
+        for i in 0..MAX_ERRORS + 5 {
+            log.push(&format!("error {i}"));
+        }
+        assert_eq!(log.iter().count(), MAX_ERRORS);
+        assert_eq!(log.get(0).unwrap().message, "error 5");
+    }
+}
@@ -0,0 +1,261 @@
+use std::{error, fmt};
+
+#[derive(Debug, PartialEq)]
+pub struct ExpressionError(String);
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid expression: {}", self.0)
+    }
+}
+
+impl error::Error for ExpressionError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut num = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' {
+                        num.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = num
+                    .parse::<f64>()
+                    .map_err(|_| ExpressionError(format!("bad number '{num}'")))?;
+                tokens.push(Token::Number(value));
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                chars.next();
+            }
+            _ => return Err(ExpressionError(format!("unexpected '{c}'"))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// How deeply parentheses, signs and powers may nest before an expression is refused, keeping
+/// the parser's recursion from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+/// A recursive descent parser evaluating arithmetic expressions.
+///
+/// ```text
+/// expr   := term (('+' | '-') term)*
+/// term   := unary (('*' | '/' | '%') unary)*
+/// unary  := '-' unary | power
+/// power  := atom ('^' unary)?
+/// atom   := number | ident | '(' expr ')'
+/// ```
+struct Parser<'a, F: Fn(&str) -> Option<f64>> {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    lookup: &'a F,
+}
+
+impl<'a, F: Fn(&str) -> Option<f64>> Parser<'a, F> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expr(&mut self) -> Result<f64, ExpressionError> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.next();
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, ExpressionError> {
+        let mut value = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek().cloned() {
+            self.next();
+            let rhs = self.unary()?;
+            if op != '*' && rhs == 0.0 {
+                return Err(ExpressionError("division by zero".to_string()));
+            }
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, ExpressionError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ExpressionError("nested too deeply".to_string()));
+        }
+        self.depth += 1;
+        let value = if let Some(Token::Op('-')) = self.peek() {
+            self.next();
+            self.unary().map(|value| -value)
+        } else {
+            self.power()
+        };
+        self.depth -= 1;
+        value
+    }
+
+    fn power(&mut self) -> Result<f64, ExpressionError> {
+        let base = self.atom()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.next();
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, ExpressionError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Ident(name)) => (self.lookup)(&name)
+                .ok_or_else(|| ExpressionError(format!("unknown variable '{name}'"))),
+            Some(Token::LParen) => {
+                let value = self.expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err(ExpressionError("missing ')'".to_string())),
+                }
+            }
+            Some(token) => Err(ExpressionError(format!("unexpected {token:?}"))),
+            None => Err(ExpressionError("unexpected end".to_string())),
+        }
+    }
+}
+
+/// Evaluates an arithmetic expression. Identifiers are resolved through `lookup`.
+pub fn evaluate<F: Fn(&str) -> Option<f64>>(
+    expr: &str,
+    lookup: &F,
+) -> Result<f64, ExpressionError> {
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+        depth: 0,
+        lookup,
+    };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(ExpressionError(format!("unexpected {token:?}")));
+    }
+    Ok(value)
+}
+
+/// Formats an evaluated value, dropping the decimals of whole numbers.
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
+
+#[cfg(test)]
+mod test_expression {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<f64, ExpressionError> {
+        evaluate(expr, &|name| match name {
+            "gold" => Some(25.0),
+            "hp" => Some(80.0),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3.0));
+        assert_eq!(eval("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(eval("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(eval("7 % 4"), Ok(3.0));
+        assert_eq!(eval("1.5 * 2"), Ok(3.0));
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(eval("gold / 2"), Ok(12.5));
+        assert_eq!(eval("hp * 100 / 160"), Ok(50.0));
+        assert_eq!(
+            eval("mana / 2"),
+            Err(ExpressionError("unknown variable 'mana'".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("1 2").is_err());
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("os.exit()").is_err());
+        let nested = format!("{}1{}", "(".repeat(10000), ")".repeat(10000));
+        assert_eq!(
+            eval(&nested),
+            Err(ExpressionError("nested too deeply".to_string()))
+        );
+        assert_eq!(eval(&"-".repeat(10000)), eval(&nested));
+        assert_eq!(
+            eval(&format!("{}1{}", "(".repeat(20), ")".repeat(20))),
+            Ok(1.0)
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(12.0), "12");
+        assert_eq!(format_value(12.5), "12.5");
+        assert_eq!(format_value(-3.0), "-3");
+    }
+}
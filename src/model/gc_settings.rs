@@ -0,0 +1,106 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// How the Lua garbage collector finds garbage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GcMode {
+    /// Collects in small steps interleaved with the scripts, tuned by the pause and step
+    /// multiplier.
+    #[default]
+    Incremental,
+    /// Collects young objects often and everything rarely, using Lua's own parameters.
+    Generational,
+}
+
+impl fmt::Display for GcMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incremental => write!(f, "incremental"),
+            Self::Generational => write!(f, "generational"),
+        }
+    }
+}
+
+impl FromStr for GcMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "incremental" => Ok(Self::Incremental),
+            "generational" => Ok(Self::Generational),
+            _ => Err(format!(
+                "Invalid garbage collector mode: {s}, use incremental or generational"
+            )),
+        }
+    }
+}
+
+/// How the Lua garbage collector runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcSettings {
+    pub mode: GcMode,
+    /// How far, in percent of the memory in use after a collection, memory grows before the
+    /// next collection starts.
+    pub pause: u32,
+    /// How much work each step does, in percent of the memory allocated since the last step.
+    pub step_multiplier: u32,
+    /// Kilobytes of collection work done on ticks where nothing else happened, none turns it
+    /// off. Only in incremental mode.
+    pub idle_step: u32,
+}
+
+impl Default for GcSettings {
+    fn default() -> Self {
+        Self {
+            mode: GcMode::default(),
+            pause: 200,
+            step_multiplier: 100,
+            idle_step: 64,
+        }
+    }
+}
+
+impl GcSettings {
+    /// The kilobytes to collect on an idle tick, if any.
+    pub fn idle_step(&self) -> Option<u32> {
+        match self.mode {
+            GcMode::Incremental if self.idle_step > 0 => Some(self.idle_step),
+            _ => None,
+        }
+    }
+}
+
+impl SaveData for GcSettings {
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("gc.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn on_load(&mut self) {
+        self.pause = self.pause.max(100);
+        self.step_multiplier = self.step_multiplier.max(100);
+    }
+}
+
+#[cfg(test)]
+mod gc_settings_test {
+    use super::*;
+
+    #[test]
+    fn test_idle_step() {
+        let mut settings = GcSettings::default();
+        assert_eq!(settings.idle_step(), Some(64));
+        settings.idle_step = 0;
+        assert_eq!(settings.idle_step(), None);
+        settings.idle_step = 16;
+        settings.mode = "generational".parse().unwrap();
+        assert_eq!(settings.idle_step(), None);
+        assert_eq!(settings.mode.to_string(), "generational");
+        assert!("manual".parse::<GcMode>().is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Connection, Line};
+
+/// How typed input is treated for a saved server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum InputMode {
+    #[default]
+    Standard,
+    /// For MUSHes and MUCKs, where poses and code start with characters the client would
+    /// otherwise treat as commands.
+    Mush,
+}
+
+impl fmt::Display for InputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard => write!(f, "standard"),
+            Self::Mush => write!(f, "mush"),
+        }
+    }
+}
+
+impl FromStr for InputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "mush" => Ok(Self::Mush),
+            _ => Err(format!("Invalid input mode: {s}, use standard or mush")),
+        }
+    }
+}
+
+/// The input mode and client command prefix of the server connected to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InputRules {
+    pub mode: InputMode,
+    /// Typed lines starting with this are client commands instead of lines starting with `/`.
+    pub prefix: Option<char>,
+}
+
+impl From<&Connection> for InputRules {
+    fn from(connection: &Connection) -> Self {
+        Self {
+            mode: connection.input_mode,
+            prefix: connection.command_prefix,
+        }
+    }
+}
+
+impl InputRules {
+    /// Rewrites a typed line. Lines that should reach the server exactly as typed are flagged
+    /// `literal`, they aren't split on the command separator or matched against aliases.
+    pub fn prepare(&self, line: &mut Line) {
+        if self.mode == InputMode::Standard && self.prefix.is_none() {
+            return;
+        }
+        let prefix = self.prefix.unwrap_or('/');
+        let content = line.line().to_string();
+        if let Some(rest) = content.strip_prefix(prefix) {
+            if rest.starts_with(prefix) {
+                // A doubled prefix sends it once, eg. `//` sends `/`
+                line.set_content(rest);
+                line.flags.literal = true;
+            } else if prefix != '/' {
+                line.set_content(&format!("/{rest}"));
+            }
+        } else if content.starts_with('/')
+            || (self.mode == InputMode::Mush && content.starts_with(char::is_whitespace))
+        {
+            line.flags.literal = true;
+        }
+    }
+
+    /// Pasted blank lines are sent in the mush mode, eg. to separate paragraphs.
+    pub fn keep_blank_lines(&self) -> bool {
+        self.mode == InputMode::Mush
+    }
+}
+
+#[cfg(test)]
+mod test_input_rules {
+    use super::*;
+
+    fn prepare(rules: &InputRules, input: &str) -> (String, bool) {
+        let mut line = Line::from(input);
+        rules.prepare(&mut line);
+        (line.line().to_string(), line.flags.literal)
+    }
+
+    #[test]
+    fn test_standard() {
+        let rules = InputRules::default();
+        assert_eq!(prepare(&rules, "/connect"), ("/connect".to_string(), false));
+        assert_eq!(prepare(&rules, "//look"), ("//look".to_string(), false));
+        assert_eq!(prepare(&rules, "  :waves"), ("  :waves".to_string(), false));
+    }
+
+    #[test]
+    fn test_mush() {
+        let rules = InputRules {
+            mode: InputMode::Mush,
+            prefix: None,
+        };
+        assert_eq!(prepare(&rules, "/connect"), ("/connect".to_string(), false));
+        assert_eq!(prepare(&rules, "//look"), ("/look".to_string(), true));
+        assert_eq!(prepare(&rules, "  :waves"), ("  :waves".to_string(), true));
+        assert_eq!(prepare(&rules, ":waves"), (":waves".to_string(), false));
+    }
+
+    #[test]
+    fn test_prefix() {
+        let rules = InputRules {
+            mode: InputMode::Standard,
+            prefix: Some('.'),
+        };
+        assert_eq!(prepare(&rules, ".connect"), ("/connect".to_string(), false));
+        assert_eq!(prepare(&rules, "..look"), (".look".to_string(), true));
+        assert_eq!(
+            prepare(&rules, "/me waves"),
+            ("/me waves".to_string(), true)
+        );
+        assert_eq!(prepare(&rules, "look"), ("look".to_string(), false));
+    }
+
+    #[test]
+    fn test_input_mode_names() {
+        assert_eq!("mush".parse(), Ok(InputMode::Mush));
+        assert_eq!(InputMode::Standard.to_string(), "standard");
+        assert!("muck".parse::<InputMode>().is_err());
+    }
+}
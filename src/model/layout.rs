@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+pub const REGION_HEIGHT_MAX: u16 = 10;
+/// The fewest rows regions leave for output.
+pub const OUTPUT_HEIGHT_MIN: u16 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionPosition {
+    Top,
+    Bottom,
+}
+
+impl From<&str> for RegionPosition {
+    fn from(position: &str) -> Self {
+        match position {
+            "top" => RegionPosition::Top,
+            _ => RegionPosition::Bottom,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub position: RegionPosition,
+    pub height: u16,
+}
+
+impl Region {
+    pub fn new(name: &str, position: RegionPosition, height: u16) -> Self {
+        Self {
+            name: name.to_string(),
+            position,
+            height: height.min(REGION_HEIGHT_MAX),
+        }
+    }
+}
+
+/// Keeps track of the script declared screen regions and their content.
+/// Regions are stacked in the order they were declared, top regions below the
+/// top bar and bottom regions above the status area.
+#[derive(Debug, Default, Clone)]
+pub struct Layout {
+    regions: Vec<Region>,
+    content: HashMap<String, Vec<String>>,
+}
+
+impl Layout {
+    pub fn set_region(&mut self, region: Region) {
+        if let Some(existing) = self.regions.iter_mut().find(|r| r.name == region.name) {
+            *existing = region;
+        } else {
+            self.regions.push(region);
+        }
+    }
+
+    pub fn remove_region(&mut self, name: &str) -> bool {
+        self.content.remove(name);
+        let len = self.regions.len();
+        self.regions.retain(|r| r.name != name);
+        len != self.regions.len()
+    }
+
+    pub fn set_lines(&mut self, name: &str, lines: Vec<String>) {
+        self.content.insert(name.to_string(), lines);
+    }
+
+    pub fn lines(&self, name: &str) -> &[String] {
+        self.content
+            .get(name)
+            .map(|l| l.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+
+    pub fn regions(&self, position: RegionPosition) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(move |r| r.position == position)
+    }
+
+    pub fn height(&self, position: RegionPosition) -> u16 {
+        self.regions(position).map(|r| r.height).sum()
+    }
+
+    /// Returns the heights of the top and bottom regions when `rows` rows are shared by them and
+    /// the output. Regions are cut off, bottom ones first, to leave the output at least
+    /// [OUTPUT_HEIGHT_MIN] rows.
+    pub fn fit(&self, rows: u16) -> (u16, u16) {
+        let space = rows.saturating_sub(OUTPUT_HEIGHT_MIN);
+        let top = self.height(RegionPosition::Top).min(space);
+        let bottom = self.height(RegionPosition::Bottom).min(space - top);
+        (top, bottom)
+    }
+
+    /// Checks if any row of the named region is drawn when `rows` rows are shared by the regions
+    /// and the output, rather than all of it being cut off.
+    pub fn shown(&self, name: &str, rows: u16) -> bool {
+        let Some(region) = self.get(name) else {
+            return false;
+        };
+        let (top, bottom) = self.fit(rows);
+        let space = match region.position {
+            RegionPosition::Top => top,
+            RegionPosition::Bottom => bottom,
+        };
+        region.height > 0 && self.offset(name, 0).is_some_and(|row| row < space)
+    }
+
+    /// Returns the first screen row of the named region given the first row
+    /// available for regions at that position.
+    pub fn offset(&self, name: &str, start_line: u16) -> Option<u16> {
+        let region = self.get(name)?;
+        let mut line = start_line;
+        for r in self.regions(region.position) {
+            if r.name == name {
+                return Some(line);
+            }
+            line += r.height;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test_layout {
+    use super::*;
+
+    #[test]
+    fn test_region_heights() {
+        let mut layout = Layout::default();
+        layout.set_region(Region::new("hud", RegionPosition::Top, 2));
+        layout.set_region(Region::new("map", RegionPosition::Top, 5));
+        layout.set_region(Region::new("bar", RegionPosition::Bottom, 1));
+        assert_eq!(layout.height(RegionPosition::Top), 7);
+        assert_eq!(layout.height(RegionPosition::Bottom), 1);
+
+        layout.set_region(Region::new("map", RegionPosition::Top, 3));
+        assert_eq!(layout.height(RegionPosition::Top), 5);
+        assert!(layout.remove_region("hud"));
+        assert!(!layout.remove_region("hud"));
+        assert_eq!(layout.height(RegionPosition::Top), 3);
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut layout = Layout::default();
+        layout.set_region(Region::new("hud", RegionPosition::Top, 10));
+        layout.set_region(Region::new("bar", RegionPosition::Bottom, 10));
+        assert_eq!(layout.fit(40), (10, 10));
+        assert_eq!(layout.fit(16), (10, 3));
+        assert_eq!(layout.fit(8), (5, 0));
+        assert_eq!(layout.fit(2), (0, 0));
+    }
+
+    #[test]
+    fn test_shown() {
+        let mut layout = Layout::default();
+        layout.set_region(Region::new("hud", RegionPosition::Top, 2));
+        layout.set_region(Region::new("bar", RegionPosition::Bottom, 2));
+        layout.set_region(Region::new("map", RegionPosition::Bottom, 5));
+        assert!(layout.shown("hud", 40));
+        assert!(layout.shown("map", 40));
+        // Bottom regions are cut off first
+        assert!(layout.shown("bar", 6));
+        assert!(!layout.shown("map", 6));
+        assert!(!layout.shown("bar", 5));
+        assert!(!layout.shown("gone", 40));
+    }
+
+    #[test]
+    fn test_region_offset() {
+        let mut layout = Layout::default();
+        layout.set_region(Region::new("hud", RegionPosition::Top, 2));
+        layout.set_region(Region::new("bar", RegionPosition::Bottom, 1));
+        layout.set_region(Region::new("map", RegionPosition::Top, 5));
+        assert_eq!(layout.offset("hud", 2), Some(2));
+        assert_eq!(layout.offset("map", 2), Some(4));
+        assert_eq!(layout.offset("bar", 20), Some(20));
+        assert_eq!(layout.offset("nothing", 2), None);
+    }
+
+    #[test]
+    fn test_height_clamp() {
+        let region = Region::new("huge", RegionPosition::Bottom, 100);
+        assert_eq!(region.height, REGION_HEIGHT_MAX);
+        assert_eq!(RegionPosition::from("top"), RegionPosition::Top);
+        assert_eq!(RegionPosition::from("bottom"), RegionPosition::Bottom);
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut layout = Layout::default();
+        layout.set_region(Region::new("hud", RegionPosition::Top, 2));
+        assert!(layout.lines("hud").is_empty());
+        layout.set_lines("hud", vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(layout.lines("hud"), ["a", "b"]);
+        layout.remove_region("hud");
+        assert!(layout.lines("hud").is_empty());
+    }
+}
@@ -1,11 +1,21 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
 use log::error;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::sync::Arc;
 use strip_ansi_escapes::strip as strip_ansi;
 
+use super::Origin;
+
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct Flags {
     pub gag: bool,
     pub skip_log: bool,
+    pub skip_history: bool,
+    /// Replaces the output printed with the same [THROTTLE_TAG], if it's still among the recent
+    /// lines.
     pub replace_last: bool,
     pub prompt: bool,
     pub bypass_script: bool,
@@ -14,16 +24,36 @@ pub struct Flags {
     pub tts_interrupt: bool,
     pub separate_receives: bool,
     pub source: Option<String>,
+    /// Sent by a script while input was handled, ie. by an alias.
+    pub from_alias: bool,
+    /// Typed to be sent as is, without aliases or command splitting.
+    pub literal: bool,
+    /// Where a line of output comes from.
+    pub origin: Origin,
+    pub tags: BTreeMap<String, String>,
 }
 
+/// A line of text. The text is shared, so the clones made as a line passes from the connection
+/// through the scripts to the screen and the log don't copy it.
 #[derive(Debug, Clone)]
 pub struct Line {
-    content: String,
-    clean_content: String,
+    content: Arc<str>,
+    clean_content: Arc<str>,
     clean_utf8: bool,
+    timestamp: DateTime<Local>,
     pub flags: Flags,
 }
 
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%H:%M:%S";
+
+/// The tag naming the key of output printed with `blight.output_throttled`.
+pub const THROTTLE_TAG: &str = "throttle";
+
+/// Checks that `format` is a strftime format that can be used with [Line::format_timestamp].
+pub fn is_valid_timestamp_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
 impl Eq for Line {}
 
 impl PartialEq for Line {
@@ -32,19 +62,29 @@ impl PartialEq for Line {
     }
 }
 
-fn get_content_from(line: &str) -> (String, String, bool) {
+/// Checks that stripping escape sequences and carriage returns would leave the text as it is.
+fn is_plain(line: &str) -> bool {
+    line.bytes()
+        .all(|byte| (byte >= 0x20 && byte != 0x7f) || byte == b'\n')
+}
+
+fn get_content_from(line: &str) -> (Arc<str>, Arc<str>, bool) {
+    let content: Arc<str> = Arc::from(line.trim_end());
+    if is_plain(&content) {
+        return (content.clone(), content, true);
+    }
     let mut clean_utf8 = true;
-    let content = line.trim_end().to_string();
-    let bytes = strip_ansi(&content);
-    let clean_content = if let Ok(clean) = String::from_utf8(bytes.clone()) {
-        clean
-    } else {
-        error!("[Line]: Unparsable &str : {:?}", line);
-        clean_utf8 = false;
-        String::from_utf8_lossy(&bytes).to_mut().clone()
+    let bytes = strip_ansi(content.as_bytes());
+    let clean_content = match String::from_utf8(bytes) {
+        Ok(clean) => clean,
+        Err(err) => {
+            error!("[Line]: Unparsable &str : {:?}", line);
+            clean_utf8 = false;
+            String::from_utf8_lossy(err.as_bytes()).into_owned()
+        }
     };
     let clean_content = clean_content.replace('\r', "");
-    (content, clean_content, clean_utf8)
+    (content, Arc::from(clean_content), clean_utf8)
 }
 
 impl fmt::Display for Line {
@@ -59,6 +99,7 @@ impl From<&Line> for Line {
             content: line.content.clone(),
             clean_content: line.clean_content.clone(),
             clean_utf8: line.clean_utf8,
+            timestamp: line.timestamp,
             flags: line.flags.clone(),
         }
     }
@@ -71,6 +112,7 @@ impl From<&str> for Line {
             content,
             clean_content,
             clean_utf8,
+            timestamp: Local::now(),
             flags: Flags::default(),
         }
     }
@@ -83,6 +125,7 @@ impl From<String> for Line {
             content,
             clean_content,
             clean_utf8,
+            timestamp: Local::now(),
             flags: Flags::default(),
         }
     }
@@ -95,25 +138,25 @@ impl From<&String> for Line {
             content,
             clean_content,
             clean_utf8,
+            timestamp: Local::now(),
             flags: Flags::default(),
         }
     }
 }
 
 impl From<&[u8]> for Line {
-    fn from(line: &[u8]) -> Self {
-        let line = if let Ok(line) = String::from_utf8(line.to_vec()) {
-            line
-        } else {
-            error!("[Line]: Unparsable bytes : {:?}", line);
-            String::from_utf8_lossy(line).to_mut().clone()
-        };
+    fn from(bytes: &[u8]) -> Self {
+        let line = String::from_utf8_lossy(bytes);
+        if let Cow::Owned(_) = line {
+            error!("[Line]: Unparsable bytes : {:?}", bytes);
+        }
 
         let (content, clean_content, clean_utf8) = get_content_from(&line);
         Self {
             content,
             clean_content,
             clean_utf8,
+            timestamp: Local::now(),
             flags: Flags::default(),
         }
     }
@@ -121,19 +164,15 @@ impl From<&[u8]> for Line {
 
 impl From<&Vec<u8>> for Line {
     fn from(line: &Vec<u8>) -> Self {
-        let mut clean_utf8 = true;
-        let line = if let Ok(line) = String::from_utf8(line.clone()) {
-            line
-        } else {
-            clean_utf8 = false;
-            String::from_utf8_lossy(line).to_mut().clone()
-        };
+        let line = String::from_utf8_lossy(line);
+        let clean_utf8 = matches!(line, Cow::Borrowed(_));
 
         let (content, clean_content, _) = get_content_from(&line);
         Self {
             content,
             clean_content,
             clean_utf8,
+            timestamp: Local::now(),
             flags: Flags::default(),
         }
     }
@@ -150,7 +189,7 @@ impl Line {
 
     pub fn print_line(&self) -> Option<&str> {
         if !self.flags.gag {
-            Some(self.content.as_str())
+            Some(&*self.content)
         } else {
             None
         }
@@ -160,11 +199,22 @@ impl Line {
         self.clean_utf8
     }
 
+    /// The time the line was created, which for MUD output is when it was received.
+    pub fn timestamp(&self) -> DateTime<Local> {
+        self.timestamp
+    }
+
+    /// Formats the timestamp of the line. `format` must be valid according to
+    /// [is_valid_timestamp_format].
+    pub fn format_timestamp(&self, format: &str) -> String {
+        self.timestamp.format(format).to_string()
+    }
+
     pub fn log_line(&self) -> Option<&str> {
         if self.flags.skip_log || (self.flags.prompt && self.content.is_empty()) {
             None
         } else {
-            Some(self.clean_content.as_str())
+            Some(&*self.clean_content)
         }
     }
 
@@ -176,9 +226,19 @@ impl Line {
         &self.clean_content
     }
 
+    /// The text of the line, shared with the line rather than copied.
+    pub fn shared_line(&self) -> Arc<str> {
+        self.content.clone()
+    }
+
+    /// The text of the line without escape sequences, shared with the line rather than copied.
+    pub fn shared_clean_line(&self) -> Arc<str> {
+        self.clean_content.clone()
+    }
+
     pub fn clear(&mut self) {
-        self.content.clear();
-        self.clean_content.clear();
+        self.content = Arc::from("");
+        self.clean_content = self.content.clone();
     }
 
     pub fn lines(&self) -> std::str::Lines {
@@ -192,12 +252,36 @@ impl Line {
     pub fn replace_with(&mut self, other: &Line) {
         self.flags = other.flags.clone();
     }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.flags.tags.get(key).map(|val| val.as_str())
+    }
+
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        self.flags.tags.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        self.flags.tags.remove(key)
+    }
 }
 
 #[cfg(test)]
 mod test_line {
 
-    use super::Line;
+    use super::{is_valid_timestamp_format, Line};
+
+    #[test]
+    fn test_timestamp() {
+        let line = Line::from("testline");
+        assert_eq!(Line::from(&line).timestamp(), line.timestamp());
+        assert_eq!(
+            line.format_timestamp("%H:%M"),
+            line.timestamp().format("%H:%M").to_string()
+        );
+        assert!(is_valid_timestamp_format("[%Y-%m-%d %H:%M:%S]"));
+        assert!(!is_valid_timestamp_format("%Q"));
+    }
 
     #[test]
     fn test_gag_flag() {
@@ -237,6 +321,21 @@ mod test_line {
         assert_eq!(line.is_utf8(), false);
     }
 
+    #[test]
+    fn test_shared_content() {
+        let line = Line::from("testline");
+        assert_eq!(line.clean_line().as_ptr(), line.line().as_ptr());
+        let clone = line.clone();
+        assert_eq!(clone.line().as_ptr(), line.line().as_ptr());
+        assert_eq!(line.shared_line().as_ptr(), line.line().as_ptr());
+        assert_eq!(line.shared_clean_line().as_ptr(), line.line().as_ptr());
+        let line = Line::from("\x1b[32mtest\tline\x1b[0m");
+        assert_eq!(line.clean_line(), "testline");
+        let line = Line::from("test\tline");
+        assert_eq!(line.line(), "test\tline");
+        assert_eq!(line.clean_line(), "testline");
+    }
+
     #[test]
     fn test_set_content() {
         let mut line = Line::from("test");
@@ -269,6 +368,18 @@ mod test_line {
         assert_ne!(other.flags, line.flags);
     }
 
+    #[test]
+    fn test_tags() {
+        let mut line = Line::from("test");
+        assert_eq!(line.tag("channel"), None);
+        line.set_tag("channel", "gossip");
+        assert_eq!(line.tag("channel"), Some("gossip"));
+        let clone = Line::from(&line);
+        assert_eq!(clone.tag("channel"), Some("gossip"));
+        assert_eq!(line.remove_tag("channel"), Some("gossip".to_string()));
+        assert_eq!(line.tag("channel"), None);
+    }
+
     #[test]
     fn test_from_string() {
         let line = Line::from("test".to_string());
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Upper bounds of the latency histogram buckets in milliseconds. Anything slower lands in a
+/// last, open ended bucket.
+pub const LATENCY_BUCKETS: [u64; 5] = [1, 5, 16, 50, 100];
+
+/// Counts and latencies of one kind of work, eg. handling a type of event.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Timings {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Number of samples per bucket of [LATENCY_BUCKETS], plus one for the slower ones.
+    pub histogram: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl Timings {
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        let millis = elapsed.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| millis < *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.histogram[bucket] += 1;
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.count > 0 {
+            self.total / self.count as u32
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn merge(&mut self, other: &Timings) {
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+        for (bucket, count) in self.histogram.iter_mut().zip(other.histogram) {
+            *bucket += count;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Time spent handling each type of event.
+    pub events: BTreeMap<String, Timings>,
+    /// Time spent drawing to the terminal after handling an event.
+    pub render: Timings,
+    /// Events waiting behind the one being handled, when it was taken off the queue.
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+    /// Lines in the scrollback and the bytes of their text, as of the last tick.
+    pub scrollback_lines: usize,
+    pub scrollback_bytes: usize,
+    /// Older lines of the scrollback kept on disk.
+    pub scrollback_spilled: usize,
+}
+
+impl MetricsSnapshot {
+    /// All event types added together.
+    pub fn total(&self) -> Timings {
+        self.events
+            .values()
+            .fold(Timings::default(), |mut total, timings| {
+                total.merge(timings);
+                total
+            })
+    }
+}
+
+/// Metrics of the main event loop, shared between the loop recording them and the scripts and
+/// macros reading them.
+#[derive(Debug, Default, Clone)]
+pub struct EventMetrics(Arc<Mutex<MetricsSnapshot>>);
+
+impl EventMetrics {
+    /// Records that an event was taken off the queue with `depth` events waiting behind it.
+    pub fn record_queue_depth(&self, depth: usize) {
+        if let Ok(mut metrics) = self.0.lock() {
+            metrics.queue_depth = depth;
+            metrics.max_queue_depth = metrics.max_queue_depth.max(depth);
+        }
+    }
+
+    pub fn record_event(&self, name: &str, elapsed: Duration) {
+        if let Ok(mut metrics) = self.0.lock() {
+            match metrics.events.get_mut(name) {
+                Some(timings) => timings.record(elapsed),
+                None => {
+                    let mut timings = Timings::default();
+                    timings.record(elapsed);
+                    metrics.events.insert(name.to_string(), timings);
+                }
+            }
+        }
+    }
+
+    pub fn record_render(&self, elapsed: Duration) {
+        if let Ok(mut metrics) = self.0.lock() {
+            metrics.render.record(elapsed);
+        }
+    }
+
+    pub fn record_scrollback(&self, lines: usize, bytes: usize, spilled: usize) {
+        if let Ok(mut metrics) = self.0.lock() {
+            metrics.scrollback_lines = lines;
+            metrics.scrollback_bytes = bytes;
+            metrics.scrollback_spilled = spilled;
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.0.lock().map(|m| m.clone()).unwrap_or_default()
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut metrics) = self.0.lock() {
+            *metrics = MetricsSnapshot::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_metrics {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_record_events() {
+        let metrics = EventMetrics::default();
+        metrics.record_event("MudOutput", ms(2));
+        metrics.record_event("MudOutput", ms(4));
+        metrics.record_event("Redraw", ms(120));
+        metrics.record_render(ms(20));
+
+        let snapshot = metrics.snapshot();
+        let output = &snapshot.events["MudOutput"];
+        assert_eq!(output.count, 2);
+        assert_eq!(output.average(), ms(3));
+        assert_eq!(output.max, ms(4));
+        assert_eq!(output.histogram, [0, 2, 0, 0, 0, 0]);
+        assert_eq!(snapshot.events["Redraw"].histogram, [0, 0, 0, 0, 0, 1]);
+        assert_eq!(snapshot.render.histogram, [0, 0, 0, 1, 0, 0]);
+
+        let total = snapshot.total();
+        assert_eq!(total.count, 3);
+        assert_eq!(total.max, ms(120));
+        assert_eq!(total.histogram, [0, 2, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_queue_depth() {
+        let metrics = EventMetrics::default();
+        metrics.record_queue_depth(12);
+        metrics.record_queue_depth(3);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.queue_depth, 3);
+        assert_eq!(snapshot.max_queue_depth, 12);
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+        assert_eq!(Timings::default().average(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let metrics = EventMetrics::default();
+        metrics.record_scrollback(2, 14, 1024);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.scrollback_lines, 2);
+        assert_eq!(snapshot.scrollback_bytes, 14);
+        assert_eq!(snapshot.scrollback_spilled, 1024);
+    }
+}
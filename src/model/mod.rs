@@ -1,13 +1,51 @@
+mod activity;
+mod color_filter;
 mod completions;
 mod connection;
+mod error_log;
+mod expression;
+mod gc_settings;
+mod input_rules;
+mod layout;
 mod line;
+mod metrics;
+mod origin;
+mod palette;
 mod prompt_mask;
+mod reader_filters;
 mod regex;
+mod reload_exclusions;
+mod script_order;
+mod scrollback;
+mod sent_log;
 mod settings;
+mod sound_theme;
+mod web_settings;
+mod widget;
 
 pub use self::{regex::Regex, regex::RegexOptions};
+pub use activity::{Activity, ActivityBuffer, ActivityLevel, ACTIVITY_INDICATOR};
+pub use color_filter::{contrast, ColorFilter, ColorFilters, MinContrast};
 pub use completions::Completions;
-pub use connection::{Connection, Servers};
-pub use line::Line;
-pub use prompt_mask::PromptMask;
+pub use connection::{
+    autoconnect_servers, find_servers, Capabilities, Connection, ServerInfo, ServerInfos, Servers,
+};
+pub use error_log::{ErrorLog, ScriptError};
+pub use expression::{evaluate, format_value};
+pub use gc_settings::{GcMode, GcSettings};
+pub use input_rules::{InputMode, InputRules};
+pub use layout::{Layout, Region, RegionPosition};
+pub use line::{is_valid_timestamp_format, Line, DEFAULT_TIMESTAMP_FORMAT, THROTTLE_TAG};
+pub use metrics::{EventMetrics, Timings, LATENCY_BUCKETS};
+pub use origin::{Origin, OriginStyle, OriginStyles};
+pub use palette::{Color, Palette, Palettes};
+pub use prompt_mask::{MaskStyle, PromptMask};
+pub use reader_filters::*;
+pub use reload_exclusions::ReloadExclusions;
+pub use script_order::ScriptOrder;
+pub use scrollback::{ScrollbackSettings, MIN_SCROLLBACK_LINES};
+pub use sent_log::{SentCommand, SentLog};
 pub use settings::*;
+pub use sound_theme::{SoundTheme, SoundThemeConfig};
+pub use web_settings::WebSettings;
+pub use widget::{Widget, WidgetKind, Widgets};
@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Color, Line};
+use crate::io::SaveData;
+
+/// Where a line of output comes from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Received from the server.
+    #[default]
+    Server,
+    /// Printed by the client itself, eg. by its commands.
+    Client,
+    /// Printed by a script that isn't part of a plugin.
+    Script,
+    /// Printed by the plugin with the name.
+    Plugin(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Server => write!(f, "server"),
+            Self::Client => write!(f, "client"),
+            Self::Script => write!(f, "script"),
+            Self::Plugin(name) => write!(f, "plugin:{name}"),
+        }
+    }
+}
+
+impl FromStr for Origin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "server" => Ok(Self::Server),
+            "client" => Ok(Self::Client),
+            "script" => Ok(Self::Script),
+            _ => match s.strip_prefix("plugin:") {
+                Some(name) if !name.is_empty() => Ok(Self::Plugin(name.to_string())),
+                _ => Err(format!(
+                    "Invalid origin: {s}, use server, client, script or plugin:<name>"
+                )),
+            },
+        }
+    }
+}
+
+/// How the output of an origin is shown.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OriginStyle {
+    /// Printed in front of each line, followed by a space.
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// The color of the marker.
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Output styles by origin, as in [Origin]'s `Display`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct OriginStyles {
+    styles: BTreeMap<String, OriginStyle>,
+}
+
+impl OriginStyles {
+    pub fn get(&self, origin: &Origin) -> Option<&OriginStyle> {
+        self.styles.get(&origin.to_string())
+    }
+
+    /// Sets the style of an origin, the default style removes it.
+    pub fn set(&mut self, origin: &Origin, style: OriginStyle) {
+        if style == OriginStyle::default() {
+            self.styles.remove(&origin.to_string());
+        } else {
+            self.styles.insert(origin.to_string(), style);
+        }
+    }
+
+    pub fn styles(&self) -> impl Iterator<Item = (&String, &OriginStyle)> {
+        self.styles.iter()
+    }
+
+    /// The style output of the origin is shown with. Plugins without a style of their own use
+    /// the one of scripts.
+    pub fn style(&self, origin: &Origin) -> Option<&OriginStyle> {
+        match origin {
+            Origin::Plugin(_) => self.get(origin).or_else(|| self.get(&Origin::Script)),
+            _ => self.get(origin),
+        }
+    }
+
+    pub fn hides(&self, origin: &Origin) -> bool {
+        self.style(origin).is_some_and(|style| style.hidden)
+    }
+
+    /// The line with the marker of its origin in front, if it has one.
+    pub fn decorate(&self, line: &Line) -> Option<Line> {
+        let style = self.style(&line.flags.origin)?;
+        let marker = style.marker.as_ref()?;
+        let marker = match style.color {
+            Some(color) => format!("{}{marker}\x1b[0m", color.escape(false)),
+            None => marker.to_string(),
+        };
+        let mut decorated = line.clone();
+        decorated.set_content(&format!("{marker} {}", line.line()));
+        Some(decorated)
+    }
+}
+
+impl SaveData for OriginStyles {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("origin_styles.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_origin {
+    use super::*;
+
+    #[test]
+    fn test_origin_names() {
+        assert_eq!("script".parse(), Ok(Origin::Script));
+        assert_eq!(
+            "plugin:mapper".parse(),
+            Ok(Origin::Plugin("mapper".to_string()))
+        );
+        assert!("plugin:".parse::<Origin>().is_err());
+        assert!("mud".parse::<Origin>().is_err());
+        assert_eq!(
+            Origin::Plugin("mapper".to_string()).to_string(),
+            "plugin:mapper"
+        );
+    }
+
+    #[test]
+    fn test_plugin_style() {
+        let mut styles = OriginStyles::default();
+        styles.set(
+            &Origin::Script,
+            OriginStyle {
+                hidden: true,
+                ..Default::default()
+            },
+        );
+        let mapper = Origin::Plugin("mapper".to_string());
+        assert!(styles.hides(&mapper));
+        styles.set(
+            &mapper,
+            OriginStyle {
+                marker: Some("m".to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(!styles.hides(&mapper));
+        assert!(!styles.hides(&Origin::Server));
+        styles.set(&mapper, OriginStyle::default());
+        assert_eq!(styles.get(&mapper), None);
+    }
+
+    #[test]
+    fn test_decorate() {
+        let mut styles = OriginStyles::default();
+        styles.set(
+            &Origin::Client,
+            OriginStyle {
+                marker: Some("#".to_string()),
+                color: Some(Color::Indexed(3)),
+                hidden: false,
+            },
+        );
+        let mut line = Line::from("\x1b[32mConnected");
+        assert_eq!(styles.decorate(&line), None);
+        line.flags.origin = Origin::Client;
+        assert_eq!(
+            styles.decorate(&line),
+            Some(Line::from("\x1b[33m#\x1b[0m \x1b[32mConnected"))
+        );
+    }
+}
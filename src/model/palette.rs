@@ -0,0 +1,312 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::io::SaveData;
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// The xterm defaults of the basic colors.
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+/// A color as it's set by an SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Color {
+    /// One of the 256 colors, the first 16 are the basic ones.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The red, green and blue of the color, with the xterm defaults for the 256 colors.
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Self::Indexed(index @ 0..=15) => BASIC_RGB[index as usize],
+            Self::Indexed(index @ 16..=231) => {
+                let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+                let index = index - 16;
+                (level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            Self::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                (gray, gray, gray)
+            }
+            Self::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// The escape sequence that sets this color, as the foreground or background.
+    pub fn escape(&self, background: bool) -> String {
+        format!("\x1b[{}m", self.sgr(background))
+    }
+
+    /// The SGR parameters that set this color, as the foreground or background.
+    fn sgr(&self, background: bool) -> String {
+        let (base, bright, extended) = if background {
+            (40, 100, 48)
+        } else {
+            (30, 90, 38)
+        };
+        match *self {
+            Self::Indexed(index @ 0..=7) => format!("{}", base + index as u16),
+            Self::Indexed(index @ 8..=15) => format!("{}", bright + index as u16 - 8),
+            Self::Indexed(index) => format!("{extended};5;{index}"),
+            Self::Rgb(r, g, b) => format!("{extended};2;{r};{g};{b}"),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Indexed(index @ 0..=7) => write!(f, "{}", COLOR_NAMES[index as usize]),
+            Self::Indexed(index @ 8..=15) => {
+                write!(f, "bright_{}", COLOR_NAMES[index as usize - 8])
+            }
+            Self::Indexed(index) => write!(f, "{index}"),
+            Self::Rgb(r, g, b) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parses a color name like "blue" or "bright_blue", a 256 color index or "#rrggbb".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        let (bright, base) = match name.strip_prefix("bright_") {
+            Some(base) => (8, base),
+            None => (0, name.as_str()),
+        };
+        if let Some(index) = COLOR_NAMES.iter().position(|color| *color == base) {
+            return Ok(Self::Indexed(index as u8 + bright));
+        }
+        if let Ok(index) = name.parse::<u8>() {
+            return Ok(Self::Indexed(index));
+        }
+        if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                return Ok(Self::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+            }
+        }
+        Err(format!(
+            "Invalid color: {s}, use a name like blue or bright_blue, 0-255 or #rrggbb"
+        ))
+    }
+}
+
+/// Colors of a server that are shown as other colors.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Palette {
+    colors: BTreeMap<Color, Color>,
+}
+
+impl Palette {
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn set(&mut self, from: Color, to: Option<Color>) {
+        match to {
+            Some(to) => self.colors.insert(from, to),
+            None => self.colors.remove(&from),
+        };
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = (&Color, &Color)> {
+        self.colors.iter()
+    }
+
+    /// Rewrites the colors set by the SGR sequences in `line`.
+    pub fn apply(&self, line: &str) -> String {
+        if self.is_empty() {
+            return line.to_string();
+        }
+        map_colors(line, |color, _| self.colors.get(&color).copied())
+    }
+
+    /// A grid of the 256 colors with their numbers, showing how they look with this palette.
+    pub fn preview(&self) -> Vec<String> {
+        let row = |colors: std::ops::Range<u16>| -> String {
+            let mut line = String::new();
+            for index in colors {
+                line.push_str(&format!("\x1b[38;5;{index}m{index:>4}"));
+            }
+            line.push_str("\x1b[0m");
+            self.apply(&line)
+        };
+        let mut lines = vec![row(0..8), row(8..16), String::new()];
+        for start in (16..232).step_by(18) {
+            lines.push(row(start..start + 18));
+        }
+        lines.push(String::new());
+        lines.push(row(232..244));
+        lines.push(row(244..256));
+        lines
+    }
+}
+
+/// Rewrites the colors set by the SGR sequences in `line` with `map`, which gets each color and
+/// whether it's a background color and returns the color to show instead, if any.
+pub fn map_colors(line: &str, map: impl Fn(Color, bool) -> Option<Color>) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find("\x1b[") {
+        result.push_str(&rest[..start]);
+        let sequence = &rest[start + 2..];
+        let params_len = sequence
+            .find(|c: char| !c.is_ascii_digit() && c != ';')
+            .unwrap_or(sequence.len());
+        if sequence[params_len..].starts_with('m') {
+            result.push_str("\x1b[");
+            result.push_str(&remap_params(&sequence[..params_len], &map));
+            result.push('m');
+            rest = &sequence[params_len + 1..];
+        } else {
+            result.push_str("\x1b[");
+            rest = sequence;
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn remap_params(params: &str, map: &impl Fn(Color, bool) -> Option<Color>) -> String {
+    let codes: Vec<&str> = params.split(';').collect();
+    let mut remapped: Vec<String> = Vec::with_capacity(codes.len());
+    let mut i = 0;
+    while i < codes.len() {
+        let code = codes[i].parse::<u16>().ok();
+        let number = |offset: usize| codes.get(i + offset)?.parse::<u8>().ok();
+        let (color, background, len) = match code {
+            Some(code @ 30..=37) => (Some(Color::Indexed(code as u8 - 30)), false, 1),
+            Some(code @ 90..=97) => (Some(Color::Indexed(code as u8 - 82)), false, 1),
+            Some(code @ 40..=47) => (Some(Color::Indexed(code as u8 - 40)), true, 1),
+            Some(code @ 100..=107) => (Some(Color::Indexed(code as u8 - 92)), true, 1),
+            Some(code @ (38 | 48)) => match codes.get(i + 1).copied() {
+                Some("5") => (number(2).map(Color::Indexed), code == 48, 3),
+                Some("2") => (
+                    number(2)
+                        .zip(number(3))
+                        .zip(number(4))
+                        .map(|((r, g), b)| Color::Rgb(r, g, b)),
+                    code == 48,
+                    5,
+                ),
+                _ => (None, false, 1),
+            },
+            _ => (None, false, 1),
+        };
+        match color.and_then(|color| map(color, background)) {
+            Some(to) => remapped.push(to.sgr(background)),
+            None => {
+                let end = (i + len).min(codes.len());
+                remapped.extend(codes[i..end].iter().map(|code| code.to_string()));
+            }
+        }
+        i += len;
+    }
+    remapped.join(";")
+}
+
+/// Palettes by saved server name.
+pub type Palettes = HashMap<String, Palette>;
+
+impl SaveData for Palettes {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("palettes.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_palette {
+    use super::*;
+
+    fn palette(colors: &[(&str, &str)]) -> Palette {
+        let mut palette = Palette::default();
+        for (from, to) in colors {
+            palette.set(from.parse().unwrap(), Some(to.parse().unwrap()));
+        }
+        palette
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!("blue".parse(), Ok(Color::Indexed(4)));
+        assert_eq!("Bright_Cyan".parse(), Ok(Color::Indexed(14)));
+        assert_eq!("202".parse(), Ok(Color::Indexed(202)));
+        assert_eq!("#00ff80".parse(), Ok(Color::Rgb(0, 255, 128)));
+        assert!("256".parse::<Color>().is_err());
+        assert!("#00ff8".parse::<Color>().is_err());
+        assert!("teal".parse::<Color>().is_err());
+        assert_eq!(Color::Indexed(12).to_string(), "bright_blue");
+        assert_eq!(Color::Rgb(0, 255, 128).to_string(), "#00ff80");
+    }
+
+    #[test]
+    fn test_apply() {
+        let palette = palette(&[
+            ("blue", "bright_cyan"),
+            ("202", "#ffffff"),
+            ("#102030", "1"),
+        ]);
+        assert_eq!(
+            palette.apply("\x1b[1;34mblue\x1b[0m \x1b[44mbg\x1b[m"),
+            "\x1b[1;96mblue\x1b[0m \x1b[106mbg\x1b[m"
+        );
+        assert_eq!(
+            palette.apply("\x1b[38;5;202morange \x1b[38;5;4mblue"),
+            "\x1b[38;2;255;255;255morange \x1b[96mblue"
+        );
+        assert_eq!(
+            palette.apply("\x1b[48;2;16;32;48mdark \x1b[38;2;16;32;49mother"),
+            "\x1b[41mdark \x1b[38;2;16;32;49mother"
+        );
+        assert_eq!(palette.apply("\x1b[2Jplain [34m"), "\x1b[2Jplain [34m");
+        assert_eq!(palette.apply("cut \x1b[38;5"), "cut \x1b[38;5");
+    }
+
+    #[test]
+    fn test_empty_palette() {
+        let line = "\x1b[34mblue";
+        assert_eq!(Palette::default().apply(line), line);
+    }
+
+    #[test]
+    fn test_preview() {
+        let preview = palette(&[("blue", "bright_cyan")]).preview();
+        assert_eq!(preview.len(), 18);
+        assert!(preview[0].contains("\x1b[96m   4"));
+        assert!(preview[16].contains("\x1b[38;5;232m 232"));
+    }
+}
@@ -1,7 +1,85 @@
-use mlua::{Integer as LuaInt, Lua, Result as LuaResult, String as LuaString, Table as LuaTable};
+use mlua::{Integer as LuaInt, Lua, Result as LuaResult, Table as LuaTable, Value as LuaValue};
 use std::collections::BTreeMap;
 use std::ops::{AddAssign, Deref};
 
+/// Structured styling for a mask entry. Converted into the matching ANSI
+/// escape sequence when the mask is applied to the prompt input.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct MaskStyle {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reset: bool,
+}
+
+fn color_code(color: &str, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let (bright, name) = match color.strip_prefix("bright_") {
+        Some(name) => (true, name),
+        None => (false, color),
+    };
+    let offset = match name {
+        "black" => 0,
+        "red" => 1,
+        "green" => 2,
+        "yellow" => 3,
+        "blue" => 4,
+        "magenta" => 5,
+        "cyan" => 6,
+        "white" => 7,
+        _ => {
+            let index = color.parse::<u8>().ok()?;
+            return Some(format!("{};5;{}", base + 8, index));
+        }
+    };
+    let base = if bright { base + 60 } else { base };
+    Some(format!("{}", base + offset))
+}
+
+impl MaskStyle {
+    pub fn to_ansi(&self) -> String {
+        let mut codes: Vec<String> = vec![];
+        if self.reset {
+            codes.push("0".to_string());
+        }
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.italic {
+            codes.push("3".to_string());
+        }
+        if self.underline {
+            codes.push("4".to_string());
+        }
+        if let Some(code) = self.fg.as_ref().and_then(|fg| color_code(fg, false)) {
+            codes.push(code);
+        }
+        if let Some(code) = self.bg.as_ref().and_then(|bg| color_code(bg, true)) {
+            codes.push(code);
+        }
+        if codes.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+}
+
+impl From<LuaTable<'_>> for MaskStyle {
+    fn from(table: LuaTable) -> Self {
+        Self {
+            fg: table.get("fg").unwrap_or_default(),
+            bg: table.get("bg").unwrap_or_default(),
+            bold: table.get("bold").unwrap_or_default(),
+            italic: table.get("italic").unwrap_or_default(),
+            underline: table.get("underline").unwrap_or_default(),
+            reset: table.get("reset").unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PromptMask {
     mask: BTreeMap<i32, String>,
@@ -33,6 +111,15 @@ impl PromptMask {
         masked_buf.iter().collect()
     }
 
+    /// Wraps the characters between `start` and `end` (inclusive, 0-indexed)
+    /// in the given style, resetting the styling after the last character.
+    pub fn highlight(start: i32, end: i32, style: &MaskStyle) -> Self {
+        let mut mask = BTreeMap::new();
+        mask.insert(start, style.to_ansi());
+        mask.insert(end + 1, "\x1b[0m".to_string());
+        PromptMask { mask }
+    }
+
     pub fn to_table<'a>(&'a self, ctx: &'a Lua) -> LuaResult<LuaTable> {
         ctx.create_table_from(self.iter().map(|(idx, mask)| (*idx + 1, (*mask).clone())))
     }
@@ -61,11 +148,16 @@ impl From<BTreeMap<i32, String>> for PromptMask {
 impl From<LuaTable<'_>> for PromptMask {
     fn from(mask_table: LuaTable) -> Self {
         let mask = mask_table
-            .pairs::<LuaInt, LuaString>()
-            .collect::<Result<Vec<(LuaInt, LuaString)>, _>>()
-            .unwrap()
-            .iter()
-            .map(|(idx, mask)| ((*idx as i32) - 1, mask.to_str().unwrap().to_string()))
+            .pairs::<LuaInt, LuaValue>()
+            .filter_map(Result::ok)
+            .filter_map(|(idx, mask)| {
+                let mask = match mask {
+                    LuaValue::String(mask) => mask.to_str().ok()?.to_string(),
+                    LuaValue::Table(style) => MaskStyle::from(style).to_ansi(),
+                    _ => return None,
+                };
+                Some(((idx as i32) - 1, mask))
+            })
             .collect::<BTreeMap<i32, String>>();
 
         PromptMask { mask }
@@ -74,7 +166,7 @@ impl From<LuaTable<'_>> for PromptMask {
 
 #[cfg(test)]
 mod test_prompt_mask {
-    use crate::model::PromptMask;
+    use crate::model::{MaskStyle, PromptMask};
     use mlua::{Lua, Table as LuaTable};
     use std::collections::BTreeMap;
 
@@ -100,6 +192,41 @@ mod test_prompt_mask {
         assert_eq!(lua_mask, expected_mask)
     }
 
+    #[test]
+    fn test_from_luatable_styles() {
+        let lua = Lua::new();
+        let styled_mask: LuaTable = lua
+            .load(
+                r#"
+    {
+        [1] = { fg = "red", bold = true },
+        [3] = { bg = "bright_blue" },
+        [5] = { fg = "202", reset = true },
+    }
+"#,
+            )
+            .eval()
+            .unwrap();
+        let expected = BTreeMap::from([
+            (0, "\x1b[1;31m".to_string()),
+            (2, "\x1b[104m".to_string()),
+            (4, "\x1b[0;38;5;202m".to_string()),
+        ]);
+        assert_eq!(PromptMask::from(styled_mask), PromptMask::from(expected));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let style = MaskStyle {
+            fg: Some("green".to_string()),
+            underline: true,
+            ..Default::default()
+        };
+        let mask = PromptMask::highlight(2, 4, &style);
+        let buf = vec!['a', 'b', 'c', 'd', 'e', 'f'];
+        assert_eq!(mask.mask_buffer(&buf), "ab\x1b[4;32mcde\x1b[0mf");
+    }
+
     #[test]
     fn test_add_assign() {
         let mut mask_a = PromptMask::from(BTreeMap::from([
@@ -0,0 +1,86 @@
+use crate::io::SaveData;
+use anyhow::bail;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The filters applied to output while reader mode is enabled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReaderFilters {
+    filters: HashMap<String, bool>,
+}
+
+pub const PROGRESS_FILTER: &str = "progress";
+pub const REPEAT_FILTER: &str = "repeats";
+pub const DECORATION_FILTER: &str = "decoration";
+pub const SPEECH_FILTER: &str = "speech";
+
+pub const READER_FILTERS: [&str; 4] = [
+    PROGRESS_FILTER,
+    REPEAT_FILTER,
+    DECORATION_FILTER,
+    SPEECH_FILTER,
+];
+
+impl ReaderFilters {
+    pub fn get(&self, key: &str) -> Result<bool> {
+        if READER_FILTERS.contains(&key) {
+            Ok(*self.filters.get(key).unwrap_or(&true))
+        } else {
+            bail!("Unknown reader filter: {}", key)
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: bool) -> Result<()> {
+        if READER_FILTERS.contains(&key) {
+            self.filters.insert(key.to_string(), value);
+            Ok(())
+        } else {
+            bail!("Unknown reader filter: {}", key)
+        }
+    }
+
+    /// Checks a filter known to exist.
+    pub fn enabled(&self, key: &str) -> bool {
+        self.get(key).unwrap_or(false)
+    }
+}
+
+impl Default for ReaderFilters {
+    fn default() -> Self {
+        let filters = READER_FILTERS
+            .iter()
+            .map(|key| (key.to_string(), true))
+            .collect();
+        Self { filters }
+    }
+}
+
+impl SaveData for ReaderFilters {
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("reader_filters.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod reader_filters_test {
+    use super::*;
+
+    #[test]
+    fn get_set_filters() {
+        let mut filters = ReaderFilters::default();
+        assert!(filters.get(REPEAT_FILTER).unwrap());
+        filters.set(REPEAT_FILTER, false).unwrap();
+        assert!(!filters.enabled(REPEAT_FILTER));
+        assert_eq!(
+            "Unknown reader filter: colors",
+            filters.set("colors", true).unwrap_err().to_string()
+        );
+        assert!(!filters.enabled("colors"));
+    }
+}
@@ -13,30 +13,50 @@ pub struct RegexOptions {
     pub ignore_whitespace: bool,
 }
 
+impl RegexOptions {
+    /// The options as inline flags, eg. `im` for a case insensitive multi line regex.
+    pub fn flags(&self) -> String {
+        [
+            (self.case_insensitive, 'i'),
+            (self.multi_line, 'm'),
+            (self.dot_matches_new_line, 's'),
+            (self.swap_greed, 'U'),
+            (self.ignore_whitespace, 'x'),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| *flag)
+        .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Regex {
     inner: MRegex,
+    options: RegexOptions,
 }
 
 impl Regex {
     pub fn new(pattern: &str, options: Option<RegexOptions>) -> Result<Self> {
-        let mut regex_builder = RegexBuilder::new(pattern);
-        if let Some(options) = options {
-            regex_builder.case_insensitive(options.case_insensitive);
-            regex_builder.multi_line(options.multi_line);
-            regex_builder.dot_matches_new_line(options.dot_matches_new_line);
-            regex_builder.swap_greed(options.swap_greed);
-            regex_builder.ignore_whitespace(options.ignore_whitespace);
-        }
-        Ok(Self {
-            inner: regex_builder.build()?,
-        })
+        let options = options.unwrap_or_default();
+        let inner = RegexBuilder::new(pattern)
+            .case_insensitive(options.case_insensitive)
+            .multi_line(options.multi_line)
+            .dot_matches_new_line(options.dot_matches_new_line)
+            .swap_greed(options.swap_greed)
+            .ignore_whitespace(options.ignore_whitespace)
+            .build()?;
+        Ok(Self { inner, options })
+    }
+
+    pub fn options(&self) -> &RegexOptions {
+        &self.options
     }
 }
 
 impl PartialEq for Regex {
     fn eq(&self, other: &Self) -> bool {
-        self.inner.as_str() == other.inner.as_str()
+        self.inner.as_str() == other.inner.as_str() && self.options == other.options
     }
 }
 
@@ -53,3 +73,23 @@ impl DerefMut for Regex {
         &mut self.inner
     }
 }
+
+#[cfg(test)]
+mod test_regex {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let options = RegexOptions {
+            case_insensitive: true,
+            multi_line: true,
+            ..Default::default()
+        };
+        let re = Regex::new("^kobold$", Some(options.clone())).unwrap();
+        assert!(re.is_match("a\nKOBOLD"));
+        assert_eq!(re.options(), &options);
+        assert_eq!(re.options().flags(), "im");
+        assert_ne!(re, Regex::new("^kobold$", None).unwrap());
+        assert_eq!(Regex::new("kobold", None).unwrap().options().flags(), "");
+    }
+}
@@ -0,0 +1,58 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Scripts left alone by the `live_reload` setting. A changed script is skipped when its path
+/// contains any of the patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReloadExclusions {
+    patterns: BTreeSet<String>,
+}
+
+impl ReloadExclusions {
+    pub fn add(&mut self, pattern: &str) {
+        self.patterns.insert(pattern.to_string());
+    }
+
+    pub fn remove(&mut self, pattern: &str) {
+        self.patterns.remove(pattern);
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &String> {
+        self.patterns.iter()
+    }
+
+    pub fn excludes(&self, path: &str) -> bool {
+        self.patterns.iter().any(|pattern| path.contains(pattern))
+    }
+}
+
+impl SaveData for ReloadExclusions {
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("reload_exclusions.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod reload_exclusions_test {
+    use super::*;
+
+    #[test]
+    fn test_excludes() {
+        let mut exclusions = ReloadExclusions::default();
+        assert!(!exclusions.excludes("/home/user/.config/blightmud/config.lua"));
+        exclusions.add("/vendor/");
+        exclusions.add("config.lua");
+        assert!(exclusions.excludes("/home/user/.config/blightmud/config.lua"));
+        assert!(exclusions.excludes("/home/user/scripts/vendor/lib.lua"));
+        assert!(!exclusions.excludes("/home/user/scripts/main.lua"));
+        exclusions.remove("config.lua");
+        assert!(!exclusions.excludes("/home/user/.config/blightmud/config.lua"));
+        assert_eq!(exclusions.patterns().count(), 1);
+    }
+}
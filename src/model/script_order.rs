@@ -0,0 +1,172 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// The order the scripts in the config directory are loaded in on start. Scripts in `order` load
+/// first, in that order, the others after them by name. A script always loads after the scripts
+/// it requires.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptOrder {
+    #[serde(default)]
+    order: Vec<String>,
+    #[serde(default)]
+    requires: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ScriptOrder {
+    /// The file names of the `.lua` scripts in a directory, by name.
+    pub fn scripts_in(dir: &Path) -> Vec<String> {
+        let mut scripts = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| name.ends_with(".lua"))
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        scripts.sort();
+        scripts
+    }
+
+    pub fn set_order(&mut self, order: Vec<String>) {
+        self.order = order;
+    }
+
+    pub fn requires(&self, script: &str) -> impl Iterator<Item = &String> {
+        self.requires.get(script).into_iter().flatten()
+    }
+
+    /// Sets the scripts a script requires, no scripts removes it. Requirements that would make
+    /// scripts require each other are refused.
+    pub fn set_requires(&mut self, script: &str, requires: Vec<String>) -> Result<(), String> {
+        let mut updated = self.clone();
+        if requires.is_empty() {
+            updated.requires.remove(script);
+        } else {
+            updated
+                .requires
+                .insert(script.to_string(), requires.into_iter().collect());
+        }
+        let names = updated
+            .order
+            .iter()
+            .chain(updated.requires.keys())
+            .chain(updated.requires.values().flatten())
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        updated.resolve(&names)?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// Orders the scripts for loading, or explains why they can't be ordered: a script requires
+    /// one that isn't among them or scripts require each other.
+    pub fn resolve(&self, scripts: &[String]) -> Result<Vec<String>, String> {
+        let mut declared = scripts.to_vec();
+        declared.sort_by_key(|script| {
+            let position = self.order.iter().position(|name| name == script);
+            (position.unwrap_or(usize::MAX), script.clone())
+        });
+        let available = scripts.iter().collect::<HashSet<&String>>();
+        let mut resolved = vec![];
+        let mut visiting = vec![];
+        for script in &declared {
+            self.visit(script, &available, &mut visiting, &mut resolved)?;
+        }
+        Ok(resolved)
+    }
+
+    fn visit(
+        &self,
+        script: &String,
+        available: &HashSet<&String>,
+        visiting: &mut Vec<String>,
+        resolved: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if resolved.contains(script) {
+            return Ok(());
+        }
+        if let Some(start) = visiting.iter().position(|name| name == script) {
+            let mut cycle = visiting[start..].to_vec();
+            cycle.push(script.clone());
+            return Err(format!(
+                "Scripts require each other: {}",
+                cycle.join(" -> ")
+            ));
+        }
+        visiting.push(script.clone());
+        for required in self.requires(script) {
+            if !available.contains(required) {
+                return Err(format!("{script} requires {required}, which isn't there"));
+            }
+            self.visit(required, available, visiting, resolved)?;
+        }
+        visiting.pop();
+        resolved.push(script.clone());
+        Ok(())
+    }
+}
+
+impl SaveData for ScriptOrder {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("script_order.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod script_order_test {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let scripts = names(&["b.lua", "a.lua", "c.lua"]);
+        let mut order = ScriptOrder::default();
+        assert_eq!(
+            order.resolve(&scripts),
+            Ok(names(&["a.lua", "b.lua", "c.lua"]))
+        );
+        order.set_order(names(&["c.lua", "missing.lua"]));
+        assert_eq!(
+            order.resolve(&scripts),
+            Ok(names(&["c.lua", "a.lua", "b.lua"]))
+        );
+        order.set_requires("c.lua", names(&["b.lua"])).unwrap();
+        assert_eq!(
+            order.resolve(&scripts),
+            Ok(names(&["b.lua", "c.lua", "a.lua"]))
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let mut order = ScriptOrder::default();
+        order.set_requires("a.lua", names(&["b.lua"])).unwrap();
+        order.set_requires("b.lua", names(&["c.lua"])).unwrap();
+        assert_eq!(
+            order.set_requires("c.lua", names(&["a.lua"])),
+            Err("Scripts require each other: a.lua -> b.lua -> c.lua -> a.lua".to_string())
+        );
+        assert_eq!(order.requires("c.lua").count(), 0);
+        assert_eq!(
+            order.resolve(&names(&["a.lua", "b.lua"])),
+            Err("b.lua requires c.lua, which isn't there".to_string())
+        );
+        order.set_requires("b.lua", vec![]).unwrap();
+        assert_eq!(
+            order.resolve(&names(&["a.lua", "b.lua"])),
+            Ok(names(&["b.lua", "a.lua"]))
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+
+/// How much of the scrollback is kept in memory, whether older lines are kept on disk, and how
+/// far back scrolling holds new output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrollbackSettings {
+    /// Lines kept in memory.
+    pub lines: usize,
+    /// Megabytes of text kept in memory.
+    pub memory: usize,
+    /// Keeps lines that leave memory in a file, read back when scrolling up past them.
+    pub spill: bool,
+    /// Scrolled back further than this many lines new output is held, 0 never holds it.
+    pub auto_pause: usize,
+}
+
+/// The fewest lines the scrollback keeps in memory, two chunks of drained lines.
+pub const MIN_SCROLLBACK_LINES: usize = 2048;
+
+impl Default for ScrollbackSettings {
+    fn default() -> Self {
+        Self {
+            lines: 32 * 1024,
+            memory: 64,
+            spill: true,
+            auto_pause: 500,
+        }
+    }
+}
+
+impl ScrollbackSettings {
+    pub fn max_bytes(&self) -> usize {
+        self.memory.saturating_mul(1024 * 1024)
+    }
+}
+
+impl SaveData for ScrollbackSettings {
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("scrollback.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn on_load(&mut self) {
+        self.lines = self.lines.max(MIN_SCROLLBACK_LINES);
+        self.memory = self.memory.max(1);
+    }
+}
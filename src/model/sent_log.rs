@@ -0,0 +1,144 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Local};
+
+use super::Line;
+
+/// Number of sent commands kept, older ones are dropped.
+pub const SENT_LOG_CAPACITY: usize = 500;
+
+/// Where a command sent to the server came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SentOrigin {
+    Typed,
+    Alias,
+    Script,
+}
+
+impl SentOrigin {
+    pub fn of(line: &Line) -> Self {
+        if line.flags.from_alias {
+            Self::Alias
+        } else if line.flags.source.as_deref() == Some("user") {
+            Self::Typed
+        } else {
+            Self::Script
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Typed => "typed",
+            Self::Alias => "alias",
+            Self::Script => "script",
+        }
+    }
+}
+
+/// A line as it was sent to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentCommand {
+    /// Counts up from 1 over the session, so an index keeps pointing at the same command while
+    /// older ones are dropped.
+    pub index: u64,
+    pub time: DateTime<Local>,
+    pub command: String,
+    pub origin: SentOrigin,
+}
+
+#[derive(Debug, Default)]
+struct SentCommands {
+    commands: VecDeque<SentCommand>,
+    last_index: u64,
+}
+
+/// The commands sent to the server, shared between the event loop recording them and the scripts
+/// reading them. Masked input and lines kept out of the log aren't recorded.
+#[derive(Debug, Default, Clone)]
+pub struct SentLog(Arc<Mutex<SentCommands>>);
+
+impl SentLog {
+    pub fn record(&self, line: &Line) {
+        if line.flags.skip_log {
+            return;
+        }
+        let mut sent = self.0.lock().unwrap();
+        sent.last_index += 1;
+        let command = SentCommand {
+            index: sent.last_index,
+            time: Local::now(),
+            command: line.line().to_string(),
+            origin: SentOrigin::of(line),
+        };
+        if sent.commands.len() >= SENT_LOG_CAPACITY {
+            sent.commands.pop_front();
+        }
+        sent.commands.push_back(command);
+    }
+
+    /// The last `count` commands, oldest first.
+    pub fn recent(&self, count: usize) -> Vec<SentCommand> {
+        let sent = self.0.lock().unwrap();
+        let skip = sent.commands.len().saturating_sub(count);
+        sent.commands.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn get(&self, index: u64) -> Option<SentCommand> {
+        let sent = self.0.lock().unwrap();
+        sent.commands
+            .iter()
+            .find(|command| command.index == index)
+            .cloned()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().commands.clear();
+    }
+}
+
+#[cfg(test)]
+mod test_sent_log {
+    use super::*;
+
+    fn line(content: &str, source: &str) -> Line {
+        let mut line = Line::from(content);
+        line.flags.source = Some(source.to_string());
+        line
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(SentOrigin::of(&line("look", "user")), SentOrigin::Typed);
+        assert_eq!(SentOrigin::of(&line("look", "script")), SentOrigin::Script);
+        let mut expanded = line("kill orc", "script");
+        expanded.flags.from_alias = true;
+        assert_eq!(SentOrigin::of(&expanded), SentOrigin::Alias);
+    }
+
+    #[test]
+    fn test_record() {
+        let log = SentLog::default();
+        for i in 0..SENT_LOG_CAPACITY + 2 {
+            log.record(&line(&format!("say {i}"), "user"));
+        }
+        let mut password = line("hunter2", "user");
+        password.flags.skip_log = true;
+        log.record(&password);
+
+        let recent = log.recent(2);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[1].command, format!("say {}", SENT_LOG_CAPACITY + 1));
+        assert_eq!(recent[1].index, SENT_LOG_CAPACITY as u64 + 2);
+        assert_eq!(log.recent(usize::MAX).len(), SENT_LOG_CAPACITY);
+        assert_eq!(log.get(1), None);
+        assert_eq!(log.get(3).unwrap().command, "say 2");
+
+        log.clear();
+        assert!(log.recent(10).is_empty());
+        log.record(&line("look", "user"));
+        assert_eq!(log.recent(1)[0].index, SENT_LOG_CAPACITY as u64 + 3);
+    }
+}
@@ -19,13 +19,27 @@ pub const SCROLL_SPLIT: &str = "scroll_split";
 pub const SCROLL_LOCK: &str = "scroll_lock";
 pub const READER_MODE: &str = "reader_mode";
 pub const HIDE_TOPBAR: &str = "hide_topbar";
+pub const TUI_RENDERER: &str = "tui_renderer";
 pub const COMMAND_SEARCH: &str = "command_search";
 pub const SMART_HISTORY: &str = "smart_history";
 pub const ECHO_INPUT: &str = "echo_input";
+pub const COMMAND_STACKING: &str = "command_stacking";
+pub const TIMESTAMPS: &str = "timestamps";
+pub const PROMPT_SPACING: &str = "prompt_spacing";
+pub const OUTPUT_SEPARATOR: &str = "output_separator";
+pub const MSSP_SUMMARY: &str = "mssp_summary";
+pub const COLLAPSE_REPEATS: &str = "collapse_repeats";
+pub const LOG_DEDUPE: &str = "log_dedupe";
+pub const PENDING_ECHO: &str = "pending_echo";
+pub const PAGING: &str = "paging";
+pub const CONNECTION_WATCHDOG: &str = "connection_watchdog";
+pub const STALL_RECONNECT: &str = "stall_reconnect";
+pub const LIVE_RELOAD: &str = "live_reload";
+pub const INPUT_FIFO: &str = "input_fifo";
 
 pub const KEEPALIVE_ENABLED: &str = "keepalive_enabled";
 
-pub const SETTINGS: [&str; 13] = [
+pub const SETTINGS: &[&str] = &[
     LOGGING_ENABLED,
     TTS_ENABLED,
     MOUSE_ENABLED,
@@ -35,9 +49,24 @@ pub const SETTINGS: [&str; 13] = [
     SCROLL_LOCK,
     READER_MODE,
     HIDE_TOPBAR,
+    #[cfg(feature = "tui")]
+    TUI_RENDERER,
     COMMAND_SEARCH,
     SMART_HISTORY,
     ECHO_INPUT,
+    COMMAND_STACKING,
+    TIMESTAMPS,
+    PROMPT_SPACING,
+    OUTPUT_SEPARATOR,
+    MSSP_SUMMARY,
+    COLLAPSE_REPEATS,
+    LOG_DEDUPE,
+    PENDING_ECHO,
+    PAGING,
+    CONNECTION_WATCHDOG,
+    STALL_RECONNECT,
+    LIVE_RELOAD,
+    INPUT_FIFO,
     KEEPALIVE_ENABLED,
 ];
 
@@ -72,9 +101,24 @@ impl Default for Settings {
         settings.insert(SCROLL_LOCK.to_string(), true);
         settings.insert(READER_MODE.to_string(), false);
         settings.insert(HIDE_TOPBAR.to_string(), false);
+        #[cfg(feature = "tui")]
+        settings.insert(TUI_RENDERER.to_string(), false);
         settings.insert(COMMAND_SEARCH.to_string(), false);
         settings.insert(SMART_HISTORY.to_string(), false);
         settings.insert(ECHO_INPUT.to_string(), true);
+        settings.insert(COMMAND_STACKING.to_string(), false);
+        settings.insert(TIMESTAMPS.to_string(), false);
+        settings.insert(PROMPT_SPACING.to_string(), false);
+        settings.insert(OUTPUT_SEPARATOR.to_string(), false);
+        settings.insert(MSSP_SUMMARY.to_string(), false);
+        settings.insert(COLLAPSE_REPEATS.to_string(), false);
+        settings.insert(LOG_DEDUPE.to_string(), false);
+        settings.insert(PENDING_ECHO.to_string(), false);
+        settings.insert(PAGING.to_string(), false);
+        settings.insert(CONNECTION_WATCHDOG.to_string(), true);
+        settings.insert(STALL_RECONNECT.to_string(), false);
+        settings.insert(LIVE_RELOAD.to_string(), false);
+        settings.insert(INPUT_FIFO.to_string(), true);
         settings.insert(KEEPALIVE_ENABLED.to_string(), true);
         Self { settings }
     }
@@ -0,0 +1,180 @@
+use crate::io::SaveData;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The file describing a sound theme, inside the theme's directory.
+pub const SOUND_THEME_FILE: &str = "theme.ron";
+
+fn default_amplify() -> f32 {
+    1.0
+}
+
+/// The sound played for an event. The file is relative to the theme's directory.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ThemeSound {
+    pub file: String,
+    #[serde(default = "default_amplify")]
+    pub amplify: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    description: String,
+    sounds: BTreeMap<String, ThemeSound>,
+}
+
+/// A pack of sounds played for named events, eg. "tell_received", read from
+/// `<data dir>/sound_themes/<name>/theme.ron`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundTheme {
+    pub name: String,
+    pub description: String,
+    dir: PathBuf,
+    sounds: BTreeMap<String, ThemeSound>,
+}
+
+impl SoundTheme {
+    pub fn themes_dir() -> PathBuf {
+        crate::DATA_DIR.join("sound_themes")
+    }
+
+    /// Loads an installed theme by name.
+    pub fn load(name: &str) -> Result<Self> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("Invalid sound theme name: '{}'", name);
+        }
+        let dir = Self::themes_dir().join(name);
+        if !dir.join(SOUND_THEME_FILE).exists() {
+            bail!("Unknown sound theme: {}", name);
+        }
+        Self::load_from(name, &dir)
+    }
+
+    /// Loads a theme from a directory, checking that all of its sound files exist.
+    pub fn load_from(name: &str, dir: &Path) -> Result<Self> {
+        let path = dir.join(SOUND_THEME_FILE);
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        let theme: ThemeFile = ron::from_str(&contents)
+            .with_context(|| format!("Invalid sound theme {}", path.display()))?;
+        for (event, sound) in &theme.sounds {
+            if !dir.join(&sound.file).is_file() {
+                bail!(
+                    "Sound theme '{}' is missing '{}' for {}",
+                    name,
+                    sound.file,
+                    event
+                );
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            description: theme.description,
+            dir: dir.to_path_buf(),
+            sounds: theme.sounds,
+        })
+    }
+
+    /// The file and amplification to play for an event, if the theme has a sound for it.
+    pub fn sound(&self, event: &str) -> Option<(PathBuf, f32)> {
+        self.sounds
+            .get(event)
+            .map(|sound| (self.dir.join(&sound.file), sound.amplify))
+    }
+
+    pub fn events(&self) -> Vec<String> {
+        self.sounds.keys().cloned().collect()
+    }
+
+    /// The names of the installed themes.
+    pub fn available() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Self::themes_dir()) else {
+            return vec![];
+        };
+        let mut themes: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(SOUND_THEME_FILE).is_file())
+            .filter_map(|entry| entry.file_name().to_str().map(String::from))
+            .collect();
+        themes.sort();
+        themes
+    }
+}
+
+/// The sound theme selected with `/soundtheme`, restored on startup.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoundThemeConfig {
+    pub theme: Option<String>,
+}
+
+impl SaveData for SoundThemeConfig {
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("sound_theme.ron")
+    }
+}
+
+#[cfg(test)]
+mod sound_theme_test {
+    use super::*;
+
+    fn theme_dir(name: &str, theme: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blightmud_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(SOUND_THEME_FILE), theme).unwrap();
+        for file in files {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_load_theme() {
+        let dir = theme_dir(
+            "theme_load",
+            r#"(
+                description: "Beeps",
+                sounds: {
+                    "tell_received": (file: "tell.wav", amplify: 0.5),
+                    "death": (file: "death.wav"),
+                },
+            )"#,
+            &["tell.wav", "death.wav"],
+        );
+        let theme = SoundTheme::load_from("beeps", &dir).unwrap();
+        assert_eq!(theme.description, "Beeps");
+        assert_eq!(theme.events(), vec!["death", "tell_received"]);
+        assert_eq!(
+            theme.sound("tell_received"),
+            Some((dir.join("tell.wav"), 0.5))
+        );
+        assert_eq!(theme.sound("death"), Some((dir.join("death.wav"), 1.0)));
+        assert_eq!(theme.sound("level_up"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_theme() {
+        let dir = theme_dir(
+            "theme_missing",
+            r#"(sounds: { "death": (file: "death.wav") })"#,
+            &[],
+        );
+        assert_eq!(
+            SoundTheme::load_from("broken", &dir)
+                .unwrap_err()
+                .to_string(),
+            "Sound theme 'broken' is missing 'death.wav' for death"
+        );
+        std::fs::write(dir.join(SOUND_THEME_FILE), "(sounds: [])").unwrap();
+        assert!(SoundTheme::load_from("broken", &dir).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(
+            SoundTheme::load("../escape").unwrap_err().to_string(),
+            "Invalid sound theme name: '../escape'"
+        );
+    }
+}
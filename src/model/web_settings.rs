@@ -0,0 +1,30 @@
+use crate::io::SaveData;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Where the web frontend listens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSettings {
+    /// The address the server binds to. Only the loopback interface by default, anything else
+    /// exposes the page to the network.
+    pub bind: IpAddr,
+}
+
+impl Default for WebSettings {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+}
+
+impl SaveData for WebSettings {
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("web.ron")
+    }
+
+    fn is_pretty() -> bool {
+        true
+    }
+}
@@ -0,0 +1,276 @@
+use std::time::{Duration, Instant};
+
+const FILLED: char = '█';
+const EMPTY: char = '░';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidgetKind {
+    Bar,
+    Gauge,
+    Countdown,
+}
+
+/// A small graphical element rendered into a layout region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Widget {
+    pub id: String,
+    pub region: String,
+    pub kind: WidgetKind,
+    pub label: String,
+    pub value: f64,
+    pub max: f64,
+    pub color: Option<String>,
+    /// Seconds until the widget is removed. Countdowns always expire when they reach zero.
+    pub expire: Option<f64>,
+}
+
+impl Widget {
+    pub fn new(id: &str, region: &str, kind: WidgetKind) -> Self {
+        Self {
+            id: id.to_string(),
+            region: region.to_string(),
+            kind,
+            label: String::new(),
+            value: 0.0,
+            max: 1.0,
+            color: None,
+            expire: None,
+        }
+    }
+
+    fn suffix(&self, value: f64) -> String {
+        match self.kind {
+            WidgetKind::Bar => format!("{}/{}", fmt_num(value), fmt_num(self.max)),
+            WidgetKind::Gauge => format!("{:>3.0}%", self.ratio(value) * 100.0),
+            WidgetKind::Countdown => format!("{value:.1}s"),
+        }
+    }
+
+    fn ratio(&self, value: f64) -> f64 {
+        if self.max > 0.0 {
+            (value / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Renders the widget as a single line of at most `width` printable characters.
+    pub fn render(&self, value: f64, width: u16) -> String {
+        let suffix = self.suffix(value);
+        let label = if self.label.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", self.label)
+        };
+        let label_width = label.chars().count();
+        let bar_width = (width as usize).saturating_sub(label_width + suffix.chars().count() + 3);
+        let filled = (self.ratio(value) * bar_width as f64).round() as usize;
+        let (color, reset) = match &self.color {
+            Some(color) => (color.as_str(), "\x1b[0m"),
+            None => ("", ""),
+        };
+        format!(
+            "{label}[{color}{}{reset}{}] {suffix}",
+            FILLED.to_string().repeat(filled),
+            EMPTY.to_string().repeat(bar_width - filled),
+        )
+    }
+}
+
+fn fmt_num(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value:.1}")
+    }
+}
+
+#[derive(Debug)]
+struct ActiveWidget {
+    widget: Widget,
+    updated: Instant,
+}
+
+impl ActiveWidget {
+    fn value(&self, now: Instant) -> f64 {
+        match self.widget.kind {
+            WidgetKind::Countdown => {
+                (self.widget.value - now.duration_since(self.updated).as_secs_f64()).max(0.0)
+            }
+            _ => self.widget.value,
+        }
+    }
+
+    fn expired(&self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated);
+        match (self.widget.kind, self.widget.expire) {
+            (WidgetKind::Countdown, _) if self.value(now) <= 0.0 => true,
+            (_, Some(expire)) if expire <= 0.0 => true,
+            // Times too long to measure, eg. `math.huge`, and NaN never expire
+            (_, Some(expire)) => {
+                Duration::try_from_secs_f64(expire).is_ok_and(|expire| elapsed >= expire)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Keeps track of all active widgets.
+#[derive(Debug, Default)]
+pub struct Widgets {
+    widgets: Vec<ActiveWidget>,
+}
+
+impl Widgets {
+    /// Adds or replaces a widget and returns the regions that need a redraw.
+    pub fn set(&mut self, widget: Widget, now: Instant) -> Vec<String> {
+        let mut regions = vec![widget.region.clone()];
+        if let Some(active) = self.widgets.iter_mut().find(|w| w.widget.id == widget.id) {
+            if active.widget.region != widget.region {
+                regions.push(active.widget.region.clone());
+            }
+            active.widget = widget;
+            active.updated = now;
+        } else {
+            self.widgets.push(ActiveWidget {
+                widget,
+                updated: now,
+            });
+        }
+        regions
+    }
+
+    /// Sets the value of a widget, restarting its expiry. Returns its region.
+    pub fn update(&mut self, id: &str, value: f64, now: Instant) -> Option<String> {
+        let active = self.widgets.iter_mut().find(|w| w.widget.id == id)?;
+        active.widget.value = value;
+        active.updated = now;
+        Some(active.widget.region.clone())
+    }
+
+    /// Removes a widget and returns its region.
+    pub fn remove(&mut self, id: &str) -> Option<String> {
+        let index = self.widgets.iter().position(|w| w.widget.id == id)?;
+        Some(self.widgets.remove(index).widget.region)
+    }
+
+    /// Drops expired widgets and returns the regions that need a redraw, either because a widget
+    /// expired or because they contain a running countdown.
+    pub fn tick(&mut self, now: Instant) -> Vec<String> {
+        let mut regions: Vec<String> = vec![];
+        self.widgets.retain(|w| {
+            let expired = w.expired(now);
+            if expired || w.widget.kind == WidgetKind::Countdown {
+                regions.push(w.widget.region.clone());
+            }
+            !expired
+        });
+        regions.sort();
+        regions.dedup();
+        regions
+    }
+
+    pub fn lines(&self, region: &str, width: u16, now: Instant) -> Vec<String> {
+        self.widgets
+            .iter()
+            .filter(|w| w.widget.region == region)
+            .map(|w| w.widget.render(w.value(now), width))
+            .collect()
+    }
+
+    /// Returns all regions containing widgets.
+    pub fn regions(&self) -> Vec<String> {
+        let mut regions: Vec<String> = self
+            .widgets
+            .iter()
+            .map(|w| w.widget.region.clone())
+            .collect();
+        regions.sort();
+        regions.dedup();
+        regions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.widgets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test_widget {
+    use super::*;
+
+    fn bar() -> Widget {
+        let mut widget = Widget::new("bal", "hud", WidgetKind::Bar);
+        widget.label = "Bal".to_string();
+        widget.value = 2.0;
+        widget.max = 4.0;
+        widget
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(bar().render(2.0, 20), "Bal [█████░░░░░] 2/4");
+        let mut gauge = bar();
+        gauge.kind = WidgetKind::Gauge;
+        assert_eq!(gauge.render(1.0, 20), "Bal [██░░░░░░░]  25%");
+        let mut countdown = bar();
+        countdown.kind = WidgetKind::Countdown;
+        assert_eq!(countdown.render(4.0, 20), "Bal [█████████] 4.0s");
+        let mut colored = bar();
+        colored.color = Some("\x1b[31m".to_string());
+        assert_eq!(
+            colored.render(4.0, 20),
+            "Bal [\x1b[31m██████████\x1b[0m] 4/4"
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let now = Instant::now();
+        let mut widgets = Widgets::default();
+        let mut expiring = bar();
+        expiring.expire = Some(1.0);
+        widgets.set(expiring, now);
+        let mut countdown = Widget::new("cd", "status", WidgetKind::Countdown);
+        countdown.value = 2.0;
+        countdown.max = 2.0;
+        widgets.set(countdown, now);
+
+        assert_eq!(widgets.tick(now), vec!["status"]);
+        assert_eq!(widgets.lines("hud", 20, now).len(), 1);
+        assert_eq!(
+            widgets.update("bal", 3.0, now + Duration::from_millis(800)),
+            Some("hud".to_string())
+        );
+        assert_eq!(
+            widgets.tick(now + Duration::from_millis(1500)),
+            vec!["status"]
+        );
+        assert_eq!(widgets.lines("hud", 20, now).len(), 1);
+        assert_eq!(
+            widgets.tick(now + Duration::from_millis(2000)),
+            vec!["hud", "status"]
+        );
+        assert!(widgets.is_empty());
+
+        for expire in [f64::INFINITY, f64::NAN] {
+            let mut lasting = bar();
+            lasting.expire = Some(expire);
+            widgets.set(lasting, now);
+            assert!(widgets.tick(now + Duration::from_secs(3600)).is_empty());
+            assert!(!widgets.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let now = Instant::now();
+        let mut widgets = Widgets::default();
+        widgets.set(bar(), now);
+        let mut moved = bar();
+        moved.region = "other".to_string();
+        assert_eq!(widgets.set(moved, now), vec!["other", "hud"]);
+        assert_eq!(widgets.remove("bal"), Some("other".to_string()));
+        assert_eq!(widgets.remove("bal"), None);
+    }
+}
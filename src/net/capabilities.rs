@@ -0,0 +1,112 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use libmudtelnet::telnet::{op_command as cmd, op_option as opt};
+use log::debug;
+
+use crate::{
+    io::SaveData,
+    model::{find_servers, Capabilities, ServerInfos, Servers},
+    session::Session,
+};
+
+/// How long after connecting the server's negotiation is collected before it's recorded.
+pub const PROBE_DURATION: Duration = Duration::from_secs(5);
+
+/// Collects what the server negotiates right after connecting, shared between the receive
+/// thread noting it and the thread recording it.
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityProbe(Arc<Mutex<Capabilities>>);
+
+impl CapabilityProbe {
+    /// Starts collecting for a new connection.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = Capabilities::default();
+    }
+
+    pub fn protocol(&self, option: u8) {
+        // ECHO only masks a password and GA is a prompt style
+        if option != opt::ECHO && option != cmd::GA {
+            self.0
+                .lock()
+                .unwrap()
+                .protocols
+                .insert(Capabilities::option_name(option));
+        }
+    }
+
+    /// Notes how the server terminates prompts, the first one seen is kept.
+    pub fn prompt(&self, style: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .prompt
+            .get_or_insert_with(|| style.to_string());
+    }
+
+    pub fn utf8(&self, utf8: bool) {
+        self.0.lock().unwrap().utf8 = utf8;
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Records what the server negotiated for the saved servers pointing at it, once the
+/// negotiation after connecting should be done. Nothing is recorded if the connection closed
+/// in the meantime.
+pub fn spawn_probe_thread(session: Session, connection_id: u16) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("probe-thread".to_string())
+        .spawn(move || {
+            thread::sleep(PROBE_DURATION);
+            let (host, port) = {
+                let connection = session.connection.lock().unwrap();
+                if !connection.connected() || connection.id != connection_id {
+                    return;
+                }
+                (connection.host.clone(), connection.port)
+            };
+            let mut capabilities = session.capabilities.capabilities();
+            capabilities.tls = session.encrypted();
+            capabilities.time = chrono::Local::now().timestamp();
+            debug!("{host}:{port}: {}", capabilities.summary());
+            let names = find_servers(&Servers::load(), &host, port);
+            if !names.is_empty() {
+                let mut infos = ServerInfos::load();
+                for name in names {
+                    infos.entry(name).or_default().capabilities = Some(capabilities.clone());
+                }
+                infos.save();
+            }
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test_capabilities {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let probe = CapabilityProbe::default();
+        probe.protocol(opt::GMCP);
+        probe.protocol(opt::ECHO);
+        probe.protocol(cmd::GA);
+        probe.prompt("EOR");
+        probe.prompt("GA");
+        probe.utf8(true);
+        let capabilities = probe.capabilities();
+        assert_eq!(
+            capabilities.summary(),
+            "Server supports: GMCP, UTF-8; prompt style: EOR"
+        );
+
+        probe.reset();
+        assert_eq!(probe.capabilities(), Capabilities::default());
+    }
+}
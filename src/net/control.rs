@@ -0,0 +1,354 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use anyhow::{bail, Result};
+use log::{debug, error, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    event::Event,
+    model::{Line, Origin},
+    session::Session,
+    DATA_DIR, VERSION,
+};
+
+#[cfg(test)]
+use mockall::automock;
+
+const SOCKET_NAME: &str = "blightmud.sock";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Where the control socket and input pipe are created, `$XDG_RUNTIME_DIR` if it's set.
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| DATA_DIR.clone())
+}
+
+/// Where the control socket is created, see [runtime_dir].
+pub fn control_socket_path() -> PathBuf {
+    runtime_dir().join(SOCKET_NAME)
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// What the control socket needs from the session.
+#[cfg_attr(test, automock)]
+trait Controller {
+    fn send_event(&self, event: Event);
+    fn status(&self) -> Value;
+    fn triggers(&self) -> Value;
+}
+
+impl Controller for Session {
+    fn send_event(&self, event: Event) {
+        self.main_writer.send(event).ok();
+    }
+
+    fn status(&self) -> Value {
+        let connected = self.connected();
+        json!({
+            "version": VERSION,
+            "connected": connected,
+            "host": connected.then(|| self.host()),
+            "port": connected.then(|| self.port()),
+            "tls": connected && self.encrypted(),
+        })
+    }
+
+    fn triggers(&self) -> Value {
+        let triggers = self
+            .lua_script
+            .lock()
+            .map(|script| script.triggers())
+            .unwrap_or_default();
+        json!(triggers)
+    }
+}
+
+fn string_param(params: &Value, name: &str) -> Result<String, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Expected a '{name}' string")))
+}
+
+fn call(controller: &dyn Controller, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "send" => {
+            let mut line = Line::from(string_param(params, "input")?);
+            line.flags.source = Some("user".to_string());
+            controller.send_event(Event::ServerInput(line));
+            Ok(Value::Bool(true))
+        }
+        "output" => {
+            let mut line = Line::from(string_param(params, "text")?);
+            let from_mud = params.get("mud").and_then(Value::as_bool) == Some(true);
+            if !from_mud {
+                line.flags.origin = Origin::Script;
+            }
+            controller.send_event(if from_mud {
+                Event::MudOutput(line)
+            } else {
+                Event::Output(line)
+            });
+            Ok(Value::Bool(true))
+        }
+        "status" => Ok(controller.status()),
+        "triggers" => Ok(controller.triggers()),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method: {method}"),
+        )),
+    }
+}
+
+/// Handles a JSON-RPC 2.0 request and returns the response.
+fn handle_request(controller: &dyn Controller, request: &str) -> Value {
+    let (id, result) = match serde_json::from_str::<Request>(request) {
+        Ok(request) => (
+            request.id,
+            call(controller, &request.method, &request.params),
+        ),
+        Err(err) => (
+            Value::Null,
+            Err(RpcError::new(PARSE_ERROR, err.to_string())),
+        ),
+    };
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    }
+}
+
+fn handle_client(stream: UnixStream, session: &Session) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for request in BufReader::new(stream).lines() {
+        let request = request?;
+        if request.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", handle_request(session, &request))?;
+    }
+    Ok(())
+}
+
+/// The control socket of a running client, which lets external programs send input, print
+/// output and query the session with JSON-RPC. The socket is removed when this is dropped.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    /// Listens on [control_socket_path] unless another instance of Blightmud already does.
+    pub fn start(session: Session) -> Option<Self> {
+        Self::listen(control_socket_path(), session)
+    }
+
+    fn listen(path: PathBuf, session: Session) -> Option<Self> {
+        if UnixStream::connect(&path).is_ok() {
+            info!("Control socket {} is in use", path.display());
+            return None;
+        }
+        fs::remove_file(&path).ok();
+        let listener = match bind_private(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to create control socket {}: {err}", path.display());
+                return None;
+            }
+        };
+        thread::Builder::new()
+            .name("control-thread".to_string())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let session = session.clone();
+                    thread::Builder::new()
+                        .name("control-client-thread".to_string())
+                        .spawn(move || {
+                            if let Err(err) = handle_client(stream, &session) {
+                                debug!("Control client disconnected: {err}");
+                            }
+                        })
+                        .ok();
+                }
+            })
+            .ok()?;
+        Some(Self { path })
+    }
+}
+
+/// Binds a socket only the user can connect to. The umask is set around binding so the socket
+/// is created with those permissions, rather than changing them once others could connect.
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    listener
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Sends a request to the control socket of a running Blightmud and returns the result.
+pub fn control_request(method: &str, params: Value) -> Result<Value> {
+    request(&control_socket_path(), method, params)
+}
+
+fn request(path: &Path, method: &str, params: Value) -> Result<Value> {
+    let stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(err) => bail!("No running Blightmud at {}: {err}", path.display()),
+    };
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(&stream, "{request}")?;
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response)?;
+    let mut response: Value = serde_json::from_str(&response)?;
+    if let Some(message) = response["error"]["message"].as_str() {
+        bail!("{message}");
+    }
+    Ok(response["result"].take())
+}
+
+#[cfg(test)]
+mod test_control {
+    use std::{os::unix::fs::PermissionsExt, sync::mpsc::channel};
+
+    use mockall::predicate::eq;
+
+    use super::*;
+    use crate::session::SessionBuilder;
+
+    #[test]
+    fn test_methods() {
+        let mut controller = MockController::new();
+        let mut input = Line::from("look");
+        input.flags.source = Some("user".to_string());
+        controller
+            .expect_send_event()
+            .with(eq(Event::ServerInput(input)))
+            .times(1)
+            .return_const(());
+        controller
+            .expect_send_event()
+            .with(eq(Event::MudOutput(Line::from("You feel hungry"))))
+            .times(1)
+            .return_const(());
+        controller
+            .expect_send_event()
+            .with(eq(Event::Output(Line::from("Overlay"))))
+            .times(1)
+            .return_const(());
+        controller
+            .expect_status()
+            .times(1)
+            .returning(|| json!({ "connected": false }));
+
+        let response = handle_request(
+            &controller,
+            r#"{"jsonrpc":"2.0","id":7,"method":"send","params":{"input":"look"}}"#,
+        );
+        assert_eq!(
+            response,
+            json!({ "jsonrpc": "2.0", "id": 7, "result": true })
+        );
+        handle_request(
+            &controller,
+            r#"{"id":8,"method":"output","params":{"text":"You feel hungry","mud":true}}"#,
+        );
+        handle_request(
+            &controller,
+            r#"{"id":9,"method":"output","params":{"text":"Overlay"}}"#,
+        );
+        let response = handle_request(&controller, r#"{"id":10,"method":"status"}"#);
+        assert_eq!(response["result"], json!({ "connected": false }));
+    }
+
+    #[test]
+    fn test_control_socket() {
+        let (writer, reader) = channel();
+        let (timer_writer, _timer_reader) = channel();
+        let session = SessionBuilder::new()
+            .main_writer(writer)
+            .timer_writer(timer_writer)
+            .screen_dimensions((80, 80))
+            .build();
+        let path = DATA_DIR.join("control_test.sock");
+        let socket = ControlSocket::listen(path.clone(), session.clone()).unwrap();
+        assert!(ControlSocket::listen(path.clone(), session).is_none());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let status = request(&path, "status", json!({})).unwrap();
+        assert_eq!(status["connected"], false);
+        assert_eq!(status["version"], VERSION);
+        request(&path, "send", json!({ "input": "look" })).unwrap();
+        assert!(reader
+            .try_iter()
+            .any(|event| matches!(event, Event::ServerInput(line) if line.line() == "look")));
+        let err = request(&path, "send", json!({})).unwrap_err();
+        assert_eq!(err.to_string(), "Expected a 'input' string");
+
+        drop(socket);
+        assert!(!path.exists());
+        assert!(request(&path, "status", json!({})).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let controller = MockController::new();
+
+        let response = handle_request(&controller, "not json");
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = handle_request(&controller, r#"{"id":"a","method":"quit"}"#);
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = handle_request(&controller, r#"{"id":1,"method":"send","params":{}}"#);
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(response["error"]["message"], "Expected a 'input' string");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::event::Event;
+use crate::model::{find_servers, Servers};
+use crate::VERSION;
+use std::{sync::mpsc::Sender, thread};
+
+#[cfg(test)]
+use mockall::automock;
+
+use reqwest::blocking::{Client, ClientBuilder};
+use reqwest::header;
+use serde::Deserialize;
+
+/// A server as described by a public MUD list endpoint. The endpoint is expected to return a JSON
+/// array of these objects.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+struct ListedServer {
+    name: String,
+    host: String,
+    port: u16,
+}
+
+#[cfg_attr(test, automock)]
+trait FetchServerList {
+    fn fetch(&self, url: &str) -> Result<Vec<ListedServer>, String>;
+}
+
+struct Fetcher {
+    client: Client,
+}
+
+impl Fetcher {
+    fn new() -> Self {
+        let client = ClientBuilder::new()
+            .default_headers(header::HeaderMap::from_iter(vec![(
+                header::USER_AGENT,
+                // safety: only errors on non-printable characters.
+                header::HeaderValue::from_str(&format!("Blightmud/{}", VERSION)).unwrap(),
+            )]))
+            .build()
+            // safety: errors if TLS backend cannot be initialized, or the resolver cannot load
+            // the system configuration.
+            .expect("failed to initialize reqwest client");
+        Self { client }
+    }
+}
+
+impl FetchServerList for Fetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<ListedServer>, String> {
+        self.client
+            .get(url)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json::<Vec<ListedServer>>())
+            .map_err(|err| err.to_string())
+    }
+}
+
+fn run(writer: Sender<Event>, url: &str, known: &Servers, fetcher: &dyn FetchServerList) {
+    match fetcher.fetch(url) {
+        Ok(mut listed) => {
+            listed.sort_by(|a, b| a.name.cmp(&b.name));
+            let new = listed
+                .into_iter()
+                .filter(|s| find_servers(known, &s.host, s.port).is_empty())
+                .collect::<Vec<ListedServer>>();
+            writer
+                .send(Event::Info(format!(
+                    "Discovered {} new server(s) at {url}",
+                    new.len()
+                )))
+                .unwrap();
+            for server in new {
+                writer
+                    .send(Event::Info(format!(
+                        "{:<20} {}:{}",
+                        server.name, server.host, server.port
+                    )))
+                    .unwrap();
+            }
+        }
+        Err(err) => {
+            writer
+                .send(Event::Error(format!("Failed to fetch server list: {err}")))
+                .unwrap();
+        }
+    }
+}
+
+/// Fetch a list of servers from `url` and write [Event::Info] messages describing the ones that
+/// aren't saved yet to the provided [Sender].
+pub fn discover_servers(writer: Sender<Event>, url: String, known: Servers) {
+    thread::Builder::new()
+        .name("discover-servers-thread".to_string())
+        .spawn(move || {
+            run(writer, &url, &known, &Fetcher::new());
+        })
+        .ok();
+}
+
+#[cfg(test)]
+mod test_discover_servers {
+    use crate::event::Event;
+    use crate::model::{Connection, Servers};
+    use crate::net::discover_servers::{run, ListedServer, MockFetchServerList};
+
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    #[test]
+    fn test_discover() {
+        let mut fetcher = MockFetchServerList::new();
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+
+        fetcher.expect_fetch().times(1).returning(|_| {
+            Ok(vec![
+                ListedServer {
+                    name: "Zmud".to_string(),
+                    host: "z.example.com".to_string(),
+                    port: 4000,
+                },
+                ListedServer {
+                    name: "Amud".to_string(),
+                    host: "a.example.com".to_string(),
+                    port: 23,
+                },
+            ])
+        });
+
+        let mut known = Servers::new();
+        known.insert(
+            "z".to_string(),
+            Connection::new("z.example.com", 4000, false, false),
+        );
+
+        run(writer, "https://example.com/list", &known, &fetcher);
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Info("Discovered 1 new server(s) at https://example.com/list".to_string())
+        );
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Info(format!("{:<20} a.example.com:23", "Amud"))
+        );
+        assert!(reader.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_discover_error() {
+        let mut fetcher = MockFetchServerList::new();
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+
+        fetcher
+            .expect_fetch()
+            .times(1)
+            .returning(|_| Err("timeout".to_string()));
+
+        run(
+            writer,
+            "https://example.com/list",
+            &Servers::new(),
+            &fetcher,
+        );
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Error("Failed to fetch server list: timeout".to_string())
+        );
+    }
+}
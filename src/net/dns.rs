@@ -0,0 +1,352 @@
+use std::{
+    fmt::{Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    str::FromStr,
+    sync::mpsc::channel,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::io::SaveData;
+
+const DNS_PORT: u16 = 53;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+/// Without EDNS a server answers over UDP in at most this many bytes.
+const MAX_RESPONSE: usize = 512;
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// Which address family is tried first when a host has both.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpPreference {
+    #[default]
+    Ipv6,
+    Ipv4,
+}
+
+impl Display for IpPreference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ipv6 => write!(f, "ipv6"),
+            Self::Ipv4 => write!(f, "ipv4"),
+        }
+    }
+}
+
+impl FromStr for IpPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ipv6" => Ok(Self::Ipv6),
+            "ipv4" => Ok(Self::Ipv4),
+            _ => bail!("Unknown address family: {s}, use ipv4 or ipv6"),
+        }
+    }
+}
+
+/// How host names are resolved when connecting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsSettings {
+    pub prefer: IpPreference,
+    /// Name servers asked in order instead of the system resolver, which is used if there are
+    /// none.
+    pub servers: Vec<IpAddr>,
+    /// Seconds to wait for an answer, from each name server.
+    pub timeout: u64,
+}
+
+impl Default for DnsSettings {
+    fn default() -> Self {
+        Self {
+            prefer: IpPreference::default(),
+            servers: vec![],
+            timeout: 5,
+        }
+    }
+}
+
+impl SaveData for DnsSettings {
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn relative_path() -> std::path::PathBuf {
+        crate::CONFIG_DIR.join("dns.ron")
+    }
+
+    fn on_load(&mut self) {
+        self.timeout = self.timeout.max(1);
+    }
+}
+
+/// The addresses a host resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// Who answered: the system resolver or a name server, nobody for an IP address.
+    pub source: String,
+    /// In the order a connection tries them.
+    pub addresses: Vec<SocketAddr>,
+}
+
+/// Resolves a host with the given settings. Errors name the host and what failed, as the
+/// system resolver's own errors rarely say.
+pub fn resolve(host: &str, port: u16, settings: &DnsSettings) -> Result<Resolution> {
+    debug!("resolving IP addresses for {host}:{port}");
+    let timeout = Duration::from_secs(settings.timeout.max(1));
+    let (source, addresses) = if let Ok(ip) = host.parse::<IpAddr>() {
+        (
+            "no lookup needed".to_string(),
+            vec![SocketAddr::new(ip, port)],
+        )
+    } else if settings.servers.is_empty() {
+        (
+            "system resolver".to_string(),
+            resolve_system(host, port, timeout)?,
+        )
+    } else {
+        let mut last_error = anyhow!("no name servers");
+        let mut answer = None;
+        for server in &settings.servers {
+            match resolve_with(*server, host, timeout) {
+                Ok(ips) => {
+                    let addresses = ips.into_iter().map(|ip| SocketAddr::new(ip, port));
+                    answer = Some((format!("name server {server}"), addresses.collect()));
+                    break;
+                }
+                Err(err) => {
+                    debug!("name server {server} failed: {err}");
+                    last_error = anyhow!("{server}: {err}");
+                }
+            }
+        }
+        answer.ok_or_else(|| anyhow!("Failed to resolve {host} ({last_error})"))?
+    };
+    if addresses.is_empty() {
+        bail!("Failed to resolve {host}: {source} found no addresses");
+    }
+    let addresses = order_addresses(addresses, settings.prefer);
+    debug!("resolved {} potential addresses", addresses.len());
+    Ok(Resolution { source, addresses })
+}
+
+/// Resolves with the system resolver, giving up after `timeout`. The lookup itself can't be
+/// cancelled so it finishes in the background.
+fn resolve_system(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>> {
+    let (tx, rx) = channel();
+    let target = (host.to_string(), port);
+    thread::Builder::new()
+        .name("dns-thread".to_string())
+        .spawn(move || {
+            let result = target
+                .to_socket_addrs()
+                .map(|addrs| addrs.collect::<Vec<_>>());
+            tx.send(result).ok();
+        })?;
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result.map_err(|err| anyhow!("Failed to resolve {host}: {err}")),
+        Err(_) => bail!(
+            "Failed to resolve {host}: no answer within {}s",
+            timeout.as_secs()
+        ),
+    }
+}
+
+/// Asks a name server for the IPv4 and IPv6 addresses of a host.
+fn resolve_with(server: IpAddr, host: &str, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let mut ips = query(server, host, TYPE_A, timeout)?;
+    ips.extend(query(server, host, TYPE_AAAA, timeout)?);
+    Ok(ips)
+}
+
+fn query(server: IpAddr, host: &str, record_type: u16, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let local: SocketAddr = match server {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect((server, DNS_PORT))?;
+    let id = query_id();
+    socket.send(&build_query(id, host, record_type)?)?;
+    let mut response = [0; MAX_RESPONSE];
+    let len = socket.recv(&mut response).map_err(|err| match err.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+            anyhow!("no answer within {}s", timeout.as_secs())
+        }
+        _ => err.into(),
+    })?;
+    parse_response(id, &response[..len])
+}
+
+fn query_id() -> u16 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    (nanos ^ std::process::id()) as u16
+}
+
+fn build_query(id: u16, host: &str, record_type: u16) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(MAX_RESPONSE);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("Invalid host name: {host}");
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Reads the addresses from a response, skipping CNAMEs and other records. A name that doesn't
+/// exist has no addresses.
+fn parse_response(id: u16, response: &[u8]) -> Result<Vec<IpAddr>> {
+    let read_u16 = |pos: usize| -> Result<u16> {
+        response
+            .get(pos..pos + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(|| anyhow!("truncated response"))
+    };
+    if read_u16(0)? != id {
+        bail!("response to another query");
+    }
+    match read_u16(2)? & 0x000f {
+        0 | RCODE_NXDOMAIN => {}
+        rcode => bail!("server error (rcode {rcode})"),
+    }
+    let questions = read_u16(4)?;
+    let answers = read_u16(6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(response, pos)? + 4;
+    }
+    let mut ips = vec![];
+    for _ in 0..answers {
+        pos = skip_name(response, pos)?;
+        let record_type = read_u16(pos)?;
+        let len = read_u16(pos + 8)? as usize;
+        let data = response
+            .get(pos + 10..pos + 10 + len)
+            .ok_or_else(|| anyhow!("truncated response"))?;
+        match record_type {
+            TYPE_A if len == 4 => ips.push(IpAddr::from(<[u8; 4]>::try_from(data)?)),
+            TYPE_AAAA if len == 16 => ips.push(IpAddr::from(<[u8; 16]>::try_from(data)?)),
+            _ => {}
+        }
+        pos += 10 + len;
+    }
+    Ok(ips)
+}
+
+/// Returns where the name starting at `pos` ends.
+fn skip_name(response: &[u8], mut pos: usize) -> Result<usize> {
+    loop {
+        match response.get(pos) {
+            None => bail!("truncated response"),
+            Some(0) => return Ok(pos + 1),
+            // A pointer to a name earlier in the response
+            Some(len) if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            Some(len) => pos += 1 + *len as usize,
+        }
+    }
+}
+
+/// Interleaves the address families, starting with the preferred one, so a connection falls
+/// back to the other family quickly when one is broken.
+fn order_addresses(addresses: Vec<SocketAddr>, prefer: IpPreference) -> Vec<SocketAddr> {
+    let (addrs_v6, addrs_v4): (Vec<_>, Vec<_>) =
+        addresses.into_iter().partition(|addr| addr.is_ipv6());
+    let (first, second) = match prefer {
+        IpPreference::Ipv6 => (addrs_v6, addrs_v4),
+        IpPreference::Ipv4 => (addrs_v4, addrs_v6),
+    };
+    let mut addrs = Vec::with_capacity(first.len() + second.len());
+    let (mut left, mut right) = (first.into_iter(), second.into_iter());
+    while let Some(addr) = left.next() {
+        addrs.push(addr);
+        std::mem::swap(&mut left, &mut right);
+    }
+    addrs.extend(right);
+    addrs
+}
+
+#[cfg(test)]
+mod test_dns {
+    use super::*;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_order_addresses() {
+        let addresses = vec![addr("10.0.0.1:23"), addr("10.0.0.2:23"), addr("[::1]:23")];
+        assert_eq!(
+            order_addresses(addresses.clone(), IpPreference::Ipv6),
+            vec![addr("[::1]:23"), addr("10.0.0.1:23"), addr("10.0.0.2:23")]
+        );
+        assert_eq!(
+            order_addresses(addresses, IpPreference::Ipv4),
+            vec![addr("10.0.0.1:23"), addr("[::1]:23"), addr("10.0.0.2:23")]
+        );
+    }
+
+    #[test]
+    fn test_ip_address() {
+        let resolution = resolve("127.0.0.1", 4000, &DnsSettings::default()).unwrap();
+        assert_eq!(resolution.addresses, vec![addr("127.0.0.1:4000")]);
+        assert_eq!(resolution.source, "no lookup needed");
+    }
+
+    #[test]
+    fn test_build_query() {
+        assert_eq!(
+            build_query(0x1234, "mud.org.", TYPE_A).unwrap(),
+            [
+                &[0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0][..],
+                b"\x03mud\x03org\x00",
+                &[0, 1, 0, 1],
+            ]
+            .concat()
+        );
+        assert!(build_query(1, "mud..org", TYPE_A).is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut response = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 3, 0, 0, 0, 0];
+        response.extend_from_slice(b"\x03mud\x03org\x00\x00\x01\x00\x01");
+        // A CNAME pointing at the question's name, then two addresses
+        response.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        response.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 1]);
+        response.extend_from_slice(&[0xc0, 12, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16]);
+        response.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(
+            parse_response(0x1234, &response).unwrap(),
+            vec![
+                IpAddr::from([10, 0, 0, 1]),
+                IpAddr::from(Ipv6Addr::LOCALHOST)
+            ]
+        );
+        assert!(parse_response(0x4321, &response).is_err());
+        assert!(parse_response(0x1234, &response[..response.len() - 1]).is_err());
+
+        let nxdomain = [0x12, 0x34, 0x81, 0x83, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(parse_response(0x1234, &nxdomain).unwrap().is_empty());
+        let refused = [0x12, 0x34, 0x81, 0x85, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(parse_response(0x1234, &refused).is_err());
+    }
+}
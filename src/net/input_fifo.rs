@@ -0,0 +1,191 @@
+use std::{
+    ffi::CString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+};
+
+use anyhow::{bail, Result};
+use log::{debug, error};
+
+use super::control::runtime_dir;
+use crate::{event::Event, model::Line};
+
+const FIFO_NAME: &str = "blightmud.in";
+
+/// Where the input pipe is created, see [runtime_dir].
+pub fn input_fifo_path() -> PathBuf {
+    runtime_dir().join(FIFO_NAME)
+}
+
+/// Refuses pipes that someone else could write commands to.
+fn check_permissions(path: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.file_type().is_fifo() {
+        bail!("{} exists and isn't a named pipe", path.display());
+    }
+    if metadata.uid() != unsafe { libc::getuid() } {
+        bail!("{} belongs to another user", path.display());
+    }
+    if metadata.mode() & 0o077 != 0 {
+        bail!("{} can be written by other users", path.display());
+    }
+    Ok(())
+}
+
+fn create_fifo(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn read_commands(file: File, writer: &Sender<Event>) {
+    for line in BufReader::new(file).lines().map_while(|line| line.ok()) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut line = Line::from(line);
+        line.flags.source = Some("user".to_string());
+        writer.send(Event::ServerInput(line)).ok();
+    }
+}
+
+/// A named pipe external programs can write commands to, one per line. The commands are handled
+/// as if they were typed, so aliases apply. The pipe is removed when this is dropped.
+pub struct InputFifo {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+}
+
+impl InputFifo {
+    /// Opens the pipe at [input_fifo_path], creating it if needed.
+    pub fn start(writer: Sender<Event>) -> Option<Self> {
+        Self::start_at(input_fifo_path(), writer)
+    }
+
+    /// Opens the pipe at `path`, showing why on screen if it can't be used.
+    fn start_at(path: PathBuf, writer: Sender<Event>) -> Option<Self> {
+        match Self::open(path, writer.clone()) {
+            Ok(fifo) => Some(fifo),
+            Err(err) => {
+                error!("Failed to open the input pipe: {err}");
+                writer
+                    .send(Event::Error(format!(
+                        "Failed to open the input pipe: {err}"
+                    )))
+                    .ok();
+                None
+            }
+        }
+    }
+
+    fn open(path: PathBuf, writer: Sender<Event>) -> Result<Self> {
+        if !path.exists() {
+            create_fifo(&path)?;
+        }
+        check_permissions(&path)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_path = path.clone();
+        let thread_stopped = stopped.clone();
+        thread::Builder::new()
+            .name("input-fifo-thread".to_string())
+            .spawn(move || {
+                // Opening blocks until a writer shows up and reading ends when the last writer
+                // closes the pipe, so it's reopened for the next one.
+                while let Ok(file) = File::open(&thread_path) {
+                    if thread_stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    read_commands(file, &writer);
+                }
+                debug!("Input pipe closed");
+            })?;
+        Ok(Self { path, stopped })
+    }
+}
+
+impl Drop for InputFifo {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        // Wakes the reading thread if it's waiting for a writer
+        OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)
+            .ok();
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod test_input_fifo {
+    use std::{io::Write, os::unix::fs::PermissionsExt, sync::mpsc::channel, time::Duration};
+
+    use super::*;
+    use crate::DATA_DIR;
+
+    #[test]
+    fn test_commands() {
+        let path = DATA_DIR.join("input_fifo_test.in");
+        fs::remove_file(&path).ok();
+        let (writer, reader) = channel();
+        let fifo = InputFifo::open(path.clone(), writer).unwrap();
+
+        let mut pipe = OpenOptions::new().write(true).open(&path).unwrap();
+        writeln!(pipe, "look\n\nsay hi").unwrap();
+        drop(pipe);
+        for expected in ["look", "say hi"] {
+            match reader.recv_timeout(Duration::from_secs(5)) {
+                Ok(Event::ServerInput(line)) => {
+                    assert_eq!(line.line(), expected);
+                    assert_eq!(line.flags.source.as_deref(), Some("user"));
+                }
+                other => panic!("Unexpected event: {other:?}"),
+            }
+        }
+
+        drop(fifo);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_start_error() {
+        let path = DATA_DIR.join("input_fifo_error.in");
+        fs::remove_file(&path).ok();
+        fs::write(&path, "").unwrap();
+        let (writer, reader) = channel();
+        assert!(InputFifo::start_at(path.clone(), writer).is_none());
+        match reader.try_recv() {
+            Ok(Event::Error(msg)) => assert!(msg.starts_with("Failed to open the input pipe")),
+            other => panic!("Unexpected event: {other:?}"),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_permissions() {
+        let path = DATA_DIR.join("input_fifo_perms.in");
+        fs::remove_file(&path).ok();
+        fs::write(&path, "").unwrap();
+        assert!(check_permissions(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        create_fifo(&path).unwrap();
+        assert!(check_permissions(&path).is_ok());
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o622)).unwrap();
+        assert!(check_permissions(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,19 +1,44 @@
 pub use self::{
+    capabilities::{spawn_probe_thread, CapabilityProbe},
     check_version::check_latest_version,
+    control::{control_request, ControlSocket},
+    discover_servers::discover_servers,
+    dns::{resolve, DnsSettings},
+    input_fifo::InputFifo,
+    mssp::{parse_mssp, MsspValues},
     mud_connection::MudConnection,
     output_buffer::OutputBuffer,
+    relay::{RelayWorker, WebhookFormat},
     rw_stream::RwStream,
-    tcp_stream::{spawn_connect_thread, spawn_receive_thread, spawn_transmit_thread, BUFFER_SIZE},
-    telnet::{TelnetHandler, TelnetMode},
+    stream_filter::{StreamDirection, StreamFilters},
+    tcp_stream::{
+        spawn_connect_thread, spawn_connect_thread_with_retries, spawn_receive_thread,
+        spawn_transmit_thread, BUFFER_SIZE, STARTUP_RETRY_DELAYS,
+    },
+    telnet::{accepted_charset_is_utf8, StreamChange, TelnetHandler, TelnetMode, START_TLS},
     tls::CertificateValidation,
     util::open_tcp_stream,
+    watchdog::{Watchdog, WatchdogStatus},
+    web::{MirrorKind, WebMirror, WebStatus},
 };
 
+mod capabilities;
 mod check_version;
+mod control;
+mod discover_servers;
+mod dns;
+mod input_fifo;
+mod mssp;
 mod mud_connection;
 mod output_buffer;
+mod relay;
 mod rw_stream;
+mod stream_filter;
 mod tcp_stream;
 mod telnet;
 mod tls;
 mod util;
+mod watchdog;
+mod web;
+#[cfg(feature = "web")]
+mod web_server;
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+const MSSP_VAR: u8 = 1;
+const MSSP_VAL: u8 = 2;
+
+/// MSSP variables and their values. A variable can be sent with several values.
+pub type MsspValues = BTreeMap<String, Vec<String>>;
+
+/// Parses an MSSP subnegotiation payload. Bytes before the first variable are skipped and a
+/// variable without values gets an empty one.
+pub fn parse_mssp(data: &[u8]) -> MsspValues {
+    let mut values = MsspValues::new();
+    let mut iter = data.iter().skip_while(|b| **b != MSSP_VAR).peekable();
+    while iter.next().is_some() {
+        let mut key = vec![];
+        while let Some(b) = iter.next_if(|b| **b != MSSP_VAL && **b != MSSP_VAR) {
+            key.push(*b);
+        }
+        let mut vals = vec![];
+        while iter.next_if(|b| **b == MSSP_VAL).is_some() {
+            let mut val = vec![];
+            while let Some(b) = iter.next_if(|b| **b != MSSP_VAL && **b != MSSP_VAR) {
+                val.push(*b);
+            }
+            vals.push(String::from_utf8_lossy(&val).to_string());
+        }
+        if vals.is_empty() {
+            vals.push(String::new());
+        }
+        values
+            .entry(String::from_utf8_lossy(&key).to_string())
+            .or_default()
+            .extend(vals);
+    }
+    values
+}
+
+#[cfg(test)]
+mod test_mssp {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_mssp() {
+        let mut data = vec![];
+        for (key, vals) in [("NAME", vec!["Blight"]), ("PORT", vec!["4000", "4001"])] {
+            data.push(MSSP_VAR);
+            data.extend(key.bytes());
+            for val in vals {
+                data.push(MSSP_VAL);
+                data.extend(val.bytes());
+            }
+        }
+        let values = parse_mssp(&data);
+        assert_eq!(values["NAME"], vec!["Blight"]);
+        assert_eq!(values["PORT"], vec!["4000", "4001"]);
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_malformed_mssp() {
+        assert!(parse_mssp(&[]).is_empty());
+        assert!(parse_mssp(b"junk").is_empty());
+        let values = parse_mssp(&[b'x', MSSP_VAR, b'A', MSSP_VAR, b'B', MSSP_VAL, b'1']);
+        assert_eq!(values["A"], vec![""]);
+        assert_eq!(values["B"], vec!["1"]);
+    }
+}
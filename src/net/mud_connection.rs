@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use lazy_static::lazy_static;
 use log::debug;
 use std::{
@@ -10,6 +10,7 @@ use std::{
 };
 
 use crate::net::open_tcp_stream;
+use crate::net::telnet::START_TLS_FOLLOWS;
 use crate::net::tls::{CertificateValidation, TlsStream};
 
 use super::RwStream;
@@ -18,7 +19,9 @@ use super::RwStream;
 pub struct MudConnection {
     pub id: u16,
     stream: Option<RwStream<TcpStream>>,
-    tls_stream: Option<TlsStream>,
+    /// Shared with the clones reading and writing, so they all switch over when a plain
+    /// connection is upgraded to TLS.
+    tls_stream: Arc<Mutex<Option<TlsStream>>>,
     pub host: String,
     pub port: u16,
     pub tls: bool,
@@ -38,7 +41,7 @@ impl MudConnection {
         Self {
             id: connection_id(),
             stream: None,
-            tls_stream: None,
+            tls_stream: Arc::new(Mutex::new(None)),
             host: "0.0.0.0".to_string(),
             port: 4000,
             tls: false,
@@ -46,19 +49,23 @@ impl MudConnection {
         }
     }
 
-    fn get_input_stream(&self) -> Option<&Arc<Mutex<dyn Read + Send>>> {
-        if let Some(stream) = &self.tls_stream {
-            Some(&stream.input_stream)
+    fn get_input_stream(&self) -> Option<Arc<Mutex<dyn Read + Send>>> {
+        if let Some(stream) = self.tls_stream.lock().unwrap().as_ref() {
+            Some(stream.input_stream.clone())
         } else {
-            self.stream.as_ref().map(|stream| &stream.input_stream)
+            self.stream
+                .as_ref()
+                .map(|stream| stream.input_stream.clone())
         }
     }
 
-    fn get_output_stream(&self) -> Option<&Arc<Mutex<dyn Write + Send>>> {
-        if let Some(stream) = &self.tls_stream {
-            Some(&stream.output_stream)
+    fn get_output_stream(&self) -> Option<Arc<Mutex<dyn Write + Send>>> {
+        if let Some(stream) = self.tls_stream.lock().unwrap().as_ref() {
+            Some(stream.output_stream.clone())
         } else {
-            self.stream.as_ref().map(|stream| &stream.output_stream)
+            self.stream
+                .as_ref()
+                .map(|stream| stream.output_stream.clone())
         }
     }
 
@@ -81,39 +88,78 @@ impl MudConnection {
 
         let stream = open_tcp_stream(&self.host, self.port)?;
         if tls {
-            self.tls_stream = Some(TlsStream::tls_init(stream, host, tls_validation)?);
+            let tls_stream = TlsStream::tls_init(stream, host, tls_validation)?;
+            self.tls_stream = Arc::new(Mutex::new(Some(tls_stream)));
+            self.stream = None;
         } else {
+            self.tls_stream = Arc::new(Mutex::new(None));
             self.stream = Some(RwStream::new(stream));
         }
         self.id = connection_id();
         Ok(())
     }
 
+    /// Upgrades a plain connection to TLS once the server is ready for the handshake
+    /// (START-TLS). The server is told the handshake follows before it's done here, while
+    /// writes by other threads wait for it.
+    pub fn start_tls(&self) -> Result<()> {
+        let Some(stream) = &self.stream else {
+            bail!("Not connected");
+        };
+        let mut tls_stream = self.tls_stream.lock().unwrap();
+        if tls_stream.is_some() {
+            bail!("The connection already uses TLS");
+        }
+        debug!("Starting TLS with {}:{}", self.host, self.port);
+        if let Ok(mut output) = stream.output_stream.lock() {
+            output.write_all(&START_TLS_FOLLOWS)?;
+            output.flush()?;
+        }
+        let upgraded =
+            TlsStream::tls_init(stream.inner().try_clone()?, &self.host, self.tls_validation)?;
+        {
+            let tls = upgraded.inner_mut();
+            while tls.conn.is_handshaking() {
+                tls.conn.complete_io(&mut tls.sock)?;
+            }
+        }
+        *tls_stream = Some(upgraded);
+        debug!("TLS started with {}:{}", self.host, self.port);
+        Ok(())
+    }
+
     pub fn disconnect(&mut self) -> Result<()> {
-        if let Some(stream) = &self.stream {
+        let tls_stream = self.tls_stream.lock().unwrap().take();
+        if let Some(stream) = tls_stream {
             debug!("Disconnecting from {}:{}", self.host, self.port);
-            stream.inner().shutdown(Shutdown::Both)?;
+            stream.inner_mut().conn.send_close_notify();
+            stream.inner().sock.shutdown(Shutdown::Both)?;
             debug!("Disconnected from {}:{}", self.host, self.port);
             self.stream = None;
-        } else if let Some(stream) = &self.tls_stream {
+        } else if let Some(stream) = &self.stream {
             debug!("Disconnecting from {}:{}", self.host, self.port);
-            stream.inner_mut().conn.send_close_notify();
-            stream.inner().sock.shutdown(Shutdown::Both)?;
+            stream.inner().shutdown(Shutdown::Both)?;
             debug!("Disconnected from {}:{}", self.host, self.port);
-            self.tls_stream = None;
+            self.stream = None;
         }
         Ok(())
     }
 
+    /// Whether the connection uses TLS, either from the start or after an upgrade. `tls` only
+    /// says the former, which is what reconnecting needs.
+    pub fn encrypted(&self) -> bool {
+        self.tls_stream.lock().unwrap().is_some()
+    }
+
     pub fn connected(&self) -> bool {
-        self.stream.is_some() || self.tls_stream.is_some()
+        self.stream.is_some() || self.tls_stream.lock().unwrap().is_some()
     }
 }
 
 impl Read for MudConnection {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let mut result = Ok(0);
-        if let Some(stream) = &mut self.get_input_stream() {
+        if let Some(stream) = self.get_input_stream() {
             if let Ok(mut stream) = stream.lock() {
                 result = stream.read(buf);
             }
@@ -125,7 +171,7 @@ impl Read for MudConnection {
 impl Write for MudConnection {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let mut result = Ok(0);
-        if let Some(stream) = &mut self.get_output_stream() {
+        if let Some(stream) = self.get_output_stream() {
             if let Ok(mut stream) = stream.lock() {
                 result = stream.write(buf);
             }
@@ -135,7 +181,7 @@ impl Write for MudConnection {
 
     fn flush(&mut self) -> std::io::Result<()> {
         let mut result = Ok(());
-        if let Some(stream) = &mut self.get_output_stream() {
+        if let Some(stream) = self.get_output_stream() {
             if let Ok(mut stream) = stream.lock() {
                 result = stream.flush();
             }
@@ -145,7 +191,7 @@ impl Write for MudConnection {
 
     fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
         let mut result = Ok(());
-        if let Some(stream) = &mut self.get_output_stream() {
+        if let Some(stream) = self.get_output_stream() {
             if let Ok(mut stream) = stream.lock() {
                 result = stream.write_all(buf);
             }
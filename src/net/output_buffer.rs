@@ -1,6 +1,6 @@
 use log::debug;
 
-use crate::model::Line;
+use crate::model::{Line, MinContrast, Palette};
 
 use super::{tcp_stream::BUFFER_SIZE, telnet::TelnetMode};
 
@@ -8,6 +8,12 @@ pub struct OutputBuffer {
     buffer: Vec<u8>,
     telnet_mode: TelnetMode,
     new_data: bool,
+    /// The server agreed to send UTF-8 over CHARSET.
+    utf8: bool,
+    /// Colors of the server shown as other colors.
+    palette: Palette,
+    /// Keeps the colors of the server readable on the terminal background.
+    min_contrast: Option<MinContrast>,
 }
 
 impl OutputBuffer {
@@ -16,6 +22,9 @@ impl OutputBuffer {
             buffer: Vec::with_capacity(BUFFER_SIZE),
             telnet_mode: telnet_mode.clone(),
             new_data: false,
+            utf8: false,
+            palette: Palette::default(),
+            min_contrast: None,
         }
     }
 
@@ -23,9 +32,50 @@ impl OutputBuffer {
         self.telnet_mode = mode.clone();
     }
 
+    pub fn set_utf8(&mut self, utf8: bool) {
+        self.utf8 = utf8;
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn set_min_contrast(&mut self, min_contrast: Option<MinContrast>) {
+        self.min_contrast = min_contrast;
+    }
+
+    /// Decodes received text, remaps its colors and keeps them readable. Unless the server agreed to send UTF-8,
+    /// bytes that aren't valid UTF-8 are taken as Latin-1 rather than replaced.
+    fn decode(&self, bytes: &[u8]) -> Line {
+        let line = if self.utf8 {
+            Line::from(bytes)
+        } else {
+            let mut text = String::with_capacity(bytes.len());
+            for chunk in bytes.utf8_chunks() {
+                text.push_str(chunk.valid());
+                text.extend(chunk.invalid().iter().map(|byte| char::from(*byte)));
+            }
+            Line::from(text)
+        };
+        let line = if self.palette.is_empty() {
+            line
+        } else {
+            Line::from(self.palette.apply(line.line()))
+        };
+        match &self.min_contrast {
+            Some(min_contrast) => Line::from(min_contrast.apply(line.line())),
+            None => line,
+        }
+    }
+
     pub fn buffer_to_prompt(&mut self, consume_buffer: bool) -> Line {
-        let mut prompt = if !self.buffer.is_empty() {
-            Line::from(&self.buffer)
+        // A character can be cut off at the end of what has been received so far.
+        let end = match std::str::from_utf8(&self.buffer) {
+            Err(err) if !consume_buffer && err.error_len().is_none() => err.valid_up_to(),
+            _ => self.buffer.len(),
+        };
+        let mut prompt = if end > 0 {
+            self.decode(&self.buffer[..end])
         } else {
             Line::from("")
         };
@@ -60,7 +110,7 @@ impl OutputBuffer {
                     lines.push(Line::from("".to_string()));
                     cut_len
                 } else {
-                    let mut line = Line::from(&self.buffer[last_cut..i]);
+                    let mut line = self.decode(&self.buffer[last_cut..i]);
                     if self.telnet_mode == TelnetMode::UnterminatedPrompt
                         && last_cut == 0
                         && existing_buffer_len > 0
@@ -95,6 +145,7 @@ impl OutputBuffer {
     pub fn clear(&mut self) {
         self.buffer.clear();
         self.telnet_mode = TelnetMode::default();
+        self.utf8 = false;
     }
 
     #[cfg(test)]
@@ -111,7 +162,10 @@ impl OutputBuffer {
 mod output_buffer_tests {
 
     use super::OutputBuffer;
-    use crate::{model::Line, net::TelnetMode};
+    use crate::{
+        model::{Color, Line, MinContrast, Palette},
+        net::TelnetMode,
+    };
 
     #[test]
     fn test_prompt_capture() {
@@ -224,4 +278,50 @@ mod output_buffer_tests {
         assert_eq!(iter.next(), Some(&Line::from("line 5")));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn test_8bit_text() {
+        let mut buffer = OutputBuffer::new(&TelnetMode::default());
+        let lines = buffer.receive(b"caf\xe9 \xc3\xa9\r\n");
+        assert_eq!(lines, vec![Line::from("café é")]);
+        buffer.set_utf8(true);
+        let lines = buffer.receive(b"caf\xe9 \xc3\xa9\r\n");
+        assert_eq!(lines, vec![Line::from("caf\u{fffd} é")]);
+        buffer.clear();
+        let lines = buffer.receive(b"caf\xe9\r\n");
+        assert_eq!(lines, vec![Line::from("café")]);
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut buffer = OutputBuffer::new(&TelnetMode::default());
+        let mut palette = Palette::default();
+        palette.set(Color::Indexed(4), Some(Color::Indexed(14)));
+        buffer.set_palette(palette);
+        let lines = buffer.receive(b"\x1b[34mThe sea\x1b[0m\r\n");
+        assert_eq!(lines, vec![Line::from("\x1b[96mThe sea\x1b[0m")]);
+        buffer.receive(b"\x1b[34m> ");
+        assert_eq!(buffer.buffer_to_prompt(false), Line::from("\x1b[96m> "));
+    }
+
+    #[test]
+    fn test_min_contrast() {
+        let mut buffer = OutputBuffer::new(&TelnetMode::default());
+        buffer.set_min_contrast(Some(MinContrast {
+            background: Color::Indexed(0),
+            ratio: 3.0,
+        }));
+        let lines = buffer.receive(b"\x1b[38;5;233mShadows\x1b[0m\r\n\x1b[37mLight\r\n");
+        assert!(lines[0].line().starts_with("\x1b[38;2;"));
+        assert_eq!(lines[1], Line::from("\x1b[37mLight"));
+    }
+
+    #[test]
+    fn test_prompt_cut_off_character() {
+        let mut buffer = OutputBuffer::new(&TelnetMode::default());
+        buffer.receive(b"HP \xe2\x99");
+        assert_eq!(buffer.buffer_to_prompt(false), Line::from("HP "));
+        buffer.receive(b"\xa5 >");
+        assert_eq!(buffer.buffer_to_prompt(true), Line::from("HP \u{2665} >"));
+    }
 }
@@ -0,0 +1,376 @@
+use crate::event::Event;
+use crate::model::{Line, Origin};
+use crate::VERSION;
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Sender, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+use mockall::automock;
+
+use reqwest::blocking::{Client, ClientBuilder};
+use reqwest::header;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Messages waiting to be posted before more are dropped, so a burst of tagged lines can't queue
+/// up without bound behind a slow webhook.
+const QUEUE_SIZE: usize = 64;
+
+/// The payload format expected by a webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Discord,
+    Slack,
+}
+
+impl WebhookFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "discord" => Some(Self::Discord),
+            "slack" => Some(Self::Slack),
+            _ => None,
+        }
+    }
+
+    fn payload(&self, text: &str) -> serde_json::Value {
+        match self {
+            Self::Discord => json!({ "content": text }),
+            Self::Slack => json!({ "text": text }),
+        }
+    }
+}
+
+/// A message returned from a listen endpoint. The endpoint is expected to return a JSON array of
+/// these objects with ids greater than the `since` query parameter.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+struct RelayMessage {
+    id: u64,
+    author: Option<String>,
+    content: String,
+}
+
+impl RelayMessage {
+    fn line(&self) -> Line {
+        let mut line = match &self.author {
+            Some(author) => Line::from(format!("[relay] {author}: {}", self.content)),
+            None => Line::from(format!("[relay] {}", self.content)),
+        };
+        line.flags.origin = Origin::Client;
+        line
+    }
+}
+
+#[cfg_attr(test, automock)]
+trait RelayClient {
+    fn post(&self, url: &str, body: serde_json::Value) -> Result<(), String>;
+    fn fetch(&self, url: &str, since: u64) -> Result<Vec<RelayMessage>, String>;
+}
+
+#[derive(Clone)]
+struct HttpClient {
+    client: Client,
+}
+
+impl HttpClient {
+    fn new() -> Self {
+        let client = ClientBuilder::new()
+            .default_headers(header::HeaderMap::from_iter(vec![(
+                header::USER_AGENT,
+                // safety: only errors on non-printable characters.
+                header::HeaderValue::from_str(&format!("Blightmud/{}", VERSION)).unwrap(),
+            )]))
+            .timeout(Duration::from_secs(10))
+            .build()
+            // safety: errors if TLS backend cannot be initialized, or the resolver cannot load
+            // the system configuration.
+            .expect("failed to initialize reqwest client");
+        Self { client }
+    }
+}
+
+impl RelayClient for HttpClient {
+    fn post(&self, url: &str, body: serde_json::Value) -> Result<(), String> {
+        self.client
+            .post(url)
+            .json(&body)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    fn fetch(&self, url: &str, since: u64) -> Result<Vec<RelayMessage>, String> {
+        self.client
+            .get(url)
+            .query(&[("since", since)])
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json::<Vec<RelayMessage>>())
+            .map_err(|err| err.to_string())
+    }
+}
+
+fn post(
+    writer: &Sender<Event>,
+    url: &str,
+    format: WebhookFormat,
+    text: &str,
+    client: &dyn RelayClient,
+) {
+    if let Err(err) = client.post(url, format.payload(text)) {
+        writer
+            .send(Event::Error(format!("Failed to relay message: {err}")))
+            .unwrap();
+    }
+}
+
+/// Fetches new messages from the listen endpoint and writes them as [Event::Output], returning
+/// the id of the last message seen.
+fn poll(writer: &Sender<Event>, url: &str, since: u64, client: &dyn RelayClient) -> u64 {
+    match client.fetch(url, since) {
+        Ok(mut messages) => {
+            messages.sort_by_key(|msg| msg.id);
+            let mut last = since;
+            for msg in messages.into_iter().filter(|msg| msg.id > since) {
+                last = msg.id;
+                writer.send(Event::Output(msg.line())).unwrap();
+            }
+            last
+        }
+        Err(err) => {
+            writer
+                .send(Event::Error(format!(
+                    "Failed to fetch relay messages: {err}"
+                )))
+                .unwrap();
+            since
+        }
+    }
+}
+
+/// A message waiting to be posted to a webhook.
+struct Post {
+    url: String,
+    format: WebhookFormat,
+    text: String,
+}
+
+/// Posts the queued messages one at a time until the queue is dropped.
+fn spawn_worker(
+    writer: Sender<Event>,
+    client: impl RelayClient + Send + 'static,
+) -> SyncSender<Post> {
+    let (queue, posts) = sync_channel::<Post>(QUEUE_SIZE);
+    thread::Builder::new()
+        .name("relay-thread".to_string())
+        .spawn(move || {
+            for msg in posts {
+                post(&writer, &msg.url, msg.format, &msg.text, &client);
+            }
+        })
+        .ok();
+    queue
+}
+
+/// Posts messages to webhooks on a single thread, and polls a listen endpoint, sharing one HTTP
+/// client. The thread ends when the relay is dropped. Failures are written as [Event::Error]
+/// messages to the provided [Sender].
+pub struct RelayWorker {
+    writer: Sender<Event>,
+    client: HttpClient,
+    queue: SyncSender<Post>,
+}
+
+impl RelayWorker {
+    pub fn new(writer: Sender<Event>) -> Self {
+        let client = HttpClient::new();
+        let queue = spawn_worker(writer.clone(), client.clone());
+        Self {
+            writer,
+            client,
+            queue,
+        }
+    }
+
+    /// Queues `text` to be posted to the webhook at `url`. Returns false, dropping the text, if
+    /// the queue is full.
+    pub fn send(&self, url: String, format: WebhookFormat, text: String) -> bool {
+        let queued = self.queue.try_send(Post { url, format, text }).is_ok();
+        if !queued {
+            warn!("Relay queue is full, dropping message");
+        }
+        queued
+    }
+
+    /// Polls the endpoint at `url` every `interval` and writes new messages as [Event::Output]
+    /// until the returned flag is cleared. Messages that exist when listening starts are
+    /// skipped.
+    pub fn listen(&self, url: String, interval: Duration) -> Arc<AtomicBool> {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let writer = self.writer.clone();
+        let client = self.client.clone();
+        thread::Builder::new()
+            .name("relay-listen-thread".to_string())
+            .spawn(move || listen(&writer, &url, interval, &flag, &client))
+            .ok();
+        running
+    }
+}
+
+fn listen(
+    writer: &Sender<Event>,
+    url: &str,
+    interval: Duration,
+    flag: &AtomicBool,
+    client: &dyn RelayClient,
+) {
+    let mut since = client
+        .fetch(url, 0)
+        .ok()
+        .and_then(|messages| messages.iter().map(|msg| msg.id).max())
+        .unwrap_or_default();
+    while flag.load(Ordering::Relaxed) {
+        thread::sleep(interval);
+        if flag.load(Ordering::Relaxed) {
+            since = poll(writer, url, since, client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_relay {
+    use crate::event::Event;
+    use crate::model::Line;
+    use crate::net::relay::{
+        poll, post, spawn_worker, MockRelayClient, Post, RelayMessage, WebhookFormat,
+    };
+    use serde_json::json;
+
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    #[test]
+    fn test_post() {
+        let mut client = MockRelayClient::new();
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+
+        client
+            .expect_post()
+            .withf(|url, body| url == "https://hook" && *body == json!({ "text": "hello" }))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        client
+            .expect_post()
+            .withf(|_, body| *body == json!({ "content": "fail" }))
+            .times(1)
+            .returning(|_, _| Err("timeout".to_string()));
+
+        post(
+            &writer,
+            "https://hook",
+            WebhookFormat::Slack,
+            "hello",
+            &client,
+        );
+        assert!(reader.try_recv().is_err());
+        post(
+            &writer,
+            "https://hook",
+            WebhookFormat::Discord,
+            "fail",
+            &client,
+        );
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Error("Failed to relay message: timeout".to_string())
+        );
+    }
+
+    #[test]
+    fn test_worker() {
+        let mut client = MockRelayClient::new();
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+
+        let mut seq = mockall::Sequence::new();
+        for (text, result) in [("first", Err("timeout".to_string())), ("second", Ok(()))] {
+            client
+                .expect_post()
+                .withf(move |_, body| *body == json!({ "content": text }))
+                .times(1)
+                .in_sequence(&mut seq)
+                .return_const(result);
+        }
+
+        let queue = spawn_worker(writer, client);
+        for text in ["first", "second"] {
+            queue
+                .try_send(Post {
+                    url: "https://hook".to_string(),
+                    format: WebhookFormat::Discord,
+                    text: text.to_string(),
+                })
+                .unwrap();
+        }
+        // The worker ends, dropping its writer, once the queue is dropped.
+        drop(queue);
+        assert_eq!(
+            reader.iter().collect::<Vec<Event>>(),
+            vec![Event::Error("Failed to relay message: timeout".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_poll() {
+        let mut client = MockRelayClient::new();
+        let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
+
+        client
+            .expect_fetch()
+            .withf(|_, since| *since == 1)
+            .times(1)
+            .returning(|_, _| {
+                Ok(vec![
+                    RelayMessage {
+                        id: 3,
+                        author: None,
+                        content: "Raid at 8".to_string(),
+                    },
+                    RelayMessage {
+                        id: 1,
+                        author: Some("Old".to_string()),
+                        content: "seen".to_string(),
+                    },
+                    RelayMessage {
+                        id: 2,
+                        author: Some("Bob".to_string()),
+                        content: "hi".to_string(),
+                    },
+                ])
+            });
+
+        assert_eq!(poll(&writer, "https://listen", 1, &client), 3);
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Output(Line::from("[relay] Bob: hi"))
+        );
+        assert_eq!(
+            reader.try_recv().unwrap(),
+            Event::Output(Line::from("[relay] Raid at 8"))
+        );
+        assert!(reader.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_webhook_format() {
+        assert_eq!(WebhookFormat::parse("slack"), Some(WebhookFormat::Slack));
+        assert_eq!(
+            WebhookFormat::parse("discord"),
+            Some(WebhookFormat::Discord)
+        );
+        assert_eq!(WebhookFormat::parse("irc"), None);
+    }
+}
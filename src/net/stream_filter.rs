@@ -0,0 +1,91 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{bail, Error};
+
+/// The direction of the byte stream a filter is applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamDirection {
+    Inbound,
+    Outbound,
+}
+
+impl FromStr for StreamDirection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in" | "inbound" | "recv" => Ok(Self::Inbound),
+            "out" | "outbound" | "send" => Ok(Self::Outbound),
+            _ => bail!("Invalid stream direction: {s}"),
+        }
+    }
+}
+
+/// Tracks which stream directions currently have filters registered so the network threads
+/// can skip locking the script state when there is nothing to apply.
+#[derive(Debug, Clone, Default)]
+pub struct StreamFilters {
+    inbound: Arc<AtomicBool>,
+    outbound: Arc<AtomicBool>,
+}
+
+impl StreamFilters {
+    fn flag(&self, direction: StreamDirection) -> &AtomicBool {
+        match direction {
+            StreamDirection::Inbound => &self.inbound,
+            StreamDirection::Outbound => &self.outbound,
+        }
+    }
+
+    pub fn is_active(&self, direction: StreamDirection) -> bool {
+        self.flag(direction).load(Ordering::Relaxed)
+    }
+
+    pub fn set_active(&self, direction: StreamDirection, active: bool) {
+        self.flag(direction).store(active, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.set_active(StreamDirection::Inbound, false);
+        self.set_active(StreamDirection::Outbound, false);
+    }
+}
+
+#[cfg(test)]
+mod test_stream_filter {
+    use super::*;
+
+    #[test]
+    fn test_direction_parsing() {
+        assert_eq!(
+            "in".parse::<StreamDirection>().unwrap(),
+            StreamDirection::Inbound
+        );
+        assert_eq!(
+            "recv".parse::<StreamDirection>().unwrap(),
+            StreamDirection::Inbound
+        );
+        assert_eq!(
+            "outbound".parse::<StreamDirection>().unwrap(),
+            StreamDirection::Outbound
+        );
+        assert!("sideways".parse::<StreamDirection>().is_err());
+    }
+
+    #[test]
+    fn test_shared_flags() {
+        let filters = StreamFilters::default();
+        let other = filters.clone();
+        filters.set_active(StreamDirection::Outbound, true);
+        assert!(other.is_active(StreamDirection::Outbound));
+        assert!(!other.is_active(StreamDirection::Inbound));
+        other.clear();
+        assert!(!filters.is_active(StreamDirection::Outbound));
+    }
+}
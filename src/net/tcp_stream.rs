@@ -1,67 +1,143 @@
-use crate::{event::Event, model::Connection, net::TelnetHandler, session::Session};
-use flate2::read::ZlibDecoder;
-use libmudtelnet::bytes::Bytes;
+use crate::{
+    event::Event,
+    model::Connection,
+    net::{StreamChange, TelnetHandler},
+    session::Session,
+};
+use flate2::{Decompress, FlushDecompress, Status};
+use libmudtelnet::{
+    bytes::Bytes,
+    telnet::{op_command as cmd, op_option as opt},
+};
 use log::{debug, error};
 use std::{
-    io::{Chain, Cursor, Read, Write},
+    io::{Read, Write},
     sync::mpsc::Receiver,
     thread,
+    time::{Duration, Instant},
 };
 
-use super::MudConnection;
-
-type Decoder = ZlibDecoder<Chain<Cursor<Vec<u8>>, MudConnection>>;
+use super::{MudConnection, StreamDirection};
 
 pub const BUFFER_SIZE: usize = 32 * 1024;
 
+/// Starts compression, a server starting a new stream without ending the last one sends it
+/// where the old stream breaks.
+const MCCP2_START: [u8; 5] = [cmd::IAC, cmd::SB, opt::MCCP2, cmd::IAC, cmd::SE];
+
 struct MudReceiver {
     connection: MudConnection,
-    decoder: Option<Decoder>,
+    decompress: Option<Decompress>,
+    /// Received data that hasn't been decompressed yet.
+    input: Vec<u8>,
+    /// Decompressed data to hand out before decompressing more.
+    decoded: Vec<u8>,
+    /// Why the server's compression was given up on.
+    compression_error: Option<String>,
 }
 
 impl MudReceiver {
     fn open_zlib_stream(&mut self, existing: Vec<u8>) {
+        if self.decompress.is_some() {
+            // The request to compress was itself compressed, keep reading the current stream.
+            debug!("Ignoring nested Zlib stream");
+            self.decoded = existing;
+            return;
+        }
         debug!("Opening Zlib stream");
-        let chain = ZlibDecoder::new(Cursor::new(existing).chain(self.connection.clone()));
-        self.decoder.replace(chain);
+        self.decompress = Some(Decompress::new(true));
+        self.input = existing;
     }
 
-    fn read_bytes(&mut self) -> Vec<u8> {
+    fn read_connection(&mut self) -> Vec<u8> {
         let mut data = vec![0; BUFFER_SIZE];
-        if let Some(decoder) = &mut self.decoder {
-            debug!(
-                "Waiting for zlib data... ({} ---> {})",
-                decoder.total_in(),
-                decoder.total_out()
-            );
-            match decoder.read(&mut data) {
-                Ok(bytes_read) => {
-                    debug!("Read {} bytes from zlib stream", bytes_read);
-                    if bytes_read > 0 {
-                        data = data[..bytes_read].to_vec();
-                    } else {
-                        data = vec![];
-                    }
+        match self.connection.read(&mut data) {
+            Ok(bytes_read) => {
+                debug!("Read {bytes_read} bytes from stream");
+                data.truncate(bytes_read);
+            }
+            Err(err) => {
+                error!("Error: {err}");
+                data = vec![];
+            }
+        }
+        data
+    }
+
+    /// Reads the next data from the server, decompressed if MCCP2 is on. The data after the
+    /// end of a compressed stream is read as it is. An empty result means the connection
+    /// closed.
+    fn read_bytes(&mut self) -> Vec<u8> {
+        if !self.decoded.is_empty() {
+            return std::mem::take(&mut self.decoded);
+        }
+        loop {
+            if self.decompress.is_none() {
+                if !self.input.is_empty() {
+                    return std::mem::take(&mut self.input);
                 }
-                Err(err) => {
-                    error!("Error: {}", err.to_string());
-                    data = vec![];
+                let data = self.read_connection();
+                debug!("Bytes: {:?}", data);
+                return data;
+            }
+            if self.input.is_empty() {
+                let data = self.read_connection();
+                if data.is_empty() {
+                    return data;
                 }
+                self.input = data;
             }
-        } else {
-            match self.connection.read(&mut data) {
-                Ok(bytes_read) => {
-                    debug!("Read {bytes_read} bytes from stream");
-                    data = data[..bytes_read].to_vec();
+
+            let mut data = Vec::with_capacity(BUFFER_SIZE);
+            let (result, consumed) = match &mut self.decompress {
+                Some(decompress) => {
+                    let total_in = decompress.total_in();
+                    let result =
+                        decompress.decompress_vec(&self.input, &mut data, FlushDecompress::None);
+                    (result, (decompress.total_in() - total_in) as usize)
+                }
+                None => continue,
+            };
+            match result {
+                Ok(status) => {
+                    self.input.drain(..consumed);
+                    if status == Status::StreamEnd {
+                        debug!("Zlib stream ended");
+                        self.decompress = None;
+                    } else if consumed == 0 && data.is_empty() {
+                        // Not enough data to decompress anything yet
+                        let more = self.read_connection();
+                        if more.is_empty() {
+                            return more;
+                        }
+                        self.input.extend(more);
+                    }
+                    if !data.is_empty() {
+                        debug!("Bytes: {:?}", data);
+                        return data;
+                    }
                 }
                 Err(err) => {
-                    error!("Error: {err}");
-                    data = vec![];
+                    error!("Zlib error: {err}");
+                    self.decompress = None;
+                    // Where exactly the stream broke is unknown, so search all of the input.
+                    let input = std::mem::take(&mut self.input);
+                    match input
+                        .windows(MCCP2_START.len())
+                        .position(|window| window == MCCP2_START)
+                    {
+                        Some(start) => {
+                            debug!("Zlib stream restarted");
+                            self.input = input[start..].to_vec();
+                        }
+                        None => self.compression_error = Some(err.to_string()),
+                    }
+                    if !data.is_empty() {
+                        return data;
+                    }
                 }
             }
         }
-        debug!("Bytes: {:?}", data);
-        data
     }
 }
 
@@ -69,14 +145,28 @@ impl From<&Session> for MudReceiver {
     fn from(session: &Session) -> Self {
         Self {
             connection: session.connection.lock().unwrap().clone(),
-            decoder: None,
+            decompress: None,
+            input: vec![],
+            decoded: vec![],
+            compression_error: None,
         }
     }
 }
 
-pub fn spawn_connect_thread(
+/// Seconds to wait between attempts when connecting at startup, eg. while the network is
+/// still coming up.
+pub const STARTUP_RETRY_DELAYS: [u64; 5] = [2, 4, 8, 16, 32];
+
+pub fn spawn_connect_thread(session: Session, connection: Connection) -> thread::JoinHandle<()> {
+    spawn_connect_thread_with_retries(session, connection, &[])
+}
+
+/// Connects like [spawn_connect_thread] but tries again after each of `delays` before giving
+/// up. Retrying stops if another connection was made in the meantime.
+pub fn spawn_connect_thread_with_retries(
     mut session: Session,
     connection: Connection,
+    delays: &'static [u64],
 ) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name("connect-thread".to_string())
@@ -86,12 +176,27 @@ pub fn spawn_connect_thread(
                 port,
                 tls,
                 verify_cert,
+                ..
             } = connection;
-            if !session.connect(&host, port, tls, verify_cert.into()) {
+            let mut delays = delays.iter();
+            while !session.connect(&host, port, tls, verify_cert.into()) {
+                let Some(delay) = delays.next() else {
+                    session
+                        .main_writer
+                        .send(Event::Error(format!("Failed to connect to {host}:{port}")))
+                        .unwrap();
+                    break;
+                };
                 session
                     .main_writer
-                    .send(Event::Error(format!("Failed to connect to {host}:{port}")))
+                    .send(Event::Error(format!(
+                        "Failed to connect to {host}:{port}, retrying in {delay}s"
+                    )))
                     .unwrap();
+                thread::sleep(Duration::from_secs(*delay));
+                if session.connected() {
+                    break;
+                }
             }
         })
         .unwrap()
@@ -106,14 +211,28 @@ pub fn spawn_receive_thread(session: Session) -> thread::JoinHandle<()> {
             let mut telnet_handler = TelnetHandler::new(session.clone());
 
             debug!("Receive stream spawned");
-            let mut remaining_bytes = None;
+            let mut stream_change = None;
             loop {
-                if let Some(bytes) = remaining_bytes {
-                    mud_receiver.open_zlib_stream(bytes);
+                match stream_change.take() {
+                    Some(StreamChange::Compressed(bytes)) => mud_receiver.open_zlib_stream(bytes),
+                    Some(StreamChange::StartTls) if !session.start_tls() => {
+                        writer.send(Event::Disconnect).unwrap();
+                        break;
+                    }
+                    Some(StreamChange::StartTls) | None => {}
                 }
 
                 let bytes = mud_receiver.read_bytes();
+                if let Some(err) = mud_receiver.compression_error.take() {
+                    writer
+                        .send(Event::Error(format!(
+                            "The server's compressed data is broken ({err}), continuing without compression"
+                        )))
+                        .unwrap();
+                    telnet_handler.stop_compression();
+                }
                 if bytes.is_empty() {
+                    session.watchdog.stop();
                     writer
                         .send(Event::Info("Connection closed".to_string()))
                         .unwrap();
@@ -121,7 +240,19 @@ pub fn spawn_receive_thread(session: Session) -> thread::JoinHandle<()> {
                     break;
                 }
 
-                remaining_bytes = telnet_handler.parse(&bytes);
+                session.watchdog.received(Instant::now());
+
+                let bytes = if session.stream_filters.is_active(StreamDirection::Inbound) {
+                    session
+                        .lua_script
+                        .lock()
+                        .unwrap()
+                        .filter_stream(StreamDirection::Inbound, bytes)
+                } else {
+                    bytes
+                };
+
+                stream_change = telnet_handler.parse(&bytes);
             }
             debug!("Receive stream closing");
         })
@@ -151,3 +282,134 @@ pub fn spawn_transmit_thread(
         })
         .unwrap()
 }
+
+#[cfg(test)]
+mod test_tcp_stream {
+    use std::{net::TcpListener, sync::mpsc::channel};
+
+    use flate2::{write::ZlibEncoder, Compression, FlushCompress};
+
+    use super::*;
+    use crate::session::SessionBuilder;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Compresses without ending the stream, like a server that is restarted.
+    fn compress_unfinished(data: &[u8]) -> Vec<u8> {
+        let mut compress = flate2::Compress::new(Compression::default(), true);
+        let mut out = Vec::with_capacity(1024);
+        compress
+            .compress_vec(data, &mut out, FlushCompress::Sync)
+            .unwrap();
+        out
+    }
+
+    /// Reads everything the server sends, starting a new stream when asked to like the
+    /// receive thread does.
+    fn receive(sent: Vec<u8>) -> (Vec<u8>, Option<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(&sent).unwrap();
+        });
+        let (writer, _reader) = channel();
+        let (timer_writer, _timer_reader) = channel();
+        let mut session = SessionBuilder::new()
+            .main_writer(writer)
+            .timer_writer(timer_writer)
+            .screen_dimensions((80, 80))
+            .build();
+        assert!(session.connect("127.0.0.1", port, false, false.into()));
+        server.join().unwrap();
+
+        let mut receiver = MudReceiver::from(&session);
+        receiver.open_zlib_stream(vec![]);
+        let mut received = vec![];
+        loop {
+            let bytes = receiver.read_bytes();
+            if bytes.is_empty() {
+                break;
+            }
+            match bytes.strip_prefix(&MCCP2_START) {
+                Some(rest) => receiver.open_zlib_stream(rest.to_vec()),
+                None => received.extend(bytes),
+            }
+        }
+        (received, receiver.compression_error)
+    }
+
+    #[test]
+    fn test_data_after_stream_end() {
+        let (received, error) = receive([compress(b"compressed "), b"plain".to_vec()].concat());
+        assert_eq!(received, b"compressed plain");
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn test_restarted_stream() {
+        let (received, error) = receive(
+            [
+                compress_unfinished(b"before "),
+                MCCP2_START.to_vec(),
+                compress(b"after"),
+            ]
+            .concat(),
+        );
+        assert_eq!(received, b"before after");
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn test_broken_stream() {
+        let (received, error) = receive(
+            [
+                compress_unfinished(b"before "),
+                vec![cmd::IAC, cmd::IAC, cmd::IAC],
+            ]
+            .concat(),
+        );
+        assert_eq!(received, b"before ");
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn test_connect_retries() {
+        let (writer, reader) = channel();
+        let (timer_writer, _timer_reader) = channel();
+        let session = SessionBuilder::new()
+            .main_writer(writer)
+            .timer_writer(timer_writer)
+            .screen_dimensions((80, 80))
+            .build();
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let connection = Connection::new("127.0.0.1", port, false, false);
+        spawn_connect_thread_with_retries(session, connection, &[0, 0])
+            .join()
+            .unwrap();
+        let errors: Vec<String> = reader
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::Error(err) if err.starts_with("Failed to connect") => Some(err),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                format!("Failed to connect to 127.0.0.1:{port}, retrying in 0s"),
+                format!("Failed to connect to 127.0.0.1:{port}, retrying in 0s"),
+                format!("Failed to connect to 127.0.0.1:{port}"),
+            ]
+        );
+    }
+}
@@ -1,14 +1,118 @@
 use crate::event::Event;
-use crate::net::OutputBuffer;
+use crate::net::{CapabilityProbe, OutputBuffer};
 use crate::session::Session;
 use libmudtelnet::{
+    bytes::Bytes,
     events::TelnetEvents,
     telnet::{op_command as cmd, op_option as opt},
     Parser,
 };
 use log::debug;
+use std::ops::Range;
 use std::sync::{mpsc::Sender, Arc, Mutex};
 
+/// The CHARSET option (RFC 2066), negotiated by `telnet_charset.lua`.
+const CHARSET: u8 = 42;
+const CHARSET_ACCEPTED: u8 = 2;
+
+/// The START-TLS option, the server asks to upgrade a plain connection to TLS.
+pub const START_TLS: u8 = 46;
+const START_TLS_FOLLOWS_CODE: u8 = 1;
+/// Sent by both sides right before the TLS handshake.
+pub const START_TLS_FOLLOWS: [u8; 6] = [
+    cmd::IAC,
+    cmd::SB,
+    START_TLS,
+    START_TLS_FOLLOWS_CODE,
+    cmd::IAC,
+    cmd::SE,
+];
+
+/// Checks if a subnegotiation sent to the server accepts a charset, returning whether it's UTF-8.
+pub fn accepted_charset_is_utf8(proto: u8, data: &[u8]) -> Option<bool> {
+    match data.split_first() {
+        Some((&CHARSET_ACCEPTED, charset)) if proto == CHARSET => {
+            Some(charset.eq_ignore_ascii_case(b"UTF-8"))
+        }
+        _ => None,
+    }
+}
+
+/// Where in the telnet stream the last received data ended.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+enum StreamState {
+    #[default]
+    Data,
+    Iac,
+    Negotiation,
+    Subnegotiation,
+    SubnegotiationIac,
+}
+
+/// A part of the received data, in the order it was received.
+#[derive(Eq, PartialEq, Debug)]
+enum Segment {
+    /// Data and telnet commands the parser can handle.
+    Telnet(Range<usize>),
+    /// An escaped IAC at the given position, which is a 255 byte of data.
+    EscapedIac(usize),
+}
+
+/// Splits received data into segments for the parser, which would take an escaped IAC for the
+/// start of a command. Returns the segments and where the data ends that can be handled now, as
+/// a command cut off at the end must wait for the rest of it.
+fn split_stream(data: &[u8], state: &mut StreamState) -> (Vec<Segment>, usize) {
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut command_start = 0;
+    for (i, byte) in data.iter().copied().enumerate() {
+        *state = match (*state, byte) {
+            (StreamState::Data, cmd::IAC) => {
+                command_start = i;
+                StreamState::Iac
+            }
+            (StreamState::Iac, cmd::IAC) => {
+                if command_start > start {
+                    segments.push(Segment::Telnet(start..command_start));
+                }
+                segments.push(Segment::EscapedIac(command_start));
+                start = i + 1;
+                StreamState::Data
+            }
+            (StreamState::Iac, cmd::SB) => StreamState::Subnegotiation,
+            (StreamState::Iac, cmd::WILL | cmd::WONT | cmd::DO | cmd::DONT) => {
+                StreamState::Negotiation
+            }
+            (StreamState::Subnegotiation, cmd::IAC) => StreamState::SubnegotiationIac,
+            (StreamState::SubnegotiationIac, cmd::SE) => StreamState::Data,
+            (StreamState::SubnegotiationIac, _) => StreamState::Subnegotiation,
+            (StreamState::Subnegotiation, _) => StreamState::Subnegotiation,
+            _ => StreamState::Data,
+        };
+    }
+    let end = match state {
+        StreamState::Iac | StreamState::Negotiation => {
+            *state = StreamState::Data;
+            command_start
+        }
+        _ => data.len(),
+    };
+    if end > start {
+        segments.push(Segment::Telnet(start..end));
+    }
+    (segments, end)
+}
+
+/// A change to how the data that follows is read, which the receive thread has to make before
+/// reading on.
+#[derive(Eq, PartialEq, Debug)]
+pub enum StreamChange {
+    /// The rest of the data, which is compressed with MCCP2.
+    Compressed(Vec<u8>),
+    /// The server waits for the TLS handshake.
+    StartTls,
+}
+
 #[derive(Default, Eq, PartialEq, Clone, Debug)]
 pub enum TelnetMode {
     TerminatedPrompt,
@@ -20,9 +124,15 @@ pub struct TelnetHandler {
     parser: Arc<Mutex<Parser>>,
     main_writer: Sender<Event>,
     output_buffer: Arc<Mutex<OutputBuffer>>,
+    capabilities: CapabilityProbe,
     mode: TelnetMode,
     will_ga: bool,
     will_eor: bool,
+    /// The server sends 8-bit binary data rather than NVT text (RFC 856).
+    binary: bool,
+    stream_state: StreamState,
+    /// A command cut off at the end of the last received data.
+    partial: Vec<u8>,
 }
 
 impl TelnetHandler {
@@ -31,9 +141,13 @@ impl TelnetHandler {
             parser: session.telnet_parser,
             main_writer: session.main_writer,
             output_buffer: session.output_buffer,
+            capabilities: session.capabilities,
             mode: TelnetMode::UnterminatedPrompt,
             will_ga: false,
             will_eor: false,
+            binary: false,
+            stream_state: StreamState::default(),
+            partial: vec![],
         }
     }
 
@@ -59,19 +173,77 @@ impl TelnetHandler {
         self.update_telnet_mode();
     }
 
-    pub fn parse(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+    /// Asks the server to stop compressing after its MCCP2 stream broke. Later requests to
+    /// compress are ignored.
+    pub fn stop_compression(&mut self) {
+        if let Ok(mut parser) = self.parser.lock() {
+            parser._wont(opt::MCCP2);
+        }
+        self.main_writer
+            .send(Event::ServerSend(Bytes::copy_from_slice(&[
+                cmd::IAC,
+                cmd::DONT,
+                opt::MCCP2,
+            ])))
+            .unwrap();
+        self.main_writer
+            .send(Event::RemoveTag("MCCP2".to_string()))
+            .unwrap();
+    }
+
+    /// Handles received data. Stops at a change to how the data that follows is read.
+    pub fn parse(&mut self, data: &[u8]) -> Option<StreamChange> {
+        let mut received = std::mem::take(&mut self.partial);
+        received.extend_from_slice(data);
+        let data = received;
+        let (segments, end) = split_stream(&data, &mut self.stream_state);
+        self.partial = data[end..].to_vec();
+        for segment in segments {
+            let (events, segment_end) = match segment {
+                Segment::Telnet(range) => {
+                    let events = if let Ok(mut parser) = self.parser.lock() {
+                        parser.receive(&data[range.clone()])
+                    } else {
+                        vec![]
+                    };
+                    (events, range.end)
+                }
+                Segment::EscapedIac(i) => (
+                    vec![TelnetEvents::DataReceive(Bytes::from_static(&[cmd::IAC]))],
+                    i + 2,
+                ),
+            };
+            if let Some(change) = self.handle_events(events) {
+                self.partial.clear();
+                self.stream_state = StreamState::default();
+                return Some(match change {
+                    StreamChange::Compressed(mut compressed) => {
+                        // The segments that follow were split up without knowing they're
+                        // compressed.
+                        compressed.extend_from_slice(&data[segment_end..]);
+                        StreamChange::Compressed(compressed)
+                    }
+                    // Nothing is sent in plain text once the handshake is due.
+                    StreamChange::StartTls => StreamChange::StartTls,
+                });
+            }
+        }
+        None
+    }
+
+    fn handle_events(&mut self, events: Vec<TelnetEvents>) -> Option<StreamChange> {
         let mut result = None;
-        let events = if let Ok(mut parser) = self.parser.lock() {
-            parser.receive(data)
-        } else {
-            vec![]
-        };
         for event in events {
             match event {
                 TelnetEvents::IAC(iac) => {
                     debug!("IAC: {}", iac.command);
                     match iac.command {
                         cmd::GA | cmd::EOR => {
+                            self.capabilities.prompt(if iac.command == cmd::GA {
+                                "GA"
+                            } else {
+                                "EOR"
+                            });
                             if self.mode != TelnetMode::TerminatedPrompt {
                                 debug!("Setting telnet mode: TerminatedPrompt");
                                 if iac.command == cmd::GA {
@@ -106,6 +278,7 @@ impl TelnetHandler {
                         if let Ok(mut parser) = self.parser.lock() {
                             parser._will(neg.option);
                         }
+                        self.capabilities.protocol(neg.option);
                         if neg.option == opt::EOR {
                             self.toggle_eor(true);
                             self.main_writer
@@ -116,10 +289,18 @@ impl TelnetHandler {
                             self.main_writer
                                 .send(Event::AddTag("GA".to_string()))
                                 .unwrap();
+                        } else if neg.option == opt::ECHO && neg.command == cmd::WILL {
+                            // The server echoing for us is how it asks for a password
+                            self.main_writer.send(Event::ServerEcho(true)).unwrap();
+                        } else if neg.option == opt::BINARY && neg.command == cmd::WILL {
+                            self.binary = true;
+                        }
+                        // START-TLS is enabled once the handshake is done.
+                        if neg.option != START_TLS {
+                            self.main_writer
+                                .send(Event::ProtoEnabled(neg.option))
+                                .unwrap();
                         }
-                        self.main_writer
-                            .send(Event::ProtoEnabled(neg.option))
-                            .unwrap();
                     }
                     if neg.command == cmd::WONT {
                         if neg.option == opt::EOR {
@@ -132,6 +313,10 @@ impl TelnetHandler {
                             self.main_writer
                                 .send(Event::RemoveTag("GA".to_string()))
                                 .unwrap();
+                        } else if neg.option == opt::ECHO {
+                            self.main_writer.send(Event::ServerEcho(false)).unwrap();
+                        } else if neg.option == opt::BINARY {
+                            self.binary = false;
                         }
                         self.main_writer
                             .send(Event::ProtoDisabled(neg.option))
@@ -140,7 +325,7 @@ impl TelnetHandler {
                 }
                 TelnetEvents::DecompressImmediate(buffer) => {
                     debug!("Breaking on buff: {:?}", &buffer);
-                    result = Some(buffer.to_vec());
+                    result = Some(StreamChange::Compressed(buffer.to_vec()));
                     break;
                 }
                 TelnetEvents::Subnegotiation(data) => match data.option {
@@ -150,9 +335,19 @@ impl TelnetHandler {
                             .send(Event::AddTag("MCCP2".to_string()))
                             .unwrap();
                     }
+                    START_TLS => {
+                        if data.buffer[..] == [START_TLS_FOLLOWS_CODE] {
+                            debug!("Server is ready for the TLS handshake");
+                            result = Some(StreamChange::StartTls);
+                            break;
+                        }
+                    }
                     opt => {
                         self.main_writer
-                            .send(Event::ProtoSubnegRecv(opt, data.buffer))
+                            .send(Event::ProtoSubnegRecv(
+                                opt,
+                                Parser::unescape_iac(data.buffer),
+                            ))
                             .unwrap();
                     }
                 },
@@ -164,7 +359,13 @@ impl TelnetHandler {
                 }
                 TelnetEvents::DataReceive(msg) => {
                     debug!("Data receive: {:?}", msg);
-                    if !msg.is_empty() && msg[0] != 0 {
+                    // NUL is a no-op in NVT text, and follows a CR that isn't a line break.
+                    let msg: Vec<u8> = if self.binary {
+                        msg.to_vec()
+                    } else {
+                        msg.iter().copied().filter(|byte| *byte != 0).collect()
+                    };
+                    if !msg.is_empty() {
                         if let Ok(mut output_buffer) = self.output_buffer.lock() {
                             let new_lines = output_buffer.receive(&msg);
                             for line in new_lines {
@@ -195,6 +396,7 @@ impl TelnetHandler {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::model::Line;
     use crate::Session;
     use crate::SessionBuilder;
     use crate::{event::Event, timer::TimerEvent};
@@ -233,4 +435,198 @@ mod test {
         th.toggle_eor(false);
         assert_eq!(th.mode, TelnetMode::UnterminatedPrompt);
     }
+
+    #[test]
+    fn test_server_echo() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::WILL, opt::ECHO]);
+        assert!(reader
+            .try_iter()
+            .any(|event| event == Event::ServerEcho(true)));
+        th.parse(&[cmd::IAC, cmd::WONT, opt::ECHO]);
+        assert!(reader
+            .try_iter()
+            .any(|event| event == Event::ServerEcho(false)));
+    }
+
+    fn mud_output(reader: &Receiver<Event>) -> Vec<String> {
+        reader
+            .try_iter()
+            .filter_map(|event| match event {
+                Event::MudOutput(line) => Some(line.line().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_escaped_iac() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(b"\xff\xffcaf\xe9 \xff\xff\r\nnext\r\n");
+        assert_eq!(mud_output(&reader), vec!["ÿcafé ÿ", "next"]);
+    }
+
+    #[test]
+    fn test_split_commands() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(b"hello\r\n\xff");
+        th.parse(&[cmd::WILL]);
+        assert_eq!(mud_output(&reader), vec!["hello"]);
+        th.parse(&[opt::ECHO, b'a', b'\r', b'\n']);
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::ServerEcho(true)));
+        assert!(events.contains(&Event::MudOutput(Line::from("a"))));
+    }
+
+    #[test]
+    fn test_binary() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(b"a\r\0b\r\n");
+        assert_eq!(mud_output(&reader), vec!["a\rb"]);
+        th.parse(&[cmd::IAC, cmd::WILL, opt::BINARY]);
+        assert!(th.binary);
+        th.parse(b"a\0b\r\n");
+        assert_eq!(mud_output(&reader), vec!["a\0b"]);
+        th.parse(&[cmd::IAC, cmd::WONT, opt::BINARY]);
+        assert!(!th.binary);
+    }
+
+    #[test]
+    fn test_subnegotiation_unescaped() {
+        let (session, reader, _timer_reader) = build_session();
+        session
+            .telnet_parser
+            .lock()
+            .unwrap()
+            .options
+            .support(opt::GMCP);
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::DO, opt::GMCP]);
+        th.parse(&[cmd::IAC, cmd::SB, opt::GMCP, b'a', cmd::IAC, cmd::IAC]);
+        th.parse(&[b'b', cmd::IAC, cmd::SE]);
+        assert!(
+            reader
+                .try_iter()
+                .any(|event| event
+                    == Event::ProtoSubnegRecv(opt::GMCP, Bytes::from_static(b"a\xffb")))
+        );
+    }
+
+    #[test]
+    fn test_compressed_rest() {
+        let (session, _reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::WILL, opt::MCCP2]);
+        let rest = th.parse(&[
+            cmd::IAC,
+            cmd::SB,
+            opt::MCCP2,
+            cmd::IAC,
+            cmd::SE,
+            1,
+            cmd::IAC,
+            cmd::IAC,
+            2,
+            cmd::IAC,
+        ]);
+        assert_eq!(
+            rest,
+            Some(StreamChange::Compressed(vec![
+                1,
+                cmd::IAC,
+                cmd::IAC,
+                2,
+                cmd::IAC
+            ]))
+        );
+        assert!(th.partial.is_empty());
+    }
+
+    #[test]
+    fn test_stop_compression() {
+        let (session, reader, _timer_reader) = build_session();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::WILL, opt::MCCP2]);
+        th.stop_compression();
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::ServerSend(Bytes::from_static(&[
+            cmd::IAC,
+            cmd::DONT,
+            opt::MCCP2
+        ]))));
+        assert!(events.contains(&Event::RemoveTag("MCCP2".to_string())));
+        let rest = th.parse(&[cmd::IAC, cmd::SB, opt::MCCP2, cmd::IAC, cmd::SE, 1]);
+        assert_eq!(rest, None);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let (session, _reader, _timer_reader) = build_session();
+        let capabilities = session.capabilities.clone();
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[
+            cmd::IAC,
+            cmd::WILL,
+            opt::MCCP2,
+            cmd::IAC,
+            cmd::WILL,
+            opt::ECHO,
+        ]);
+        th.parse(b"> ");
+        th.parse(&[cmd::IAC, cmd::GA]);
+        assert_eq!(
+            capabilities.capabilities().summary(),
+            "Server supports: MCCP2; prompt style: GA"
+        );
+    }
+
+    #[test]
+    fn test_start_tls() {
+        let (session, reader, _timer_reader) = build_session();
+        session
+            .telnet_parser
+            .lock()
+            .unwrap()
+            .options
+            .support_local(START_TLS);
+        let mut th = TelnetHandler::new(session);
+
+        th.parse(&[cmd::IAC, cmd::DO, START_TLS]);
+        let events: Vec<Event> = reader.try_iter().collect();
+        assert!(events.contains(&Event::ServerSend(Bytes::from_static(&[
+            cmd::IAC,
+            cmd::WILL,
+            START_TLS
+        ]))));
+        assert!(!events.contains(&Event::ProtoEnabled(START_TLS)));
+
+        let mut data = START_TLS_FOLLOWS.to_vec();
+        data.extend_from_slice(&[0x16, 0x03, 0x01]);
+        assert_eq!(th.parse(&data), Some(StreamChange::StartTls));
+        assert!(th.partial.is_empty());
+        assert!(mud_output(&reader).is_empty());
+    }
+
+    #[test]
+    fn test_accepted_charset() {
+        assert_eq!(accepted_charset_is_utf8(CHARSET, b"\x02UTF-8"), Some(true));
+        assert_eq!(
+            accepted_charset_is_utf8(CHARSET, b"\x02ISO-8859-1"),
+            Some(false)
+        );
+        assert_eq!(accepted_charset_is_utf8(CHARSET, b"\x03"), None);
+        assert_eq!(accepted_charset_is_utf8(opt::GMCP, b"\x02UTF-8"), None);
+    }
 }
@@ -1,4 +1,4 @@
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::net::{SocketAddr, TcpStream};
 use std::time::Duration;
 
 use anyhow::{anyhow, Result};
@@ -8,10 +8,13 @@ use socket2::{Socket, TcpKeepalive};
 use crate::io::SaveData;
 use crate::model::{self, KEEPALIVE_ENABLED};
 
+use super::dns::{resolve, DnsSettings};
+
 /// Connect to a remote host and port, returning a `TcpStream` if successful.
 ///
-/// This function will resolve potential IP addresses for the given host/port combination and
-/// prefer connecting to an IPv6 address if available, falling back to IPv4 if necessary.
+/// This function will resolve potential IP addresses for the given host/port combination with
+/// the DNS settings and prefer connecting to the preferred address family (IPv6 unless
+/// configured otherwise) if available, falling back to the other if necessary.
 ///
 /// Unless disabled by setting the` KEEPALIVE_ENABLED` setting to false the streams returned
 /// by this function will have [TCP keepalive](https://en.wikipedia.org/wiki/Keepalive#TCP_keepalive)
@@ -21,11 +24,17 @@ pub fn open_tcp_stream(host: &str, port: u16) -> Result<TcpStream> {
     let keepalive = model::Settings::try_load()?
         .get(KEEPALIVE_ENABLED)
         .unwrap_or(true);
-    stream_with_options(host, port, keepalive)
+    stream_with_options(host, port, keepalive, &DnsSettings::try_load()?)
 }
 
-fn stream_with_options(host: &str, port: u16, keepalive: bool) -> Result<TcpStream> {
-    let sock = Socket::from(opportunistic_connect(prepare_addresses(host, port)?)?);
+fn stream_with_options(
+    host: &str,
+    port: u16,
+    keepalive: bool,
+    dns: &DnsSettings,
+) -> Result<TcpStream> {
+    let addresses = resolve(host, port, dns)?.addresses;
+    let sock = Socket::from(opportunistic_connect(addresses)?);
     if keepalive {
         debug!("enabling TCP keepalive");
         // Values are loosely based on Mudlet's settings, but tuned to be a little more aggressive.
@@ -66,27 +75,6 @@ fn opportunistic_connect(addrs: Vec<SocketAddr>) -> Result<TcpStream> {
     Err(last_error)
 }
 
-// Lookup IP addresses for the given host/port, returning a Vec that interleaves IPv6/IPv4 addresses.
-// This makes it easy to prefer IPv6 addresses, but fall-back to IPv4 as we attempt connecting to
-// each address in sequence.
-fn prepare_addresses(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
-    debug!("resolving IP addresses for {host}:{port}");
-    let (addrs_v4, addrs_v6): (Vec<_>, Vec<_>) =
-        (host, port).to_socket_addrs()?.partition(|a| match a {
-            SocketAddr::V4(_) => true,
-            SocketAddr::V6(_) => false,
-        });
-    let mut addrs = Vec::with_capacity(addrs_v4.len() + addrs_v6.len());
-    let (mut left, mut right) = (addrs_v6.into_iter(), addrs_v4.into_iter());
-    while let Some(a) = left.next() {
-        addrs.push(a);
-        std::mem::swap(&mut left, &mut right);
-    }
-    addrs.extend(right);
-    debug!("resolved {} potential addresses", addrs.len());
-    Ok(addrs)
-}
-
 #[cfg(test)]
 mod tests {
     use std::net::TcpListener;
@@ -95,6 +83,7 @@ mod tests {
 
     use socket2::Socket;
 
+    use crate::net::dns::DnsSettings;
     use crate::net::util::stream_with_options;
 
     #[test]
@@ -114,16 +103,26 @@ mod tests {
 
         // Creating a stream with keepalive=true should result in a socket configured
         // with keepalive enabled.
-        let stream =
-            stream_with_options(addr.ip().to_string().as_str(), addr.port(), true).unwrap();
+        let stream = stream_with_options(
+            addr.ip().to_string().as_str(),
+            addr.port(),
+            true,
+            &DnsSettings::default(),
+        )
+        .unwrap();
         let sock = Socket::from(stream);
         assert!(sock.keepalive().unwrap());
         drop(sock);
 
         // And creating a stream with keepalive=false should result in a socket configured
         // without keepalive enabled.
-        let stream =
-            stream_with_options(addr.ip().to_string().as_str(), addr.port(), false).unwrap();
+        let stream = stream_with_options(
+            addr.ip().to_string().as_str(),
+            addr.port(),
+            false,
+            &DnsSettings::default(),
+        )
+        .unwrap();
         let sock = Socket::from(stream);
         assert!(!sock.keepalive().unwrap());
 
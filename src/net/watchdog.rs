@@ -0,0 +1,173 @@
+use libmudtelnet::telnet::op_command as cmd;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long the server can be quiet before it's probed.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for any answer to the probe before the connection counts as stalled.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Telnet TIMING-MARK (RFC 860), which the server has to answer with WILL or WONT.
+const TIMING_MARK: u8 = 6;
+
+/// What the connection watchdog found when it was last checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogStatus {
+    /// The server has been quiet for a while, ask it for a reply to tell a quiet server apart
+    /// from a dead connection.
+    Probe,
+    /// Nothing came back from the probe, with the seconds since the server last sent anything.
+    Stalled(u64),
+    /// The server sent something again after stalling.
+    Recovered,
+}
+
+#[derive(Debug, Default)]
+struct WatchdogState {
+    enabled: bool,
+    /// When the server last sent anything, none while disconnected.
+    last_received: Option<Instant>,
+    probe_sent: Option<Instant>,
+    stalled: bool,
+    recovered: bool,
+}
+
+/// Notices when the server stops sending anything, shared between the receive thread that
+/// reports data and the main thread that checks it every timer tick.
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog {
+    state: Arc<Mutex<WatchdogState>>,
+}
+
+impl Watchdog {
+    /// Asks the server for a reply without it showing anything to the user.
+    pub const PROBE: [u8; 3] = [cmd::IAC, cmd::DO, TIMING_MARK];
+
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.enabled = enabled;
+        }
+    }
+
+    /// Starts watching a new connection.
+    pub fn start(&self, now: Instant) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = WatchdogState {
+                enabled: state.enabled,
+                last_received: Some(now),
+                ..Default::default()
+            };
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = WatchdogState {
+                enabled: state.enabled,
+                ..Default::default()
+            };
+        }
+    }
+
+    /// Records data from the server, which answers a probe as well.
+    pub fn received(&self, now: Instant) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.last_received.is_none() {
+                return;
+            }
+            state.last_received = Some(now);
+            state.probe_sent = None;
+            if state.stalled {
+                state.stalled = false;
+                state.recovered = true;
+            }
+        }
+    }
+
+    pub fn check(&self, now: Instant) -> Option<WatchdogStatus> {
+        let mut state = self.state.lock().ok()?;
+        if std::mem::take(&mut state.recovered) {
+            return Some(WatchdogStatus::Recovered);
+        }
+        let last_received = state.last_received?;
+        if !state.enabled || state.stalled {
+            return None;
+        }
+        match state.probe_sent {
+            Some(sent) if now.duration_since(sent) >= PROBE_TIMEOUT => {
+                state.stalled = true;
+                Some(WatchdogStatus::Stalled(
+                    now.duration_since(last_received).as_secs(),
+                ))
+            }
+            None if now.duration_since(last_received) >= STALL_TIMEOUT => {
+                state.probe_sent = Some(now);
+                Some(WatchdogStatus::Probe)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_watchdog {
+    use super::*;
+
+    #[test]
+    fn test_quiet_server() {
+        let watchdog = Watchdog::default();
+        watchdog.set_enabled(true);
+        let start = Instant::now();
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT), None);
+
+        watchdog.start(start);
+        assert_eq!(watchdog.check(start + Duration::from_secs(1)), None);
+        assert_eq!(
+            watchdog.check(start + STALL_TIMEOUT),
+            Some(WatchdogStatus::Probe)
+        );
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT), None);
+
+        // The probe was answered
+        watchdog.received(start + STALL_TIMEOUT + Duration::from_secs(1));
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT + PROBE_TIMEOUT), None);
+    }
+
+    #[test]
+    fn test_stalled_server() {
+        let watchdog = Watchdog::default();
+        watchdog.set_enabled(true);
+        let start = Instant::now();
+        watchdog.start(start);
+        assert_eq!(
+            watchdog.check(start + STALL_TIMEOUT),
+            Some(WatchdogStatus::Probe)
+        );
+        assert_eq!(
+            watchdog.check(start + STALL_TIMEOUT + PROBE_TIMEOUT),
+            Some(WatchdogStatus::Stalled(75))
+        );
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT * 3), None);
+
+        watchdog.received(start + STALL_TIMEOUT * 3);
+        assert_eq!(
+            watchdog.check(start + STALL_TIMEOUT * 3),
+            Some(WatchdogStatus::Recovered)
+        );
+
+        watchdog.stop();
+        watchdog.received(start + STALL_TIMEOUT * 4);
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT * 8), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let watchdog = Watchdog::default();
+        let start = Instant::now();
+        watchdog.start(start);
+        assert_eq!(watchdog.check(start + STALL_TIMEOUT * 2), None);
+    }
+}
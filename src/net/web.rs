@@ -0,0 +1,120 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::mpsc::Sender;
+#[cfg(feature = "web")]
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+#[cfg(feature = "web")]
+use super::web_server::WebServer;
+use crate::event::Event;
+
+/// What a mirrored line is, so the page can style it like the terminal does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MirrorKind {
+    Output,
+    Prompt,
+    Input,
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebStatus {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub token: String,
+    pub clients: usize,
+}
+
+impl WebStatus {
+    /// The url of the page. A server listening on every interface is reached over the loopback
+    /// one from this machine, other machines use its address on their network.
+    pub fn url(&self) -> String {
+        let host = match self.bind {
+            addr if addr.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            addr => addr,
+        };
+        format!(
+            "http://{}/?token={}",
+            SocketAddr::new(host, self.port),
+            self.token
+        )
+    }
+
+    /// Checks if the page can be opened from other machines.
+    pub fn is_exposed(&self) -> bool {
+        !self.bind.is_loopback()
+    }
+}
+
+/// The web frontend of the session, shared between the screen mirroring lines to it and the
+/// scripts starting and stopping it. Without the `web` feature it can't be started.
+#[derive(Clone, Default)]
+pub struct WebMirror {
+    #[cfg(feature = "web")]
+    server: Arc<Mutex<Option<WebServer>>>,
+}
+
+#[cfg(feature = "web")]
+impl WebMirror {
+    /// Starts serving the session on `port` of the `bind` address, replacing a running server
+    /// and its token.
+    pub fn start(
+        &self,
+        bind: IpAddr,
+        port: u16,
+        writer: Sender<Event>,
+    ) -> Result<WebStatus, String> {
+        let mut server = self.server.lock().unwrap();
+        *server = None;
+        let started = WebServer::start(bind, port, writer)
+            .map_err(|err| format!("Failed to start the web server: {err}"))?;
+        *server = Some(started);
+        Ok(self.status_of(server.as_ref()).unwrap())
+    }
+
+    pub fn stop(&self) {
+        self.server.lock().unwrap().take();
+    }
+
+    pub fn status(&self) -> Option<WebStatus> {
+        self.status_of(self.server.lock().unwrap().as_ref())
+    }
+
+    fn status_of(&self, server: Option<&WebServer>) -> Option<WebStatus> {
+        server.map(|server| WebStatus {
+            bind: server.bind,
+            port: server.port,
+            token: server.token.clone(),
+            clients: server.clients(),
+        })
+    }
+
+    pub fn mirror(&self, kind: MirrorKind, text: &str) {
+        if let Some(server) = self.server.lock().unwrap().as_ref() {
+            server.broadcast(kind, text);
+        }
+    }
+}
+
+#[cfg(not(feature = "web"))]
+impl WebMirror {
+    pub fn start(
+        &self,
+        _bind: IpAddr,
+        _port: u16,
+        _writer: Sender<Event>,
+    ) -> Result<WebStatus, String> {
+        Err("Blightmud was built without the web frontend, see `/help web`".to_string())
+    }
+
+    pub fn stop(&self) {}
+
+    pub fn status(&self) -> Option<WebStatus> {
+        None
+    }
+
+    pub fn mirror(&self, _kind: MirrorKind, _text: &str) {}
+}
@@ -0,0 +1,585 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, error};
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde_json::json;
+
+use super::web::MirrorKind;
+use crate::{event::Event, io::ansi_to_html, model::Line};
+
+const INDEX_HTML: &str = include_str!("../../resources/web/index.html");
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Messages replayed to a page when it connects, so it doesn't start out blank.
+const BACKLOG_SIZE: usize = 1000;
+const MAX_REQUEST_SIZE: u64 = 8192;
+const MAX_FRAME_SIZE: u64 = 65536;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// A page that doesn't keep up is dropped rather than stalling the main loop.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Default)]
+struct Clients {
+    streams: Vec<TcpStream>,
+    backlog: VecDeque<String>,
+}
+
+/// Serves the session as a web page, on localhost unless bound elsewhere. The page and its
+/// websocket require the token generated when the server starts.
+pub struct WebServer {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub token: String,
+    running: Arc<AtomicBool>,
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl WebServer {
+    /// Listens on `port` of the `bind` address, or any free port if it's 0. Input typed on the
+    /// page is written to `writer` as [Event::ServerInput].
+    pub fn start(bind: IpAddr, port: u16, writer: Sender<Event>) -> io::Result<Self> {
+        let listener = TcpListener::bind((bind, port))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let token = generate_token()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let clients = Arc::new(Mutex::new(Clients::default()));
+
+        let server = Self {
+            bind,
+            port,
+            token: token.clone(),
+            running: running.clone(),
+            clients: clients.clone(),
+        };
+        thread::Builder::new()
+            .name("web-listen-thread".to_string())
+            .spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, addr)) => {
+                            debug!("Web client connected from {addr}");
+                            let token = token.clone();
+                            let clients = clients.clone();
+                            let writer = writer.clone();
+                            thread::Builder::new()
+                                .name("web-client-thread".to_string())
+                                .spawn(move || {
+                                    if let Err(err) =
+                                        handle_client(stream, &token, &clients, &writer)
+                                    {
+                                        debug!("Web client disconnected: {err}");
+                                    }
+                                })
+                                .ok();
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(100));
+                        }
+                        Err(err) => {
+                            error!("Web server stopped: {err}");
+                            break;
+                        }
+                    }
+                }
+            })?;
+        Ok(server)
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients
+            .lock()
+            .map(|clients| clients.streams.len())
+            .unwrap_or_default()
+    }
+
+    /// Sends a line to every connected page, dropping the ones that can't be written to.
+    pub fn broadcast(&self, kind: MirrorKind, text: &str) {
+        let message = json!({ "kind": kind, "html": ansi_to_html(text) }).to_string();
+        let frame = encode_frame(OP_TEXT, message.as_bytes());
+        if let Ok(mut clients) = self.clients.lock() {
+            if clients.backlog.len() >= BACKLOG_SIZE {
+                clients.backlog.pop_front();
+            }
+            clients.backlog.push_back(message);
+            clients
+                .streams
+                .retain_mut(|stream| stream.write_all(&frame).is_ok());
+        }
+    }
+
+    fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Ok(mut clients) = self.clients.lock() {
+            for stream in clients.streams.drain(..) {
+                stream.shutdown(Shutdown::Both).ok();
+            }
+        }
+    }
+}
+
+impl Drop for WebServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| io::Error::other("failed to generate a token"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    path: String,
+    token: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Checks that a browser opening the websocket does so from the page served here, so pages
+    /// of other sites can't reach it. Clients that aren't browsers don't send an origin. Only the
+    /// host is compared, as a proxy adding https changes the scheme.
+    fn same_origin(&self) -> bool {
+        match (self.header("Origin"), self.header("Host")) {
+            (None, _) => true,
+            (Some(origin), Some(host)) => origin
+                .split_once("://")
+                .is_some_and(|(_, origin_host)| origin_host == host),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Compares the given token in constant time, so how long it takes doesn't tell how much of a
+/// guess was right.
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Reads the request line and headers of an HTTP request.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let mut reader = reader.take(MAX_REQUEST_SIZE);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let target = line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed request"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let token = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("token="))
+        .map(str::to_string);
+    let path = path.to_string();
+
+    let mut headers = vec![];
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete request",
+            ));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(Request {
+        path,
+        token,
+        headers,
+    })
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    token: &str,
+    clients: &Mutex<Clients>,
+    writer: &Sender<Event>,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+    if !request
+        .token
+        .as_deref()
+        .is_some_and(|given| tokens_match(given, token))
+    {
+        return respond(&mut stream, "403 Forbidden", "text/plain", "Invalid token");
+    }
+
+    match request.path.as_str() {
+        "/" => respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            INDEX_HTML,
+        ),
+        "/ws" if !request.same_origin() => respond(
+            &mut stream,
+            "403 Forbidden",
+            "text/plain",
+            "Unexpected origin",
+        ),
+        "/ws" => {
+            let Some(key) = request.header("Sec-WebSocket-Key") else {
+                return respond(
+                    &mut stream,
+                    "400 Bad Request",
+                    "text/plain",
+                    "Expected a websocket",
+                );
+            };
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key)
+            )?;
+            stream.set_read_timeout(None)?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            if let Ok(mut clients) = clients.lock() {
+                for message in &clients.backlog {
+                    stream.write_all(&encode_frame(OP_TEXT, message.as_bytes()))?;
+                }
+                clients.streams.push(stream.try_clone()?);
+            }
+            let result = read_input(&mut reader, &mut stream, writer);
+            stream.shutdown(Shutdown::Both).ok();
+            result
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found"),
+    }
+}
+
+/// Sends the lines typed on the page to the mud until the page closes the websocket. Messages
+/// split over several frames are put back together first.
+fn read_input(
+    reader: &mut impl Read,
+    stream: &mut impl Write,
+    writer: &Sender<Event>,
+) -> io::Result<()> {
+    // The opcode and payload so far of a message that continues in the next frame
+    let mut message: Option<(u8, Vec<u8>)> = None;
+    loop {
+        let (fin, opcode, payload) = read_frame(reader)?;
+        match opcode {
+            OP_CONTINUATION => {
+                let Some((opcode, mut data)) = message.take() else {
+                    continue;
+                };
+                data.extend_from_slice(&payload);
+                if data.len() as u64 > MAX_FRAME_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "message too large",
+                    ));
+                }
+                if !fin {
+                    message = Some((opcode, data));
+                } else if opcode == OP_TEXT && !send_input(&data, writer) {
+                    return Ok(());
+                }
+            }
+            OP_TEXT | OP_BINARY if !fin => message = Some((opcode, payload)),
+            OP_TEXT if !send_input(&payload, writer) => return Ok(()),
+            OP_PING => stream.write_all(&encode_frame(OP_PONG, &payload))?,
+            OP_CLOSE => {
+                stream.write_all(&encode_frame(OP_CLOSE, &[])).ok();
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sends each line of a text message as typed input, returning false once the main thread is
+/// gone.
+fn send_input(payload: &[u8], writer: &Sender<Event>) -> bool {
+    for input in String::from_utf8_lossy(payload).split('\n') {
+        let mut line = Line::from(input.trim_end_matches('\r'));
+        line.flags.source = Some("user".to_string());
+        if writer.send(Event::ServerInput(line)).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+fn accept_key(key: &str) -> String {
+    let hash = digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{key}{WEBSOCKET_GUID}").as_bytes(),
+    );
+    STANDARD.encode(hash.as_ref())
+}
+
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Reads a single websocket frame, unmasking the payload. Returns whether it's the final frame
+/// of its message along with the opcode and payload.
+fn read_frame(reader: &mut impl Read) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    let mut mask = [0u8; 4];
+    if head[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((fin, opcode, payload))
+}
+
+#[cfg(test)]
+mod test_web_server {
+    use std::io::Cursor;
+    use std::net::Ipv4Addr;
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    fn masked_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = masked_fragment(opcode, payload);
+        frame[0] |= 0x80;
+        frame
+    }
+
+    /// A frame that isn't the last of its message.
+    fn masked_fragment(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_accept_key() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let frame = masked_frame(OP_TEXT, b"look");
+        assert_eq!(
+            read_frame(&mut Cursor::new(frame)).unwrap(),
+            (true, OP_TEXT, b"look".to_vec())
+        );
+        let long = vec![b'x'; 300];
+        let frame = encode_frame(OP_TEXT, &long);
+        assert_eq!(&frame[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(read_frame(&mut Cursor::new(frame)).unwrap().2, long);
+
+        let mut huge = vec![0x81, 127];
+        huge.extend_from_slice(&(MAX_FRAME_SIZE + 1).to_be_bytes());
+        assert!(read_frame(&mut Cursor::new(huge)).is_err());
+    }
+
+    #[test]
+    fn test_fragmented_input() {
+        let mut frames = masked_fragment(OP_TEXT, b"lo");
+        frames.extend(masked_frame(OP_PING, b"ping"));
+        frames.extend(masked_fragment(OP_CONTINUATION, b"ok\nsa"));
+        frames.extend(masked_frame(OP_CONTINUATION, b"y hi"));
+        frames.extend(masked_frame(OP_TEXT, b"north"));
+        frames.extend(masked_frame(OP_CLOSE, b""));
+        let (writer, reader) = channel();
+        let mut written = vec![];
+        read_input(&mut Cursor::new(frames), &mut written, &writer).unwrap();
+
+        let input = reader
+            .try_iter()
+            .map(|event| match event {
+                Event::ServerInput(line) => line.line().to_string(),
+                other => panic!("Unexpected event: {other:?}"),
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(input, vec!["look", "say hi", "north"]);
+        // The ping in between fragments is still answered
+        assert!(written.starts_with(&encode_frame(OP_PONG, b"ping")));
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc124", "abc123"));
+        assert!(!tokens_match("abc", "abc123"));
+        assert!(!tokens_match("", "abc123"));
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let request = |headers: &str| {
+            read_request(&mut Cursor::new(format!(
+                "GET /ws HTTP/1.1\r\n{headers}\r\n"
+            )))
+            .unwrap()
+        };
+        assert!(request("Host: 127.0.0.1:8080\r\n").same_origin());
+        assert!(request("Host: 127.0.0.1:8080\r\nOrigin: http://127.0.0.1:8080\r\n").same_origin());
+        assert!(!request("Host: 127.0.0.1:8080\r\nOrigin: https://evil.example\r\n").same_origin());
+        assert!(!request("Origin: http://127.0.0.1:8080\r\n").same_origin());
+        assert!(request("Host: mud.example\r\nOrigin: https://mud.example\r\n").same_origin());
+        assert!(
+            !request("Host: mud.example\r\nOrigin: https://mud.example.evil\r\n").same_origin()
+        );
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = read_request(&mut Cursor::new(
+            "GET /ws?token=abc&x=1 HTTP/1.1\r\nHost: localhost\r\nSec-WebSocket-Key: key==\r\n\r\n",
+        ))
+        .unwrap();
+        assert_eq!(request.path, "/ws");
+        assert_eq!(request.token.as_deref(), Some("abc"));
+        assert_eq!(request.header("sec-websocket-key"), Some("key=="));
+        assert!(read_request(&mut Cursor::new("GET / HTTP/1.1\r\nHost: x\r\n")).is_err());
+    }
+
+    #[test]
+    fn test_mirror_session() {
+        let (writer, reader) = channel();
+        let server = WebServer::start(Ipv4Addr::LOCALHOST.into(), 0, writer).unwrap();
+        server.broadcast(MirrorKind::Output, "\x1b[31mHello\x1b[0m");
+
+        let mut denied = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        write!(denied, "GET /?token=wrong HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        denied.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"));
+
+        let mut foreign = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        write!(
+            foreign,
+            "GET /ws?token={} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nOrigin: https://evil.example\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            server.token, server.port
+        )
+        .unwrap();
+        let mut response = String::new();
+        foreign.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"));
+
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        write!(
+            stream,
+            "GET /ws?token={} HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            server.token
+        )
+        .unwrap();
+        let mut stream = BufReader::new(stream);
+        let response = read_request(&mut stream).unwrap();
+        assert_eq!(
+            response.header("Sec-WebSocket-Accept"),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+        let (_, _, message) = read_frame(&mut stream).unwrap();
+        assert_eq!(
+            String::from_utf8(message).unwrap(),
+            r#"{"html":"<span style=\"color:#cd0000;\">Hello</span>","kind":"output"}"#
+        );
+
+        stream
+            .get_mut()
+            .write_all(&masked_frame(OP_TEXT, b"look"))
+            .unwrap();
+        let Ok(Event::ServerInput(line)) = reader.recv_timeout(Duration::from_secs(5)) else {
+            panic!("expected input from the page");
+        };
+        assert_eq!(line.line(), "look");
+        assert_eq!(line.flags.source.as_deref(), Some("user"));
+        assert_eq!(server.clients(), 1);
+    }
+}
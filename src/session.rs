@@ -3,15 +3,24 @@ use libmudtelnet::{
     compatibility::CompatibilityTable, telnet::op_command as cmd, telnet::op_option as opt, Parser,
 };
 use log::debug;
-use std::sync::{atomic::AtomicBool, mpsc::Sender, Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::Sender,
+    Arc, Mutex,
+};
 
 use crate::{
     event::QuitMethod,
-    io::{LogWriter, Logger},
+    io::{LogWriter, Logger, Recorder, SaveData},
     lua::{LuaScript, LuaScriptBuilder},
+    model::{
+        find_servers, Activity, ColorFilters, EventMetrics, InputRules, Palettes, Regex, Servers,
+    },
     net::MudConnection,
     net::BUFFER_SIZE,
-    net::{OutputBuffer, TelnetMode},
+    net::{
+        CapabilityProbe, OutputBuffer, StreamFilters, TelnetMode, Watchdog, WebMirror, START_TLS,
+    },
     timer::TimerEvent,
     tts::TTSController,
     ui::CommandBuffer,
@@ -32,9 +41,30 @@ pub struct Session {
     pub prompt_input: Arc<Mutex<String>>,
     pub lua_script: Arc<Mutex<LuaScript>>,
     pub logger: Arc<Mutex<dyn LogWriter + Send>>,
+    pub recorder: Arc<Mutex<Recorder>>,
     pub tts_ctrl: Arc<Mutex<TTSController>>,
     pub command_buffer: Arc<Mutex<CommandBuffer>>,
     pub echo_input: Arc<AtomicBool>,
+    /// Typed input is shown as asterisks and kept out of the echo, logs and history.
+    pub masked_input: Arc<AtomicBool>,
+    /// The server echoes input itself (telnet ECHO), which masks the input as well.
+    pub server_echo: Arc<AtomicBool>,
+    /// Keystrokes are sent to the server as they're typed, toggled by a script or `/charmode`.
+    pub char_mode: Arc<AtomicBool>,
+    pub command_stacking: Arc<AtomicBool>,
+    /// Output waits for the user to ask for the next page, see the `paging` setting.
+    pub paging: Arc<AtomicBool>,
+    pub stream_filters: StreamFilters,
+    /// Notices when the server stops responding.
+    pub watchdog: Watchdog,
+    /// What the server negotiates right after connecting.
+    pub capabilities: CapabilityProbe,
+    /// How typed lines are sent to the server, from its saved settings.
+    pub input_rules: Arc<Mutex<InputRules>>,
+    pub metrics: EventMetrics,
+    pub web: WebMirror,
+    /// The buffers with unread output, see `/help activity`.
+    pub activity: Activity,
 }
 
 #[cfg_attr(test, automock)]
@@ -52,6 +82,12 @@ impl Session {
             connected = match connection.connect(host, port, tls, tls_validation) {
                 Ok(_) => {
                     conn_id = connection.id;
+                    if let Ok(mut parser) = self.telnet_parser.lock() {
+                        // A plain connection is upgraded to TLS when the server offers it.
+                        let mut start_tls = parser.options.get_option(START_TLS);
+                        start_tls.local = !tls;
+                        parser.options.set_option(START_TLS, start_tls);
+                    }
                     true
                 }
                 Err(err) => {
@@ -72,6 +108,35 @@ impl Session {
         connected
     }
 
+    /// Upgrades the connection to TLS after the server asked for it, returns false if the
+    /// handshake failed and the connection can't be used anymore.
+    pub fn start_tls(&self) -> bool {
+        let result = self.connection.lock().unwrap().start_tls();
+        match result {
+            Ok(()) => {
+                self.main_writer
+                    .send(Event::Info("Connection upgraded to TLS".to_string()))
+                    .unwrap();
+                self.main_writer
+                    .send(Event::AddTag("TLS".to_string()))
+                    .unwrap();
+                self.main_writer
+                    .send(Event::ProtoEnabled(START_TLS))
+                    .unwrap();
+                true
+            }
+            Err(err) => {
+                debug!("Failed to start TLS: {}", err);
+                self.main_writer
+                    .send(Event::Error(format!(
+                        "Failed to upgrade the connection to TLS: {err}"
+                    )))
+                    .unwrap();
+                false
+            }
+        }
+    }
+
     pub fn disconnect(&mut self) {
         let mut connection = self.connection.lock().unwrap();
         if connection.connected() {
@@ -105,6 +170,17 @@ impl Session {
         }
     }
 
+    /// Checks if input is masked, either by a script or because the server echoes it.
+    pub fn input_masked(&self) -> bool {
+        self.masked_input.load(Ordering::Relaxed) || self.server_echo.load(Ordering::Relaxed)
+    }
+
+    /// Checks if keystrokes are sent to the server as they're typed. Only a script or
+    /// `/charmode` turns this on, as servers negotiate SGA and ECHO for password prompts too.
+    pub fn char_mode_active(&self) -> bool {
+        self.char_mode.load(Ordering::Relaxed)
+    }
+
     pub fn connected(&self) -> bool {
         let connection = self.connection.lock().unwrap();
         connection.connected()
@@ -125,6 +201,37 @@ impl Session {
         connection.tls
     }
 
+    pub fn encrypted(&self) -> bool {
+        let connection = self.connection.lock().unwrap();
+        connection.encrypted()
+    }
+
+    /// The name of the saved server connected to, the first by name if several match.
+    pub fn server_name(&self) -> Option<String> {
+        find_servers(&Servers::load(), &self.host(), self.port())
+            .into_iter()
+            .next()
+    }
+
+    /// Picks the input rules and palette of the saved server connected to, the defaults if it
+    /// isn't saved, and the minimum contrast of server colors.
+    pub fn refresh_server_settings(&self) {
+        let name = self.server_name();
+        let rules = name
+            .as_ref()
+            .and_then(|name| Servers::load().remove(name))
+            .as_ref()
+            .map(InputRules::from)
+            .unwrap_or_default();
+        *self.input_rules.lock().unwrap() = rules;
+        let palette = name
+            .and_then(|name| Palettes::load().remove(&name))
+            .unwrap_or_default();
+        let mut output_buffer = self.output_buffer.lock().unwrap();
+        output_buffer.set_palette(palette);
+        output_buffer.set_min_contrast(ColorFilters::load().min_contrast());
+    }
+
     pub fn verify_cert(&self) -> bool {
         let connection = self.connection.lock().unwrap();
         match connection.tls_validation {
@@ -151,6 +258,28 @@ impl Session {
         }
     }
 
+    pub fn start_recording(&self, name: Option<String>, scrub: Vec<Regex>) {
+        if let Ok(mut recorder) = self.recorder.lock() {
+            let dimensions = termion::terminal_size().unwrap_or((80, 24));
+            let event = match recorder.start(name.as_deref(), dimensions, scrub) {
+                Ok(path) => Event::Info(format!("Recording to: {}", path.display())),
+                Err(err) => Event::Error(format!("Failed to start recording: {err}")),
+            };
+            self.main_writer.send(event).unwrap();
+        }
+    }
+
+    pub fn stop_recording(&self) {
+        if let Ok(mut recorder) = self.recorder.lock() {
+            let event = match recorder.stop() {
+                Ok(Some(path)) => Event::Info(format!("Recording saved to: {}", path.display())),
+                Ok(None) => Event::Info("Not recording".to_string()),
+                Err(err) => Event::Error(format!("Failed to save recording: {err}")),
+            };
+            self.main_writer.send(event).unwrap();
+        }
+    }
+
     pub fn send_event(&mut self, event: Event) {
         self.main_writer.send(event).unwrap();
     }
@@ -174,6 +303,7 @@ pub struct SessionBuilder {
     save_history: bool,
     headless: bool,
     echo_input: bool,
+    command_stacking: bool,
 }
 
 impl SessionBuilder {
@@ -187,6 +317,7 @@ impl SessionBuilder {
             save_history: false,
             headless: false,
             echo_input: true,
+            command_stacking: false,
         }
     }
 
@@ -230,6 +361,11 @@ impl SessionBuilder {
         self
     }
 
+    pub fn command_stacking(mut self, command_stacking: bool) -> Self {
+        self.command_stacking = command_stacking;
+        self
+    }
+
     pub fn build(self) -> Session {
         let main_writer = self.main_writer.unwrap();
         let timer_writer = self.timer_writer.unwrap();
@@ -239,11 +375,19 @@ impl SessionBuilder {
         let headless = self.headless;
         let tts_ctrl = Arc::new(Mutex::new(TTSController::new(tts_enabled, headless)));
         let echo_input = self.echo_input;
+        let command_stacking = self.command_stacking;
+
+        let metrics = EventMetrics::default();
+        let web = WebMirror::default();
+        let activity = Activity::default();
 
         let lua_builder = LuaScriptBuilder::new(main_writer.clone())
             .dimensions(dimensions)
             .tts_enabled(tts_enabled)
-            .reader_mode(reader_mode);
+            .reader_mode(reader_mode)
+            .metrics(metrics.clone())
+            .web(web.clone())
+            .activity(activity.clone());
 
         let lua_script = Arc::new(Mutex::new(lua_builder.build()));
         Session {
@@ -260,9 +404,22 @@ impl SessionBuilder {
             prompt_input: Arc::new(Mutex::new(String::new())),
             lua_script: lua_script.clone(),
             logger: Arc::new(Mutex::new(Logger::default())),
+            recorder: Arc::new(Mutex::new(Recorder::default())),
             tts_ctrl: tts_ctrl.clone(),
             command_buffer: Arc::new(Mutex::new(CommandBuffer::new(tts_ctrl, lua_script))),
             echo_input: Arc::new(AtomicBool::new(echo_input)),
+            masked_input: Arc::new(AtomicBool::new(false)),
+            server_echo: Arc::new(AtomicBool::new(false)),
+            char_mode: Arc::new(AtomicBool::new(false)),
+            command_stacking: Arc::new(AtomicBool::new(command_stacking)),
+            paging: Arc::new(AtomicBool::new(false)),
+            stream_filters: StreamFilters::default(),
+            watchdog: Watchdog::default(),
+            capabilities: CapabilityProbe::default(),
+            input_rules: Arc::new(Mutex::new(InputRules::default())),
+            metrics,
+            web,
+            activity,
         }
     }
 }
@@ -271,8 +428,9 @@ fn build_compatibility_table() -> CompatibilityTable {
     let mut telnet_compat = CompatibilityTable::default();
     telnet_compat.support(opt::MCCP2);
     telnet_compat.support(opt::EOR);
-    telnet_compat.support(opt::ECHO);
+    telnet_compat.support_remote(opt::ECHO);
     telnet_compat.support(cmd::GA);
+    telnet_compat.support(opt::BINARY);
     telnet_compat
 }
 
@@ -0,0 +1,92 @@
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 16;
+const WORD_START_BONUS: i64 = 24;
+const GAP_PENALTY: i64 = 2;
+const MAX_GAP_PENALTY: i64 = 16;
+
+fn is_word_start(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+/// Scores how well `text` matches `pattern`, case insensitively. Every character of the pattern
+/// has to appear in the text in order. Consecutive matches and matches at the start of words
+/// score higher, gaps and longer texts lower. Returns `None` if the text doesn't match.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let mut pattern = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut prev = None;
+    let mut gap = 0;
+    let mut matched_prev = false;
+    let mut len = 0;
+    for c in text.chars() {
+        len += 1;
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        if pattern.peek() == Some(&lower) {
+            pattern.next();
+            score += MATCH_SCORE;
+            if matched_prev {
+                score += CONSECUTIVE_BONUS;
+            }
+            if is_word_start(prev, c) {
+                score += WORD_START_BONUS;
+            }
+            score -= (gap * GAP_PENALTY).min(MAX_GAP_PENALTY);
+            gap = 0;
+            matched_prev = true;
+        } else {
+            if pattern.peek().is_some() {
+                gap += 1;
+            }
+            matched_prev = false;
+        }
+        prev = Some(c);
+    }
+    if pattern.peek().is_some() {
+        None
+    } else {
+        Some(score - len / 4)
+    }
+}
+
+/// Returns the indexes of the texts matching the pattern, best match first. Equally good matches
+/// keep their order.
+pub fn filter<S: AsRef<str>>(pattern: &str, texts: &[S]) -> Vec<usize> {
+    let mut scored: Vec<(usize, i64)> = texts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, text)| score(pattern, text.as_ref()).map(|score| (i, score)))
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod fuzzy_tests {
+
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert!(score("abc", "a big cat").is_some());
+        assert!(score("ABC", "abc").is_some());
+        assert!(score("abc", "acb").is_none());
+        assert!(score("", "anything").is_some());
+        assert!(score("rec", "/record start") > score("rec", "/prefer cats"));
+        assert!(score("cf", "cast fireball") > score("cf", "scarf"));
+        assert!(score("look", "look") > score("look", "look at the sign"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let texts = ["scarf", "cast fireball", "flee", "cast frost"];
+        assert_eq!(filter("cf", &texts), vec![3, 1, 0]);
+        assert_eq!(filter("", &texts), vec![0, 1, 2, 3]);
+        assert!(filter("xyz", &texts).is_empty());
+    }
+}
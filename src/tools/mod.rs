@@ -1,4 +1,5 @@
 mod crash_handler;
+pub mod fuzzy;
 pub mod patch;
 pub mod util;
 
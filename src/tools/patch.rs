@@ -9,7 +9,12 @@ use {
     },
     lazy_static::lazy_static,
     serde::{Deserialize, Serialize},
-    std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender},
+    std::{
+        collections::HashMap,
+        io,
+        path::{Path, PathBuf},
+        sync::mpsc::Sender,
+    },
 };
 
 #[cfg(not(test))]
@@ -55,12 +60,7 @@ pub fn migrate_v2_settings_and_servers(main_writer: Sender<Event>) {
 
     impl From<V2Connection> for Connection {
         fn from(v2: V2Connection) -> Connection {
-            Connection {
-                host: v2.host,
-                port: v2.port,
-                tls: v2.tls.unwrap_or_default(),
-                verify_cert: false,
-            }
+            Connection::new(&v2.host, v2.port, v2.tls.unwrap_or_default(), false)
         }
     }
 
@@ -146,6 +146,46 @@ pub fn migrate_v2_settings_and_servers(main_writer: Sender<Event>) {
     }
 }
 
+/// Moves the contents of each `from` directory into `to`, leaving anything that already exists
+/// in `to` behind. `from` is removed once it's empty so this only does anything once.
+pub fn migrate_dirs(moves: &[(PathBuf, PathBuf)], main_writer: Sender<Event>) {
+    for (from, to) in moves {
+        if from == to || !from.is_dir() {
+            continue;
+        }
+        match merge_dir(from, to) {
+            Ok(()) if from.exists() => main_writer
+                .send(Event::Error(format!(
+                    "Moved {from:?} to {to:?} except for files that already exist there"
+                )))
+                .unwrap(),
+            Ok(()) => main_writer
+                .send(Event::Info(format!("Moved {from:?} to {to:?}")))
+                .unwrap(),
+            Err(err) => main_writer
+                .send(Event::Error(format!(
+                    "Can't move {from:?} to {to:?}: {err}"
+                )))
+                .unwrap(),
+        }
+    }
+}
+
+fn merge_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if !dest.exists() {
+            fs::rename(entry.path(), dest)?;
+        } else if entry.path().is_dir() && dest.is_dir() {
+            merge_dir(&entry.path(), &dest)?;
+        }
+    }
+    fs::remove_dir(from).ok();
+    Ok(())
+}
+
 #[cfg(test)]
 lazy_static! {
     pub static ref V2_SERVERS_PATH: PathBuf = DATA_DIR.join("v2_servers.ron");
@@ -301,6 +341,42 @@ mod patch_test {
         assert_event!(event, Event::Error(..));
     }
 
+    #[test]
+    fn test_migrate_dirs() {
+        let root = std::env::temp_dir().join(format!("blightmud_dirs_{}", std::process::id()));
+        let (from, to) = (root.join("data/plugins"), root.join("cache/plugins"));
+        for dir in ["a", "b/lib", "c"] {
+            fs::create_dir_all(from.join(dir)).unwrap();
+            fs::write(from.join(dir).join("main.lua"), dir).unwrap();
+        }
+        fs::create_dir_all(to.join("b")).unwrap();
+        fs::create_dir_all(to.join("c")).unwrap();
+        fs::write(to.join("c/main.lua"), "newer").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        migrate_dirs(&[(from.clone(), to.clone())], tx.clone());
+        let event = rx.recv().unwrap();
+        assert_event!(event, Event::Error(..));
+        assert_eq!(fs::read_to_string(to.join("a/main.lua")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(to.join("b/lib/main.lua")).unwrap(),
+            "b/lib"
+        );
+        assert_eq!(fs::read_to_string(to.join("c/main.lua")).unwrap(), "newer");
+        assert!(!from.join("a").exists());
+        assert!(from.join("c/main.lua").exists());
+
+        fs::remove_dir_all(from.join("c")).unwrap();
+        migrate_dirs(&[(from.clone(), to.clone())], tx.clone());
+        let event = rx.recv().unwrap();
+        assert_event!(event, Event::Info(..));
+        assert!(!from.exists());
+        migrate_dirs(&[(from, to)], tx);
+        assert!(rx.try_recv().is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
     fn test_doesnt_migrate_over_existing_servers_file() {
         create_v2_servers_file();
         let mut file = fs::File::create(&Servers::relative_path()).unwrap();
@@ -9,6 +9,46 @@ pub fn expand_tilde(path: &str) -> Cow<str> {
     }
 }
 
+/// "my-snapshot_2" => true, "../escape" => false
+///
+/// Checks that a name picked by the user can be used as a file name in one of the client's
+/// directories, ie. that it's made of letters, digits, `-` and `_` only.
+pub fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// "n;n;e\;w" => ["n", "n", "e;w"]
+///
+/// Splits stacked commands on `separator`. A separator preceded by a backslash is kept as part of
+/// the command.
+pub fn split_commands(input: &str, separator: &str) -> Vec<String> {
+    if separator.is_empty() {
+        return vec![input.to_string()];
+    }
+    let escaped = format!("\\{separator}");
+    let mut commands = vec![];
+    let mut current = String::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix(&escaped) {
+            current.push_str(separator);
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix(separator) {
+            commands.push(std::mem::take(&mut current));
+            rest = tail;
+        } else {
+            let c = rest.chars().next().unwrap();
+            current.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    commands.push(current);
+    commands
+}
+
 #[cfg(test)]
 mod util_tests {
     use super::*;
@@ -27,4 +67,20 @@ mod util_tests {
         assert_eq!("/leave/it/alone", expand_tilde("/leave/it/alone"));
         assert_eq!("/leave/~/alone", expand_tilde("/leave/~/alone"));
     }
+
+    #[test]
+    fn command_splitting() {
+        assert_eq!(
+            split_commands("n;n;e;kill rat", ";"),
+            ["n", "n", "e", "kill rat"]
+        );
+        assert_eq!(
+            split_commands("say hi\\; there;w", ";"),
+            ["say hi; there", "w"]
+        );
+        assert_eq!(split_commands("n||s", "||"), ["n", "s"]);
+        assert_eq!(split_commands("look", ";"), ["look"]);
+        assert_eq!(split_commands("a;;b", ";"), ["a", "", "b"]);
+        assert_eq!(split_commands("a;b", ""), ["a;b"]);
+    }
 }
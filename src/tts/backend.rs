@@ -0,0 +1,324 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::error;
+use tts::Tts as TTS;
+
+use super::{TTSBackend, TTSEvent};
+
+/// How often a command backend checks whether the current utterance has been spoken.
+const COMMAND_POLL: Duration = Duration::from_millis(20);
+/// Piper writes raw audio for the text on stdin, played with aplay. The model and length scale
+/// are passed as `$0` and `$1`.
+const PIPER_PIPELINE: &str =
+    "piper --model \"$0\" --length_scale \"$1\" --output_raw | aplay -q -r 22050 -f S16_LE -t raw -";
+
+/// A speech engine the TTS thread speaks through. Backends report the end of each utterance with
+/// [TTSEvent::Next] so the speech queue moves on, and keep `speaking` set while talking.
+pub trait SpeechBackend {
+    fn speak(&mut self, msg: &str, interrupt: bool) -> Result<()>;
+    fn stop(&mut self) -> Result<()>;
+    /// Sets the rate, from -100 to 100.
+    fn set_rate(&mut self, rate: f32) -> Result<()>;
+    fn rate(&self) -> f32;
+}
+
+pub fn create_backend(
+    backend: &TTSBackend,
+    tx: Sender<TTSEvent>,
+    speaking: Arc<AtomicBool>,
+) -> Result<Box<dyn SpeechBackend>> {
+    match backend {
+        TTSBackend::Builtin => Ok(Box::new(BuiltinBackend::new(tx, speaking)?)),
+        _ => Ok(Box::new(CommandBackend::new(backend.clone(), tx, speaking))),
+    }
+}
+
+/// The platform's speech engine through the tts crate.
+struct BuiltinBackend {
+    tts: TTS,
+}
+
+impl BuiltinBackend {
+    fn new(tx: Sender<TTSEvent>, speaking: Arc<AtomicBool>) -> Result<Self> {
+        let mut tts = TTS::default()?;
+        if let Err(err) = setup_callbacks(&mut tts, tx, speaking) {
+            error!("[TTS]: {}", err.to_string());
+        }
+        Ok(Self { tts })
+    }
+}
+
+fn setup_callbacks(
+    tts: &mut TTS,
+    tx: Sender<TTSEvent>,
+    speaking: Arc<AtomicBool>,
+) -> Result<(), tts::Error> {
+    let end = speaking.clone();
+    tts.on_utterance_end(Some(Box::new(move |_| {
+        end.store(false, Ordering::Relaxed);
+        tx.send(TTSEvent::Next(1)).ok();
+    })))?;
+    let begin = speaking.clone();
+    tts.on_utterance_begin(Some(Box::new(move |_| {
+        begin.store(true, Ordering::Relaxed);
+    })))?;
+    tts.on_utterance_stop(Some(Box::new(move |_| {
+        speaking.store(false, Ordering::Relaxed);
+    })))
+}
+
+impl SpeechBackend for BuiltinBackend {
+    fn speak(&mut self, msg: &str, interrupt: bool) -> Result<()> {
+        self.tts.speak(msg, interrupt)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.tts.stop()?;
+        Ok(())
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Result<()> {
+        self.tts.set_rate(rate)?;
+        Ok(())
+    }
+
+    fn rate(&self) -> f32 {
+        self.tts.get_rate().unwrap_or_default()
+    }
+}
+
+/// A program to run for an utterance, with the text either in the arguments or on stdin.
+#[derive(Debug, PartialEq)]
+struct SpeechCommand {
+    program: String,
+    args: Vec<String>,
+    input: Option<String>,
+}
+
+impl SpeechCommand {
+    fn new(program: &str, args: &[&str], input: Option<&str>) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            input: input.map(String::from),
+        }
+    }
+
+    fn spawn(&self, rate: f32) -> Result<Child> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("BLIGHTMUD_TTS_RATE", rate.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Some(input) = &self.input {
+                stdin.write_all(input.as_bytes())?;
+                stdin.write_all(b"\n")?;
+            }
+        }
+        Ok(child)
+    }
+}
+
+/// The command speaking `msg` for a command based backend.
+fn speech_command(backend: &TTSBackend, msg: &str, rate: f32) -> Option<SpeechCommand> {
+    match backend {
+        TTSBackend::Builtin => None,
+        TTSBackend::SpeechDispatcher => {
+            let rate = (rate.round() as i32).to_string();
+            Some(SpeechCommand::new(
+                "spd-say",
+                &["--wait", "--rate", &rate, "--", msg],
+                None,
+            ))
+        }
+        TTSBackend::EspeakNg => {
+            let wpm = ((175.0 + rate * 1.25).round() as i32).to_string();
+            Some(SpeechCommand::new(
+                "espeak-ng",
+                &["-s", &wpm, "--", msg],
+                None,
+            ))
+        }
+        TTSBackend::Piper { model } => {
+            let length_scale = format!("{:.2}", 2f32.powf(-rate / 100.0));
+            Some(SpeechCommand::new(
+                "sh",
+                &["-c", PIPER_PIPELINE, model, &length_scale],
+                Some(msg),
+            ))
+        }
+        TTSBackend::Command { program, args } => {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            Some(SpeechCommand::new(program, &args, Some(msg)))
+        }
+    }
+}
+
+/// The command cancelling speech that outlives the killed process, eg. in speech-dispatcher.
+fn cancel_command(backend: &TTSBackend) -> Option<SpeechCommand> {
+    match backend {
+        TTSBackend::SpeechDispatcher => Some(SpeechCommand::new("spd-say", &["--cancel"], None)),
+        _ => None,
+    }
+}
+
+enum CommandEvent {
+    Say(SpeechCommand, f32),
+    Stop,
+}
+
+/// Speaks by running a program for every utterance. The programs run one at a time on a worker
+/// thread.
+struct CommandBackend {
+    backend: TTSBackend,
+    rate: f32,
+    worker: Sender<CommandEvent>,
+}
+
+impl CommandBackend {
+    fn new(backend: TTSBackend, tx: Sender<TTSEvent>, speaking: Arc<AtomicBool>) -> Self {
+        let (worker, rx) = channel();
+        thread::Builder::new()
+            .name("tts-command-thread".to_string())
+            .spawn(move || run_commands(rx, tx, speaking))
+            .unwrap();
+        Self {
+            backend,
+            rate: 0.0,
+            worker,
+        }
+    }
+}
+
+impl SpeechBackend for CommandBackend {
+    fn speak(&mut self, msg: &str, interrupt: bool) -> Result<()> {
+        if interrupt {
+            self.stop()?;
+        }
+        if let Some(command) = speech_command(&self.backend, msg, self.rate) {
+            self.worker.send(CommandEvent::Say(command, self.rate))?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.worker.send(CommandEvent::Stop)?;
+        if let Some(cancel) = cancel_command(&self.backend) {
+            cancel.spawn(self.rate)?.wait()?;
+        }
+        Ok(())
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Result<()> {
+        self.rate = rate;
+        Ok(())
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+}
+
+fn run_commands(rx: Receiver<CommandEvent>, tx: Sender<TTSEvent>, speaking: Arc<AtomicBool>) {
+    let mut queue: VecDeque<(SpeechCommand, f32)> = VecDeque::new();
+    let mut current: Option<Child> = None;
+    loop {
+        match rx.recv_timeout(COMMAND_POLL) {
+            Ok(CommandEvent::Say(command, rate)) => queue.push_back((command, rate)),
+            Ok(CommandEvent::Stop) => {
+                queue.clear();
+                if let Some(mut child) = current.take() {
+                    child.kill().ok();
+                    child.wait().ok();
+                }
+                speaking.store(false, Ordering::Relaxed);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Some(child) = &mut current {
+            if matches!(child.try_wait(), Ok(None)) {
+                continue;
+            }
+            current = None;
+            speaking.store(false, Ordering::Relaxed);
+            tx.send(TTSEvent::Next(1)).ok();
+        }
+        if let Some((command, rate)) = queue.pop_front() {
+            match command.spawn(rate) {
+                Ok(child) => {
+                    speaking.store(true, Ordering::Relaxed);
+                    current = Some(child);
+                }
+                Err(err) => error!("[TTS]: Failed to run {}: {}", command.program, err),
+            }
+        }
+    }
+    if let Some(mut child) = current {
+        child.kill().ok();
+    }
+}
+
+#[cfg(test)]
+mod test_backend {
+    use super::*;
+
+    #[test]
+    fn test_speech_commands() {
+        assert_eq!(speech_command(&TTSBackend::Builtin, "hi", 0.0), None);
+        assert_eq!(
+            speech_command(&TTSBackend::SpeechDispatcher, "hi", 20.4),
+            Some(SpeechCommand::new(
+                "spd-say",
+                &["--wait", "--rate", "20", "--", "hi"],
+                None
+            ))
+        );
+        assert_eq!(
+            speech_command(&TTSBackend::EspeakNg, "-hi", -100.0),
+            Some(SpeechCommand::new(
+                "espeak-ng",
+                &["-s", "50", "--", "-hi"],
+                None
+            ))
+        );
+        let piper = speech_command(
+            &TTSBackend::Piper {
+                model: "voice.onnx".to_string(),
+            },
+            "hi",
+            100.0,
+        )
+        .unwrap();
+        assert_eq!(piper.args[2..], ["voice.onnx", "0.50"]);
+        assert_eq!(piper.input.as_deref(), Some("hi"));
+        assert_eq!(
+            speech_command(
+                &TTSBackend::Command {
+                    program: "say".to_string(),
+                    args: vec!["-v".to_string(), "Alex".to_string()],
+                },
+                "hi",
+                0.0
+            ),
+            Some(SpeechCommand::new("say", &["-v", "Alex"], Some("hi")))
+        );
+    }
+}
@@ -1,4 +1,10 @@
 #[cfg(feature = "tts")]
+mod backend;
+mod rules;
+#[cfg(feature = "tts")]
 mod speech_queue;
+mod substitutions;
 mod text_to_speech;
-pub use self::text_to_speech::{TTSController, TTSEvent, TTSSettings};
+pub use self::rules::{SpeechKind, SpeechRule, TTSRules};
+pub use self::substitutions::TTSSubstitutions;
+pub use self::text_to_speech::{TTSBackend, TTSController, TTSEvent, TTSSettings};
@@ -0,0 +1,279 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use anyhow::{bail, Result};
+use log::error;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{io::SaveData, model::Line};
+
+/// What kind of text is about to be spoken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeechKind {
+    Output,
+    Prompt,
+    Input,
+    Info,
+    Error,
+}
+
+impl SpeechKind {
+    const NAMES: [&'static str; 5] = ["output", "prompt", "input", "info", "error"];
+
+    /// Whether the text was sent to or received from the mud. Info and error messages are
+    /// neither.
+    fn direction(&self) -> Option<Direction> {
+        match self {
+            Self::Output | Self::Prompt => Some(Direction::Received),
+            Self::Input => Some(Direction::Sent),
+            Self::Info | Self::Error => None,
+        }
+    }
+}
+
+impl FromStr for SpeechKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "output" => Ok(Self::Output),
+            "prompt" => Ok(Self::Prompt),
+            "input" => Ok(Self::Input),
+            "info" => Ok(Self::Info),
+            "error" => Ok(Self::Error),
+            _ => bail!(
+                "Unknown speech kind: {}, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SpeechKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Output => "output",
+            Self::Prompt => "prompt",
+            Self::Input => "input",
+            Self::Info => "info",
+            Self::Error => "error",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sent" => Ok(Self::Sent),
+            "received" => Ok(Self::Received),
+            _ => bail!("Unknown direction: {}, expected sent or received", s),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sent => write!(f, "sent"),
+            Self::Received => write!(f, "received"),
+        }
+    }
+}
+
+/// Speaks or mutes the text matching all of its criteria. A rule without criteria matches
+/// everything.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechRule {
+    pub speak: bool,
+    /// Rules with a higher priority are checked first.
+    pub priority: i32,
+    /// The line has this tag, eg. set by `line:set_tag()`.
+    pub tag: Option<String>,
+    /// A regular expression matching the text.
+    pub pattern: Option<String>,
+    /// The line was or wasn't matched by a trigger.
+    pub matched: Option<bool>,
+    pub kind: Option<SpeechKind>,
+    pub direction: Option<Direction>,
+    /// Where the line came from, eg. "user" or "script".
+    pub source: Option<String>,
+}
+
+/// The rules deciding what's spoken.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TTSRules {
+    pub rules: Vec<SpeechRule>,
+}
+
+impl TTSRules {
+    pub fn add(&mut self, rule: SpeechRule) -> Result<()> {
+        if let Some(pattern) = &rule.pattern {
+            Regex::new(pattern)?;
+        }
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<SpeechRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index))
+    }
+
+    /// Compiles the rules in the order they're checked, skipping rules with invalid patterns.
+    pub fn compile(&self) -> SpeechRouter {
+        let mut rules: Vec<(SpeechRule, Option<Regex>)> = self
+            .rules
+            .iter()
+            .filter_map(|rule| match rule.pattern.as_deref().map(Regex::new) {
+                Some(Err(err)) => {
+                    error!("[TTS]: Invalid rule pattern: {}", err);
+                    None
+                }
+                Some(Ok(re)) => Some((rule.clone(), Some(re))),
+                None => Some((rule.clone(), None)),
+            })
+            .collect();
+        rules.sort_by_key(|(rule, _)| std::cmp::Reverse(rule.priority));
+        SpeechRouter(rules)
+    }
+}
+
+impl SaveData for TTSRules {
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("tts_rules.ron")
+    }
+}
+
+/// Compiled [TTSRules].
+#[derive(Debug, Default)]
+pub struct SpeechRouter(Vec<(SpeechRule, Option<Regex>)>);
+
+impl SpeechRouter {
+    /// Whether a line should be spoken. The first matching rule decides, text matching no rule
+    /// is spoken.
+    pub fn speaks(&self, line: &Line, kind: SpeechKind) -> bool {
+        self.0
+            .iter()
+            .find(|(rule, re)| {
+                rule.tag.as_ref().is_none_or(|tag| line.tag(tag).is_some())
+                    && re.as_ref().is_none_or(|re| re.is_match(line.clean_line()))
+                    && rule
+                        .matched
+                        .is_none_or(|matched| line.flags.matched == matched)
+                    && rule.kind.is_none_or(|k| k == kind)
+                    && rule
+                        .direction
+                        .is_none_or(|direction| kind.direction() == Some(direction))
+                    && rule
+                        .source
+                        .as_ref()
+                        .is_none_or(|source| line.flags.source.as_ref() == Some(source))
+            })
+            .is_none_or(|(rule, _)| rule.speak)
+    }
+}
+
+#[cfg(test)]
+mod test_rules {
+    use super::*;
+
+    fn router(rules: Vec<SpeechRule>) -> SpeechRouter {
+        let mut tts_rules = TTSRules::default();
+        for rule in rules {
+            tts_rules.add(rule).unwrap();
+        }
+        tts_rules.compile()
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let router = TTSRules::default().compile();
+        assert!(router.speaks(&Line::from("Hello"), SpeechKind::Output));
+    }
+
+    #[test]
+    fn test_priority() {
+        let router = router(vec![
+            SpeechRule {
+                speak: false,
+                kind: Some(SpeechKind::Output),
+                ..Default::default()
+            },
+            SpeechRule {
+                speak: true,
+                priority: 10,
+                pattern: Some("tells you".to_string()),
+                ..Default::default()
+            },
+        ]);
+        assert!(router.speaks(&Line::from("Bob tells you 'hi'"), SpeechKind::Output));
+        assert!(!router.speaks(&Line::from("A rat arrives."), SpeechKind::Output));
+        assert!(router.speaks(&Line::from("hp: 100 >"), SpeechKind::Prompt));
+    }
+
+    #[test]
+    fn test_criteria() {
+        let router = router(vec![
+            SpeechRule {
+                speak: false,
+                direction: Some(Direction::Sent),
+                ..Default::default()
+            },
+            SpeechRule {
+                speak: false,
+                tag: Some("chat".to_string()),
+                ..Default::default()
+            },
+            SpeechRule {
+                speak: false,
+                matched: Some(true),
+                source: Some("script".to_string()),
+                ..Default::default()
+            },
+        ]);
+        assert!(!router.speaks(&Line::from("look"), SpeechKind::Input));
+        assert!(router.speaks(&Line::from("Info"), SpeechKind::Info));
+
+        let mut chat = Line::from("[chat] hello");
+        assert!(router.speaks(&chat, SpeechKind::Output));
+        chat.set_tag("chat", "");
+        assert!(!router.speaks(&chat, SpeechKind::Output));
+
+        let mut line = Line::from("Matched");
+        line.flags.matched = true;
+        assert!(router.speaks(&line, SpeechKind::Output));
+        line.flags.source = Some("script".to_string());
+        assert!(!router.speaks(&line, SpeechKind::Output));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let mut rules = TTSRules::default();
+        assert!(rules
+            .add(SpeechRule {
+                pattern: Some("(".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(rules.remove(0), None);
+        assert!("window".parse::<SpeechKind>().is_err());
+        assert_eq!("prompt".parse::<SpeechKind>().unwrap(), SpeechKind::Prompt);
+        assert_eq!(SpeechKind::Prompt.to_string(), "prompt");
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::error;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::io::SaveData;
+
+/// Text matching `pattern` is spoken as `spoken`, which may refer to capture groups as eg. `$1`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Substitution {
+    pub pattern: String,
+    pub spoken: String,
+}
+
+impl Substitution {
+    fn new(pattern: &str, spoken: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            spoken: spoken.to_string(),
+        }
+    }
+}
+
+/// The substitutions applied to text before it's spoken, in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TTSSubstitutions {
+    pub substitutions: Vec<Substitution>,
+}
+
+impl Default for TTSSubstitutions {
+    fn default() -> Self {
+        Self {
+            substitutions: vec![
+                Substitution::new(r"(?i)\bhp\b", "hit points"),
+                Substitution::new(r"(?i)\bsp\b", "spell points"),
+                Substitution::new(r"(?i)\bmp\b", "mana points"),
+                Substitution::new(r"(?i)\b(exp|xp)\b", "experience"),
+                Substitution::new(r"(?i)\bnw\b", "northwest"),
+                Substitution::new(r"(?i)\bne\b", "northeast"),
+                Substitution::new(r"(?i)\bsw\b", "southwest"),
+                Substitution::new(r"(?i)\bse\b", "southeast"),
+                Substitution::new(r"(?i)\bafk\b", "away from keyboard"),
+            ],
+        }
+    }
+}
+
+impl TTSSubstitutions {
+    /// Adds a substitution, or changes the spoken form of an existing pattern.
+    pub fn add(&mut self, pattern: &str, spoken: &str) -> Result<()> {
+        Regex::new(pattern)?;
+        if let Some(sub) = self
+            .substitutions
+            .iter_mut()
+            .find(|sub| sub.pattern == pattern)
+        {
+            sub.spoken = spoken.to_string();
+        } else {
+            self.substitutions.push(Substitution::new(pattern, spoken));
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        let len = self.substitutions.len();
+        self.substitutions.retain(|sub| sub.pattern != pattern);
+        self.substitutions.len() != len
+    }
+
+    /// Compiles the patterns, skipping any that aren't valid regular expressions.
+    pub fn compile(&self) -> Substituter {
+        Substituter(
+            self.substitutions
+                .iter()
+                .filter_map(|sub| match Regex::new(&sub.pattern) {
+                    Ok(re) => Some((re, sub.spoken.clone())),
+                    Err(err) => {
+                        error!("[TTS]: Invalid substitution '{}': {}", sub.pattern, err);
+                        None
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+impl SaveData for TTSSubstitutions {
+    fn is_pretty() -> bool {
+        true
+    }
+
+    fn relative_path() -> PathBuf {
+        crate::CONFIG_DIR.join("tts_substitutions.ron")
+    }
+}
+
+/// Compiled [TTSSubstitutions].
+#[derive(Debug, Default)]
+pub struct Substituter(Vec<(Regex, String)>);
+
+impl Substituter {
+    pub fn apply(&self, text: &str) -> String {
+        self.0.iter().fold(text.to_string(), |text, (re, spoken)| {
+            re.replace_all(&text, spoken.as_str()).to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_substitutions {
+    use super::*;
+
+    #[test]
+    fn test_default_substitutions() {
+        let substituter = TTSSubstitutions::default().compile();
+        assert_eq!(
+            substituter.apply("HP: 100 Exp: 20, exits: nw se"),
+            "hit points: 100 experience: 20, exits: northwest southeast"
+        );
+        assert_eq!(substituter.apply("Chomp the sphinx"), "Chomp the sphinx");
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut subs = TTSSubstitutions {
+            substitutions: vec![],
+        };
+        subs.add(r"\[(\d+)%\]", "$1 percent").unwrap();
+        subs.add("gate", "the gate").unwrap();
+        subs.add("gate", "the city gate").unwrap();
+        assert!(subs.add("(", "paren").is_err());
+        assert_eq!(subs.substitutions.len(), 2);
+        assert_eq!(
+            subs.compile().apply("[50%] at gate"),
+            "50 percent at the city gate"
+        );
+        assert!(subs.remove("gate"));
+        assert!(!subs.remove("gate"));
+
+        subs.substitutions.push(Substitution::new("(", "paren"));
+        assert_eq!(subs.compile().apply("[5%] ("), "5 percent (");
+    }
+}
@@ -1,19 +1,31 @@
-use std::{path::PathBuf, sync::mpsc::Sender};
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, mpsc::Sender, Arc},
+};
 
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "tts")]
 use {
-    super::speech_queue::SpeechQueue,
+    super::{
+        backend::{create_backend, SpeechBackend},
+        speech_queue::SpeechQueue,
+    },
     anyhow::Result,
     log::{debug, error},
     std::{
-        sync::mpsc::{channel, Receiver},
+        sync::{
+            atomic::Ordering,
+            mpsc::{channel, Receiver},
+        },
         thread,
     },
-    tts::Tts as TTS,
 };
 
+use super::{
+    rules::{SpeechKind, SpeechRouter, TTSRules},
+    substitutions::{Substituter, TTSSubstitutions},
+};
 use crate::{io::SaveData, model::Line};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -34,19 +46,67 @@ pub enum TTSEvent {
     ScanForwardToInput,
     Begin,
     End,
+    SetBackend(TTSBackend),
+    ReloadSubstitutions,
+    ReloadRules,
     Shutdown,
 }
 
 pub struct TTSController {
     rt: Option<Sender<TTSEvent>>,
     enabled: bool,
+    speaking: Arc<AtomicBool>,
+    substituter: Substituter,
+    router: SpeechRouter,
     pub settings: TTSSettings,
 }
 
+/// The speech engine text-to-speech is spoken through.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TTSBackend {
+    /// The platform's speech engine
+    #[default]
+    Builtin,
+    /// Speech-dispatcher through `spd-say`
+    SpeechDispatcher,
+    /// Piper voices, played with `aplay`
+    Piper {
+        model: String,
+    },
+    EspeakNg,
+    /// A program reading the text to speak from stdin
+    Command {
+        program: String,
+        args: Vec<String>,
+    },
+}
+
+impl TTSBackend {
+    pub const NAMES: [&'static str; 5] = [
+        "builtin",
+        "speech-dispatcher",
+        "piper",
+        "espeak-ng",
+        "command",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Builtin => Self::NAMES[0],
+            Self::SpeechDispatcher => Self::NAMES[1],
+            Self::Piper { .. } => Self::NAMES[2],
+            Self::EspeakNg => Self::NAMES[3],
+            Self::Command { .. } => Self::NAMES[4],
+        }
+    }
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct TTSSettings {
     pub echo_keys: bool,
     pub rate: f32,
+    #[serde(default)]
+    pub backend: TTSBackend,
 }
 
 impl SaveData for TTSSettings {
@@ -61,16 +121,30 @@ impl SaveData for TTSSettings {
 
 impl TTSController {
     pub fn new(enabled: bool, no_thread: bool) -> Self {
-        let rt = if !no_thread { spawn_tts_thread() } else { None };
-
         let settings = if !cfg!(test) {
             TTSSettings::load()
         } else {
             TTSSettings::default()
         };
+
+        let (substitutions, rules) = if !cfg!(test) {
+            (TTSSubstitutions::load(), TTSRules::load())
+        } else {
+            (TTSSubstitutions::default(), TTSRules::default())
+        };
+
+        let speaking = Arc::new(AtomicBool::new(false));
+        let rt = if !no_thread {
+            spawn_tts_thread(settings.backend.clone(), speaking.clone())
+        } else {
+            None
+        };
         let tts_ctrl = Self {
             rt,
             enabled,
+            speaking,
+            substituter: substitutions.compile(),
+            router: rules.compile(),
             settings,
         };
 
@@ -80,14 +154,35 @@ impl TTSController {
         tts_ctrl
     }
 
+    /// Set while an utterance is being spoken, used to duck the music.
+    pub fn speaking(&self) -> Arc<AtomicBool> {
+        self.speaking.clone()
+    }
+
     fn reload_settings(&mut self) {
         self.settings = TTSSettings::load();
     }
 
+    /// Applies the substitution dictionary to the text to speak.
+    fn substitute(&self, event: TTSEvent) -> TTSEvent {
+        match event {
+            TTSEvent::Speak(msg, interrupt) => {
+                TTSEvent::Speak(self.substituter.apply(&msg), interrupt)
+            }
+            TTSEvent::SpeakInput(msg) => TTSEvent::SpeakInput(self.substituter.apply(&msg)),
+            TTSEvent::SpeakDirect(msg) => TTSEvent::SpeakDirect(self.substituter.apply(&msg)),
+            _ => event,
+        }
+    }
+
     fn send(&self, event: TTSEvent) {
         if let Some(rt) = &self.rt {
+            let event = self.substitute(event);
             match event {
-                TTSEvent::SetRate(_) | TTSEvent::ChangeRate(_) | TTSEvent::SpeakDirect(_) => {
+                TTSEvent::SetRate(_)
+                | TTSEvent::ChangeRate(_)
+                | TTSEvent::SpeakDirect(_)
+                | TTSEvent::SetBackend(_) => {
                     rt.send(event).ok();
                 }
                 _ => {
@@ -118,6 +213,18 @@ impl TTSController {
                 self.settings.echo_keys = enabled;
                 self.settings.save();
             }
+            TTSEvent::SetBackend(ref backend) => {
+                self.reload_settings();
+                self.settings.backend = backend.clone();
+                self.settings.save();
+                self.send(event);
+            }
+            TTSEvent::ReloadSubstitutions => {
+                self.substituter = TTSSubstitutions::load().compile();
+            }
+            TTSEvent::ReloadRules => {
+                self.router = TTSRules::load().compile();
+            }
             _ => {
                 self.send(event);
             }
@@ -140,8 +247,8 @@ impl TTSController {
         }
     }
 
-    pub fn speak_line(&self, line: &Line) {
-        if !line.flags.tts_gag {
+    pub fn speak_line(&self, line: &Line, kind: SpeechKind) {
+        if !line.flags.tts_gag && self.router.speaks(line, kind) {
             let speak = line.clean_line().trim();
             for l in speak.lines() {
                 self.send(TTSEvent::Speak(l.to_string(), line.flags.tts_interrupt));
@@ -149,9 +256,15 @@ impl TTSController {
         }
     }
 
-    pub fn speak_input(&self, line: &str) {
+    pub fn speak_input(&self, send: &Line) {
+        let Some(line) = send.print_line() else {
+            return;
+        };
         if self.enabled {
             self.flush();
+            if !self.router.speaks(send, SpeechKind::Input) {
+                return;
+            }
             let input = line.trim();
             let speak = if !input.is_empty() {
                 format!("input: {input}")
@@ -167,11 +280,15 @@ impl TTSController {
     }
 
     pub fn speak_info(&self, msg: &str) {
-        self.send(TTSEvent::Speak(format!("info: {msg}"), false));
+        if self.router.speaks(&Line::from(msg), SpeechKind::Info) {
+            self.send(TTSEvent::Speak(format!("info: {msg}"), false));
+        }
     }
 
     pub fn speak_error(&self, msg: &str) {
-        self.send(TTSEvent::Speak(format!("error: {msg}"), false));
+        if self.router.speaks(&Line::from(msg), SpeechKind::Error) {
+            self.send(TTSEvent::Speak(format!("error: {msg}"), false));
+        }
     }
 
     pub fn flush(&self) {
@@ -187,8 +304,8 @@ impl TTSController {
 
 #[inline]
 #[cfg(feature = "tts")]
-fn speak(tts: &mut TTS, msg: &str, force: bool) -> bool {
-    if let Err(err) = tts.speak(msg, force) {
+fn speak(backend: &mut dyn SpeechBackend, msg: &str, force: bool) -> bool {
+    if let Err(err) = backend.speak(msg, force) {
         error!("[TTS]: {}", err.to_string());
         true
     } else {
@@ -197,7 +314,12 @@ fn speak(tts: &mut TTS, msg: &str, force: bool) -> bool {
 }
 
 #[cfg(feature = "tts")]
-fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
+fn run_tts(
+    mut backend: Box<dyn SpeechBackend>,
+    rx: Receiver<TTSEvent>,
+    tx: Sender<TTSEvent>,
+    speaking: Arc<AtomicBool>,
+) -> Result<()> {
     let mut queue = SpeechQueue::new(1000);
     let rx = rx;
 
@@ -206,7 +328,7 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
         match event {
             TTSEvent::Speak(msg, force) => {
                 if let Some(msg) = queue.push(msg, force) {
-                    if speak(tts, &msg, force) {
+                    if speak(backend.as_mut(), &msg, force) {
                         continue;
                     }
                 }
@@ -216,19 +338,19 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
             }
             TTSEvent::SpeakDirect(msg) => {
                 if !msg.is_empty() {
-                    tts.speak(msg, true).ok();
+                    backend.speak(&msg, true).ok();
                 }
             }
             TTSEvent::Next(step) => {
                 if let Some(msg) = queue.next(step) {
-                    if speak(tts, &msg, true) {
+                    if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
             }
             TTSEvent::Prev(step) => {
                 if let Some(msg) = queue.prev(step) {
-                    if speak(tts, &msg, true) {
+                    if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
@@ -236,10 +358,10 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
             TTSEvent::ScanBack(step) => {
                 if let Some(msg) = queue.scan_back(step) {
                     if msg.is_empty() {
-                        if speak(tts, "blank", true) {
+                        if speak(backend.as_mut(), "blank", true) {
                             continue;
                         }
-                    } else if speak(tts, &msg, true) {
+                    } else if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
@@ -247,10 +369,10 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
             TTSEvent::ScanForward(step) => {
                 if let Some(msg) = queue.scan_forward(step) {
                     if msg.is_empty() {
-                        if speak(tts, "blank", true) {
+                        if speak(backend.as_mut(), "blank", true) {
                             continue;
                         }
-                    } else if speak(tts, &msg, true) {
+                    } else if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
@@ -258,10 +380,10 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
             TTSEvent::ScanBackToInput => {
                 if let Some(msg) = queue.scan_back_to_input() {
                     if msg.is_empty() {
-                        if speak(tts, "blank", true) {
+                        if speak(backend.as_mut(), "blank", true) {
                             continue;
                         }
-                    } else if speak(tts, &msg, true) {
+                    } else if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
@@ -269,44 +391,56 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
             TTSEvent::ScanForwardToInput => {
                 if let Some(msg) = queue.scan_forward_to_input() {
                     if msg.is_empty() {
-                        if speak(tts, "blank", true) {
+                        if speak(backend.as_mut(), "blank", true) {
                             continue;
                         }
-                    } else if speak(tts, &msg, true) {
+                    } else if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
             }
             TTSEvent::Begin => {
                 if let Some(msg) = queue.current() {
-                    if speak(tts, &msg, true) {
+                    if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
             }
             TTSEvent::End => {
                 if let Some(msg) = queue.next(1) {
-                    if speak(tts, &msg, true) {
+                    if speak(backend.as_mut(), &msg, true) {
                         continue;
                     }
                 }
             }
             TTSEvent::Flush => {
                 queue.flush();
-                tts.stop().unwrap();
+                backend.stop().ok();
+                speaking.store(false, Ordering::Relaxed);
             }
             TTSEvent::SetRate(rate) => {
-                tts.set_rate(rate.clamp(-100.0, 100.0))?;
+                backend.set_rate(rate.clamp(-100.0, 100.0))?;
             }
             TTSEvent::ChangeRate(increment) => {
-                tts.set_rate((tts.get_rate()? + increment).clamp(-100.0, 100.0))?;
+                backend.set_rate((backend.rate() + increment).clamp(-100.0, 100.0))?;
+            }
+            TTSEvent::SetBackend(config) => {
+                match create_backend(&config, tx.clone(), speaking.clone()) {
+                    Ok(mut new_backend) => {
+                        backend.stop().ok();
+                        new_backend.set_rate(backend.rate()).ok();
+                        backend = new_backend;
+                    }
+                    Err(err) => error!("[TTS]: {}", err.to_string()),
+                }
             }
             TTSEvent::Shutdown => {
-                tts.stop().unwrap();
+                backend.stop().ok();
+                speaking.store(false, Ordering::Relaxed);
                 break;
             }
             TTSEvent::KeyPress(key) => {
-                tts.speak(key, true)?;
+                backend.speak(&key.to_string(), true)?;
             }
             _ => {}
         }
@@ -315,34 +449,50 @@ fn run_tts(tts: &mut TTS, rx: Receiver<TTSEvent>) -> Result<()> {
 }
 
 #[cfg(feature = "tts")]
-fn setup_callbacks(tts: &mut TTS, tx: Sender<TTSEvent>) -> Result<(), tts::Error> {
-    tts.on_utterance_end(Some(Box::new(move |_| {
-        tx.send(TTSEvent::Next(1)).ok();
-    })))
-}
-
-#[cfg(feature = "tts")]
-fn spawn_tts_thread() -> Option<Sender<TTSEvent>> {
+fn spawn_tts_thread(backend: TTSBackend, speaking: Arc<AtomicBool>) -> Option<Sender<TTSEvent>> {
     let (tx, rx): (Sender<TTSEvent>, Receiver<TTSEvent>) = channel();
     let ttx = tx.clone();
     thread::Builder::new()
         .name("tts-thread".to_string())
-        .spawn(move || match TTS::default() {
-            Ok(mut tts) => {
-                if let Err(err) = setup_callbacks(&mut tts, ttx) {
-                    error!("[TTS]: {}", err.to_string());
-                }
-                if let Err(err) = run_tts(&mut tts, rx) {
-                    error!("[TTS]: {}", err.to_string());
+        .spawn(
+            move || match create_backend(&backend, ttx.clone(), speaking.clone()) {
+                Ok(backend) => {
+                    if let Err(err) = run_tts(backend, rx, ttx, speaking) {
+                        error!("[TTS]: {}", err.to_string());
+                    }
                 }
-            }
-            Err(err) => error!("[TTS]: {}", err.to_string()),
-        })
+                Err(err) => error!("[TTS]: {}", err.to_string()),
+            },
+        )
         .unwrap();
     Some(tx)
 }
 
 #[cfg(not(feature = "tts"))]
-fn spawn_tts_thread() -> Option<Sender<TTSEvent>> {
+fn spawn_tts_thread(_backend: TTSBackend, _speaking: Arc<AtomicBool>) -> Option<Sender<TTSEvent>> {
     None
 }
+
+#[cfg(test)]
+mod test_tts_controller {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    #[test]
+    fn test_substitutions_applied() {
+        let (tx, rx) = channel();
+        let mut ctrl = TTSController::new(true, true);
+        ctrl.rt = Some(tx);
+        ctrl.speak("hp low", false);
+        assert_eq!(
+            rx.try_recv(),
+            Ok(TTSEvent::Speak("hit points low".to_string(), false))
+        );
+        ctrl.handle(TTSEvent::SpeakDirect("Exits: nw".to_string()));
+        assert_eq!(
+            rx.try_recv(),
+            Ok(TTSEvent::SpeakDirect("Exits: northwest".to_string()))
+        );
+    }
+}
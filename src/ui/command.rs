@@ -2,16 +2,19 @@ use crate::event::QuitMethod;
 use crate::model::{Completions, Line, PromptMask, Servers};
 use crate::{event::Event, tts::TTSController};
 use crate::{lua::LuaScript, lua::UiEvent, session::Session, SaveData};
+use libmudtelnet::Parser;
 use log::debug;
 use rs_complete::CompletionTree;
 use std::collections::HashSet;
 use std::thread;
 use std::{
     io::stdin,
-    sync::{mpsc::Sender, Arc, Mutex},
+    sync::{atomic::Ordering, mpsc::Sender, Arc, Mutex},
 };
 use termion::{event::Key, input::TermRead};
 
+use super::keypad::{self, KeypadReader};
+
 #[derive(Default)]
 struct CompletionStepData {
     options: Vec<String>,
@@ -278,7 +281,7 @@ fn parse_key_event(
     tts_ctrl: &mut Arc<Mutex<TTSController>>,
     script: &mut Arc<Mutex<LuaScript>>,
 ) {
-    match key {
+    match keypad::fallback(key) {
         Key::Char('\n') => {
             let mut line = Line::from(buffer.submit());
             line.flags.source = Some("user".to_string());
@@ -318,6 +321,117 @@ fn parse_key_event(
     };
 }
 
+/// Translates a key to the bytes a terminal would send for it, for keys that go straight to the
+/// server in character mode. Keys left to Blightmud, like scrolling, return `None`.
+fn char_mode_bytes(key: Key) -> Option<Vec<u8>> {
+    let bytes = match key {
+        Key::Char('\n') => b"\r\n".to_vec(),
+        Key::Char(c) => c.to_string().into_bytes(),
+        Key::Ctrl('c') => return None,
+        Key::Ctrl(c) if c.is_ascii_alphabetic() => vec![c.to_ascii_lowercase() as u8 & 0x1f],
+        Key::Backspace => vec![0x7f],
+        Key::Esc => vec![0x1b],
+        Key::Up => b"\x1b[A".to_vec(),
+        Key::Down => b"\x1b[B".to_vec(),
+        Key::Right => b"\x1b[C".to_vec(),
+        Key::Left => b"\x1b[D".to_vec(),
+        Key::Home => b"\x1b[H".to_vec(),
+        Key::End => b"\x1b[F".to_vec(),
+        Key::Delete => b"\x1b[3~".to_vec(),
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// A decoded key press as handed to `blight.on_key` callbacks.
+#[derive(Debug, PartialEq, Eq)]
+struct KeyPress {
+    key: String,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl KeyPress {
+    fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+}
+
+fn char_name(c: char) -> String {
+    match c {
+        '\n' => "enter".to_string(),
+        '\t' => "tab".to_string(),
+        '\u{7f}' => "backspace".to_string(),
+        '\u{1b}' => "escape".to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn key_press(key: Key) -> Option<KeyPress> {
+    if let Some(name) = keypad::key_name(key) {
+        return Some(KeyPress::new(name));
+    }
+    let press = match key {
+        Key::Char(c) => KeyPress {
+            shift: c.is_uppercase(),
+            ..KeyPress::new(&char_name(c))
+        },
+        Key::Ctrl(c) => KeyPress::new(&char_name(c)).ctrl(),
+        Key::Alt(c) => KeyPress::new(&char_name(c)).alt(),
+        Key::F(n) => KeyPress::new(&format!("f{n}")),
+        Key::Backspace => KeyPress::new("backspace"),
+        Key::Esc => KeyPress::new("escape"),
+        Key::Left => KeyPress::new("left"),
+        Key::ShiftLeft => KeyPress::new("left").shift(),
+        Key::AltLeft => KeyPress::new("left").alt(),
+        Key::CtrlLeft => KeyPress::new("left").ctrl(),
+        Key::Right => KeyPress::new("right"),
+        Key::ShiftRight => KeyPress::new("right").shift(),
+        Key::AltRight => KeyPress::new("right").alt(),
+        Key::CtrlRight => KeyPress::new("right").ctrl(),
+        Key::Up => KeyPress::new("up"),
+        Key::ShiftUp => KeyPress::new("up").shift(),
+        Key::AltUp => KeyPress::new("up").alt(),
+        Key::CtrlUp => KeyPress::new("up").ctrl(),
+        Key::Down => KeyPress::new("down"),
+        Key::ShiftDown => KeyPress::new("down").shift(),
+        Key::AltDown => KeyPress::new("down").alt(),
+        Key::CtrlDown => KeyPress::new("down").ctrl(),
+        Key::Home => KeyPress::new("home"),
+        Key::CtrlHome => KeyPress::new("home").ctrl(),
+        Key::End => KeyPress::new("end"),
+        Key::CtrlEnd => KeyPress::new("end").ctrl(),
+        Key::PageUp => KeyPress::new("pageup"),
+        Key::PageDown => KeyPress::new("pagedown"),
+        Key::BackTab => KeyPress::new("tab").shift(),
+        Key::Delete => KeyPress::new("delete"),
+        Key::Insert => KeyPress::new("insert"),
+        _ => return None,
+    };
+    Some(press)
+}
+
 fn check_command_binds(
     cmd: termion::event::Key,
     buffer: &mut CommandBuffer,
@@ -326,20 +440,26 @@ fn check_command_binds(
 ) -> bool {
     let mut ran = false;
     if let Ok(mut script) = script.lock() {
-        ran = match cmd {
-            Key::Ctrl(c) => script.check_bindings(&human_key("ctrl-", c)),
-            Key::Alt(c) => script.check_bindings(&human_key("alt-", c)),
-            Key::F(n) => script.check_bindings(&format!("f{n}")),
-            Key::PageUp => script.check_bindings("pageup") || script.check_bindings("page up"),
-            Key::PageDown => {
-                script.check_bindings("pagedown") || script.check_bindings("page down")
-            }
-            Key::Home => script.check_bindings("home"),
-            Key::End => script.check_bindings("end"),
-            Key::Up => script.check_bindings("up"),
-            Key::Down => script.check_bindings("down"),
-            _ => false,
-        }
+        let consumed = key_press(cmd)
+            .is_some_and(|press| script.on_key(&press.key, press.ctrl, press.alt, press.shift));
+        ran = consumed
+            || match cmd {
+                Key::Ctrl(c) => script.check_bindings(&human_key("ctrl-", c)),
+                Key::Alt(c) => script.check_bindings(&human_key("alt-", c)),
+                Key::F(n) => script.check_bindings(&format!("f{n}")),
+                Key::PageUp => script.check_bindings("pageup") || script.check_bindings("page up"),
+                Key::PageDown => {
+                    script.check_bindings("pagedown") || script.check_bindings("page down")
+                }
+                Key::Home => script.check_bindings("home"),
+                Key::End => script.check_bindings("end"),
+                Key::Up => script.check_bindings("up"),
+                Key::Down => script.check_bindings("down"),
+                Key::Char(_) => {
+                    keypad::key_name(cmd).is_some_and(|name| script.check_bindings(name))
+                }
+                _ => false,
+            };
     }
     handle_script_ui_io(buffer, script, writer);
     ran
@@ -403,6 +523,7 @@ fn handle_script_ui_io(
             UiEvent::ScrollUp => writer.send(Event::ScrollUp).unwrap(),
             UiEvent::ScrollTop => writer.send(Event::ScrollTop).unwrap(),
             UiEvent::ScrollBottom => writer.send(Event::ScrollBottom).unwrap(),
+            UiEvent::TogglePause => writer.send(Event::TogglePause).unwrap(),
             UiEvent::Complete => buffer.tab_complete(),
             UiEvent::Unknown(_) => {}
         });
@@ -413,6 +534,76 @@ fn handle_script_ui_io(
     }
 }
 
+/// Runs a key press through the bindings and the prompt, and passes on the prompt's new state.
+fn handle_key(
+    key: Key,
+    buffer: &mut CommandBuffer,
+    writer: &Sender<Event>,
+    tts_ctrl: &mut Arc<Mutex<TTSController>>,
+    script: &mut Arc<Mutex<LuaScript>>,
+) {
+    let orig_pos = buffer.get_pos();
+    let orig_len = buffer.buffer.len();
+    let bind_ran = check_command_binds(key, buffer, script, writer);
+    if !bind_ran {
+        parse_key_event(key, buffer, writer, tts_ctrl, script);
+    }
+    if orig_len == buffer.buffer.len() && orig_pos != buffer.get_pos() {
+        writer
+            .send(Event::UserInputCursor(buffer.get_pos()))
+            .unwrap();
+    } else if !bind_ran || orig_len != buffer.buffer.len() {
+        if let Ok(mut luascript) = script.lock() {
+            luascript.set_prompt_mask_content(&buffer.prompt_mask);
+            luascript.set_prompt_content(buffer.get_buffer(), buffer.get_pos());
+        }
+        writer
+            .send(Event::UserInputBuffer(
+                buffer.get_buffer(),
+                buffer.get_pos(),
+            ))
+            .unwrap();
+    }
+}
+
+/// Sends a bracketed paste as commands when a paste template or transform is set, see
+/// `/help paste`. Otherwise it's typed in as before bracketed paste was turned on.
+fn handle_paste(
+    text: &str,
+    char_mode: bool,
+    keep_blank: bool,
+    buffer: &mut CommandBuffer,
+    writer: &Sender<Event>,
+    tts_ctrl: &mut Arc<Mutex<TTSController>>,
+    script: &mut Arc<Mutex<LuaScript>>,
+) {
+    let commands = script
+        .lock()
+        .ok()
+        .and_then(|mut script| script.on_paste(text, keep_blank));
+    if let Some(commands) = commands {
+        for command in commands {
+            let mut line = Line::from(command);
+            line.flags.source = Some("user".to_string());
+            writer.send(Event::ServerInput(line)).unwrap();
+        }
+        handle_script_ui_io(buffer, script, writer);
+    } else if char_mode {
+        let bytes: Vec<u8> = text
+            .chars()
+            .filter_map(|c| char_mode_bytes(Key::Char(c)))
+            .flatten()
+            .collect();
+        writer
+            .send(Event::ServerSend(Parser::escape_iac(bytes)))
+            .unwrap();
+    } else {
+        for c in text.chars() {
+            handle_key(Key::Char(c), buffer, writer, tts_ctrl, script);
+        }
+    }
+}
+
 pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name("input-thread".to_string())
@@ -422,7 +613,8 @@ pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
             let mut script = session.lua_script.clone();
             let stdin = stdin();
             let buffer = session.command_buffer.clone();
-            let mut tts_ctrl = session.tts_ctrl;
+            let mut tts_ctrl = session.tts_ctrl.clone();
+            let mut paste: Option<String> = None;
 
             if let Ok(mut buffer) = buffer.lock() {
                 for server in Servers::load().keys() {
@@ -433,14 +625,37 @@ pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
                     .insert(include_str!("../../resources/completions.txt"));
             }
 
-            for e in stdin.events() {
+            for e in KeypadReader::new(stdin).events() {
                 match e.unwrap() {
-                    termion::event::Event::Key(key) => {
-                        if let Ok(mut buffer) = buffer.lock() {
-                            let orig_pos = buffer.get_pos();
-                            let orig_len = buffer.buffer.len();
-                            let bind_ran = check_command_binds(key, &mut buffer, &script, &writer);
-                            if !bind_ran {
+                    termion::event::Event::Key(Key::Char(keypad::PASTE_START)) => {
+                        paste = Some(String::new())
+                    }
+                    termion::event::Event::Key(Key::Char(keypad::PASTE_END)) => {
+                        let keep_blank = session.input_rules.lock().unwrap().keep_blank_lines();
+                        if let (Some(text), Ok(mut buffer)) = (paste.take(), buffer.lock()) {
+                            handle_paste(
+                                &text,
+                                session.char_mode_active(),
+                                keep_blank,
+                                &mut buffer,
+                                &writer,
+                                &mut tts_ctrl,
+                                &mut script,
+                            );
+                        }
+                    }
+                    termion::event::Event::Key(key) if paste.is_some() => {
+                        if let (Key::Char(c), Some(text)) = (key, paste.as_mut()) {
+                            text.push(c);
+                        }
+                    }
+                    termion::event::Event::Key(key) if session.char_mode_active() => {
+                        if let Some(bytes) = char_mode_bytes(keypad::fallback(key)) {
+                            writer
+                                .send(Event::ServerSend(Parser::escape_iac(bytes)))
+                                .unwrap();
+                        } else if let Ok(mut buffer) = buffer.lock() {
+                            if !check_command_binds(key, &mut buffer, &script, &writer) {
                                 parse_key_event(
                                     key,
                                     &mut buffer,
@@ -449,23 +664,17 @@ pub fn spawn_input_thread(session: Session) -> thread::JoinHandle<()> {
                                     &mut script,
                                 );
                             }
-                            if orig_len == buffer.buffer.len() && orig_pos != buffer.get_pos() {
-                                writer
-                                    .send(Event::UserInputCursor(buffer.get_pos()))
-                                    .unwrap();
-                            } else if !bind_ran || orig_len != buffer.buffer.len() {
-                                if let Ok(mut luascript) = script.lock() {
-                                    luascript.set_prompt_mask_content(&buffer.prompt_mask);
-                                    luascript
-                                        .set_prompt_content(buffer.get_buffer(), buffer.get_pos());
-                                }
-                                writer
-                                    .send(Event::UserInputBuffer(
-                                        buffer.get_buffer(),
-                                        buffer.get_pos(),
-                                    ))
-                                    .unwrap();
-                            }
+                        }
+                    }
+                    termion::event::Event::Key(Key::Char(' '))
+                        if session.paging.load(Ordering::Relaxed)
+                            && buffer.lock().is_ok_and(|buffer| buffer.buffer.is_empty()) =>
+                    {
+                        writer.send(Event::NextPage).unwrap();
+                    }
+                    termion::event::Event::Key(key) => {
+                        if let Ok(mut buffer) = buffer.lock() {
+                            handle_key(key, &mut buffer, &writer, &mut tts_ctrl, &mut script);
                         }
                     }
                     termion::event::Event::Mouse(event) => parse_mouse_event(event, &writer),
@@ -671,6 +880,39 @@ mod command_test {
         assert_eq!(human_key("f", 'x'), "fx");
     }
 
+    #[test]
+    fn test_key_press() {
+        use super::{key_press, KeyPress};
+
+        assert_eq!(key_press(Key::Char('a')), Some(KeyPress::new("a")));
+        assert_eq!(key_press(Key::Char('A')), Some(KeyPress::new("A").shift()));
+        assert_eq!(key_press(Key::Char('\n')), Some(KeyPress::new("enter")));
+        assert_eq!(key_press(Key::Ctrl('p')), Some(KeyPress::new("p").ctrl()));
+        assert_eq!(key_press(Key::Alt('x')), Some(KeyPress::new("x").alt()));
+        assert_eq!(key_press(Key::CtrlUp), Some(KeyPress::new("up").ctrl()));
+        assert_eq!(key_press(Key::BackTab), Some(KeyPress::new("tab").shift()));
+        assert_eq!(key_press(Key::F(5)), Some(KeyPress::new("f5")));
+        assert_eq!(key_press(Key::Null), None);
+    }
+
+    #[test]
+    fn test_char_mode_bytes() {
+        use super::char_mode_bytes;
+
+        assert_eq!(char_mode_bytes(Key::Char('a')), Some(b"a".to_vec()));
+        assert_eq!(char_mode_bytes(Key::Char('\n')), Some(b"\r\n".to_vec()));
+        assert_eq!(
+            char_mode_bytes(Key::Char('ö')),
+            Some("ö".as_bytes().to_vec())
+        );
+        assert_eq!(char_mode_bytes(Key::Ctrl('d')), Some(vec![0x04]));
+        assert_eq!(char_mode_bytes(Key::Backspace), Some(vec![0x7f]));
+        assert_eq!(char_mode_bytes(Key::Up), Some(b"\x1b[A".to_vec()));
+        assert_eq!(char_mode_bytes(Key::Ctrl('c')), None);
+        assert_eq!(char_mode_bytes(Key::PageUp), None);
+        assert_eq!(char_mode_bytes(Key::F(1)), None);
+    }
+
     #[test]
     fn test_completions() {
         let mut buffer = get_command().0;
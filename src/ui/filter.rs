@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use super::history::History;
+use crate::model::Regex;
+
+/// The scrollback narrowed down to the lines matching a pattern. The full history is set aside
+/// while the filtered lines are shown in its place.
+pub struct Filter {
+    pub pattern: Regex,
+    full: History,
+    origins: Vec<usize>,
+}
+
+impl Filter {
+    /// Returns the filter along with the history of matching lines to show.
+    pub fn new(pattern: Regex, full: History) -> (Self, History) {
+        let origins = full.find_all(&pattern);
+        let mut filtered = History::new();
+        for &index in &origins {
+            filtered.append(full.inner[index].clone());
+        }
+        (
+            Self {
+                pattern,
+                full,
+                origins,
+            },
+            filtered,
+        )
+    }
+
+    /// Appends output to the full history, and the lines matching the pattern to `filtered`.
+    /// Returns the lines that were added to `filtered`.
+    pub fn append(
+        &mut self,
+        filtered: &mut History,
+        line: impl AsRef<str> + Into<Arc<str>>,
+    ) -> Vec<Arc<str>> {
+        let text = line.as_ref();
+        let added = if text.trim().is_empty() {
+            1
+        } else {
+            text.lines().count()
+        };
+        let len = self.full.len();
+        self.full.append(line);
+        let drained = len + added - self.full.len();
+        if drained > 0 {
+            let removed = self
+                .origins
+                .iter()
+                .take_while(|&&index| index < drained)
+                .count();
+            self.origins.drain(0..removed);
+            filtered.remove_oldest(removed);
+            self.origins.iter_mut().for_each(|index| *index -= drained);
+        }
+
+        let mut matched = vec![];
+        for index in self.full.len() - added..self.full.len() {
+            let line = &self.full.inner[index];
+            if self.pattern.is_match(line) {
+                let len = filtered.len();
+                filtered.append(line.clone());
+                if filtered.len() <= len {
+                    self.origins.drain(0..len + 1 - filtered.len());
+                }
+                self.origins.push(index);
+                matched.push(line.clone());
+            }
+        }
+        matched
+    }
+
+    /// Returns the index in the full history of the filtered line at `index`.
+    pub fn origin(&self, index: usize) -> Option<usize> {
+        self.origins.get(index).copied()
+    }
+
+    pub fn full_mut(&mut self) -> &mut History {
+        &mut self.full
+    }
+
+    /// Returns the full history.
+    pub fn restore(self) -> History {
+        self.full
+    }
+}
+
+#[cfg(test)]
+mod test_filter {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let mut history = History::new();
+        for line in ["Bob tells you 'hi'", "A kobold hits you", "You miss"] {
+            history.append(line);
+        }
+        let pattern = Regex::new("tells you", None).unwrap();
+        let (mut filter, mut filtered) = Filter::new(pattern, history);
+        assert_eq!(filtered.inner, vec![Arc::from("Bob tells you 'hi'")]);
+
+        assert!(filter.append(&mut filtered, "You hit a kobold").is_empty());
+        assert_eq!(
+            filter.append(&mut filtered, "Ann tells you 'raid'\nYou dodge"),
+            vec![Arc::from("Ann tells you 'raid'")]
+        );
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered.bytes(), 38);
+        assert_eq!(filter.origin(0), Some(0));
+        assert_eq!(filter.origin(1), Some(4));
+        assert_eq!(filter.origin(2), None);
+
+        let history = filter.restore();
+        assert_eq!(history.len(), 6);
+    }
+
+    #[test]
+    fn test_filter_drain() {
+        let mut history = History::new();
+        history.append("tells you");
+        let pattern = Regex::new("tells you", None).unwrap();
+        let (mut filter, mut filtered) = Filter::new(pattern, history);
+        for _ in 0..filtered.capacity {
+            filter.append(&mut filtered, "spam");
+        }
+        filter.append(&mut filtered, "tells you");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered.bytes(), 9);
+        assert_eq!(filter.origin(0), Some(filter.restore().len() - 1));
+    }
+}
@@ -71,6 +71,10 @@ impl UserInterface for HeadlessScreen {
         Ok(())
     }
 
+    fn find_all(&self, _pattern: &crate::model::Regex) -> Vec<usize> {
+        vec![]
+    }
+
     fn set_host(&mut self, _host: &str, _port: u16) -> anyhow::Result<()> {
         Ok(())
     }
@@ -95,6 +99,18 @@ impl UserInterface for HeadlessScreen {
         Ok(())
     }
 
+    fn set_region(&mut self, _region: crate::model::Region) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn remove_region(&mut self, _name: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn set_region_lines(&mut self, _name: &str, _lines: Vec<String>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn flush(&mut self) {
         std::io::stdout().flush().ok();
     }
@@ -11,15 +11,46 @@ use pulldown_cmark_mdcat::{ResourceUrlHandler, Settings as MDSettings, Theme};
 use std::fmt::Write;
 use syntect::parsing::SyntaxSet;
 
+/// Search results show at most this many characters of the matching line.
+const SNIPPET_LENGTH: usize = 60;
+
 pub struct HelpHandler {
     writer: Sender<Event>,
     files: HashMap<&'static str, &'static str>,
+    registered: HashMap<String, String>,
 }
 
 impl HelpHandler {
     pub fn new(writer: Sender<Event>) -> Self {
         let files = load_files();
-        Self { writer, files }
+        Self {
+            writer,
+            files,
+            registered: HashMap::new(),
+        }
+    }
+
+    /// The names of the help files that ship with Blightmud.
+    pub fn builtin_topics() -> Vec<&'static str> {
+        load_files().into_keys().collect()
+    }
+
+    /// Adds a help topic written in markdown, replacing any earlier topic of that name. Topics
+    /// that ship with Blightmud can't be replaced.
+    pub fn register(&mut self, topic: String, content: String) {
+        if !self.files.contains_key(topic.as_str()) {
+            self.registered.insert(topic, content);
+        }
+    }
+
+    pub fn show_search(&self, pattern: &str) -> Result<()> {
+        if let Some(line) = self.search_helpfiles(pattern, "Help topics mentioning") {
+            self.writer.send(Event::Output(line))?;
+        } else {
+            self.writer
+                .send(Event::Info(format!("No help topics mention '{pattern}'")))?;
+        }
+        Ok(())
     }
 
     pub fn show_help(&self, file: &str, lock: bool) -> Result<()> {
@@ -29,7 +60,10 @@ impl HelpHandler {
         }
         if let Some(line) = self.parse_helpfile(file) {
             self.writer.send(Event::Output(line)).unwrap();
-        } else if let Some(line) = self.search_helpfiles(file) {
+        } else if let Some(line) = self.search_helpfiles(
+            file,
+            "No such help file exists.\nThe following help files contain a match for your search",
+        ) {
             self.writer.send(Event::Output(line)).unwrap();
         } else {
             self.writer
@@ -56,7 +90,10 @@ impl HelpHandler {
     }
 
     fn get_plugin_helpfile_path(&self, file: &str) -> PathBuf {
-        crate::DATA_DIR.join("plugins").join(file).join("README.md")
+        crate::CACHE_DIR
+            .join("plugins")
+            .join(file)
+            .join("README.md")
     }
 
     fn parse_markdown(&self, file_content: &str) -> Option<Line> {
@@ -93,7 +130,9 @@ impl HelpHandler {
 
     fn parse_helpfile(&self, file: &str) -> Option<Line> {
         let plugin_help_path = self.get_plugin_helpfile_path(file);
-        if plugin_help_path.exists() {
+        if let Some(content) = self.registered.get(file) {
+            self.parse_markdown(content)
+        } else if plugin_help_path.exists() {
             if let Some(path) = plugin_help_path.to_str() {
                 let content = self.read_from_file(path);
                 self.parse_markdown(&content)
@@ -119,13 +158,16 @@ impl HelpHandler {
             } else {
                 "$USER_CONFIG_DIR"
             };
+            let cache_path = crate::CACHE_DIR.to_path_buf();
+            let cache_dir = cache_path.to_str().unwrap_or("$USER_CACHE_DIR");
 
             let file_content = self
                 .file_content(file)
                 .replace("$VERSION", VERSION)
                 .replace("$LOGDIR", logdir)
                 .replace("$DATADIR", datadir)
-                .replace("$CONFIGDIR", config_dir);
+                .replace("$CONFIGDIR", config_dir)
+                .replace("$CACHEDIR", cache_dir);
 
             self.parse_markdown(&file_content)
         } else {
@@ -133,18 +175,38 @@ impl HelpHandler {
         }
     }
 
-    pub fn search_helpfiles(&self, pattern: &str) -> Option<Line> {
-        let mut matches = vec![];
-        for key in self.files.keys() {
-            let content = self.file_content(key);
-            if content.contains(pattern) {
-                matches.push(key);
-            }
-        }
+    /// Finds the topics mentioning `pattern`, ignoring case, along with the first line that
+    /// does.
+    fn search(&self, pattern: &str) -> Vec<(&str, String)> {
+        let pattern = pattern.to_lowercase();
+        let mut topics: Vec<&str> = self
+            .files
+            .keys()
+            .copied()
+            .chain(self.registered.keys().map(String::as_str))
+            .collect();
+        topics.sort_unstable();
+        topics
+            .into_iter()
+            .filter_map(|topic| {
+                let content = match self.registered.get(topic) {
+                    Some(content) => Cow::from(content.as_str()),
+                    None => self.file_content(topic),
+                };
+                content
+                    .lines()
+                    .find(|line| line.to_lowercase().contains(&pattern))
+                    .map(|line| (topic, line.trim().chars().take(SNIPPET_LENGTH).collect()))
+            })
+            .collect()
+    }
+
+    fn search_helpfiles(&self, pattern: &str, heading: &str) -> Option<Line> {
+        let matches = self.search(pattern);
         if !matches.is_empty() {
-            let mut output = "No such help file exists.\nThe following help files contain a match for your search:".to_string();
-            for key in matches {
-                write!(output, "\n- {key}").unwrap();
+            let mut output = format!("{heading}:");
+            for (topic, snippet) in matches {
+                write!(output, "\n- {topic}: {snippet}").unwrap();
             }
             Some(Line::from(output))
         } else {
@@ -220,6 +282,8 @@ fn load_files() -> HashMap<&'static str, &'static str> {
         #[cfg(not(feature = "tts"))]
         "tts" => "no_tts.md",
         "status_area" => "status_area.md",
+        "layout" => "layout.md",
+        "widget" => "widget.md",
         "alias" => "aliases.md",
         "script" => "script.md",
         "spellcheck" => "spellcheck.md",
@@ -228,6 +292,7 @@ fn load_files() -> HashMap<&'static str, &'static str> {
         "gmcp" => "gmcp.md",
         "msdp" => "msdp.md",
         "mssp" => "mssp.md",
+        "protocol" => "protocol.md",
         "regex" => "regex.md",
         "line" => "line.md",
         "mud" => "mud.md",
@@ -238,12 +303,27 @@ fn load_files() -> HashMap<&'static str, &'static str> {
         "scripting" => "scripting.md",
         "settings" => "settings.md",
         "storage" => "storage.md",
+        "snapshot" => "snapshot.md",
+        "backup" => "backup.md",
+        "relay" => "relay.md",
         "colors" => "colors.md",
         "tasks" => "tasks.md",
         "socket" => "socket.md",
+        "dns" => "dns.md",
+        "palette" => "palette.md",
         "plugin" => "plugin.md",
         "plugin_developer" => "plugin_developer.md",
         "servers" => "servers.md",
+        "login" => "login.md",
+        "vars" => "vars.md",
+        "target" => "target.md",
+        "report" => "report.md",
+        "numpad" => "numpad.md",
+        "paste" => "paste.md",
+        "fmt" => "fmt.md",
+        "ui" => "ui.md",
+        "screen" => "screen.md",
+        "debugger" => "debugger.md",
         "search" => "search.md",
         "scrolling" => "scrolling.md",
         "ttype" => "ttype.md",
@@ -251,6 +331,21 @@ fn load_files() -> HashMap<&'static str, &'static str> {
         "prompt" => "prompt.md",
         "prompt_mask" => "prompt_mask.md",
         "history" => "history.md",
+        "inbox" => "inbox.md",
+        "activity" => "activity.md",
+        "finder" => "finder.md",
+        "fuzzy" => "fuzzy.md",
+        "help_topics" => "help_topics.md",
+        "reader" => "reader.md",
+        "origin" => "origin.md",
+        "sounds" => "sounds.md",
+        "metrics" => "metrics.md",
+        "memory" => "memory.md",
+        "sent" => "sent.md",
+        "guard" => "guard.md",
+        "web" => "web.md",
+        "control" => "control.md",
+        "encryption" => "encryption.md",
         "script_example" => "scripte_example.md"
     }
 }
@@ -281,6 +376,27 @@ mod help_test {
         assert_eq!(handler.parse_helpfile("nothing"), None);
     }
 
+    #[test]
+    fn registered_topics() {
+        let mut handler = handler();
+        handler.register(
+            "mapper".to_string(),
+            "# Mapper\nPlots xyzzy rooms".to_string(),
+        );
+        handler.register("help".to_string(), "# Replaced".to_string());
+        assert!(handler.parse_helpfile("mapper").is_some());
+        assert!(!handler.registered.contains_key("help"));
+        assert_eq!(
+            handler.search("XYZZY"),
+            vec![("mapper", "Plots xyzzy rooms".to_string())]
+        );
+        assert!(handler
+            .search("scrollback")
+            .iter()
+            .any(|(topic, _)| *topic == "screen"));
+        assert!(HelpHandler::builtin_topics().contains(&"help"));
+    }
+
     #[test]
     fn confirm_help_render() {
         let (writer, reader): (Sender<Event>, Receiver<Event>) = channel();
@@ -1,9 +1,136 @@
-use crate::model::Regex;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
+use anyhow::Result;
+use log::error;
+
+use crate::io::crypt;
+use crate::model::{Regex, ScrollbackSettings};
+
+fn spill_dir() -> PathBuf {
+    crate::DATA_DIR.join("scrollback")
+}
+
+/// Checks if a process is running, or was at least not ours to check.
+fn is_running(pid: libc::pid_t) -> bool {
+    let found = unsafe { libc::kill(pid, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Removes the spill files left behind by Blightmud processes that are no longer running, eg.
+/// after a crash. Files are named `<pid>-<id>.txt` after the process that wrote them.
+fn remove_stale_spills(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let pid = name
+            .to_str()
+            .and_then(|name| name.split_once('-'))
+            .and_then(|(pid, _)| pid.parse::<libc::pid_t>().ok());
+        if pid.is_some_and(|pid| !is_running(pid)) {
+            fs::remove_file(entry.path()).ok();
+        }
+    }
+}
+
+/// Cleans up the scrollback spills of earlier sessions.
+pub fn remove_stale_spill_files() {
+    remove_stale_spills(&spill_dir());
+}
+
+/// Lines drained from a history, appended to a file in chunks that are read back when
+/// scrolling up past the lines in memory. Each chunk is encrypted on its own when encryption is
+/// on. The file is removed when the spill is dropped.
+struct Spill {
+    path: PathBuf,
+    file: File,
+    /// The offset and number of lines of each chunk, oldest first.
+    chunks: Vec<(u64, usize)>,
+    end: u64,
+}
+
+impl Spill {
+    fn create() -> io::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let dir = spill_dir();
+        fs::create_dir_all(&dir)?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("{}-{id}.txt", std::process::id()));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            chunks: vec![],
+            end: 0,
+        })
+    }
+
+    fn push(&mut self, lines: &[Arc<str>]) -> Result<()> {
+        let mut data = lines.join("\n");
+        data.push('\n');
+        let data = crypt::encrypt(data.into_bytes())?;
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&data)?;
+        self.chunks.push((self.end, lines.len()));
+        self.end += data.len() as u64;
+        Ok(())
+    }
+
+    fn read(&mut self, chunk: usize) -> Result<Vec<Arc<str>>> {
+        let (start, _) = self.chunks[chunk];
+        let end = self.chunks.get(chunk + 1).map_or(self.end, |next| next.0);
+        let mut data = vec![0; (end - start) as usize];
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut data)?;
+        let data = crypt::decrypt(data)?;
+        let data = String::from_utf8_lossy(&data);
+        Ok(data
+            .strip_suffix('\n')
+            .unwrap_or(&data)
+            .split('\n')
+            .map(Arc::from)
+            .collect())
+    }
+
+    fn lines(&self) -> usize {
+        self.chunks.iter().map(|(_, lines)| lines).sum()
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// The lines of output printed to the screen. Lines are shared with the output they were printed
+/// from where they fit on a single row.
 pub struct History {
-    pub inner: Vec<String>,
+    pub inner: Vec<Arc<str>>,
     pub capacity: usize,
     pub drain_length: usize,
+    /// The bytes of text in `inner`, roughly, as lines can be replaced in place.
+    bytes: usize,
+    max_bytes: usize,
+    /// Where drained lines are kept, if anywhere.
+    spill: Option<Spill>,
+    /// Lines and chunks at the front of `inner` read back from the spill, which still has them.
+    restored_lines: usize,
+    restored_chunks: usize,
 }
 
 impl History {
@@ -14,29 +141,147 @@ impl History {
             inner: Vec::with_capacity(capacity),
             capacity,
             drain_length,
+            bytes: 0,
+            max_bytes: usize::MAX,
+            spill: None,
+            restored_lines: 0,
+            restored_chunks: 0,
         }
     }
 
-    pub fn drain(&mut self) {
-        if self.inner.len() >= self.capacity {
-            self.inner.drain(0..self.drain_length);
+    /// Limits the lines and text kept in memory, and keeps the lines drained past the limits on
+    /// disk if the settings ask for it.
+    pub fn configure(&mut self, settings: &ScrollbackSettings) {
+        self.capacity = settings.lines.max(2 * self.drain_length);
+        self.max_bytes = settings.max_bytes();
+        if !settings.spill {
+            self.spill = None;
+            self.restored_lines = 0;
+            self.restored_chunks = 0;
+        } else if self.spill.is_none() {
+            match Spill::create() {
+                Ok(spill) => self.spill = Some(spill),
+                Err(err) => error!("Failed to create scrollback spill file: {err}"),
+            }
         }
+        self.drain();
     }
 
-    pub fn append(&mut self, line: &str) {
-        if !line.trim().is_empty() {
-            for line in line.lines() {
-                self.inner.push(String::from(line));
+    /// Drains the oldest lines while the history is over its limits, into the spill if there is
+    /// one. Nothing is drained while lines read back from the spill are in memory.
+    pub fn drain(&mut self) {
+        if self.restored_lines > 0 {
+            return;
+        }
+        while self.inner.len() > self.drain_length
+            && (self.inner.len() >= self.capacity || self.bytes > self.max_bytes)
+        {
+            let drained: Vec<Arc<str>> = self.inner.drain(0..self.drain_length).collect();
+            let bytes: usize = drained.iter().map(|line| line.len()).sum();
+            self.bytes = self.bytes.saturating_sub(bytes);
+            if let Some(spill) = &mut self.spill {
+                if let Err(err) = spill.push(&drained) {
+                    error!("Failed to spill scrollback to disk: {err}");
+                    self.spill = None;
+                }
             }
+        }
+    }
+
+    /// Appends text, split into lines. A single line is kept as is, which shares it when it's
+    /// an `Arc<str>`.
+    pub fn append(&mut self, line: impl AsRef<str> + Into<Arc<str>>) {
+        let text = line.as_ref();
+        if text.trim().is_empty() {
+            self.inner.push(Arc::from(""));
+        } else if !text.contains('\n') {
+            self.bytes += text.len();
+            self.inner.push(line.into());
         } else {
-            self.inner.push("".to_string());
+            for line in text.lines() {
+                self.bytes += line.len();
+                self.inner.push(Arc::from(line));
+            }
         }
         self.drain();
     }
 
-    pub fn remove_last_if_prefix(&mut self, line: &str) -> Option<String> {
+    /// Reads the newest chunk of the spill that isn't in memory back to the front of the
+    /// history. Returns the number of lines read, which the indices of all lines moved by.
+    pub fn restore(&mut self) -> usize {
+        let Some(spill) = &mut self.spill else {
+            return 0;
+        };
+        let Some(chunk) = spill.chunks.len().checked_sub(self.restored_chunks + 1) else {
+            return 0;
+        };
+        match spill.read(chunk) {
+            Ok(lines) => {
+                let count = lines.len();
+                self.bytes += lines.iter().map(|line| line.len()).sum::<usize>();
+                self.inner.splice(0..0, lines);
+                self.restored_lines += count;
+                self.restored_chunks += 1;
+                count
+            }
+            Err(err) => {
+                error!("Failed to read scrollback from disk: {err}");
+                0
+            }
+        }
+    }
+
+    /// Drops the lines read back from the spill once they're no longer shown, and drains what
+    /// came in meanwhile.
+    pub fn release(&mut self) {
+        if self.restored_lines > 0 {
+            let released: usize = self
+                .inner
+                .drain(0..self.restored_lines)
+                .map(|line| line.len())
+                .sum();
+            self.bytes = self.bytes.saturating_sub(released);
+            self.restored_lines = 0;
+            self.restored_chunks = 0;
+            self.drain();
+        }
+    }
+
+    /// The number of lines on disk that aren't in memory.
+    pub fn spilled(&self) -> usize {
+        self.spill
+            .as_ref()
+            .map_or(0, |spill| spill.lines() - self.restored_lines)
+    }
+
+    /// Replaces the newest run of lines equal to `old` among the last `within` lines with `new`.
+    /// Returns false, changing nothing, if there is no such run.
+    pub fn replace_recent(&mut self, old: &[Arc<str>], new: Vec<Arc<str>>, within: usize) -> bool {
+        if old.is_empty() || old.len() > self.inner.len() {
+            return false;
+        }
+        let first = self
+            .inner
+            .len()
+            .saturating_sub(within)
+            .max(self.restored_lines);
+        let Some(start) = (first..=self.inner.len() - old.len())
+            .rev()
+            .find(|start| self.inner[*start..*start + old.len()] == *old)
+        else {
+            return false;
+        };
+        let removed: usize = old.iter().map(|line| line.len()).sum();
+        self.bytes = self.bytes.saturating_sub(removed);
+        self.bytes += new.iter().map(|line| line.len()).sum::<usize>();
+        self.inner.splice(start..start + old.len(), new);
+        true
+    }
+
+    pub fn remove_last_if_prefix(&mut self, line: &str) -> Option<Arc<str>> {
         if let Some(prefix) = self.inner.last() {
-            if line.starts_with(prefix) {
+            if line.starts_with(&**prefix) {
+                self.bytes = self.bytes.saturating_sub(prefix.len());
                 self.inner.pop()
             } else {
                 None
@@ -50,6 +295,17 @@ impl History {
         self.inner.len()
     }
 
+    /// The bytes of text in memory.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Removes the oldest `count` lines, without keeping them in the spill.
+    pub fn remove_oldest(&mut self, count: usize) {
+        let removed: usize = self.inner.drain(0..count).map(|line| line.len()).sum();
+        self.bytes = self.bytes.saturating_sub(removed);
+    }
+
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
@@ -68,6 +324,15 @@ impl History {
             .position(|l| pattern.is_match(l))
             .map(|index| pos - index - 1)
     }
+
+    pub fn find_all(&self, pattern: &Regex) -> Vec<usize> {
+        self.inner
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| pattern.is_match(l))
+            .map(|(index, _)| index)
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +348,18 @@ mod test {
         assert_eq!(history.len(), 1);
     }
 
+    #[test]
+    fn test_append_shared() {
+        let mut history = History::new();
+        let line: Arc<str> = Arc::from("a shared line");
+        history.append(line.clone());
+        assert!(Arc::ptr_eq(&history.inner[0], &line));
+        history.append(Arc::<str>::from("two\r\nlines"));
+        assert_eq!(history.len(), 3);
+        assert_eq!(&*history.inner[2], "lines");
+        assert_eq!(history.bytes, 21);
+    }
+
     #[test]
     fn test_remove_last() {
         let mut history = History::new();
@@ -97,6 +374,26 @@ mod test {
         assert_eq!(history.len(), 2);
     }
 
+    #[test]
+    fn test_replace_recent() {
+        let lines = |lines: &[&str]| -> Vec<Arc<str>> {
+            lines.iter().map(|line| Arc::from(*line)).collect()
+        };
+        let mut history = History::new();
+        history.append("HP: 100");
+        history.append("You see a door.");
+        history.append("HP: 90");
+        assert!(history.replace_recent(&lines(&["HP: 90"]), lines(&["HP: 80", "MP: 5"]), 10));
+        assert_eq!(
+            history.inner,
+            lines(&["HP: 100", "You see a door.", "HP: 80", "MP: 5"])
+        );
+        assert_eq!(history.bytes, 33);
+        assert!(!history.replace_recent(&lines(&["HP: 100"]), lines(&["HP: 70"]), 2));
+        assert!(!history.replace_recent(&lines(&["HP: 90"]), lines(&["HP: 70"]), 10));
+        assert_eq!(history.len(), 4);
+    }
+
     #[test]
     fn confirm_drain() {
         let mut history = History::new();
@@ -110,6 +407,70 @@ mod test {
         assert_eq!(history.len(), 31 * 1024);
     }
 
+    #[test]
+    fn test_spill() {
+        let mut history = History::new();
+        history.configure(&ScrollbackSettings {
+            lines: 2048,
+            ..Default::default()
+        });
+        for i in 0..4096 {
+            history.append(format!("line {i}"));
+        }
+        assert_eq!(history.len(), 1024);
+        assert_eq!(history.spilled(), 3072);
+        assert_eq!(&*history.inner[0], "line 3072");
+
+        assert_eq!(history.restore(), 1024);
+        assert_eq!(&*history.inner[0], "line 2048");
+        assert_eq!(history.spilled(), 2048);
+        history.append("line 4096");
+        assert_eq!(history.len(), 2049);
+        assert_eq!(history.restore(), 1024);
+        assert_eq!(history.restore(), 1024);
+        assert_eq!(&*history.inner[0], "line 0");
+        assert_eq!(history.restore(), 0);
+
+        history.release();
+        assert_eq!(history.len(), 1025);
+        assert_eq!(&*history.inner[0], "line 3072");
+        assert_eq!(history.spilled(), 3072);
+    }
+
+    #[test]
+    fn test_remove_stale_spills() {
+        let dir = std::env::temp_dir().join(format!("blightmud_spills_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let own = dir.join(format!("{}-0.txt", std::process::id()));
+        let stale = dir.join(format!("{}-0.txt", libc::pid_t::MAX));
+        let other = dir.join("notes.txt");
+        for path in [&own, &stale, &other] {
+            fs::write(path, "line").unwrap();
+        }
+        remove_stale_spills(&dir);
+        assert!(own.exists());
+        assert!(!stale.exists());
+        assert!(other.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut history = History::new();
+        history.configure(&ScrollbackSettings {
+            memory: 1,
+            spill: false,
+            ..Default::default()
+        });
+        let line = "x".repeat(1024);
+        for _ in 0..2048 {
+            history.append(line.as_str());
+        }
+        assert_eq!(history.len(), 1024);
+        assert_eq!(history.spilled(), 0);
+        assert_eq!(history.restore(), 0);
+    }
+
     #[test]
     fn test_find() {
         let mut history = History::new();
@@ -144,4 +505,16 @@ mod test {
             index += 1;
         }
     }
+
+    #[test]
+    fn test_find_all() {
+        let mut history = History::new();
+        for line in ["a kobold attacks", "you dodge", "a kobold flees", ""] {
+            history.append(line);
+        }
+        let pattern = Regex::new("kobold", None).unwrap();
+        assert_eq!(history.find_all(&pattern), vec![0, 2]);
+        let pattern = Regex::new("dragon", None).unwrap();
+        assert!(history.find_all(&pattern).is_empty());
+    }
 }
@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use termion::event::Key;
+
+/// Keypad keys are mapped onto chars in the unicode private use area so they can pass through
+/// termion's input parser.
+const KEYPAD_BASE: u32 = 0xE000;
+
+/// Application keypad mode sequences (`ESC O <byte>`), the key they fall back to when nothing is
+/// bound to them and their binding name.
+const KEYPAD: [(u8, char, &str); 17] = [
+    (b'p', '0', "kp0"),
+    (b'q', '1', "kp1"),
+    (b'r', '2', "kp2"),
+    (b's', '3', "kp3"),
+    (b't', '4', "kp4"),
+    (b'u', '5', "kp5"),
+    (b'v', '6', "kp6"),
+    (b'w', '7', "kp7"),
+    (b'x', '8', "kp8"),
+    (b'y', '9', "kp9"),
+    (b'n', '.', "kp."),
+    (b'k', '+', "kp+"),
+    (b'm', '-', "kp-"),
+    (b'j', '*', "kp*"),
+    (b'o', '/', "kp/"),
+    (b'X', '=', "kp="),
+    (b'M', '\n', "kp_enter"),
+];
+
+/// Bracketed paste markers, mapped past the keypad chars so they reach the input thread as keys.
+pub const PASTE_START: char = '\u{E100}';
+pub const PASTE_END: char = '\u{E101}';
+const PASTE_MARKERS: [(&[u8], char); 2] = [(b"\x1b[200~", PASTE_START), (b"\x1b[201~", PASTE_END)];
+
+fn keypad_index(c: char) -> Option<usize> {
+    let index = (c as u32).checked_sub(KEYPAD_BASE)? as usize;
+    if index < KEYPAD.len() {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+/// Returns the binding name of a translated keypad key.
+pub fn key_name(key: Key) -> Option<&'static str> {
+    match key {
+        Key::Char(c) => keypad_index(c).map(|i| KEYPAD[i].2),
+        _ => None,
+    }
+}
+
+/// Turns a translated keypad key into the regular key it represents.
+pub fn fallback(key: Key) -> Key {
+    match key {
+        Key::Char(c) => keypad_index(c).map_or(key, |i| Key::Char(KEYPAD[i].1)),
+        _ => key,
+    }
+}
+
+/// Rewrites application keypad sequences and paste markers in `input` to private use chars.
+/// Keypad sequences split over multiple reads are passed through untouched.
+fn translate(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut buf = [0; 4];
+    let mut i = 0;
+    'input: while i < input.len() {
+        for (marker, c) in PASTE_MARKERS {
+            if input[i..].starts_with(marker) {
+                output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                i += marker.len();
+                continue 'input;
+            }
+        }
+        if input[i] == b'\x1b' && input.get(i + 1) == Some(&b'O') {
+            if let Some(index) = input
+                .get(i + 2)
+                .and_then(|b| KEYPAD.iter().position(|(seq, _, _)| seq == b))
+            {
+                let c = char::from_u32(KEYPAD_BASE + index as u32).unwrap();
+                output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                i += 3;
+                continue;
+            }
+        }
+        output.push(input[i]);
+        i += 1;
+    }
+    output
+}
+
+/// Length of the end of `input` that could be a paste marker cut off by the read. Pastes arrive
+/// in many reads, unlike key presses, so their end marker is easily split.
+fn partial_marker(input: &[u8]) -> usize {
+    (2..PASTE_MARKERS[0].0.len())
+        .rev()
+        .find(|&len| {
+            input.len() >= len
+                && PASTE_MARKERS
+                    .iter()
+                    .any(|(marker, _)| marker.starts_with(&input[input.len() - len..]))
+        })
+        .unwrap_or(0)
+}
+
+/// Wraps the terminal input, translating application keypad sequences and paste markers.
+pub struct KeypadReader<R: Read> {
+    inner: R,
+    pending: VecDeque<u8>,
+    partial: Vec<u8>,
+}
+
+impl<R: Read> KeypadReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: VecDeque::new(),
+            partial: vec![],
+        }
+    }
+}
+
+impl<R: Read> Read for KeypadReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            let mut read = [0; 64];
+            let n = self.inner.read(&mut read)?;
+            let mut input = std::mem::take(&mut self.partial);
+            input.extend_from_slice(&read[..n]);
+            if n > 0 {
+                let keep = partial_marker(&input);
+                self.partial = input.split_off(input.len() - keep);
+            }
+            self.pending.extend(translate(&input));
+            if n == 0 {
+                break;
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        for (i, b) in self.pending.drain(..n).enumerate() {
+            buf[i] = b;
+        }
+        Ok(n)
+    }
+}
+
+/// Puts the terminal in application keypad and bracketed paste mode until dropped.
+pub struct KeypadTerminal<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> From<W> for KeypadTerminal<W> {
+    fn from(mut inner: W) -> Self {
+        let _ = inner.write_all(b"\x1b=\x1b[?2004h");
+        Self { inner }
+    }
+}
+
+impl<W: Write> Drop for KeypadTerminal<W> {
+    fn drop(&mut self) {
+        let _ = self.inner.write_all(b"\x1b[?2004l\x1b>");
+        let _ = self.inner.flush();
+    }
+}
+
+impl<W: Write> Write for KeypadTerminal<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test_keypad {
+    use super::*;
+    use termion::input::TermRead;
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        KeypadReader::new(input)
+            .keys()
+            .map(|k| k.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_translate() {
+        let keys = keys(b"a\x1bOx\x1bOM\x1bOP\x1b[A");
+        assert_eq!(keys.len(), 5);
+        assert_eq!(keys[0], Key::Char('a'));
+        assert_eq!(key_name(keys[1]), Some("kp8"));
+        assert_eq!(key_name(keys[2]), Some("kp_enter"));
+        assert_eq!(keys[3], Key::F(1));
+        assert_eq!(keys[4], Key::Up);
+    }
+
+    #[test]
+    fn test_fallback() {
+        let keys = keys(b"\x1bOp\x1bOn\x1bOM");
+        assert_eq!(
+            keys.into_iter().map(fallback).collect::<Vec<Key>>(),
+            vec![Key::Char('0'), Key::Char('.'), Key::Char('\n')]
+        );
+        assert_eq!(fallback(Key::Char('x')), Key::Char('x'));
+        assert_eq!(key_name(Key::Char('8')), None);
+    }
+
+    #[test]
+    fn test_paste_markers() {
+        let keys = keys(b"\x1b[200~a\rb\x1b[201~\x1b[2~");
+        assert_eq!(
+            keys,
+            vec![
+                Key::Char(PASTE_START),
+                Key::Char('a'),
+                Key::Char('\n'),
+                Key::Char('b'),
+                Key::Char(PASTE_END),
+                Key::Insert,
+            ]
+        );
+    }
+
+    /// Hands out its input a few bytes at a time, like a terminal receiving a long paste.
+    struct ChunkedReader<'a>(&'a [u8], usize);
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.1.min(self.0.len()).min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_split_paste_markers() {
+        let keys: Vec<Key> = KeypadReader::new(ChunkedReader(b"\x1b[200~ab\x1b[201~c", 4))
+            .keys()
+            .map(|k| k.unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                Key::Char(PASTE_START),
+                Key::Char('a'),
+                Key::Char('b'),
+                Key::Char(PASTE_END),
+                Key::Char('c'),
+            ]
+        );
+        assert_eq!(partial_marker(b"abc\x1b[20"), 4);
+        assert_eq!(partial_marker(b"abc\x1b"), 0);
+        assert_eq!(partial_marker(b"\x1b[A"), 0);
+    }
+
+    #[test]
+    fn test_terminal_mode() {
+        let mut out = vec![];
+        {
+            let mut term = KeypadTerminal::from(&mut out);
+            term.write_all(b"hi").unwrap();
+        }
+        assert_eq!(out, b"\x1b=\x1b[?2004hhi\x1b[?2004l\x1b>");
+    }
+}
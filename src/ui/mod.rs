@@ -4,23 +4,35 @@ pub use self::{
     command::CommandBuffer,
     headless_screen::HeadlessScreen,
     help_handler::HelpHandler,
+    history::remove_stale_spill_files,
     reader_screen::ReaderScreen,
     split_screen::SplitScreen,
     ui_wrapper::UiWrapper,
     user_interface::{wrap_line, UserInterface},
 };
 
+#[cfg(feature = "tui")]
+pub use self::tui_screen::TuiScreen;
+
+pub(crate) use self::printable_chars::PrintableCharsIterator;
+
 #[cfg(test)]
 pub use self::user_interface::MockUserInterface;
 
 mod ansi;
 mod command;
+mod filter;
 mod headless_screen;
 mod help_handler;
 mod history;
+mod keypad;
+mod pager;
 mod printable_chars;
+mod reader_profile;
 mod reader_screen;
 mod scroll_data;
 mod split_screen;
+#[cfg(feature = "tui")]
+mod tui_screen;
 mod ui_wrapper;
 mod user_interface;
@@ -0,0 +1,135 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Local};
+
+use crate::model::Line;
+
+/// Output arriving this long after the previous line starts a new burst.
+const BURST_GAP_MS: i64 = 2000;
+
+/// Stops bursts of output longer than a page until the user asks for more, like `more`.
+#[derive(Default)]
+pub struct Pager {
+    enabled: bool,
+    /// Set while lines are held, shared with the input thread which turns space into showing
+    /// the next page.
+    waiting: Arc<AtomicBool>,
+    /// Lines shown since the burst started or the user last paged on.
+    shown: usize,
+    last_output: Option<DateTime<Local>>,
+    held: VecDeque<Line>,
+}
+
+impl Pager {
+    pub fn new(waiting: Arc<AtomicBool>) -> Self {
+        Self {
+            waiting,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The number of lines waiting to be shown.
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Holds `line` if the page is full, returning whether it was held.
+    pub fn hold(&mut self, line: &Line, page_size: usize) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let timestamp = line.timestamp();
+        if self.held.is_empty()
+            && self
+                .last_output
+                .is_some_and(|last| (timestamp - last).num_milliseconds() >= BURST_GAP_MS)
+        {
+            self.shown = 0;
+        }
+        self.last_output = Some(timestamp);
+        if !self.held.is_empty() || self.shown >= page_size {
+            self.held.push_back(line.clone());
+            self.waiting.store(true, Ordering::Relaxed);
+            true
+        } else {
+            self.shown += 1;
+            false
+        }
+    }
+
+    /// Takes the lines of the next page off the held lines.
+    pub fn next_page(&mut self, page_size: usize) -> Vec<Line> {
+        let count = page_size.min(self.held.len());
+        self.shown = count;
+        let page = self.held.drain(..count).collect();
+        if self.held.is_empty() {
+            self.waiting.store(false, Ordering::Relaxed);
+        }
+        page
+    }
+
+    /// Takes all held lines and starts counting the page over.
+    pub fn release(&mut self) -> Vec<Line> {
+        self.shown = 0;
+        self.waiting.store(false, Ordering::Relaxed);
+        self.held.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod test_pager {
+    use super::*;
+
+    #[test]
+    fn test_paging() {
+        let waiting = Arc::new(AtomicBool::new(false));
+        let mut pager = Pager::new(waiting.clone());
+        let line = Line::from("A rat is here.");
+        assert!(!pager.hold(&line, 2));
+
+        pager.set_enabled(true);
+        assert!(!pager.hold(&line, 2));
+        assert!(!pager.hold(&line, 2));
+        assert!(pager.hold(&line, 2));
+        assert!(pager.hold(&line, 2));
+        assert!(pager.hold(&line, 2));
+        assert!(waiting.load(Ordering::Relaxed));
+        assert_eq!(pager.held(), 3);
+
+        assert_eq!(pager.next_page(2).len(), 2);
+        assert!(waiting.load(Ordering::Relaxed));
+        assert!(pager.hold(&line, 2));
+        assert_eq!(pager.next_page(2).len(), 2);
+        assert!(!waiting.load(Ordering::Relaxed));
+        assert!(pager.hold(&line, 2));
+
+        assert_eq!(pager.release().len(), 1);
+        assert!(!waiting.load(Ordering::Relaxed));
+        assert!(!pager.hold(&line, 2));
+    }
+
+    #[test]
+    fn test_new_burst() {
+        let mut pager = Pager::new(Arc::new(AtomicBool::new(false)));
+        pager.set_enabled(true);
+        let line = Line::from("A rat is here.");
+        assert!(!pager.hold(&line, 1));
+        pager.last_output = Some(line.timestamp() - chrono::Duration::milliseconds(BURST_GAP_MS));
+        assert!(!pager.hold(&line, 1));
+        assert!(pager.hold(&line, 1));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::model::{
+    Line, ReaderFilters, DECORATION_FILTER, PROGRESS_FILTER, REPEAT_FILTER, SPEECH_FILTER,
+};
+
+/// Lines tagged with this, eg. by a trigger with the `decoration` option, are always treated as
+/// decoration.
+pub const DECORATION_TAG: &str = "decoration";
+
+/// Characters a progress bar is filled with, between its brackets.
+const BAR_FILL: &str = "#=*-.>~ █▓▒░■□▮▯▰▱";
+/// Characters that make up a progress bar on their own.
+const BAR_BLOCKS: &str = "█▓▒░■□▮▯▰▱";
+const MIN_BAR_LENGTH: usize = 5;
+/// Progress bars only come with a short label.
+const MAX_BAR_LABEL: usize = 20;
+
+/// The parts of `text` enclosed by `open` and `close`.
+fn enclosed(text: &str, open: char, close: char) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        let after = &rest[start + open.len_utf8()..];
+        let Some(end) = after.find(close) else {
+            break;
+        };
+        segments.push(&after[..end]);
+        rest = &after[end + close.len_utf8()..];
+    }
+    segments
+}
+
+fn is_bar_segment(segment: &str) -> bool {
+    segment.chars().count() >= MIN_BAR_LENGTH
+        && segment.chars().all(|c| BAR_FILL.contains(c))
+        && segment.chars().any(|c| c != ' ')
+}
+
+/// Checks if a line looks like a progress bar, eg. "Casting [#####.....] 50%".
+pub fn is_progress_bar(text: &str) -> bool {
+    if text.chars().filter(|c| c.is_alphabetic()).count() > MAX_BAR_LABEL {
+        return false;
+    }
+    let mut block_run = 0;
+    for c in text.chars() {
+        block_run = if BAR_BLOCKS.contains(c) {
+            block_run + 1
+        } else {
+            0
+        };
+        if block_run >= MIN_BAR_LENGTH {
+            return true;
+        }
+    }
+    [('[', ']'), ('|', '|'), ('<', '>'), ('(', ')')]
+        .iter()
+        .any(|(open, close)| {
+            enclosed(text, *open, *close)
+                .into_iter()
+                .any(is_bar_segment)
+        })
+}
+
+/// Checks if a line is decoration without meaningful text, like a rule of dashes or a bit of
+/// ASCII art.
+pub fn is_decoration(text: &str) -> bool {
+    let text = text.trim();
+    let len = text.chars().count();
+    let alphanumeric = text.chars().filter(|c| c.is_alphanumeric()).count();
+    if alphanumeric == 0 {
+        return len >= 3;
+    }
+    let has_word = text
+        .split(|c: char| !c.is_alphabetic())
+        .any(|word| word.chars().count() >= 3);
+    len >= 8 && !has_word && alphanumeric * 5 < len
+}
+
+/// Drops runs of three or more of the same symbol, which screen readers read out one by one.
+pub fn speech_text(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spoken = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let run = chars[i..].iter().take_while(|&&next| next == c).count();
+        if c.is_alphanumeric() || c.is_whitespace() || run < 3 {
+            spoken.extend(&chars[i..i + run]);
+        }
+        i += run;
+    }
+    let spoken = spoken.split_whitespace().collect::<Vec<_>>().join(" ");
+    if spoken.chars().any(char::is_alphanumeric) {
+        Some(spoken)
+    } else {
+        None
+    }
+}
+
+/// Shapes output for screen readers while reader mode is enabled, using the enabled
+/// [ReaderFilters].
+pub struct ReaderProfile {
+    filters: ReaderFilters,
+    last: Option<String>,
+    repeats: usize,
+}
+
+impl ReaderProfile {
+    pub fn new(filters: ReaderFilters) -> Self {
+        Self {
+            filters,
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    /// Returns the lines to show in place of `line`, which may be none at all. A line ending a
+    /// run of repeated lines is preceded by the repeat count of the run.
+    pub fn filter(&mut self, line: &Line) -> Vec<Line> {
+        if line.print_line().is_none() {
+            return vec![line.clone()];
+        }
+        let text = line.clean_line();
+        if self.filters.enabled(PROGRESS_FILTER) && is_progress_bar(text) {
+            return vec![];
+        }
+        if self.filters.enabled(DECORATION_FILTER)
+            && (line.tag(DECORATION_TAG).is_some() || is_decoration(text))
+        {
+            return vec![];
+        }
+        let mut lines = vec![];
+        if self.filters.enabled(REPEAT_FILTER) {
+            if !text.trim().is_empty() && self.last.as_deref() == Some(text) {
+                self.repeats += 1;
+                return lines;
+            }
+            lines.extend(self.flush());
+            self.last = Some(text.to_string());
+        }
+        lines.push(line.clone());
+        lines
+    }
+
+    /// Ends the current run of repeated lines, returning its repeat count if the last line was
+    /// repeated.
+    pub fn flush(&mut self) -> Option<Line> {
+        self.last = None;
+        let repeats = std::mem::take(&mut self.repeats);
+        (repeats > 0).then(|| Line::from(format!("(x{})", repeats + 1)))
+    }
+
+    /// The text to speak for a line, `None` if there is nothing meaningful to say.
+    pub fn speech(&self, text: &str) -> Option<String> {
+        if self.filters.enabled(SPEECH_FILTER) {
+            speech_text(text)
+        } else {
+            Some(text.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_reader_profile {
+
+    use super::*;
+
+    fn shown(profile: &mut ReaderProfile, line: &str) -> Vec<String> {
+        profile
+            .filter(&Line::from(line))
+            .iter()
+            .map(|line| line.line().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert!(is_progress_bar("Casting [#####.....] 50%"));
+        assert!(is_progress_bar("|==========>     |"));
+        assert!(is_progress_bar("Download: █████░░░░░"));
+        assert!(!is_progress_bar("[OOC] Bob: hello there"));
+        assert!(!is_progress_bar("You say: [ok]"));
+        assert!(!is_progress_bar(
+            "[=====] This line has far too many words to be a progress bar"
+        ));
+    }
+
+    #[test]
+    fn test_decoration() {
+        assert!(is_decoration("=========="));
+        assert!(is_decoration("+--------+"));
+        assert!(is_decoration("  /\\_/\\   ( o.o )  > ^ < "));
+        assert!(!is_decoration(":)"));
+        assert!(!is_decoration("-- Bob --"));
+        assert!(!is_decoration("| HP 100 |"));
+        assert!(!is_decoration("You see a dog."));
+    }
+
+    #[test]
+    fn test_speech_text() {
+        assert_eq!(
+            speech_text("=== Who is online ==="),
+            Some("Who is online".to_string())
+        );
+        assert_eq!(
+            speech_text("Hello... world!"),
+            Some("Hello world!".to_string())
+        );
+        assert_eq!(speech_text("Hi!!"), Some("Hi!!".to_string()));
+        assert_eq!(speech_text("*~*~*~*"), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut profile = ReaderProfile::new(ReaderFilters::default());
+        assert!(shown(&mut profile, "Casting [#####.....] 50%").is_empty());
+        assert!(shown(&mut profile, "==========").is_empty());
+        let mut tagged = Line::from("Welcome to the realm");
+        tagged.set_tag(DECORATION_TAG, "");
+        assert!(profile.filter(&tagged).is_empty());
+
+        assert_eq!(
+            shown(&mut profile, "The rat bites you."),
+            ["The rat bites you."]
+        );
+        assert!(shown(&mut profile, "The rat bites you.").is_empty());
+        assert!(shown(&mut profile, "The rat bites you.").is_empty());
+        assert_eq!(
+            shown(&mut profile, "You kill the rat."),
+            ["(x3)", "You kill the rat."]
+        );
+        assert_eq!(profile.flush(), None);
+        assert_eq!(
+            shown(&mut profile, "You kill the rat."),
+            ["You kill the rat."]
+        );
+
+        let mut filters = ReaderFilters::default();
+        filters.set(REPEAT_FILTER, false).unwrap();
+        filters.set(PROGRESS_FILTER, false).unwrap();
+        let mut profile = ReaderProfile::new(filters);
+        assert_eq!(shown(&mut profile, "Casting [#####]"), ["Casting [#####]"]);
+        assert_eq!(shown(&mut profile, "Again"), ["Again"]);
+        assert_eq!(shown(&mut profile, "Again"), ["Again"]);
+    }
+}
@@ -1,4 +1,5 @@
 use std::io::Write;
+use std::sync::Arc;
 
 use anyhow::Result;
 use termion::{
@@ -7,7 +8,7 @@ use termion::{
 };
 
 use crate::{
-    model::{Line, Regex},
+    model::{Line, Regex, ScrollbackSettings},
     ui::{
         printable_chars::PrintableCharsIterator, DisableOriginMode, ResetScrollRegion, ScrollRegion,
     },
@@ -66,7 +67,7 @@ impl ReaderScreen {
     #[inline]
     fn print_line(&mut self, line: &Line) {
         if let Some(print_line) = &line.print_line() {
-            self.history.append(print_line);
+            self.history.append(line.shared_line());
             if !self.scroll_data.active {
                 writeln!(
                     self.screen,
@@ -261,6 +262,7 @@ impl UserInterface for ReaderScreen {
     }
 
     fn reset_scroll(&mut self) -> Result<()> {
+        self.history.release();
         self.scroll_data.reset(&self.history)?;
         let output_range = self.output_line;
         let output_start_index = self.history.inner.len() as i32 - output_range as i32;
@@ -344,6 +346,9 @@ impl UserInterface for ReaderScreen {
                 self.scroll_data.active = true;
                 self.scroll_data.pos = self.history.inner.len() - output_range;
             }
+            if self.scroll_data.pos < 5 {
+                self.scroll_data.pos += self.history.restore();
+            }
             self.scroll_data.pos -= self.scroll_data.pos.min(5);
             self.draw_scroll()?;
         }
@@ -381,6 +386,26 @@ impl UserInterface for ReaderScreen {
         Ok(())
     }
 
+    fn find_all(&self, pattern: &Regex) -> Vec<usize> {
+        self.history.find_all(pattern)
+    }
+
+    fn scrollback(&self) -> &[Arc<str>] {
+        &self.history.inner
+    }
+
+    fn scrollback_bytes(&self) -> usize {
+        self.history.bytes()
+    }
+
+    fn scrollback_spilled(&self) -> usize {
+        self.history.spilled()
+    }
+
+    fn set_scrollback_settings(&mut self, settings: &ScrollbackSettings) {
+        self.history.configure(settings);
+    }
+
     fn set_host(&mut self, _host: &str, _port: u16) -> Result<()> {
         Ok(())
     }
@@ -405,6 +430,18 @@ impl UserInterface for ReaderScreen {
         Ok(())
     }
 
+    fn set_region(&mut self, _region: crate::model::Region) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_region(&mut self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_region_lines(&mut self, _name: &str, _lines: Vec<String>) -> Result<()> {
+        Ok(())
+    }
+
     fn flush(&mut self) {
         self.screen.flush().unwrap();
     }
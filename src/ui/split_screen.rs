@@ -1,46 +1,84 @@
+use super::filter::Filter;
 use super::history::History;
 use super::scroll_data::ScrollData;
 use super::user_interface::TerminalSizeError;
 use super::wrap_line;
 use crate::io::SaveData;
-use crate::model::{Settings, HIDE_TOPBAR};
+use crate::model::{
+    Layout, Region, RegionPosition, ScrollbackSettings, Settings, HIDE_TOPBAR, THROTTLE_TAG,
+};
 use crate::{model::Line, model::Regex, ui::ansi::*, ui::printable_chars::PrintableCharsIterator};
 use anyhow::Result;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::sync::Arc;
 use termion::color::{self, Bg, Fg};
 use termion::cursor;
 
 use super::UserInterface;
 
 const SCROLL_LIVE_BUFFER_SIZE: u16 = 10;
+/// How far back throttled output is looked for to be replaced.
+const THROTTLE_SEARCH_LINES: usize = 200;
+const HELD_INDICATOR: &str = "held_output";
 const PROMPT_HEIGHT: u16 = 1;
 const STATUS_HEIGHT_MIN: u16 = 0;
 const STATUS_HEIGHT_MAX: u16 = 5;
 
-struct StatusArea {
+pub(super) struct StatusArea {
     start_line: u16,
     width: u16,
     status_lines: Vec<Option<String>>,
     scroll_marker: bool,
+    search_counter: Option<(usize, usize)>,
+    filter: Option<String>,
+    indicators: Vec<(String, String)>,
 }
 
 impl StatusArea {
-    fn new(height: u16, start_line: u16, width: u16) -> Self {
+    pub(super) fn new(height: u16, start_line: u16, width: u16) -> Self {
         let height = Self::clamp_height(height);
         Self {
             start_line,
             width,
             status_lines: vec![None; height],
             scroll_marker: false,
+            search_counter: None,
+            filter: None,
+            indicators: vec![],
         }
     }
 
-    fn set_scroll_marker(&mut self, value: bool) {
+    pub(super) fn set_scroll_marker(&mut self, value: bool) {
         self.scroll_marker = value;
     }
 
-    fn clamp_height(height: u16) -> usize {
+    /// Shows the position of the current search match out of the total matches on the first
+    /// status line.
+    pub(super) fn set_search_counter(&mut self, counter: Option<(usize, usize)>) {
+        self.search_counter = counter;
+    }
+
+    /// Shows the pattern of the active output filter on the first status line.
+    pub(super) fn set_filter(&mut self, pattern: Option<String>) {
+        self.filter = pattern;
+    }
+
+    /// Shows the progress of a long running operation on the first status line, or removes it on
+    /// `None`. Indicators are shown in the order they were added.
+    pub(super) fn set_indicator(&mut self, id: String, text: Option<String>) {
+        let pos = self.indicators.iter().position(|(key, _)| *key == id);
+        match (pos, text) {
+            (Some(pos), Some(text)) => self.indicators[pos].1 = text,
+            (None, Some(text)) => self.indicators.push((id, text)),
+            (Some(pos), None) => {
+                self.indicators.remove(pos);
+            }
+            (None, None) => {}
+        }
+    }
+
+    pub(super) fn clamp_height(height: u16) -> usize {
         height.clamp(STATUS_HEIGHT_MIN, STATUS_HEIGHT_MAX) as usize
     }
 
@@ -48,7 +86,7 @@ impl StatusArea {
         index.clamp(0, self.status_lines.len() - 1)
     }
 
-    fn set_height(&mut self, height: u16, start_line: u16) {
+    pub(super) fn set_height(&mut self, height: u16, start_line: u16) {
         self.clear();
         self.status_lines.resize(Self::clamp_height(height), None);
         self.update_pos(start_line);
@@ -62,7 +100,7 @@ impl StatusArea {
         self.width = width;
     }
 
-    fn set_status_line(&mut self, index: usize, line: String) {
+    pub(super) fn set_status_line(&mut self, index: usize, line: String) {
         let index = self.clamp_index(index);
         if !line.trim().is_empty() {
             self.status_lines[index] = Some(line);
@@ -75,15 +113,25 @@ impl StatusArea {
         self.status_lines = vec![None; self.status_lines.len()];
     }
 
-    fn redraw_line(&mut self, screen: &mut impl Write, line_no: usize) -> Result<()> {
-        let line_no = self.clamp_index(line_no);
-        let index = self.start_line as usize + line_no;
-
+    /// Returns the text shown on a status line, the markers and indicators on the first line
+    /// followed by the custom info set for the line.
+    pub(super) fn info(&self, line_no: usize) -> String {
         let mut info = if self.scroll_marker && line_no == 0 {
             "(more) ".to_string()
         } else {
             String::new()
         };
+        if let (Some(pattern), 0) = (&self.filter, line_no) {
+            info.push_str(&format!("(filter: {pattern}) "));
+        }
+        if let (Some((current, total)), 0) = (self.search_counter, line_no) {
+            info.push_str(&format!("(search {current}/{total}) "));
+        }
+        if line_no == 0 {
+            for (_, text) in &self.indicators {
+                info.push_str(&format!("({text}) "));
+            }
+        }
 
         if let Some(Some(custom_info)) = self.status_lines.get(line_no) {
             info = if info.is_empty() {
@@ -92,6 +140,13 @@ impl StatusArea {
                 format!("{info}━ {custom_info} ")
             };
         }
+        info
+    }
+
+    fn redraw_line(&mut self, screen: &mut impl Write, line_no: usize) -> Result<()> {
+        let line_no = self.clamp_index(line_no);
+        let index = self.start_line as usize + line_no;
+        let info = self.info(line_no);
 
         if line_no == 0 || line_no == self.status_lines.len() - 1 {
             self.draw_bar(index, screen, &info)?;
@@ -154,7 +209,7 @@ impl StatusArea {
         Ok(())
     }
 
-    fn height(&self) -> u16 {
+    pub(super) fn height(&self) -> u16 {
         self.status_lines.len() as u16
     }
 }
@@ -171,11 +226,29 @@ pub struct SplitScreen {
     status_area: StatusArea,
     cursor_prompt_pos: u16,
     history: History,
+    filter: Option<Filter>,
     scroll_data: ScrollData,
     connection: Option<String>,
     tags: HashSet<String>,
     prompt_input: String,
     prompt_input_pos: usize,
+    layout: Layout,
+    region_start_line: u16,
+    overlay: Option<Vec<String>>,
+    collapse_repeats: bool,
+    repeats: Repeats,
+    /// Output is held until the user resumes it.
+    paused: bool,
+    /// Lines received since the output was last brought up to date.
+    held: usize,
+    /// Scrolled back further than this the live output below the scroll view is held as well.
+    auto_pause: usize,
+    pending_echo: bool,
+    /// Rows of commands sent since the last prompt, as shown and as they're shown once the
+    /// prompt arrives.
+    pending: Vec<(String, String)>,
+    /// The rows last printed for each key of throttled output.
+    throttled: HashMap<String, Vec<Arc<str>>>,
 }
 
 impl UserInterface for SplitScreen {
@@ -189,10 +262,15 @@ impl UserInterface for SplitScreen {
         if width > 0 && height > 0 {
             self.width = width;
             self.height = height;
-            self.output_line = height - self.status_area.height() - 2;
-            self.mud_prompt_line = height - self.status_area.height() - 1;
+            self.region_start_line = if settings.get(HIDE_TOPBAR)? { 1 } else { 2 };
+            let last_line = height.saturating_sub(self.status_area.height() + 2);
+            let (top, bottom) = self
+                .layout
+                .fit((last_line + 1).saturating_sub(self.region_start_line));
+            self.output_start_line = self.region_start_line + top;
+            self.output_line = last_line.saturating_sub(bottom);
+            self.mud_prompt_line = last_line + 1;
             self.prompt_line = height;
-            self.output_start_line = if settings.get(HIDE_TOPBAR)? { 1 } else { 2 };
 
             write!(
                 self.screen,
@@ -204,6 +282,8 @@ impl UserInterface for SplitScreen {
             self.redraw_top_bar()?;
             self.reset_scroll()?;
             self.redraw_status_area()?;
+            self.redraw_regions()?;
+            self.draw_overlay()?;
             self.screen.flush()?;
             write!(
                 self.screen,
@@ -218,30 +298,40 @@ impl UserInterface for SplitScreen {
     }
 
     fn print_error(&mut self, output: &str) {
-        let line = &format!("{}[!!] {}{}", Fg(color::Red), output, Fg(color::Reset));
+        let line = format!("{}[!!] {}{}", Fg(color::Red), output, Fg(color::Reset));
         self.print_line(line);
     }
 
     fn print_info(&mut self, output: &str) {
-        let line = &format!("[**] {output}");
+        let line = format!("[**] {output}");
         self.print_line(line);
     }
 
     fn print_output(&mut self, line: &Line) {
         //debug!("UI: {:?}", line);
         if let Some(print_line) = line.print_line() {
-            if !line.is_utf8() || print_line.trim().is_empty() {
-                self.print_line(print_line);
-            } else {
-                let mut count = 0;
+            if let Some(key) = line.tag(THROTTLE_TAG) {
+                self.print_throttled(key, print_line, line.flags.replace_last);
+            } else if !line.is_utf8() || print_line.trim().is_empty() {
+                self.print_line(line.shared_line());
+            } else if !self.print_repeat(print_line) {
                 let cur_line = self.history.len();
-                for l in wrap_line(print_line, self.width as usize) {
-                    self.print_line(l);
-                    count += 1;
+                let rows = wrap_line(print_line, self.width as usize);
+                let count = rows.len();
+                if count == 1 && rows[0].len() == print_line.len() {
+                    // The history shares the text with the line when it fits on a row as is
+                    self.print_line(line.shared_line());
+                } else {
+                    for row in rows {
+                        self.print_line(row);
+                    }
                 }
-                if self.scroll_data.scroll_lock && count > self.height {
+                if self.scroll_data.scroll_lock && count > self.height as usize {
                     self.scroll_to(cur_line).ok();
                 }
+                if self.collapse_repeats && count == 1 {
+                    self.repeats.start(print_line);
+                }
             }
         }
     }
@@ -297,15 +387,34 @@ impl UserInterface for SplitScreen {
             self.reset_scroll().ok();
         }
         if let Some(line) = send.print_line() {
-            let line = &format!(
+            let sent = &format!(
                 "{}{}> {}{}",
                 termion::style::Reset,
                 Fg(color::LightYellow),
                 line,
                 Fg(color::Reset),
             );
-            for line in wrap_line(line, self.width as usize) {
-                self.print_line(line);
+            if self.pending_echo && self.filter.is_none() {
+                // The marker is as wide as the prompt sign so both wrap the same way.
+                let pending = &format!(
+                    "{}{}\u{2026} {}{}",
+                    termion::style::Reset,
+                    Fg(color::LightBlack),
+                    line,
+                    Fg(color::Reset),
+                );
+                let width = self.width as usize;
+                for (pending, sent) in wrap_line(pending, width)
+                    .into_iter()
+                    .zip(wrap_line(sent, width))
+                {
+                    self.print_line(pending);
+                    self.pending.push((pending.to_string(), sent.to_string()));
+                }
+            } else {
+                for line in wrap_line(sent, self.width as usize) {
+                    self.print_line(line);
+                }
             }
         }
     }
@@ -318,7 +427,11 @@ impl UserInterface for SplitScreen {
     fn reset_scroll(&mut self) -> Result<()> {
         let reset_split = self.scroll_data.split;
         let reset_scroll = self.scroll_data.active;
+        self.history.release();
         self.scroll_data.reset(&self.history)?;
+        if self.status_area.search_counter.take().is_some() {
+            self.status_area.redraw_line(&mut self.screen, 0)?;
+        }
         if reset_split {
             write!(self.screen, "{ResetScrollRegion}")?;
             write!(
@@ -358,7 +471,9 @@ impl UserInterface for SplitScreen {
                 )?;
             }
         }
-        Ok(())
+        self.held = 0;
+        self.draw_held_indicator()?;
+        self.draw_overlay()
     }
 
     fn scroll_down(&mut self) -> Result<()> {
@@ -413,6 +528,9 @@ impl UserInterface for SplitScreen {
                 self.init_scroll()?;
                 self.scroll_data.pos = self.history.inner.len() - output_range;
             }
+            if self.scroll_data.pos < 5 {
+                self.scroll_data.pos += self.history.restore();
+            }
             self.scroll_data.pos -= self.scroll_data.pos.min(5);
             self.draw_scroll()?;
         }
@@ -431,6 +549,7 @@ impl UserInterface for SplitScreen {
         if let Some(line) = self.history.find_backward(pattern, pos) {
             self.scroll_data.hilite = Some(pattern.clone());
             self.scroll_to(0.max(line))?;
+            self.update_search_counter(pattern, line)?;
         }
         Ok(())
     }
@@ -444,6 +563,45 @@ impl UserInterface for SplitScreen {
             {
                 self.scroll_data.hilite = Some(pattern.clone());
                 self.scroll_to(line.min(self.history.len() - 1))?;
+                self.update_search_counter(pattern, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_all(&self, pattern: &Regex) -> Vec<usize> {
+        self.history.find_all(pattern)
+    }
+
+    fn set_filter(&mut self, pattern: Option<Regex>) -> Result<()> {
+        if let Some(filter) = self.filter.take() {
+            self.history = filter.restore();
+        }
+        if let Some(pattern) = pattern {
+            let history = std::mem::replace(&mut self.history, History::new());
+            let (filter, filtered) = Filter::new(pattern, history);
+            self.history = filtered;
+            self.filter = Some(filter);
+        }
+        self.redraw_output()
+    }
+
+    fn filter_context(&mut self) -> Result<()> {
+        let pos = if self.scroll_data.active {
+            self.scroll_data.pos
+        } else {
+            self.history
+                .len()
+                .saturating_sub(self.output_range() as usize)
+        };
+        if let Some(filter) = self.filter.take() {
+            let origin = filter.origin(pos);
+            let pattern = filter.pattern.clone();
+            self.history = filter.restore();
+            self.redraw_output()?;
+            if let Some(origin) = origin {
+                self.scroll_data.hilite = Some(pattern);
+                self.scroll_to(origin.saturating_sub(self.scroll_range() as usize / 2))?;
             }
         }
         Ok(())
@@ -490,6 +648,129 @@ impl UserInterface for SplitScreen {
         Ok(())
     }
 
+    fn scrollback(&self) -> &[Arc<str>] {
+        &self.history.inner
+    }
+
+    fn scrollback_bytes(&self) -> usize {
+        self.history.bytes()
+    }
+
+    fn scrollback_spilled(&self) -> usize {
+        self.history.spilled()
+    }
+
+    fn set_scrollback_settings(&mut self, settings: &ScrollbackSettings) {
+        match &mut self.filter {
+            Some(filter) => filter.full_mut().configure(settings),
+            None => self.history.configure(settings),
+        }
+        self.auto_pause = settings.auto_pause;
+    }
+
+    fn set_indicator(&mut self, id: String, text: Option<String>) -> Result<()> {
+        self.status_area.set_indicator(id, text);
+        self.status_area.redraw_line(&mut self.screen, 0)?;
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
+    fn set_region(&mut self, region: Region) -> Result<()> {
+        self.layout.set_region(region);
+        self.setup()?;
+        let input_str = self.prompt_input.as_str().to_owned();
+        self.print_prompt_input(&input_str, self.prompt_input_pos);
+        Ok(())
+    }
+
+    fn remove_region(&mut self, name: &str) -> Result<()> {
+        if self.layout.remove_region(name) {
+            self.setup()?;
+            let input_str = self.prompt_input.as_str().to_owned();
+            self.print_prompt_input(&input_str, self.prompt_input_pos);
+        }
+        Ok(())
+    }
+
+    fn set_region_lines(&mut self, name: &str, lines: Vec<String>) -> Result<()> {
+        self.layout.set_lines(name, lines);
+        self.redraw_region(name)?;
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
+    fn set_collapse_repeats(&mut self, enabled: bool) {
+        self.collapse_repeats = enabled;
+        self.repeats.reset();
+    }
+
+    fn set_pending_echo(&mut self, enabled: bool) {
+        self.pending_echo = enabled;
+        if !enabled {
+            self.acknowledge_sent().ok();
+        }
+    }
+
+    fn toggle_pause(&mut self) -> Result<()> {
+        self.paused = !self.paused;
+        if self.paused {
+            self.draw_held_indicator()
+        } else {
+            self.reset_scroll()
+        }
+    }
+
+    /// Checks if new output is kept off the screen, because output is paused or the user is
+    /// reading far back in the scrollback.
+    fn output_held(&self) -> bool {
+        self.paused
+            || !self.scroll_data.not_scrolled_or_split()
+            || (self.scroll_data.active
+                && self.auto_pause > 0
+                && self.history.len().saturating_sub(self.scroll_data.pos) > self.auto_pause)
+    }
+
+    fn region_shown(&self, name: &str) -> bool {
+        self.layout.shown(
+            name,
+            self.mud_prompt_line.saturating_sub(self.region_start_line),
+        )
+    }
+
+    fn acknowledge_sent(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let len = self.history.len();
+        let pending = std::mem::take(&mut self.pending);
+        for index in replace_pending(&mut self.history.inner, pending) {
+            let from_bottom = len - 1 - index;
+            if !self.scroll_data.active
+                && !self.paused
+                && from_bottom < self.output_range() as usize
+            {
+                write!(
+                    self.screen,
+                    "{}{}{}",
+                    termion::cursor::Goto(1, self.output_line - from_bottom as u16),
+                    termion::clear::CurrentLine,
+                    self.history.inner[index],
+                )?;
+            }
+        }
+        write!(self.screen, "{}", self.goto_prompt())?;
+        self.draw_overlay()
+    }
+
+    fn set_overlay(&mut self, lines: Option<Vec<String>>) -> Result<()> {
+        let closed = self.overlay.is_some() && lines.is_none();
+        self.overlay = lines;
+        if closed {
+            self.redraw_output()?;
+        }
+        self.draw_overlay()
+    }
+
     fn flush(&mut self) {
         self.screen.flush().unwrap();
     }
@@ -504,6 +785,9 @@ impl UserInterface for SplitScreen {
 
     fn destroy(mut self: Box<Self>) -> Result<(Box<dyn Write>, History)> {
         self.reset()?;
+        if let Some(filter) = self.filter.take() {
+            self.history = filter.restore();
+        }
         Ok((self.screen, self.history))
     }
 }
@@ -511,7 +795,10 @@ impl UserInterface for SplitScreen {
 impl SplitScreen {
     pub fn new(screen: Box<dyn Write>, history: History) -> Result<Self> {
         let (width, height) = termion::terminal_size()?;
+        Ok(Self::with_size(screen, history, width, height))
+    }
 
+    fn with_size(screen: Box<dyn Write>, history: History, width: u16, height: u16) -> Self {
         let output_start_line = 2;
         let status_area_height = 1;
         let output_line = height - status_area_height - 2;
@@ -520,7 +807,7 @@ impl SplitScreen {
 
         let status_area = StatusArea::new(status_area_height, mud_prompt_line + 1, width);
 
-        Ok(Self {
+        Self {
             screen,
             width,
             height,
@@ -532,17 +819,114 @@ impl SplitScreen {
             prompt_line,
             cursor_prompt_pos: 1,
             history,
+            filter: None,
             scroll_data: ScrollData::new(),
             connection: None,
             tags: HashSet::new(),
             prompt_input: String::new(),
             prompt_input_pos: 0,
-        })
+            layout: Layout::default(),
+            region_start_line: output_start_line,
+            overlay: None,
+            collapse_repeats: false,
+            repeats: Repeats::default(),
+            paused: false,
+            held: 0,
+            auto_pause: ScrollbackSettings::default().auto_pause,
+            pending_echo: false,
+            pending: vec![],
+            throttled: HashMap::new(),
+        }
     }
 
-    fn print_line(&mut self, line: &str) {
-        self.history.append(line);
-        if self.scroll_data.not_scrolled_or_split() {
+    fn print_line(&mut self, line: impl AsRef<str> + Into<Arc<str>>) {
+        self.repeats.reset();
+        if let Some(filter) = &mut self.filter {
+            for line in filter.append(&mut self.history, line) {
+                self.write_output_line(&line);
+            }
+        } else {
+            let line: Arc<str> = line.into();
+            self.history.append(line.clone());
+            self.write_output_line(&line);
+        }
+    }
+
+    /// Collapses a line identical to the last one printed into it, redrawing the last line with
+    /// a repeat counter. The line is added to the history as is.
+    fn print_repeat(&mut self, line: &str) -> bool {
+        if self.filter.is_some() || self.output_held() {
+            return false;
+        }
+        if let Some(count) = self.repeats.repeat(line) {
+            self.history.append(line);
+            write!(
+                self.screen,
+                "{}{}{}{} (x{}){}{}",
+                termion::cursor::Goto(1, self.output_line),
+                termion::clear::CurrentLine,
+                line,
+                Fg(color::LightBlack),
+                count,
+                Fg(color::Reset),
+                self.goto_prompt(),
+            )
+            .unwrap();
+            self.draw_overlay().ok();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Prints throttled output, replacing the rows printed for the same key if asked to and
+    /// they're still among the recent lines. Replacing is left out while the output on screen
+    /// isn't the newest, so it's added like any other line then.
+    fn print_throttled(&mut self, key: &str, line: &str, replace: bool) {
+        let rows: Vec<Arc<str>> = wrap_line(line, self.width as usize)
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+        let replaced = replace
+            && self.filter.is_none()
+            && !self.scroll_data.active
+            && !self.output_held()
+            && self.throttled.get(key).is_some_and(|old| {
+                self.history
+                    .replace_recent(old, rows.clone(), THROTTLE_SEARCH_LINES)
+            });
+        if replaced {
+            self.repeats.reset();
+            self.reset_scroll().ok();
+        } else {
+            for row in &rows {
+                self.print_line(row.clone());
+            }
+        }
+        self.throttled.insert(key.to_string(), rows);
+    }
+
+    /// Shows how many lines are waiting in the status area while output is held.
+    fn draw_held_indicator(&mut self) -> Result<()> {
+        self.status_area.set_indicator(
+            HELD_INDICATOR.to_string(),
+            held_indicator(self.paused, self.held),
+        );
+        self.status_area.redraw_line(&mut self.screen, 0)?;
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
+    fn write_output_line(&mut self, line: &str) {
+        if self.output_held() {
+            self.held += 1;
+            // Only the changes of the shown count are drawn, so spam doesn't redraw the status
+            // bar for every line
+            if held_indicator(self.paused, self.held) != held_indicator(self.paused, self.held - 1)
+            {
+                self.draw_held_indicator().ok();
+            }
+        } else {
             write!(
                 self.screen,
                 "{}\r\n{}{}",
@@ -551,9 +935,38 @@ impl SplitScreen {
                 self.goto_prompt(),
             )
             .unwrap();
+            self.draw_overlay().ok();
         }
     }
 
+    /// Draws the overlay as a box centered over the output area.
+    fn draw_overlay(&mut self) -> Result<()> {
+        let Some(lines) = &self.overlay else {
+            return Ok(());
+        };
+        let output_range = self.output_range();
+        let max_height = output_range.saturating_sub(2) as usize;
+        let mut lines = boxed_lines(&lines[..lines.len().min(max_height)], self.width as usize);
+        // Even the box around no lines is too tall for a tiny output area
+        lines.truncate(output_range as usize);
+        let width = lines
+            .first()
+            .map_or(0, |l| l.as_str().printable_chars().count()) as u16;
+        let column = (self.width.saturating_sub(width)) / 2 + 1;
+        let top = self.output_start_line + output_range.saturating_sub(lines.len() as u16) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            write!(
+                self.screen,
+                "{}{}{}",
+                termion::cursor::Goto(column, top + i as u16),
+                line,
+                termion::style::Reset,
+            )?;
+        }
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
     fn clear_prompt(&mut self) {
         write!(
             self.screen,
@@ -619,6 +1032,49 @@ impl SplitScreen {
         Ok(())
     }
 
+    fn region_line(&self, position: RegionPosition) -> u16 {
+        match position {
+            RegionPosition::Top => self.region_start_line,
+            RegionPosition::Bottom => self.output_line + 1,
+        }
+    }
+
+    fn redraw_region(&mut self, name: &str) -> Result<()> {
+        if let Some(region) = self.layout.get(name) {
+            let start = self.region_line(region.position);
+            let first_line = self.layout.offset(name, start).unwrap_or(start);
+            let lines = self.layout.lines(name);
+            // Rows of regions that don't fit are left to the output
+            let end_line = match region.position {
+                RegionPosition::Top => self.output_start_line,
+                RegionPosition::Bottom => self.mud_prompt_line,
+            };
+            for i in 0..region.height.min(end_line.saturating_sub(first_line)) {
+                write!(
+                    self.screen,
+                    "{}{}{}{}",
+                    termion::cursor::Goto(1, first_line + i),
+                    termion::clear::CurrentLine,
+                    lines.get(i as usize).map(|l| l.as_str()).unwrap_or(""),
+                    termion::style::Reset,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn redraw_regions(&mut self) -> Result<()> {
+        let names = [RegionPosition::Top, RegionPosition::Bottom]
+            .iter()
+            .flat_map(|pos| self.layout.regions(*pos).map(|r| r.name.clone()))
+            .collect::<Vec<String>>();
+        for name in names {
+            self.redraw_region(&name)?;
+        }
+        write!(self.screen, "{}", self.goto_prompt())?;
+        Ok(())
+    }
+
     fn goto_prompt(&self) -> String {
         format!(
             "{}",
@@ -655,7 +1111,42 @@ impl SplitScreen {
         Ok(())
     }
 
+    /// Redraws the output area after the displayed history was swapped.
+    fn redraw_output(&mut self) -> Result<()> {
+        for line_no in self.output_start_line..=self.output_line {
+            write!(
+                self.screen,
+                "{}{}",
+                termion::cursor::Goto(1, line_no),
+                termion::clear::CurrentLine
+            )?;
+        }
+        let pattern = self
+            .filter
+            .as_ref()
+            .map(|filter| filter.pattern.as_str().to_string());
+        self.status_area.set_filter(pattern);
+        self.status_area.redraw_line(&mut self.screen, 0)?;
+        self.reset_scroll()
+    }
+
+    fn update_search_counter(&mut self, pattern: &Regex, line: usize) -> Result<()> {
+        if self.scroll_data.active {
+            let matches = self.history.find_all(pattern);
+            let current = matches.iter().position(|&i| i == line).map_or(0, |i| i + 1);
+            self.status_area
+                .set_search_counter(Some((current, matches.len())));
+            self.status_area.redraw_line(&mut self.screen, 0)?;
+        }
+        Ok(())
+    }
+
     fn draw_scroll(&mut self) -> Result<()> {
+        self.draw_scroll_lines()?;
+        self.draw_overlay()
+    }
+
+    fn draw_scroll_lines(&mut self) -> Result<()> {
         let output_range = self.scroll_range();
         for i in 0..output_range {
             let index = self.scroll_data.pos + i as usize;
@@ -673,7 +1164,7 @@ impl SplitScreen {
                             Fg(color::Reset)
                         ),
                     )
-                    .to_string();
+                    .into();
             }
             write!(
                 self.screen,
@@ -687,21 +1178,269 @@ impl SplitScreen {
     }
 
     fn scroll_range(&self) -> u16 {
-        if self.scroll_data.allow_split && self.height > SCROLL_LIVE_BUFFER_SIZE * 2 {
-            self.output_line - self.output_start_line - SCROLL_LIVE_BUFFER_SIZE + 1
+        let output_range = self.output_range();
+        if self.scroll_data.allow_split && output_range > SCROLL_LIVE_BUFFER_SIZE * 2 {
+            output_range - SCROLL_LIVE_BUFFER_SIZE
         } else {
-            self.output_range()
+            output_range
         }
     }
 
     fn output_range(&self) -> u16 {
-        self.output_line - self.output_start_line + 1
+        (self.output_line + 1).saturating_sub(self.output_start_line)
+    }
+}
+
+/// A run of identical output lines.
+#[derive(Default)]
+struct Repeats {
+    line: Option<String>,
+    count: usize,
+}
+
+impl Repeats {
+    fn start(&mut self, line: &str) {
+        self.line = Some(line.to_string());
+        self.count = 1;
+    }
+
+    fn reset(&mut self) {
+        self.line = None;
+        self.count = 0;
+    }
+
+    /// Counts `line` if it continues the run, returning the length of the run.
+    fn repeat(&mut self, line: &str) -> Option<usize> {
+        if self.line.as_deref() == Some(line) {
+            self.count += 1;
+            Some(self.count)
+        } else {
+            None
+        }
+    }
+}
+
+/// Replaces the most recent rows of pending commands in the history, returning the indices
+/// of the rows that were replaced. Rows that left the history already are skipped.
+fn replace_pending(history: &mut [Arc<str>], pending: Vec<(String, String)>) -> Vec<usize> {
+    let mut end = history.len();
+    let mut replaced = vec![];
+    for (pending, sent) in pending.into_iter().rev() {
+        if let Some(index) = history[..end].iter().rposition(|line| **line == *pending) {
+            history[index] = Arc::from(sent);
+            replaced.push(index);
+            end = index;
+        }
+    }
+    replaced
+}
+
+/// Frames the lines in a box no wider than `max_width`, padding them to the same width and
+/// cutting off lines that are too long.
+fn boxed_lines(lines: &[String], max_width: usize) -> Vec<String> {
+    let inner = lines
+        .iter()
+        .map(|line| line.as_str().printable_chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(max_width.saturating_sub(4));
+    let border = "─".repeat(inner + 2);
+    let mut boxed = vec![format!("┌{border}┐")];
+    for line in lines {
+        let mut line = line.as_str();
+        if let Some((i, _)) = line.printable_char_indices().nth(inner) {
+            line = &line[..i];
+        }
+        let pad = inner - line.printable_chars().count();
+        boxed.push(format!(
+            "│ {line}{} {}│",
+            termion::style::Reset,
+            " ".repeat(pad)
+        ));
+    }
+    boxed.push(format!("└{border}┘"));
+    boxed
+}
+
+/// The status area text for the held output. Past a hundred lines the count is rounded down to
+/// the hundred, so it changes less often while output pours in.
+pub(super) fn held_indicator(paused: bool, held: usize) -> Option<String> {
+    let count = match held {
+        0 => None,
+        1..100 => Some(format!("{held} lines pending")),
+        _ => Some(format!("{}+ lines pending", held / 100 * 100)),
+    };
+    match (paused, count) {
+        (false, count) => count,
+        (true, None) => Some("PAUSED".to_string()),
+        (true, Some(count)) => Some(format!("PAUSED {count}")),
     }
 }
 
 #[cfg(test)]
 mod screen_test {
     use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Output written to the screen, readable after it's handed to the screen.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.borrow_mut())).unwrap()
+        }
+    }
+
+    fn test_screen() -> (SplitScreen, Output) {
+        let output = Output::default();
+        let screen = SplitScreen::with_size(Box::new(output.clone()), History::new(), 80, 24);
+        (screen, output)
+    }
+
+    #[test]
+    fn test_held_indicator() {
+        assert_eq!(held_indicator(false, 0), None);
+        assert_eq!(held_indicator(true, 0), Some("PAUSED".to_string()));
+        assert_eq!(
+            held_indicator(true, 42),
+            Some("PAUSED 42 lines pending".to_string())
+        );
+        assert_eq!(
+            held_indicator(false, 1250),
+            Some("1200+ lines pending".to_string())
+        );
+        assert_eq!(held_indicator(false, 1201), held_indicator(false, 1299));
+    }
+
+    #[test]
+    fn test_pause() {
+        let (mut screen, output) = test_screen();
+        screen.toggle_pause().unwrap();
+        for i in 0..3 {
+            screen.print_line(format!("held line {i}"));
+        }
+        assert_eq!(screen.held, 3);
+        assert!(!output.take().contains("held line"));
+        assert_eq!(screen.status_area.info(0), "(PAUSED 3 lines pending) ");
+
+        // Resuming shows the held lines
+        screen.toggle_pause().unwrap();
+        assert_eq!(screen.held, 0);
+        let written = output.take();
+        for i in 0..3 {
+            assert!(written.contains(&format!("held line {i}")));
+        }
+        assert_eq!(screen.status_area.info(0), "");
+        screen.print_line("live line");
+        assert!(output.take().contains("live line"));
+    }
+
+    #[test]
+    fn test_held_redraws() {
+        let (mut screen, output) = test_screen();
+        screen.toggle_pause().unwrap();
+        for _ in 0..99 {
+            screen.print_line("spam");
+        }
+        output.take();
+        // The shown count stays at 100+ until 200 lines are held
+        for _ in 0..100 {
+            screen.print_line("spam");
+        }
+        assert_eq!(output.take().matches("lines pending").count(), 1);
+        assert_eq!(screen.held, 199);
+    }
+
+    #[test]
+    fn test_auto_pause() {
+        let (mut screen, output) = test_screen();
+        screen.set_scrollback_settings(&ScrollbackSettings {
+            auto_pause: 10,
+            ..Default::default()
+        });
+        for i in 0..100 {
+            screen.print_line(format!("line {i}"));
+        }
+        screen.scroll_data.active = true;
+        screen.scroll_data.split = true;
+        screen.scroll_data.pos = 95;
+        output.take();
+        screen.print_line("close to the bottom");
+        assert_eq!(screen.held, 0);
+        assert!(output.take().contains("close to the bottom"));
+
+        screen.scroll_data.pos = 50;
+        screen.print_line("far from the bottom");
+        assert_eq!(screen.held, 1);
+        assert!(!output.take().contains("far from the bottom"));
+        assert_eq!(screen.status_area.info(0), "(1 lines pending) ");
+
+        // Turned off, output is only held by pausing
+        screen.set_scrollback_settings(&ScrollbackSettings {
+            auto_pause: 0,
+            ..Default::default()
+        });
+        screen.print_line("shown again");
+        assert!(output.take().contains("shown again"));
+    }
+
+    #[test]
+    fn test_repeats() {
+        let mut repeats = Repeats::default();
+        assert_eq!(repeats.repeat("The rat bites you."), None);
+        repeats.start("The rat bites you.");
+        assert_eq!(repeats.repeat("The rat bites you."), Some(2));
+        assert_eq!(repeats.repeat("The rat bites you."), Some(3));
+        assert_eq!(repeats.repeat("You kill the rat."), None);
+        repeats.reset();
+        assert_eq!(repeats.repeat("The rat bites you."), None);
+    }
+
+    #[test]
+    fn test_replace_pending() {
+        let mut history: Vec<Arc<str>> = ["~ look", "A room", "~ look", "~ north"]
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+        let pending = vec![
+            ("~ look".to_string(), "> look".to_string()),
+            ("~ north".to_string(), "> north".to_string()),
+            ("~ gone".to_string(), "> gone".to_string()),
+        ];
+        assert_eq!(replace_pending(&mut history, pending), vec![3, 2]);
+        assert_eq!(
+            history,
+            ["~ look", "A room", "> look", "> north"].map(Arc::from)
+        );
+    }
+
+    #[test]
+    fn test_boxed_lines() {
+        let lines = vec!["attack".to_string(), "\x1b[7mflee\x1b[0m".to_string()];
+        assert_eq!(
+            boxed_lines(&lines, 80),
+            vec![
+                "┌────────┐".to_string(),
+                "│ attack\x1b[m │".to_string(),
+                "│ \x1b[7mflee\x1b[0m\x1b[m   │".to_string(),
+                "└────────┘".to_string(),
+            ]
+        );
+        let boxed = boxed_lines(&["a long menu entry".to_string()], 10);
+        assert_eq!(boxed[1], "│ a long\x1b[m │");
+    }
 
     #[test]
     fn test_append_history() {
@@ -711,7 +1450,7 @@ mod screen_test {
         history.append(line);
         assert_eq!(
             history.inner,
-            vec![
+            [
                 "a nice line",
                 "",
                 "with a blank line",
@@ -719,6 +1458,7 @@ mod screen_test {
                 "c",
                 "test",
             ]
+            .map(Arc::from)
         );
     }
 
@@ -0,0 +1,1044 @@
+use super::filter::Filter;
+use super::history::History;
+use super::scroll_data::ScrollData;
+use super::split_screen::{held_indicator, StatusArea};
+use super::user_interface::TerminalSizeError;
+use super::wrap_line;
+use crate::io::SaveData;
+use crate::model::{
+    Layout, Line, Regex, Region, RegionPosition, ScrollbackSettings, Settings, HIDE_TOPBAR,
+};
+use crate::ui::printable_chars::PrintableCharsIterator;
+use anyhow::Result;
+use ratatui::backend::TermionBackend;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout as Split, Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{self, Span};
+use ratatui::widgets::{Block, Clear, Padding, Paragraph, Widget};
+use ratatui::{Terminal, TerminalOptions, Viewport};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+use termion::color::{self, Fg};
+use vte::{Params, Parser, Perform};
+
+use super::UserInterface;
+
+const SCROLL_LIVE_BUFFER_SIZE: u16 = 10;
+const HELD_INDICATOR: &str = "held_output";
+const PROMPT_HEIGHT: u16 = 1;
+
+/// The sixteen colors of the basic SGR codes, in code order.
+const ANSI_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// The terminal writer, shared with ratatui for as long as the screen lives and handed on to
+/// the next screen when it's destroyed.
+#[derive(Clone)]
+struct ScreenWriter(Rc<RefCell<Option<Box<dyn Write>>>>);
+
+impl ScreenWriter {
+    fn take(&self) -> Option<Box<dyn Write>> {
+        self.0.borrow_mut().take()
+    }
+}
+
+impl Write for ScreenWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.0.borrow_mut() {
+            Some(writer) => writer.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.0.borrow_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The rows of the screen each part is drawn to.
+struct Areas {
+    top_bar: Rect,
+    top_regions: Rect,
+    output: Rect,
+    bottom_regions: Rect,
+    mud_prompt: Rect,
+    status: Rect,
+    prompt: Rect,
+}
+
+/// A screen drawn with ratatui. Every change is kept as state and the whole screen is rendered
+/// into a buffer on flush, ratatui only writing the cells that changed since the last frame.
+pub struct TuiScreen {
+    terminal: Terminal<TermionBackend<ScreenWriter>>,
+    screen: ScreenWriter,
+    width: u16,
+    height: u16,
+    hide_topbar: bool,
+    mud_prompt: Line,
+    status_area: StatusArea,
+    history: History,
+    filter: Option<Filter>,
+    scroll_data: ScrollData,
+    connection: Option<String>,
+    tags: BTreeSet<String>,
+    prompt_input: String,
+    prompt_input_pos: usize,
+    layout: Layout,
+    overlay: Option<Vec<String>>,
+    /// Output is held until the user resumes it.
+    paused: bool,
+    /// Lines received since the output was paused.
+    held: usize,
+    dirty: bool,
+}
+
+impl UserInterface for TuiScreen {
+    fn setup(&mut self) -> Result<()> {
+        let settings = Settings::try_load()?;
+
+        // Get params in case screen resized
+        let (width, height) = termion::terminal_size()?;
+        if width > 0 && height > 0 {
+            self.width = width;
+            self.height = height;
+            self.hide_topbar = settings.get(HIDE_TOPBAR)?;
+            self.terminal.resize(Rect::new(0, 0, width, height))?;
+            self.reset_scroll()?;
+            self.draw()
+        } else {
+            Err(TerminalSizeError.into())
+        }
+    }
+
+    fn print_error(&mut self, output: &str) {
+        let line = format!("{}[!!] {}{}", Fg(color::Red), output, Fg(color::Reset));
+        self.print_line(line);
+    }
+
+    fn print_info(&mut self, output: &str) {
+        let line = format!("[**] {output}");
+        self.print_line(line);
+    }
+
+    fn print_output(&mut self, line: &Line) {
+        if let Some(print_line) = line.print_line() {
+            if !line.is_utf8() || print_line.trim().is_empty() {
+                self.print_line(line.shared_line());
+            } else {
+                let cur_line = self.history.len();
+                let rows = wrap_line(print_line, self.width as usize);
+                let count = rows.len();
+                if count == 1 && rows[0].len() == print_line.len() {
+                    // The history shares the text with the line when it fits on a row as is
+                    self.print_line(line.shared_line());
+                } else {
+                    for row in rows {
+                        self.print_line(row);
+                    }
+                }
+                if self.scroll_data.scroll_lock && count > self.height as usize {
+                    self.scroll_to(cur_line).ok();
+                }
+            }
+        }
+    }
+
+    fn print_prompt(&mut self, prompt: &Line) {
+        self.mud_prompt = prompt.clone();
+        self.dirty = true;
+    }
+
+    fn print_prompt_input(&mut self, input: &str, pos: usize) {
+        // Sanity check
+        debug_assert!(pos <= input.len());
+
+        self.prompt_input = input.to_string();
+        self.prompt_input_pos = pos;
+        self.dirty = true;
+    }
+
+    fn print_send(&mut self, send: &Line) {
+        if self.scroll_data.active && send.flags.source != Some("script".to_string()) {
+            self.reset_scroll().ok();
+        }
+        if let Some(line) = send.print_line() {
+            let sent = &format!(
+                "{}{}> {}{}",
+                termion::style::Reset,
+                Fg(color::LightYellow),
+                line,
+                Fg(color::Reset),
+            );
+            for line in wrap_line(sent, self.width as usize) {
+                self.print_line(line);
+            }
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.terminal.clear()?;
+        Ok(())
+    }
+
+    fn reset_scroll(&mut self) -> Result<()> {
+        self.history.release();
+        self.scroll_data.reset(&self.history)?;
+        self.status_area.set_search_counter(None);
+        self.status_area.set_scroll_marker(false);
+        self.held = 0;
+        self.update_held_indicator();
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn scroll_down(&mut self) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if self.scroll_data.active {
+            let max_start_index = self
+                .history
+                .len()
+                .saturating_sub(self.scroll_range() as usize);
+            let new_start_index = self.scroll_data.pos + 5;
+            if new_start_index >= max_start_index {
+                self.reset_scroll()?;
+            } else {
+                self.scroll_data.pos = new_start_index;
+                self.dirty = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_lock(&mut self, lock: bool) -> Result<()> {
+        self.scroll_data.lock(lock)
+    }
+
+    fn scroll_to(&mut self, row: usize) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if self.history.len() > self.scroll_range() as usize {
+            let max_start_index = self.history.len() - self.scroll_range() as usize;
+            if row < max_start_index {
+                self.init_scroll();
+                self.scroll_data.pos = row;
+            } else {
+                self.reset_scroll()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn scroll_top(&mut self) -> Result<()> {
+        if self.history.len() >= self.output_range() as usize {
+            self.init_scroll();
+            self.scroll_data.pos = 0;
+        }
+        Ok(())
+    }
+
+    fn scroll_up(&mut self) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        let scroll_range = self.scroll_range() as usize;
+        if self.history.len() > scroll_range {
+            if !self.scroll_data.active {
+                self.init_scroll();
+                self.scroll_data.pos = self.history.len() - scroll_range;
+            }
+            if self.scroll_data.pos < 5 {
+                self.scroll_data.pos += self.history.restore();
+            }
+            self.scroll_data.pos -= self.scroll_data.pos.min(5);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn find_up(&mut self, pattern: &Regex) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        let pos = if self.scroll_data.active {
+            self.scroll_data.pos
+        } else if self.history.len() > self.scroll_range() as usize {
+            self.history.len() - self.scroll_range() as usize
+        } else {
+            self.history.len()
+        };
+        if let Some(line) = self.history.find_backward(pattern, pos) {
+            self.scroll_data.hilite = Some(pattern.clone());
+            self.scroll_to(line)?;
+            self.update_search_counter(pattern, line);
+        }
+        Ok(())
+    }
+
+    fn find_down(&mut self, pattern: &Regex) -> Result<()> {
+        self.scroll_data.clamp(&self.history);
+        if self.scroll_data.active {
+            if let Some(line) = self
+                .history
+                .find_forward(pattern, self.history.len().min(self.scroll_data.pos + 1))
+            {
+                self.scroll_data.hilite = Some(pattern.clone());
+                self.scroll_to(line.min(self.history.len() - 1))?;
+                self.update_search_counter(pattern, line);
+            }
+        }
+        Ok(())
+    }
+
+    fn find_all(&self, pattern: &Regex) -> Vec<usize> {
+        self.history.find_all(pattern)
+    }
+
+    fn set_filter(&mut self, pattern: Option<Regex>) -> Result<()> {
+        if let Some(filter) = self.filter.take() {
+            self.history = filter.restore();
+        }
+        if let Some(pattern) = pattern {
+            let history = std::mem::replace(&mut self.history, History::new());
+            let (filter, filtered) = Filter::new(pattern, history);
+            self.history = filtered;
+            self.filter = Some(filter);
+        }
+        let pattern = self
+            .filter
+            .as_ref()
+            .map(|filter| filter.pattern.as_str().to_string());
+        self.status_area.set_filter(pattern);
+        self.reset_scroll()
+    }
+
+    fn filter_context(&mut self) -> Result<()> {
+        let pos = if self.scroll_data.active {
+            self.scroll_data.pos
+        } else {
+            self.history
+                .len()
+                .saturating_sub(self.output_range() as usize)
+        };
+        if let Some(filter) = self.filter.take() {
+            let origin = filter.origin(pos);
+            let pattern = filter.pattern.clone();
+            self.history = filter.restore();
+            self.status_area.set_filter(None);
+            self.reset_scroll()?;
+            if let Some(origin) = origin {
+                self.scroll_data.hilite = Some(pattern);
+                self.scroll_to(origin.saturating_sub(self.scroll_range() as usize / 2))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_host(&mut self, host: &str, port: u16) -> Result<()> {
+        self.connection = if !host.is_empty() {
+            Some(format!("{host}:{port}"))
+        } else {
+            None
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn add_tag(&mut self, tag: &str) -> Result<()> {
+        self.tags.insert(tag.to_string());
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn remove_tag(&mut self, tag: &str) -> Result<()> {
+        self.tags.remove(tag);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn clear_tags(&mut self) -> Result<()> {
+        self.tags.clear();
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn set_status_area_height(&mut self, height: u16) -> Result<()> {
+        self.status_area.set_height(height, 0);
+        self.reset_scroll()
+    }
+
+    fn set_status_line(&mut self, line: usize, info: String) -> Result<()> {
+        self.status_area.set_status_line(line, info);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn scrollback(&self) -> &[Arc<str>] {
+        &self.history.inner
+    }
+
+    fn scrollback_bytes(&self) -> usize {
+        self.history.bytes()
+    }
+
+    fn scrollback_spilled(&self) -> usize {
+        self.history.spilled()
+    }
+
+    fn set_scrollback_settings(&mut self, settings: &ScrollbackSettings) {
+        match &mut self.filter {
+            Some(filter) => filter.full_mut().configure(settings),
+            None => self.history.configure(settings),
+        }
+    }
+
+    fn set_indicator(&mut self, id: String, text: Option<String>) -> Result<()> {
+        self.status_area.set_indicator(id, text);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn set_region(&mut self, region: Region) -> Result<()> {
+        self.layout.set_region(region);
+        self.reset_scroll()
+    }
+
+    fn remove_region(&mut self, name: &str) -> Result<()> {
+        if self.layout.remove_region(name) {
+            self.reset_scroll()?;
+        }
+        Ok(())
+    }
+
+    fn set_region_lines(&mut self, name: &str, lines: Vec<String>) -> Result<()> {
+        self.layout.set_lines(name, lines);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn output_held(&self) -> bool {
+        self.paused || !self.scroll_data.not_scrolled_or_split()
+    }
+
+    fn region_shown(&self, name: &str) -> bool {
+        let areas = self.areas();
+        let rows = areas.top_regions.height + areas.output.height + areas.bottom_regions.height;
+        self.layout.shown(name, rows)
+    }
+
+    fn toggle_pause(&mut self) -> Result<()> {
+        self.paused = !self.paused;
+        if self.paused {
+            self.update_held_indicator();
+            self.dirty = true;
+            Ok(())
+        } else {
+            self.reset_scroll()
+        }
+    }
+
+    fn set_overlay(&mut self, lines: Option<Vec<String>>) -> Result<()> {
+        self.overlay = lines;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if self.dirty {
+            self.draw().unwrap();
+        }
+        self.terminal.backend_mut().flush().unwrap();
+    }
+
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    fn destroy(mut self: Box<Self>) -> Result<(Box<dyn Write>, History)> {
+        self.reset()?;
+        if let Some(filter) = self.filter.take() {
+            self.history = filter.restore();
+        }
+        let screen = self
+            .screen
+            .take()
+            .expect("the screen writer is only taken here");
+        Ok((screen, self.history))
+    }
+}
+
+impl TuiScreen {
+    pub fn new(screen: Box<dyn Write>, history: History) -> Result<Self> {
+        let (width, height) = termion::terminal_size()?;
+        let screen = ScreenWriter(Rc::new(RefCell::new(Some(screen))));
+        let backend = TermionBackend::new(screen.clone());
+        let viewport = Viewport::Fixed(Rect::new(0, 0, width, height));
+        let terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
+
+        Ok(Self {
+            terminal,
+            screen,
+            width,
+            height,
+            hide_topbar: false,
+            mud_prompt: Line::from(""),
+            status_area: StatusArea::new(1, 0, width),
+            history,
+            filter: None,
+            scroll_data: ScrollData::new(),
+            connection: None,
+            tags: BTreeSet::new(),
+            prompt_input: String::new(),
+            prompt_input_pos: 0,
+            layout: Layout::default(),
+            overlay: None,
+            paused: false,
+            held: 0,
+            dirty: true,
+        })
+    }
+
+    fn print_line(&mut self, line: impl AsRef<str> + Into<Arc<str>>) {
+        let count = match &mut self.filter {
+            Some(filter) => filter.append(&mut self.history, line).len(),
+            None => {
+                self.history.append(line);
+                1
+            }
+        };
+        if self.paused && count > 0 {
+            self.held += count;
+            self.update_held_indicator();
+        }
+        self.dirty = true;
+    }
+
+    /// Shows how many lines are waiting in the status area while output is paused.
+    fn update_held_indicator(&mut self) {
+        self.status_area.set_indicator(
+            HELD_INDICATOR.to_string(),
+            held_indicator(self.paused, self.held),
+        );
+    }
+
+    fn update_search_counter(&mut self, pattern: &Regex, line: usize) {
+        if self.scroll_data.active {
+            let matches = self.history.find_all(pattern);
+            let current = matches.iter().position(|&i| i == line).map_or(0, |i| i + 1);
+            self.status_area
+                .set_search_counter(Some((current, matches.len())));
+        }
+    }
+
+    fn init_scroll(&mut self) {
+        self.scroll_data.active = true;
+        if self.scroll_range() < self.output_range() {
+            self.scroll_data.split = true;
+        } else {
+            self.status_area.set_scroll_marker(true);
+        }
+        self.dirty = true;
+    }
+
+    fn scroll_range(&self) -> u16 {
+        let output_range = self.output_range();
+        if self.scroll_data.allow_split && output_range > SCROLL_LIVE_BUFFER_SIZE * 2 {
+            output_range - SCROLL_LIVE_BUFFER_SIZE
+        } else {
+            output_range
+        }
+    }
+
+    fn output_range(&self) -> u16 {
+        self.areas().output.height
+    }
+
+    fn areas(&self) -> Areas {
+        let area = Rect::new(0, 0, self.width, self.height);
+        let [top_bar, body, mud_prompt, status, prompt] = Split::vertical([
+            Constraint::Length(u16::from(!self.hide_topbar)),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(self.status_area.height()),
+            Constraint::Length(PROMPT_HEIGHT),
+        ])
+        .areas(area);
+        let (top, bottom) = self.layout.fit(body.height);
+        let [top_regions, output, bottom_regions] = Split::vertical([
+            Constraint::Length(top),
+            Constraint::Fill(1),
+            Constraint::Length(bottom),
+        ])
+        .areas(body);
+        Areas {
+            top_bar,
+            top_regions,
+            output,
+            bottom_regions,
+            mud_prompt,
+            status,
+            prompt,
+        }
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, self.width, self.height));
+        let cursor = self.render(&mut buffer);
+        self.terminal.draw(|frame| {
+            *frame.buffer_mut() = buffer;
+            frame.set_cursor_position(cursor);
+        })?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Renders the whole screen, returning where the cursor goes.
+    fn render(&self, buf: &mut Buffer) -> Position {
+        let areas = self.areas();
+        self.render_top_bar(areas.top_bar, buf);
+        self.render_regions(RegionPosition::Top, areas.top_regions, buf);
+        self.render_output(areas.output, buf);
+        self.render_regions(RegionPosition::Bottom, areas.bottom_regions, buf);
+        if self.scroll_data.not_scrolled_or_split() {
+            let prompt = self.mud_prompt.print_line().unwrap_or("");
+            buf.set_line(
+                areas.mud_prompt.x,
+                areas.mud_prompt.y,
+                &ansi_line(prompt, Style::default()),
+                areas.mud_prompt.width,
+            );
+        }
+        self.render_status_area(areas.status, buf);
+        self.render_overlay(areas.output, buf);
+
+        render_prompt_input(&self.prompt_input, self.prompt_input_pos, areas.prompt, buf)
+    }
+
+    fn render_top_bar(&self, area: Rect, buf: &mut Buffer) {
+        if area.height == 0 {
+            return;
+        }
+        let host = if let Some(connection) = &self.connection {
+            format!("═ {connection} ")
+        } else {
+            "".to_string()
+        };
+        let tags = self
+            .tags
+            .iter()
+            .map(|s| format!("[{s}]"))
+            .collect::<String>();
+        let mut output = format!("{host}{tags}");
+        if !output.is_empty() {
+            output.push(' ');
+        }
+        let output = format!("{output:═<0$}", area.width as usize);
+        buf.set_string(area.x, area.y, output, Style::default().fg(Color::Green));
+    }
+
+    fn render_regions(&self, position: RegionPosition, area: Rect, buf: &mut Buffer) {
+        let mut y = area.y;
+        for region in self.layout.regions(position) {
+            // Rows of regions that don't fit are left to the output
+            let rows = region.height.min(area.bottom().saturating_sub(y));
+            for (i, line) in self.layout.lines(&region.name).iter().enumerate() {
+                if i as u16 >= rows {
+                    break;
+                }
+                let line = ansi_line(line, Style::default());
+                buf.set_line(area.x, y + i as u16, &line, area.width);
+            }
+            y += rows;
+        }
+    }
+
+    fn render_output(&self, area: Rect, buf: &mut Buffer) {
+        let lines = &self.history.inner;
+        if self.scroll_data.active {
+            let [scroll, separator, live] = if self.scroll_data.split {
+                Split::vertical([
+                    Constraint::Length(self.scroll_range()),
+                    Constraint::Length(1),
+                    Constraint::Fill(1),
+                ])
+                .areas(area)
+            } else {
+                [area, Rect::default(), Rect::default()]
+            };
+            let start = self.scroll_data.pos.min(lines.len());
+            let end = lines.len().min(start + scroll.height as usize);
+            let hilite = self.scroll_data.hilite.as_ref();
+            render_rows(&lines[start..end], hilite, scroll, buf);
+            if separator.height > 0 {
+                let bar = format!("{:━<1$}", "━ (scroll) ", separator.width as usize);
+                buf.set_string(
+                    separator.x,
+                    separator.y,
+                    bar,
+                    Style::default().fg(Color::Green),
+                );
+            }
+            let end = lines.len() - self.held.min(lines.len());
+            render_rows(
+                &lines[end.saturating_sub(live.height as usize)..end],
+                None,
+                live,
+                buf,
+            );
+        } else {
+            let end = lines.len() - self.held.min(lines.len());
+            render_rows(
+                &lines[end.saturating_sub(area.height as usize)..end],
+                None,
+                area,
+                buf,
+            );
+        }
+    }
+
+    fn render_status_area(&self, area: Rect, buf: &mut Buffer) {
+        let green = Style::default().fg(Color::Green);
+        for i in 0..area.height {
+            let info = self.status_area.info(i as usize);
+            let line = if i == 0 || i == area.height - 1 {
+                // The first and last lines are drawn as bars
+                let mut spans = vec![];
+                if !info.trim().is_empty() {
+                    spans.push(Span::styled("━ ", green));
+                    spans.extend(ansi_line(info.trim(), green).spans);
+                    spans.push(Span::styled(" ", green));
+                }
+                spans.push(Span::styled("━".repeat(area.width as usize), green));
+                text::Line::from(spans)
+            } else {
+                ansi_line(&info, Style::default())
+            };
+            buf.set_line(area.x, area.y + i, &line, area.width);
+        }
+    }
+
+    /// Draws the overlay as a bordered popup centered over the output area.
+    fn render_overlay(&self, area: Rect, buf: &mut Buffer) {
+        let Some(lines) = &self.overlay else {
+            return;
+        };
+        let inner = lines
+            .iter()
+            .map(|line| line.as_str().printable_chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = (inner as u16).saturating_add(4).min(area.width);
+        let height = (lines.len() as u16).saturating_add(2).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let text = lines
+            .iter()
+            .map(|line| ansi_line(line, Style::default()))
+            .collect::<Vec<_>>();
+        Clear.render(popup, buf);
+        Paragraph::new(text)
+            .block(Block::bordered().padding(Padding::horizontal(1)))
+            .render(popup, buf);
+    }
+}
+
+/// Draws rows of output from the top of the area, highlighting the matches of `hilite`.
+fn render_rows(rows: &[Arc<str>], hilite: Option<&Regex>, area: Rect, buf: &mut Buffer) {
+    // Output that doesn't fill the area sits at the bottom of it
+    let top = area.y + area.height.saturating_sub(rows.len() as u16);
+    for (i, row) in rows.iter().take(area.height as usize).enumerate() {
+        let line = match hilite {
+            Some(pattern) => {
+                let marked = pattern.replace_all(row, "\x1b[97;44m$0\x1b[39;49m");
+                ansi_line(&marked, Style::default())
+            }
+            None => ansi_line(row, Style::default()),
+        };
+        buf.set_line(area.x, top + i as u16, &line, area.width);
+    }
+}
+
+/// Draws the prompt input styled by the SGR sequences of masks and prompt segments, returning
+/// where the cursor goes.
+fn render_prompt_input(input: &str, pos: usize, area: Rect, buf: &mut Buffer) -> Position {
+    let (mut skip, pos) = input_window(input, pos, area.width as usize);
+    // Styles are read from the whole input, so text scrolled into view keeps its color
+    let spans = ansi_line(input, Style::default())
+        .spans
+        .into_iter()
+        .filter_map(|span| {
+            let count = span.content.chars().count();
+            if skip >= count {
+                skip -= count;
+                return None;
+            }
+            let content = span.content.chars().skip(skip).collect::<String>();
+            skip = 0;
+            Some(Span::styled(content, span.style))
+        })
+        .collect::<Vec<Span>>();
+    buf.set_line(area.x, area.y, &text::Line::from(spans), area.width);
+    Position::new(area.x + pos as u16, area.y)
+}
+
+/// Returns how many printable characters of the input are scrolled out of a prompt `width`
+/// columns wide and the column of the cursor, moving a full width at a time as the cursor passes
+/// the edge. `pos` counts printable characters, so escape sequences don't move the cursor.
+fn input_window(input: &str, pos: usize, width: usize) -> (usize, usize) {
+    let len = input.printable_chars().count();
+    let mut skip = 0;
+    let mut pos = pos;
+    while width > 0 && len - skip >= width && pos >= width {
+        skip += width;
+        pos -= width;
+    }
+    (skip, pos)
+}
+
+/// Collects printed text into spans styled by the SGR sequences around it.
+struct SpanBuilder {
+    base: Style,
+    style: Style,
+    text: String,
+    spans: Vec<Span<'static>>,
+}
+
+impl SpanBuilder {
+    fn push_span(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.spans.push(Span::styled(text, self.style));
+        }
+    }
+}
+
+impl Perform for SpanBuilder {
+    fn print(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\t' {
+            self.text.push(' ');
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+        if c == 'm' {
+            self.push_span();
+            let codes = params.iter().flatten().copied().collect::<Vec<u16>>();
+            self.style = apply_sgr(self.style, self.base, &codes);
+        }
+    }
+}
+
+/// Converts a line with ANSI escape sequences into a ratatui line, styling the text the way the
+/// SGR sequences do on top of `base`. Other escape sequences are left out.
+fn ansi_line(line: &str, base: Style) -> text::Line<'static> {
+    let mut builder = SpanBuilder {
+        base,
+        style: base,
+        text: String::new(),
+        spans: vec![],
+    };
+    let mut parser = Parser::new();
+    for byte in line.bytes() {
+        parser.advance(&mut builder, byte);
+    }
+    builder.push_span();
+    text::Line::from(builder.spans)
+}
+
+/// Applies the codes of one SGR sequence to `style`, resetting to `base`.
+fn apply_sgr(mut style: Style, base: Style, codes: &[u16]) -> Style {
+    if codes.is_empty() {
+        return base;
+    }
+    let mut codes = codes.iter().copied();
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => base,
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(ANSI_COLORS[code as usize - 30]),
+            90..=97 => style.fg(ANSI_COLORS[code as usize - 82]),
+            40..=47 => style.bg(ANSI_COLORS[code as usize - 40]),
+            100..=107 => style.bg(ANSI_COLORS[code as usize - 92]),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            39 => Style {
+                fg: base.fg,
+                ..style
+            },
+            49 => Style {
+                bg: base.bg,
+                ..style
+            },
+            _ => style,
+        };
+    }
+    style
+}
+
+/// Reads the 256 color or true color following a `38` or `48` code.
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match codes.next()? {
+        5 => {
+            let index = codes.next()?;
+            Some(match ANSI_COLORS.get(index as usize) {
+                Some(color) => *color,
+                None => Color::Indexed(index.min(255) as u8),
+            })
+        }
+        2 => {
+            let mut channel = || codes.next().map(|c| c.min(255) as u8);
+            Some(Color::Rgb(channel()?, channel()?, channel()?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tui_screen_test {
+    use super::*;
+
+    #[test]
+    fn test_ansi_line() {
+        let line = ansi_line("plain \x1b[31mred\x1b[0m plain", Style::default());
+        assert_eq!(line.spans.len(), 3);
+        assert_eq!(line.spans[0].content, "plain ");
+        assert_eq!(line.spans[0].style, Style::default());
+        assert_eq!(line.spans[1].content, "red");
+        assert_eq!(line.spans[1].style.fg, Some(Color::Red));
+        assert_eq!(line.spans[2].style.fg, None);
+
+        let line = ansi_line("\x1b[1;38;5;202mbold\x1b[22;39m", Style::default());
+        assert_eq!(line.spans.len(), 1);
+        assert_eq!(line.spans[0].style.fg, Some(Color::Indexed(202)));
+        assert!(line.spans[0].style.add_modifier.contains(Modifier::BOLD));
+
+        let line = ansi_line("\x1b[48;2;16;32;48mdark\x1b[97mbright", Style::default());
+        assert_eq!(line.spans[0].style.bg, Some(Color::Rgb(16, 32, 48)));
+        assert_eq!(line.spans[1].style.fg, Some(Color::White));
+        assert_eq!(line.spans[1].style.bg, Some(Color::Rgb(16, 32, 48)));
+    }
+
+    #[test]
+    fn test_ansi_line_base() {
+        let green = Style::default().fg(Color::Green);
+        let line = ansi_line("a\x1b[34mb\x1b[39mc\x1b[mdone", green);
+        let colors = line
+            .spans
+            .iter()
+            .map(|span| span.style.fg)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            vec![
+                Some(Color::Green),
+                Some(Color::Blue),
+                Some(Color::Green),
+                Some(Color::Green)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ansi_line_skips_other_sequences() {
+        let line = ansi_line("\x1b[2Kcleared\x1b[1A\ttab", Style::default());
+        assert_eq!(line.to_string(), "cleared tab");
+        // Cut off sequences are left out
+        assert_eq!(
+            ansi_line("cut \x1b[38;5", Style::default()).to_string(),
+            "cut "
+        );
+        assert_eq!(ansi_line("åäö", Style::default()).to_string(), "åäö");
+    }
+
+    #[test]
+    fn test_input_window() {
+        assert_eq!(input_window("short", 2, 10), (0, 2));
+        assert_eq!(input_window("0123456789abc", 4, 10), (0, 4));
+        assert_eq!(input_window("0123456789abc", 12, 10), (10, 2));
+        assert_eq!(
+            input_window("\x1b[31m0123456789\x1b[0mabc", 10, 10),
+            (10, 0)
+        );
+    }
+
+    #[test]
+    fn test_render_prompt_input() {
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buf = Buffer::empty(area);
+        let cursor = render_prompt_input("\x1b[1;31mred\x1b[0m text", 4, area, &mut buf);
+        assert_eq!(cursor, Position::new(4, 0));
+        let text = buf
+            .content
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        assert_eq!(text, "red text  ");
+        assert_eq!(buf[(0, 0)].fg, Color::Red);
+        assert!(buf[(0, 0)].modifier.contains(Modifier::BOLD));
+        assert_eq!(buf[(4, 0)].fg, Color::Reset);
+
+        // Scrolled past the edge the text keeps the color set before it
+        let mut buf = Buffer::empty(area);
+        let cursor = render_prompt_input("\x1b[32m0123456789abc", 12, area, &mut buf);
+        assert_eq!(cursor, Position::new(2, 0));
+        assert_eq!(buf[(0, 0)].symbol(), "a");
+        assert_eq!(buf[(0, 0)].fg, Color::Green);
+    }
+
+    #[test]
+    fn test_screen_writer() {
+        let mut writer = ScreenWriter(Rc::new(RefCell::new(Some(Box::new(Vec::new())))));
+        assert_eq!(writer.write(b"data").unwrap(), 4);
+        assert!(writer.clone().take().is_some());
+        // Writes after the writer was handed on go nowhere
+        assert_eq!(writer.write(b"data").unwrap(), 4);
+        assert!(writer.flush().is_ok());
+    }
+}
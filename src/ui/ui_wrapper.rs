@@ -1,22 +1,33 @@
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     io::{stdout, Write},
     sync::{Arc, Mutex},
 };
 
 use crate::{
     io::SaveData,
-    model::{Settings, MOUSE_ENABLED, READER_MODE},
+    model::{
+        Activity, ActivityBuffer, ActivityLevel, ColorFilter, Line, OriginStyles, ReaderFilters,
+        ScrollbackSettings, Settings, ACTIVITY_INDICATOR, MOUSE_ENABLED, READER_MODE,
+    },
+    net::{MirrorKind, WebMirror},
     session::Session,
-    tts::TTSController,
+    tts::{SpeechKind, TTSController},
 };
 
-use super::{history::History, HeadlessScreen, ReaderScreen, SplitScreen, UserInterface};
+use super::{
+    history::History, keypad::KeypadTerminal, pager::Pager, reader_profile::ReaderProfile,
+    HeadlessScreen, ReaderScreen, SplitScreen, UserInterface,
+};
 use anyhow::Result;
+use chrono::{DateTime, Local};
+use termion::color::{Fg, LightBlack, Reset};
 use termion::{input::MouseTerminal, raw::IntoRawMode, screen::IntoAlternateScreen};
 
 /// Creates the io::Write terminal handler we draw to.
 fn create_screen_writer(mouse_support: bool) -> Result<Box<dyn Write>> {
-    let screen = stdout().into_raw_mode()?.into_alternate_screen()?;
+    let screen = KeypadTerminal::from(stdout().into_raw_mode()?.into_alternate_screen()?);
     if mouse_support {
         Ok(Box::new(MouseTerminal::from(screen)))
     } else {
@@ -24,29 +35,73 @@ fn create_screen_writer(mouse_support: bool) -> Result<Box<dyn Write>> {
     }
 }
 
+/// Creates the screen the settings ask for, drawing to `writer`.
+fn create_screen(
+    writer: Box<dyn Write>,
+    history: History,
+    reader_mode: bool,
+) -> Result<Box<dyn UserInterface>> {
+    if reader_mode {
+        return Ok(Box::new(ReaderScreen::new(writer, history)?));
+    }
+    #[cfg(feature = "tui")]
+    if Settings::try_load()?.get(crate::model::TUI_RENDERER)? {
+        return Ok(Box::new(super::TuiScreen::new(writer, history)?));
+    }
+    Ok(Box::new(SplitScreen::new(writer, history)?))
+}
+
+/// Output arriving this long after the previous line starts a new burst.
+const OUTPUT_GAP_MS: i64 = 2000;
+
+/// Rows of the screen that aren't output, eg. the status area and the prompt.
+const PAGE_MARGIN: u16 = 5;
+const MORE_INDICATOR: &str = "more";
+
+#[derive(Default)]
+struct LineSpacing {
+    prompt_spacing: bool,
+    output_separator: bool,
+    after_prompt: bool,
+    last_output: Option<DateTime<Local>>,
+}
+
 pub struct UiWrapper {
     screen: Box<dyn UserInterface>,
     tts_ctrl: Arc<Mutex<TTSController>>,
+    web: WebMirror,
+    timestamp_format: Option<String>,
+    line_spacing: LineSpacing,
+    reader_profile: Option<ReaderProfile>,
+    pager: Pager,
+    color_filter: ColorFilter,
+    origin_styles: OriginStyles,
+    activity: Activity,
+    /// The last lines of each region, to tell new content and to show them on `/next-active`.
+    region_lines: HashMap<String, Vec<String>>,
 }
 
 impl UiWrapper {
     pub fn new(session: &Session) -> Result<Self> {
         let settings = Settings::try_load()?;
         let reader_mode = settings.get(READER_MODE)?;
-        let screen: Box<dyn UserInterface> = if reader_mode {
-            Box::new(ReaderScreen::new(
-                create_screen_writer(false)?,
-                History::new(),
-            )?)
-        } else {
-            Box::new(SplitScreen::new(
-                create_screen_writer(settings.get(MOUSE_ENABLED)?)?,
-                History::new(),
-            )?)
-        };
+        let writer = create_screen_writer(!reader_mode && settings.get(MOUSE_ENABLED)?)?;
+        let screen = create_screen(writer, History::new(), reader_mode)?;
         let tts_ctrl = session.tts_ctrl.clone();
 
-        Ok(Self { screen, tts_ctrl })
+        Ok(Self {
+            screen,
+            tts_ctrl,
+            web: session.web.clone(),
+            timestamp_format: None,
+            line_spacing: LineSpacing::default(),
+            reader_profile: None,
+            pager: Pager::new(session.paging.clone()),
+            color_filter: ColorFilter::default(),
+            origin_styles: OriginStyles::default(),
+            activity: session.activity.clone(),
+            region_lines: HashMap::new(),
+        })
     }
 
     pub fn new_from(
@@ -55,15 +110,20 @@ impl UiWrapper {
         reader_mode: bool,
     ) -> Result<Self> {
         let (writer, history) = screen.destroy()?;
-        let mut screen: Box<dyn UserInterface> = if reader_mode {
-            Box::new(ReaderScreen::new(writer, history)?)
-        } else {
-            Box::new(SplitScreen::new(writer, history)?)
-        };
+        let mut screen = create_screen(writer, history, reader_mode)?;
         screen.setup()?;
         Ok(Self {
             screen,
             tts_ctrl: session.tts_ctrl.clone(),
+            web: session.web.clone(),
+            timestamp_format: None,
+            line_spacing: LineSpacing::default(),
+            reader_profile: None,
+            pager: Pager::new(session.paging.clone()),
+            color_filter: ColorFilter::default(),
+            origin_styles: OriginStyles::default(),
+            activity: session.activity.clone(),
+            region_lines: HashMap::new(),
         })
     }
 
@@ -71,10 +131,172 @@ impl UiWrapper {
         Ok(Self {
             screen: Box::new(HeadlessScreen {}),
             tts_ctrl: session.tts_ctrl.clone(),
+            web: session.web.clone(),
+            timestamp_format: None,
+            line_spacing: LineSpacing::default(),
+            reader_profile: None,
+            pager: Pager::new(session.paging.clone()),
+            color_filter: ColorFilter::default(),
+            origin_styles: OriginStyles::default(),
+            activity: session.activity.clone(),
+            region_lines: HashMap::new(),
         })
     }
 }
 
+impl UiWrapper {
+    /// Prints a blank line or a separator rule before `line` as configured.
+    fn separate(&mut self, line: &crate::model::Line) {
+        let spacing = &mut self.line_spacing;
+        let timestamp = line.timestamp();
+        let burst = spacing.last_output.is_some_and(|last| {
+            spacing.output_separator && (timestamp - last).num_milliseconds() >= OUTPUT_GAP_MS
+        });
+        let blank = spacing.prompt_spacing && spacing.after_prompt;
+        spacing.last_output = Some(timestamp);
+        spacing.after_prompt = false;
+        if burst {
+            let rule = format!(
+                "{}{}{}",
+                Fg(LightBlack),
+                "─".repeat(self.screen.width() as usize),
+                Fg(Reset)
+            );
+            self.screen.print_output(&crate::model::Line::from(rule));
+        } else if blank {
+            self.screen.print_output(&crate::model::Line::from(""));
+        }
+    }
+}
+
+impl UiWrapper {
+    fn speak_line(&self, line: &Line, kind: SpeechKind) {
+        let tts_ctrl = self.tts_ctrl.lock().unwrap();
+        match &self.reader_profile {
+            Some(profile) => {
+                if let Some(text) = profile.speech(line.clean_line()) {
+                    let mut spoken = line.clone();
+                    spoken.set_content(&text);
+                    tts_ctrl.speak_line(&spoken, kind);
+                }
+            }
+            None => tts_ctrl.speak_line(line, kind),
+        }
+    }
+
+    /// The line with the color filter applied.
+    fn filter_colors<'a>(&self, line: &'a Line) -> Cow<'a, Line> {
+        if self.color_filter == ColorFilter::Off {
+            return Cow::Borrowed(line);
+        }
+        let mut filtered = line.clone();
+        filtered.set_content(&self.color_filter.apply(line.line()));
+        Cow::Owned(filtered)
+    }
+
+    fn page_size(&self) -> usize {
+        self.screen.height().saturating_sub(PAGE_MARGIN).max(1) as usize
+    }
+
+    fn draw_more_indicator(&mut self) {
+        let text = match self.pager.held() {
+            0 => None,
+            held => Some(format!("--More-- ({held} lines, space to continue)")),
+        };
+        self.screen
+            .set_indicator(MORE_INDICATOR.to_string(), text)
+            .ok();
+    }
+
+    /// Shows lines the pager held back.
+    fn show_held(&mut self, lines: Vec<Line>) {
+        for line in &lines {
+            self.output_line(line);
+        }
+        self.draw_more_indicator();
+    }
+
+    fn draw_activity_indicator(&mut self) {
+        self.screen
+            .set_indicator(ACTIVITY_INDICATOR.to_string(), self.activity.indicator())
+            .ok();
+    }
+
+    fn record_activity(&mut self, buffer: ActivityBuffer, level: ActivityLevel) {
+        if self.activity.record(buffer, level) {
+            self.draw_activity_indicator();
+        }
+    }
+
+    fn clear_activity(&mut self, buffer: &ActivityBuffer) {
+        if self.activity.clear(buffer) {
+            self.draw_activity_indicator();
+        }
+    }
+
+    /// Marks the held output as read once the screen caught up with it.
+    fn check_scrollback(&mut self) {
+        if !self.screen.output_held() {
+            self.clear_activity(&ActivityBuffer::Scrollback);
+        }
+    }
+
+    /// Records new content of a region that isn't on the screen, and marks it as read when it is.
+    fn check_region(&mut self, name: &str, changed: bool) {
+        let buffer = ActivityBuffer::Region(name.to_string());
+        if self.screen.region_shown(name) {
+            self.clear_activity(&buffer);
+        } else if changed {
+            self.record_activity(buffer, ActivityLevel::Output);
+        }
+    }
+
+    fn output_line(&mut self, line: &Line) {
+        if self.origin_styles.hides(&line.flags.origin) {
+            return;
+        }
+        if self.pager.is_enabled() {
+            let page_size = self.page_size();
+            if self.pager.hold(line, page_size) {
+                if self.pager.held() == 1 {
+                    self.tts_ctrl.lock().unwrap().speak_info("More");
+                }
+                self.draw_more_indicator();
+                return;
+            }
+        }
+        let line = &*self.filter_colors(line);
+        self.speak_line(line, SpeechKind::Output);
+        let decorated = self.origin_styles.decorate(line);
+        let line = decorated.as_ref().unwrap_or(line);
+        if let Some(content) = line.print_line() {
+            self.separate(line);
+            self.web.mirror(MirrorKind::Output, content);
+        }
+        match (&self.timestamp_format, line.print_line()) {
+            (Some(format), Some(content)) if !content.trim().is_empty() => {
+                let mut stamped = line.clone();
+                stamped.set_content(&format!(
+                    "{}{}{} {}",
+                    Fg(LightBlack),
+                    line.format_timestamp(format),
+                    Fg(Reset),
+                    content
+                ));
+                self.screen.print_output(&stamped);
+            }
+            _ => self.screen.print_output(line),
+        }
+        if line.print_line().is_some() && self.screen.output_held() {
+            let level = match line.tag("important") {
+                Some(_) => ActivityLevel::Highlight,
+                None => ActivityLevel::Output,
+            };
+            self.record_activity(ActivityBuffer::Scrollback, level);
+        }
+    }
+}
+
 impl UserInterface for UiWrapper {
     fn setup(&mut self) -> Result<()> {
         self.screen.setup()
@@ -82,21 +304,37 @@ impl UserInterface for UiWrapper {
 
     fn print_error(&mut self, output: &str) {
         self.tts_ctrl.lock().unwrap().speak_error(output);
+        self.web.mirror(MirrorKind::Error, output);
         self.screen.print_error(output);
     }
 
     fn print_info(&mut self, output: &str) {
         self.tts_ctrl.lock().unwrap().speak_info(output);
+        self.web.mirror(MirrorKind::Info, output);
         self.screen.print_info(output);
     }
 
     fn print_output(&mut self, line: &crate::model::Line) {
-        self.tts_ctrl.lock().unwrap().speak_line(line);
-        self.screen.print_output(line);
+        match self
+            .reader_profile
+            .as_mut()
+            .map(|profile| profile.filter(line))
+        {
+            Some(lines) => lines.iter().for_each(|line| self.output_line(line)),
+            None => self.output_line(line),
+        }
     }
 
     fn print_prompt(&mut self, prompt: &crate::model::Line) {
-        self.tts_ctrl.lock().unwrap().speak_line(prompt);
+        if let Some(repeats) = self.reader_profile.as_mut().and_then(ReaderProfile::flush) {
+            self.output_line(&repeats);
+        }
+        self.speak_line(prompt, SpeechKind::Prompt);
+        if !prompt.line().trim().is_empty() {
+            self.line_spacing.after_prompt = true;
+        }
+        let prompt = &*self.filter_colors(prompt);
+        self.web.mirror(MirrorKind::Prompt, prompt.line());
         self.screen.print_prompt(prompt);
     }
 
@@ -105,8 +343,13 @@ impl UserInterface for UiWrapper {
     }
 
     fn print_send(&mut self, send: &crate::model::Line) {
-        if let Some(line) = send.print_line() {
-            self.tts_ctrl.lock().unwrap().speak_input(line);
+        let held = self.pager.release();
+        if !held.is_empty() {
+            self.show_held(held);
+        }
+        self.tts_ctrl.lock().unwrap().speak_input(send);
+        if let Some(content) = send.print_line() {
+            self.web.mirror(MirrorKind::Input, content);
         }
         self.screen.print_send(send);
     }
@@ -116,11 +359,15 @@ impl UserInterface for UiWrapper {
     }
 
     fn reset_scroll(&mut self) -> Result<()> {
-        self.screen.reset_scroll()
+        self.screen.reset_scroll()?;
+        self.check_scrollback();
+        Ok(())
     }
 
     fn scroll_down(&mut self) -> Result<()> {
-        self.screen.scroll_down()
+        self.screen.scroll_down()?;
+        self.check_scrollback();
+        Ok(())
     }
 
     fn scroll_lock(&mut self, lock: bool) -> Result<()> {
@@ -128,7 +375,9 @@ impl UserInterface for UiWrapper {
     }
 
     fn scroll_to(&mut self, row: usize) -> Result<()> {
-        self.screen.scroll_to(row)
+        self.screen.scroll_to(row)?;
+        self.check_scrollback();
+        Ok(())
     }
 
     fn scroll_top(&mut self) -> Result<()> {
@@ -147,6 +396,18 @@ impl UserInterface for UiWrapper {
         self.screen.find_down(pattern)
     }
 
+    fn find_all(&self, pattern: &crate::model::Regex) -> Vec<usize> {
+        self.screen.find_all(pattern)
+    }
+
+    fn set_filter(&mut self, pattern: Option<crate::model::Regex>) -> Result<()> {
+        self.screen.set_filter(pattern)
+    }
+
+    fn filter_context(&mut self) -> Result<()> {
+        self.screen.filter_context()
+    }
+
     fn set_host(&mut self, host: &str, port: u16) -> Result<()> {
         self.screen.set_host(host, port)
     }
@@ -171,6 +432,73 @@ impl UserInterface for UiWrapper {
         self.screen.set_status_line(line, info)
     }
 
+    fn set_indicator(&mut self, id: String, text: Option<String>) -> Result<()> {
+        self.screen.set_indicator(id, text)
+    }
+
+    fn scrollback(&self) -> &[Arc<str>] {
+        self.screen.scrollback()
+    }
+
+    fn scrollback_bytes(&self) -> usize {
+        self.screen.scrollback_bytes()
+    }
+
+    fn scrollback_spilled(&self) -> usize {
+        self.screen.scrollback_spilled()
+    }
+
+    fn set_scrollback_settings(&mut self, settings: &ScrollbackSettings) {
+        self.screen.set_scrollback_settings(settings)
+    }
+
+    fn set_overlay(&mut self, lines: Option<Vec<String>>) -> Result<()> {
+        self.screen.set_overlay(lines)
+    }
+
+    fn set_region(&mut self, region: crate::model::Region) -> Result<()> {
+        let name = region.name.clone();
+        self.screen.set_region(region)?;
+        self.check_region(&name, false);
+        Ok(())
+    }
+
+    fn remove_region(&mut self, name: &str) -> Result<()> {
+        self.region_lines.remove(name);
+        self.clear_activity(&ActivityBuffer::Region(name.to_string()));
+        self.screen.remove_region(name)
+    }
+
+    fn set_region_lines(&mut self, name: &str, lines: Vec<String>) -> Result<()> {
+        let changed = self.region_lines.get(name) != Some(&lines)
+            && lines.iter().any(|line| !line.trim().is_empty());
+        self.region_lines.insert(name.to_string(), lines.clone());
+        self.screen.set_region_lines(name, lines)?;
+        self.check_region(name, changed);
+        Ok(())
+    }
+
+    fn show_activity(&mut self, buffer: &ActivityBuffer) -> Result<()> {
+        match buffer {
+            ActivityBuffer::Scrollback => {
+                self.screen.reset_scroll()?;
+                if self.screen.output_held() {
+                    self.screen.toggle_pause()?;
+                }
+            }
+            ActivityBuffer::Region(name) => {
+                let lines = self.region_lines.get(name).cloned().unwrap_or_default();
+                for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+                    self.print_info(&format!("[{name}] {line}"));
+                }
+            }
+            // Shown by the inbox script
+            ActivityBuffer::Inbox => {}
+        }
+        self.clear_activity(buffer);
+        Ok(())
+    }
+
     fn flush(&mut self) {
         self.screen.flush();
     }
@@ -186,4 +514,305 @@ impl UserInterface for UiWrapper {
     fn destroy(self: Box<Self>) -> Result<(Box<dyn Write>, History)> {
         self.screen.destroy()
     }
+
+    fn set_timestamp_format(&mut self, format: Option<String>) {
+        self.timestamp_format = format;
+    }
+
+    fn set_line_spacing(&mut self, prompt_spacing: bool, output_separator: bool) {
+        self.line_spacing.prompt_spacing = prompt_spacing;
+        self.line_spacing.output_separator = output_separator;
+    }
+
+    fn set_collapse_repeats(&mut self, enabled: bool) {
+        self.screen.set_collapse_repeats(enabled);
+    }
+
+    fn toggle_pause(&mut self) -> Result<()> {
+        self.screen.toggle_pause()?;
+        self.check_scrollback();
+        Ok(())
+    }
+
+    fn set_paging(&mut self, enabled: bool) {
+        self.pager.set_enabled(enabled);
+        if !enabled {
+            let held = self.pager.release();
+            self.show_held(held);
+        }
+    }
+
+    fn next_page(&mut self) -> Result<()> {
+        let page_size = self.page_size();
+        let page = self.pager.next_page(page_size);
+        self.show_held(page);
+        Ok(())
+    }
+
+    fn set_pending_echo(&mut self, enabled: bool) {
+        self.screen.set_pending_echo(enabled);
+    }
+
+    fn acknowledge_sent(&mut self) -> Result<()> {
+        self.screen.acknowledge_sent()
+    }
+
+    fn set_reader_filters(&mut self, filters: Option<ReaderFilters>) {
+        self.reader_profile = filters.map(ReaderProfile::new);
+    }
+
+    fn set_color_filter(&mut self, filter: ColorFilter) {
+        self.color_filter = filter;
+    }
+
+    fn set_origin_styles(&mut self, styles: OriginStyles) {
+        self.origin_styles = styles;
+    }
+}
+
+#[cfg(test)]
+mod ui_wrapper_test {
+    use std::sync::{Arc, Mutex};
+
+    use mockall::predicate::eq;
+
+    use super::{LineSpacing, UiWrapper};
+    use crate::model::{
+        ActivityBuffer, ActivityLevel, ColorFilter, Line, Origin, OriginStyle, OriginStyles,
+        ReaderFilters, ACTIVITY_INDICATOR,
+    };
+    use crate::net::WebMirror;
+    use crate::tts::TTSController;
+    use crate::ui::{MockUserInterface, UserInterface};
+
+    fn wrapper(mut screen: MockUserInterface) -> UiWrapper {
+        screen.expect_output_held().return_const(false);
+        screen.expect_region_shown().return_const(true);
+        wrap(screen)
+    }
+
+    fn wrap(screen: MockUserInterface) -> UiWrapper {
+        UiWrapper {
+            screen: Box::new(screen),
+            tts_ctrl: Arc::new(Mutex::new(TTSController::new(false, true))),
+            web: WebMirror::default(),
+            timestamp_format: None,
+            line_spacing: LineSpacing::default(),
+            reader_profile: None,
+            pager: Default::default(),
+            color_filter: Default::default(),
+            origin_styles: Default::default(),
+            activity: Default::default(),
+            region_lines: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_prompt_spacing() {
+        let mut screen = MockUserInterface::new();
+        screen.expect_print_prompt().times(2).return_const(());
+        screen
+            .expect_print_output()
+            .with(eq(Line::from("")))
+            .times(1)
+            .return_const(());
+        screen
+            .expect_print_output()
+            .withf(|line: &Line| !line.line().is_empty())
+            .times(3)
+            .return_const(());
+
+        let mut ui = wrapper(screen);
+        ui.print_prompt(&Line::from("hp: 100 >"));
+        ui.print_output(&Line::from("You see nothing."));
+        ui.set_line_spacing(true, false);
+        ui.print_prompt(&Line::from("hp: 100 >"));
+        ui.print_output(&Line::from("You see nothing."));
+        ui.print_output(&Line::from("A rat is here."));
+    }
+
+    #[test]
+    fn test_output_separator() {
+        let mut screen = MockUserInterface::new();
+        screen.expect_width().return_const(4u16);
+        screen
+            .expect_print_output()
+            .withf(|line: &Line| line.line().contains("────"))
+            .times(1)
+            .return_const(());
+        screen
+            .expect_print_output()
+            .withf(|line: &Line| !line.line().contains('─'))
+            .times(3)
+            .return_const(());
+
+        let mut ui = wrapper(screen);
+        ui.set_line_spacing(false, true);
+        let first = Line::from("You see nothing.");
+        ui.print_output(&first);
+        ui.print_output(&Line::from("A rat is here."));
+        ui.line_spacing.last_output =
+            Some(first.timestamp() - chrono::Duration::milliseconds(super::OUTPUT_GAP_MS));
+        ui.print_output(&Line::from("The rat bites you."));
+    }
+
+    #[test]
+    fn test_reader_filters() {
+        let mut screen = MockUserInterface::new();
+        screen.expect_print_prompt().times(1).return_const(());
+        for text in ["The rat bites you.", "(x2)", "You flee."] {
+            screen
+                .expect_print_output()
+                .with(eq(Line::from(text)))
+                .times(1)
+                .return_const(());
+        }
+
+        let mut ui = wrapper(screen);
+        ui.set_reader_filters(Some(ReaderFilters::default()));
+        ui.print_output(&Line::from("=========="));
+        ui.print_output(&Line::from("The rat bites you."));
+        ui.print_output(&Line::from("The rat bites you."));
+        ui.print_output(&Line::from("Fleeing [#####.....]"));
+        ui.print_prompt(&Line::from("hp: 10 >"));
+        ui.print_output(&Line::from("You flee."));
+    }
+
+    #[test]
+    fn test_color_filter() {
+        let mut screen = MockUserInterface::new();
+        screen
+            .expect_print_output()
+            .withf(|line: &Line| line.line().starts_with("\x1b[38;2;"))
+            .times(1)
+            .return_const(());
+        screen
+            .expect_print_prompt()
+            .with(eq(Line::from("\x1b[97mhp: 10 >")))
+            .times(1)
+            .return_const(());
+
+        let mut ui = wrapper(screen);
+        ui.set_color_filter(ColorFilter::Contrast);
+        ui.print_output(&Line::from("\x1b[34mThe sea"));
+        ui.print_prompt(&Line::from("\x1b[97mhp: 10 >"));
+    }
+
+    #[test]
+    fn test_origin_styles() {
+        let mut screen = MockUserInterface::new();
+        screen
+            .expect_print_output()
+            .with(eq(Line::from("You see a door.")))
+            .times(1)
+            .return_const(());
+        screen
+            .expect_print_output()
+            .with(eq(Line::from("> [**] Connected")))
+            .times(1)
+            .return_const(());
+
+        let mut styles = OriginStyles::default();
+        styles.set(
+            &Origin::Script,
+            OriginStyle {
+                hidden: true,
+                ..Default::default()
+            },
+        );
+        styles.set(
+            &Origin::Client,
+            OriginStyle {
+                marker: Some(">".to_string()),
+                ..Default::default()
+            },
+        );
+        let mut ui = wrapper(screen);
+        ui.set_origin_styles(styles);
+        ui.print_output(&Line::from("You see a door."));
+        let mut script = Line::from("Mapper: room saved");
+        script.flags.origin = Origin::Plugin("mapper".to_string());
+        ui.print_output(&script);
+        let mut client = Line::from("[**] Connected");
+        client.flags.origin = Origin::Client;
+        ui.print_output(&client);
+    }
+
+    #[test]
+    fn test_activity() {
+        let held = Arc::new(Mutex::new(true));
+        let mut screen = MockUserInterface::new();
+        let output_held = held.clone();
+        screen
+            .expect_output_held()
+            .returning(move || *output_held.lock().unwrap());
+        screen.expect_region_shown().returning(|name| name == "hud");
+        screen.expect_print_output().return_const(());
+        screen.expect_set_region_lines().returning(|_, _| Ok(()));
+        let unpause = held.clone();
+        screen.expect_reset_scroll().times(1).returning(|| Ok(()));
+        screen.expect_toggle_pause().times(1).returning(move || {
+            *unpause.lock().unwrap() = false;
+            Ok(())
+        });
+        screen
+            .expect_print_info()
+            .with(eq("[map] a room"))
+            .times(1)
+            .return_const(());
+        let indicators = Arc::new(Mutex::new(vec![]));
+        let drawn = indicators.clone();
+        screen
+            .expect_set_indicator()
+            .withf(|id, _| id == ACTIVITY_INDICATOR)
+            .returning(move |_, text| {
+                drawn.lock().unwrap().push(text);
+                Ok(())
+            });
+
+        let mut ui = wrap(screen);
+        ui.print_output(&Line::from("A rat is here."));
+        let mut tell = Line::from("Bob tells you, 'hi'");
+        tell.set_tag("important", "tell");
+        ui.print_output(&tell);
+        assert_eq!(
+            ui.activity.level(&ActivityBuffer::Scrollback),
+            Some(ActivityLevel::Highlight)
+        );
+
+        // Only regions that aren't on the screen get activity, and only for new content
+        ui.set_region_lines("hud", vec!["hp: 100".to_string()])
+            .unwrap();
+        ui.set_region_lines("map", vec!["a room".to_string(), " ".to_string()])
+            .unwrap();
+        ui.set_region_lines("map", vec!["a room".to_string(), " ".to_string()])
+            .unwrap();
+        ui.set_region_lines("empty", vec!["  ".to_string()])
+            .unwrap();
+        assert_eq!(
+            ui.activity.list(),
+            vec![
+                (ActivityBuffer::Scrollback, ActivityLevel::Highlight),
+                (
+                    ActivityBuffer::Region("map".to_string()),
+                    ActivityLevel::Output
+                ),
+            ]
+        );
+
+        ui.show_activity(&ActivityBuffer::Scrollback).unwrap();
+        ui.show_activity(&ActivityBuffer::Region("map".to_string()))
+            .unwrap();
+        assert_eq!(ui.activity.next(), None);
+        assert_eq!(
+            *indicators.lock().unwrap(),
+            vec![
+                Some("act: scrollback".to_string()),
+                Some("act: scrollback!".to_string()),
+                Some("act: scrollback!, map".to_string()),
+                Some("act: map".to_string()),
+                None,
+            ]
+        );
+    }
 }
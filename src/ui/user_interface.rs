@@ -1,9 +1,12 @@
-use std::{error, fmt, io::Write};
+use std::{error, fmt, io::Write, sync::Arc};
 
 #[cfg(test)]
 use mockall::automock;
 
-use crate::model::{Line, Regex};
+use crate::model::{
+    ActivityBuffer, ColorFilter, Line, OriginStyles, ReaderFilters, Regex, Region,
+    ScrollbackSettings,
+};
 
 use anyhow::Result;
 
@@ -48,16 +51,92 @@ pub trait UserInterface {
     fn scroll_up(&mut self) -> Result<()>;
     fn find_up(&mut self, pattern: &Regex) -> Result<()>;
     fn find_down(&mut self, pattern: &Regex) -> Result<()>;
+    fn find_all(&self, pattern: &Regex) -> Vec<usize>;
     fn set_host(&mut self, host: &str, port: u16) -> Result<()>;
     fn add_tag(&mut self, proto: &str) -> Result<()>;
     fn remove_tag(&mut self, proto: &str) -> Result<()>;
     fn clear_tags(&mut self) -> Result<()>;
     fn set_status_area_height(&mut self, height: u16) -> Result<()>;
     fn set_status_line(&mut self, line: usize, info: String) -> Result<()>;
+    fn set_region(&mut self, region: Region) -> Result<()>;
+    fn remove_region(&mut self, name: &str) -> Result<()>;
+    fn set_region_lines(&mut self, name: &str, lines: Vec<String>) -> Result<()>;
     fn flush(&mut self);
     fn width(&self) -> u16;
     fn height(&self) -> u16;
     fn destroy(self: Box<Self>) -> Result<(Box<dyn Write>, History)>;
+    /// Shows the time output lines were received in a gutter, or hides it on `None`.
+    fn set_timestamp_format(&mut self, _format: Option<String>) {}
+    /// Narrows the output down to lines matching `pattern`, or shows all output again on `None`.
+    fn set_filter(&mut self, _pattern: Option<Regex>) -> Result<()> {
+        Ok(())
+    }
+    /// Leaves the filtered output, scrolling to the line at the top of the filtered view.
+    fn filter_context(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Shows a progress indicator in the status area, or removes it on `None`.
+    fn set_indicator(&mut self, _id: String, _text: Option<String>) -> Result<()> {
+        Ok(())
+    }
+    /// Separates output with a blank line after prompts, and with a dim rule after pauses in
+    /// the output.
+    fn set_overlay(&mut self, _lines: Option<Vec<String>>) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_line_spacing(&mut self, _prompt_spacing: bool, _output_separator: bool) {}
+    /// Collapses identical consecutive output lines into one with a repeat counter.
+    fn set_collapse_repeats(&mut self, _enabled: bool) {}
+    /// Holds new output off the screen, or shows it again, while it's still received, logged
+    /// and run through triggers.
+    fn toggle_pause(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Checks if new output is kept off the screen, because it's paused or scrolled far back.
+    fn output_held(&self) -> bool {
+        false
+    }
+    /// Checks if a region is drawn, rather than cut off by a small terminal or not drawn at all.
+    fn region_shown(&self, _name: &str) -> bool {
+        false
+    }
+    /// Brings up the unread output of a buffer, see `/help activity`.
+    fn show_activity(&mut self, _buffer: &ActivityBuffer) -> Result<()> {
+        Ok(())
+    }
+    /// Stops bursts of output longer than a page until the next page is asked for.
+    fn set_paging(&mut self, _enabled: bool) {}
+    /// Shows the next page of output held by paging.
+    fn next_page(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Shows sent commands dimmed with a pending marker until the next prompt arrives.
+    fn set_pending_echo(&mut self, _enabled: bool) {}
+    /// Shows the commands sent since the last prompt as answered by the server.
+    fn acknowledge_sent(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Filters output for screen readers, `None` disables the filtering.
+    fn set_reader_filters(&mut self, _filters: Option<ReaderFilters>) {}
+    /// Transforms the colors of output and prompts, eg. for color blindness.
+    fn set_color_filter(&mut self, _filter: ColorFilter) {}
+    /// Marks or hides output by where it comes from.
+    fn set_origin_styles(&mut self, _styles: OriginStyles) {}
+    /// Limits the scrollback kept in memory and whether older lines are kept on disk.
+    fn set_scrollback_settings(&mut self, _settings: &ScrollbackSettings) {}
+    /// The lines in the scrollback, oldest first.
+    fn scrollback(&self) -> &[Arc<str>] {
+        &[]
+    }
+    /// The bytes of text in the scrollback, counted as lines are added rather than summed up.
+    fn scrollback_bytes(&self) -> usize {
+        0
+    }
+    /// The older lines of the scrollback kept on disk rather than in memory.
+    fn scrollback_spilled(&self) -> usize {
+        0
+    }
 }
 
 pub fn wrap_line(line: &str, width: usize) -> Vec<&str> {
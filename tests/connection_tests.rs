@@ -1,4 +1,4 @@
-use blightmud::RuntimeConfig;
+use blightmud::{RuntimeConfig, StartupAction};
 use common::{join_blightmud, Server};
 
 mod common;
@@ -23,6 +23,28 @@ fn test_connect() {
     join_blightmud(handle)
 }
 
+#[test]
+fn test_startup_actions() {
+    let mut server = Server::bind(0);
+
+    let rt = RuntimeConfig {
+        headless_mode: true,
+        integration_test: true,
+        actions: vec![
+            StartupAction::Eval(include_str!("common/quit_on_disconnect.lua").to_string()),
+            StartupAction::Connect(server.local_addr.to_string()),
+            StartupAction::Command("hello".to_string()),
+        ],
+        ..Default::default()
+    };
+    let handle = common::start_blightmud(rt);
+
+    let mut connection = server.listen().unwrap();
+    assert_eq!(connection.recv_string(), "hello\r\n");
+    connection.close();
+    join_blightmud(handle);
+}
+
 #[test]
 fn test_connect_world() {
     let mut server = Server::bind(0);